@@ -0,0 +1,195 @@
+//! The Prometheus `/metrics` endpoint AGQ and AGW serve
+//!
+//! Each records its own metrics; this is the small HTTP responder both
+//! expose them with, and the histogram buckets their job timings share so
+//! dashboards can put the two side by side. AGQ's dashboard API reads
+//! requests with the same limits.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// Maximum size of an HTTP request head accepted by the endpoint
+pub const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Time allowed for a client to send its request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Histogram buckets for job execution time, in seconds
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0,
+];
+
+/// Histogram buckets for time spent queued before a worker picked the job up
+pub const QUEUE_WAIT_BUCKETS: &[f64] = &[0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// Bind the metrics endpoint
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound
+pub async fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    Ok(listener)
+}
+
+/// Serve `GET /metrics` on the listener until the task is dropped
+///
+/// `render` is called on each scrape for the metrics in the Prometheus text
+/// exposition format, or `None` if they could not be collected.
+pub async fn serve<F>(listener: TcpListener, render: F)
+where
+    F: Fn() -> Option<String> + Send + Sync + 'static,
+{
+    let render = Arc::new(render);
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let render = Arc::clone(&render);
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, &*render).await {
+                        debug!("Metrics request from {peer} failed: {e}");
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept metrics connection: {e}"),
+        }
+    }
+}
+
+/// Answer a single HTTP request and close the connection
+async fn handle_connection(
+    mut stream: TcpStream,
+    render: &(dyn Fn() -> Option<String> + Send + Sync),
+) -> std::io::Result<()> {
+    let head = with_timeout(read_request_head(&mut stream)).await?;
+
+    let response = match request_path(&head) {
+        Some("/metrics") => match render() {
+            Some(text) => {
+                http_response("200 OK", "text/plain; version=0.0.4; charset=utf-8", &text)
+            }
+            None => http_response(
+                "500 Internal Server Error",
+                "text/plain",
+                "Internal Server Error\n",
+            ),
+        },
+        Some(_) => http_response("404 Not Found", "text/plain", "Not Found\n"),
+        None => http_response("400 Bad Request", "text/plain", "Bad Request\n"),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Run a request read within [`REQUEST_TIMEOUT`]
+///
+/// # Errors
+///
+/// Returns the read's error, or `TimedOut` if the client is too slow
+pub async fn with_timeout<T>(read: impl Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+    tokio::time::timeout(REQUEST_TIMEOUT, read)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))?
+}
+
+/// Read until the end of the HTTP request head, capped at [`MAX_REQUEST_BYTES`]
+///
+/// # Errors
+///
+/// Returns an error if the connection fails
+pub async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(head)
+}
+
+/// Path of a `GET` request, or `None` if the request line is not a GET
+fn request_path(head: &[u8]) -> Option<&str> {
+    let line = std::str::from_utf8(head).ok()?.lines().next()?;
+    let mut parts = line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    parts.next()
+}
+
+/// A complete HTTP/1.1 response that closes the connection
+#[must_use]
+pub fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_path() {
+        assert_eq!(
+            request_path(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some("/metrics")
+        );
+        assert_eq!(request_path(b"POST /metrics HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(request_path(b""), None);
+    }
+
+    async fn get(
+        listener: TcpListener,
+        render: impl Fn() -> Option<String> + Send + Sync + 'static,
+        path: &str,
+    ) -> String {
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, render));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        server.abort();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serve_over_http() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let response = get(
+            bind(addr).await.unwrap(),
+            || Some("up 1\n".to_string()),
+            "/metrics",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\nup 1\n"));
+
+        let response = get(bind(addr).await.unwrap(), || None, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error"));
+
+        let response = get(bind(addr).await.unwrap(), || None, "/other").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
@@ -0,0 +1,1242 @@
+//! The Plan schema shared by AGX, AGQ and AGW
+//!
+//! A Plan is an ordered list of tasks, numbered 1, 2, 3, … in order, each
+//! running a command and optionally reading the stdout of an earlier task.
+//! A task can also list earlier tasks it must wait for without reading
+//! their output; tasks that neither read from nor wait for each other may
+//! run side by side.
+//! AGX's planners produce Plans, AGQ stores them and AGW runs their tasks;
+//! this crate is the one definition of that shape.
+//!
+//! Plans carry a `schema_version`, 1 when absent. A component rejects Plans
+//! newer than the [`SCHEMA_VERSION`] it was built with instead of guessing
+//! at fields it doesn't know.
+//!
+//! Besides what to run, a Plan can say how AGQ schedules it: what happens
+//! when a task fails, how many of its jobs may run at once, each task's
+//! priority, retries and worker tags, pipelines of commands, and tasks that
+//! run once per item of their input.
+//!
+//! [`validate`] checks a Plan strictly: unknown fields, wrong types, task
+//! numbering and `input_from_task` references are each reported with the
+//! path of the offending value, so a planner's mistakes can be fixed or fed
+//! back to it. AGX checks Plans this way before submitting them, and AGQ
+//! again before storing them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Version of the Plan schema this crate defines
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Most tasks a Plan may have
+pub const MAX_TASKS: usize = 100;
+
+/// Longest task timeout, in seconds
+pub const MAX_TIMEOUT_SECS: u32 = 3600;
+
+/// Most worker tags a task may require
+pub const MAX_TAGS: usize = 16;
+
+/// Longest worker tag
+const MAX_TAG_LEN: usize = 64;
+
+/// Command of a task that runs its `stages` as a pipeline
+pub const PIPELINE_COMMAND: &str = "pipeline";
+
+const PLAN_FIELDS: &[&str] = &[
+    "schema_version",
+    "plan_id",
+    "plan_description",
+    "on_failure",
+    "max_concurrency",
+    "weight",
+    "env",
+    "tasks",
+];
+
+const TASK_FIELDS: &[&str] = &[
+    "task_number",
+    "command",
+    "args",
+    "input_from_task",
+    "depends_on",
+    "timeout_secs",
+    "tags",
+    "limits",
+    "priority",
+    "stages",
+    "artifacts",
+    "max_attempts",
+    "backoff",
+    "run_after_secs",
+    "await_event",
+    "map",
+];
+
+const LIMIT_FIELDS: &[&str] = &["memory_mb", "cpu_secs", "max_processes"];
+
+const STAGE_FIELDS: &[&str] = &["command", "args"];
+
+const BACKOFF_FIELDS: &[&str] = &["strategy", "delay_secs", "max_delay_secs"];
+
+/// A Plan (Execution Layer 2)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    #[serde(default = "current_version")]
+    pub schema_version: u32,
+    /// Stable identifier; assigned on submission when a planner leaves it out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_description: Option<String>,
+    /// How failures propagate to downstream tasks
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub on_failure: FailurePolicy,
+    /// Most jobs of the Plan, across all its Actions, that may be queued or
+    /// running at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// Share of ready queues the Plan's jobs get when other Plans' jobs are
+    /// queued too; a Plan of weight 2 gets twice the share of one of
+    /// weight 1, the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    /// Environment every job of the Plan gets, usually references to
+    /// secrets such as `secret://db_password` rather than their values;
+    /// an Action's inputs override it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    pub tasks: Vec<Task>,
+}
+
+impl Default for Plan {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            plan_id: None,
+            plan_description: None,
+            on_failure: FailurePolicy::default(),
+            max_concurrency: None,
+            weight: None,
+            env: BTreeMap::new(),
+            tasks: Vec::new(),
+        }
+    }
+}
+
+/// A single task within a Plan
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Task {
+    /// 1-based position of the task in the Plan
+    pub task_number: u32,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Earlier task whose stdout becomes this task's stdin
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_from_task: Option<u32>,
+    /// Earlier tasks that must finish before this one starts, without
+    /// their stdout being piped to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u32>,
+    /// Worker tags the task's jobs need, e.g. `gpu` or `network`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "ResourceLimits::is_unlimited")]
+    pub limits: ResourceLimits,
+    /// Scheduling priority of the task's jobs
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub priority: JobPriority,
+    /// Commands of a [`PIPELINE_COMMAND`] task, each reading the previous
+    /// one's stdout
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<PipelineStage>,
+    /// Output files the task writes to its workspace
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<String>,
+    /// Attempts allowed before the task is reported as failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+    /// Delay between attempts
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub backoff: Backoff,
+    /// Seconds to wait once the task could run, after submission or once
+    /// its input is ready, before queuing its jobs; e.g. to check on
+    /// something an earlier task started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_after_secs: Option<u64>,
+    /// Hold the task's jobs, once they could run, until an external event
+    /// fires for each, e.g. a human approval or a CI build finishing
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub await_event: bool,
+    /// Run the task once per item of its `input_from_task` output, split
+    /// this way; tasks taking their input from a map task get the outputs
+    /// of every item job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<MapSplit>,
+}
+
+impl Task {
+    /// Tasks this one waits for: its `input_from_task`, then its `depends_on`
+    pub fn upstream_tasks(&self) -> impl Iterator<Item = u32> + '_ {
+        self.input_from_task
+            .into_iter()
+            .chain(self.depends_on.iter().copied())
+    }
+}
+
+/// A single command within a pipeline task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStage {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Resource limits for a task's jobs
+///
+/// Workers run jobs under their own default limits; a task's limits can
+/// only tighten those, never relax them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceLimits {
+    /// Maximum memory in megabytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u64>,
+    /// Maximum CPU time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_secs: Option<u64>,
+    /// Maximum number of processes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Whether no limit is set
+    pub fn is_unlimited(&self) -> bool {
+        self.memory_mb.is_none() && self.cpu_secs.is_none() && self.max_processes.is_none()
+    }
+
+    /// Combine a worker's default limits with a task's
+    ///
+    /// Tasks come from untrusted plans, so their limits can only tighten the
+    /// defaults, never relax them.
+    #[must_use]
+    pub fn restrict(&self, overrides: &Self) -> Self {
+        fn tighter(default: Option<u64>, over: Option<u64>) -> Option<u64> {
+            match (default, over) {
+                (Some(d), Some(o)) => Some(d.min(o)),
+                (d, o) => d.or(o),
+            }
+        }
+
+        Self {
+            memory_mb: tighter(self.memory_mb, overrides.memory_mb),
+            cpu_secs: tighter(self.cpu_secs, overrides.cpu_secs),
+            max_processes: tighter(self.max_processes, overrides.max_processes),
+        }
+    }
+}
+
+/// What happens to a Plan's downstream jobs when one of its jobs fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Cancel every pending job that depends on the failed job
+    #[default]
+    Cancel,
+    /// Run dependents anyway once all their dependencies have finished
+    Continue,
+    /// Run the failed job again, cancelling dependents once attempts run out
+    Retry,
+}
+
+/// Scheduling priority of a job
+///
+/// Each priority has its own ready list, and workers drain the lists from
+/// highest to lowest priority, so small interactive plans are not stuck
+/// behind large batches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl JobPriority {
+    /// Every priority, in the order workers drain them
+    pub const ALL: [JobPriority; 3] = [JobPriority::High, JobPriority::Normal, JobPriority::Low];
+
+    /// Ready list for this priority within a queue, e.g. `queue:default:high`
+    ///
+    /// Normal-priority jobs use the queue itself, so workers that only know
+    /// about the base queue still receive them.
+    pub fn queue_name(self, queue: &str) -> String {
+        match self {
+            JobPriority::High => format!("{}:high", queue),
+            JobPriority::Normal => queue.to_string(),
+            JobPriority::Low => format!("{}:low", queue),
+        }
+    }
+}
+
+/// How the delay between retries of a failed job grows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackoffStrategy {
+    /// Wait the same delay before every retry
+    Fixed,
+    /// Wait the delay times the retry number
+    Linear,
+    /// Double the delay with every retry
+    #[default]
+    Exponential,
+}
+
+/// Delay before a failed job is queued again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Backoff {
+    pub strategy: BackoffStrategy,
+    /// Delay before the first retry, in seconds
+    pub delay_secs: u64,
+    /// Upper bound on any single delay, in seconds
+    pub max_delay_secs: u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            strategy: BackoffStrategy::default(),
+            delay_secs: 5,
+            max_delay_secs: 300,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay in seconds before the given retry (1-based)
+    pub fn delay(&self, retry: u32) -> u64 {
+        let delay = match self.strategy {
+            BackoffStrategy::Fixed => self.delay_secs,
+            BackoffStrategy::Linear => self.delay_secs.saturating_mul(u64::from(retry)),
+            BackoffStrategy::Exponential => {
+                let factor = 1u64
+                    .checked_shl(retry.saturating_sub(1))
+                    .unwrap_or(u64::MAX);
+                self.delay_secs.saturating_mul(factor)
+            }
+        };
+        delay.min(self.max_delay_secs)
+    }
+}
+
+/// How a map task splits its upstream job's output into items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MapSplit {
+    /// One item per non-blank line
+    Lines,
+    /// One item per element of a JSON array
+    Json,
+}
+
+impl MapSplit {
+    /// Items of an upstream job's output
+    ///
+    /// String elements of a JSON array are used as they are; other elements
+    /// are passed as JSON.
+    pub fn split(self, output: &str) -> Result<Vec<String>, String> {
+        match self {
+            MapSplit::Lines => Ok(output
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect()),
+            MapSplit::Json => {
+                let items: Vec<Value> = serde_json::from_str(output)
+                    .map_err(|e| format!("input is not a JSON array: {}", e))?;
+                Ok(items
+                    .into_iter()
+                    .map(|item| match item {
+                        Value::String(text) => text,
+                        other => other.to_string(),
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+fn current_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// A way a Plan doesn't match the schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SchemaError {
+    /// Where the problem is, e.g. `tasks[1].input_from_task`
+    pub path: String,
+    pub message: String,
+}
+
+impl SchemaError {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl Plan {
+    /// Problems with the Plan's version, task count and numbering,
+    /// commands, pipeline stages, input references, timeouts, tags, limits
+    /// and map tasks
+    pub fn check(&self) -> Vec<SchemaError> {
+        let mut errors = Vec::new();
+
+        if let Err(error) = check_schema_version(self.schema_version) {
+            errors.push(error);
+        }
+        for (name, value) in [
+            ("max_concurrency", self.max_concurrency),
+            ("weight", self.weight),
+        ] {
+            if value == Some(0) {
+                errors.push(SchemaError::new(name, "is 0, but must be at least 1"));
+            }
+        }
+        if self.tasks.is_empty() {
+            errors.push(SchemaError::new("tasks", "a plan needs at least one task"));
+        } else if self.tasks.len() > MAX_TASKS {
+            errors.push(SchemaError::new(
+                "tasks",
+                format!(
+                    "{} tasks is more than the {} a plan may have",
+                    self.tasks.len(),
+                    MAX_TASKS
+                ),
+            ));
+        }
+
+        let mut numbered = true;
+        for (index, task) in self.tasks.iter().enumerate() {
+            let path = format!("tasks[{}]", index);
+            let expected = index as u32 + 1;
+
+            if numbered && task.task_number != expected {
+                errors.push(SchemaError::new(
+                    format!("{}.task_number", path),
+                    format!(
+                        "is {}, but tasks are numbered 1, 2, 3, … in order, so this should be {}",
+                        task.task_number, expected
+                    ),
+                ));
+                // Later tasks are likely off by the same amount
+                numbered = false;
+            }
+            check_command(&task.command, &format!("{}.command", path), &mut errors);
+            if task.command == PIPELINE_COMMAND {
+                if task.stages.is_empty() {
+                    errors.push(SchemaError::new(
+                        format!("{}.stages", path),
+                        "is empty, but a pipeline task runs its stages",
+                    ));
+                }
+                for (stage_index, stage) in task.stages.iter().enumerate() {
+                    let stage_path = format!("{}.stages[{}].command", path, stage_index);
+                    if stage.command == PIPELINE_COMMAND {
+                        errors.push(SchemaError::new(
+                            stage_path,
+                            "is another pipeline; list its stages here instead",
+                        ));
+                    } else {
+                        check_command(&stage.command, &stage_path, &mut errors);
+                    }
+                }
+            } else if !task.stages.is_empty() {
+                errors.push(SchemaError::new(
+                    format!("{}.stages", path),
+                    format!(
+                        "are only run by pipeline tasks; set command to \"{}\"",
+                        PIPELINE_COMMAND
+                    ),
+                ));
+            }
+            if task.map.is_some() && task.input_from_task.is_none() {
+                errors.push(SchemaError::new(
+                    format!("{}.map", path),
+                    "splits the output of input_from_task, which this task doesn't set",
+                ));
+            }
+            if task.max_attempts == Some(0) {
+                errors.push(SchemaError::new(
+                    format!("{}.max_attempts", path),
+                    "is 0, but must be at least 1",
+                ));
+            }
+            if let Some(upstream) = task.input_from_task {
+                if upstream == 0 || upstream >= task.task_number {
+                    errors.push(SchemaError::new(
+                        format!("{}.input_from_task", path),
+                        format!(
+                            "refers to task {}, but task {} can only take input from an earlier task",
+                            upstream, task.task_number
+                        ),
+                    ));
+                }
+            }
+            for (dependency_index, &upstream) in task.depends_on.iter().enumerate() {
+                if upstream == 0 || upstream >= task.task_number {
+                    errors.push(SchemaError::new(
+                        format!("{}.depends_on[{}]", path, dependency_index),
+                        format!(
+                            "refers to task {}, but task {} can only wait for an earlier task",
+                            upstream, task.task_number
+                        ),
+                    ));
+                }
+            }
+            if let Some(timeout) = task.timeout_secs {
+                if timeout == 0 || timeout > MAX_TIMEOUT_SECS {
+                    errors.push(SchemaError::new(
+                        format!("{}.timeout_secs", path),
+                        format!(
+                            "is {}, but must be between 1 and {} seconds",
+                            timeout, MAX_TIMEOUT_SECS
+                        ),
+                    ));
+                }
+            }
+            if task.tags.len() > MAX_TAGS {
+                errors.push(SchemaError::new(
+                    format!("{}.tags", path),
+                    format!(
+                        "has {} tags, more than the {} a task may require",
+                        task.tags.len(),
+                        MAX_TAGS
+                    ),
+                ));
+            }
+            for (tag_index, tag) in task.tags.iter().enumerate() {
+                if !is_tag(tag) {
+                    errors.push(SchemaError::new(
+                        format!("{}.tags[{}]", path, tag_index),
+                        format!(
+                            "\"{}\" is not a worker tag; use letters, digits, '.', '_', ':' and '-', starting with a letter or digit",
+                            tag
+                        ),
+                    ));
+                }
+            }
+            for (name, limit) in [
+                ("memory_mb", task.limits.memory_mb),
+                ("cpu_secs", task.limits.cpu_secs),
+                ("max_processes", task.limits.max_processes),
+            ] {
+                if limit == Some(0) {
+                    errors.push(SchemaError::new(
+                        format!("{}.limits.{}", path, name),
+                        "is 0, but a limit must be greater than 0",
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+/// Parse and strictly validate Plan JSON
+pub fn parse(json: &str) -> Result<Plan, Vec<SchemaError>> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|error| vec![SchemaError::new("plan", format!("invalid JSON: {}", error))])?;
+    validate(&value)
+}
+
+/// Strictly validate a Plan, returning every problem found
+pub fn validate(value: &Value) -> Result<Plan, Vec<SchemaError>> {
+    let Some(fields) = value.as_object() else {
+        return Err(vec![SchemaError::new(
+            "plan",
+            "expected an object with a tasks array",
+        )]);
+    };
+
+    let mut errors = Vec::new();
+    unknown_fields(fields, PLAN_FIELDS, "plan", &mut errors);
+    if let Some(version) = fields.get("schema_version") {
+        if !is_count(version) {
+            errors.push(SchemaError::new(
+                "schema_version",
+                "expected a positive integer",
+            ));
+        }
+    }
+    for name in ["plan_id", "plan_description"] {
+        if fields.get(name).is_some_and(|value| !value.is_string()) {
+            errors.push(SchemaError::new(name, "expected a string"));
+        }
+    }
+    for name in ["max_concurrency", "weight"] {
+        if let Some(value) = fields.get(name) {
+            if !value.is_null() && !is_count(value) {
+                errors.push(SchemaError::new(
+                    name,
+                    format!("expected a positive integer, found {}", value),
+                ));
+            }
+        }
+    }
+    check_choice(
+        fields.get("on_failure"),
+        "on_failure",
+        &["cancel", "continue", "retry"],
+        &mut errors,
+    );
+    match fields.get("env") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(env)) => {
+            for (name, value) in env {
+                if !value.is_string() {
+                    errors.push(SchemaError::new(
+                        format!("env.{}", name),
+                        format!("expected a string, found {}", value),
+                    ));
+                }
+            }
+        }
+        Some(_) => errors.push(SchemaError::new("env", "expected an object of strings")),
+    }
+    match fields.get("tasks") {
+        Some(Value::Array(tasks)) => {
+            for (index, task) in tasks.iter().enumerate() {
+                check_task_shape(index, task, &mut errors);
+            }
+        }
+        Some(_) => errors.push(SchemaError::new("tasks", "expected an array of tasks")),
+        None => errors.push(SchemaError::new("plan", "missing the tasks array")),
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let plan: Plan = serde_json::from_value(value.clone())
+        .map_err(|error| vec![SchemaError::new("plan", error.to_string())])?;
+    let errors = plan.check();
+    if errors.is_empty() {
+        Ok(plan)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The `schema_version` of a Plan, 1 when absent, if this crate supports it
+///
+/// Only the version is checked, so Plans with fields beyond this schema
+/// pass.
+pub fn schema_version_of(value: &Value) -> Result<u32, SchemaError> {
+    let version = match value.get("schema_version") {
+        None | Some(Value::Null) => return Ok(SCHEMA_VERSION),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| SchemaError::new("schema_version", "expected a positive integer"))?,
+    };
+    check_schema_version(version)?;
+    Ok(version)
+}
+
+/// Whether a component built with this crate can read `version`
+pub fn check_schema_version(version: u32) -> Result<(), SchemaError> {
+    if version == 0 {
+        return Err(SchemaError::new(
+            "schema_version",
+            "expected a positive integer",
+        ));
+    }
+    if version > SCHEMA_VERSION {
+        return Err(SchemaError::new(
+            "schema_version",
+            format!(
+                "is {}, but only version {} is supported; upgrade this component",
+                version, SCHEMA_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn check_task_shape(index: usize, task: &Value, errors: &mut Vec<SchemaError>) {
+    let path = format!("tasks[{}]", index);
+    let Some(fields) = task.as_object() else {
+        errors.push(SchemaError::new(
+            path,
+            "expected an object with task_number and command",
+        ));
+        return;
+    };
+    unknown_fields(fields, TASK_FIELDS, &path, errors);
+
+    for name in ["task_number", "command"] {
+        if !fields.contains_key(name) {
+            errors.push(SchemaError::new(&path, format!("missing {}", name)));
+        }
+    }
+    for name in [
+        "task_number",
+        "input_from_task",
+        "timeout_secs",
+        "max_attempts",
+    ] {
+        let Some(value) = fields.get(name) else {
+            continue;
+        };
+        if value.is_null() && name != "task_number" {
+            continue;
+        }
+        if !is_count(value) {
+            errors.push(SchemaError::new(
+                format!("{}.{}", path, name),
+                format!("expected a non-negative integer, found {}", value),
+            ));
+        }
+    }
+    match fields.get("depends_on") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(values)) => {
+            for (value_index, value) in values.iter().enumerate() {
+                if !is_count(value) {
+                    errors.push(SchemaError::new(
+                        format!("{}.depends_on[{}]", path, value_index),
+                        format!("expected a task number, found {}", value),
+                    ));
+                }
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(
+            format!("{}.depends_on", path),
+            "expected an array of task numbers",
+        )),
+    }
+    if fields
+        .get("command")
+        .is_some_and(|value| !value.is_string())
+    {
+        errors.push(SchemaError::new(
+            format!("{}.command", path),
+            "expected a string",
+        ));
+    }
+    for name in ["args", "tags", "artifacts"] {
+        check_strings(fields.get(name), &format!("{}.{}", path, name), errors);
+    }
+    if let Some(value) = fields.get("run_after_secs") {
+        if !value.is_null() && !value.is_u64() {
+            errors.push(SchemaError::new(
+                format!("{}.run_after_secs", path),
+                format!("expected a non-negative integer, found {}", value),
+            ));
+        }
+    }
+    if fields
+        .get("await_event")
+        .is_some_and(|value| !value.is_boolean())
+    {
+        errors.push(SchemaError::new(
+            format!("{}.await_event", path),
+            "expected true or false",
+        ));
+    }
+    check_choice(
+        fields.get("priority"),
+        &format!("{}.priority", path),
+        &["high", "normal", "low"],
+        errors,
+    );
+    check_choice(
+        fields.get("map").filter(|value| !value.is_null()),
+        &format!("{}.map", path),
+        &["lines", "json"],
+        errors,
+    );
+    match fields.get("stages") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(stages)) => {
+            for (stage_index, stage) in stages.iter().enumerate() {
+                let stage_path = format!("{}.stages[{}]", path, stage_index);
+                let Some(stage) = stage.as_object() else {
+                    errors.push(SchemaError::new(
+                        stage_path,
+                        "expected an object with a command",
+                    ));
+                    continue;
+                };
+                unknown_fields(stage, STAGE_FIELDS, &stage_path, errors);
+                match stage.get("command") {
+                    Some(Value::String(_)) => {}
+                    Some(_) => errors.push(SchemaError::new(
+                        format!("{}.command", stage_path),
+                        "expected a string",
+                    )),
+                    None => errors.push(SchemaError::new(&stage_path, "missing command")),
+                }
+                check_strings(stage.get("args"), &format!("{}.args", stage_path), errors);
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(
+            format!("{}.stages", path),
+            "expected an array of stages",
+        )),
+    }
+    match fields.get("backoff") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(backoff)) => {
+            let backoff_path = format!("{}.backoff", path);
+            unknown_fields(backoff, BACKOFF_FIELDS, &backoff_path, errors);
+            check_choice(
+                backoff.get("strategy"),
+                &format!("{}.strategy", backoff_path),
+                &["fixed", "linear", "exponential"],
+                errors,
+            );
+            for name in ["delay_secs", "max_delay_secs"] {
+                if let Some(value) = backoff.get(name) {
+                    if !value.is_u64() {
+                        errors.push(SchemaError::new(
+                            format!("{}.{}", backoff_path, name),
+                            format!("expected a non-negative integer, found {}", value),
+                        ));
+                    }
+                }
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(
+            format!("{}.backoff", path),
+            "expected an object with a strategy and delays",
+        )),
+    }
+    match fields.get("limits") {
+        None | Some(Value::Null) => {}
+        Some(Value::Object(limits)) => {
+            let limits_path = format!("{}.limits", path);
+            unknown_fields(limits, LIMIT_FIELDS, &limits_path, errors);
+            for (name, value) in limits {
+                if LIMIT_FIELDS.contains(&name.as_str()) && !value.is_null() && !value.is_u64() {
+                    errors.push(SchemaError::new(
+                        format!("{}.{}", limits_path, name),
+                        format!("expected a non-negative integer, found {}", value),
+                    ));
+                }
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(
+            format!("{}.limits", path),
+            "expected an object of limits",
+        )),
+    }
+}
+
+/// Report a value that is not an array of strings
+fn check_strings(value: Option<&Value>, path: &str, errors: &mut Vec<SchemaError>) {
+    match value {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(values)) => {
+            for (index, value) in values.iter().enumerate() {
+                if !value.is_string() {
+                    errors.push(SchemaError::new(
+                        format!("{}[{}]", path, index),
+                        format!("expected a string, found {}", value),
+                    ));
+                }
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(path, "expected an array of strings")),
+    }
+}
+
+/// Report a value that is not one of `choices`
+fn check_choice(
+    value: Option<&Value>,
+    path: &str,
+    choices: &[&str],
+    errors: &mut Vec<SchemaError>,
+) {
+    let Some(value) = value else {
+        return;
+    };
+    if !value
+        .as_str()
+        .is_some_and(|choice| choices.contains(&choice))
+    {
+        errors.push(SchemaError::new(
+            path,
+            format!("expected one of {}, found {}", choices.join(", "), value),
+        ));
+    }
+}
+
+fn unknown_fields(
+    fields: &Map<String, Value>,
+    known: &[&str],
+    path: &str,
+    errors: &mut Vec<SchemaError>,
+) {
+    for name in fields.keys() {
+        if known.contains(&name.as_str()) {
+            continue;
+        }
+        let hint = known
+            .iter()
+            .find(|field| field.contains(name.as_str()) || name.contains(*field))
+            .map(|field| format!("did you mean {}?", field))
+            .unwrap_or_else(|| format!("expected one of {}", known.join(", ")));
+        errors.push(SchemaError::new(
+            path,
+            format!("unknown field \"{}\"; {}", name, hint),
+        ));
+    }
+}
+
+/// Report a command that is blank or contains control characters
+fn check_command(command: &str, path: &str, errors: &mut Vec<SchemaError>) {
+    if command.trim().is_empty() {
+        errors.push(SchemaError::new(path, "is empty; name the tool to run"));
+    } else if command.chars().any(char::is_control) {
+        errors.push(SchemaError::new(
+            path,
+            format!("{:?} contains control characters", command),
+        ));
+    }
+}
+
+/// Whether `tag` can name a worker tag, such as `gpu` or `vram:24`
+fn is_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphanumeric())
+        && tag.len() <= MAX_TAG_LEN
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | ':' | '-'))
+}
+
+/// Whether `value` is an integer that fits a `u32`
+fn is_count(value: &Value) -> bool {
+    value
+        .as_u64()
+        .is_some_and(|value| u32::try_from(value).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn messages(value: Value) -> Vec<String> {
+        validate(&value)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_plans_parse_with_the_current_version() {
+        let plan = parse(
+            r#"{"plan_id": "p", "tasks": [
+                {"task_number": 1, "command": "sort"},
+                {"task_number": 2, "command": "uniq", "args": ["-c"], "input_from_task": 1}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.schema_version, SCHEMA_VERSION);
+        assert_eq!(plan.tasks[1].args, vec!["-c"]);
+        assert_eq!(plan.tasks[1].input_from_task, Some(1));
+    }
+
+    #[test]
+    fn unknown_fields_are_named() {
+        let errors = messages(json!({
+            "steps": [],
+            "tasks": [{"task_number": 1, "command": "sort", "input_from": 1, "tool": "x"}]
+        }));
+        assert_eq!(
+            errors,
+            vec![
+                "plan: unknown field \"steps\"; expected one of schema_version, plan_id, plan_description, on_failure, max_concurrency, weight, env, tasks",
+                "tasks[0]: unknown field \"input_from\"; did you mean input_from_task?",
+                "tasks[0]: unknown field \"tool\"; expected one of task_number, command, args, input_from_task, depends_on, timeout_secs, tags, limits, priority, stages, artifacts, max_attempts, backoff, run_after_secs, await_event, map",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_numbering_and_references_are_explained() {
+        let errors = messages(json!({"tasks": [
+            {"task_number": 1, "command": "sort"},
+            {"task_number": 3, "command": "uniq", "input_from_task": 3},
+            {"task_number": 4, "command": " ", "timeout_secs": 0}
+        ]}));
+        assert_eq!(
+            errors,
+            vec![
+                "tasks[1].task_number: is 3, but tasks are numbered 1, 2, 3, … in order, so this should be 2",
+                "tasks[1].input_from_task: refers to task 3, but task 3 can only take input from an earlier task",
+                "tasks[2].command: is empty; name the tool to run",
+                "tasks[2].timeout_secs: is 0, but must be between 1 and 3600 seconds",
+            ]
+        );
+    }
+
+    #[test]
+    fn tasks_can_wait_for_earlier_tasks() {
+        let plan = parse(
+            r#"{"tasks": [
+                {"task_number": 1, "command": "curl", "args": ["-o", "a.html"]},
+                {"task_number": 2, "command": "curl", "args": ["-o", "b.html"]},
+                {"task_number": 3, "command": "wc", "args": ["a.html", "b.html"], "depends_on": [1, 2]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[2].depends_on, vec![1, 2]);
+        assert!(plan.tasks[0].depends_on.is_empty());
+
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "sort", "depends_on": [1]},
+                {"task_number": 2, "command": "uniq", "depends_on": ["1", 3]}
+            ]})),
+            vec!["tasks[1].depends_on[0]: expected a task number, found \"1\""]
+        );
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "sort", "depends_on": [1]},
+                {"task_number": 2, "command": "uniq", "depends_on": [1, 3]}
+            ]})),
+            vec![
+                "tasks[0].depends_on[0]: refers to task 1, but task 1 can only wait for an earlier task",
+                "tasks[1].depends_on[1]: refers to task 3, but task 2 can only wait for an earlier task",
+            ]
+        );
+    }
+
+    #[test]
+    fn tags_must_be_worker_tags() {
+        let plan = parse(
+            r#"{"tasks": [{"task_number": 1, "command": "agx-ocr", "tags": ["gpu", "vram:24"]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[0].tags, vec!["gpu", "vram:24"]);
+
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "curl", "tags": ["network", "-net", 3]}
+            ]})),
+            vec!["tasks[0].tags[2]: expected a string, found 3"]
+        );
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "curl", "tags": ["network", "has space"]}
+            ]})),
+            vec!["tasks[0].tags[1]: \"has space\" is not a worker tag; use letters, digits, '.', '_', ':' and '-', starting with a letter or digit"]
+        );
+    }
+
+    #[test]
+    fn limits_are_checked() {
+        let plan = parse(
+            r#"{"tasks": [{"task_number": 1, "command": "sort", "limits": {"memory_mb": 512, "cpu_secs": 30}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.tasks[0].limits.memory_mb, Some(512));
+        assert_eq!(plan.tasks[0].limits.max_processes, None);
+        assert_eq!(
+            serde_json::to_value(&plan.tasks[0]).unwrap()["limits"],
+            json!({"memory_mb": 512, "cpu_secs": 30})
+        );
+
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "sort", "limits": {"memory": 512, "cpu_secs": "30"}}
+            ]})),
+            vec![
+                "tasks[0].limits: unknown field \"memory\"; did you mean memory_mb?",
+                "tasks[0].limits.cpu_secs: expected a non-negative integer, found \"30\"",
+            ]
+        );
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "sort", "limits": {"max_processes": 0}}
+            ]})),
+            vec!["tasks[0].limits.max_processes: is 0, but a limit must be greater than 0"]
+        );
+    }
+
+    #[test]
+    fn limits_only_tighten_defaults() {
+        let defaults = ResourceLimits {
+            memory_mb: Some(512),
+            cpu_secs: Some(60),
+            max_processes: None,
+        };
+        let overrides = ResourceLimits {
+            memory_mb: Some(4096),
+            cpu_secs: Some(10),
+            max_processes: Some(32),
+        };
+
+        let limits = defaults.restrict(&overrides);
+        assert_eq!(limits.memory_mb, Some(512));
+        assert_eq!(limits.cpu_secs, Some(10));
+        assert_eq!(limits.max_processes, Some(32));
+    }
+
+    #[test]
+    fn types_are_checked_with_their_path() {
+        let errors = messages(json!({"tasks": [
+            {"task_number": "1", "command": "sort", "args": ["-n", 3]},
+            {"command": 7}
+        ]}));
+        assert_eq!(
+            errors,
+            vec![
+                "tasks[0].task_number: expected a non-negative integer, found \"1\"",
+                "tasks[0].args[1]: expected a string, found 3",
+                "tasks[1]: missing task_number",
+                "tasks[1].command: expected a string",
+            ]
+        );
+        assert_eq!(
+            messages(json!({"tasks": []})),
+            vec!["tasks: a plan needs at least one task"]
+        );
+    }
+
+    #[test]
+    fn scheduling_fields_parse() {
+        let plan = parse(
+            r#"{"plan_id": "p", "on_failure": "retry", "weight": 2, "env": {"DB": "secret://db"},
+                "tasks": [
+                {"task_number": 1, "command": "ls", "priority": "high", "max_attempts": 3,
+                 "backoff": {"strategy": "fixed", "delay_secs": 10}},
+                {"task_number": 2, "command": "pipeline", "input_from_task": 1, "map": "lines",
+                 "stages": [{"command": "sort"}, {"command": "uniq", "args": ["-c"]}],
+                 "artifacts": ["out.txt"], "run_after_secs": 60, "await_event": true}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(plan.on_failure, FailurePolicy::Retry);
+        assert_eq!(plan.env["DB"], "secret://db");
+        assert_eq!(plan.tasks[0].priority, JobPriority::High);
+        assert_eq!(plan.tasks[0].backoff.delay(3), 10);
+        assert_eq!(plan.tasks[0].backoff.max_delay_secs, 300);
+        assert_eq!(plan.tasks[1].stages[1].args, vec!["-c"]);
+        assert_eq!(plan.tasks[1].map, Some(MapSplit::Lines));
+        assert!(plan.tasks[1].await_event);
+
+        let plain = parse(r#"{"tasks": [{"task_number": 1, "command": "sort"}]}"#).unwrap();
+        assert_eq!(
+            serde_json::to_value(&plain).unwrap(),
+            json!({"schema_version": 1, "tasks": [{"task_number": 1, "command": "sort", "args": []}]})
+        );
+    }
+
+    #[test]
+    fn scheduling_fields_are_checked() {
+        assert_eq!(
+            messages(json!({"on_failure": "ignore", "weight": -1, "env": {"A": 1}, "tasks": [
+                {"task_number": 1, "command": "sort", "priority": "urgent", "await_event": "yes",
+                 "stages": [{"cmd": "wc"}], "backoff": {"strategy": "random", "delay": 1}}
+            ]})),
+            vec![
+                "weight: expected a positive integer, found -1",
+                "on_failure: expected one of cancel, continue, retry, found \"ignore\"",
+                "env.A: expected a string, found 1",
+                "tasks[0].await_event: expected true or false",
+                "tasks[0].priority: expected one of high, normal, low, found \"urgent\"",
+                "tasks[0].stages[0]: unknown field \"cmd\"; expected one of command, args",
+                "tasks[0].stages[0]: missing command",
+                "tasks[0].backoff: unknown field \"delay\"; did you mean delay_secs?",
+                "tasks[0].backoff.strategy: expected one of fixed, linear, exponential, found \"random\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn pipelines_and_map_tasks_are_checked() {
+        assert_eq!(
+            messages(json!({"tasks": [
+                {"task_number": 1, "command": "echo\nrm"},
+                {"task_number": 2, "command": "pipeline"},
+                {"task_number": 3, "command": "sort", "stages": [{"command": "wc"}]},
+                {"task_number": 4, "command": "pipeline", "stages": [{"command": " "}, {"command": "pipeline"}]},
+                {"task_number": 5, "command": "cat", "map": "json", "max_attempts": 0}
+            ]})),
+            vec![
+                "tasks[0].command: \"echo\\nrm\" contains control characters",
+                "tasks[1].stages: is empty, but a pipeline task runs its stages",
+                "tasks[2].stages: are only run by pipeline tasks; set command to \"pipeline\"",
+                "tasks[3].stages[0].command: is empty; name the tool to run",
+                "tasks[3].stages[1].command: is another pipeline; list its stages here instead",
+                "tasks[4].map: splits the output of input_from_task, which this task doesn't set",
+                "tasks[4].max_attempts: is 0, but must be at least 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn map_splits_output_into_items() {
+        assert_eq!(
+            MapSplit::Lines.split("a.png\r\n\n  \nb c.png\n").unwrap(),
+            ["a.png", "b c.png"]
+        );
+        assert_eq!(
+            MapSplit::Json
+                .split(r#"["a.png", 2, {"page": 3}]"#)
+                .unwrap(),
+            ["a.png", "2", r#"{"page":3}"#]
+        );
+        assert!(MapSplit::Json.split(r#"{"a": 1}"#).is_err());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        assert_eq!(schema_version_of(&json!({"tasks": []})), Ok(1));
+        assert_eq!(
+            schema_version_of(&json!({"schema_version": 1, "stages": []})),
+            Ok(1)
+        );
+        assert_eq!(
+            schema_version_of(&json!({"schema_version": 2}))
+                .unwrap_err()
+                .to_string(),
+            "schema_version: is 2, but only version 1 is supported; upgrade this component"
+        );
+        assert!(schema_version_of(&json!({"schema_version": "1"})).is_err());
+    }
+}
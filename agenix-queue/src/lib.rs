@@ -0,0 +1,89 @@
+//! Names of the ready queues jobs are routed to
+//!
+//! Tasks list the worker tags their jobs need, e.g. `gpu` or `vram:24`.
+//! Each distinct set of requirements gets its own ready queue, so a worker
+//! only takes jobs from queues whose requirements it meets:
+//!
+//! - no requirements: `queue:default`
+//! - just `gpu`: `queue:gpu`
+//! - anything else: `queue:tags:<tag>+<tag>...`, with the tags sorted
+//!
+//! AGQ pushes jobs to these queues and AGW pops from them, so both name
+//! them with this crate.
+
+use std::collections::BTreeSet;
+
+/// Ready queue for jobs without requirements
+pub const DEFAULT_QUEUE: &str = "queue:default";
+
+/// Ready queue for jobs that only require a GPU
+pub const GPU_QUEUE: &str = "queue:gpu";
+
+/// Prefix of ready queues for other sets of requirements
+pub const TAGGED_QUEUE_PREFIX: &str = "queue:tags:";
+
+/// Tags every worker is assumed to have
+///
+/// AGQ tags tasks that need no GPU `cpu`, so requiring it does not narrow
+/// which workers can run a job.
+const IMPLIED_TAGS: &[&str] = &["cpu"];
+
+/// The tags in a job's tag list that narrow which workers can run it
+#[must_use]
+pub fn requirements(tags: &[String]) -> BTreeSet<&str> {
+    tags.iter()
+        .map(String::as_str)
+        .filter(|tag| !IMPLIED_TAGS.contains(tag))
+        .collect()
+}
+
+/// Ready queue for jobs with a set of requirements
+#[must_use]
+pub fn queue_for(requirements: &BTreeSet<&str>) -> String {
+    let tags: Vec<&str> = requirements.iter().copied().collect();
+    match tags.as_slice() {
+        [] => DEFAULT_QUEUE.to_string(),
+        ["gpu"] => GPU_QUEUE.to_string(),
+        tags => format!("{}{}", TAGGED_QUEUE_PREFIX, tags.join("+")),
+    }
+}
+
+/// Requirements of the jobs in a ready queue, or `None` if the queue is not
+/// a ready queue
+#[must_use]
+pub fn queue_requirements(queue: &str) -> Option<BTreeSet<&str>> {
+    match queue {
+        DEFAULT_QUEUE => Some(BTreeSet::new()),
+        GPU_QUEUE => Some(BTreeSet::from(["gpu"])),
+        _ => {
+            let tags = queue.strip_prefix(TAGGED_QUEUE_PREFIX)?;
+            let requirements: BTreeSet<&str> = tags.split('+').collect();
+            (!requirements.contains("")).then_some(requirements)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_queue_for_requirements() {
+        for (job_tags, queue) in [
+            (tags(&[]), "queue:default"),
+            (tags(&["cpu"]), "queue:default"),
+            (tags(&["gpu"]), "queue:gpu"),
+            (tags(&["vram:24", "gpu", "gpu"]), "queue:tags:gpu+vram:24"),
+        ] {
+            let requirements = requirements(&job_tags);
+            assert_eq!(queue_for(&requirements), queue);
+            assert_eq!(queue_requirements(queue).unwrap(), requirements);
+        }
+        assert!(queue_requirements("queue:processing").is_none());
+        assert!(queue_requirements("queue:tags:gpu++x").is_none());
+    }
+}
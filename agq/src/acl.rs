@@ -0,0 +1,325 @@
+//! Named client credentials and role-based command permissions
+//!
+//! Each client authenticates with its own key and gets a [`Role`] that
+//! decides which commands it may run: a planning UI can submit Plans and
+//! Actions without being able to purge jobs or change queue limits, and a
+//! worker can take jobs and write their output without submitting work.
+//!
+//! Credentials are read from a JSON file passed with `--acl-file`:
+//!
+//! ```json
+//! {"clients": [
+//!   {"name": "planner-ui", "key": "<hex key>", "role": "submit"},
+//!   {"name": "gpu-01", "key": "<hex key>", "role": "worker"}
+//! ]}
+//! ```
+//!
+//! The shared session key, if any, is kept as the `default` client with
+//! the admin role, so single-key deployments keep working unchanged.
+
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::path::Path;
+use subtle::ConstantTimeEq;
+
+/// Minimum key length in bytes, matching the session key
+const MIN_KEY_BYTES: usize = 16;
+
+/// Name of the client authenticating with the shared session key
+pub const DEFAULT_CLIENT: &str = "default";
+
+/// What a client is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Submit and schedule Plans and Actions, and read their state
+    Submit,
+    /// Take jobs, report their status, and store their output
+    Worker,
+    /// Every command, including purging jobs and setting queue limits
+    Admin,
+}
+
+impl Role {
+    /// Whether the role may run commands of a class
+    #[must_use]
+    pub fn allows(self, class: CommandClass) -> bool {
+        match class {
+            CommandClass::Read => true,
+            CommandClass::Submit => matches!(self, Role::Submit | Role::Admin),
+            CommandClass::Work => matches!(self, Role::Worker | Role::Admin),
+            CommandClass::Admin => self == Role::Admin,
+        }
+    }
+}
+
+/// Permission a command needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandClass {
+    /// Inspecting Plans, Actions, jobs, workers, tools, queues, and the
+    /// audit log
+    Read,
+    /// Creating or scheduling work
+    Submit,
+    /// Queue and storage primitives workers use to run jobs
+    Work,
+    /// Deleting data and changing server settings
+    Admin,
+}
+
+/// Permission a command needs, or `None` for commands AGQ does not know
+#[must_use]
+pub fn command_class(command: &str) -> Option<CommandClass> {
+    let class = match command {
+        "PING"
+        | "PLAN.LIST"
+        | "PLAN.GET"
+        | "PLAN.STATUS"
+        | "PLAN.RESULTS"
+        | "PLAN.SCHEDULES"
+        | "PLAN.TEMPLATES"
+        | "ACTION.LIST"
+        | "ACTION.GET"
+        | "JOBS.LIST"
+        | "JOB.LIST"
+        | "JOB.GET"
+        | "ARTIFACT.GET"
+        | "WORKERS.LIST"
+        | "QUEUE.STATS"
+        | "QUEUE.UNSCHEDULABLE"
+        | "STATS.MEMORY"
+        | "AUDIT.QUERY"
+        | "TOOLS.CATALOG"
+        | "TOOLS.STATS"
+        | "REPLICA.STATUS"
+        | "SUBSCRIBE" => CommandClass::Read,
+        "PLAN.SUBMIT" | "ACTION.SUBMIT" | "PLAN.SCHEDULE" | "PLAN.UNSCHEDULE"
+        | "PLAN.TEMPLATE.SAVE" | "PLAN.INSTANTIATE" | "PLAN.CANCEL" | "JOB.CANCEL"
+        | "EVENT.FIRE" => CommandClass::Submit,
+        "GET" | "SET" | "DEL" | "EXISTS" | "TTL" | "LPUSH" | "RPOP" | "BRPOP" | "LLEN"
+        | "LRANGE" | "LREM" | "RPOPLPUSH" | "BRPOPLPUSH" | "ZADD" | "ZRANGE" | "ZRANGEBYSCORE"
+        | "ZREM" | "ZSCORE" | "ZCARD" | "HSET" | "HGET" | "HDEL" | "HGETALL" | "HEXISTS"
+        | "HLEN" | "HINCRBY" | "JOB.LOG" | "ARTIFACT.PUT" | "WORKER.QUEUES" | "TOOLS.REGISTER"
+        | "JOB.ENV" => CommandClass::Work,
+        "JOB.PURGE" | "QUEUE.LIMIT" | "REPLICA.SYNC" | "REPLICA.PROMOTE" | "SECRET.SET"
+        | "SECRET.DEL" | "SECRET.LIST" => CommandClass::Admin,
+        _ => return None,
+    };
+    Some(class)
+}
+
+/// An authenticated client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Client {
+    /// Check the client may run a command
+    ///
+    /// Unknown commands pass, so they are reported as unknown rather than
+    /// forbidden.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoPerm`] if the client's role does not allow it
+    pub fn authorize(&self, command: &str) -> Result<()> {
+        match command_class(command) {
+            Some(class) if !self.role.allows(class) => Err(Error::NoPerm {
+                client: self.name.clone(),
+                command: command.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A client's name, key, and role
+#[derive(Debug, Clone)]
+struct Credential {
+    name: String,
+    key: Vec<u8>,
+    role: Role,
+}
+
+/// The credentials clients may authenticate with
+#[derive(Debug, Clone, Default)]
+pub struct Acl {
+    credentials: Vec<Credential>,
+}
+
+#[derive(Deserialize)]
+struct AclFile {
+    clients: Vec<ClientEntry>,
+}
+
+#[derive(Deserialize)]
+struct ClientEntry {
+    name: String,
+    key: String,
+    role: Role,
+}
+
+impl Acl {
+    /// ACL with only the shared session key, as the admin `default` client
+    #[must_use]
+    pub fn with_session_key(key: Vec<u8>) -> Self {
+        let mut acl = Self::default();
+        acl.credentials.push(Credential {
+            name: DEFAULT_CLIENT.to_string(),
+            key,
+            role: Role::Admin,
+        });
+        acl
+    }
+
+    /// Add the clients listed in an ACL file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, a key is not
+    /// hex or shorter than 16 bytes, or a name or key is used twice
+    pub fn load(mut self, path: &Path) -> Result<Self> {
+        let json = std::fs::read(path)?;
+        let file: AclFile = serde_json::from_slice(&json)
+            .map_err(|e| Error::InvalidArguments(format!("Invalid ACL file: {}", e)))?;
+
+        for entry in file.clients {
+            let key = hex::decode(&entry.key).map_err(|_| {
+                Error::InvalidArguments(format!("Key of client {} is not hex", entry.name))
+            })?;
+            self.add(entry.name, key, entry.role)?;
+        }
+        Ok(self)
+    }
+
+    /// Add a client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is shorter than 16 bytes, or the name or
+    /// key is already in use
+    pub fn add(&mut self, name: String, key: Vec<u8>, role: Role) -> Result<()> {
+        if key.len() < MIN_KEY_BYTES {
+            return Err(Error::InvalidArguments(format!(
+                "Key of client {} must be at least {} bytes",
+                name, MIN_KEY_BYTES
+            )));
+        }
+        if self.credentials.iter().any(|c| c.name == name) {
+            return Err(Error::InvalidArguments(format!(
+                "Client {} is defined twice",
+                name
+            )));
+        }
+        if self.credentials.iter().any(|c| c.key == key) {
+            return Err(Error::InvalidArguments(format!(
+                "Client {} reuses another client's key",
+                name
+            )));
+        }
+        self.credentials.push(Credential { name, key, role });
+        Ok(())
+    }
+
+    /// Whether no client can authenticate
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    /// The client a key belongs to, optionally checking the client's name
+    ///
+    /// Every credential is compared in constant time, so neither the
+    /// position of a match nor how much of a key matched leaks through
+    /// timing.
+    #[must_use]
+    pub fn authenticate(&self, name: Option<&str>, key: &[u8]) -> Option<Client> {
+        let mut found = None;
+        for credential in &self.credentials {
+            // Pad to same length for constant-time comparison
+            let max_len = key.len().max(credential.key.len());
+            let mut provided = key.to_vec();
+            let mut expected = credential.key.clone();
+            provided.resize(max_len, 0);
+            expected.resize(max_len, 0);
+
+            let matches = bool::from(provided.ct_eq(&expected))
+                && key.len() == credential.key.len()
+                && name.is_none_or(|name| name == credential.name);
+            if matches && found.is_none() {
+                found = Some(Client {
+                    name: credential.name.clone(),
+                    role: credential.role,
+                });
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_permissions() {
+        let client = |role| Client {
+            name: "c".to_string(),
+            role,
+        };
+
+        let submit = client(Role::Submit);
+        assert!(submit.authorize("ACTION.SUBMIT").is_ok());
+        assert!(submit.authorize("JOB.LIST").is_ok());
+        assert!(submit.authorize("JOB.PURGE").is_err());
+        assert!(submit.authorize("BRPOPLPUSH").is_err());
+
+        let worker = client(Role::Worker);
+        assert!(worker.authorize("BRPOPLPUSH").is_ok());
+        assert!(worker.authorize("JOB.LOG").is_ok());
+        assert!(worker.authorize("PLAN.SUBMIT").is_err());
+        assert!(worker.authorize("QUEUE.LIMIT").is_err());
+
+        let admin = client(Role::Admin);
+        for command in ["JOB.PURGE", "PLAN.SUBMIT", "SET", "NOT.A.COMMAND"] {
+            assert!(admin.authorize(command).is_ok());
+        }
+        assert!(submit.authorize("NOT.A.COMMAND").is_ok());
+    }
+
+    #[test]
+    fn test_acl_file() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("acl.json");
+        let ui_key = "11".repeat(16);
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"clients": [{{"name": "planner-ui", "key": "{}", "role": "submit"}}]}}"#,
+                ui_key
+            ),
+        )
+        .unwrap();
+
+        let acl = Acl::with_session_key(vec![0x22; 32]).load(&path).unwrap();
+        let ui = acl
+            .authenticate(None, &hex::decode(&ui_key).unwrap())
+            .unwrap();
+        assert_eq!((ui.name.as_str(), ui.role), ("planner-ui", Role::Submit));
+        assert_eq!(
+            acl.authenticate(Some("default"), &[0x22; 32]).unwrap().role,
+            Role::Admin
+        );
+        assert!(acl.authenticate(Some("planner-ui"), &[0x22; 32]).is_none());
+        assert!(acl.authenticate(None, &[0x22; 31]).is_none());
+
+        std::fs::write(
+            &path,
+            r#"{"clients": [{"name": "short", "key": "abcd", "role": "worker"}]}"#,
+        )
+        .unwrap();
+        assert!(Acl::default().load(&path).is_err());
+    }
+}
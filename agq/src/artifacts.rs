@@ -0,0 +1,170 @@
+//! Content-addressed artifact storage
+//!
+//! Artifacts are stored under the hex SHA-256 of their data, so identical
+//! outputs from different jobs share one copy and a hash reference always
+//! resolves to the bytes it was computed from. By default they live in the
+//! database as `artifact:<sha256>`; with `--artifact-dir` they are written
+//! to files instead, keeping large job outputs out of the key-value store.
+//!
+//! Either way, `artifacts:all` indexes every hash by when it was last
+//! stored, for retention.
+
+use crate::error::{Error, Result};
+use crate::retention::ARTIFACTS_INDEX;
+use crate::storage::{Database, SortedSetOps, StringOps};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where artifact data is kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ArtifactStore {
+    /// In the database, as `artifact:<sha256>`
+    #[default]
+    Database,
+    /// In files under a directory, as `<dir>/<first two hex digits>/<sha256>`
+    Directory(PathBuf),
+}
+
+/// Whether a string is a hex SHA-256 as artifacts are stored under
+#[must_use]
+pub fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl ArtifactStore {
+    /// Store artifacts in files under a directory, creating it if needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn directory(path: &Path) -> Result<Self> {
+        std::fs::create_dir_all(path)?;
+        Ok(Self::Directory(path.to_path_buf()))
+    }
+
+    /// Store data, returning its hash
+    ///
+    /// Storing data that is already stored only refreshes its place in the
+    /// retention index.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data cannot be written
+    pub fn put(&self, db: &Database, data: &[u8], now: u64) -> Result<String> {
+        let hash = hex::encode(ring::digest::digest(&ring::digest::SHA256, data));
+
+        match self {
+            Self::Database => {
+                let key = format!("artifact:{}", hash);
+                if !db.exists(&key)? {
+                    db.set(&key, data)?;
+                }
+            }
+            Self::Directory(dir) => {
+                let path = file_path(dir, &hash);
+                if !path.exists() {
+                    write_atomically(&path, data)?;
+                }
+            }
+        }
+        db.zadd(ARTIFACTS_INDEX, now as f64, hash.as_bytes())?;
+        Ok(hash)
+    }
+
+    /// Data stored under a hash, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash is malformed or the data cannot be read
+    pub fn get(&self, db: &Database, hash: &str) -> Result<Option<Vec<u8>>> {
+        if !is_valid_hash(hash) {
+            return Err(Error::InvalidArguments(
+                "Artifact hash must be 64 lowercase hex characters".to_string(),
+            ));
+        }
+
+        match self {
+            Self::Database => db.get(&format!("artifact:{}", hash)),
+            Self::Directory(dir) => match std::fs::read(file_path(dir, hash)) {
+                Ok(data) => Ok(Some(data)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+
+    /// Delete the data stored under a hash and remove it from the index
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data cannot be deleted
+    pub fn delete(&self, db: &Database, hash: &str) -> Result<()> {
+        match self {
+            Self::Database => {
+                db.del(&format!("artifact:{}", hash))?;
+            }
+            Self::Directory(dir) if is_valid_hash(hash) => {
+                match std::fs::remove_file(file_path(dir, hash)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+            Self::Directory(_) => {}
+        }
+        db.zrem(ARTIFACTS_INDEX, hash.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// File an artifact is stored in
+fn file_path(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(&hash[..2]).join(hash)
+}
+
+/// Write a file so readers never see it partly written
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| Error::Protocol("Artifact path has no parent".to_string()))?;
+    std::fs::create_dir_all(dir)?;
+
+    let temp = dir.join(format!(".{}.tmp", uuid::Uuid::new_v4().simple()));
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_directory_store() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        let store = ArtifactStore::directory(&temp.path().join("artifacts")).unwrap();
+
+        let hash = store.put(&db, b"page text", 100).unwrap();
+        assert_eq!(store.put(&db, b"page text", 200).unwrap(), hash);
+        assert_eq!(store.get(&db, &hash).unwrap().unwrap(), b"page text");
+        assert!(temp
+            .path()
+            .join("artifacts")
+            .join(&hash[..2])
+            .join(&hash)
+            .exists());
+        assert!(db.get(&format!("artifact:{}", hash)).unwrap().is_none());
+        assert_eq!(
+            db.zscore(ARTIFACTS_INDEX, hash.as_bytes()).unwrap(),
+            Some(200.0)
+        );
+
+        store.delete(&db, &hash).unwrap();
+        assert!(store.get(&db, &hash).unwrap().is_none());
+        assert_eq!(db.zcard(ARTIFACTS_INDEX).unwrap(), 0);
+        assert!(store.get(&db, "../etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,497 @@
+//! Audit log of every change to AGQ's state
+//!
+//! Each command that changes state appends a [`Record`] of who ran it, the
+//! command, the key or ID it named, and the Plan, Action, and job it acted
+//! on. Job status changes get a record of their own with the status before
+//! and after, whether a client caused them or AGQ did, e.g. retrying a job
+//! after its backoff. Worker heartbeats are not recorded.
+//!
+//! Records are kept in the `audit:log` sorted set, scored by a sequence
+//! number, and are never changed or removed: clients cannot write `audit:`
+//! keys, and retention leaves them alone. `AUDIT.QUERY` reads them back.
+
+use crate::error::{Error, Result};
+use crate::job::{Job, JobStatus};
+use crate::resp::RespValue;
+use crate::storage::{Database, HashOps, SortedSetOps};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// Sorted set of audit records as JSON, scored by sequence number
+pub const AUDIT_LOG: &str = "audit:log";
+
+/// Hash holding the last sequence number given to a record
+const AUDIT_SEQ: &str = "audit:seq";
+
+/// Prefix of the keys the audit log is kept in
+const AUDIT_PREFIX: &str = "audit:";
+
+/// Who records are attributed to when AGQ changes state on its own
+pub const INTERNAL_CLIENT: &str = "agq";
+
+/// Longest key or ID kept in a record
+const MAX_KEY_LEN: usize = 256;
+
+tokio::task_local! {
+    /// Client and command the running task is serving
+    static ACTOR: Actor;
+}
+
+/// Who caused a change, and with which command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    pub client: String,
+    pub command: String,
+}
+
+impl Actor {
+    /// The client and command being served, or AGQ itself outside a command
+    #[must_use]
+    pub fn current() -> Self {
+        ACTOR.try_with(Clone::clone).unwrap_or_else(|_| Self {
+            client: INTERNAL_CLIENT.to_string(),
+            command: "internal".to_string(),
+        })
+    }
+
+    /// Run a future with changes it makes attributed to this actor
+    pub async fn scope<F: std::future::Future>(self, future: F) -> F::Output {
+        ACTOR.scope(self, future).await
+    }
+}
+
+/// One change to AGQ's state
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// Position in the log, starting at 1
+    pub seq: u64,
+    pub timestamp: u64,
+    /// Name of the client that ran the command, or `agq`
+    pub client: String,
+    pub command: String,
+    /// Key, queue, or ID the command named
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Job status before the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// Job status after the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// Whether a command changes state and is recorded
+///
+/// `PING` with a worker ID records a heartbeat, but is left out, as is
+/// `SECRET.LIST`, which only reads.
+#[must_use]
+pub fn is_mutation(command: &str) -> bool {
+    use crate::acl::{command_class, CommandClass};
+
+    matches!(
+        command,
+        "SET"
+            | "DEL"
+            | "LPUSH"
+            | "RPOP"
+            | "BRPOP"
+            | "LREM"
+            | "RPOPLPUSH"
+            | "BRPOPLPUSH"
+            | "ZADD"
+            | "ZREM"
+            | "HSET"
+            | "HDEL"
+            | "HINCRBY"
+            | "JOB.LOG"
+            | "ARTIFACT.PUT"
+            | "TOOLS.REGISTER"
+    ) || (matches!(
+        command_class(command),
+        Some(CommandClass::Submit | CommandClass::Admin)
+    ) && command != "SECRET.LIST")
+}
+
+/// Refuse raw writes to the keys the audit log is kept in
+///
+/// # Errors
+///
+/// Returns an error if a data command would write an `audit:` key
+pub fn check_writable(command: &str, args: &[RespValue]) -> Result<()> {
+    let destination = match command {
+        "RPOPLPUSH" | "BRPOPLPUSH" => args.get(2),
+        _ => None,
+    };
+    for key in [args.get(1), destination].into_iter().flatten() {
+        if key.as_string()?.starts_with(AUDIT_PREFIX) {
+            return Err(Error::InvalidArguments(
+                "The audit log is read-only".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Status a command is about to replace, read before it runs
+///
+/// Only `SET job:<id>:status` replaces one.
+#[must_use]
+pub fn status_before(db: &Database, command: &str, args: &[RespValue]) -> Option<String> {
+    use crate::storage::StringOps;
+
+    let key = args.get(1)?.as_string().ok()?;
+    if command != "SET" || !key.starts_with("job:") || !key.ends_with(":status") {
+        return None;
+    }
+    let status = db.get(&key).ok().flatten()?;
+    Some(String::from_utf8_lossy(&status).into_owned())
+}
+
+/// Record a command that succeeded
+///
+/// A blocking pop that timed out changed nothing and is not recorded.
+/// Errors writing the record are logged, since the change has been made.
+pub fn command_ran(
+    db: &Database,
+    actor: &Actor,
+    args: &[RespValue],
+    before: Option<String>,
+    reply: &RespValue,
+) {
+    let command = actor.command.as_str();
+    if matches!(command, "RPOP" | "BRPOP" | "RPOPLPUSH" | "BRPOPLPUSH")
+        && matches!(reply, RespValue::NullBulkString)
+    {
+        return;
+    }
+
+    let arg = |index: usize| args.get(index).and_then(|arg| arg.as_string().ok());
+    let reply_text = match reply {
+        RespValue::BulkString(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        _ => None,
+    };
+    let mut record = Record {
+        client: actor.client.clone(),
+        command: command.to_string(),
+        ..Record::default()
+    };
+
+    match command {
+        "PLAN.SUBMIT" | "PLAN.INSTANTIATE" => {
+            record.plan_id = reply_text;
+            if command == "PLAN.INSTANTIATE" {
+                record.key = arg(1);
+            }
+        }
+        "ACTION.SUBMIT" | "PLAN.SCHEDULE" => {
+            let json = arg(1)
+                .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+                .unwrap_or_default();
+            let field = |name: &str| json.get(name).and_then(|v| v.as_str()).map(String::from);
+            record.plan_id = field("plan_id");
+            record.action_id = field("action_id");
+            record.key = field("schedule_id");
+        }
+        "JOB.LOG" | "JOB.CANCEL" => record.job_id = arg(1),
+        "PLAN.CANCEL" => record.plan_id = arg(1),
+        "EVENT.FIRE" => record.job_id = reply_text,
+        "JOB.PURGE" => match arg(1) {
+            Some(first) if first.eq_ignore_ascii_case("OLDER_THAN") => {
+                record.key = arg(2).map(|seconds| format!("OLDER_THAN {}", seconds));
+            }
+            job_id => record.job_id = job_id,
+        },
+        "ARTIFACT.PUT" => record.key = reply_text,
+        _ => {
+            let key = arg(1).unwrap_or_default();
+            if key.starts_with("queue:") {
+                // Pops reply with the job taken; LREM names the job removed
+                record.job_id = match command {
+                    "LREM" => arg(3),
+                    _ => reply_text,
+                };
+            } else {
+                let (plan_id, action_id, job_id) = ids_in_key(&key);
+                record.plan_id = plan_id;
+                record.action_id = action_id;
+                record.job_id = job_id;
+            }
+            if command == "SET" && key.ends_with(":status") && record.job_id.is_some() {
+                record.before = before;
+                record.after = arg(2);
+            }
+            record.key = Some(key);
+        }
+    }
+    if let Some(key) = &mut record.key {
+        truncate(key, MAX_KEY_LEN);
+    }
+    append(db, record);
+}
+
+/// Record a job changing status
+///
+/// `before` is `None` for a job being stored for the first time.
+pub fn job_changed(db: &Database, job: &Job, before: Option<JobStatus>, after: &str) {
+    let actor = Actor::current();
+    append(
+        db,
+        Record {
+            client: actor.client,
+            command: actor.command,
+            plan_id: Some(job.plan_id.clone()),
+            action_id: Some(job.action_id.clone()),
+            job_id: Some(job.id.clone()),
+            before: before.map(|status| status.as_str().to_string()),
+            after: Some(after.to_string()),
+            ..Record::default()
+        },
+    );
+}
+
+/// Append a record, numbering and timestamping it
+fn append(db: &Database, mut record: Record) {
+    record.timestamp = crate::server::get_current_timestamp_secs().unwrap_or(0);
+    let appended = db.hincrby(AUDIT_SEQ, "seq", 1).and_then(|seq| {
+        record.seq = seq as u64;
+        let json = serde_json::to_vec(&record)
+            .map_err(|e| Error::Protocol(format!("Failed to serialize audit record: {}", e)))?;
+        db.zadd(AUDIT_LOG, seq as f64, &json)
+    });
+    if let Err(e) = appended {
+        error!("Failed to append audit record {:?}: {}", record, e);
+    }
+}
+
+/// Plan, Action, and job IDs in a `plan:<id>...`, `action:<id>...`, or
+/// `job:<id>...` key
+fn ids_in_key(key: &str) -> (Option<String>, Option<String>, Option<String>) {
+    let mut parts = key.splitn(3, ':');
+    let (Some(kind), Some(id)) = (parts.next(), parts.next()) else {
+        return (None, None, None);
+    };
+    let id = Some(id.to_string());
+    match kind {
+        "plan" => (id, None, None),
+        "action" => (None, id, None),
+        "job" => (None, None, id),
+        _ => (None, None, None),
+    }
+}
+
+fn truncate(text: &mut String, max_len: usize) {
+    if text.len() > max_len {
+        let mut end = max_len;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+}
+
+/// Filters and page of an audit log query
+#[derive(Debug, Default)]
+pub struct Query {
+    pub client: Option<String>,
+    pub command: Option<String>,
+    pub plan_id: Option<String>,
+    pub action_id: Option<String>,
+    pub job_id: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Only records after this sequence number
+    pub after: u64,
+    pub limit: u64,
+}
+
+impl Query {
+    pub const DEFAULT_LIMIT: u64 = 100;
+    pub const MAX_LIMIT: u64 = 1000;
+
+    fn matches(&self, record: &Record) -> bool {
+        let id_matches = |wanted: &Option<String>, id: &Option<String>| {
+            wanted
+                .as_deref()
+                .is_none_or(|wanted| Some(wanted) == id.as_deref())
+        };
+        self.client
+            .as_deref()
+            .is_none_or(|client| client == record.client)
+            && self
+                .command
+                .as_deref()
+                .is_none_or(|command| command.eq_ignore_ascii_case(&record.command))
+            && id_matches(&self.plan_id, &record.plan_id)
+            && id_matches(&self.action_id, &record.action_id)
+            && id_matches(&self.job_id, &record.job_id)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+    }
+}
+
+/// Records matching a query, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the log cannot be read or holds invalid JSON
+pub fn query(db: &Database, query: &Query) -> Result<Vec<Record>> {
+    /// Records read from the log at a time
+    const PAGE: u64 = 1000;
+
+    let mut records = Vec::new();
+    let mut start = query.after + 1;
+    while (records.len() as u64) < query.limit {
+        let page = db.zrangebyscore(AUDIT_LOG, start as f64, (start + PAGE - 1) as f64)?;
+        if page.is_empty() {
+            break;
+        }
+        for (json, _seq) in page {
+            let record: Record = serde_json::from_slice(&json)
+                .map_err(|e| Error::Protocol(format!("Invalid audit record: {}", e)))?;
+            // Sequence numbers follow time, so nothing later can match
+            if query.until.is_some_and(|until| record.timestamp > until) {
+                return Ok(records);
+            }
+            if query.matches(&record) {
+                records.push(record);
+                if records.len() as u64 == query.limit {
+                    break;
+                }
+            }
+        }
+        start += PAGE;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn bulk(text: &str) -> RespValue {
+        RespValue::BulkString(text.as_bytes().to_vec())
+    }
+
+    fn actor(client: &str, command: &str) -> Actor {
+        Actor {
+            client: client.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_records_commands() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+
+        let set = [bulk("SET"), bulk("job:j1:status"), bulk("completed")];
+        command_ran(
+            &db,
+            &actor("worker-1", "SET"),
+            &set,
+            Some("running".to_string()),
+            &RespValue::SimpleString("OK".to_string()),
+        );
+        let pop = [
+            bulk("BRPOPLPUSH"),
+            bulk("queue:default"),
+            bulk("queue:processing"),
+        ];
+        command_ran(
+            &db,
+            &actor("worker-1", "BRPOPLPUSH"),
+            &pop,
+            None,
+            &RespValue::NullBulkString,
+        );
+        command_ran(
+            &db,
+            &actor("worker-1", "BRPOPLPUSH"),
+            &pop,
+            None,
+            &bulk("j2"),
+        );
+        command_ran(
+            &db,
+            &actor("ci", "PLAN.SUBMIT"),
+            &[bulk("PLAN.SUBMIT"), bulk("{}")],
+            None,
+            &bulk("ocr"),
+        );
+
+        let all = Query {
+            limit: Query::DEFAULT_LIMIT,
+            ..Query::default()
+        };
+        let records = query(&db, &all).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].seq, 1);
+        assert_eq!(records[0].job_id.as_deref(), Some("j1"));
+        assert_eq!(records[0].before.as_deref(), Some("running"));
+        assert_eq!(records[0].after.as_deref(), Some("completed"));
+        assert_eq!(records[1].job_id.as_deref(), Some("j2"));
+        assert_eq!(records[2].plan_id.as_deref(), Some("ocr"));
+
+        let by_worker = Query {
+            client: Some("worker-1".to_string()),
+            after: 1,
+            limit: 10,
+            ..Query::default()
+        };
+        let records = query(&db, &by_worker).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].command, "BRPOPLPUSH");
+
+        assert!(check_writable("DEL", &[bulk("DEL"), bulk(AUDIT_LOG)]).is_err());
+        assert!(check_writable("RPOPLPUSH", &pop).is_ok());
+        assert!(is_mutation("PLAN.SUBMIT") && !is_mutation("PING"));
+    }
+
+    #[tokio::test]
+    async fn test_records_job_transitions() {
+        use crate::orchestrator::Orchestrator;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        let job = Job::new(
+            "j1".to_string(),
+            "a1".to_string(),
+            "p1".to_string(),
+            1,
+            "echo".to_string(),
+            Vec::new(),
+            serde_json::Value::Null,
+            Vec::new(),
+        );
+        actor("ci", "ACTION.SUBMIT")
+            .scope(async { Orchestrator::new(&db).submit_jobs(vec![job]).unwrap() })
+            .await;
+        Orchestrator::new(&db).complete_job("j1", 0).unwrap();
+
+        let for_job = Query {
+            job_id: Some("j1".to_string()),
+            limit: 10,
+            ..Query::default()
+        };
+        let transitions: Vec<(String, Option<String>, Option<String>)> = query(&db, &for_job)
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.client, record.before, record.after))
+            .collect();
+        let status = |status: &str| Some(status.to_string());
+        assert_eq!(
+            transitions,
+            [
+                ("ci".to_string(), None, status("pending")),
+                ("ci".to_string(), status("pending"), status("ready")),
+                ("agq".to_string(), status("ready"), status("completed")),
+            ]
+        );
+    }
+}
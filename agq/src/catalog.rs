@@ -0,0 +1,213 @@
+//! Capability catalog of the tools workers can run
+//!
+//! Workers register the names of their tools at `worker:<id>:tools`, and
+//! the `--describe` model card of each Agentic Unit among them with
+//! `TOOLS.REGISTER`. Cards are stored in the `worker:<id>:manifests` hash,
+//! keyed by tool name. The catalog gathers the tools of the live workers,
+//! so planners can plan with what the fleet can actually run.
+
+use crate::error::{Error, Result};
+use crate::storage::{Database, HashOps, StringOps};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Maximum size of a model card in bytes
+pub const MAX_CARD_BYTES: usize = 64 * 1024;
+
+/// Maximum number of model cards per worker
+pub const MAX_CARDS_PER_WORKER: usize = 100;
+
+/// Maximum length of a tool name, matching what workers register
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// A tool in the catalog
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    pub name: String,
+    /// The tool's model card, if its workers registered one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card: Option<serde_json::Value>,
+    /// Live workers that have the tool, sorted
+    pub workers: Vec<String>,
+}
+
+fn manifests_key(worker_id: &str) -> String {
+    format!("worker:{}:manifests", worker_id)
+}
+
+/// Store a worker's model card for a tool, returning the tool's name
+///
+/// The card must follow `describe.schema.json`: an object with a `name`,
+/// `version`, `description`, and a list of `capabilities`.
+///
+/// # Errors
+/// Returns an error if the card is too large or malformed, the worker has
+/// too many cards, or the database operation fails
+pub fn register(db: &Database, worker_id: &str, card: &str) -> Result<String> {
+    if card.len() > MAX_CARD_BYTES {
+        return Err(Error::InvalidArguments(format!(
+            "Model card exceeds {} bytes",
+            MAX_CARD_BYTES
+        )));
+    }
+    let card: serde_json::Value = serde_json::from_str(card)
+        .map_err(|e| Error::InvalidArguments(format!("Invalid model card JSON: {}", e)))?;
+    let name = validate(&card)?;
+
+    let key = manifests_key(worker_id);
+    if !db.hexists(&key, &name)? && db.hlen(&key)? as usize >= MAX_CARDS_PER_WORKER {
+        return Err(Error::InvalidArguments(format!(
+            "Worker {} has registered {} model cards, the maximum",
+            worker_id, MAX_CARDS_PER_WORKER
+        )));
+    }
+    db.hset(&key, &name, card.to_string().as_bytes())?;
+    Ok(name)
+}
+
+/// Check a model card has the fields `describe.schema.json` requires,
+/// returning its name
+fn validate(card: &serde_json::Value) -> Result<String> {
+    let invalid = |reason: &str| Error::InvalidArguments(format!("Invalid model card: {}", reason));
+    let text = |field: &str| {
+        card.get(field)
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| invalid(&format!("{} must be a string", field)))
+    };
+
+    if !card.is_object() {
+        return Err(invalid("not a JSON object"));
+    }
+    let name = text("name")?;
+    if name.is_empty()
+        || name.len() > MAX_TOOL_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(invalid(
+            "name must be 1-64 letters, digits, hyphens, or underscores",
+        ));
+    }
+    text("version")?;
+    text("description")?;
+    let capabilities = card
+        .get("capabilities")
+        .and_then(|value| value.as_array())
+        .ok_or_else(|| invalid("capabilities must be a list"))?;
+    if !capabilities.iter().all(serde_json::Value::is_string) {
+        return Err(invalid("capabilities must be strings"));
+    }
+    Ok(name.to_string())
+}
+
+/// The tools of a set of workers, sorted by name
+///
+/// Workers with the same model card for a tool share an entry; a tool
+/// whose workers registered different cards, e.g. different versions, gets
+/// one entry per card. Tools registered by name only get an entry without
+/// a card.
+///
+/// # Errors
+/// Returns an error if a database operation fails
+pub fn catalog(db: &Database, worker_ids: &[String]) -> Result<Vec<Entry>> {
+    // (name, card JSON) -> workers
+    let mut tools: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+
+    for worker_id in worker_ids {
+        let cards = db.hgetall(&manifests_key(worker_id))?;
+        let mut described = Vec::with_capacity(cards.len());
+        for (name, card) in cards {
+            let card = String::from_utf8_lossy(&card).into_owned();
+            described.push(name.clone());
+            tools
+                .entry((name, card))
+                .or_default()
+                .push(worker_id.clone());
+        }
+
+        let names = db.get(&format!("worker:{}:tools", worker_id))?;
+        let names = names
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        for name in names.split(',').map(str::trim) {
+            if !name.is_empty() && !described.iter().any(|d| d == name) {
+                tools
+                    .entry((name.to_string(), String::new()))
+                    .or_default()
+                    .push(worker_id.clone());
+            }
+        }
+    }
+
+    Ok(tools
+        .into_iter()
+        .map(|((name, card), mut workers)| {
+            workers.sort();
+            workers.dedup();
+            Entry {
+                name,
+                card: serde_json::from_str(&card).ok(),
+                workers,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn card(version: &str) -> String {
+        serde_json::json!({
+            "name": "agx-ocr",
+            "version": version,
+            "description": "OCR",
+            "capabilities": ["ocr"]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_catalog_groups_workers_by_card() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+
+        assert_eq!(register(&db, "w1", &card("1.0")).unwrap(), "agx-ocr");
+        register(&db, "w2", &card("1.0")).unwrap();
+        register(&db, "w3", &card("2.0")).unwrap();
+        db.set("worker:w1:tools", b"agx-ocr,sort").unwrap();
+        db.set("worker:w2:tools", b"sort").unwrap();
+        register(&db, "gone", &card("3.0")).unwrap();
+
+        let workers: Vec<String> = ["w1", "w2", "w3"].map(String::from).to_vec();
+        let entries = catalog(&db, &workers).unwrap();
+        let summary: Vec<(&str, Option<&str>, Vec<&str>)> = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.name.as_str(),
+                    entry
+                        .card
+                        .as_ref()
+                        .and_then(|card| card["version"].as_str()),
+                    entry.workers.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("agx-ocr", Some("1.0"), vec!["w1", "w2"]),
+                ("agx-ocr", Some("2.0"), vec!["w3"]),
+                ("sort", None, vec!["w1", "w2"]),
+            ]
+        );
+
+        assert!(register(&db, "w1", "{\"name\": \"agx-ocr\"}").is_err());
+        assert!(register(&db, "w1", &card("1.0").replace("agx-ocr", "../x")).is_err());
+        assert!(register(&db, "w1", "[]").is_err());
+    }
+}
@@ -0,0 +1,594 @@
+//! Read-only HTTP/JSON API for dashboards
+//!
+//! With `--dashboard-addr`, AGQ serves cluster state over plain HTTP so a web
+//! UI or `curl` can inspect it without speaking RESP:
+//!
+//! - `GET /api/plans`, `/api/plans/<id>`, `/api/plans/<id>/status`,
+//!   `/api/plans/<id>/results`
+//! - `GET /api/actions`, `/api/actions/<id>`
+//! - `GET /api/jobs` (filtered like `JOB.LIST`), `/api/jobs/<id>`
+//! - `GET /api/workers`
+//! - `GET /api/queues`
+//! - `GET /api/events`, a Server-Sent Events stream of job events
+//! - `POST /api/events/<token>`, a webhook firing the event a job waits for
+//!
+//! Each read endpoint runs the matching read command, so requests
+//! authenticate with a client key as `Authorization: Bearer <key>` and are
+//! checked against the client's role like any RESP connection. The webhook
+//! needs no key, so CI systems can call it: the event token, known only to
+//! clients that can read the job, is its credential. It runs `EVENT.FIRE`
+//! as the `webhook` client with the request body as the payload.
+
+use crate::acl::{Acl, Client, Role};
+use crate::error::{Error, Result};
+use crate::events;
+use crate::job::JobPriority;
+use crate::orchestrator::{
+    self, AWAITING_EVENT, DELAYED_QUEUE, PROCESSING_QUEUE, THROTTLED_QUEUE, UNSCHEDULABLE_QUEUE,
+};
+use crate::resp::RespValue;
+use crate::routing;
+use crate::server::{self, Settings};
+use crate::storage::{Database, ListOps, SortedSetOps};
+use agenix_metrics::{http_response, read_request_head, with_timeout};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+/// What a request asks for
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    /// A read command, with its arguments
+    Command(Vec<String>),
+    /// Depth of every queue
+    Queues,
+    /// Job events on a SUBSCRIBE channel
+    Events(String),
+    /// Firing the event a token names
+    Fire(String),
+}
+
+/// Client the webhook fires events as, in the audit log
+const WEBHOOK_CLIENT: &str = "webhook";
+
+/// Bind the dashboard API
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(
+        "Serving dashboard API on http://{}/api",
+        listener.local_addr()?
+    );
+    Ok(listener)
+}
+
+/// Serve the dashboard API on the listener until the task is dropped
+pub async fn serve(
+    listener: TcpListener,
+    acl: Arc<Acl>,
+    settings: Arc<Settings>,
+    db: Arc<Database>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let acl = Arc::clone(&acl);
+                let settings = Arc::clone(&settings);
+                let db = Arc::clone(&db);
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, &acl, &settings, &db).await {
+                        debug!("Dashboard request from {peer} failed: {e}");
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept dashboard connection: {e}"),
+        }
+    }
+}
+
+/// Answer a single HTTP request and close the connection
+async fn handle_connection(
+    mut stream: TcpStream,
+    acl: &Acl,
+    settings: &Settings,
+    db: &Database,
+) -> std::io::Result<()> {
+    let request = with_timeout(async {
+        let mut request = read_request_head(&mut stream).await?;
+        read_body(&mut stream, &mut request).await?;
+        Ok(request)
+    })
+    .await?;
+    let request = String::from_utf8_lossy(&request);
+
+    let response = match respond(&request, acl, settings, db).await {
+        Ok(Reply::Json(body)) => http_response("200 OK", "application/json", &body),
+        Ok(Reply::Events(client, channel)) => {
+            return stream_events(stream, &client, &channel).await;
+        }
+        Err((status, message)) => http_response(
+            status,
+            "application/json",
+            &serde_json::json!({ "error": message }).to_string(),
+        ),
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read the rest of a request's body, as long as its `Content-Length`
+/// but no more than an event payload may be
+async fn read_body(stream: &mut TcpStream, request: &mut Vec<u8>) -> std::io::Result<()> {
+    let Some(head_len) = request.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(());
+    };
+    let head = String::from_utf8_lossy(&request[..head_len]);
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0)
+        .min(server::MAX_EVENT_PAYLOAD + 1);
+
+    let end = head_len + 4 + length;
+    let mut buf = [0u8; 4096];
+    while request.len() < end {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    request.truncate(end);
+    Ok(())
+}
+
+/// Successful answer to a request
+enum Reply {
+    Json(String),
+    Events(Client, String),
+}
+
+/// Work out the answer to a request, or the HTTP status and message of the
+/// error
+async fn respond(
+    request: &str,
+    acl: &Acl,
+    settings: &Settings,
+    db: &Database,
+) -> std::result::Result<Reply, (&'static str, String)> {
+    let (head, body) = request.split_once("\r\n\r\n").unwrap_or((request, ""));
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(("400 Bad Request", "Malformed request".to_string()));
+    };
+    let route = parse_route(target)
+        .ok_or_else(|| ("404 Not Found", format!("No endpoint at {}", target)))?;
+    let allowed = match route {
+        Route::Fire(_) => "POST",
+        _ => "GET",
+    };
+    if method != allowed {
+        return Err((
+            "405 Method Not Allowed",
+            "The dashboard API is read-only but for firing events".to_string(),
+        ));
+    }
+    if let Route::Fire(token) = route {
+        return fire(&token, body, acl, settings, db).await;
+    }
+
+    let key = bearer_token(head).ok_or_else(|| {
+        (
+            "401 Unauthorized",
+            "Requires Authorization: Bearer <key>".to_string(),
+        )
+    })?;
+    let mut client = None;
+    run(&["AUTH", key], &mut client, acl, settings, db)
+        .await
+        .map_err(|_| ("401 Unauthorized", "Invalid key".to_string()))?;
+
+    match route {
+        Route::Command(args) => {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let response = run(&args, &mut client, acl, settings, db)
+                .await
+                .map_err(|e| error_status(&e))?;
+            Ok(Reply::Json(to_json(response).to_string()))
+        }
+        Route::Queues => {
+            let client = client.ok_or_else(|| error_status(&Error::NoAuth))?;
+            client
+                .authorize("QUEUE.STATS")
+                .map_err(|e| error_status(&e))?;
+            let depths = queue_depths(db).map_err(|e| error_status(&e))?;
+            Ok(Reply::Json(depths.to_string()))
+        }
+        Route::Events(channel) => {
+            let client = client.ok_or_else(|| error_status(&Error::NoAuth))?;
+            client
+                .authorize("SUBSCRIBE")
+                .map_err(|e| error_status(&e))?;
+            if channel != events::ALL_PLANS {
+                server::validate_identifier(&channel, "plan_id").map_err(|e| error_status(&e))?;
+            }
+            Ok(Reply::Events(client, channel))
+        }
+        Route::Fire(_) => unreachable!("webhooks are answered before authenticating"),
+    }
+}
+
+/// Fire the event a token names, with the request body as its payload
+async fn fire(
+    token: &str,
+    body: &str,
+    acl: &Acl,
+    settings: &Settings,
+    db: &Database,
+) -> std::result::Result<Reply, (&'static str, String)> {
+    if body.len() > server::MAX_EVENT_PAYLOAD {
+        return Err((
+            "413 Payload Too Large",
+            format!(
+                "Event payload exceeds maximum of {} bytes",
+                server::MAX_EVENT_PAYLOAD
+            ),
+        ));
+    }
+    let mut client = Some(Client {
+        name: WEBHOOK_CLIENT.to_string(),
+        role: Role::Submit,
+    });
+    let mut args = vec!["EVENT.FIRE", token];
+    if !body.is_empty() {
+        args.push(body);
+    }
+    match run(&args, &mut client, acl, settings, db).await {
+        Ok(RespValue::BulkString(job_id)) => Ok(Reply::Json(
+            serde_json::json!({ "job_id": String::from_utf8_lossy(&job_id) }).to_string(),
+        )),
+        Ok(_) => Err((
+            "404 Not Found",
+            format!("No job is waiting for event {}", token),
+        )),
+        Err(e) => Err(error_status(&e)),
+    }
+}
+
+/// Run a command as the connection's client
+async fn run(
+    args: &[&str],
+    client: &mut Option<Client>,
+    acl: &Acl,
+    settings: &Settings,
+    db: &Database,
+) -> Result<RespValue> {
+    let command = RespValue::Array(
+        args.iter()
+            .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec()))
+            .collect(),
+    );
+    server::handle_command(command, client, acl, settings, db).await
+}
+
+/// The endpoint a request target names
+fn parse_route(target: &str) -> Option<Route> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments: Vec<String> = path
+        .trim_end_matches('/')
+        .split('/')
+        .skip(1)
+        .map(percent_decode)
+        .collect::<Option<_>>()?;
+    let mut params: Vec<(String, String)> = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        params.push((percent_decode(name)?, percent_decode(value)?));
+    }
+    let param = |name: &str, default: &str| {
+        params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map_or_else(|| default.to_string(), |(_, value)| value.clone())
+    };
+    let command = |args: &[&str]| {
+        Some(Route::Command(
+            args.iter().map(|arg| arg.to_string()).collect(),
+        ))
+    };
+
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    match segments.as_slice() {
+        ["api", "plans"] => command(&["PLAN.LIST", &param("offset", "0"), &param("limit", "50")]),
+        ["api", "plans", plan_id] => command(&["PLAN.GET", plan_id]),
+        ["api", "plans", plan_id, view @ ("status" | "results")] => {
+            let mut args = vec![format!("PLAN.{}", view.to_uppercase()), plan_id.to_string()];
+            args.extend(
+                params
+                    .iter()
+                    .find(|(name, _)| name == "action_id")
+                    .map(|(_, id)| id.clone()),
+            );
+            Some(Route::Command(args))
+        }
+        ["api", "actions"] => command(&[
+            "ACTION.LIST",
+            &param("status", "all"),
+            &param("offset", "0"),
+            &param("limit", "100"),
+        ]),
+        ["api", "actions", action_id] => command(&["ACTION.GET", action_id]),
+        ["api", "jobs"] => {
+            let mut args = vec!["JOB.LIST".to_string()];
+            for (name, value) in &params {
+                args.extend([name.clone(), value.clone()]);
+            }
+            Some(Route::Command(args))
+        }
+        ["api", "jobs", job_id] => command(&["JOB.GET", job_id]),
+        ["api", "workers"] => command(&["WORKERS.LIST"]),
+        ["api", "queues"] => Some(Route::Queues),
+        ["api", "events"] => Some(Route::Events(param("plan_id", events::ALL_PLANS))),
+        ["api", "events", token] => Some(Route::Fire(token.to_string())),
+        _ => None,
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a URL component
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Key from an `Authorization: Bearer <key>` header
+fn bearer_token(head: &str) -> Option<&str> {
+    head.lines().skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("authorization") {
+            return None;
+        }
+        let (scheme, token) = value.trim().split_once(' ')?;
+        scheme
+            .eq_ignore_ascii_case("bearer")
+            .then(|| token.trim())
+            .filter(|token| !token.is_empty())
+    })
+}
+
+/// HTTP status and message for a command error
+fn error_status(error: &Error) -> (&'static str, String) {
+    let message = match error {
+        Error::InvalidArguments(message) => message.clone(),
+        error => error.to_string(),
+    };
+    let status = match error {
+        Error::NoAuth => "401 Unauthorized",
+        Error::NoPerm { .. } => "403 Forbidden",
+        Error::InvalidArguments(message) if message.contains("not found") => "404 Not Found",
+        Error::InvalidArguments(_) | Error::Protocol(_) => "400 Bad Request",
+        _ => "500 Internal Server Error",
+    };
+    (status, message)
+}
+
+/// A command's reply as JSON
+///
+/// Bulk strings holding a JSON object or array, as most read commands
+/// return, are embedded as JSON rather than as strings.
+fn to_json(value: RespValue) -> serde_json::Value {
+    match value {
+        RespValue::SimpleString(text) => serde_json::Value::String(text),
+        RespValue::Error(message) => serde_json::json!({ "error": message }),
+        RespValue::Integer(n) => n.into(),
+        RespValue::BulkString(data) => {
+            if matches!(data.first(), Some(b'{' | b'[')) {
+                if let Ok(value) = serde_json::from_slice(&data) {
+                    return value;
+                }
+            }
+            serde_json::Value::String(String::from_utf8_lossy(&data).into_owned())
+        }
+        RespValue::Array(items) => items.into_iter().map(to_json).collect(),
+        RespValue::NullBulkString => serde_json::Value::Null,
+        RespValue::Map(entries) => entries
+            .into_iter()
+            .map(|(key, value)| match to_json(key) {
+                serde_json::Value::String(key) => (key, to_json(value)),
+                key => (key.to_string(), to_json(value)),
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+/// Number of jobs in every queue
+fn queue_depths(db: &Database) -> Result<serde_json::Value> {
+    let mut depths = serde_json::Map::new();
+    for base in routing::ready_queues(db)? {
+        for priority in JobPriority::ALL {
+            let queue = priority.queue_name(&base);
+            let depth = db.llen(&queue)? + orchestrator::waiting_turn(db, &queue)?;
+            depths.insert(queue.clone(), depth.into());
+        }
+    }
+    depths.insert(
+        PROCESSING_QUEUE.to_string(),
+        db.llen(PROCESSING_QUEUE)?.into(),
+    );
+    for queue in [
+        DELAYED_QUEUE,
+        THROTTLED_QUEUE,
+        UNSCHEDULABLE_QUEUE,
+        AWAITING_EVENT,
+    ] {
+        depths.insert(queue.to_string(), db.zcard(queue)?.into());
+    }
+    Ok(depths.into())
+}
+
+/// Send job events on a channel as Server-Sent Events until the client
+/// disconnects
+async fn stream_events(
+    mut stream: TcpStream,
+    client: &Client,
+    channel: &str,
+) -> std::io::Result<()> {
+    let mut events = events::subscribe();
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        )
+        .await?;
+    debug!("Dashboard events {} -> {}", channel, client.name);
+
+    loop {
+        match events.recv().await {
+            Ok(event) if event.matches(channel) => {
+                let message = format!("data: {}\n\n", event.to_json());
+                stream.write_all(message.as_bytes()).await?;
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("Dashboard events on {} missed {} events", channel, missed);
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_route() {
+        let command = |args: &[&str]| {
+            Some(Route::Command(
+                args.iter().map(|arg| arg.to_string()).collect(),
+            ))
+        };
+        assert_eq!(
+            parse_route("/api/plans?limit=10"),
+            command(&["PLAN.LIST", "0", "10"])
+        );
+        assert_eq!(
+            parse_route("/api/plans/ocr/status?action_id=a1"),
+            command(&["PLAN.STATUS", "ocr", "a1"])
+        );
+        assert_eq!(
+            parse_route("/api/jobs?plan_id=ocr&status=failed"),
+            command(&["JOB.LIST", "plan_id", "ocr", "status", "failed"])
+        );
+        assert_eq!(
+            parse_route("/api/jobs/job%201/"),
+            command(&["JOB.GET", "job 1"])
+        );
+        assert_eq!(
+            parse_route("/api/events"),
+            Some(Route::Events("*".to_string()))
+        );
+        assert_eq!(
+            parse_route("/api/events/abc123"),
+            Some(Route::Fire("abc123".to_string()))
+        );
+        assert_eq!(parse_route("/api/jobs/a/b"), None);
+        assert_eq!(parse_route("/api/jobs/%zz"), None);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let reply = RespValue::Array(vec![
+            RespValue::BulkString(br#"{"worker_id": "w1"}"#.to_vec()),
+            RespValue::BulkString(b"12".to_vec()),
+            RespValue::NullBulkString,
+        ]);
+        assert_eq!(
+            to_json(reply),
+            serde_json::json!([{"worker_id": "w1"}, "12", null])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        let acl = Acl::with_session_key(b"dashboard_test_key".to_vec());
+        let settings = Settings::default();
+        let request = |line: &str, key: Option<&str>| {
+            let auth = key.map_or_else(String::new, |key| {
+                format!("Authorization: Bearer {}\r\n", key)
+            });
+            format!("{} HTTP/1.1\r\nHost: agq\r\n{}\r\n", line, auth)
+        };
+        let status = |reply: std::result::Result<Reply, (&'static str, String)>| match reply {
+            Ok(Reply::Json(body)) => ("200 OK", body),
+            Ok(Reply::Events(..)) => ("200 OK", String::new()),
+            Err((status, message)) => (status, message),
+        };
+
+        let key = Some("dashboard_test_key");
+        let (code, body) =
+            status(respond(&request("GET /api/workers", key), &acl, &settings, &db).await);
+        assert_eq!((code, body.as_str()), ("200 OK", "[]"));
+        let (code, body) =
+            status(respond(&request("GET /api/queues", key), &acl, &settings, &db).await);
+        assert_eq!(code, "200 OK");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()["queue:processing"],
+            0
+        );
+
+        for (line, key, expected) in [
+            ("GET /api/workers", None, "401 Unauthorized"),
+            ("GET /api/workers", Some("wrong"), "401 Unauthorized"),
+            ("GET /api/jobs/missing", key, "404 Not Found"),
+            ("GET /api/nothing", key, "404 Not Found"),
+            ("GET /api/jobs?bogus=1", key, "400 Bad Request"),
+            ("POST /api/plans", key, "405 Method Not Allowed"),
+            // Webhooks need no key, only a token some job is waiting on
+            ("POST /api/events/nope", None, "404 Not Found"),
+            ("GET /api/events/nope", key, "405 Method Not Allowed"),
+        ] {
+            let (code, _) = status(respond(&request(line, key), &acl, &settings, &db).await);
+            assert_eq!(code, expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let head = "GET /api/workers HTTP/1.1\r\nHost: x\r\nauthorization: Bearer abc123\r\n\r\n";
+        assert_eq!(bearer_token(head), Some("abc123"));
+        assert_eq!(
+            bearer_token("GET / HTTP/1.1\r\nAuthorization: Basic x\r\n\r\n"),
+            None
+        );
+    }
+}
@@ -20,6 +20,22 @@ pub enum Error {
     #[error("Authentication required")]
     NoAuth,
 
+    /// Authenticated client's role does not allow the command
+    #[error("Client {client} may not run {command}")]
+    NoPerm { client: String, command: String },
+
+    /// Client is over its rate limit for a kind of command
+    #[error("Client {client} is over its {command} rate limit")]
+    RateLimited {
+        client: String,
+        command: String,
+        retry_after_ms: u64,
+    },
+
+    /// Write sent to a standby, which only applies its leader's changes
+    #[error("This AGQ is a standby of {leader}")]
+    ReadOnly { leader: String },
+
     /// Invalid command
     #[error("Unknown command: {0}")]
     UnknownCommand(String),
@@ -47,6 +63,19 @@ impl Error {
     pub fn to_resp_error(&self) -> String {
         match self {
             Error::NoAuth => "-ERR NOAUTH Authentication required\r\n".to_string(),
+            Error::NoPerm { client, command } => {
+                format!("-ERR NOPERM client '{client}' may not run '{command}'\r\n")
+            }
+            Error::RateLimited {
+                client,
+                command,
+                retry_after_ms,
+            } => format!(
+                "-ERR RATE_LIMITED client '{client}' is over its '{command}' rate, retry after {retry_after_ms}ms\r\n"
+            ),
+            Error::ReadOnly { leader } => {
+                format!("-ERR READONLY this AGQ is a standby of {leader}\r\n")
+            }
             Error::UnknownCommand(cmd) => format!("-ERR unknown command '{cmd}'\r\n"),
             Error::InvalidArguments(msg) => format!("-ERR {msg}\r\n"),
             Error::Protocol(msg) => format!("-ERR Protocol error: {msg}\r\n"),
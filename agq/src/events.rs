@@ -0,0 +1,238 @@
+//! Job event stream for SUBSCRIBE
+//!
+//! Every job state change is published as an [`Event`]: transitions the
+//! orchestrator makes when it saves a job, and statuses workers report with
+//! `SET job:<id>:status`. When the last job of an Action finishes, an
+//! `action` event carries the Action's overall status. Events are only
+//! delivered to connections subscribed at the time; nothing is stored.
+
+use crate::error::Result;
+use crate::job::Job;
+use crate::storage::{Database, StringOps};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// Events buffered per subscriber before a slow one starts missing events
+const EVENT_BUFFER: usize = 1024;
+
+/// Channel subscribing to the events of every plan
+pub const ALL_PLANS: &str = "*";
+
+static EVENTS: Lazy<broadcast::Sender<Event>> = Lazy::new(|| broadcast::channel(EVENT_BUFFER).0);
+
+/// What an event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// A job changed status
+    Job,
+    /// Every job of an Action finished
+    Action,
+}
+
+/// A job or Action state change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: EventKind,
+    pub plan_id: String,
+    pub action_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    pub status: String,
+    pub timestamp: u64,
+}
+
+impl Event {
+    /// Whether the event belongs on a SUBSCRIBE channel
+    #[must_use]
+    pub fn matches(&self, channel: &str) -> bool {
+        channel == ALL_PLANS || channel == self.plan_id
+    }
+
+    /// Event as a JSON object
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Receive every event published from now on
+pub fn subscribe() -> broadcast::Receiver<Event> {
+    EVENTS.subscribe()
+}
+
+/// Publish a job's new status, and the Action's status once it finishes
+///
+/// Does nothing if no connection is subscribed. Errors reading the Action's
+/// other jobs are logged, since they must not fail the status change.
+pub fn job_changed(db: &Database, job: &Job, status: &str) {
+    if EVENTS.receiver_count() == 0 {
+        return;
+    }
+
+    let timestamp = crate::server::get_current_timestamp_secs().unwrap_or(0);
+    publish(Event {
+        kind: EventKind::Job,
+        plan_id: job.plan_id.clone(),
+        action_id: job.action_id.clone(),
+        job_id: Some(job.id.clone()),
+        status: status.to_string(),
+        timestamp,
+    });
+
+    if !is_finished(status) {
+        return;
+    }
+    match action_status(db, &job.action_id) {
+        Ok(Some(action_status)) => publish(Event {
+            kind: EventKind::Action,
+            plan_id: job.plan_id.clone(),
+            action_id: job.action_id.clone(),
+            job_id: None,
+            status: action_status.to_string(),
+            timestamp,
+        }),
+        Ok(None) => {}
+        Err(e) => warn!("Could not check action {}: {}", job.action_id, e),
+    }
+}
+
+/// Publish the status a worker reported with `SET job:<id>:status`
+///
+/// Keys other than a job status are ignored.
+pub fn observe_set(db: &Database, key: &str, value: &[u8]) {
+    if EVENTS.receiver_count() == 0 {
+        return;
+    }
+    let Some(job_id) = key
+        .strip_prefix("job:")
+        .and_then(|rest| rest.strip_suffix(":status"))
+    else {
+        return;
+    };
+
+    let job = db
+        .get(&format!("job:{}", job_id))
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_slice::<Job>(&json).ok());
+    if let Some(job) = job {
+        job_changed(db, &job, &String::from_utf8_lossy(value));
+    }
+}
+
+fn publish(event: Event) {
+    // Sending only fails when nobody is subscribed
+    let _ = EVENTS.send(event);
+}
+
+/// Overall status of an Action once all its jobs have finished
+fn action_status(db: &Database, action_id: &str) -> Result<Option<&'static str>> {
+    let jobs = crate::server::load_action_jobs(db, action_id)?;
+    let mut statuses = Vec::with_capacity(jobs.len());
+    for job in &jobs {
+        statuses.push(crate::server::reported_job_status(db, job)?);
+    }
+    if !statuses.iter().all(|status| is_finished(status)) {
+        return Ok(None);
+    }
+    let statuses: Vec<&str> = statuses.iter().map(String::as_str).collect();
+    Ok(Some(crate::server::aggregate_plan_status(&statuses)))
+}
+
+fn is_finished(status: &str) -> bool {
+    matches!(
+        status,
+        "completed" | "failed" | "timeout" | "policy_violation" | "cancelled"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::ListOps;
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    fn save_job(db: &Database, id: &str, task_number: u32) {
+        let job = Job::new(
+            id.to_string(),
+            "action_ev".to_string(),
+            "plan_ev".to_string(),
+            task_number,
+            "echo".to_string(),
+            vec![],
+            serde_json::json!({}),
+            vec![],
+        );
+        db.set(&format!("job:{}", id), &serde_json::to_vec(&job).unwrap())
+            .unwrap();
+        db.lpush("action:action_ev:jobs", id.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_worker_statuses_publish_events() {
+        let (db, _temp) = test_db();
+        save_job(&db, "job_ev1", 1);
+        save_job(&db, "job_ev2", 2);
+        let mut events = subscribe();
+
+        db.set("job:job_ev1:status", b"completed").unwrap();
+        observe_set(&db, "job:job_ev1:status", b"completed");
+        db.set("job:job_ev2:status", b"failed").unwrap();
+        observe_set(&db, "job:job_ev2:status", b"failed");
+        observe_set(&db, "job:job_ev2:stdout", b"ignored");
+
+        // Other tests may publish concurrently, so only look at this plan
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if event.plan_id == "plan_ev" {
+                received.push((event.kind, event.job_id, event.status));
+            }
+        }
+        assert_eq!(
+            received,
+            [
+                (
+                    EventKind::Job,
+                    Some("job_ev1".to_string()),
+                    "completed".to_string()
+                ),
+                (
+                    EventKind::Job,
+                    Some("job_ev2".to_string()),
+                    "failed".to_string()
+                ),
+                (EventKind::Action, None, "failed".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_channels() {
+        let event = Event {
+            kind: EventKind::Action,
+            plan_id: "plan_a".to_string(),
+            action_id: "action_a".to_string(),
+            job_id: None,
+            status: "completed".to_string(),
+            timestamp: 1,
+        };
+        assert!(event.matches("plan_a"));
+        assert!(event.matches(ALL_PLANS));
+        assert!(!event.matches("plan_b"));
+        assert_eq!(
+            event.to_json(),
+            r#"{"type":"action","plan_id":"plan_a","action_id":"action_a","status":"completed","timestamp":1}"#
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use agenix_plan::ResourceLimits;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+/// Plans and the scheduling settings their tasks carry into jobs, as
+/// defined by the shared Plan schema
+pub use agenix_plan::{
+    Backoff, BackoffStrategy, FailurePolicy, JobPriority, MapSplit, PipelineStage, Plan, Task,
+};
+
 /// Status of a Job (Task execution)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -15,6 +22,11 @@ pub enum JobStatus {
     Completed,
     /// Execution failed
     Failed,
+    /// Killed by the worker for exceeding its timeout
+    Timeout,
+    /// Rejected by the worker's command policy without running
+    #[serde(rename = "policy_violation")]
+    PolicyViolation,
     /// Cancelled by user or system
     Cancelled,
 }
@@ -23,9 +35,27 @@ impl JobStatus {
     pub fn is_terminal(&self) -> bool {
         matches!(
             self,
-            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+            JobStatus::Completed
+                | JobStatus::Failed
+                | JobStatus::Timeout
+                | JobStatus::PolicyViolation
+                | JobStatus::Cancelled
         )
     }
+
+    /// Status as it appears in job JSON, e.g. `policy_violation`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Ready => "ready",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Timeout => "timeout",
+            JobStatus::PolicyViolation => "policy_violation",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
 }
 
 /// A Job represents a single Task execution unit within the AGQ system.
@@ -52,6 +82,11 @@ pub struct Job {
     /// Arguments for the command
     pub args: Vec<String>,
 
+    /// Commands of a pipeline job (`command` is `pipeline`), run by the
+    /// worker with each stage's stdout piped to the next stage's stdin
+    #[serde(default)]
+    pub stages: Vec<PipelineStage>,
+
     /// Environment variables / Input substitutions
     pub env: serde_json::Value,
 
@@ -61,6 +96,11 @@ pub struct Job {
     /// IDs of jobs that must complete successfully before this job can start
     pub dependencies: HashSet<String>,
 
+    /// Dependencies from the task's `depends_on`, which the job only waits
+    /// for; their output is not piped to its stdin
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub order_only: HashSet<String>,
+
     /// IDs of jobs that depend on this job (reverse dependency graph)
     /// Used for efficient DAG traversal upon completion
     pub dependents: HashSet<String>,
@@ -82,9 +122,89 @@ pub struct Job {
 
     /// Required worker tags (e.g., "gpu", "linux")
     pub tags: Vec<String>,
+
+    /// Scheduling priority
+    #[serde(default)]
+    pub priority: JobPriority,
+
+    /// Execution timeout in seconds, enforced by the worker
+    #[serde(default)]
+    pub timeout_secs: Option<u32>,
+
+    /// Resource limits, which the worker applies within its own defaults
+    #[serde(default, skip_serializing_if = "ResourceLimits::is_unlimited")]
+    pub limits: ResourceLimits,
+
+    /// Files the job writes to its workspace that the worker uploads
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+
+    /// How many times the job may run before it is reported as failed
+    /// (unset means once)
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+
+    /// Failure policy of the plan this job belongs to
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+
+    /// Times the job has failed and been queued again for another attempt
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Delay between attempts
+    #[serde(default)]
+    pub backoff: Backoff,
+
+    /// Times the job was requeued after its worker stopped responding
+    #[serde(default)]
+    pub reclaims: u32,
+
+    /// Seconds to wait once the job could run before queuing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_after_secs: Option<u64>,
+
+    /// Timestamp when a Pending job waiting in `queue:delayed`, for its
+    /// `run_after_secs` or a retry backoff, is queued
+    #[serde(default, alias = "retry_at", skip_serializing_if = "Option::is_none")]
+    pub run_after: Option<u64>,
+
+    /// Hold the job, once it could run, until an external event fires
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub await_event: bool,
+
+    /// Token that fires the event a Pending job is waiting for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_token: Option<String>,
+
+    /// Why the job was cancelled, or failed without a result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+
+    /// Most jobs of this job's Plan that may be queued or running at once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+
+    /// Share of ready queues this job's Plan gets relative to other Plans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+
+    /// How a map job splits its upstream output; instead of running, a map
+    /// job is expanded into one job per item once its upstream job finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<MapSplit>,
+
+    /// Position of an item job among the jobs its map job expanded into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_index: Option<u32>,
+
+    /// Jobs a map job was expanded into, in item order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expanded_into: Vec<String>,
 }
 
 impl Job {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
         action_id: String,
@@ -102,9 +222,11 @@ impl Job {
             task_number,
             command,
             args,
+            stages: Vec::new(),
             env,
             status: JobStatus::Pending,
             dependencies: HashSet::new(),
+            order_only: HashSet::new(),
             dependents: HashSet::new(),
             worker_id: None,
             created_at: crate::server::get_current_timestamp_secs().unwrap_or(0),
@@ -112,24 +234,88 @@ impl Job {
             completed_at: None,
             exit_code: None,
             tags,
+            priority: JobPriority::default(),
+            timeout_secs: None,
+            limits: ResourceLimits::default(),
+            artifacts: Vec::new(),
+            max_attempts: None,
+            on_failure: FailurePolicy::default(),
+            retries: 0,
+            backoff: Backoff::default(),
+            reclaims: 0,
+            run_after_secs: None,
+            run_after: None,
+            await_event: false,
+            event_token: None,
+            cause: None,
+            max_concurrency: None,
+            weight: None,
+            map: None,
+            item_index: None,
+            expanded_into: Vec::new(),
         }
     }
 }
 
-/// Represents a Plan template (Execution Layer 2)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Plan {
-    pub plan_id: String,
-    pub plan_description: Option<String>,
-    pub tasks: Vec<TaskTemplate>,
+/// Environment of a job run for an Action input: the Plan's `env` with
+/// the input's fields over it
+///
+/// Returns `None` if the Plan has an `env` but the input is neither an
+/// object nor null, so there is nothing to merge it into.
+#[must_use]
+pub fn job_env(plan: &Plan, input: &serde_json::Value) -> Option<serde_json::Value> {
+    if plan.env.is_empty() {
+        return Some(input.clone());
+    }
+    let mut env: serde_json::Map<String, serde_json::Value> = plan
+        .env
+        .iter()
+        .map(|(name, value)| (name.clone(), serde_json::Value::from(value.as_str())))
+        .collect();
+    match input {
+        serde_json::Value::Object(fields) => {
+            env.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        serde_json::Value::Null => {}
+        _ => return None,
+    }
+    Some(serde_json::Value::Object(env))
 }
 
-/// Represents a Task definition within a Plan
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TaskTemplate {
-    pub task_number: u32,
-    pub command: String,
-    pub args: Vec<String>,
-    pub input_from_task: Option<u32>,
-    pub timeout_secs: Option<u32>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_env_merges_plan_env_under_input() {
+        let mut plan: Plan = serde_json::from_value(serde_json::json!({
+            "plan_id": "p",
+            "tasks": [{"task_number": 1, "command": "psql"}]
+        }))
+        .unwrap();
+        assert_eq!(
+            job_env(&plan, &serde_json::json!("raw")),
+            Some(serde_json::json!("raw"))
+        );
+
+        plan.env
+            .insert("DB_PASSWORD".to_string(), "secret://db".to_string());
+        plan.env.insert("DB_NAME".to_string(), "prod".to_string());
+        assert_eq!(
+            job_env(
+                &plan,
+                &serde_json::json!({"DB_NAME": "staging", "file": "a.csv"})
+            ),
+            Some(serde_json::json!({
+                "DB_PASSWORD": "secret://db",
+                "DB_NAME": "staging",
+                "file": "a.csv"
+            }))
+        );
+        assert_eq!(
+            job_env(&plan, &serde_json::Value::Null),
+            Some(serde_json::json!({"DB_PASSWORD": "secret://db", "DB_NAME": "prod"}))
+        );
+        assert_eq!(job_env(&plan, &serde_json::json!(["a.csv"])), None);
+    }
 }
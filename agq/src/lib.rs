@@ -3,15 +3,37 @@
 //! A minimal RESP server for handling Job queuing and worker coordination.
 //! AGQ stores Plans, creates Jobs, and dispatches them to workers.
 
+pub mod acl;
+pub mod artifacts;
+pub mod audit;
+pub mod catalog;
+pub mod dashboard;
 pub mod error;
+pub mod events;
 pub mod job;
+pub mod memory;
+pub mod metrics;
 pub mod orchestrator;
+pub mod ratelimit;
+pub mod recovery;
+pub mod replication;
 pub mod resp;
+pub mod retention;
+pub mod routing;
+pub mod schedule;
+pub mod secrets;
 pub mod server;
 pub mod storage;
+pub mod template;
+pub mod toolstats;
 pub mod workers;
 
+pub use acl::Acl;
 pub use error::{Error, Result};
 pub use server::Server;
-pub use storage::Database;
-pub use workers::start_plan_worker;
+pub use storage::{Backend, Database};
+pub use workers::{
+    start_compactor, start_plan_scheduler, start_plan_worker, start_retention_sweeper,
+    start_retry_scheduler, start_snapshotter, start_timeout_enforcer, start_worker_reaper,
+    TimeoutPolicy,
+};
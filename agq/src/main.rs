@@ -2,11 +2,25 @@
 //!
 //! Main entry point for the AGQ server.
 
-use agq::{start_plan_worker, Database, Result, Server};
+use agq::{
+    artifacts::ArtifactStore,
+    dashboard,
+    memory::HighWater,
+    metrics,
+    ratelimit::{Rate, RateLimits},
+    recovery,
+    replication::{self, Replication},
+    retention, server, start_compactor, start_plan_scheduler, start_plan_worker,
+    start_retention_sweeper, start_retry_scheduler, start_snapshotter, start_timeout_enforcer,
+    start_worker_reaper, Acl, Backend, Database, Result, Server, TimeoutPolicy,
+};
 use clap::Parser;
 use ring::rand::{SecureRandom, SystemRandom};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
 use tracing::{error, info, warn};
 
 /// AGQ - Queue Manager for the AGX Agentic Ecosystem
@@ -15,6 +29,9 @@ use tracing::{error, info, warn};
 /// - `AGQ_BIND_ADDR`: Bind address (overridden by --bind)
 /// - `AGQ_SESSION_KEY`: Session key (overridden by --session-key)
 /// - `AGQ_DATA_DIR`: Data directory (overridden by --data-dir)
+/// - `AGQ_ACL_FILE`: Client credentials file (overridden by --acl-file)
+/// - `AGQ_LEADER_KEY`: Key to authenticate to the leader with (overridden
+///   by --leader-key)
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -27,10 +44,166 @@ struct Args {
     #[arg(short, long)]
     session_key: Option<String>,
 
+    /// JSON file of named client keys and their roles
+    /// The session key stays valid as the admin client `default`
+    #[arg(long)]
+    acl_file: Option<PathBuf>,
+
     /// Data directory for database storage
     /// Defaults to ~/.agq/ if not specified
     #[arg(short, long)]
     data_dir: Option<String>,
+
+    /// Address to serve Prometheus metrics on (e.g. 0.0.0.0:9090)
+    #[arg(long)]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Address to serve the read-only dashboard API on (e.g. 127.0.0.1:8080)
+    #[arg(long)]
+    dashboard_addr: Option<SocketAddr>,
+
+    /// Storage backend: a redb file, a SQLite file, or memory only
+    #[arg(long, value_enum, default_value_t = Backend::File)]
+    storage: Backend,
+
+    /// Seconds between database snapshots (0 disables snapshots)
+    #[arg(long, default_value_t = 300)]
+    snapshot_interval: u64,
+
+    /// Number of database snapshots to keep
+    #[arg(long, default_value_t = 3)]
+    snapshot_keep: usize,
+
+    /// Seconds to keep finished jobs before deleting them (0 keeps them)
+    #[arg(long, default_value_t = 0)]
+    job_ttl: u64,
+
+    /// Seconds to keep the results and logs of finished jobs (0 keeps them)
+    #[arg(long, default_value_t = 0)]
+    result_ttl: u64,
+
+    /// Seconds to keep artifacts after they were last stored (0 keeps them)
+    #[arg(long, default_value_t = 0)]
+    artifact_ttl: u64,
+
+    /// Seconds to keep failed plan submissions in the dead-letter queue
+    /// (0 keeps them)
+    #[arg(long, default_value_t = 0)]
+    queue_ttl: u64,
+
+    /// Seconds between retention sweeps
+    #[arg(long, default_value_t = 60)]
+    retention_interval: u64,
+
+    /// Seconds between compactions, which delete expired keys and measure
+    /// storage use (0 disables them)
+    #[arg(long, default_value_t = 300)]
+    compact_interval: u64,
+
+    /// Stored bytes above which AGQ warns that storage is filling up
+    /// (0 disables the warning)
+    #[arg(long, default_value_t = 0)]
+    memory_high_water: u64,
+
+    /// Seconds PLAN.SUBMIT remembers an idempotency key
+    #[arg(long, default_value_t = server::DEFAULT_IDEMPOTENCY_TTL_SECS)]
+    idempotency_ttl: u64,
+
+    /// Directory to store artifacts in (default: in the database)
+    #[arg(long)]
+    artifact_dir: Option<PathBuf>,
+
+    /// Largest job stdout, stderr or result stored inline, in bytes
+    ///
+    /// Larger outputs must be stored with ARTIFACT.PUT and referenced by hash.
+    #[arg(long, default_value_t = server::DEFAULT_MAX_INLINE_RESULT)]
+    max_inline_result: usize,
+
+    /// Seconds a job whose task sets no timeout may run before AGQ takes it
+    /// back from its worker (0 lets such jobs run indefinitely)
+    #[arg(long, default_value_t = 3600)]
+    default_job_timeout: u64,
+
+    /// Seconds past its timeout a job is given to report before AGQ takes it
+    /// back
+    #[arg(long, default_value_t = 60)]
+    timeout_grace: u64,
+
+    /// Plan and Action submissions each client may make, as RATE[/BURST]
+    /// per second, e.g. 5/20 (default: unlimited)
+    #[arg(long)]
+    submit_rate: Option<Rate>,
+
+    /// Job results each client may post, as RATE[/BURST] per second
+    /// (default: unlimited)
+    #[arg(long)]
+    result_rate: Option<Rate>,
+
+    /// Run as a hot standby of the AGQ at this address (format: HOST:PORT),
+    /// refusing writes until promoted
+    #[arg(long)]
+    follow: Option<String>,
+
+    /// Key to authenticate to the leader with, as an admin client
+    /// (hex-encoded, default: this AGQ's session key)
+    #[arg(long)]
+    leader_key: Option<String>,
+
+    /// Seconds a standby waits without contact from its leader before
+    /// promoting itself (0 promotes only on REPLICA.PROMOTE)
+    #[arg(long, default_value_t = 0)]
+    failover_after: u64,
+}
+
+/// Background work only a leader does: running Plans and jobs, enforcing
+/// retention, and compacting storage
+struct LeaderTasks {
+    timeouts: TimeoutPolicy,
+    retention: retention::Policy,
+    retention_interval: u64,
+    artifacts: ArtifactStore,
+    compact_interval: u64,
+    high_water: HighWater,
+}
+
+impl LeaderTasks {
+    fn start(self, db: &Arc<Database>) {
+        let worker_db = Arc::clone(db);
+        tokio::spawn(async move {
+            start_plan_worker(worker_db).await;
+        });
+        let scheduler_db = Arc::clone(db);
+        tokio::spawn(async move {
+            start_retry_scheduler(scheduler_db).await;
+        });
+        let reaper_db = Arc::clone(db);
+        tokio::spawn(async move {
+            start_worker_reaper(reaper_db).await;
+        });
+        let timeout_db = Arc::clone(db);
+        let timeouts = self.timeouts;
+        tokio::spawn(async move {
+            start_timeout_enforcer(timeout_db, timeouts).await;
+        });
+        let plan_scheduler_db = Arc::clone(db);
+        tokio::spawn(async move {
+            start_plan_scheduler(plan_scheduler_db).await;
+        });
+        if self.retention.is_enabled() {
+            let retention_db = Arc::clone(db);
+            let interval = self.retention_interval.max(1);
+            tokio::spawn(async move {
+                start_retention_sweeper(retention_db, self.retention, self.artifacts, interval)
+                    .await;
+            });
+        }
+        if self.compact_interval > 0 {
+            let compactor_db = Arc::clone(db);
+            tokio::spawn(async move {
+                start_compactor(compactor_db, self.high_water, self.compact_interval).await;
+            });
+        }
+    }
 }
 
 #[tokio::main]
@@ -73,16 +246,60 @@ async fn main() -> Result<()> {
     };
 
     // Initialize database
-    let db_path = data_dir.join("data.redb");
-    info!("Initializing database at: {}", db_path.display());
-    let db = Database::open(&db_path)?;
+    let db_path = data_dir.join(args.storage.file_name());
+    let snapshot_dir = data_dir.join("snapshots");
+    // Snapshots in transit to or from a standby; apart from `snapshots`,
+    // which recovery reads
+    let replica_dir = data_dir.join("replica");
+    let db = recovery::open(args.storage, &db_path, &snapshot_dir)?;
     let db_arc = Arc::new(db);
 
-    // Start internal worker threads
-    let worker_db = Arc::clone(&db_arc);
-    tokio::spawn(async move {
-        start_plan_worker(worker_db).await;
-    });
+    if args.snapshot_interval > 0 {
+        let snapshot_db = Arc::clone(&db_arc);
+        let snapshot_dir = snapshot_dir.clone();
+        tokio::spawn(async move {
+            start_snapshotter(
+                snapshot_db,
+                snapshot_dir,
+                args.snapshot_interval,
+                args.snapshot_keep,
+            )
+            .await;
+        });
+    }
+
+    let artifacts = match &args.artifact_dir {
+        Some(dir) => {
+            info!("Storing artifacts in {}", dir.display());
+            ArtifactStore::directory(dir)?
+        }
+        None => ArtifactStore::Database,
+    };
+
+    let ttl = |secs: u64| (secs > 0).then_some(secs);
+    let retention = retention::Policy {
+        job_ttl: ttl(args.job_ttl),
+        result_ttl: ttl(args.result_ttl),
+        artifact_ttl: ttl(args.artifact_ttl),
+        queue_ttl: ttl(args.queue_ttl),
+    };
+    let high_water = HighWater::new(args.memory_high_water);
+    let leader_tasks = LeaderTasks {
+        timeouts: TimeoutPolicy {
+            default_secs: (args.default_job_timeout > 0).then_some(args.default_job_timeout),
+            grace_secs: args.timeout_grace,
+        },
+        retention,
+        retention_interval: args.retention_interval,
+        artifacts: artifacts.clone(),
+        compact_interval: args.compact_interval,
+        high_water: high_water.clone(),
+    };
+
+    if let Some(addr) = args.metrics_addr {
+        let listener = metrics::bind(addr).await?;
+        tokio::spawn(metrics::serve(listener, Arc::clone(&db_arc)));
+    }
 
     // Get or generate session key (CLI overrides env var)
     let session_key = if let Some(key_hex) = args.session_key {
@@ -104,18 +321,119 @@ async fn main() -> Result<()> {
         key
     };
 
+    // A standby follows its leader and leaves running Plans and jobs to it
+    // until promoted; a leader recovers from any crash and starts at once
+    let replication = match args.follow {
+        Some(leader) => {
+            let key = match args
+                .leader_key
+                .or_else(|| std::env::var("AGQ_LEADER_KEY").ok())
+            {
+                Some(key_hex) => parse_hex_key(&key_hex)?,
+                None => session_key.clone(),
+            };
+            let failover_after =
+                (args.failover_after > 0).then(|| Duration::from_secs(args.failover_after));
+            let replication = Replication::standby(leader.clone()).with_staging_dir(&replica_dir);
+            info!("Running as a standby of {}", leader);
+            tokio::spawn(replication::follow(
+                leader,
+                key,
+                Arc::clone(&db_arc),
+                replication.clone(),
+                failover_after,
+            ));
+
+            let promoted = replication.clone();
+            let db = Arc::clone(&db_arc);
+            tokio::spawn(async move {
+                promoted.promoted().await;
+                if let Err(e) = recovery::recover(&db) {
+                    error!("Recovery after promotion failed: {}", e);
+                }
+                leader_tasks.start(&db);
+            });
+            replication
+        }
+        None => {
+            recovery::recover(&db_arc)?;
+            leader_tasks.start(&db_arc);
+            Replication::default().with_staging_dir(&replica_dir)
+        }
+    };
+
+    // Load named client credentials (CLI overrides env var)
+    let mut acl = Acl::with_session_key(session_key);
+    if let Some(path) = args
+        .acl_file
+        .or_else(|| std::env::var_os("AGQ_ACL_FILE").map(PathBuf::from))
+    {
+        acl = acl.load(&path)?;
+        info!("Loaded client credentials from {}", path.display());
+    }
+
     // Create and run server
-    let server = Server::new(&bind_addr, session_key, (*db_arc).clone()).await?;
+    let settings = server::Settings {
+        idempotency_ttl_secs: args.idempotency_ttl,
+        max_inline_result: args.max_inline_result,
+        artifacts,
+        rate_limits: RateLimits::new(args.submit_rate, args.result_rate),
+        replication,
+        memory_high_water: high_water,
+    };
+    if let Some(addr) = args.dashboard_addr {
+        let listener = dashboard::bind(addr).await?;
+        tokio::spawn(dashboard::serve(
+            listener,
+            Arc::new(acl.clone()),
+            Arc::new(settings.clone()),
+            Arc::clone(&db_arc),
+        ));
+    }
+    let server = Server::with_acl(&bind_addr, acl, (*db_arc).clone())
+        .await?
+        .with_settings(settings);
     info!("AGQ server started successfully on {}", bind_addr);
 
-    if let Err(e) = server.run().await {
+    if let Err(e) = server.run_until(shutdown_signal()).await {
         error!("Server error: {}", e);
         return Err(e);
     }
 
+    // Connections are closed; leave the database ready for a restart. An
+    // in-memory database only survives in its snapshot.
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    recovery::mark_clean_shutdown(&db_arc, now)?;
+    if args.snapshot_interval > 0 || args.storage == Backend::Memory {
+        recovery::write_snapshot(&db_arc, &snapshot_dir, args.snapshot_keep, now)?;
+        info!("Wrote final snapshot to {}", snapshot_dir.display());
+    }
+    info!("AGQ stopped");
+
     Ok(())
 }
 
+/// Wait for SIGTERM or Ctrl-C
+async fn shutdown_signal() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Received Ctrl-C"),
+        () = terminate => info!("Received SIGTERM"),
+    }
+}
+
 /// Parse hex-encoded session key
 ///
 /// # Security
@@ -0,0 +1,211 @@
+//! What AGQ's storage is being used for
+//!
+//! [`usage`] walks every stored entry and totals its bytes by namespace:
+//! job records, job results and logs, queues, and the first segment of any
+//! other key (`plan`, `worker`, `audit`, ...). `STATS.MEMORY` reports it,
+//! and the compactor publishes it as metrics after purging expired keys.
+//!
+//! A [`HighWater`] mark warns once when stored bytes rise above it and
+//! again when they fall back below, rather than on every check.
+
+use crate::error::Result;
+use crate::storage::Database;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Namespace of a job's output, logs, and artifacts
+pub const RESULTS: &str = "results";
+
+/// Namespace of job records and the state kept for each job
+pub const JOBS: &str = "jobs";
+
+/// Namespace of ready, delayed, held, and internal queues
+pub const QUEUES: &str = "queues";
+
+/// Per-job keys holding what the job produced, as `job:<id>:<suffix>`
+const RESULT_SUFFIXES: &[&str] = &[
+    "result",
+    "stdout",
+    "stdout_encoding",
+    "stdout_artifact",
+    "stderr",
+    "stderr_encoding",
+    "stderr_artifact",
+    "artifacts",
+    "log",
+];
+
+/// Namespace a stored key counts towards
+///
+/// List elements and hash fields are stored as `<key>:<item>`, so the
+/// namespace is decided by prefix and holds for them too.
+#[must_use]
+pub fn namespace(key: &str) -> &str {
+    if let Some(rest) = key.strip_prefix("job:") {
+        let is_result = rest
+            .split(':')
+            .nth(1)
+            .is_some_and(|suffix| RESULT_SUFFIXES.contains(&suffix));
+        return if is_result { RESULTS } else { JOBS };
+    }
+    if key.starts_with("queue:") || key.starts_with("fair:") || key.starts_with("agq:internal:") {
+        return QUEUES;
+    }
+    match key.split_once(':') {
+        Some((prefix, _)) if !prefix.is_empty() => prefix,
+        _ => "other",
+    }
+}
+
+/// Entries and bytes stored in one namespace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Namespace {
+    /// Stored entries: keys, list elements, hash fields, set members
+    pub entries: u64,
+    /// Bytes taken by their keys and values
+    pub bytes: u64,
+}
+
+/// Storage in use, in bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// Keys and values of every entry
+    pub stored_bytes: u64,
+    /// Pages allocated to the database, including its own metadata
+    pub allocated_bytes: u64,
+    /// Allocated space freed by deletes and not yet reused
+    pub fragmented_bytes: u64,
+    /// Use by namespace
+    pub namespaces: BTreeMap<String, Namespace>,
+}
+
+/// Measure the storage in use
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn usage(db: &Database) -> Result<Usage> {
+    let mut namespaces: BTreeMap<String, Namespace> = BTreeMap::new();
+    db.for_each_entry(|key, bytes| {
+        let name = namespace(key);
+        let used = match namespaces.get_mut(name) {
+            Some(used) => used,
+            None => namespaces.entry(name.to_string()).or_default(),
+        };
+        used.entries += 1;
+        used.bytes += bytes as u64;
+    })?;
+    let stats = db.storage_stats()?;
+    Ok(Usage {
+        stored_bytes: stats.stored_bytes,
+        allocated_bytes: stats.allocated_bytes,
+        fragmented_bytes: stats.fragmented_bytes,
+        namespaces,
+    })
+}
+
+/// Stored bytes above which AGQ warns that storage is filling up
+#[derive(Debug, Clone, Default)]
+pub struct HighWater {
+    /// The mark, or `None` for no warnings
+    limit: Option<u64>,
+    /// Whether the last check was above the mark
+    over: Arc<AtomicBool>,
+}
+
+impl HighWater {
+    /// A mark at `limit` bytes; 0 disables it
+    #[must_use]
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit: (limit > 0).then_some(limit),
+            over: Arc::default(),
+        }
+    }
+
+    /// The mark in bytes, if set
+    #[must_use]
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Whether storage was above the mark when last checked
+    #[must_use]
+    pub fn is_over(&self) -> bool {
+        self.over.load(Ordering::Relaxed)
+    }
+
+    /// Compare usage against the mark, logging when it is crossed
+    ///
+    /// Returns whether usage is above the mark.
+    pub fn check(&self, usage: &Usage) -> bool {
+        let Some(limit) = self.limit else {
+            return false;
+        };
+        let over = usage.stored_bytes > limit;
+        if over != self.over.swap(over, Ordering::Relaxed) {
+            if over {
+                let largest = usage
+                    .namespaces
+                    .iter()
+                    .max_by_key(|(_, used)| used.bytes)
+                    .map_or("none", |(name, _)| name.as_str());
+                warn!(
+                    "Storage is above its high-water mark: {} of {} bytes stored, most in {}",
+                    usage.stored_bytes, limit, largest
+                );
+            } else {
+                info!(
+                    "Storage is back below its high-water mark: {} of {} bytes stored",
+                    usage.stored_bytes, limit
+                );
+            }
+        }
+        over
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ListOps, StringOps};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_usage_by_namespace() {
+        assert_eq!(namespace("job:j1"), JOBS);
+        assert_eq!(namespace("job:j1:status"), JOBS);
+        assert_eq!(namespace("job:j1:stdout"), RESULTS);
+        assert_eq!(namespace("job:j1:log:stderr:0"), RESULTS);
+        assert_eq!(namespace("queue:ready:high"), QUEUES);
+        assert_eq!(namespace("worker:w1"), "worker");
+        assert_eq!(namespace("plain"), "other");
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        db.set("job:j1", b"{}").unwrap();
+        db.set("job:j1:stdout", b"hello").unwrap();
+        db.lpush("queue:ready", b"j1").unwrap();
+
+        let usage = usage(&db).unwrap();
+        assert_eq!(
+            usage.namespaces[RESULTS],
+            Namespace {
+                entries: 1,
+                bytes: 18
+            }
+        );
+        assert_eq!(usage.namespaces[JOBS].entries, 1);
+        // The list's metadata and its one element
+        assert_eq!(usage.namespaces[QUEUES].entries, 2);
+
+        let high_water = HighWater::new(1);
+        assert!(!high_water.is_over());
+        assert!(high_water.check(&usage));
+        assert!(high_water.is_over());
+        assert!(!HighWater::new(0).check(&usage));
+    }
+}
@@ -0,0 +1,395 @@
+//! Prometheus metrics and the `/metrics` HTTP endpoint
+//!
+//! Job events are recorded as workers report them: the claim a worker
+//! writes to `job:<id>:worker` when it takes a job gives the queue wait,
+//! and the result envelope at `job:<id>:result` gives the final status and
+//! execution time. Queue depths, job counts by status, and worker
+//! heartbeats are read from the database on each scrape.
+
+use crate::error::{Error, Result};
+use crate::job::JobPriority;
+use crate::memory::Usage;
+use crate::orchestrator::{
+    self, Orchestrator, AWAITING_EVENT, DELAYED_QUEUE, PROCESSING_QUEUE, THROTTLED_QUEUE,
+    UNSCHEDULABLE_QUEUE,
+};
+use crate::routing;
+use crate::storage::{Database, HashOps, ListOps, SortedSetOps, StringOps};
+use agenix_metrics::{DURATION_BUCKETS, QUEUE_WAIT_BUCKETS};
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::warn;
+
+pub use agenix_metrics::bind;
+
+/// Internal queues of plan submissions
+const INTERNAL_QUEUES: &[&str] = &["agq:internal:plan.submit", "agq:internal:plan.submit:dlq"];
+
+/// Metrics shared by every connection
+pub static METRICS: Lazy<QueueMetrics> =
+    Lazy::new(|| QueueMetrics::new().expect("AGQ metrics must register successfully"));
+
+/// Prometheus metrics describing the queue and its workers
+#[derive(Debug, Clone)]
+pub struct QueueMetrics {
+    registry: Registry,
+    pub jobs_claimed: IntCounter,
+    pub jobs_finished: IntCounterVec,
+    pub queue_wait_seconds: Histogram,
+    pub job_duration_seconds: Histogram,
+    pub queue_depth: IntGaugeVec,
+    pub jobs: IntGaugeVec,
+    pub workers: IntGaugeVec,
+    pub worker_heartbeat_age_seconds: GaugeVec,
+    pub storage_bytes: IntGaugeVec,
+    pub storage_allocated_bytes: IntGauge,
+    pub storage_over_high_water: IntGauge,
+}
+
+impl QueueMetrics {
+    /// Create and register all queue metrics
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a metric cannot be registered
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("agq".to_string()), None).map_err(metric_error)?;
+
+        let jobs_claimed = IntCounter::new("jobs_claimed_total", "Jobs taken by a worker")
+            .map_err(metric_error)?;
+        let jobs_finished = IntCounterVec::new(
+            Opts::new("jobs_finished_total", "Jobs workers reported finished"),
+            &["status"],
+        )
+        .map_err(metric_error)?;
+        let queue_wait_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "queue_wait_seconds",
+                "Time between job creation and pickup in seconds",
+            )
+            .buckets(QUEUE_WAIT_BUCKETS.to_vec()),
+        )
+        .map_err(metric_error)?;
+        let job_duration_seconds = Histogram::with_opts(
+            HistogramOpts::new("job_duration_seconds", "Job execution time in seconds")
+                .buckets(DURATION_BUCKETS.to_vec()),
+        )
+        .map_err(metric_error)?;
+        let queue_depth = IntGaugeVec::new(
+            Opts::new("queue_depth", "Job IDs waiting in each queue"),
+            &["queue"],
+        )
+        .map_err(metric_error)?;
+        let jobs = IntGaugeVec::new(Opts::new("jobs", "Stored jobs by status"), &["status"])
+            .map_err(metric_error)?;
+        let workers = IntGaugeVec::new(
+            Opts::new("workers", "Registered workers by heartbeat state"),
+            &["state"],
+        )
+        .map_err(metric_error)?;
+        let worker_heartbeat_age_seconds = GaugeVec::new(
+            Opts::new(
+                "worker_heartbeat_age_seconds",
+                "Seconds since each worker's last heartbeat",
+            ),
+            &["worker"],
+        )
+        .map_err(metric_error)?;
+        let storage_bytes = IntGaugeVec::new(
+            Opts::new("storage_bytes", "Bytes stored by namespace"),
+            &["namespace"],
+        )
+        .map_err(metric_error)?;
+        let storage_allocated_bytes = IntGauge::new(
+            "storage_allocated_bytes",
+            "Bytes allocated to the database, including free pages",
+        )
+        .map_err(metric_error)?;
+        let storage_over_high_water = IntGauge::new(
+            "storage_over_high_water",
+            "1 if stored bytes are above the high-water mark",
+        )
+        .map_err(metric_error)?;
+
+        registry
+            .register(Box::new(jobs_claimed.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(jobs_finished.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(queue_wait_seconds.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(job_duration_seconds.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(queue_depth.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(jobs.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(workers.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(worker_heartbeat_age_seconds.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(storage_bytes.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(storage_allocated_bytes.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(storage_over_high_water.clone()))
+            .map_err(metric_error)?;
+
+        Ok(Self {
+            registry,
+            jobs_claimed,
+            jobs_finished,
+            queue_wait_seconds,
+            job_duration_seconds,
+            queue_depth,
+            jobs,
+            workers,
+            worker_heartbeat_age_seconds,
+            storage_bytes,
+            storage_allocated_bytes,
+            storage_over_high_water,
+        })
+    }
+
+    /// Record a job event from a key a worker wrote with SET
+    ///
+    /// Keys other than a job claim or result are ignored.
+    pub fn observe_set(&self, db: &Database, key: &str, value: &[u8], now: u64) {
+        let Some(rest) = key.strip_prefix("job:") else {
+            return;
+        };
+
+        if let Some(job_id) = rest.strip_suffix(":worker") {
+            self.jobs_claimed.inc();
+            let created_at = db
+                .get(&format!("job:{}", job_id))
+                .ok()
+                .flatten()
+                .and_then(|json| serde_json::from_slice::<serde_json::Value>(&json).ok())
+                .and_then(|job| job["created_at"].as_u64());
+            if let Some(created_at) = created_at {
+                self.queue_wait_seconds
+                    .observe(now.saturating_sub(created_at) as f64);
+            }
+        } else if rest.ends_with(":result") {
+            let Ok(result) = serde_json::from_slice::<serde_json::Value>(value) else {
+                return;
+            };
+            let status = result["status"].as_str().unwrap_or("unknown");
+            self.jobs_finished.with_label_values(&[status]).inc();
+            if let Some(duration_ms) = result["duration_ms"].as_u64() {
+                self.job_duration_seconds
+                    .observe(duration_ms as f64 / 1000.0);
+            }
+        }
+    }
+
+    /// Refresh the gauges read from the database
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read
+    pub fn collect(&self, db: &Database, now: u64) -> Result<()> {
+        for base in routing::ready_queues(db)? {
+            for priority in JobPriority::ALL {
+                let queue = priority.queue_name(&base);
+                let depth = db.llen(&queue)? + orchestrator::waiting_turn(db, &queue)?;
+                self.set_depth(&queue, depth);
+            }
+        }
+        for queue in INTERNAL_QUEUES.iter().chain([&PROCESSING_QUEUE]) {
+            self.set_depth(queue, db.llen(queue)?);
+        }
+        for queue in [
+            DELAYED_QUEUE,
+            THROTTLED_QUEUE,
+            UNSCHEDULABLE_QUEUE,
+            AWAITING_EVENT,
+        ] {
+            self.set_depth(queue, db.zcard(queue)?);
+        }
+
+        let mut by_status: HashMap<String, i64> = HashMap::new();
+        for job_id in Orchestrator::new(db).job_ids()? {
+            if let Some(status) = job_status(db, &job_id)? {
+                *by_status.entry(status).or_default() += 1;
+            }
+        }
+        self.jobs.reset();
+        for (status, count) in by_status {
+            self.jobs.with_label_values(&[&status]).set(count);
+        }
+
+        let (mut alive, mut expired) = (0, 0);
+        self.worker_heartbeat_age_seconds.reset();
+        for (worker_id, _last_seen) in db.zrange("workers:all", 0, -1)? {
+            let worker_id = String::from_utf8_lossy(&worker_id);
+            let worker_key = format!("worker:{}", worker_id);
+            let field = |name| -> Result<Option<u64>> {
+                Ok(db
+                    .hget(&worker_key, name)?
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .and_then(|s| s.parse().ok()))
+            };
+
+            if field("expire_at")?.is_some_and(|expire_at| now < expire_at) {
+                alive += 1;
+            } else {
+                expired += 1;
+            }
+            if let Some(last_seen) = field("last_seen")? {
+                self.worker_heartbeat_age_seconds
+                    .with_label_values(&[&worker_id])
+                    .set(now.saturating_sub(last_seen) as f64);
+            }
+        }
+        self.workers.with_label_values(&["alive"]).set(alive);
+        self.workers.with_label_values(&["expired"]).set(expired);
+
+        Ok(())
+    }
+
+    /// Record storage use measured by the compactor
+    ///
+    /// Measuring reads every entry, so it is done periodically rather than
+    /// on each scrape.
+    pub fn observe_usage(&self, usage: &Usage, over_high_water: bool) {
+        self.storage_bytes.reset();
+        for (namespace, used) in &usage.namespaces {
+            self.storage_bytes
+                .with_label_values(&[namespace])
+                .set(used.bytes as i64);
+        }
+        self.storage_allocated_bytes
+            .set(usage.allocated_bytes as i64);
+        self.storage_over_high_water.set(i64::from(over_high_water));
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            warn!("Failed to encode metrics: {e}");
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    fn set_depth(&self, queue: &str, depth: u64) {
+        self.queue_depth
+            .with_label_values(&[queue])
+            .set(i64::try_from(depth).unwrap_or(i64::MAX));
+    }
+}
+
+/// Status a worker reported for a job, or else its stored status
+fn job_status(db: &Database, job_id: &str) -> Result<Option<String>> {
+    if let Some(status) = db.get(&format!("job:{}:status", job_id))? {
+        return Ok(Some(String::from_utf8_lossy(&status).into_owned()));
+    }
+    Ok(db
+        .get(&format!("job:{}", job_id))?
+        .and_then(|json| serde_json::from_slice::<serde_json::Value>(&json).ok())
+        .and_then(|job| job["status"].as_str().map(str::to_string)))
+}
+
+fn metric_error(e: prometheus::Error) -> Error {
+    Error::Protocol(format!("Failed to create metrics: {e}"))
+}
+
+/// Serve `GET /metrics` on the listener until the task is dropped
+///
+/// Cluster state is read from the database on each scrape.
+pub async fn serve(listener: TcpListener, db: Arc<Database>) {
+    agenix_metrics::serve(listener, move || {
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        match METRICS.collect(&db, now) {
+            Ok(()) => Some(METRICS.encode()),
+            Err(e) => {
+                warn!("Failed to collect metrics: {e}");
+                None
+            }
+        }
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    #[test]
+    fn test_observe_job_events() {
+        let (db, _temp) = test_db();
+        let metrics = QueueMetrics::new().unwrap();
+        db.set("job:j1", br#"{"created_at": 100}"#).unwrap();
+
+        metrics.observe_set(&db, "job:j1:worker", b"worker-1", 112);
+        metrics.observe_set(
+            &db,
+            "job:j1:result",
+            br#"{"status": "completed", "duration_ms": 2500}"#,
+            115,
+        );
+        metrics.observe_set(&db, "plan:p1", b"{}", 115);
+
+        let text = metrics.encode();
+        assert!(text.contains("agq_jobs_claimed_total 1"));
+        assert!(text.contains("agq_jobs_finished_total{status=\"completed\"} 1"));
+        assert!(text.contains("agq_queue_wait_seconds_sum 12"));
+        assert!(text.contains("agq_job_duration_seconds_sum 2.5"));
+    }
+
+    #[test]
+    fn test_collect_reads_cluster_state() {
+        let (db, _temp) = test_db();
+        let metrics = QueueMetrics::new().unwrap();
+        db.lpush("queue:default", b"j1").unwrap();
+        db.lpush("queue:default:high", b"j2").unwrap();
+        db.zadd("plans:all", 1.0, b"p1").unwrap();
+        db.lpush("plan:p1:actions", b"a1").unwrap();
+        db.lpush("action:a1:jobs", b"j1").unwrap();
+        db.lpush("action:a1:jobs", b"j2").unwrap();
+        db.set("job:j1", br#"{"status": "ready"}"#).unwrap();
+        db.set("job:j2", br#"{"status": "ready"}"#).unwrap();
+        db.set("job:j2:status", b"running").unwrap();
+        db.zadd("workers:all", 90.0, b"w1").unwrap();
+        db.hset("worker:w1", "last_seen", b"90").unwrap();
+        db.hset("worker:w1", "expire_at", b"150").unwrap();
+
+        metrics.collect(&db, 100).unwrap();
+
+        let text = metrics.encode();
+        assert!(text.contains("agq_queue_depth{queue=\"queue:default\"} 1"));
+        assert!(text.contains("agq_queue_depth{queue=\"queue:default:high\"} 1"));
+        assert!(text.contains("agq_queue_depth{queue=\"queue:delayed\"} 0"));
+        assert!(text.contains("agq_jobs{status=\"ready\"} 1"));
+        assert!(text.contains("agq_jobs{status=\"running\"} 1"));
+        assert!(text.contains("agq_workers{state=\"alive\"} 1"));
+        assert!(text.contains("agq_worker_heartbeat_age_seconds{worker=\"w1\"} 10"));
+    }
+}
@@ -1,8 +1,99 @@
 use crate::error::Result;
-use crate::job::{Job, JobStatus};
-use crate::storage::Database;
-use std::collections::HashSet;
+use crate::job::{FailurePolicy, Job, JobStatus, MapSplit};
+use crate::routing;
+use crate::storage::{Database, SortedSetOps};
+use std::collections::{HashMap, HashSet};
 use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Attempts a job gets under the retry failure policy when its task does not
+/// set `max_attempts`
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+
+/// Sorted set of jobs waiting to be queued, for their task's `run_after_secs`
+/// or a retry backoff, scored by when they are due
+pub const DELAYED_QUEUE: &str = "queue:delayed";
+
+/// Sorted set of jobs held until an external event fires for them, scored
+/// by when they started waiting; `event:<token>` names the job a token
+/// releases
+pub const AWAITING_EVENT: &str = "queue:awaiting_event";
+
+/// Queue holding the IDs of jobs that workers have taken but not finished
+pub const PROCESSING_QUEUE: &str = "queue:processing";
+
+/// Sorted set of jobs held back by a concurrency limit, scored by when
+/// they were held
+pub const THROTTLED_QUEUE: &str = "queue:throttled";
+
+/// Hash of queue name to the most jobs it may have queued or running at once
+pub const QUEUE_LIMITS: &str = "queue:limits";
+
+/// Sorted set of jobs no live worker has the tags to run, scored by when
+/// they were held
+pub const UNSCHEDULABLE_QUEUE: &str = "queue:unschedulable";
+
+/// Sorted set of jobs seen in `queue:processing`, scored by when they must
+/// have finished
+pub const RUNNING_JOBS: &str = "jobs:running";
+
+/// Jobs of one Plan a ready queue holds at once, per unit of the Plan's
+/// weight
+pub const FAIR_SHARE: u64 = 10;
+
+/// Sorted set of ready queues with jobs waiting their turn
+pub const FAIR_QUEUES: &str = "fair:queues";
+
+/// Sorted set of every submitted job, scored by creation time
+pub const JOBS_INDEX: &str = "jobs:all";
+
+/// Most jobs a map job may expand into
+pub const MAX_MAP_ITEMS: usize = 1000;
+
+/// Env field a fired event's payload is given to its job in
+pub const EVENT_PAYLOAD: &str = "EVENT_PAYLOAD";
+
+/// Ready queues that always exist, before priority sub-lists
+///
+/// Jobs with other requirements go to `queue:tags:` queues, see
+/// [`routing`].
+pub const READY_QUEUES: &[&str] = &[routing::DEFAULT_QUEUE, routing::GPU_QUEUE];
+
+/// Ready queue a job is pushed to
+///
+/// The queue is chosen by the job's required tags (see [`routing`]).
+/// Non-normal priorities use a sub-list, e.g. `queue:default:high`.
+pub fn ready_queue(job: &Job) -> String {
+    job.priority.queue_name(&base_queue(job))
+}
+
+/// Whether a queue is a ready queue or one of its priority sub-lists
+pub fn is_ready_queue(queue: &str) -> bool {
+    [
+        queue.strip_suffix(":high"),
+        queue.strip_suffix(":low"),
+        Some(queue),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|base| routing::queue_requirements(base).is_some())
+}
+
+/// Jobs waiting their turn for a ready queue, see [`FAIR_SHARE`]
+pub fn waiting_turn(db: &Database, queue: &str) -> Result<u64> {
+    use crate::storage::ListOps;
+
+    let mut waiting = 0;
+    for (plan_id, _weight) in db.zrange(&waiting_key(queue), 0, -1)? {
+        waiting += db.llen(&backlog_key(queue, &String::from_utf8_lossy(&plan_id)))?;
+    }
+    Ok(waiting)
+}
+
+/// Ready queue of a job before its priority sub-list
+fn base_queue(job: &Job) -> String {
+    routing::queue_for(&routing::requirements(&job.tags))
+}
 
 /// Orchestrator manages the lifecycle of Jobs and their dependencies.
 pub struct Orchestrator<'a> {
@@ -17,7 +108,7 @@ impl<'a> Orchestrator<'a> {
     /// Submit a set of jobs (usually from a single Action)
     ///
     /// This function:
-    /// 1. Stores all jobs in the database
+    /// 1. Stores all jobs in the database and indexes them in `jobs:all`
     /// 2. Identifies jobs with no pending dependencies
     /// 3. Moves those ready jobs to the appropriate queues
     pub fn submit_jobs(&self, jobs: Vec<Job>) -> Result<()> {
@@ -26,6 +117,8 @@ impl<'a> Orchestrator<'a> {
         for job in jobs {
             // Store the job
             self.save_job(&job)?;
+            self.db
+                .zadd(JOBS_INDEX, job.created_at as f64, job.id.as_bytes())?;
 
             // Check if ready (no dependencies)
             if job.dependencies.is_empty() {
@@ -35,7 +128,7 @@ impl<'a> Orchestrator<'a> {
 
         // Queue ready jobs
         for job in ready_jobs {
-            self.enqueue_job(&job)?;
+            self.release_job(job)?;
         }
 
         Ok(())
@@ -59,24 +152,659 @@ impl<'a> Orchestrator<'a> {
         Ok(())
     }
 
-    /// Mark a job as failed
+    /// Mark a job as failed and apply its plan's failure policy
+    ///
+    /// A job with attempts left is scheduled to run again after its backoff
+    /// instead. Otherwise the policy decides what happens downstream:
+    ///
+    /// - `cancel`: every pending job downstream of the failed job is
+    ///   cancelled, with the failed job recorded as the cause
+    /// - `continue`: dependents are queued once all their dependencies
+    ///   have finished, whether or not they succeeded
+    /// - `retry`: like `cancel`, but jobs without `max_attempts` get
+    ///   three attempts rather than one
     pub fn fail_job(&self, job_id: &str, exit_code: i32) -> Result<()> {
         let mut job = self.get_job(job_id)?;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+
+        let attempts = max_attempts(&job);
+        if job.retries.saturating_add(1) < attempts {
+            job.exit_code = Some(exit_code);
+            return self.schedule_retry(job, attempts, now);
+        }
 
-        // Update status
-        job.status = JobStatus::Failed;
-        job.completed_at = Some(crate::server::get_current_timestamp_secs().unwrap_or(0));
         job.exit_code = Some(exit_code);
+        self.mark_failed(job, JobStatus::Failed, now)
+    }
+
+    /// Record that a worker has started a job
+    ///
+    /// Returns the job, so the caller can work out when it must finish.
+    pub fn mark_running(&self, job_id: &str, worker_id: Option<String>, now: u64) -> Result<Job> {
+        let mut job = self.get_job(job_id)?;
+        job.status = JobStatus::Running;
+        job.worker_id = worker_id;
+        job.started_at = Some(now);
+        self.save_job(&job)?;
+        Ok(job)
+    }
+
+    /// Cancel a job that has not finished, and every pending job downstream
+    /// of it
+    ///
+    /// A queued or held job is taken off its queue. A running job is taken
+    /// back by revoking its worker's claim, which tells the worker to stop
+    /// it. Returns the number of jobs cancelled: 0 if the job had already
+    /// finished.
+    pub fn cancel_job(&self, job_id: &str, now: u64) -> Result<usize> {
+        use crate::storage::{ListOps, StringOps};
+
+        let mut job = self.get_job(job_id)?;
+        if job.status.is_terminal() || self.db.exists(&format!("job:{}:status", job.id))? {
+            return Ok(0);
+        }
+
+        job.status = JobStatus::Cancelled;
+        job.completed_at = Some(now);
+        job.cause = Some("cancelled by request".to_string());
+        self.save_job(&job)?;
+
+        let id = job.id.as_bytes();
+        for held in [
+            DELAYED_QUEUE,
+            AWAITING_EVENT,
+            THROTTLED_QUEUE,
+            UNSCHEDULABLE_QUEUE,
+            RUNNING_JOBS,
+        ] {
+            self.db.zrem(held, id)?;
+        }
+        let queue = ready_queue(&job);
+        if self.db.lrem(&queue, 0, id)? > 0 {
+            self.job_taken(&queue, &job.id)?;
+        }
+        self.db.lrem(PROCESSING_QUEUE, 0, id)?;
+        self.db.del(&format!("job:{}:worker", job.id))?;
+        // Lets workers waiting on this job as a dependency see it will not run
+        self.db.set(
+            &format!("job:{}:status", job.id),
+            JobStatus::Cancelled.as_str().as_bytes(),
+        )?;
+
+        let cause = format!("upstream job {} was cancelled", job.id);
+        let cancelled = 1 + self.cancel_dependents(&job, &cause)?;
+        info!("Cancelled job {} ({} jobs in all)", job.id, cancelled);
+        Ok(cancelled)
+    }
+
+    /// Cancel every unfinished job of every Action of a Plan
+    ///
+    /// Returns the number of jobs cancelled.
+    pub fn cancel_plan(&self, plan_id: &str, now: u64) -> Result<usize> {
+        use crate::storage::ListOps;
+
+        let mut cancelled = 0;
+        for action_id in self.db.lrange(&format!("plan:{}:actions", plan_id), 0, -1)? {
+            let action_id = String::from_utf8_lossy(&action_id);
+            for job_id in self.db.lrange(&format!("action:{}:jobs", action_id), 0, -1)? {
+                match self.cancel_job(&String::from_utf8_lossy(&job_id), now) {
+                    Ok(count) => cancelled += count,
+                    Err(e) => warn!("Could not cancel a job of plan {}: {}", plan_id, e),
+                }
+            }
+        }
+        Ok(cancelled)
+    }
+
+    /// Take back a job that ran past its timeout without a result
+    ///
+    /// The job counts as a failed attempt: with attempts left it is
+    /// scheduled to run again, otherwise it ends as `timeout` and its plan's
+    /// failure policy applies. Returns whether the job will run again.
+    pub fn time_out_job(&self, job_id: &str, now: u64) -> Result<bool> {
+        let mut job = self.get_job(job_id)?;
+        let ran_for = now.saturating_sub(job.started_at.unwrap_or(now));
+        job.cause = Some(format!("no result {}s after starting", ran_for));
+
+        let attempts = max_attempts(&job);
+        if job.retries.saturating_add(1) < attempts {
+            self.schedule_retry(job, attempts, now)?;
+            return Ok(true);
+        }
+
+        self.mark_failed(job, JobStatus::Timeout, now)?;
+        Ok(false)
+    }
+
+    /// Requeue a job whose worker stopped sending heartbeats mid-run
+    ///
+    /// After `max_reclaims` requeues the job is failed instead, so a job
+    /// that keeps crashing its workers cannot take down the whole fleet.
+    /// Returns whether the job was requeued.
+    pub fn reclaim_job(&self, job_id: &str, max_reclaims: u32) -> Result<bool> {
+        let mut job = self.get_job(job_id)?;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+
+        if job.reclaims >= max_reclaims {
+            job.cause = Some(format!("worker lost {} times", job.reclaims + 1));
+            self.mark_failed(job, JobStatus::Failed, now)?;
+            return Ok(false);
+        }
+
+        job.reclaims += 1;
+        job.worker_id = None;
+        job.started_at = None;
+        warn!(
+            "Job {} lost its worker, requeuing ({}/{})",
+            job_id, job.reclaims, max_reclaims
+        );
+        self.enqueue_job(&job)?;
+        Ok(true)
+    }
+
+    /// Record a job as failed for good, with `status` saying how, and apply
+    /// its plan's failure policy
+    fn mark_failed(&self, mut job: Job, status: JobStatus, now: u64) -> Result<()> {
+        job.status = status;
+        job.completed_at = Some(now);
+        self.save_job(&job)?;
+
+        warn!("Job {} failed ({})", job.id, status.as_str());
+
+        match job.on_failure {
+            FailurePolicy::Continue => self.trigger_dependents(&job),
+            FailurePolicy::Cancel | FailurePolicy::Retry => {
+                let cause = format!("upstream job {} failed", job.id);
+                let cancelled = self.cancel_dependents(&job, &cause)?;
+                if cancelled > 0 {
+                    info!("Cancelled {} jobs downstream of {}", cancelled, job.id);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Queue a failed job again once its backoff delay has passed
+    fn schedule_retry(&self, mut job: Job, attempts: u32, now: u64) -> Result<()> {
+        job.retries += 1;
+        job.worker_id = None;
+        job.started_at = None;
+
+        let delay = job.backoff.delay(job.retries);
+        warn!(
+            "Job {} failed, retrying in {}s ({}/{})",
+            job.id,
+            delay,
+            job.retries + 1,
+            attempts
+        );
+
+        if delay == 0 {
+            return self.enqueue_job(&job);
+        }
+
+        self.delay_job(job, now.saturating_add(delay))
+    }
+
+    /// Queue a job that could now run, unless its task asks to wait first
+    fn release_job(&self, job: Job) -> Result<()> {
+        if job.await_event {
+            return self.await_event(job);
+        }
+        self.run_when_due(job)
+    }
+
+    /// Queue a job, or delay it by its `run_after_secs`
+    fn run_when_due(&self, job: Job) -> Result<()> {
+        match job.run_after_secs.filter(|&secs| secs > 0) {
+            Some(secs) => {
+                let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+                debug!("Delaying job {} by {}s", job.id, secs);
+                self.delay_job(job, now.saturating_add(secs))
+            }
+            None => self.enqueue_job(&job),
+        }
+    }
+
+    /// Hold a job as Pending in `queue:awaiting_event` until its event fires
+    fn await_event(&self, mut job: Job) -> Result<()> {
+        use crate::storage::StringOps;
+
+        let token = Uuid::new_v4().simple().to_string();
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        job.status = JobStatus::Pending;
+        job.event_token = Some(token.clone());
         self.save_job(&job)?;
+        self.db.set(&event_key(&token), job.id.as_bytes())?;
+        self.db
+            .zadd(AWAITING_EVENT, now as f64, job.id.as_bytes())?;
+        info!("Job {} is waiting for event {}", job.id, token);
+        Ok(())
+    }
+
+    /// Release the job waiting for the event a token names
+    ///
+    /// A payload is added to the job's env as `EVENT_PAYLOAD`. The job is
+    /// then queued, or delayed by its `run_after_secs`. Returns the job's
+    /// ID, or `None` if no job is waiting for the event.
+    pub fn fire_event(&self, token: &str, payload: Option<&str>) -> Result<Option<String>> {
+        use crate::storage::StringOps;
+
+        let key = event_key(token);
+        let Some(job_id) = self.db.get(&key)? else {
+            return Ok(None);
+        };
+        self.db.del(&key)?;
+        self.db.zrem(AWAITING_EVENT, &job_id)?;
+        let mut job = match self.get_job(&String::from_utf8_lossy(&job_id)) {
+            Ok(job) => job,
+            // Jobs removed while waiting are gone
+            Err(_) => return Ok(None),
+        };
+
+        // Jobs cancelled while waiting stay cancelled
+        if job.status != JobStatus::Pending || job.event_token.take().is_none() {
+            return Ok(None);
+        }
+        if let Some(payload) = payload {
+            let payload = serde_json::Value::from(payload);
+            match &mut job.env {
+                serde_json::Value::Object(fields) => {
+                    fields.insert(EVENT_PAYLOAD.to_string(), payload);
+                }
+                env @ serde_json::Value::Null => {
+                    *env = serde_json::json!({ EVENT_PAYLOAD: payload });
+                }
+                _ => warn!("Job {} has no env object for its event payload", job.id),
+            }
+        }
+        info!("Event fired for job {}", job.id);
+
+        let job_id = job.id.clone();
+        self.run_when_due(job)?;
+        Ok(Some(job_id))
+    }
+
+    /// Hold a job as Pending in `queue:delayed` until `due`
+    fn delay_job(&self, mut job: Job, due: u64) -> Result<()> {
+        job.status = JobStatus::Pending;
+        job.run_after = Some(due);
+        self.save_job(&job)?;
+        self.db.zadd(DELAYED_QUEUE, due as f64, job.id.as_bytes())?;
+        Ok(())
+    }
+
+    /// Queue every delayed job that is due
+    ///
+    /// Returns the number of jobs queued.
+    pub fn promote_delayed(&self, now: u64) -> Result<usize> {
+        let mut promoted = 0;
+        for (member, _due) in self.db.zrangebyscore(DELAYED_QUEUE, 0.0, now as f64)? {
+            self.db.zrem(DELAYED_QUEUE, &member)?;
+            let job_id = String::from_utf8_lossy(&member);
+            let mut job = self.get_job(&job_id)?;
+
+            // Jobs cancelled while waiting stay cancelled
+            if job.status != JobStatus::Pending || job.run_after.take().is_none() {
+                continue;
+            }
+            self.enqueue_job(&job)?;
+            promoted += 1;
+        }
+        Ok(promoted)
+    }
+
+    /// Queue jobs held back by a concurrency limit that now has room
+    ///
+    /// Jobs are released oldest first. Each active set is counted once per
+    /// pass and the count kept up to date as jobs are released, so a full
+    /// limit costs a single scan however many jobs it holds back. Returns
+    /// the number of jobs queued.
+    pub fn promote_throttled(&self) -> Result<usize> {
+        let mut active: HashMap<String, u64> = HashMap::new();
+        let mut promoted = 0;
+        'held: for (member, _held_at) in self.db.zrange(THROTTLED_QUEUE, 0, -1)? {
+            let job_id = String::from_utf8_lossy(&member);
+            let job = match self.get_job(&job_id) {
+                Ok(job) if job.status == JobStatus::Pending => job,
+                // Jobs cancelled or removed while held are dropped
+                _ => {
+                    self.db.zrem(THROTTLED_QUEUE, &member)?;
+                    continue;
+                }
+            };
+
+            let limits = self.concurrency_limits(&job)?;
+            for (_, key, limit) in &limits {
+                let count = match active.get(key) {
+                    Some(&count) => count,
+                    None => {
+                        let count = self.count_active(key, &job.id)?;
+                        active.insert(key.clone(), count);
+                        count
+                    }
+                };
+                if count >= u64::from(*limit) {
+                    continue 'held;
+                }
+            }
 
-        warn!("Job {} failed", job_id);
+            self.enqueue_job(&job)?;
+            promoted += 1;
+            for (_, key, _) in limits {
+                *active.entry(key).or_default() += 1;
+            }
+        }
+        Ok(promoted)
+    }
+
+    /// Queue held jobs that a live worker now has the tags to run
+    ///
+    /// Returns the number of jobs released. A released job may still be
+    /// held back by a concurrency limit.
+    pub fn promote_unschedulable(&self, now: u64) -> Result<usize> {
+        let mut satisfiable: HashMap<String, bool> = HashMap::new();
+        let mut promoted = 0;
+        for (member, _held_at) in self.db.zrange(UNSCHEDULABLE_QUEUE, 0, -1)? {
+            let job_id = String::from_utf8_lossy(&member);
+            let job = match self.get_job(&job_id) {
+                Ok(job) if job.status == JobStatus::Pending => job,
+                // Jobs cancelled or removed while held are dropped
+                _ => {
+                    self.db.zrem(UNSCHEDULABLE_QUEUE, &member)?;
+                    continue;
+                }
+            };
+            let queue = base_queue(&job);
+            let ok = match satisfiable.get(&queue) {
+                Some(&ok) => ok,
+                None => {
+                    let requirements = routing::requirements(&job.tags);
+                    let ok = routing::is_satisfiable(self.db, &requirements, now)?;
+                    satisfiable.insert(queue, ok);
+                    ok
+                }
+            };
+            if ok {
+                self.enqueue_job(&job)?;
+                promoted += 1;
+            }
+        }
+        Ok(promoted)
+    }
 
-        // TODO: Handle failure propagation (cancel dependents?)
-        // For now, dependents will just stay pending forever (or until timeout)
+    /// Record that a worker took a job from a ready queue
+    ///
+    /// The next job of the same Plan waiting its turn for the queue, if
+    /// any, is moved onto it.
+    pub fn job_taken(&self, queue: &str, job_id: &str) -> Result<()> {
+        use crate::storage::HashOps;
 
+        let Ok(job) = self.get_job(job_id) else {
+            return Ok(());
+        };
+        let queued = queued_key(queue);
+        if self.db.hincrby(&queued, &job.plan_id, -1)? <= 0 {
+            self.db.hdel(&queued, &job.plan_id)?;
+        }
+        self.release_turns(queue, &job.plan_id)?;
         Ok(())
     }
 
+    /// Recount the jobs each Plan has in ready queues that have jobs
+    /// waiting their turn, and move waiting jobs onto any room
+    ///
+    /// This corrects the counts for jobs that left a queue other than by
+    /// a worker taking them. Returns the number of jobs queued.
+    pub fn promote_fair(&self) -> Result<usize> {
+        use crate::storage::{HashOps, ListOps};
+
+        let mut promoted = 0;
+        for (queue, _) in self.db.zrange(FAIR_QUEUES, 0, -1)? {
+            let queue = String::from_utf8_lossy(&queue).into_owned();
+            let mut counts: HashMap<String, u64> = HashMap::new();
+            for job_id in self.db.lrange(&queue, 0, -1)? {
+                if let Ok(job) = self.get_job(&String::from_utf8_lossy(&job_id)) {
+                    *counts.entry(job.plan_id).or_default() += 1;
+                }
+            }
+            let queued = queued_key(&queue);
+            for (plan_id, _count) in self.db.hgetall(&queued)? {
+                if !counts.contains_key(&plan_id) {
+                    self.db.hdel(&queued, &plan_id)?;
+                }
+            }
+            for (plan_id, count) in &counts {
+                self.db
+                    .hset(&queued, plan_id, count.to_string().as_bytes())?;
+            }
+
+            let waiting = waiting_key(&queue);
+            for (plan_id, _weight) in self.db.zrange(&waiting, 0, -1)? {
+                promoted += self.release_turns(&queue, &String::from_utf8_lossy(&plan_id))?;
+            }
+            if self.db.zcard(&waiting)? == 0 {
+                self.db.zrem(FAIR_QUEUES, queue.as_bytes())?;
+            }
+        }
+        Ok(promoted)
+    }
+
+    /// Set or remove the most jobs a ready queue may have queued or running
+    ///
+    /// A limit of 0 removes it. Jobs already queued when a limit is set do
+    /// not count against it.
+    pub fn set_queue_limit(&self, queue: &str, limit: u32) -> Result<()> {
+        use crate::storage::HashOps;
+
+        if limit == 0 {
+            // The queue's active set is left to be pruned as its jobs finish
+            self.db.hdel(QUEUE_LIMITS, queue)?;
+        } else {
+            self.db
+                .hset(QUEUE_LIMITS, queue, limit.to_string().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Most jobs a ready queue may have queued or running, if limited
+    pub fn queue_limit(&self, queue: &str) -> Result<Option<u32>> {
+        use crate::storage::HashOps;
+
+        Ok(self
+            .db
+            .hget(QUEUE_LIMITS, queue)?
+            .and_then(|limit| String::from_utf8_lossy(&limit).parse().ok()))
+    }
+
+    /// Name of the first concurrency limit the job would exceed, if any
+    fn concurrency_limit(&self, job: &Job) -> Result<Option<String>> {
+        for (name, key, limit) in self.concurrency_limits(job)? {
+            if self.count_active(&key, &job.id)? >= u64::from(limit) {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The job's Plan limit and its queue's limit, as name, active set, and
+    /// most jobs allowed
+    fn concurrency_limits(&self, job: &Job) -> Result<Vec<(String, String, u32)>> {
+        let mut limits = Vec::new();
+        if let Some(limit) = job.max_concurrency {
+            limits.push((
+                format!("plan {}", job.plan_id),
+                active_key(&format!("plan:{}", job.plan_id)),
+                limit,
+            ));
+        }
+        let queue = base_queue(job);
+        if let Some(limit) = self.queue_limit(&queue)? {
+            let key = active_key(&queue);
+            limits.push((queue, key, limit));
+        }
+        Ok(limits)
+    }
+
+    /// Jobs in an active set other than `job_id` that are still queued or
+    /// running, dropping those that have finished
+    fn count_active(&self, key: &str, job_id: &str) -> Result<u64> {
+        use crate::storage::StringOps;
+
+        let mut active = 0;
+        for (member, _queued_at) in self.db.zrange(key, 0, -1)? {
+            if member == job_id.as_bytes() {
+                continue;
+            }
+            let id = String::from_utf8_lossy(&member);
+            let reported = self
+                .db
+                .get(&format!("job:{}:status", id))?
+                .and_then(|status| {
+                    serde_json::from_value::<JobStatus>(serde_json::Value::String(
+                        String::from_utf8_lossy(&status).into_owned(),
+                    ))
+                    .ok()
+                });
+            let status = match reported {
+                Some(status) => Some(status),
+                None => self.get_job(&id).ok().map(|job| job.status),
+            };
+            match status {
+                Some(JobStatus::Ready | JobStatus::Running) => active += 1,
+                _ => {
+                    self.db.zrem(key, &member)?;
+                }
+            }
+        }
+        Ok(active)
+    }
+
+    /// Record a queued job against the limits that apply to it
+    fn track_active(&self, job: &Job, now: u64) -> Result<()> {
+        if job.max_concurrency.is_some() {
+            let key = active_key(&format!("plan:{}", job.plan_id));
+            self.db.zadd(&key, now as f64, job.id.as_bytes())?;
+        }
+        let queue = base_queue(job);
+        if self.queue_limit(&queue)?.is_some() {
+            self.db
+                .zadd(&active_key(&queue), now as f64, job.id.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Hold a job back if its Plan already has its share of a ready queue
+    ///
+    /// A Plan may have [`FAIR_SHARE`] jobs per unit of its weight in a ready
+    /// queue at once. The rest wait in order in the Plan's backlog for the
+    /// queue, and move onto it as workers take the Plan's jobs, so a large
+    /// Plan never fills a queue ahead of a small one: Plans sharing a queue
+    /// take turns, in proportion to their weights.
+    ///
+    /// Returns whether the job was held.
+    fn hold_for_turn(&self, job: &Job, queue: &str) -> Result<bool> {
+        use crate::storage::{HashOps, ListOps};
+
+        let backlog = backlog_key(queue, &job.plan_id);
+        let weight = job.weight.unwrap_or(1).max(1);
+        if self.db.llen(&backlog)? == 0
+            && self.queued_for(queue, &job.plan_id)? < fair_share(weight)
+        {
+            self.db.hincrby(&queued_key(queue), &job.plan_id, 1)?;
+            return Ok(false);
+        }
+
+        self.db.lpush(&backlog, job.id.as_bytes())?;
+        self.db.zadd(
+            &waiting_key(queue),
+            f64::from(weight),
+            job.plan_id.as_bytes(),
+        )?;
+        self.db.zadd(FAIR_QUEUES, 0.0, queue.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Move a Plan's jobs waiting their turn onto a ready queue until it
+    /// has its share of the queue
+    ///
+    /// Returns the number of jobs moved.
+    fn release_turns(&self, queue: &str, plan_id: &str) -> Result<usize> {
+        use crate::storage::{HashOps, ListOps};
+
+        let waiting = waiting_key(queue);
+        let Some(weight) = self.db.zscore(&waiting, plan_id.as_bytes())? else {
+            return Ok(0);
+        };
+        let backlog = backlog_key(queue, plan_id);
+        let mut released = 0;
+        while self.queued_for(queue, plan_id)? < fair_share(weight as u32) {
+            let Some(job_id) = self.db.rpop(&backlog)? else {
+                self.db.zrem(&waiting, plan_id.as_bytes())?;
+                // A job held since the backlog was found empty must not be
+                // left without its Plan in the waiting set
+                if self.db.llen(&backlog)? > 0 {
+                    self.db.zadd(&waiting, weight, plan_id.as_bytes())?;
+                }
+                break;
+            };
+            // Jobs cancelled or removed while waiting are dropped
+            match self.get_job(&String::from_utf8_lossy(&job_id)) {
+                Ok(job) if job.status == JobStatus::Ready => {}
+                _ => continue,
+            }
+            self.db.lpush(queue, &job_id)?;
+            self.db.hincrby(&queued_key(queue), plan_id, 1)?;
+            released += 1;
+        }
+        Ok(released)
+    }
+
+    /// Jobs of a Plan counted as in a ready queue
+    fn queued_for(&self, queue: &str, plan_id: &str) -> Result<u64> {
+        use crate::storage::HashOps;
+
+        Ok(self
+            .db
+            .hget(&queued_key(queue), plan_id)?
+            .and_then(|count| String::from_utf8_lossy(&count).parse().ok())
+            .unwrap_or(0))
+    }
+
+    /// Cancel every pending job downstream of a job that failed or was
+    /// cancelled, with `cause` recorded as the reason
+    ///
+    /// Returns the number of jobs cancelled.
+    fn cancel_dependents(&self, upstream: &Job, cause: &str) -> Result<usize> {
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+
+        let mut visited = HashSet::new();
+        let mut frontier: Vec<String> = upstream.dependents.iter().cloned().collect();
+        let mut cancelled = 0;
+
+        while let Some(dependent_id) = frontier.pop() {
+            if !visited.insert(dependent_id.clone()) {
+                continue;
+            }
+
+            let mut dependent = self.get_job(&dependent_id)?;
+            if dependent.status != JobStatus::Pending {
+                continue;
+            }
+
+            dependent.status = JobStatus::Cancelled;
+            dependent.completed_at = Some(now);
+            dependent.cause = Some(cause.to_string());
+            self.save_job(&dependent)?;
+            cancelled += 1;
+
+            debug!("Cancelled job {}: {}", dependent.id, cause);
+            frontier.extend(dependent.dependents.iter().cloned());
+        }
+
+        Ok(cancelled)
+    }
+
     /// Check dependents and enqueue them if all their dependencies are met
     fn trigger_dependents(&self, completed_job: &Job) -> Result<()> {
         for dependent_id in &completed_job.dependents {
@@ -89,20 +817,159 @@ impl<'a> Orchestrator<'a> {
             // Check if ALL dependencies are completed
             let all_met = self.check_dependencies_met(&dependent)?;
 
-            if all_met {
+            if all_met && dependent.map.is_some() {
+                // A map job splits its input, which may have finished
+                // before a job it only waits for
+                let input = match dependent
+                    .dependencies
+                    .iter()
+                    .find(|id| !dependent.order_only.contains(*id))
+                {
+                    Some(id) if dependent.order_only.contains(&completed_job.id) => {
+                        self.get_job(id)?
+                    }
+                    _ => completed_job.clone(),
+                };
+                self.expand_map_job(dependent, &input)?;
+            } else if all_met {
                 debug!("All dependencies met for job {}, queuing", dependent.id);
-                self.enqueue_job(&dependent)?;
+                self.release_job(dependent)?;
             }
         }
 
         Ok(())
     }
 
+    /// Replace a map job by one job per item of its upstream job's output
+    ///
+    /// Each item job is a copy of the map job with the item in its env as
+    /// `item`, and its position as `item_index`, so commands can refer to
+    /// `${env.item}`. Jobs downstream of the map job wait for every item
+    /// job instead; with no items they are queued straight away. The map
+    /// job itself is recorded as completed, listing the jobs it became. A
+    /// map job whose input cannot be split fails.
+    fn expand_map_job(&self, mut map_job: Job, upstream: &Job) -> Result<()> {
+        use crate::storage::{HashOps, ListOps};
+
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        let split = map_job.map.unwrap_or(MapSplit::Lines);
+        let items = match self.inline_stdout(&upstream.id)? {
+            Some(output) => split.split(&output).and_then(|items| {
+                if items.len() > MAX_MAP_ITEMS {
+                    Err(format!(
+                        "{} items exceeds the maximum of {}",
+                        items.len(),
+                        MAX_MAP_ITEMS
+                    ))
+                } else {
+                    Ok(items)
+                }
+            }),
+            None => Err(format!(
+                "output of upstream job {} is binary or too large to split",
+                upstream.id
+            )),
+        };
+        let items = match items {
+            Ok(items) => items,
+            Err(cause) => {
+                warn!("Map job {} cannot split its input: {}", map_job.id, cause);
+                map_job.cause = Some(cause);
+                return self.mark_failed(map_job, JobStatus::Failed, now);
+            }
+        };
+
+        let mut item_jobs = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let mut env = match &map_job.env {
+                serde_json::Value::Object(fields) => fields.clone(),
+                _ => serde_json::Map::new(),
+            };
+            env.insert("item".to_string(), serde_json::Value::String(item));
+            env.insert("item_index".to_string(), index.into());
+
+            let mut job = map_job.clone();
+            job.id = format!("job_{}", Uuid::new_v4().simple());
+            job.env = serde_json::Value::Object(env);
+            job.map = None;
+            job.item_index = Some(index as u32);
+            // The item replaces the upstream output as the job's input
+            job.dependencies.clear();
+            job.order_only.clear();
+            job.created_at = now;
+            item_jobs.push(job);
+        }
+        let item_ids: Vec<String> = item_jobs.iter().map(|job| job.id.clone()).collect();
+
+        for dependent_id in &map_job.dependents {
+            let mut dependent = self.get_job(dependent_id)?;
+            dependent.dependencies.remove(&map_job.id);
+            dependent.dependencies.extend(item_ids.iter().cloned());
+            if dependent.order_only.remove(&map_job.id) {
+                dependent.order_only.extend(item_ids.iter().cloned());
+            }
+            self.save_job(&dependent)?;
+        }
+
+        let action_key = format!("action:{}", map_job.action_id);
+        for item_id in &item_ids {
+            self.db
+                .lpush(&format!("{}:jobs", action_key), item_id.as_bytes())?;
+        }
+        self.db
+            .hincrby(&action_key, "jobs_created", item_ids.len() as i64)?;
+        self.db.hincrby(
+            &format!("plan:{}:stats", map_job.plan_id),
+            "total_jobs",
+            item_ids.len() as i64,
+        )?;
+
+        map_job.status = JobStatus::Completed;
+        map_job.completed_at = Some(now);
+        map_job.exit_code = Some(0);
+        map_job.expanded_into = item_ids;
+        self.save_job(&map_job)?;
+        info!(
+            "Map job {} expanded into {} jobs",
+            map_job.id,
+            map_job.expanded_into.len()
+        );
+
+        if item_jobs.is_empty() {
+            return self.trigger_dependents(&map_job);
+        }
+        self.submit_jobs(item_jobs)
+    }
+
+    /// Stdout of a finished job, if it was stored inline as text
+    fn inline_stdout(&self, job_id: &str) -> Result<Option<String>> {
+        use crate::storage::StringOps;
+
+        if self.db.exists(&format!("job:{}:stdout_artifact", job_id))? {
+            return Ok(None);
+        }
+        let encoding = self.db.get(&format!("job:{}:stdout_encoding", job_id))?;
+        if encoding.as_deref() == Some(b"base64") {
+            return Ok(None);
+        }
+        let output = self
+            .db
+            .get(&format!("job:{}:stdout", job_id))?
+            .unwrap_or_default();
+        Ok(Some(String::from_utf8_lossy(&output).into_owned()))
+    }
+
     /// Check if all dependencies for a job are in Completed state
+    ///
+    /// Under the `continue` failure policy, any finished dependency counts.
     fn check_dependencies_met(&self, job: &Job) -> Result<bool> {
         for dep_id in &job.dependencies {
             let dep = self.get_job(dep_id)?;
-            if dep.status != JobStatus::Completed {
+            let met = match job.on_failure {
+                FailurePolicy::Continue => dep.status.is_terminal(),
+                FailurePolicy::Cancel | FailurePolicy::Retry => dep.status == JobStatus::Completed,
+            };
+            if !met {
                 return Ok(false);
             }
         }
@@ -112,30 +979,167 @@ impl<'a> Orchestrator<'a> {
     /// Move a job to the Ready state and push to the appropriate queue
     fn enqueue_job(&self, job: &Job) -> Result<()> {
         let mut job = job.clone();
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+
+        // Jobs no live worker can run wait as Pending until one registers
+        let requirements = routing::requirements(&job.tags);
+        if !routing::is_satisfiable(self.db, &requirements, now)? {
+            if job.status != JobStatus::Pending {
+                job.status = JobStatus::Pending;
+                self.save_job(&job)?;
+            }
+            if self
+                .db
+                .zscore(UNSCHEDULABLE_QUEUE, job.id.as_bytes())?
+                .is_none()
+            {
+                self.db
+                    .zadd(UNSCHEDULABLE_QUEUE, now as f64, job.id.as_bytes())?;
+                warn!(
+                    "Holding job {}: no eligible worker has tags {:?}",
+                    job.id, requirements
+                );
+            }
+            return Ok(());
+        }
+        self.db.zrem(UNSCHEDULABLE_QUEUE, job.id.as_bytes())?;
+
+        // Jobs over a concurrency limit wait as Pending until there is room
+        if let Some(limit) = self.concurrency_limit(&job)? {
+            if job.status != JobStatus::Pending {
+                job.status = JobStatus::Pending;
+                self.save_job(&job)?;
+            }
+            if self
+                .db
+                .zscore(THROTTLED_QUEUE, job.id.as_bytes())?
+                .is_none()
+            {
+                self.db
+                    .zadd(THROTTLED_QUEUE, now as f64, job.id.as_bytes())?;
+                debug!(
+                    "Holding job {}: {} is at its concurrency limit",
+                    job.id, limit
+                );
+            }
+            return Ok(());
+        }
+        self.db.zrem(THROTTLED_QUEUE, job.id.as_bytes())?;
+        self.track_active(&job, now)?;
+
         job.status = JobStatus::Ready;
         self.save_job(&job)?;
 
-        // Determine queue based on tags
-        // Default: queue:default
-        // If tags contains "gpu": queue:gpu
-        let queue_name = if job.tags.contains(&"gpu".to_string()) {
-            "queue:gpu"
-        } else {
-            "queue:default"
-        };
+        let queue_name = ready_queue(&job);
+        let queue = base_queue(&job);
+        if !READY_QUEUES.contains(&queue.as_str())
+            && self
+                .db
+                .zscore(routing::TAGGED_QUEUES, queue.as_bytes())?
+                .is_none()
+        {
+            self.db
+                .zadd(routing::TAGGED_QUEUES, now as f64, queue.as_bytes())?;
+        }
+
+        if self.hold_for_turn(&job, &queue_name)? {
+            debug!(
+                "Holding job {}: plan {} has its share of {}",
+                job.id, job.plan_id, queue_name
+            );
+            return Ok(());
+        }
 
         // Push job ID to Redis list
         // We push the ID, workers will fetch metadata via JOB.GET
         // Note: We use the raw storage interface here
         // In a real implementation, we might want a cleaner abstraction for queues
         use crate::storage::ListOps;
-        self.db.lpush(queue_name, job.id.as_bytes())?;
+        self.db.lpush(&queue_name, job.id.as_bytes())?;
 
         info!("Enqueued job {} to {}", job.id, queue_name);
 
         Ok(())
     }
 
+    /// IDs of the jobs of every Action of every stored Plan
+    pub fn job_ids(&self) -> Result<Vec<String>> {
+        use crate::storage::ListOps;
+
+        let mut job_ids = Vec::new();
+        for (plan_id, _created_at) in self.db.zrange("plans:all", 0, -1)? {
+            let plan_id = String::from_utf8_lossy(&plan_id);
+            for action_id in self.db.lrange(&format!("plan:{}:actions", plan_id), 0, -1)? {
+                let action_id = String::from_utf8_lossy(&action_id);
+                for job_id in self.db.lrange(&format!("action:{}:jobs", action_id), 0, -1)? {
+                    job_ids.push(String::from_utf8_lossy(&job_id).into_owned());
+                }
+            }
+        }
+        Ok(job_ids)
+    }
+
+    /// Requeue jobs that a crash left out of every queue
+    ///
+    /// A Ready job is pushed onto its ready queue unless it is already in
+    /// that queue or `queue:processing`, or a worker has reported a status
+    /// for it. A Pending job waiting in `queue:delayed` is put back in
+    /// `queue:delayed` if it is missing from it. Jobs missing from the
+    /// `jobs:all` index are added to it.
+    ///
+    /// Returns the number of jobs requeued.
+    pub fn recover_jobs(&self, job_ids: &[String]) -> Result<usize> {
+        use crate::storage::{ListOps, StringOps};
+
+        let mut queued: HashMap<String, HashSet<Vec<u8>>> = HashMap::new();
+        let mut recovered = 0;
+
+        for job_id in job_ids {
+            let job = self.get_job(job_id)?;
+            // Jobs stored before the index existed
+            if self.db.zscore(JOBS_INDEX, job.id.as_bytes())?.is_none() {
+                self.db
+                    .zadd(JOBS_INDEX, job.created_at as f64, job.id.as_bytes())?;
+            }
+            match job.status {
+                JobStatus::Ready => {
+                    if self.db.exists(&format!("job:{}:status", job.id))? {
+                        continue;
+                    }
+                    let queue_name = ready_queue(&job);
+                    let mut in_queue = false;
+                    let backlog = backlog_key(&queue_name, &job.plan_id);
+                    for queue in [queue_name.as_str(), PROCESSING_QUEUE, backlog.as_str()] {
+                        if !queued.contains_key(queue) {
+                            let ids = self.db.lrange(queue, 0, -1)?.into_iter().collect();
+                            queued.insert(queue.to_string(), ids);
+                        }
+                        in_queue |= queued[queue].contains(job.id.as_bytes());
+                    }
+                    if in_queue {
+                        continue;
+                    }
+                    self.db.lpush(&queue_name, job.id.as_bytes())?;
+                    warn!("Requeued job {} lost from {}", job.id, queue_name);
+                }
+                JobStatus::Pending => {
+                    let Some(due) = job.run_after else {
+                        continue;
+                    };
+                    if self.db.zscore(DELAYED_QUEUE, job.id.as_bytes())?.is_some() {
+                        continue;
+                    }
+                    self.db.zadd(DELAYED_QUEUE, due as f64, job.id.as_bytes())?;
+                    warn!("Restored delayed job {} due at {}", job.id, due);
+                }
+                _ => continue,
+            }
+            recovered += 1;
+        }
+
+        Ok(recovered)
+    }
+
     // --- Storage Helpers ---
 
     fn save_job(&self, job: &Job) -> Result<()> {
@@ -144,7 +1148,16 @@ impl<'a> Orchestrator<'a> {
             .map_err(|e| crate::error::Error::Protocol(format!("Failed to serialize job: {}", e)))?;
 
         use crate::storage::StringOps;
+        let before = self
+            .db
+            .get(&key)?
+            .and_then(|json| serde_json::from_slice::<Job>(&json).ok())
+            .map(|stored| stored.status);
         self.db.set(&key, json.as_bytes())?;
+        if before != Some(job.status) {
+            crate::audit::job_changed(self.db, job, before, job.status.as_str());
+        }
+        crate::events::job_changed(self.db, job, job.status.as_str());
         Ok(())
     }
 
@@ -161,3 +1174,568 @@ impl<'a> Orchestrator<'a> {
         Ok(job)
     }
 }
+
+/// Attempts a job gets before it fails for good
+fn max_attempts(job: &Job) -> u32 {
+    job.max_attempts.unwrap_or(match job.on_failure {
+        FailurePolicy::Retry => DEFAULT_RETRY_ATTEMPTS,
+        FailurePolicy::Cancel | FailurePolicy::Continue => 1,
+    })
+}
+
+/// Jobs of a Plan with a weight that a ready queue holds at once
+fn fair_share(weight: u32) -> u64 {
+    FAIR_SHARE * u64::from(weight.max(1))
+}
+
+/// Sorted set of the Plans with jobs waiting their turn for a ready queue,
+/// scored by their weights
+fn waiting_key(queue: &str) -> String {
+    format!("fair:{}:plans", queue)
+}
+
+/// Jobs of a Plan waiting their turn for a ready queue, oldest last
+fn backlog_key(queue: &str, plan_id: &str) -> String {
+    format!("fair:{}:plan:{}", queue, plan_id)
+}
+
+/// Hash of Plan ID to how many of its jobs are in a ready queue
+fn queued_key(queue: &str) -> String {
+    format!("fair:{}:queued", queue)
+}
+
+/// Sorted set of the jobs counted against a Plan or queue limit
+fn active_key(owner: &str) -> String {
+    format!("{}:active", owner)
+}
+
+/// ID of the job an event token releases
+pub fn event_key(token: &str) -> String {
+    format!("event:{}", token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Backoff, BackoffStrategy, JobPriority};
+    use crate::storage::ListOps;
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    /// Jobs `a -> b -> c` plus an unrelated job `d`, all with one policy
+    fn chain(policy: FailurePolicy) -> Vec<Job> {
+        let mut jobs: Vec<Job> = ["a", "b", "c", "d"]
+            .iter()
+            .zip(1..)
+            .map(|(id, n)| {
+                let mut job = Job::new(
+                    id.to_string(),
+                    "action".to_string(),
+                    "plan".to_string(),
+                    n,
+                    "echo".to_string(),
+                    Vec::new(),
+                    serde_json::Value::Null,
+                    vec!["cpu".to_string()],
+                );
+                job.on_failure = policy;
+                job
+            })
+            .collect();
+        for (upstream, downstream) in [(0, 1), (1, 2)] {
+            let (up_id, down_id) = (jobs[upstream].id.clone(), jobs[downstream].id.clone());
+            jobs[upstream].dependents.insert(down_id);
+            jobs[downstream].dependencies.insert(up_id);
+        }
+        jobs
+    }
+
+    #[test]
+    fn test_fail_job_cancels_downstream() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator
+            .submit_jobs(chain(FailurePolicy::Cancel))
+            .unwrap();
+
+        orchestrator.fail_job("a", 1).unwrap();
+
+        assert_eq!(orchestrator.get_job("a").unwrap().status, JobStatus::Failed);
+        for id in ["b", "c"] {
+            let job = orchestrator.get_job(id).unwrap();
+            assert_eq!(job.status, JobStatus::Cancelled);
+            assert_eq!(job.cause.as_deref(), Some("upstream job a failed"));
+        }
+        assert_eq!(orchestrator.get_job("d").unwrap().status, JobStatus::Ready);
+    }
+
+    #[test]
+    fn test_cancel_takes_jobs_back_from_queues_and_workers() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator
+            .submit_jobs(chain(FailurePolicy::Continue))
+            .unwrap();
+        for id in ["a", "b", "c", "d"] {
+            db.lpush("action:action:jobs", id.as_bytes()).unwrap();
+        }
+        db.lpush("plan:plan:actions", b"action").unwrap();
+
+        // A worker takes `a` and starts running it
+        let queue = ready_queue(&orchestrator.get_job("a").unwrap());
+        db.lrem(&queue, 0, b"a").unwrap();
+        db.lpush(PROCESSING_QUEUE, b"a").unwrap();
+        db.set("job:a:worker", b"w1").unwrap();
+        orchestrator
+            .mark_running("a", Some("w1".to_string()), 100)
+            .unwrap();
+
+        assert_eq!(orchestrator.cancel_job("a", 200).unwrap(), 3);
+        assert_eq!(db.llen(PROCESSING_QUEUE).unwrap(), 0);
+        assert_eq!(db.get("job:a:worker").unwrap(), None);
+        assert_eq!(
+            db.get("job:a:status").unwrap().as_deref(),
+            Some(&b"cancelled"[..])
+        );
+        let c = orchestrator.get_job("c").unwrap();
+        assert_eq!(c.status, JobStatus::Cancelled);
+        assert_eq!(c.cause.as_deref(), Some("upstream job a was cancelled"));
+        assert_eq!(orchestrator.cancel_job("a", 200).unwrap(), 0);
+
+        // Only `d`, still queued, is left to cancel
+        assert_eq!(orchestrator.cancel_plan("plan", 300).unwrap(), 1);
+        assert_eq!(db.llen(&queue).unwrap(), 0);
+        assert_eq!(
+            orchestrator.get_job("d").unwrap().status,
+            JobStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_fail_job_continue_runs_dependents() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator
+            .submit_jobs(chain(FailurePolicy::Continue))
+            .unwrap();
+
+        orchestrator.fail_job("a", 1).unwrap();
+
+        assert_eq!(orchestrator.get_job("b").unwrap().status, JobStatus::Ready);
+        assert_eq!(
+            orchestrator.get_job("c").unwrap().status,
+            JobStatus::Pending
+        );
+    }
+
+    #[test]
+    fn test_fail_job_retries_before_cancelling() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Retry);
+        jobs[0].max_attempts = Some(2);
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        orchestrator.fail_job("a", 1).unwrap();
+        let job = orchestrator.get_job("a").unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.retries, 1);
+        assert_eq!(
+            orchestrator.get_job("b").unwrap().status,
+            JobStatus::Pending
+        );
+
+        // Held back until the 5s default backoff has passed
+        let due = job.run_after.unwrap();
+        assert_eq!(orchestrator.promote_delayed(due - 1).unwrap(), 0);
+        assert_eq!(orchestrator.promote_delayed(due).unwrap(), 1);
+        assert_eq!(orchestrator.get_job("a").unwrap().status, JobStatus::Ready);
+        // Queued once on submit and again for the retry
+        assert_eq!(db.llen("queue:default").unwrap(), 3);
+
+        orchestrator.fail_job("a", 1).unwrap();
+        assert_eq!(orchestrator.get_job("a").unwrap().status, JobStatus::Failed);
+        assert_eq!(
+            orchestrator.get_job("c").unwrap().status,
+            JobStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_run_after_delays_jobs_once_they_could_run() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Cancel);
+        jobs[0].run_after_secs = Some(60);
+        jobs[1].run_after_secs = Some(300);
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        let a = orchestrator.get_job("a").unwrap();
+        assert_eq!(a.status, JobStatus::Pending);
+        let due = a.run_after.unwrap();
+        // Only d, which has no delay, is queued
+        assert_eq!(db.llen("queue:default").unwrap(), 1);
+        assert_eq!(orchestrator.promote_delayed(due - 1).unwrap(), 0);
+        assert_eq!(orchestrator.promote_delayed(due).unwrap(), 1);
+        let a = orchestrator.get_job("a").unwrap();
+        assert_eq!((a.status, a.run_after), (JobStatus::Ready, None));
+
+        // The wait starts once the upstream job finishes
+        orchestrator.complete_job("a", 0).unwrap();
+        let b = orchestrator.get_job("b").unwrap();
+        assert_eq!(b.status, JobStatus::Pending);
+        assert!(b.run_after.unwrap() >= due + 300 - 60);
+        assert_eq!(db.zcard(DELAYED_QUEUE).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_await_event_holds_jobs_until_fired() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Cancel);
+        jobs[1].await_event = true;
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        // The wait starts once the upstream job finishes
+        assert!(orchestrator.get_job("b").unwrap().event_token.is_none());
+        orchestrator.complete_job("a", 0).unwrap();
+        let b = orchestrator.get_job("b").unwrap();
+        assert_eq!(b.status, JobStatus::Pending);
+        let token = b.event_token.unwrap();
+        assert_eq!(db.zcard(AWAITING_EVENT).unwrap(), 1);
+
+        assert_eq!(orchestrator.fire_event("bogus", None).unwrap(), None);
+        assert_eq!(
+            orchestrator.fire_event(&token, Some("approved")).unwrap(),
+            Some("b".to_string())
+        );
+        let b = orchestrator.get_job("b").unwrap();
+        assert_eq!((b.status, b.event_token), (JobStatus::Ready, None));
+        assert_eq!(b.env, serde_json::json!({ EVENT_PAYLOAD: "approved" }));
+        assert_eq!(db.zcard(AWAITING_EVENT).unwrap(), 0);
+        // An event fires once
+        assert_eq!(orchestrator.fire_event(&token, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_enqueue_by_priority() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Cancel);
+        jobs[0].priority = JobPriority::High;
+        jobs[3].priority = JobPriority::Low;
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        assert_eq!(db.lrange("queue:default:high", 0, -1).unwrap(), [b"a"]);
+        assert_eq!(db.lrange("queue:default:low", 0, -1).unwrap(), [b"d"]);
+        assert_eq!(db.llen("queue:default").unwrap(), 0);
+
+        // Dependents are queued by their own priority
+        orchestrator.complete_job("a", 0).unwrap();
+        assert_eq!(db.lrange("queue:default", 0, -1).unwrap(), [b"b"]);
+    }
+
+    /// Independent jobs `f1`..`fn` of one plan
+    fn fan_out(count: u32, max_concurrency: Option<u32>) -> Vec<Job> {
+        (1..=count)
+            .map(|n| {
+                let mut job = Job::new(
+                    format!("f{}", n),
+                    "action".to_string(),
+                    "plan".to_string(),
+                    n,
+                    "echo".to_string(),
+                    Vec::new(),
+                    serde_json::Value::Null,
+                    vec!["cpu".to_string()],
+                );
+                job.max_concurrency = max_concurrency;
+                job
+            })
+            .collect()
+    }
+
+    /// Register a live worker with comma-separated tags
+    fn add_worker(db: &Database, worker_id: &str, tags: &str) {
+        use crate::storage::{HashOps, StringOps};
+
+        db.zadd("workers:all", 0.0, worker_id.as_bytes()).unwrap();
+        db.hset(
+            &format!("worker:{}", worker_id),
+            "expire_at",
+            b"99999999999",
+        )
+        .unwrap();
+        db.set(&format!("worker:{}:tags", worker_id), tags.as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_unschedulable_jobs_wait_for_worker() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        add_worker(&db, "cpu-1", "cpu");
+
+        let mut jobs = fan_out(2, None);
+        jobs[1].tags = vec!["gpu".to_string(), "vram:24".to_string()];
+        orchestrator.submit_jobs(jobs).unwrap();
+        assert_eq!(db.lrange("queue:default", 0, -1).unwrap(), [b"f1"]);
+        assert_eq!(db.zcard(UNSCHEDULABLE_QUEUE).unwrap(), 1);
+        assert_eq!(
+            orchestrator.get_job("f2").unwrap().status,
+            JobStatus::Pending
+        );
+
+        // A GPU without enough memory is not enough
+        add_worker(&db, "gpu-1", "gpu,vram:8");
+        assert_eq!(orchestrator.promote_unschedulable(0).unwrap(), 0);
+
+        add_worker(&db, "gpu-2", "cpu,gpu,vram:24");
+        assert_eq!(orchestrator.promote_unschedulable(0).unwrap(), 1);
+        assert_eq!(db.lrange("queue:tags:gpu+vram:24", 0, -1).unwrap(), [b"f2"]);
+        assert_eq!(db.zcard(UNSCHEDULABLE_QUEUE).unwrap(), 0);
+        assert_eq!(
+            routing::worker_queues(&db, "gpu-2").unwrap(),
+            ["queue:tags:gpu+vram:24", "queue:gpu", "queue:default"]
+        );
+    }
+
+    #[test]
+    fn test_map_job_expands_per_item() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Cancel);
+        jobs[1].map = Some(MapSplit::Lines);
+        jobs[1].env = serde_json::json!({"lang": "eng"});
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        db.set("job:a:stdout", b"scan-1.png\nscan-2.png\n").unwrap();
+        orchestrator.complete_job("a", 0).unwrap();
+
+        let map_job = orchestrator.get_job("b").unwrap();
+        assert_eq!(map_job.status, JobStatus::Completed);
+        assert_eq!(map_job.expanded_into.len(), 2);
+        for (index, id) in map_job.expanded_into.iter().enumerate() {
+            let item = orchestrator.get_job(id).unwrap();
+            assert_eq!(item.status, JobStatus::Ready);
+            assert_eq!(item.item_index, Some(index as u32));
+            assert_eq!(item.env["item"], format!("scan-{}.png", index + 1));
+            assert_eq!(item.env["lang"], "eng");
+        }
+
+        // The join waits for every item
+        let (first, second) = (&map_job.expanded_into[0], &map_job.expanded_into[1]);
+        orchestrator.complete_job(first, 0).unwrap();
+        assert_eq!(
+            orchestrator.get_job("c").unwrap().status,
+            JobStatus::Pending
+        );
+        orchestrator.complete_job(second, 0).unwrap();
+        assert_eq!(orchestrator.get_job("c").unwrap().status, JobStatus::Ready);
+        assert_eq!(db.lrange("action:action:jobs", 0, -1).unwrap().len(), 2);
+
+        // Input that cannot be split fails the map job
+        let mut jobs = chain(FailurePolicy::Cancel);
+        for job in &mut jobs {
+            job.id = format!("json-{}", job.id);
+        }
+        jobs[0].dependents = HashSet::from(["json-b".to_string()]);
+        jobs[1].dependencies = HashSet::from(["json-a".to_string()]);
+        jobs[1].dependents = HashSet::from(["json-c".to_string()]);
+        jobs[2].dependencies = HashSet::from(["json-b".to_string()]);
+        jobs[1].map = Some(MapSplit::Json);
+        orchestrator.submit_jobs(jobs).unwrap();
+        db.set("job:json-a:stdout", b"not json").unwrap();
+        orchestrator.complete_job("json-a", 0).unwrap();
+        let map_job = orchestrator.get_job("json-b").unwrap();
+        assert_eq!(map_job.status, JobStatus::Failed);
+        assert!(map_job.cause.unwrap().contains("not a JSON array"));
+        assert_eq!(
+            orchestrator.get_job("json-c").unwrap().status,
+            JobStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn test_map_job_splits_its_input_not_jobs_it_waits_for() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let mut jobs = chain(FailurePolicy::Cancel);
+        jobs[1].map = Some(MapSplit::Lines);
+        // b also waits for d, without reading its output
+        jobs[1].dependencies.insert("d".to_string());
+        jobs[1].order_only.insert("d".to_string());
+        jobs[3].dependents.insert("b".to_string());
+        orchestrator.submit_jobs(jobs).unwrap();
+
+        db.set("job:a:stdout", b"scan-1.png\nscan-2.png\n").unwrap();
+        orchestrator.complete_job("a", 0).unwrap();
+        assert_eq!(
+            orchestrator.get_job("b").unwrap().status,
+            JobStatus::Pending
+        );
+
+        db.set("job:d:stdout", b"done\n").unwrap();
+        orchestrator.complete_job("d", 0).unwrap();
+        let map_job = orchestrator.get_job("b").unwrap();
+        assert_eq!(map_job.expanded_into.len(), 2);
+        let item = orchestrator.get_job(&map_job.expanded_into[0]).unwrap();
+        assert_eq!(item.env["item"], "scan-1.png");
+        assert!(item.dependencies.is_empty() && item.order_only.is_empty());
+    }
+
+    #[test]
+    fn test_plan_concurrency_limit() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator.submit_jobs(fan_out(4, Some(2))).unwrap();
+
+        assert_eq!(db.lrange("queue:default", 0, -1).unwrap(), [b"f2", b"f1"]);
+        assert_eq!(db.zcard(THROTTLED_QUEUE).unwrap(), 2);
+        assert_eq!(
+            orchestrator.get_job("f3").unwrap().status,
+            JobStatus::Pending
+        );
+
+        // Nothing is released while both queued jobs are still running
+        db.set("job:f1:status", b"running").unwrap();
+        assert_eq!(orchestrator.promote_throttled().unwrap(), 0);
+
+        db.set("job:f1:status", b"completed").unwrap();
+        assert_eq!(orchestrator.promote_throttled().unwrap(), 1);
+        assert_eq!(db.lrange("queue:default", 0, 0).unwrap(), [b"f3"]);
+        assert_eq!(orchestrator.get_job("f3").unwrap().status, JobStatus::Ready);
+        assert_eq!(db.zcard(THROTTLED_QUEUE).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_throttled_jobs_fill_only_freed_slots() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator.submit_jobs(fan_out(6, Some(2))).unwrap();
+        assert_eq!(db.zcard(THROTTLED_QUEUE).unwrap(), 4);
+
+        // Two slots free up; jobs released in the pass count against the
+        // rest of it
+        db.set("job:f1:status", b"completed").unwrap();
+        db.set("job:f2:status", b"completed").unwrap();
+        assert_eq!(orchestrator.promote_throttled().unwrap(), 2);
+        assert_eq!(db.lrange("queue:default", 0, 1).unwrap(), [b"f4", b"f3"]);
+        assert_eq!(
+            orchestrator.get_job("f5").unwrap().status,
+            JobStatus::Pending
+        );
+        assert_eq!(db.zcard(THROTTLED_QUEUE).unwrap(), 2);
+        assert_eq!(orchestrator.promote_throttled().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_plans_take_turns() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let plan = |plan_id: &str, count: u32, weight: Option<u32>| {
+            let mut jobs = fan_out(count, None);
+            for job in &mut jobs {
+                job.id = format!("{}-{}", plan_id, job.id);
+                job.plan_id = plan_id.to_string();
+                job.weight = weight;
+            }
+            jobs
+        };
+        let take = |count: usize| -> Vec<String> {
+            (0..count)
+                .map(|_| {
+                    let job_id = db.rpop("queue:default").unwrap().unwrap();
+                    let job_id = String::from_utf8(job_id).unwrap();
+                    orchestrator.job_taken("queue:default", &job_id).unwrap();
+                    job_id
+                })
+                .collect()
+        };
+
+        orchestrator.submit_jobs(plan("batch", 100, None)).unwrap();
+        orchestrator.submit_jobs(plan("small", 3, None)).unwrap();
+        assert_eq!(db.llen("queue:default").unwrap(), 13);
+        assert_eq!(waiting_turn(&db, "queue:default").unwrap(), 90);
+
+        // The small plan's jobs go right after the batch's first share
+        let taken = take(13);
+        assert_eq!(&taken[10..], ["small-f1", "small-f2", "small-f3"]);
+        assert_eq!(db.llen("queue:default").unwrap(), 10);
+
+        // A plan of weight 3 gets three turns for each of the batch's
+        orchestrator
+            .submit_jobs(plan("heavy", 100, Some(3)))
+            .unwrap();
+        take(10);
+        let taken = take(40);
+        let heavy = taken.iter().filter(|id| id.starts_with("heavy")).count();
+        assert_eq!(heavy, 30);
+
+        // Counts thrown off by jobs leaving the queue some other way are
+        // corrected
+        while db.rpop("queue:default").unwrap().is_some() {}
+        assert_eq!(orchestrator.promote_fair().unwrap(), 40);
+        assert_eq!(db.llen("queue:default").unwrap(), 40);
+        assert!(is_ready_queue("queue:tags:gpu+vram:24:high"));
+        assert!(!is_ready_queue(PROCESSING_QUEUE));
+    }
+
+    #[test]
+    fn test_queue_concurrency_limit() {
+        use crate::storage::StringOps;
+
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        orchestrator.set_queue_limit("queue:default", 1).unwrap();
+        assert_eq!(orchestrator.queue_limit("queue:default").unwrap(), Some(1));
+
+        add_worker(&db, "gpu-1", "gpu");
+        let mut jobs = fan_out(3, None);
+        jobs[2].tags = vec!["gpu".to_string()];
+        orchestrator.submit_jobs(jobs).unwrap();
+        assert_eq!(db.lrange("queue:default", 0, -1).unwrap(), [b"f1"]);
+        assert_eq!(db.lrange("queue:gpu", 0, -1).unwrap(), [b"f3"]);
+
+        // Removing the limit releases the held job
+        db.set("job:f1:status", b"running").unwrap();
+        orchestrator.set_queue_limit("queue:default", 0).unwrap();
+        assert_eq!(orchestrator.queue_limit("queue:default").unwrap(), None);
+        assert_eq!(orchestrator.promote_throttled().unwrap(), 1);
+        assert_eq!(db.zcard(THROTTLED_QUEUE).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_backoff_delays() {
+        let backoff = |strategy| Backoff {
+            strategy,
+            delay_secs: 10,
+            max_delay_secs: 60,
+        };
+        let delays = |backoff: Backoff| (1..=4).map(|n| backoff.delay(n)).collect::<Vec<_>>();
+
+        assert_eq!(delays(backoff(BackoffStrategy::Fixed)), [10, 10, 10, 10]);
+        assert_eq!(delays(backoff(BackoffStrategy::Linear)), [10, 20, 30, 40]);
+        assert_eq!(
+            delays(backoff(BackoffStrategy::Exponential)),
+            [10, 20, 40, 60]
+        );
+        assert_eq!(backoff(BackoffStrategy::Exponential).delay(200), 60);
+    }
+}
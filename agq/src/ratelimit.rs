@@ -0,0 +1,215 @@
+//! Per-client rate limits on submissions and job results
+//!
+//! AGQ runs every command against one database, so a client submitting
+//! Plans or posting results in a tight loop slows every other client down.
+//! Each authenticated client gets a token bucket for each limited kind of
+//! command: the bucket holds up to `burst` tokens and refills at a steady
+//! `rate` per second, and each command takes a token. A command arriving at
+//! an empty bucket is refused with a `RATE_LIMITED` error saying when a
+//! token will be free.
+//!
+//! Submissions are `PLAN.SUBMIT`, `PLAN.INSTANTIATE` and `ACTION.SUBMIT`;
+//! a job result is posted when a worker stores `job:<id>:result`.
+
+use crate::error::{Error, Result};
+use crate::resp::RespValue;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A steady rate and the burst allowed above it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    /// Tokens added per second
+    pub per_sec: f64,
+    /// Most tokens a bucket holds
+    pub burst: u32,
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    /// Parse `RATE[/BURST]`, e.g. `5` or `0.5/10`; the burst defaults to
+    /// the rate rounded up
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (rate, burst) = match s.split_once('/') {
+            Some((rate, burst)) => (rate, Some(burst)),
+            None => (s, None),
+        };
+        let per_sec: f64 = rate
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid rate '{}'", rate))?;
+        if !per_sec.is_finite() || per_sec <= 0.0 {
+            return Err("rate must be a positive number per second".to_string());
+        }
+        let burst = match burst {
+            Some(burst) => burst
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid burst '{}'", burst))?,
+            None => per_sec.ceil() as u32,
+        };
+        if burst == 0 {
+            return Err("burst must be at least 1".to_string());
+        }
+        Ok(Self { per_sec, burst })
+    }
+}
+
+/// A kind of command that is rate limited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limited {
+    /// Submitting Plans and Actions
+    Submit,
+    /// Posting a job's result
+    Result,
+}
+
+impl Limited {
+    /// The limited kind a command is, if any
+    #[must_use]
+    pub fn of(command: &str, args: &[RespValue]) -> Option<Self> {
+        match command {
+            "PLAN.SUBMIT" | "PLAN.INSTANTIATE" | "ACTION.SUBMIT" => Some(Self::Submit),
+            "SET" => {
+                let key = args.get(1)?.as_string().ok()?;
+                let job_id = key.strip_prefix("job:")?.strip_suffix(":result")?;
+                (!job_id.contains(':')).then_some(Self::Result)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Tokens left in a bucket as of a time
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The configured rates and every client's buckets
+///
+/// Clones share buckets, so a limit holds across a client's connections.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimits {
+    submit: Option<Rate>,
+    result: Option<Rate>,
+    buckets: Arc<Mutex<HashMap<(String, Limited), Bucket>>>,
+}
+
+impl RateLimits {
+    /// Limits on submissions and job results; `None` leaves a kind unlimited
+    #[must_use]
+    pub fn new(submit: Option<Rate>, result: Option<Rate>) -> Self {
+        Self {
+            submit,
+            result,
+            ..Self::default()
+        }
+    }
+
+    /// Take a token for a client's command, if the command is limited
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::RateLimited`] if the client's bucket is empty
+    pub fn check(&self, client: &str, command: &str, args: &[RespValue]) -> Result<()> {
+        let Some(kind) = Limited::of(command, args) else {
+            return Ok(());
+        };
+        let rate = match kind {
+            Limited::Submit => self.submit,
+            Limited::Result => self.result,
+        };
+        let Some(rate) = rate else {
+            return Ok(());
+        };
+
+        self.take(client, kind, rate, Instant::now())
+            .map_err(|wait| Error::RateLimited {
+                client: client.to_string(),
+                command: command.to_string(),
+                retry_after_ms: wait.as_millis().max(1) as u64,
+            })
+    }
+
+    /// Take a token from a bucket, or say how long until one is free
+    fn take(
+        &self,
+        client: &str,
+        kind: Limited,
+        rate: Rate,
+        now: Instant,
+    ) -> std::result::Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry((client.to_string(), kind)).or_insert(Bucket {
+            tokens: f64::from(rate.burst),
+            updated: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate.per_sec).min(f64::from(rate.burst));
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / rate.per_sec,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let rate: Rate = "2/3".parse().unwrap();
+        let limits = RateLimits::new(Some(rate), None);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limits.take("ui", Limited::Submit, rate, start).is_ok());
+        }
+        let wait = limits.take("ui", Limited::Submit, rate, start).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        assert!(limits.take("cli", Limited::Submit, rate, start).is_ok());
+
+        let later = start + Duration::from_millis(500);
+        assert!(limits.take("ui", Limited::Submit, rate, later).is_ok());
+        assert!(limits.take("ui", Limited::Submit, rate, later).is_err());
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        let rate: Rate = "0.5".parse().unwrap();
+        assert_eq!(rate.burst, 1);
+        assert_eq!("10/50".parse::<Rate>().unwrap().burst, 50);
+        for invalid in ["", "0", "-1", "5/0", "x/2", "2/x"] {
+            assert!(invalid.parse::<Rate>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_limited_commands() {
+        let set = |key: &str| {
+            vec![
+                RespValue::BulkString(b"SET".to_vec()),
+                RespValue::BulkString(key.as_bytes().to_vec()),
+            ]
+        };
+        assert_eq!(Limited::of("PLAN.SUBMIT", &[]), Some(Limited::Submit));
+        assert_eq!(
+            Limited::of("SET", &set("job:j1:result")),
+            Some(Limited::Result)
+        );
+        assert_eq!(Limited::of("SET", &set("job:j1:stdout")), None);
+        assert_eq!(Limited::of("GET", &set("job:j1:result")), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Snapshots and crash recovery
+//!
+//! Every write to the database is its own transaction, committed
+//! durably before the command returns, so a restart sees all acknowledged
+//! writes. What a crash can lose is the step between two writes: a job
+//! saved as Ready but never pushed onto its queue, or a plan submission
+//! taken by the plan worker but not yet stored. [`recover`] repairs these
+//! on startup. A graceful shutdown leaves a marker with
+//! [`mark_clean_shutdown`], and a restart that finds it skips checking
+//! every job.
+//!
+//! Snapshots guard against the database file itself being lost or
+//! corrupted. They are complete redb files, whatever the backend, written
+//! to the snapshot directory as `data-<timestamp>.redb`, and
+//! [`open_or_restore`] falls back to the latest one when the database cannot
+//! be opened. An in-memory
+//! database is loaded from the latest snapshot on startup, which makes
+//! snapshots its only persistence.
+
+use crate::error::Result;
+use crate::orchestrator::Orchestrator;
+use crate::storage::{Backend, Database, ListOps, StringOps};
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
+
+/// Queue of plan submissions waiting for the plan worker
+const PLAN_SUBMIT_QUEUE: &str = "agq:internal:plan.submit";
+
+/// Plan submissions the plan worker has taken but not finished
+const PLAN_SUBMIT_PROCESSING: &str = "agq:internal:plan.submit:processing";
+
+/// Set by a graceful shutdown to when it finished, and cleared on startup
+pub const CLEAN_SHUTDOWN_MARKER: &str = "agq:internal:clean_shutdown";
+
+/// Record that the server shut down gracefully
+///
+/// # Errors
+///
+/// Returns an error if the marker cannot be written
+pub fn mark_clean_shutdown(db: &Database, now: u64) -> Result<()> {
+    db.set(CLEAN_SHUTDOWN_MARKER, now.to_string().as_bytes())
+}
+
+/// Repair queues left inconsistent by a crash
+///
+/// Plan submissions the plan worker was processing are put back on its
+/// queue, and unless the last shutdown was graceful, jobs of every stored
+/// Action are checked with [`Orchestrator::recover_jobs`].
+///
+/// Returns the number of submissions and jobs requeued.
+///
+/// # Errors
+///
+/// Returns an error if a queue or job cannot be read or written
+pub fn recover(db: &Database) -> Result<usize> {
+    let mut recovered = 0;
+    while db
+        .rpoplpush(PLAN_SUBMIT_PROCESSING, PLAN_SUBMIT_QUEUE)?
+        .is_some()
+    {
+        recovered += 1;
+    }
+    if recovered > 0 {
+        warn!("Requeued {} interrupted plan submissions", recovered);
+    }
+
+    if let Some(stopped_at) = db.get(CLEAN_SHUTDOWN_MARKER)? {
+        db.del(CLEAN_SHUTDOWN_MARKER)?;
+        info!(
+            "Last shutdown at {} was clean, skipping job recovery",
+            String::from_utf8_lossy(&stopped_at)
+        );
+        return Ok(recovered);
+    }
+
+    let orchestrator = Orchestrator::new(db);
+    let job_ids = orchestrator.job_ids()?;
+    recovered += orchestrator.recover_jobs(&job_ids)?;
+
+    info!(
+        "Recovery checked {} jobs, requeued {}",
+        job_ids.len(),
+        recovered
+    );
+    Ok(recovered)
+}
+
+/// Open the database with the given backend
+///
+/// A database file is opened with [`open_or_restore`]. An in-memory
+/// database starts from the latest snapshot, if there is one.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened or the snapshot
+/// cannot be loaded
+pub fn open(backend: Backend, db_path: &Path, snapshot_dir: &Path) -> Result<Database> {
+    match backend {
+        Backend::File | Backend::Sqlite => open_or_restore(backend, db_path, snapshot_dir),
+        Backend::Memory => {
+            let db = Database::open_in_memory()?;
+            if let Some(snapshot) = latest_snapshot(snapshot_dir)? {
+                db.load_snapshot(&snapshot)?;
+            }
+            Ok(db)
+        }
+    }
+}
+
+/// Open the database file, restoring the latest snapshot if it cannot be opened
+///
+/// The unreadable file is kept next to the database with a `.corrupt`
+/// extension, along with a SQLite database's write-ahead log.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened and there is no
+/// snapshot to restore, or restoring fails
+pub fn open_or_restore(backend: Backend, db_path: &Path, snapshot_dir: &Path) -> Result<Database> {
+    let open_error = match Database::open_with(backend, db_path) {
+        Ok(db) => return Ok(db),
+        Err(e) => e,
+    };
+    let Some(snapshot) = latest_snapshot(snapshot_dir)? else {
+        return Err(open_error);
+    };
+
+    error!(
+        "Failed to open database ({}), restoring snapshot {}",
+        open_error,
+        snapshot.display()
+    );
+    let mut corrupt = db_path.as_os_str().to_owned();
+    corrupt.push(".corrupt");
+    std::fs::rename(db_path, &corrupt)?;
+    if backend != Backend::Sqlite {
+        std::fs::copy(&snapshot, db_path)?;
+        return Database::open(db_path);
+    }
+
+    // SQLite keeps recent commits in `<file>-wal`, which belongs with the
+    // corrupt file rather than the new one
+    for suffix in ["-wal", "-shm"] {
+        let mut side_file = db_path.as_os_str().to_owned();
+        side_file.push(suffix);
+        if Path::new(&side_file).exists() {
+            let mut moved = corrupt.clone();
+            moved.push(suffix);
+            std::fs::rename(&side_file, moved)?;
+        }
+    }
+    let db = Database::open_sqlite(db_path)?;
+    db.restore_snapshot(&snapshot)?;
+    Ok(db)
+}
+
+/// Write a snapshot and delete all but the newest `keep` snapshots
+///
+/// # Errors
+///
+/// Returns an error if the snapshot cannot be written or the snapshot
+/// directory cannot be read
+pub fn write_snapshot(db: &Database, snapshot_dir: &Path, keep: usize, now: u64) -> Result<()> {
+    std::fs::create_dir_all(snapshot_dir)?;
+    db.snapshot(snapshot_dir.join(format!("data-{}.redb", now)))?;
+
+    let snapshots = list_snapshots(snapshot_dir)?;
+    for (_taken_at, old) in snapshots.iter().rev().skip(keep.max(1)) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Newest snapshot in the snapshot directory
+///
+/// # Errors
+///
+/// Returns an error if the snapshot directory exists but cannot be read
+pub fn latest_snapshot(snapshot_dir: &Path) -> Result<Option<PathBuf>> {
+    Ok(list_snapshots(snapshot_dir)?.pop().map(|(_, path)| path))
+}
+
+/// Snapshots in the snapshot directory, oldest first
+fn list_snapshots(snapshot_dir: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    if !snapshot_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(snapshot_dir)? {
+        let path = entry?.path();
+        let taken_at = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("data-"))
+            .and_then(|name| name.strip_suffix(".redb"))
+            .and_then(|timestamp| timestamp.parse::<u64>().ok());
+        if let Some(taken_at) = taken_at {
+            snapshots.push((taken_at, path));
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{Job, JobStatus};
+    use crate::storage::{SortedSetOps, StringOps};
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    fn save_job(db: &Database, id: &str, status: JobStatus) {
+        let mut job = Job::new(
+            id.to_string(),
+            "action_r".to_string(),
+            "plan_r".to_string(),
+            1,
+            "echo".to_string(),
+            vec![],
+            serde_json::json!({}),
+            vec![],
+        );
+        job.status = status;
+        db.set(&format!("job:{}", id), &serde_json::to_vec(&job).unwrap())
+            .unwrap();
+        db.lpush("action:action_r:jobs", id.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_recover_requeues_lost_jobs() {
+        let (db, _temp) = test_db();
+        db.zadd("plans:all", 1.0, b"plan_r").unwrap();
+        db.lpush("plan:plan_r:actions", b"action_r").unwrap();
+        db.lpush(PLAN_SUBMIT_PROCESSING, b"{}").unwrap();
+
+        save_job(&db, "lost", JobStatus::Ready);
+        save_job(&db, "queued", JobStatus::Ready);
+        db.lpush("queue:default", b"queued").unwrap();
+        save_job(&db, "running", JobStatus::Ready);
+        db.lpush("queue:processing", b"running").unwrap();
+        save_job(&db, "reported", JobStatus::Ready);
+        db.set("job:reported:status", b"completed").unwrap();
+        save_job(&db, "waiting", JobStatus::Pending);
+
+        assert_eq!(recover(&db).unwrap(), 2);
+        assert_eq!(
+            db.lrange("queue:default", 0, -1).unwrap(),
+            [b"lost".to_vec(), b"queued".to_vec()]
+        );
+        assert_eq!(db.llen(PLAN_SUBMIT_QUEUE).unwrap(), 1);
+        assert_eq!(db.llen(PLAN_SUBMIT_PROCESSING).unwrap(), 0);
+
+        // A second pass finds nothing to repair
+        assert_eq!(recover(&db).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clean_shutdown_skips_job_recovery() {
+        let (db, _temp) = test_db();
+        db.zadd("plans:all", 1.0, b"plan_r").unwrap();
+        db.lpush("plan:plan_r:actions", b"action_r").unwrap();
+        db.lpush(PLAN_SUBMIT_PROCESSING, b"{}").unwrap();
+        save_job(&db, "lost", JobStatus::Ready);
+
+        mark_clean_shutdown(&db, 100).unwrap();
+        assert_eq!(recover(&db).unwrap(), 1);
+        assert_eq!(db.llen("queue:default").unwrap(), 0);
+        assert!(db.get(CLEAN_SHUTDOWN_MARKER).unwrap().is_none());
+
+        // Without the marker, the next startup checks every job
+        assert_eq!(recover(&db).unwrap(), 1);
+        assert_eq!(db.llen("queue:default").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let (db, temp) = test_db();
+        let snapshot_dir = temp.path().join("snapshots");
+        db.set("plan:kept", b"v1").unwrap();
+        db.lpush("queue:default", b"job_1").unwrap();
+
+        for now in [100, 200, 300] {
+            write_snapshot(&db, &snapshot_dir, 2, now).unwrap();
+        }
+        let taken: Vec<u64> = list_snapshots(&snapshot_dir)
+            .unwrap()
+            .into_iter()
+            .map(|(taken_at, _)| taken_at)
+            .collect();
+        assert_eq!(taken, [200, 300]);
+
+        let db_path = temp.path().join("broken.redb");
+        std::fs::write(&db_path, b"not a database").unwrap();
+        let restored = open_or_restore(Backend::File, &db_path, &snapshot_dir).unwrap();
+        assert_eq!(restored.get("plan:kept").unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(restored.llen("queue:default").unwrap(), 1);
+        assert!(temp.path().join("broken.redb.corrupt").exists());
+
+        let memory = super::open(Backend::Memory, &db_path, &snapshot_dir).unwrap();
+        assert_eq!(memory.get("plan:kept").unwrap(), Some(b"v1".to_vec()));
+    }
+
+    #[test]
+    fn test_sqlite_snapshot_restore() {
+        let temp = TempDir::new().unwrap();
+        let snapshot_dir = temp.path().join("snapshots");
+        let db_path = temp.path().join("data.sqlite3");
+        {
+            let db = super::open(Backend::Sqlite, &db_path, &snapshot_dir).unwrap();
+            db.set("plan:kept", b"v1").unwrap();
+            db.lpush("queue:default", b"job_1").unwrap();
+            write_snapshot(&db, &snapshot_dir, 2, 100).unwrap();
+            db.set("plan:later", b"v2").unwrap();
+        }
+
+        // Reopening keeps every committed write
+        let db = super::open(Backend::Sqlite, &db_path, &snapshot_dir).unwrap();
+        assert_eq!(db.get("plan:later").unwrap(), Some(b"v2".to_vec()));
+        drop(db);
+
+        std::fs::write(&db_path, vec![0xAB; 8192]).unwrap();
+        let restored = super::open(Backend::Sqlite, &db_path, &snapshot_dir).unwrap();
+        assert_eq!(restored.get("plan:kept").unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(restored.get("plan:later").unwrap(), None);
+        assert_eq!(restored.llen("queue:default").unwrap(), 1);
+        assert!(temp.path().join("data.sqlite3.corrupt").exists());
+
+        // The snapshot a SQLite database wrote loads into any backend
+        let memory = super::open(Backend::Memory, &db_path, &snapshot_dir).unwrap();
+        assert_eq!(memory.get("plan:kept").unwrap(), Some(b"v1".to_vec()));
+    }
+}
@@ -0,0 +1,559 @@
+//! Hot standby replication
+//!
+//! A standby AGQ, started with `--follow <leader>`, keeps a copy of its
+//! leader's database so it can take over when the leader is lost. It
+//! connects to the leader as an admin client and sends `REPLICA.SYNC`. The
+//! leader replies with a snapshot of its database, then streams every write
+//! it commits, numbered in commit order (see [`crate::storage::Change`]).
+//! The standby applies the writes in order and refuses writes from its own
+//! clients. Whenever the stream breaks, through a lost connection, a gap in
+//! the numbering, or the standby falling too far behind, the standby
+//! reconnects and starts over from a fresh snapshot.
+//!
+//! Snapshots in transit are staged in a directory under the data directory
+//! that only AGQ's user can read, since they hold everything the database
+//! does, secrets included.
+//!
+//! A standby becomes a leader when an operator sends `REPLICA.PROMOTE`, or
+//! on its own once it has not heard from its leader for `--failover-after`
+//! seconds. Promotion is one-way: the old leader rejoins only by being
+//! restarted as a standby of the new one.
+
+use crate::audit;
+use crate::error::{Error, Result};
+use crate::resp::{RespParser, RespValue};
+use crate::storage::{Change, Database};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout, Duration};
+use tracing::{info, warn};
+
+/// How often a leader pings a standby it has no changes to send
+pub const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a standby waits to hear from its leader before reconnecting
+pub const LEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Size of the pieces a snapshot is sent in, well under the largest RESP
+/// message
+pub const SNAPSHOT_CHUNK: usize = 256 * 1024;
+
+/// First and longest wait before a standby reconnects to its leader
+const RETRY_MIN: Duration = Duration::from_millis(500);
+const RETRY_MAX: Duration = Duration::from_secs(10);
+
+/// This AGQ's role, and how far a standby has caught up with its leader
+///
+/// Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct Replication {
+    state: Arc<Mutex<State>>,
+    /// Whether this AGQ is a leader, either from the start or by promotion
+    promoted: Arc<watch::Sender<bool>>,
+    /// Directory snapshots are staged in while they are sent or received
+    staging_dir: Option<Arc<PathBuf>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Address of the leader while this AGQ is a standby
+    leader: Option<String>,
+    /// Whether a standby is streaming changes from its leader
+    connected: bool,
+    /// Number of the last leader change a standby applied, once it has a
+    /// copy of the leader's database
+    applied: Option<u64>,
+    /// When a standby last heard from its leader
+    last_contact: Option<Instant>,
+    /// Standbys streaming this AGQ's changes
+    standbys: usize,
+}
+
+impl Default for Replication {
+    /// A leader
+    fn default() -> Self {
+        Self::with_leader(None)
+    }
+}
+
+impl Replication {
+    /// A standby of the AGQ at `leader`
+    #[must_use]
+    pub fn standby(leader: impl Into<String>) -> Self {
+        Self::with_leader(Some(leader.into()))
+    }
+
+    fn with_leader(leader: Option<String>) -> Self {
+        let (promoted, _) = watch::channel(leader.is_none());
+        Self {
+            state: Arc::new(Mutex::new(State {
+                leader,
+                ..State::default()
+            })),
+            promoted: Arc::new(promoted),
+            staging_dir: None,
+        }
+    }
+
+    /// Stage snapshots sent to or received from other AGQs in `dir`,
+    /// usually `replica` in the data directory
+    ///
+    /// Without one, this AGQ can neither serve nor follow a leader.
+    #[must_use]
+    pub fn with_staging_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.staging_dir = Some(Arc::new(dir.into()));
+        self
+    }
+
+    /// A new file for a snapshot in transit, in the staging directory
+    ///
+    /// The directory is created, or restricted, so only this user can
+    /// open it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no staging directory or it cannot be
+    /// created
+    pub(crate) fn stage_snapshot(&self) -> Result<StagedSnapshot> {
+        let dir = self.staging_dir.as_deref().ok_or_else(|| {
+            Error::Protocol("Replication needs a directory to stage snapshots in".to_string())
+        })?;
+        create_private_dir(dir)?;
+        Ok(StagedSnapshot {
+            path: dir.join(format!("snapshot-{}.redb", uuid::Uuid::new_v4())),
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Address of the leader, if this AGQ is a standby
+    #[must_use]
+    pub fn leader(&self) -> Option<String> {
+        self.state().leader.clone()
+    }
+
+    /// Refuse commands that write while this AGQ is a standby
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ReadOnly`] for a write other than `REPLICA.PROMOTE`
+    pub fn check_writable(&self, command: &str) -> Result<()> {
+        match self.leader() {
+            Some(leader) if command != "REPLICA.PROMOTE" && audit::is_mutation(command) => {
+                Err(Error::ReadOnly { leader })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Make a standby the leader, returning `false` if it already was one
+    pub fn promote(&self) -> bool {
+        let mut state = self.state();
+        let Some(leader) = state.leader.take() else {
+            return false;
+        };
+        state.connected = false;
+        drop(state);
+
+        warn!("Promoted to leader, no longer following {}", leader);
+        self.promoted.send_replace(true);
+        true
+    }
+
+    /// Wait until this AGQ is a leader; returns at once for a leader
+    pub async fn promoted(&self) {
+        // The sender lives as long as `self`, so this only returns once set
+        let _ = self.promoted.subscribe().wait_for(|leader| *leader).await;
+    }
+
+    /// Role and progress as JSON, for `REPLICA.STATUS`
+    #[must_use]
+    pub fn status(&self, db: &Database) -> serde_json::Value {
+        let state = self.state();
+        match &state.leader {
+            Some(leader) => serde_json::json!({
+                "role": "standby",
+                "leader": leader,
+                "connected": state.connected,
+                "applied_seq": state.applied,
+                "secs_since_contact": state
+                    .last_contact
+                    .map(|contact| contact.elapsed().as_secs()),
+            }),
+            None => serde_json::json!({
+                "role": "leader",
+                "seq": db.change_seq(),
+                "standbys": state.standbys,
+            }),
+        }
+    }
+
+    /// Count a standby streaming this AGQ's changes for as long as the
+    /// returned guard lives
+    pub(crate) fn standby_connected(&self) -> StandbyGuard {
+        self.state().standbys += 1;
+        StandbyGuard(self.clone())
+    }
+
+    /// Whether a standby that has copied its leader's database has not
+    /// heard from it for `after`
+    fn leader_silent_for(&self, after: Duration) -> bool {
+        let state = self.state();
+        state.applied.is_some()
+            && state
+                .last_contact
+                .is_some_and(|contact| contact.elapsed() >= after)
+    }
+}
+
+/// A snapshot file staged in transit, deleted when dropped
+pub(crate) struct StagedSnapshot {
+    path: PathBuf,
+}
+
+impl StagedSnapshot {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for StagedSnapshot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Create a directory only this user can open, or restrict an existing one
+fn create_private_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Counts a connected standby until dropped
+pub(crate) struct StandbyGuard(Replication);
+
+impl Drop for StandbyGuard {
+    fn drop(&mut self) {
+        self.0.state().standbys -= 1;
+    }
+}
+
+/// Follow a leader until this AGQ is promoted
+///
+/// Connects to the leader with an admin `key`, copies its database and
+/// applies its changes, reconnecting whenever the stream breaks. With
+/// `failover_after`, promotes this AGQ once it has copied the leader's
+/// database and then not heard from it for that long.
+pub async fn follow(
+    leader: String,
+    key: Vec<u8>,
+    db: Arc<Database>,
+    replication: Replication,
+    failover_after: Option<Duration>,
+) {
+    let mut retry = RETRY_MIN;
+    loop {
+        let attempt = Instant::now();
+        tokio::select! {
+            () = replication.promoted() => return,
+            result = sync(&leader, &key, &db, &replication) => {
+                if let Err(e) = result {
+                    warn!("Lost replication stream from {}: {}", leader, e);
+                }
+            }
+        }
+        let heard_from_leader = {
+            let mut state = replication.state();
+            state.connected = false;
+            state.last_contact.is_some_and(|contact| contact > attempt)
+        };
+        if heard_from_leader {
+            retry = RETRY_MIN;
+        }
+
+        if let Some(after) = failover_after {
+            if replication.leader_silent_for(after) {
+                warn!(
+                    "No contact from leader {} for {:?}, failing over",
+                    leader, after
+                );
+                replication.promote();
+                return;
+            }
+        }
+        tokio::select! {
+            () = replication.promoted() => return,
+            () = sleep(retry) => {}
+        }
+        retry = (retry * 2).min(RETRY_MAX);
+    }
+}
+
+/// Copy the leader's database, then apply its changes until the stream
+/// breaks
+async fn sync(leader: &str, key: &[u8], db: &Database, replication: &Replication) -> Result<()> {
+    let stream = timeout(LEADER_TIMEOUT, TcpStream::connect(leader))
+        .await
+        .map_err(|_| Error::Timeout)??;
+    let mut connection = Connection::new(stream);
+    connection.send(&[b"AUTH".to_vec(), key.to_vec()]).await?;
+    connection.receive().await?;
+    connection.send(&[b"REPLICA.SYNC".to_vec()]).await?;
+    info!("Syncing from leader {}", leader);
+
+    let staged = replication.stage_snapshot()?;
+    let mut applied = receive_snapshot(&mut connection, staged.path()).await?;
+    // Copying the snapshot in is blocking file I/O
+    let restore_db = db.clone();
+    let path = staged.path().to_path_buf();
+    tokio::task::spawn_blocking(move || restore_db.restore_snapshot(path))
+        .await
+        .map_err(|e| Error::Protocol(format!("Snapshot restore task failed: {}", e)))??;
+    drop(staged);
+    {
+        let mut state = replication.state();
+        state.connected = true;
+        state.applied = Some(applied);
+        state.last_contact = Some(Instant::now());
+    }
+    info!("Synced from leader {} at change {}", leader, applied);
+
+    loop {
+        let message = connection.message().await?;
+        replication.state().last_contact = Some(Instant::now());
+        match message.split_first() {
+            Some((kind, [seq, change @ ..])) if kind == b"change" => {
+                let seq = parse_seq(seq)?;
+                if seq != applied + 1 {
+                    return Err(Error::Protocol(format!(
+                        "Expected change {} from leader, got {}",
+                        applied + 1,
+                        seq
+                    )));
+                }
+                db.apply(&Change::from_args(change.to_vec())?)?;
+                applied = seq;
+                replication.state().applied = Some(applied);
+            }
+            Some((kind, [_])) if kind == b"ping" => {}
+            _ => {
+                return Err(Error::Protocol(
+                    "Unexpected message from leader".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Write the snapshot the leader sends to a file, returning the number of
+/// the last change it includes
+async fn receive_snapshot(connection: &mut Connection, path: &Path) -> Result<u64> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    loop {
+        let message = connection.message().await?;
+        match message.split_first() {
+            Some((kind, [chunk])) if kind == b"snapshot" => file.write_all(chunk).await?,
+            Some((kind, [seq])) if kind == b"synced" => {
+                file.sync_all().await?;
+                return parse_seq(seq);
+            }
+            _ => {
+                return Err(Error::Protocol(
+                    "Unexpected message from leader".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+fn parse_seq(seq: &[u8]) -> Result<u64> {
+    std::str::from_utf8(seq)
+        .ok()
+        .and_then(|seq| seq.parse().ok())
+        .ok_or_else(|| Error::Protocol("Invalid change number from leader".to_string()))
+}
+
+/// Encode a replication message as a RESP array of bulk strings
+pub(crate) fn encode(parts: Vec<Vec<u8>>) -> Vec<u8> {
+    RespValue::Array(parts.into_iter().map(RespValue::BulkString).collect()).encode()
+}
+
+/// A standby's connection to its leader
+struct Connection {
+    stream: TcpStream,
+    parser: RespParser,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            parser: RespParser::new(),
+            buffer: vec![0u8; 64 * 1024],
+        }
+    }
+
+    async fn send(&mut self, parts: &[Vec<u8>]) -> Result<()> {
+        self.stream.write_all(&encode(parts.to_vec())).await?;
+        Ok(())
+    }
+
+    /// The next value from the leader; an error reply becomes an error
+    async fn receive(&mut self) -> Result<RespValue> {
+        loop {
+            match self.parser.parse()? {
+                Some(RespValue::Error(message)) => {
+                    return Err(Error::Protocol(format!("Leader replied: {}", message)))
+                }
+                Some(value) => return Ok(value),
+                None => {}
+            }
+            let n = timeout(LEADER_TIMEOUT, self.stream.read(&mut self.buffer))
+                .await
+                .map_err(|_| Error::Timeout)??;
+            if n == 0 {
+                return Err(Error::ConnectionClosed);
+            }
+            self.parser.feed(&self.buffer[..n])?;
+        }
+    }
+
+    /// The next replication message from the leader, as its parts
+    async fn message(&mut self) -> Result<Vec<Vec<u8>>> {
+        match self.receive().await? {
+            RespValue::Array(parts) => parts
+                .into_iter()
+                .map(|part| match part {
+                    RespValue::BulkString(part) => Ok(part),
+                    _ => Err(Error::Protocol(
+                        "Unexpected message from leader".to_string(),
+                    )),
+                })
+                .collect(),
+            _ => Err(Error::Protocol(
+                "Unexpected message from leader".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::Settings;
+    use crate::storage::{HashOps, StringOps};
+    use crate::Server;
+    use tempfile::TempDir;
+
+    const KEY: &[u8] = b"replication-test-key";
+
+    async fn wait_for(mut done: impl FnMut() -> bool) {
+        for _ in 0..200 {
+            if done() {
+                return;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        panic!("timed out waiting for the standby");
+    }
+
+    #[tokio::test]
+    async fn test_standby_follows_leader_until_promoted() {
+        let temp = TempDir::new().unwrap();
+        let leader_db = Database::open(temp.path().join("leader.redb")).unwrap();
+        leader_db.set("before", b"snapshot").unwrap();
+        let server = Server::new("127.0.0.1:0", KEY.to_vec(), leader_db.clone())
+            .await
+            .unwrap()
+            .with_settings(Settings {
+                replication: Replication::default().with_staging_dir(temp.path().join("leader")),
+                ..Settings::default()
+            });
+        let addr = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+
+        let standby_db = Arc::new(Database::open(temp.path().join("standby.redb")).unwrap());
+        standby_db.set("stale", b"gone after sync").unwrap();
+        let replication =
+            Replication::standby(addr.clone()).with_staging_dir(temp.path().join("standby"));
+        tokio::spawn(follow(
+            addr.clone(),
+            KEY.to_vec(),
+            Arc::clone(&standby_db),
+            replication.clone(),
+            None,
+        ));
+
+        wait_for(|| replication.status(&standby_db)["connected"] == true).await;
+        assert_eq!(
+            standby_db.get("before").unwrap().as_deref(),
+            Some(&b"snapshot"[..])
+        );
+        assert!(standby_db.get("stale").unwrap().is_none());
+        for staging in ["leader", "standby"] {
+            let staged = std::fs::read_dir(temp.path().join(staging)).unwrap();
+            assert_eq!(staged.count(), 0, "{staging} kept a snapshot");
+        }
+
+        leader_db.hset("job:j1", "status", b"running").unwrap();
+        leader_db.hincrby("stats", "done", 2).unwrap();
+        leader_db.del("before").unwrap();
+        wait_for(|| standby_db.hget("stats", "done").unwrap().is_some()).await;
+        assert_eq!(
+            standby_db.hget("job:j1", "status").unwrap().as_deref(),
+            Some(&b"running"[..])
+        );
+        assert!(standby_db.get("before").unwrap().is_none());
+        assert!(matches!(
+            replication.check_writable("PLAN.SUBMIT"),
+            Err(Error::ReadOnly { .. })
+        ));
+        assert!(replication.check_writable("PLAN.GET").is_ok());
+
+        assert!(replication.promote());
+        assert!(!replication.promote());
+        replication.promoted().await;
+        assert!(replication.check_writable("PLAN.SUBMIT").is_ok());
+        assert_eq!(replication.status(&standby_db)["role"], "leader");
+    }
+
+    #[test]
+    fn test_staged_snapshots_are_private() {
+        assert!(Replication::default().stage_snapshot().is_err());
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("agq.redb")).unwrap();
+        db.set("secret:token", b"hunter2").unwrap();
+        let replication = Replication::default().with_staging_dir(temp.path().join("replica"));
+        let staged = replication.stage_snapshot().unwrap();
+        db.snapshot_for_replica(staged.path()).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&temp.path().join("replica")), 0o700);
+            assert_eq!(mode(staged.path()), 0o600);
+        }
+
+        let path = staged.path().to_path_buf();
+        drop(staged);
+        assert!(!path.exists());
+    }
+}
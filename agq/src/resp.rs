@@ -1,6 +1,9 @@
 //! RESP (`REdis` Serialization Protocol) parser and encoder
 //!
-//! Implements a subset of RESP protocol for AGQ communication.
+//! Implements a subset of RESP protocol for AGQ communication, enough for
+//! standard Redis clients: commands may arrive pipelined, split across reads,
+//! or as inline commands (`PING\r\n`), and replies can be encoded as RESP2
+//! or, after `HELLO 3`, RESP3.
 //! Reference: <https://redis.io/docs/reference/protocol-spec/>
 
 use crate::error::{Error, Result};
@@ -13,6 +16,43 @@ const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
 /// Maximum number of elements in an array
 const MAX_ARRAY_SIZE: usize = 1024;
 
+/// Maximum nesting of arrays
+const MAX_DEPTH: usize = 8;
+
+/// Maximum length of an inline command (64KB, as in Redis)
+const MAX_INLINE_SIZE: usize = 64 * 1024;
+
+/// Protocol version replies are encoded in, chosen by `HELLO`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// RESP2, which every connection starts with
+    #[default]
+    Resp2,
+    /// RESP3
+    Resp3,
+}
+
+impl Protocol {
+    /// Protocol for a `HELLO` version number, if supported
+    #[must_use]
+    pub fn from_version(version: i64) -> Option<Self> {
+        match version {
+            2 => Some(Self::Resp2),
+            3 => Some(Self::Resp3),
+            _ => None,
+        }
+    }
+
+    /// Version number reported by `HELLO`
+    #[must_use]
+    pub fn version(self) -> i64 {
+        match self {
+            Self::Resp2 => 2,
+            Self::Resp3 => 3,
+        }
+    }
+}
+
 /// RESP value types
 #[derive(Debug, Clone, PartialEq)]
 #[allow(clippy::module_name_repetitions)]
@@ -29,31 +69,59 @@ pub enum RespValue {
     Array(Vec<RespValue>),
     /// Null bulk string: $-1\r\n
     NullBulkString,
+    /// Map: %1\r\n+key\r\n+value\r\n (RESP3; a flat array in RESP2)
+    Map(Vec<(RespValue, RespValue)>),
 }
 
 impl RespValue {
     /// Encode RESP value to bytes
     #[must_use]
     pub fn encode(&self) -> Vec<u8> {
+        self.encode_as(Protocol::Resp2)
+    }
+
+    /// Encode RESP value to bytes in a protocol version
+    #[must_use]
+    pub fn encode_as(&self, protocol: Protocol) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.encode_into(protocol, &mut result);
+        result
+    }
+
+    fn encode_into(&self, protocol: Protocol, result: &mut Vec<u8>) {
         match self {
-            RespValue::SimpleString(s) => format!("+{s}\r\n").into_bytes(),
-            RespValue::Error(s) => format!("-{s}\r\n").into_bytes(),
-            RespValue::Integer(i) => format!(":{i}\r\n").into_bytes(),
+            RespValue::SimpleString(s) => result.extend_from_slice(format!("+{s}\r\n").as_bytes()),
+            RespValue::Error(s) => result.extend_from_slice(format!("-{s}\r\n").as_bytes()),
+            RespValue::Integer(i) => result.extend_from_slice(format!(":{i}\r\n").as_bytes()),
             RespValue::BulkString(data) => {
-                let mut result = format!("${}\r\n", data.len()).into_bytes();
+                result.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
                 result.extend_from_slice(data);
                 result.extend_from_slice(b"\r\n");
-                result
             }
             RespValue::Array(items) => {
                 let len = items.len();
-                let mut result = format!("*{len}\r\n").into_bytes();
+                result.extend_from_slice(format!("*{len}\r\n").as_bytes());
                 for item in items {
-                    result.extend_from_slice(&item.encode());
+                    item.encode_into(protocol, result);
+                }
+            }
+            RespValue::NullBulkString => match protocol {
+                Protocol::Resp2 => result.extend_from_slice(b"$-1\r\n"),
+                Protocol::Resp3 => result.extend_from_slice(b"_\r\n"),
+            },
+            RespValue::Map(entries) => {
+                let len = entries.len();
+                match protocol {
+                    Protocol::Resp2 => {
+                        result.extend_from_slice(format!("*{}\r\n", len * 2).as_bytes())
+                    }
+                    Protocol::Resp3 => result.extend_from_slice(format!("%{len}\r\n").as_bytes()),
+                }
+                for (key, value) in entries {
+                    key.encode_into(protocol, result);
+                    value.encode_into(protocol, result);
                 }
-                result
             }
-            RespValue::NullBulkString => b"$-1\r\n".to_vec(),
         }
     }
 
@@ -105,96 +173,74 @@ impl RespParser {
 
     /// Try to parse a complete RESP value from the buffer
     ///
+    /// Returns `None` until the buffer holds a complete value, so data may
+    /// arrive split across any number of reads; call repeatedly to take each
+    /// of several pipelined values. A line that does not start with a RESP
+    /// type byte is an inline command, parsed into an array of bulk strings
+    /// as Redis does. Blank inline lines are skipped.
+    ///
     /// # Errors
     ///
     /// Returns an error if the buffer contains invalid RESP data.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the cursor position overflows, which should never happen in practice.
     pub fn parse(&mut self) -> Result<Option<RespValue>> {
-        if self.buffer.is_empty() {
-            return Ok(None);
-        }
-
-        let mut cursor = std::io::Cursor::new(&self.buffer[..]);
-        match self.parse_value(&mut cursor) {
-            Ok(value) => {
-                let pos = usize::try_from(cursor.position()).expect("position overflow");
-                self.buffer.advance(pos);
-                Ok(Some(value))
-            }
-            Err(Error::Protocol(e)) if self.buffer.len() < MAX_MESSAGE_SIZE => {
-                // Incomplete message, wait for more data
-                // Log it to debug why it's incomplete
-                tracing::debug!("RespParser: Incomplete message or protocol error: {}", e);
-                Ok(None)
+        loop {
+            let Some(&first) = self.buffer.first() else {
+                return Ok(None);
+            };
+
+            let mut pos = 0;
+            let inline = !matches!(first, b'+' | b'-' | b':' | b'$' | b'*');
+            let value = if inline {
+                Self::parse_inline(&self.buffer, &mut pos)?
+            } else {
+                Self::parse_value(&self.buffer, &mut pos, 0)?
+            };
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            self.buffer.advance(pos);
+
+            if !(inline && value == RespValue::Array(Vec::new())) {
+                return Ok(Some(value));
             }
-            Err(e) => Err(e),
         }
     }
 
-    /// Parse a RESP value from cursor
-    fn parse_value<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let mut type_byte = [0u8; 1];
-        cursor
-            .read_exact(&mut type_byte)
-            .map_err(|_| Error::Protocol("Incomplete message".to_string()))?;
-
-        match type_byte[0] {
-            b'+' => self.parse_simple_string(cursor),
-            b'-' => self.parse_error(cursor),
-            b':' => self.parse_integer(cursor),
-            b'$' => self.parse_bulk_string(cursor),
-            b'*' => self.parse_array(cursor),
-            _ => Err(Error::Protocol(format!(
-                "Unknown RESP type: {}",
-                type_byte[0]
-            ))),
+    /// Parse a RESP value starting at `pos`, or `None` if it is incomplete
+    fn parse_value(buf: &[u8], pos: &mut usize, depth: usize) -> Result<Option<RespValue>> {
+        let Some(&type_byte) = buf.get(*pos) else {
+            return Ok(None);
+        };
+        *pos += 1;
+
+        match type_byte {
+            b'+' => Ok(Self::read_line(buf, pos)?
+                .map(|line| Self::line_to_string(line).map(RespValue::SimpleString))
+                .transpose()?),
+            b'-' => Ok(Self::read_line(buf, pos)?
+                .map(|line| Self::line_to_string(line).map(RespValue::Error))
+                .transpose()?),
+            b':' => Ok(Self::read_line(buf, pos)?
+                .map(|line| Self::parse_integer(line, "integer").map(RespValue::Integer))
+                .transpose()?),
+            b'$' => Self::parse_bulk_string(buf, pos),
+            b'*' => Self::parse_array(buf, pos, depth),
+            _ => Err(Error::Protocol(format!("Unknown RESP type: {type_byte}"))),
         }
     }
 
-    /// Parse simple string: +OK\r\n
-    #[allow(clippy::unused_self)]
-    fn parse_simple_string<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let line = Self::read_line(cursor)?;
-        Ok(RespValue::SimpleString(line))
-    }
-
-    /// Parse error: -ERR message\r\n
-    #[allow(clippy::unused_self)]
-    fn parse_error<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let line = Self::read_line(cursor)?;
-        Ok(RespValue::Error(line))
-    }
-
-    /// Parse integer: :1000\r\n
-    #[allow(clippy::unused_self)]
-    fn parse_integer<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let line = Self::read_line(cursor)?;
-        let num = line
-            .parse::<i64>()
-            .map_err(|_| Error::Protocol("Invalid integer".to_string()))?;
-        Ok(RespValue::Integer(num))
-    }
-
     /// Parse bulk string: $6\r\nfoobar\r\n
     ///
     /// # Security
     /// Validates size before allocating to prevent `DoS`
-    #[allow(clippy::unused_self)]
-    fn parse_bulk_string<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let size_line = Self::read_line(cursor)?;
-        let size = size_line
-            .parse::<i64>()
-            .map_err(|_| Error::Protocol("Invalid bulk string size".to_string()))?;
+    fn parse_bulk_string(buf: &[u8], pos: &mut usize) -> Result<Option<RespValue>> {
+        let Some(size_line) = Self::read_line(buf, pos)? else {
+            return Ok(None);
+        };
+        let size = Self::parse_integer(size_line, "bulk string size")?;
 
         if size == -1 {
-            return Ok(RespValue::NullBulkString);
-        }
-
-        if size < 0 {
-            return Err(Error::Protocol("Invalid bulk string size".to_string()));
+            return Ok(Some(RespValue::NullBulkString));
         }
 
         // Security: Check size limit before allocating
@@ -204,36 +250,29 @@ impl RespParser {
             return Err(Error::MessageTooLarge);
         }
 
-        let mut data = vec![0u8; size];
-        cursor
-            .read_exact(&mut data)
-            .map_err(|_| Error::Protocol("Incomplete bulk string".to_string()))?;
-
-        let mut crlf = [0u8; 2];
-        cursor
-            .read_exact(&mut crlf)
-            .map_err(|_| Error::Protocol("Missing CRLF after bulk string".to_string()))?;
-
-        if crlf != [b'\r', b'\n'] {
+        let end = *pos + size;
+        if buf.len() < end + 2 {
+            return Ok(None);
+        }
+        if &buf[end..end + 2] != b"\r\n" {
             return Err(Error::Protocol("Invalid CRLF".to_string()));
         }
 
-        Ok(RespValue::BulkString(data))
+        let data = buf[*pos..end].to_vec();
+        *pos = end + 2;
+        Ok(Some(RespValue::BulkString(data)))
     }
 
     /// Parse array: *2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n
     ///
     /// # Security
-    /// Validates array size to prevent `DoS` via deeply nested structures
-    fn parse_array<R: std::io::Read>(&self, cursor: &mut R) -> Result<RespValue> {
-        let size_line = Self::read_line(cursor)?;
-        let size = size_line
-            .parse::<i64>()
-            .map_err(|_| Error::Protocol("Invalid array size".to_string()))?;
-
-        if size < 0 {
-            return Err(Error::Protocol("Invalid array size".to_string()));
-        }
+    /// Validates array size and nesting to prevent `DoS` via deeply nested
+    /// structures
+    fn parse_array(buf: &[u8], pos: &mut usize, depth: usize) -> Result<Option<RespValue>> {
+        let Some(size_line) = Self::read_line(buf, pos)? else {
+            return Ok(None);
+        };
+        let size = Self::parse_integer(size_line, "array size")?;
 
         // Security: Limit array size to prevent DoS
         let size =
@@ -243,44 +282,154 @@ impl RespParser {
                 "Array size {size} exceeds maximum {MAX_ARRAY_SIZE}"
             )));
         }
+        if depth >= MAX_DEPTH {
+            return Err(Error::Protocol(format!(
+                "Arrays nested deeper than {MAX_DEPTH}"
+            )));
+        }
 
         let mut items = Vec::with_capacity(size);
         for _ in 0..size {
-            items.push(self.parse_value(cursor)?);
+            match Self::parse_value(buf, pos, depth + 1)? {
+                Some(item) => items.push(item),
+                None => return Ok(None),
+            }
         }
 
-        Ok(RespValue::Array(items))
+        Ok(Some(RespValue::Array(items)))
     }
 
-    /// Read a line until \r\n
-    fn read_line(cursor: &mut dyn std::io::Read) -> Result<String> {
-        let mut line = Vec::new();
-        let mut last_byte = 0u8;
-
-        loop {
-            let mut byte = [0u8; 1];
-            cursor
-                .read_exact(&mut byte)
-                .map_err(|_| Error::Protocol("Incomplete line".to_string()))?;
-
-            if byte[0] == b'\n' && last_byte == b'\r' {
-                line.pop(); // Remove \r
-                break;
+    /// Parse an inline command: arguments separated by spaces, optionally
+    /// quoted, on a line ending in \n or \r\n
+    fn parse_inline(buf: &[u8], pos: &mut usize) -> Result<Option<RespValue>> {
+        let Some(end) = buf.iter().position(|&b| b == b'\n') else {
+            if buf.len() > MAX_INLINE_SIZE {
+                return Err(Error::Protocol("Inline command too long".to_string()));
             }
+            return Ok(None);
+        };
+        if end > MAX_INLINE_SIZE {
+            return Err(Error::Protocol("Inline command too long".to_string()));
+        }
 
-            line.push(byte[0]);
-            last_byte = byte[0];
+        let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+        let args = split_inline(line)?;
+        *pos = end + 1;
+        Ok(Some(RespValue::Array(
+            args.into_iter().map(RespValue::BulkString).collect(),
+        )))
+    }
 
-            // Security: Prevent infinite line reads
-            if line.len() > MAX_MESSAGE_SIZE {
+    /// Read a line ending in \r\n, or `None` if it is incomplete
+    fn read_line<'a>(buf: &'a [u8], pos: &mut usize) -> Result<Option<&'a [u8]>> {
+        let rest = &buf[*pos..];
+        let Some(end) = rest.windows(2).position(|pair| pair == b"\r\n") else {
+            // Security: Prevent unbounded line reads
+            if rest.len() > MAX_MESSAGE_SIZE {
                 return Err(Error::MessageTooLarge);
             }
-        }
+            return Ok(None);
+        };
+        *pos += end + 2;
+        Ok(Some(&rest[..end]))
+    }
 
-        str::from_utf8(&line)
+    fn line_to_string(line: &[u8]) -> Result<String> {
+        str::from_utf8(line)
             .map(std::string::ToString::to_string)
             .map_err(|_| Error::Protocol("Invalid UTF-8 in line".to_string()))
     }
+
+    fn parse_integer(line: &[u8], what: &str) -> Result<i64> {
+        str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.parse::<i64>().ok())
+            .ok_or_else(|| Error::Protocol(format!("Invalid {what}")))
+    }
+}
+
+/// Split an inline command into arguments
+///
+/// Follows Redis: arguments are separated by spaces or tabs, and may be
+/// quoted with `"..."`, which understands `\n`, `\r`, `\t`, `\xHH` and `\`
+/// escapes, or `'...'`, which only understands `\'`.
+fn split_inline(line: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let unbalanced = || Error::Protocol("Unbalanced quotes in request".to_string());
+    let is_space = |b: u8| b == b' ' || b == b'\t';
+
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while line.get(i).copied().is_some_and(is_space) {
+            i += 1;
+        }
+        let Some(&first) = line.get(i) else {
+            return Ok(args);
+        };
+
+        let mut arg = Vec::new();
+        match first {
+            b'"' => {
+                i += 1;
+                loop {
+                    match *line.get(i).ok_or_else(unbalanced)? {
+                        b'"' => break,
+                        b'\\' if i + 1 < line.len() => {
+                            let hex = line
+                                .get(i + 2..i + 4)
+                                .and_then(|hex| str::from_utf8(hex).ok())
+                                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                            match (line[i + 1], hex) {
+                                (b'x', Some(byte)) => {
+                                    arg.push(byte);
+                                    i += 2;
+                                }
+                                (b'n', _) => arg.push(b'\n'),
+                                (b'r', _) => arg.push(b'\r'),
+                                (b't', _) => arg.push(b'\t'),
+                                (other, _) => arg.push(other),
+                            }
+                            i += 2;
+                        }
+                        byte => {
+                            arg.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+            }
+            b'\'' => {
+                i += 1;
+                loop {
+                    match *line.get(i).ok_or_else(unbalanced)? {
+                        b'\'' => break,
+                        b'\\' if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        byte => {
+                            arg.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+            }
+            _ => {
+                while let Some(&byte) = line.get(i).filter(|&&b| !is_space(b)) {
+                    arg.push(byte);
+                    i += 1;
+                }
+            }
+        }
+
+        // A closing quote must end the argument
+        if line.get(i).is_some_and(|&b| !is_space(b)) {
+            return Err(unbalanced());
+        }
+        args.push(arg);
+    }
 }
 
 impl Default for RespParser {
@@ -389,6 +538,83 @@ mod tests {
         assert_eq!(value, RespValue::NullBulkString);
     }
 
+    #[test]
+    fn test_parse_pipelined_and_split_messages() {
+        let stream: &[u8] = b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$3\r\nfoo\r\n";
+        // Feed a byte at a time, as if every byte arrived in its own segment
+        let mut parser = RespParser::new();
+        let mut values = Vec::new();
+        for byte in stream {
+            parser.feed(std::slice::from_ref(byte)).unwrap();
+            while let Some(value) = parser.parse().unwrap() {
+                values.push(value);
+            }
+        }
+        assert_eq!(values.len(), 2);
+        assert_eq!(
+            values[1],
+            RespValue::Array(vec![
+                RespValue::BulkString(b"GET".to_vec()),
+                RespValue::BulkString(b"foo".to_vec()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_inline_commands() {
+        let mut parser = RespParser::new();
+        parser
+            .feed(b"\r\nSET  key \"a b\\x21\\n\" 'it\\'s'\nPING\r\n")
+            .unwrap();
+        let bulk = |arg: &[u8]| RespValue::BulkString(arg.to_vec());
+        assert_eq!(
+            parser.parse().unwrap().unwrap(),
+            RespValue::Array(vec![
+                bulk(b"SET"),
+                bulk(b"key"),
+                bulk(b"a b!\n"),
+                bulk(b"it's")
+            ])
+        );
+        assert_eq!(
+            parser.parse().unwrap().unwrap(),
+            RespValue::Array(vec![bulk(b"PING")])
+        );
+        assert!(parser.parse().unwrap().is_none());
+
+        for line in [&b"GET \"key\r\n"[..], b"GET 'a'b\r\n"] {
+            let mut parser = RespParser::new();
+            parser.feed(line).unwrap();
+            assert!(parser.parse().is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_data_without_waiting() {
+        for data in [&b"*1\r\n?\r\n"[..], b"$3\r\nfoobar\r\n", b"*x\r\n"] {
+            let mut parser = RespParser::new();
+            parser.feed(data).unwrap();
+            assert!(parser.parse().is_err(), "{data:?}");
+        }
+
+        let mut parser = RespParser::new();
+        parser.feed(&b"*1\r\n".repeat(MAX_DEPTH + 1)).unwrap();
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_encode_resp3() {
+        let value = RespValue::Map(vec![(
+            RespValue::BulkString(b"proto".to_vec()),
+            RespValue::NullBulkString,
+        )]);
+        assert_eq!(value.encode(), b"*2\r\n$5\r\nproto\r\n$-1\r\n");
+        assert_eq!(
+            value.encode_as(Protocol::Resp3),
+            b"%1\r\n$5\r\nproto\r\n_\r\n"
+        );
+    }
+
     #[test]
     fn test_as_string_conversion() {
         let value = RespValue::BulkString(b"hello".to_vec());
@@ -0,0 +1,413 @@
+//! Retention of finished jobs, their output, and artifacts
+//!
+//! Without retention the database keeps every job record, every result and
+//! log, and every artifact forever. A [`Policy`] sets how long each is kept
+//! after a job finishes, and [`sweep`] deletes whatever has outlived it.
+//! The retention sweeper runs it periodically; JOB.PURGE removes jobs on
+//! demand with [`purge_job`] and [`purge_finished_before`].
+//!
+//! A job counts as finished once it or its worker reports a terminal
+//! status, and is aged from its completion time (or its creation time if
+//! it never recorded one).
+
+use crate::artifacts::ArtifactStore;
+use crate::error::Result;
+use crate::job::{Job, JobStatus};
+use crate::orchestrator::{
+    event_key, AWAITING_EVENT, DELAYED_QUEUE, JOBS_INDEX, THROTTLED_QUEUE, UNSCHEDULABLE_QUEUE,
+};
+use crate::storage::{Database, HashOps, ListOps, SortedSetOps, StringOps};
+use crate::workers::InternalJob;
+use tracing::{info, warn};
+
+/// Sorted set of artifact hashes, scored by when they were last stored
+pub const ARTIFACTS_INDEX: &str = "artifacts:all";
+
+/// Dead-letter queue of plan submissions that failed for good
+const PLAN_SUBMIT_DLQ: &str = "agq:internal:plan.submit:dlq";
+
+/// Keys a worker writes for a job's output, as `job:<id>:<suffix>`
+const OUTPUT_SUFFIXES: &[&str] = &[
+    "result",
+    "stdout",
+    "stdout_encoding",
+    "stdout_artifact",
+    "stderr",
+    "stderr_encoding",
+    "stderr_artifact",
+    "artifacts",
+];
+
+/// Lists of log chunks streamed with JOB.LOG, as `job:<id>:log:<stream>`
+const LOG_STREAMS: &[&str] = &["stdout", "stderr"];
+
+/// Other per-job keys, removed along with the job record
+const STATE_SUFFIXES: &[&str] = &["status", "worker", "retry"];
+
+/// How long finished work is kept, in seconds; `None` keeps it forever
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Policy {
+    /// Finished job records, with everything else stored for the job
+    pub job_ttl: Option<u64>,
+    /// Output of finished jobs: results, stdout/stderr, logs, and artifact
+    /// lists
+    pub result_ttl: Option<u64>,
+    /// Artifacts, counted from the last time they were stored
+    pub artifact_ttl: Option<u64>,
+    /// Plan submissions in the dead-letter queue
+    pub queue_ttl: Option<u64>,
+}
+
+impl Policy {
+    /// Whether anything is ever deleted
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.job_ttl.is_some()
+            || self.result_ttl.is_some()
+            || self.artifact_ttl.is_some()
+            || self.queue_ttl.is_some()
+    }
+}
+
+/// What a sweep deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Swept {
+    pub jobs: usize,
+    pub results: usize,
+    pub artifacts: usize,
+    pub queue_entries: usize,
+}
+
+impl Swept {
+    /// Whether the sweep deleted nothing
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Delete everything the policy no longer keeps
+///
+/// Artifacts are deleted from the store they were written to.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read or written
+pub fn sweep(db: &Database, policy: &Policy, artifacts: &ArtifactStore, now: u64) -> Result<Swept> {
+    let mut swept = Swept::default();
+
+    if let Some(ttl) = policy.job_ttl {
+        swept.jobs = purge_finished_before(db, now.saturating_sub(ttl))?;
+    }
+
+    if let Some(ttl) = policy.result_ttl {
+        let cutoff = now.saturating_sub(ttl);
+        for job in jobs_created_before(db, cutoff)? {
+            if finished_at(db, &job)?.is_some_and(|finished| finished <= cutoff) {
+                swept.results += delete_job_output(db, &job.id)?;
+            }
+        }
+    }
+
+    if let Some(ttl) = policy.artifact_ttl {
+        let cutoff = now.saturating_sub(ttl) as f64;
+        for (hash, _stored_at) in db.zrangebyscore(ARTIFACTS_INDEX, 0.0, cutoff)? {
+            artifacts.delete(db, &String::from_utf8_lossy(&hash))?;
+            swept.artifacts += 1;
+        }
+    }
+
+    if let Some(ttl) = policy.queue_ttl {
+        let cutoff = now.saturating_sub(ttl);
+        for entry in db.lrange(PLAN_SUBMIT_DLQ, 0, -1)? {
+            let expired = serde_json::from_slice::<InternalJob>(&entry)
+                .map_or(true, |failed| failed.timestamp <= cutoff);
+            if expired {
+                swept.queue_entries +=
+                    usize::try_from(db.lrem(PLAN_SUBMIT_DLQ, 1, &entry)?).unwrap_or_default();
+            }
+        }
+    }
+
+    if !swept.is_empty() {
+        info!(
+            "Retention removed {} jobs, {} result keys, {} artifacts, {} dead-letter entries",
+            swept.jobs, swept.results, swept.artifacts, swept.queue_entries
+        );
+    }
+    Ok(swept)
+}
+
+/// Delete every job that finished at or before `cutoff`
+///
+/// Returns the number of jobs deleted.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read or written
+pub fn purge_finished_before(db: &Database, cutoff: u64) -> Result<usize> {
+    let mut purged = 0;
+    for job in jobs_created_before(db, cutoff)? {
+        if finished_at(db, &job)?.is_some_and(|finished| finished <= cutoff) {
+            remove_job(db, &job)?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Delete a finished job and everything stored for it
+///
+/// An Action whose last job is deleted is removed as well. Returns
+/// `Ok(None)` if the job does not exist, and `Ok(Some(false))` if it has
+/// not finished and was kept.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read or written
+pub fn purge_job(db: &Database, job_id: &str) -> Result<Option<bool>> {
+    let Some(job) = load_job(db, job_id)? else {
+        return Ok(None);
+    };
+    if finished_at(db, &job)?.is_none() {
+        return Ok(Some(false));
+    }
+    remove_job(db, &job)?;
+    Ok(Some(true))
+}
+
+/// When a job finished, or `None` if it is still pending or running
+fn finished_at(db: &Database, job: &Job) -> Result<Option<u64>> {
+    let reported = crate::server::reported_job_status(db, job)?;
+    let finished = job.status.is_terminal()
+        || serde_json::from_value::<JobStatus>(reported.as_str().into())
+            .is_ok_and(|status| status.is_terminal());
+    Ok(finished.then(|| job.completed_at.unwrap_or(job.created_at)))
+}
+
+/// Jobs in the `jobs:all` index created at or before `cutoff`
+///
+/// A job cannot have finished before it was created, so older jobs are the
+/// only candidates for any cutoff.
+fn jobs_created_before(db: &Database, cutoff: u64) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for (member, _created_at) in db.zrangebyscore(JOBS_INDEX, 0.0, cutoff as f64)? {
+        let job_id = String::from_utf8_lossy(&member);
+        match load_job(db, &job_id)? {
+            Some(job) => jobs.push(job),
+            None => {
+                db.zrem(JOBS_INDEX, &member)?;
+            }
+        }
+    }
+    Ok(jobs)
+}
+
+fn load_job(db: &Database, job_id: &str) -> Result<Option<Job>> {
+    let Some(json) = db.get(&format!("job:{}", job_id))? else {
+        return Ok(None);
+    };
+    match serde_json::from_slice(&json) {
+        Ok(job) => Ok(Some(job)),
+        Err(e) => {
+            warn!("Skipping job {} with unreadable JSON: {}", job_id, e);
+            Ok(None)
+        }
+    }
+}
+
+/// Delete `job:<id>:<suffix>` keys, returning how many existed
+fn delete_job_keys(db: &Database, job_id: &str, suffixes: &[&str]) -> Result<usize> {
+    let mut deleted = 0;
+    for suffix in suffixes {
+        if db.del(&format!("job:{}:{}", job_id, suffix))? {
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+/// Delete a job's output keys and log lists, returning how many existed
+fn delete_job_output(db: &Database, job_id: &str) -> Result<usize> {
+    let mut deleted = delete_job_keys(db, job_id, OUTPUT_SUFFIXES)?;
+    for stream in LOG_STREAMS {
+        // Lists are emptied rather than deleted as a key
+        let key = format!("job:{}:log:{}", job_id, stream);
+        if db.llen(&key)? > 0 {
+            while db.rpop(&key)?.is_some() {}
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+fn remove_job(db: &Database, job: &Job) -> Result<()> {
+    crate::audit::job_changed(db, job, Some(job.status), "deleted");
+    delete_job_output(db, &job.id)?;
+    delete_job_keys(db, &job.id, STATE_SUFFIXES)?;
+    db.del(&format!("job:{}", job.id))?;
+    if let Some(token) = &job.event_token {
+        db.del(&event_key(token))?;
+    }
+    for index in [
+        JOBS_INDEX,
+        DELAYED_QUEUE,
+        THROTTLED_QUEUE,
+        UNSCHEDULABLE_QUEUE,
+        AWAITING_EVENT,
+    ] {
+        db.zrem(index, job.id.as_bytes())?;
+    }
+
+    let action_jobs = format!("action:{}:jobs", job.action_id);
+    db.lrem(&action_jobs, 0, job.id.as_bytes())?;
+    if db.llen(&action_jobs)? == 0 {
+        for field in db.hgetall(&format!("action:{}", job.action_id))? {
+            db.hdel(&format!("action:{}", job.action_id), &field.0)?;
+        }
+        db.lrem(
+            &format!("plan:{}:actions", job.plan_id),
+            0,
+            job.action_id.as_bytes(),
+        )?;
+        db.zrem("actions:all", job.action_id.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orchestrator::Orchestrator;
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    /// Submit job `id` of `action_id`, created at `created_at`
+    fn submit(db: &Database, id: &str, action_id: &str, created_at: u64) {
+        let mut job = Job::new(
+            id.to_string(),
+            action_id.to_string(),
+            "plan_r".to_string(),
+            1,
+            "echo".to_string(),
+            vec![],
+            serde_json::json!({}),
+            vec![],
+        );
+        job.created_at = created_at;
+        Orchestrator::new(db).submit_jobs(vec![job]).unwrap();
+        db.lpush(&format!("action:{}:jobs", action_id), id.as_bytes())
+            .unwrap();
+        let action_key = format!("action:{}", action_id);
+        if db.hlen(&action_key).unwrap() == 0 {
+            db.hset(&action_key, "plan_id", b"plan_r").unwrap();
+            db.lpush("plan:plan_r:actions", action_id.as_bytes())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sweep_expires_finished_work() {
+        let (db, _temp) = test_db();
+        submit(&db, "old", "action_old", 100);
+        submit(&db, "recent", "action_new", 900);
+        submit(&db, "running", "action_new", 100);
+        for (id, status) in [("old", "completed"), ("recent", "failed")] {
+            db.set(&format!("job:{}:status", id), status.as_bytes())
+                .unwrap();
+            db.set(&format!("job:{}:result", id), b"{}").unwrap();
+            db.lpush(&format!("job:{}:log:stdout", id), b"chunk")
+                .unwrap();
+        }
+        db.set("job:running:status", b"running").unwrap();
+        db.set("artifact:aaaa", b"data").unwrap();
+        db.zadd(ARTIFACTS_INDEX, 100.0, b"aaaa").unwrap();
+
+        let policy = Policy {
+            result_ttl: Some(500),
+            artifact_ttl: Some(500),
+            ..Policy::default()
+        };
+        let swept = sweep(&db, &policy, &ArtifactStore::Database, 1000).unwrap();
+        assert_eq!((swept.results, swept.artifacts, swept.jobs), (2, 1, 0));
+        assert!(!db.exists("job:old:result").unwrap());
+        assert_eq!(db.llen("job:old:log:stdout").unwrap(), 0);
+        assert!(db.exists("job:old").unwrap());
+        assert!(db.exists("job:recent:result").unwrap());
+        assert!(!db.exists("artifact:aaaa").unwrap());
+
+        let policy = Policy {
+            job_ttl: Some(500),
+            ..Policy::default()
+        };
+        assert_eq!(
+            sweep(&db, &policy, &ArtifactStore::Database, 1000)
+                .unwrap()
+                .jobs,
+            1
+        );
+        assert!(!db.exists("job:old").unwrap());
+        assert!(!db.exists("job:old:status").unwrap());
+        assert!(db.exists("job:running").unwrap());
+        assert_eq!(db.zcard(JOBS_INDEX).unwrap(), 2);
+
+        // The emptied Action goes with its last job
+        assert_eq!(db.hlen("action:action_old").unwrap(), 0);
+        assert_eq!(
+            db.lrange("plan:plan_r:actions", 0, -1).unwrap(),
+            [b"action_new".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_purge_job_keeps_unfinished_jobs() {
+        let (db, _temp) = test_db();
+        submit(&db, "done", "action_p", 100);
+        submit(&db, "busy", "action_p", 100);
+        db.set("job:done:status", b"completed").unwrap();
+
+        assert_eq!(purge_job(&db, "busy").unwrap(), Some(false));
+        assert_eq!(purge_job(&db, "done").unwrap(), Some(true));
+        assert_eq!(purge_job(&db, "done").unwrap(), None);
+        assert_eq!(
+            db.lrange("action:action_p:jobs", 0, -1).unwrap(),
+            [b"busy".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_sweep_expires_dead_letters() {
+        let (db, _temp) = test_db();
+        for (id, timestamp) in [("old", 100), ("new", 900)] {
+            let failed = InternalJob {
+                id: id.to_string(),
+                operation: "plan.submit".to_string(),
+                entity_id: "plan".to_string(),
+                payload: "{}".to_string(),
+                timestamp,
+                retry_count: 3,
+                max_retries: 3,
+            };
+            db.lpush(PLAN_SUBMIT_DLQ, &serde_json::to_vec(&failed).unwrap())
+                .unwrap();
+        }
+
+        let policy = Policy {
+            queue_ttl: Some(500),
+            ..Policy::default()
+        };
+        assert_eq!(
+            sweep(&db, &policy, &ArtifactStore::Database, 1000)
+                .unwrap()
+                .queue_entries,
+            1
+        );
+        assert_eq!(db.llen(PLAN_SUBMIT_DLQ).unwrap(), 1);
+    }
+}
@@ -0,0 +1,172 @@
+//! Matching the tags jobs require to the tags workers have
+//!
+//! Tasks list the worker tags their jobs need, e.g. `gpu` or `vram:24`.
+//! Workers register theirs at `worker:<id>:tags` as a comma-separated list.
+//! Each distinct set of requirements gets its own ready queue, so a worker
+//! only takes jobs from queues whose requirements it meets:
+//!
+//! - no requirements: `queue:default`
+//! - just `gpu`: `queue:gpu`
+//! - anything else: `queue:tags:<tag>+<tag>...`, with the tags sorted
+//!
+//! The names are defined in [`agenix_queue`], which AGW shares.
+//!
+//! A job whose requirements no live worker meets is not queued. It waits in
+//! `queue:unschedulable` until a worker that meets them sends a heartbeat.
+
+use crate::error::Result;
+use crate::storage::{Database, HashOps, SortedSetOps, StringOps};
+use std::collections::{BTreeSet, HashSet};
+
+pub use agenix_queue::{queue_for, queue_requirements, requirements, DEFAULT_QUEUE, GPU_QUEUE};
+
+/// Sorted set of `queue:tags:` ready queues, scored by when first used
+pub const TAGGED_QUEUES: &str = "queues:tagged";
+
+/// Every ready queue jobs have been routed to
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn ready_queues(db: &Database) -> Result<Vec<String>> {
+    let mut queues = vec![DEFAULT_QUEUE.to_string(), GPU_QUEUE.to_string()];
+    for (queue, _first_used) in db.zrange(TAGGED_QUEUES, 0, -1)? {
+        queues.push(String::from_utf8_lossy(&queue).into_owned());
+    }
+    Ok(queues)
+}
+
+/// Tags a worker registered
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn worker_tags(db: &Database, worker_id: &str) -> Result<HashSet<String>> {
+    let tags = db
+        .get(&format!("worker:{}:tags", worker_id))?
+        .unwrap_or_default();
+    Ok(String::from_utf8_lossy(&tags)
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Workers whose last heartbeat has not expired
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn live_workers(db: &Database, now: u64) -> Result<Vec<String>> {
+    let mut workers = Vec::new();
+    for (worker_id, _last_seen) in db.zrange("workers:all", 0, -1)? {
+        let worker_id = String::from_utf8_lossy(&worker_id).into_owned();
+        let expire_at = db
+            .hget(&format!("worker:{}", worker_id), "expire_at")?
+            .and_then(|bytes| String::from_utf8_lossy(&bytes).parse::<u64>().ok());
+        if expire_at.is_some_and(|expire_at| now < expire_at) {
+            workers.push(worker_id);
+        }
+    }
+    Ok(workers)
+}
+
+/// Live workers that have every required tag
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn eligible_workers(
+    db: &Database,
+    requirements: &BTreeSet<&str>,
+    now: u64,
+) -> Result<Vec<String>> {
+    let mut eligible = Vec::new();
+    for worker_id in live_workers(db, now)? {
+        let tags = worker_tags(db, &worker_id)?;
+        if requirements.iter().all(|tag| tags.contains(*tag)) {
+            eligible.push(worker_id);
+        }
+    }
+    Ok(eligible)
+}
+
+/// Whether some live worker can run jobs with a set of requirements
+///
+/// Jobs without requirements can always be queued, whether or not any
+/// worker is connected.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn is_satisfiable(db: &Database, requirements: &BTreeSet<&str>, now: u64) -> Result<bool> {
+    Ok(requirements.is_empty() || !eligible_workers(db, requirements, now)?.is_empty())
+}
+
+/// Ready queues a worker can take jobs from, most specific first
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be read
+pub fn worker_queues(db: &Database, worker_id: &str) -> Result<Vec<String>> {
+    let tags = worker_tags(db, worker_id)?;
+    let mut queues: Vec<(usize, String)> = ready_queues(db)?
+        .into_iter()
+        .filter_map(|queue| {
+            let requirements = queue_requirements(&queue)?;
+            let specificity = requirements.len();
+            requirements
+                .iter()
+                .all(|tag| tags.contains(*tag))
+                .then_some((specificity, queue))
+        })
+        .collect();
+    queues.sort_by_key(|(specificity, _)| std::cmp::Reverse(*specificity));
+    Ok(queues.into_iter().map(|(_, queue)| queue).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn add_worker(db: &Database, worker_id: &str, tags: &str, expire_at: u64) {
+        db.zadd("workers:all", 0.0, worker_id.as_bytes()).unwrap();
+        db.hset(
+            &format!("worker:{}", worker_id),
+            "expire_at",
+            expire_at.to_string().as_bytes(),
+        )
+        .unwrap();
+        db.set(&format!("worker:{}:tags", worker_id), tags.as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_eligible_workers() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        add_worker(&db, "cpu-1", "cpu", 200);
+        add_worker(&db, "gpu-1", "cpu,gpu,vram:24", 200);
+        add_worker(&db, "gpu-2", "gpu,network", 50);
+
+        let needs = |tags: &[&'static str]| tags.iter().copied().collect::<BTreeSet<_>>();
+        assert_eq!(
+            eligible_workers(&db, &needs(&["gpu"]), 100).unwrap(),
+            ["gpu-1"]
+        );
+        assert!(!is_satisfiable(&db, &needs(&["network"]), 100).unwrap());
+        assert!(is_satisfiable(&db, &needs(&["network"]), 10).unwrap());
+        assert!(is_satisfiable(&db, &needs(&[]), 100).unwrap());
+
+        db.zadd(TAGGED_QUEUES, 1.0, b"queue:tags:gpu+vram:24")
+            .unwrap();
+        db.zadd(TAGGED_QUEUES, 2.0, b"queue:tags:network").unwrap();
+        assert_eq!(
+            worker_queues(&db, "gpu-1").unwrap(),
+            ["queue:tags:gpu+vram:24", "queue:gpu", "queue:default"]
+        );
+        assert_eq!(worker_queues(&db, "cpu-1").unwrap(), ["queue:default"]);
+    }
+}
@@ -0,0 +1,314 @@
+//! Scheduled and recurring Plan runs
+//!
+//! A Schedule creates an Action from a stored Plan at a set time: once,
+//! after a delay, or repeatedly on a cron expression. Schedules are stored
+//! as JSON at `schedule:<id>` and indexed in the `schedules:due` sorted set
+//! by their next run time, which the plan scheduler thread polls.
+
+use crate::error::{Error, Result};
+use crate::storage::{Database, SortedSetOps, StringOps};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Sorted set of schedule IDs, scored by next run time
+pub const DUE_INDEX: &str = "schedules:due";
+
+/// Maximum number of schedules, to bound the scheduler's work per tick
+pub const MAX_SCHEDULES: u64 = 1000;
+
+/// A Plan to run at a later time, once or on a cron expression
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub schedule_id: String,
+    pub plan_id: String,
+
+    /// Cron expression (five fields, UTC) for recurring schedules; one-off
+    /// schedules have none and are removed after they run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+
+    /// Inputs of each Action the schedule creates
+    pub inputs: Vec<serde_json::Value>,
+
+    /// Timestamp of the next run
+    pub next_run_at: u64,
+
+    /// Timestamp of the last run
+    #[serde(default)]
+    pub last_run_at: Option<u64>,
+
+    /// Action created by the last run
+    #[serde(default)]
+    pub last_action_id: Option<String>,
+
+    /// Why the last run failed to create an Action
+    #[serde(default)]
+    pub last_error: Option<String>,
+
+    /// Number of runs so far
+    #[serde(default)]
+    pub runs: u64,
+
+    /// Timestamp when created
+    pub created_at: u64,
+}
+
+impl Schedule {
+    /// Create a schedule from a PLAN.SCHEDULE request
+    ///
+    /// The request names a `plan_id` and exactly one of `cron` (a five-field
+    /// expression evaluated in UTC) or `delay_secs`. `inputs` defaults to a
+    /// single empty input, and `schedule_id` is generated if absent.
+    ///
+    /// # Errors
+    /// Returns an error if a field is missing or invalid
+    pub fn from_request(request: &serde_json::Value, now: u64) -> Result<Self> {
+        let plan_id = request["plan_id"]
+            .as_str()
+            .ok_or_else(|| Error::InvalidArguments("Missing required field: plan_id".to_string()))?
+            .to_string();
+
+        let schedule_id = match request.get("schedule_id") {
+            Some(id) => id
+                .as_str()
+                .ok_or_else(|| Error::InvalidArguments("schedule_id must be a string".to_string()))?
+                .to_string(),
+            None => format!("sched_{}", Uuid::new_v4().simple()),
+        };
+
+        let inputs = match request.get("inputs") {
+            Some(inputs) => inputs
+                .as_array()
+                .filter(|inputs| !inputs.is_empty())
+                .ok_or_else(|| {
+                    Error::InvalidArguments("inputs must be a non-empty array".to_string())
+                })?
+                .clone(),
+            None => vec![serde_json::json!({})],
+        };
+
+        let cron = request["cron"].as_str().map(str::to_string);
+        let next_run_at = match (&cron, request["delay_secs"].as_u64()) {
+            (Some(expression), None) => next_cron_run(expression, now)?,
+            (None, Some(delay)) => now.saturating_add(delay),
+            _ => {
+                return Err(Error::InvalidArguments(
+                    "Schedule requires exactly one of cron or delay_secs".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            schedule_id,
+            plan_id,
+            cron,
+            inputs,
+            next_run_at,
+            last_run_at: None,
+            last_action_id: None,
+            last_error: None,
+            runs: 0,
+            created_at: now,
+        })
+    }
+
+    /// Store the schedule and index it by its next run time
+    ///
+    /// # Errors
+    /// Returns an error if the database operation fails
+    pub fn save(&self, db: &Database) -> Result<()> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| Error::Protocol(format!("Failed to serialize schedule: {}", e)))?;
+        db.set(&schedule_key(&self.schedule_id), json.as_bytes())?;
+        db.zadd(
+            DUE_INDEX,
+            self.next_run_at as f64,
+            self.schedule_id.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Load a schedule by ID
+    ///
+    /// # Errors
+    /// Returns an error if the database operation fails or the stored JSON
+    /// is invalid
+    pub fn load(db: &Database, schedule_id: &str) -> Result<Option<Self>> {
+        let Some(json) = db.get(&schedule_key(schedule_id))? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| Error::Protocol(format!("Failed to deserialize schedule: {}", e)))
+    }
+
+    /// Delete a schedule, returning whether it existed
+    ///
+    /// # Errors
+    /// Returns an error if the database operation fails
+    pub fn remove(db: &Database, schedule_id: &str) -> Result<bool> {
+        db.zrem(DUE_INDEX, schedule_id.as_bytes())?;
+        db.del(&schedule_key(schedule_id))
+    }
+}
+
+/// Next time after `now` matching a five-field cron expression, in UTC
+///
+/// # Errors
+/// Returns an error if the expression is invalid or never matches
+pub fn next_cron_run(expression: &str, now: u64) -> Result<u64> {
+    let cron = croner::Cron::new(expression)
+        .parse()
+        .map_err(|e| Error::InvalidArguments(format!("Invalid cron expression: {}", e)))?;
+    let now = i64::try_from(now)
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .ok_or_else(|| Error::Protocol("Timestamp out of range".to_string()))?;
+    let next = cron
+        .find_next_occurrence(&now, false)
+        .map_err(|e| Error::InvalidArguments(format!("Cron expression never matches: {}", e)))?;
+    u64::try_from(next.timestamp())
+        .map_err(|_| Error::Protocol("Timestamp out of range".to_string()))
+}
+
+/// Create an Action for every schedule that is due
+///
+/// Recurring schedules move on to their next matching time after `now`, so
+/// runs missed while AGQ was down are made up once rather than repeatedly.
+/// One-off schedules are removed after they run.
+///
+/// Returns the number of schedules run.
+///
+/// # Errors
+/// Returns an error if the schedule index cannot be read
+pub fn run_due(db: &Database, now: u64) -> Result<usize> {
+    let mut ran = 0;
+    for (member, _next_run_at) in db.zrangebyscore(DUE_INDEX, 0.0, now as f64)? {
+        let schedule_id = String::from_utf8_lossy(&member).into_owned();
+        let Some(mut schedule) = Schedule::load(db, &schedule_id)? else {
+            db.zrem(DUE_INDEX, &member)?;
+            continue;
+        };
+
+        let action_id = format!("action_{}", Uuid::new_v4().simple());
+        let action = serde_json::json!({
+            "action_id": action_id,
+            "plan_id": schedule.plan_id,
+            "inputs": schedule.inputs,
+        });
+        match crate::server::submit_action(&action, db) {
+            Ok(_) => {
+                info!(
+                    "Schedule {} created action {} for plan {}",
+                    schedule_id, action_id, schedule.plan_id
+                );
+                schedule.last_action_id = Some(action_id);
+                schedule.last_error = None;
+            }
+            Err(e) => {
+                warn!("Schedule {} failed to run: {}", schedule_id, e);
+                schedule.last_error = Some(e.to_string());
+            }
+        }
+        schedule.last_run_at = Some(now);
+        schedule.runs += 1;
+        ran += 1;
+
+        let next_run_at = match &schedule.cron {
+            Some(expression) => next_cron_run(expression, now).ok(),
+            None => None,
+        };
+        match next_run_at {
+            Some(next_run_at) => {
+                schedule.next_run_at = next_run_at;
+                schedule.save(db)?;
+            }
+            None => {
+                Schedule::remove(db, &schedule_id)?;
+            }
+        }
+    }
+    Ok(ran)
+}
+
+fn schedule_key(schedule_id: &str) -> String {
+    format!("schedule:{}", schedule_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{HashOps, ListOps};
+    use tempfile::TempDir;
+
+    fn test_db() -> (Database, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.redb");
+        let db = Database::open(&db_path).unwrap();
+        (db, temp_dir)
+    }
+
+    // 2024-01-01T00:00:30Z
+    const NOW: u64 = 1_704_067_230;
+
+    #[test]
+    fn test_next_cron_run() {
+        assert_eq!(next_cron_run("*/5 * * * *", NOW).unwrap(), NOW - 30 + 300);
+        assert_eq!(
+            next_cron_run("0 2 * * *", NOW).unwrap(),
+            NOW - 30 + 2 * 3600
+        );
+        assert!(next_cron_run("not cron", NOW).is_err());
+    }
+
+    #[test]
+    fn test_schedule_request_validation() {
+        let delayed =
+            Schedule::from_request(&serde_json::json!({"plan_id": "p", "delay_secs": 60}), NOW)
+                .unwrap();
+        assert_eq!(delayed.next_run_at, NOW + 60);
+        assert_eq!(delayed.inputs, vec![serde_json::json!({})]);
+
+        for request in [
+            serde_json::json!({"plan_id": "p"}),
+            serde_json::json!({"plan_id": "p", "cron": "* * * * *", "delay_secs": 1}),
+            serde_json::json!({"delay_secs": 1}),
+            serde_json::json!({"plan_id": "p", "delay_secs": 1, "inputs": []}),
+        ] {
+            assert!(Schedule::from_request(&request, NOW).is_err());
+        }
+    }
+
+    #[test]
+    fn test_run_due_creates_actions() {
+        let (db, _temp) = test_db();
+        let plan = r#"{"plan_id":"plan_s","tasks":[{"task_number":1,"command":"echo","args":[]}]}"#;
+        db.hset("plan:plan_s", "json", plan.as_bytes()).unwrap();
+
+        let recurring = Schedule::from_request(
+            &serde_json::json!({"schedule_id": "nightly", "plan_id": "plan_s", "cron": "0 2 * * *"}),
+            NOW,
+        )
+        .unwrap();
+        recurring.save(&db).unwrap();
+        let once = Schedule::from_request(
+            &serde_json::json!({"schedule_id": "once", "plan_id": "plan_s", "delay_secs": 10}),
+            NOW,
+        )
+        .unwrap();
+        once.save(&db).unwrap();
+
+        assert_eq!(run_due(&db, NOW + 5).unwrap(), 0);
+        assert_eq!(run_due(&db, NOW + 10).unwrap(), 1);
+        assert!(Schedule::load(&db, "once").unwrap().is_none());
+
+        let fire_at = recurring.next_run_at;
+        assert_eq!(run_due(&db, fire_at).unwrap(), 1);
+        let nightly = Schedule::load(&db, "nightly").unwrap().unwrap();
+        assert_eq!(nightly.runs, 1);
+        assert_eq!(nightly.next_run_at, fire_at + 24 * 3600);
+        assert!(nightly.last_error.is_none());
+        assert_eq!(db.llen("plan:plan_s:actions").unwrap(), 2);
+    }
+}
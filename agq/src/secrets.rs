@@ -0,0 +1,165 @@
+//! Secrets AGQ keeps for the jobs it runs
+//!
+//! Plans are written by an LLM, stored, and shown by `PLAN.GET`, `JOB.GET`
+//! and the dashboard, so they name the credentials their steps need rather
+//! than carry them: a Plan's `env` maps variables to references such as
+//! `secret://db_password`. An admin stores the values with `SECRET.SET`.
+//! Jobs keep the references; a worker about to run one asks for its
+//! environment with `JOB.ENV` and gets every reference AGQ holds a value
+//! for resolved. References AGQ does not hold are passed through for the
+//! worker to resolve from its own secrets file.
+//!
+//! Values are kept in the `secret:values` hash. Clients cannot read or
+//! write `secret:` keys with the data commands, and `SECRET.LIST` names the
+//! secrets without their values.
+
+use crate::error::{Error, Result};
+use crate::resp::RespValue;
+use crate::storage::{Database, HashOps};
+
+/// Prefix of a value that names a secret rather than being one
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// Hash of secret values by name
+const SECRETS_KEY: &str = "secret:values";
+
+/// Prefix of the keys secrets are kept in
+const SECRET_PREFIX: &str = "secret:";
+
+/// Longest secret value stored
+const MAX_SECRET_LEN: usize = 65536;
+
+/// The secret a value refers to, if it is a `secret://` reference
+#[must_use]
+pub fn reference(value: &str) -> Option<&str> {
+    value.strip_prefix(SECRET_SCHEME)
+}
+
+/// Store a secret, replacing any value it had
+///
+/// # Errors
+///
+/// Returns an error if the value is too long or not UTF-8, or the
+/// database fails
+pub fn set(db: &Database, name: &str, value: &[u8]) -> Result<()> {
+    if value.len() > MAX_SECRET_LEN {
+        return Err(Error::InvalidArguments(format!(
+            "Secret value exceeds maximum of {} bytes",
+            MAX_SECRET_LEN
+        )));
+    }
+    if std::str::from_utf8(value).is_err() {
+        return Err(Error::InvalidArguments(
+            "Secret value must be UTF-8, as jobs get it as an environment variable".to_string(),
+        ));
+    }
+    db.hset(SECRETS_KEY, name, value)?;
+    Ok(())
+}
+
+/// Remove a secret, returning whether it was stored
+///
+/// # Errors
+///
+/// Returns an error if the database fails
+pub fn delete(db: &Database, name: &str) -> Result<bool> {
+    Ok(db.hdel(SECRETS_KEY, name)? > 0)
+}
+
+/// Names of the stored secrets, sorted
+///
+/// # Errors
+///
+/// Returns an error if the database fails
+pub fn names(db: &Database) -> Result<Vec<String>> {
+    let mut names: Vec<String> = db
+        .hgetall(SECRETS_KEY)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// A job's environment with the secrets it refers to resolved
+///
+/// Only string values of an object are resolved; references to secrets
+/// AGQ does not hold are left as they are.
+///
+/// # Errors
+///
+/// Returns an error if the database fails
+pub fn resolve_env(db: &Database, env: &serde_json::Value) -> Result<serde_json::Value> {
+    let serde_json::Value::Object(fields) = env else {
+        return Ok(env.clone());
+    };
+    let mut resolved = fields.clone();
+    for value in resolved.values_mut() {
+        let Some(name) = value.as_str().and_then(reference) else {
+            continue;
+        };
+        if let Some(secret) = db.hget(SECRETS_KEY, name)? {
+            *value = serde_json::Value::from(String::from_utf8_lossy(&secret).into_owned());
+        }
+    }
+    Ok(serde_json::Value::Object(resolved))
+}
+
+/// Refuse data commands naming the keys secrets are kept in
+///
+/// Reads are refused as well as writes, since workers may run the data
+/// commands.
+///
+/// # Errors
+///
+/// Returns an error if a data command names a `secret:` key
+pub fn check_access(command: &str, args: &[RespValue]) -> Result<()> {
+    let destination = match command {
+        "RPOPLPUSH" | "BRPOPLPUSH" => args.get(2),
+        _ => None,
+    };
+    for key in [args.get(1), destination].into_iter().flatten() {
+        if key
+            .as_string()
+            .is_ok_and(|key| key.starts_with(SECRET_PREFIX))
+        {
+            return Err(Error::InvalidArguments(
+                "Secrets are only available through the SECRET commands".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_env_leaves_unknown_references() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+        set(&db, "db_password", b"hunter2").unwrap();
+        set(&db, "api_key", b"k").unwrap();
+        assert!(delete(&db, "api_key").unwrap());
+        assert!(set(&db, "binary", b"\xff").is_err());
+        assert_eq!(names(&db).unwrap(), ["db_password"]);
+
+        let env = serde_json::json!({
+            "DB_PASSWORD": "secret://db_password",
+            "API_KEY": "secret://api_key",
+            "file": "a.csv",
+            "count": 2
+        });
+        assert_eq!(
+            resolve_env(&db, &env).unwrap(),
+            serde_json::json!({
+                "DB_PASSWORD": "hunter2",
+                "API_KEY": "secret://api_key",
+                "file": "a.csv",
+                "count": 2
+            })
+        );
+    }
+}
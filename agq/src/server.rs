@@ -1,20 +1,38 @@
 //! TCP server implementation with RESP protocol support
 
+use crate::acl::{command_class, Acl, Client, CommandClass};
+use crate::artifacts::ArtifactStore;
+use crate::audit;
+use crate::catalog;
 use crate::error::{Error, Result};
-use crate::job::{Job, Plan};
-use crate::orchestrator::Orchestrator;
-use crate::resp::{RespParser, RespValue};
-use crate::storage::{Database, HashOps, ListOps, SortedSetOps, StringOps};
+use crate::events;
+use crate::job::{self, Job, JobStatus, Plan};
+use crate::memory::{self, HighWater};
+use crate::orchestrator::{self, Orchestrator, JOBS_INDEX};
+use crate::ratelimit::RateLimits;
+use crate::replication::{self, Replication};
+use crate::resp::{Protocol, RespParser, RespValue};
+use crate::retention;
+use crate::routing;
+use crate::schedule::{self, Schedule};
+use crate::secrets;
+use crate::storage::{Database, HashOps, ListOps, Numbered, SortedSetOps, StringOps};
+use crate::template::{self, PlanTemplate};
+use crate::toolstats;
 use crate::workers::InternalJob;
 use governor::Quota;
 use jsonschema::JSONSchema;
 use once_cell::sync::Lazy;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
 use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use subtle::ConstantTimeEq;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinSet;
 use tokio::time::{timeout, Duration};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -25,12 +43,59 @@ const MAX_CONNECTIONS: usize = 1000;
 /// Read timeout for client connections
 const READ_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// How long a shutdown waits for busy connections to finish
+///
+/// Long enough for a blocking job fetch, which workers make with a few
+/// seconds' timeout, to return.
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// ID the next client connection gets, reported by HELLO
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Seconds PLAN.SUBMIT remembers an idempotency key by default (24 hours)
+pub const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Largest job stdout, stderr, or result stored inline by default (256 KiB)
+pub const DEFAULT_MAX_INLINE_RESULT: usize = 256 * 1024;
+
+/// Tunable behaviour of commands
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Seconds PLAN.SUBMIT remembers an idempotency key
+    pub idempotency_ttl_secs: u64,
+    /// Largest value SET may store at `job:<id>:stdout`, `job:<id>:stderr`
+    /// or `job:<id>:result`; larger output goes to the artifact store
+    pub max_inline_result: usize,
+    /// Where ARTIFACT.PUT stores data
+    pub artifacts: ArtifactStore,
+    /// Per-client limits on submissions and job results
+    pub rate_limits: RateLimits,
+    /// Whether this AGQ is a leader or a standby refusing writes
+    pub replication: Replication,
+    /// Stored bytes above which STATS.MEMORY and the compactor warn
+    pub memory_high_water: HighWater,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            idempotency_ttl_secs: DEFAULT_IDEMPOTENCY_TTL_SECS,
+            max_inline_result: DEFAULT_MAX_INLINE_RESULT,
+            artifacts: ArtifactStore::default(),
+            rate_limits: RateLimits::default(),
+            replication: Replication::default(),
+            memory_high_water: HighWater::default(),
+        }
+    }
+}
+
 /// AGQ Server
 pub struct Server {
     listener: TcpListener,
-    /// Session key for authentication (in Phase 1, single static key)
-    /// In production, this would be per-session with rotation
-    session_key: Arc<Vec<u8>>,
+    /// Credentials clients authenticate with, and their roles
+    acl: Arc<Acl>,
+    /// Tunable behaviour of commands
+    settings: Arc<Settings>,
     /// Database for persistent storage
     db: Arc<Database>,
 }
@@ -47,29 +112,68 @@ impl Server {
     ///
     /// Returns an error if binding to the address fails.
     pub async fn new(addr: &str, session_key: Vec<u8>, db: Database) -> Result<Self> {
+        Self::with_acl(addr, Acl::with_session_key(session_key), db).await
+    }
+
+    /// Create a new server whose clients authenticate against an ACL
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if binding to the address fails.
+    pub async fn with_acl(addr: &str, acl: Acl, db: Database) -> Result<Self> {
         let listener = TcpListener::bind(addr).await?;
         info!("AGQ server listening on {}", addr);
 
         Ok(Self {
             listener,
-            session_key: Arc::new(session_key),
+            acl: Arc::new(acl),
+            settings: Arc::new(Settings::default()),
             db: Arc::new(db),
         })
     }
 
+    /// Replace the default command settings
+    #[must_use]
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = Arc::new(settings);
+        self
+    }
+
     /// Run the server, accepting connections
     ///
     /// # Errors
     ///
     /// Returns an error if a client connection fails.
     pub async fn run(self) -> Result<()> {
-        let mut connection_count = 0usize;
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Run the server until `shutdown` completes, then shut down gracefully
+    ///
+    /// Once `shutdown` completes, no new connections are accepted. Each open
+    /// connection finishes the commands it has received, with new
+    /// submissions and job fetches refused with a `SHUTDOWN` error, and is
+    /// closed once idle, so no client is cut off mid-command. Connections
+    /// still busy after [`SHUTDOWN_TIMEOUT`] are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a client connection fails.
+    pub async fn run_until<F: Future<Output = ()>>(self, shutdown: F) -> Result<()> {
+        let (shutting_down, shutdown_signal) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        tokio::pin!(shutdown);
 
         loop {
-            match self.listener.accept().await {
+            let accepted = tokio::select! {
+                () = &mut shutdown => break,
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                accepted = self.listener.accept() => accepted,
+            };
+            match accepted {
                 Ok((stream, addr)) => {
                     // Security: Limit concurrent connections
-                    if connection_count >= MAX_CONNECTIONS {
+                    if connections.len() >= MAX_CONNECTIONS {
                         warn!(
                             "Connection limit reached, rejecting connection from {}",
                             addr
@@ -78,16 +182,19 @@ impl Server {
                         continue;
                     }
 
-                    connection_count += 1;
                     debug!(
                         "Accepted connection from {}, total: {}",
-                        addr, connection_count
+                        addr,
+                        connections.len() + 1
                     );
 
-                    let session_key = Arc::clone(&self.session_key);
+                    let acl = Arc::clone(&self.acl);
+                    let settings = Arc::clone(&self.settings);
                     let db = Arc::clone(&self.db);
-                    tokio::spawn(async move {
-                        if let Err(e) = handle_connection(stream, session_key, db).await {
+                    let shutdown = shutdown_signal.clone();
+                    connections.spawn(async move {
+                        if let Err(e) = handle_connection(stream, acl, settings, db, shutdown).await
+                        {
                             debug!("Connection error from {}: {}", addr, e);
                         }
                     });
@@ -97,6 +204,26 @@ impl Server {
                 }
             }
         }
+
+        drop(self.listener);
+        info!(
+            "Shutting down, closing {} connections once idle",
+            connections.len()
+        );
+        shutting_down.send_replace(true);
+        let drained = timeout(SHUTDOWN_TIMEOUT, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "Dropping {} connections still busy after {:?}",
+                connections.len(),
+                SHUTDOWN_TIMEOUT
+            );
+            connections.shutdown().await;
+        }
+        Ok(())
     }
 
     /// Get the local address the server is bound to
@@ -112,17 +239,28 @@ impl Server {
 /// Handle a single client connection
 async fn handle_connection(
     mut stream: TcpStream,
-    session_key: Arc<Vec<u8>>,
+    acl: Arc<Acl>,
+    settings: Arc<Settings>,
     db: Arc<Database>,
+    mut shutdown: watch::Receiver<bool>,
 ) -> Result<()> {
     let mut parser = RespParser::new();
-    let mut authenticated = false;
+    let mut client: Option<Client> = None;
+    let mut protocol = Protocol::Resp2;
     let mut buffer = vec![0u8; 4096];
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
 
     loop {
         // Security: Timeout all reads to prevent slowloris attacks
         debug!("Waiting for data from client");
-        let read_result = timeout(READ_TIMEOUT, stream.read(&mut buffer)).await;
+        let read_result = tokio::select! {
+            biased;
+            () = shut_down(&mut shutdown) => {
+                debug!("Closing idle connection for shutdown");
+                return Ok(());
+            }
+            read = timeout(READ_TIMEOUT, stream.read(&mut buffer)) => read,
+        };
 
         let n = match read_result {
             Ok(Ok(0)) => {
@@ -143,32 +281,387 @@ async fn handle_connection(
         };
 
         // Feed data to parser
-        parser.feed(&buffer[..n])?;
+        if let Err(e) = parser.feed(&buffer[..n]) {
+            stream.write_all(e.to_resp_error().as_bytes()).await?;
+            return Err(e);
+        }
 
-        // Process all complete messages
-        while let Some(value) = parser.parse()? {
-            match handle_command(value, &mut authenticated, &session_key, &db).await {
-                Ok(response) => {
-                    stream.write_all(&response.encode()).await?;
-                }
+        // Process all complete messages, replying to a pipelined batch in
+        // one write
+        let mut replies = Vec::new();
+        loop {
+            let value = match parser.parse() {
+                Ok(Some(value)) => value,
+                Ok(None) => break,
                 Err(e) => {
-                    let error_msg = e.to_resp_error();
-                    stream.write_all(error_msg.as_bytes()).await?;
+                    // The stream cannot be resynchronised after invalid data
+                    replies.extend_from_slice(e.to_resp_error().as_bytes());
+                    stream.write_all(&replies).await?;
+                    return Err(e);
+                }
+            };
+
+            // SUBSCRIBE turns the connection into an event stream
+            if let Some(channel) = subscribe_channel(&value) {
+                let authorized = match &client {
+                    Some(client) => client.authorize("SUBSCRIBE"),
+                    None => Err(Error::NoAuth),
+                };
+                let error = match channel.and_then(|channel| authorized.map(|()| channel)) {
+                    Ok(channel) => {
+                        stream.write_all(&replies).await?;
+                        return stream_events(stream, &channel, shutdown).await;
+                    }
+                    Err(e) => e,
+                };
+                replies.extend_from_slice(error.to_resp_error().as_bytes());
+                continue;
+            }
+
+            // REPLICA.SYNC turns the connection into a replication stream
+            if is_command(&value, "REPLICA.SYNC") {
+                let authorized = match &client {
+                    Some(client) => client.authorize("REPLICA.SYNC"),
+                    None => Err(Error::NoAuth),
+                };
+                // A standby's own changes would not include its resyncs
+                let authorized = authorized.and_then(|()| match settings.replication.leader() {
+                    Some(leader) => Err(Error::ReadOnly { leader }),
+                    None => Ok(()),
+                });
+                match authorized {
+                    Ok(()) => {
+                        stream.write_all(&replies).await?;
+                        return stream_changes(stream, &db, &settings.replication, shutdown).await;
+                    }
+                    Err(e) => replies.extend_from_slice(e.to_resp_error().as_bytes()),
+                }
+                continue;
+            }
+
+            if *shutdown.borrow() && refused_during_shutdown(&value) {
+                replies.extend_from_slice(
+                    &RespValue::Error("SHUTDOWN AGQ is shutting down".to_string())
+                        .encode_as(protocol),
+                );
+                continue;
+            }
+
+            let response = match hello_args(&value) {
+                Some(args) => handle_hello(args, &mut client, &acl, &mut protocol, connection_id),
+                None => handle_command(value, &mut client, &acl, &settings, &db).await,
+            };
+            match response {
+                Ok(response) => replies.extend_from_slice(&response.encode_as(protocol)),
+                Err(e) => replies.extend_from_slice(e.to_resp_error().as_bytes()),
+            }
+        }
+        stream.write_all(&replies).await?;
+    }
+}
+
+/// Wait for the server to start shutting down
+async fn shut_down(shutdown: &mut watch::Receiver<bool>) {
+    // An error means the server is gone, which is as good as shut down
+    let _ = shutdown.wait_for(|down| *down).await;
+}
+
+/// Whether a command is refused once the server is shutting down: new
+/// submissions, and fetches that would hand a job to a worker
+fn refused_during_shutdown(value: &RespValue) -> bool {
+    let RespValue::Array(args) = value else {
+        return false;
+    };
+    let Some(command) = args.first().and_then(|arg| arg.as_string().ok()) else {
+        return false;
+    };
+    let command = command.to_uppercase();
+    matches!(
+        command.as_str(),
+        "RPOP" | "BRPOP" | "RPOPLPUSH" | "BRPOPLPUSH"
+    ) || command_class(&command) == Some(CommandClass::Submit)
+}
+
+/// Whether a value is the given command
+fn is_command(value: &RespValue, name: &str) -> bool {
+    let RespValue::Array(args) = value else {
+        return false;
+    };
+    args.first()
+        .and_then(|arg| arg.as_string().ok())
+        .is_some_and(|command| command.eq_ignore_ascii_case(name))
+}
+
+/// Arguments of a HELLO command, or `None` for any other command
+fn hello_args(value: &RespValue) -> Option<&[RespValue]> {
+    let RespValue::Array(args) = value else {
+        return None;
+    };
+    let command = args.first()?.as_string().ok()?;
+    command
+        .eq_ignore_ascii_case("HELLO")
+        .then_some(args.as_slice())
+}
+
+/// Handle HELLO command
+///
+/// Syntax: HELLO [protover [AUTH username password] [SETNAME clientname]]
+///
+/// Switches the connection to RESP2 or RESP3 and replies with server
+/// information, as Redis does, so Redis client libraries can negotiate a
+/// protocol. `AUTH` authenticates like `AUTH <name> <key>`; `SETNAME` is
+/// accepted and ignored. An unauthenticated client must use `AUTH`.
+fn handle_hello(
+    args: &[RespValue],
+    client: &mut Option<Client>,
+    acl: &Acl,
+    protocol: &mut Protocol,
+    connection_id: u64,
+) -> Result<RespValue> {
+    let mut requested = *protocol;
+    let mut auth = None;
+    if let Some(version) = args.get(1) {
+        let version = version.as_string()?.parse::<i64>().map_err(|_| {
+            Error::InvalidArguments("Protocol version is not an integer".to_string())
+        })?;
+        let Some(version) = Protocol::from_version(version) else {
+            return Ok(RespValue::Error(
+                "NOPROTO unsupported protocol version".to_string(),
+            ));
+        };
+        requested = version;
+
+        let mut options = args[2..].iter();
+        while let Some(option) = options.next() {
+            match option.as_string()?.to_uppercase().as_str() {
+                "AUTH" => match (options.next(), options.next()) {
+                    (Some(name), Some(key)) => auth = Some((name, key)),
+                    _ => {
+                        return Err(Error::InvalidArguments(
+                            "HELLO AUTH requires a username and password".to_string(),
+                        ))
+                    }
+                },
+                "SETNAME" => {
+                    options.next().ok_or_else(|| {
+                        Error::InvalidArguments("HELLO SETNAME requires a name".to_string())
+                    })?;
+                }
+                other => {
+                    return Err(Error::InvalidArguments(format!(
+                        "Unknown HELLO option '{}'",
+                        other
+                    )))
+                }
+            }
+        }
+    }
+
+    if let Some((name, key)) = auth {
+        let auth_args = [
+            RespValue::BulkString(b"AUTH".to_vec()),
+            name.clone(),
+            key.clone(),
+        ];
+        handle_auth(&auth_args, client, acl)?;
+    }
+    client.as_ref().ok_or(Error::NoAuth)?;
+
+    *protocol = requested;
+    let field =
+        |name: &str, value: RespValue| (RespValue::BulkString(name.as_bytes().to_vec()), value);
+    Ok(RespValue::Map(vec![
+        field("server", RespValue::BulkString(b"agq".to_vec())),
+        field(
+            "version",
+            RespValue::BulkString(env!("CARGO_PKG_VERSION").as_bytes().to_vec()),
+        ),
+        field("proto", RespValue::Integer(requested.version())),
+        field(
+            "id",
+            RespValue::Integer(i64::try_from(connection_id).unwrap_or(i64::MAX)),
+        ),
+        field("mode", RespValue::BulkString(b"standalone".to_vec())),
+        field("role", RespValue::BulkString(b"master".to_vec())),
+        field("modules", RespValue::Array(Vec::new())),
+    ]))
+}
+
+/// Channel of a SUBSCRIBE command, or `None` for any other command
+///
+/// Syntax: SUBSCRIBE <plan_id|*>
+fn subscribe_channel(value: &RespValue) -> Option<Result<String>> {
+    let RespValue::Array(args) = value else {
+        return None;
+    };
+    let command = args.first()?.as_string().ok()?;
+    if !command.eq_ignore_ascii_case("SUBSCRIBE") {
+        return None;
+    }
+
+    Some((|| {
+        if args.len() != 2 {
+            return Err(Error::InvalidArguments(
+                "SUBSCRIBE requires exactly one plan_id or *".to_string(),
+            ));
+        }
+        let channel = args[1].as_string()?;
+        if channel != events::ALL_PLANS {
+            validate_identifier(&channel, "plan_id")?;
+        }
+        Ok(channel)
+    })())
+}
+
+/// Push job events on a channel to a subscribed client until it disconnects
+///
+/// Each event is sent as `["message", <channel>, <event_json>]`, following
+/// Redis pub/sub. A subscribed connection accepts no further commands.
+async fn stream_events(
+    mut stream: TcpStream,
+    channel: &str,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let mut events = events::subscribe();
+    let confirmation = RespValue::Array(vec![
+        RespValue::BulkString(b"subscribe".to_vec()),
+        RespValue::BulkString(channel.as_bytes().to_vec()),
+        RespValue::Integer(1),
+    ]);
+    stream.write_all(&confirmation.encode()).await?;
+    debug!("SUBSCRIBE {} ->", channel);
+
+    let mut buffer = [0u8; 512];
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if event.matches(channel) => {
+                    let message = RespValue::Array(vec![
+                        RespValue::BulkString(b"message".to_vec()),
+                        RespValue::BulkString(channel.as_bytes().to_vec()),
+                        RespValue::BulkString(event.to_json().into_bytes()),
+                    ]);
+                    stream.write_all(&message.encode()).await?;
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Subscriber to {} missed {} events", channel, missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            read = stream.read(&mut buffer) => {
+                if read? == 0 {
+                    debug!("Subscriber to {} disconnected", channel);
+                    return Ok(());
+                }
+            }
+            () = shut_down(&mut shutdown) => return Ok(()),
+        }
+    }
+}
+
+/// Send a standby a snapshot of the database, then every change committed
+/// after it, until the standby disconnects or falls behind
+///
+/// Messages are arrays of bulk strings: `["snapshot", <bytes>]` for each
+/// piece of the snapshot, `["synced", <seq>]` once it is complete, then
+/// `["change", <seq>, <op>, <args>...]` for each change and `["ping",
+/// <seq>]` while there are none. A standby that falls behind is
+/// disconnected, and resyncs from a new snapshot.
+async fn stream_changes(
+    mut stream: TcpStream,
+    db: &Database,
+    replication: &Replication,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let sent = send_snapshot(&mut stream, db, replication).await;
+    let (mut seq, mut changes) = match sent {
+        Ok(sent) => sent,
+        Err(e) => {
+            stream.write_all(e.to_resp_error().as_bytes()).await?;
+            return Err(e);
+        }
+    };
+    let _standby = replication.standby_connected();
+    info!("Standby synced at change {}", seq);
+
+    let mut ping = tokio::time::interval(replication::PING_INTERVAL);
+    let mut buffer = [0u8; 512];
+    loop {
+        let message = tokio::select! {
+            change = changes.recv() => match change {
+                Ok((number, change)) => {
+                    seq = number;
+                    let mut message = vec![b"change".to_vec(), seq.to_string().into_bytes()];
+                    message.extend(change.to_args());
+                    message
                 }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Standby fell {} changes behind, closing it to resync", missed);
+                    return Ok(());
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            _ = ping.tick() => vec![b"ping".to_vec(), seq.to_string().into_bytes()],
+            read = stream.read(&mut buffer) => {
+                if read? == 0 {
+                    info!("Standby disconnected");
+                    return Ok(());
+                }
+                continue;
             }
+            () = shut_down(&mut shutdown) => return Ok(()),
+        };
+        stream.write_all(&replication::encode(message)).await?;
+    }
+}
+
+/// Snapshot the database for a standby and send it in pieces, then the
+/// number of the last change it includes
+///
+/// Returns that number and the changes committed after it.
+async fn send_snapshot(
+    stream: &mut TcpStream,
+    db: &Database,
+    replication: &Replication,
+) -> Result<(u64, broadcast::Receiver<Numbered>)> {
+    let staged = replication.stage_snapshot()?;
+    // Writing the snapshot is blocking file I/O
+    let snapshot_db = db.clone();
+    let path = staged.path().to_path_buf();
+    let (seq, changes) =
+        tokio::task::spawn_blocking(move || snapshot_db.snapshot_for_replica(path))
+            .await
+            .map_err(|e| Error::Protocol(format!("Snapshot task failed: {}", e)))??;
+
+    let mut file = tokio::fs::File::open(staged.path()).await?;
+    let mut chunk = vec![0u8; replication::SNAPSHOT_CHUNK];
+    loop {
+        let n = file.read(&mut chunk).await?;
+        if n == 0 {
+            break;
         }
+        let message = vec![b"snapshot".to_vec(), chunk[..n].to_vec()];
+        stream.write_all(&replication::encode(message)).await?;
     }
+    let message = vec![b"synced".to_vec(), seq.to_string().into_bytes()];
+    stream.write_all(&replication::encode(message)).await?;
+    Ok((seq, changes))
 }
 
 /// Handle a single RESP command
 ///
 /// # Security
 /// - Validates authentication state before executing commands
+/// - Checks the client's role allows the command (see [`crate::acl`])
+/// - Applies the client's rate limits (see [`crate::ratelimit`])
+/// - Refuses writes on a standby (see [`crate::replication`])
 /// - Uses constant-time comparison for session keys
-async fn handle_command(
+pub(crate) async fn handle_command(
     value: RespValue,
-    authenticated: &mut bool,
-    session_key: &[u8],
+    client: &mut Option<Client>,
+    acl: &Acl,
+    settings: &Settings,
     db: &Database,
 ) -> Result<RespValue> {
     let args = match value {
@@ -182,243 +675,138 @@ async fn handle_command(
 
     let command = args[0].as_string()?.to_uppercase();
 
-    match command.as_str() {
-        "AUTH" => handle_auth(&args, authenticated, session_key),
-        "PING" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_ping(&args, db)
-        }
-        "GET" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_get(&args, db)
-        }
-        "SET" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_set(&args, db)
-        }
-        "DEL" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_del(&args, db)
-        }
-        "EXISTS" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_exists(&args, db)
-        }
-        "TTL" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_ttl(&args, db)
-        }
-        "LPUSH" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_lpush(&args, db)
-        }
-        "RPOP" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_rpop(&args, db)
-        }
-        "BRPOP" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_brpop(&args, db).await
-        }
-        "LLEN" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_llen(&args, db)
-        }
-        "LRANGE" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_lrange(&args, db)
-        }
-        "LREM" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_lrem(&args, db)
-        }
-        "RPOPLPUSH" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_rpoplpush(&args, db)
-        }
-        "BRPOPLPUSH" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_brpoplpush(&args, db).await
-        }
-        "ZADD" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zadd(&args, db)
-        }
-        "ZRANGE" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zrange(&args, db)
-        }
-        "ZRANGEBYSCORE" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zrangebyscore(&args, db)
-        }
-        "ZREM" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zrem(&args, db)
-        }
-        "ZSCORE" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zscore(&args, db)
-        }
-        "ZCARD" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_zcard(&args, db)
-        }
-        "HSET" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hset(&args, db)
-        }
-        "HGET" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hget(&args, db)
-        }
-        "HDEL" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hdel(&args, db)
-        }
-        "HGETALL" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hgetall(&args, db)
-        }
-        "HEXISTS" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hexists(&args, db)
-        }
-        "HLEN" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hlen(&args, db)
-        }
-        "HINCRBY" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_hincrby(&args, db)
-        }
-        cmd if cmd.starts_with("PLAN.") => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            match cmd {
-                "PLAN.SUBMIT" => handle_plan_submit(&args, db),
-                "PLAN.LIST" => handle_plans_list(&args, db),
-                "PLAN.GET" => handle_plans_get(&args, db),
-                _ => Err(Error::Protocol(format!("Unknown PLAN command: {}", cmd))),
-            }
-        }
-        cmd if cmd.starts_with("ACTION.") => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            match cmd {
-                "ACTION.SUBMIT" => handle_action_submit(&args, db),
-                "ACTION.LIST" => handle_actions_list(&args, db),
-                "ACTION.GET" => handle_actions_get(&args, db),
-                _ => Err(Error::Protocol(format!("Unknown ACTION command: {}", cmd))),
-            }
-        }
-        "JOBS.LIST" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_jobs_list(&args, db)
-        }
-        "JOB.GET" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_job_get(&args, db)
-        }
-        "WORKERS.LIST" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_workers_list(&args, db)
-        }
-        "QUEUE.STATS" => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            handle_queue_stats(&args, db)
-        }
-        _ => {
-            if !*authenticated {
-                return Err(Error::NoAuth);
-            }
-            Err(Error::UnknownCommand(command))
-        }
+    if command == "AUTH" {
+        return handle_auth(&args, client, acl);
+    }
+    let client = client.as_ref().ok_or(Error::NoAuth)?;
+    client.authorize(&command)?;
+    settings.rate_limits.check(&client.name, &command, &args)?;
+    settings.replication.check_writable(&command)?;
+    secrets::check_access(&command, &args)?;
+
+    if !audit::is_mutation(&command) {
+        return run_command(&command, &args, settings, db).await;
+    }
+    audit::check_writable(&command, &args)?;
+    let before = audit::status_before(db, &command, &args);
+    let actor = audit::Actor {
+        client: client.name.clone(),
+        command: command.clone(),
+    };
+    let reply = actor
+        .clone()
+        .scope(run_command(&command, &args, settings, db))
+        .await?;
+    audit::command_ran(db, &actor, &args, before, &reply);
+    Ok(reply)
+}
+
+/// Run an authorized command
+async fn run_command(
+    command: &str,
+    args: &[RespValue],
+    settings: &Settings,
+    db: &Database,
+) -> Result<RespValue> {
+    match command {
+        "PING" => handle_ping(args, db),
+        "GET" => handle_get(args, db),
+        "SET" => handle_set(args, db, settings),
+        "DEL" => handle_del(args, db),
+        "EXISTS" => handle_exists(args, db),
+        "TTL" => handle_ttl(args, db),
+        "LPUSH" => handle_lpush(args, db),
+        "RPOP" => handle_rpop(args, db),
+        "BRPOP" => handle_brpop(args, db).await,
+        "LLEN" => handle_llen(args, db),
+        "LRANGE" => handle_lrange(args, db),
+        "LREM" => handle_lrem(args, db),
+        "RPOPLPUSH" => handle_rpoplpush(args, db),
+        "BRPOPLPUSH" => handle_brpoplpush(args, db).await,
+        "ZADD" => handle_zadd(args, db),
+        "ZRANGE" => handle_zrange(args, db),
+        "ZRANGEBYSCORE" => handle_zrangebyscore(args, db),
+        "ZREM" => handle_zrem(args, db),
+        "ZSCORE" => handle_zscore(args, db),
+        "ZCARD" => handle_zcard(args, db),
+        "HSET" => handle_hset(args, db),
+        "HGET" => handle_hget(args, db),
+        "HDEL" => handle_hdel(args, db),
+        "HGETALL" => handle_hgetall(args, db),
+        "HEXISTS" => handle_hexists(args, db),
+        "HLEN" => handle_hlen(args, db),
+        "HINCRBY" => handle_hincrby(args, db),
+        cmd if cmd.starts_with("PLAN.") => match cmd {
+            "PLAN.SUBMIT" => handle_plan_submit(args, db, settings),
+            "PLAN.LIST" => handle_plans_list(args, db),
+            "PLAN.GET" => handle_plans_get(args, db),
+            "PLAN.STATUS" => handle_plan_status(args, db),
+            "PLAN.RESULTS" => handle_plan_results(args, db),
+            "PLAN.SCHEDULE" => handle_plan_schedule(args, db),
+            "PLAN.UNSCHEDULE" => handle_plan_unschedule(args, db),
+            "PLAN.CANCEL" => handle_plan_cancel(args, db),
+            "PLAN.SCHEDULES" => handle_plan_schedules(args, db),
+            "PLAN.TEMPLATE.SAVE" => handle_plan_template_save(args, db),
+            "PLAN.TEMPLATES" => handle_plan_templates(args, db),
+            "PLAN.INSTANTIATE" => handle_plan_instantiate(args, db, settings),
+            _ => Err(Error::Protocol(format!("Unknown PLAN command: {}", cmd))),
+        },
+        cmd if cmd.starts_with("ACTION.") => match cmd {
+            "ACTION.SUBMIT" => handle_action_submit(args, db),
+            "ACTION.LIST" => handle_actions_list(args, db),
+            "ACTION.GET" => handle_actions_get(args, db),
+            _ => Err(Error::Protocol(format!("Unknown ACTION command: {}", cmd))),
+        },
+        "JOBS.LIST" => handle_jobs_list(args, db),
+        "JOB.LIST" => handle_job_list(args, db),
+        "JOB.PURGE" => handle_job_purge(args, db),
+        "JOB.CANCEL" => handle_job_cancel(args, db),
+        "JOB.GET" => handle_job_get(args, db),
+        "JOB.ENV" => handle_job_env(args, db),
+        "EVENT.FIRE" => handle_event_fire(args, db),
+        "JOB.LOG" => handle_job_log(args, db),
+        "ARTIFACT.PUT" => handle_artifact_put(args, db, settings),
+        "ARTIFACT.GET" => handle_artifact_get(args, db, settings),
+        "WORKERS.LIST" => handle_workers_list(args, db),
+        "WORKER.QUEUES" => handle_worker_queues(args, db),
+        "TOOLS.REGISTER" => handle_tools_register(args, db),
+        "TOOLS.CATALOG" => handle_tools_catalog(args, db),
+        "TOOLS.STATS" => handle_tools_stats(args, db),
+        "QUEUE.STATS" => handle_queue_stats(args, db),
+        "QUEUE.LIMIT" => handle_queue_limit(args, db),
+        "QUEUE.UNSCHEDULABLE" => handle_queue_unschedulable(args, db),
+        "STATS.MEMORY" => handle_stats_memory(args, db, settings),
+        "AUDIT.QUERY" => handle_audit_query(args, db),
+        "REPLICA.STATUS" => handle_replica_status(args, db, settings),
+        "REPLICA.PROMOTE" => handle_replica_promote(args, settings),
+        "SECRET.SET" => handle_secret_set(args, db),
+        "SECRET.DEL" => handle_secret_del(args, db),
+        "SECRET.LIST" => handle_secret_list(args, db),
+        _ => Err(Error::UnknownCommand(command.to_string())),
     }
 }
 
 /// Handle AUTH command
 ///
+/// Supports both:
+/// - AUTH key -> authenticates as whichever client owns the key
+/// - AUTH name key -> authenticates as the named client
+///
 /// # Security
 /// - Uses constant-time comparison to prevent timing attacks
 /// - Validates key is not empty
-/// - Requires exactly one argument
-fn handle_auth(
-    args: &[RespValue],
-    authenticated: &mut bool,
-    expected_key: &[u8],
-) -> Result<RespValue> {
-    if args.len() != 2 {
-        return Err(Error::InvalidArguments(
-            "AUTH requires exactly one argument".to_string(),
-        ));
-    }
+fn handle_auth(args: &[RespValue], client: &mut Option<Client>, acl: &Acl) -> Result<RespValue> {
+    let (name, key_arg) = match args {
+        [_, key] => (None, key),
+        [_, name, key] => (Some(name.as_string()?), key),
+        _ => {
+            return Err(Error::InvalidArguments(
+                "AUTH requires a key, optionally preceded by a client name".to_string(),
+            ))
+        }
+    };
 
-    let RespValue::BulkString(provided_key) = &args[1] else {
+    let RespValue::BulkString(provided_key) = key_arg else {
         return Err(Error::InvalidArguments(
             "AUTH key must be a bulk string".to_string(),
         ));
@@ -446,19 +834,9 @@ fn handle_auth(
         };
 
     // Security: Constant-time comparison to prevent timing attacks
-    // Pad to same length for constant-time comparison
-    let max_len = key_to_compare.len().max(expected_key.len());
-    let mut provided_padded = key_to_compare;
-    let mut expected_padded = expected_key.to_vec();
-
-    provided_padded.resize(max_len, 0);
-    expected_padded.resize(max_len, 0);
-
-    let keys_match = provided_padded.ct_eq(&expected_padded);
-
-    if keys_match.into() {
-        *authenticated = true;
-        info!("Client authenticated successfully");
+    if let Some(authenticated) = acl.authenticate(name.as_deref(), &key_to_compare) {
+        info!("Client {} authenticated successfully", authenticated.name);
+        *client = Some(authenticated);
         Ok(RespValue::SimpleString("OK".to_string()))
     } else {
         warn!("Authentication failed: invalid key");
@@ -468,11 +846,6 @@ fn handle_auth(
     }
 }
 
-/// Handle PING command
-///
-/// Supports both:
-/// - PING -> +PONG
-/// - PING message -> $7\r\nmessage\r\n (echo)
 /// Worker heartbeat TTL (5 minutes)
 ///
 /// Workers are expected to send heartbeats every 60 seconds.
@@ -487,6 +860,9 @@ const WORKER_HEARTBEAT_TTL_SECS: u64 = 300;
 /// heartbeats every 60 seconds. This limit caps the total number of registered workers.
 const MAX_WORKERS: usize = 1000;
 
+/// Maximum size of the metrics payload carried by a worker heartbeat (4KB)
+const MAX_WORKER_METRICS_BYTES: usize = 4 * 1024;
+
 /// Rate limiter for worker heartbeat registration (PING with worker_id)
 ///
 /// Prevents DoS attacks via rapid worker creation.
@@ -502,14 +878,20 @@ static WORKER_HEARTBEAT_LIMITER: Lazy<
     >,
 > = Lazy::new(|| governor::RateLimiter::direct(Quota::per_minute(NonZeroU32::new(1000).unwrap())));
 
+/// Handle PING command
+///
+/// Supports both:
+/// - PING -> +PONG
+/// - PING message -> $7\r\nmessage\r\n (echo)
 fn handle_ping(args: &[RespValue], db: &Database) -> Result<RespValue> {
     match args.len() {
         1 => {
             // Simple PING
             Ok(RespValue::SimpleString("PONG".to_string()))
         }
-        2 => {
-            // PING with worker_id - treat as heartbeat
+        2 | 3 => {
+            // PING with worker_id - treat as heartbeat, optionally carrying
+            // a JSON object of host metrics
             // Security: Check rate limit to prevent DoS via rapid worker creation
             if WORKER_HEARTBEAT_LIMITER.check().is_err() {
                 warn!("Worker heartbeat rate limit exceeded");
@@ -523,14 +905,45 @@ fn handle_ping(args: &[RespValue], db: &Database) -> Result<RespValue> {
             // Validate worker_id
             validate_identifier(&worker_id, "worker_id")?;
 
+            let metrics = match args.get(2) {
+                Some(RespValue::BulkString(metrics)) => Some(validate_worker_metrics(metrics)?),
+                Some(_) => {
+                    return Err(Error::InvalidArguments(
+                        "PING metrics must be a bulk string".to_string(),
+                    ))
+                }
+                None => None,
+            };
+
             // Update worker heartbeat
-            register_worker_heartbeat(db, &worker_id)?;
+            register_worker_heartbeat(db, &worker_id, metrics)?;
 
             // Echo back worker_id (standard PING behavior)
             Ok(RespValue::BulkString(worker_id.as_bytes().to_vec()))
         }
         _ => Err(Error::InvalidArguments(
-            "PING accepts 0 or 1 arguments".to_string(),
+            "PING accepts 0, 1 or 2 arguments".to_string(),
+        )),
+    }
+}
+
+/// Validate a worker metrics payload sent with a heartbeat
+///
+/// # Security
+/// - Payload size is capped at MAX_WORKER_METRICS_BYTES
+/// - Payload must be a JSON object, so WORKERS.LIST can embed it verbatim
+fn validate_worker_metrics(metrics: &[u8]) -> Result<&[u8]> {
+    if metrics.len() > MAX_WORKER_METRICS_BYTES {
+        return Err(Error::InvalidArguments(format!(
+            "Worker metrics too large (max {} bytes)",
+            MAX_WORKER_METRICS_BYTES
+        )));
+    }
+
+    match serde_json::from_slice::<serde_json::Value>(metrics) {
+        Ok(serde_json::Value::Object(_)) => Ok(metrics),
+        _ => Err(Error::InvalidArguments(
+            "Worker metrics must be a JSON object".to_string(),
         )),
     }
 }
@@ -565,11 +978,22 @@ pub(crate) fn get_current_timestamp_secs() -> Result<u64> {
         .as_secs())
 }
 
+/// Whether a key holds job output subject to the inline size limit
+fn is_inline_result_key(key: &str) -> bool {
+    key.strip_prefix("job:")
+        .and_then(|rest| rest.rsplit_once(':'))
+        .is_some_and(|(_, suffix)| matches!(suffix, "stdout" | "stderr" | "result"))
+}
+
 /// Handle SET command
 ///
 /// Syntax: SET key value [EX seconds] [PX milliseconds] [EXAT unix-time-seconds] [PXAT unix-time-milliseconds]
 /// Returns: OK
-fn handle_set(args: &[RespValue], db: &Database) -> Result<RespValue> {
+///
+/// A job's stdout, stderr, and result envelope are refused above
+/// `settings.max_inline_result`; workers store larger output with
+/// ARTIFACT.PUT and reference it from `job:<id>:<stream>_artifact`.
+fn handle_set(args: &[RespValue], db: &Database, settings: &Settings) -> Result<RespValue> {
     if args.len() < 3 {
         return Err(Error::InvalidArguments(
             "SET requires at least two arguments".to_string(),
@@ -583,6 +1007,14 @@ fn handle_set(args: &[RespValue], db: &Database) -> Result<RespValue> {
         ));
     };
 
+    if value.len() > settings.max_inline_result && is_inline_result_key(&key) {
+        return Err(Error::InvalidArguments(format!(
+            "Job output too large to store inline: {} bytes (max {}); store it with ARTIFACT.PUT",
+            value.len(),
+            settings.max_inline_result
+        )));
+    }
+
     // Maximum expiry duration: 10 years (prevents resource exhaustion)
     const MAX_EXPIRY_SECONDS: u64 = 365 * 24 * 60 * 60 * 10;
 
@@ -704,6 +1136,12 @@ fn handle_set(args: &[RespValue], db: &Database) -> Result<RespValue> {
         db.set(&key, value)?;
     }
 
+    // Workers report job claims and results with SET
+    let now = get_current_timestamp_secs()?;
+    crate::metrics::METRICS.observe_set(db, &key, value, now);
+    crate::events::observe_set(db, &key, value);
+    toolstats::observe_set(db, &key, value, now);
+
     Ok(RespValue::SimpleString("OK".to_string()))
 }
 
@@ -796,11 +1234,26 @@ fn handle_rpop(args: &[RespValue], db: &Database) -> Result<RespValue> {
     let key = args[1].as_string()?;
 
     match db.rpop(&key)? {
-        Some(value) => Ok(RespValue::BulkString(value)),
+        Some(value) => {
+            job_taken(db, &key, &value);
+            Ok(RespValue::BulkString(value))
+        }
         None => Ok(RespValue::NullBulkString),
     }
 }
 
+/// Let the orchestrator know a job was taken from a ready queue, so the
+/// next job of its Plan waiting its turn can take its place
+fn job_taken(db: &Database, queue: &str, job_id: &[u8]) {
+    if !orchestrator::is_ready_queue(queue) {
+        return;
+    }
+    let job_id = String::from_utf8_lossy(job_id);
+    if let Err(e) = Orchestrator::new(db).job_taken(queue, &job_id) {
+        warn!("Failed to release jobs waiting for {}: {}", queue, e);
+    }
+}
+
 /// Handle BRPOP command
 ///
 /// Syntax: BRPOP key timeout
@@ -819,7 +1272,10 @@ async fn handle_brpop(args: &[RespValue], db: &Database) -> Result<RespValue> {
     })?;
 
     match db.brpop(&key, timeout_secs).await? {
-        Some(value) => Ok(RespValue::BulkString(value)),
+        Some(value) => {
+            job_taken(db, &key, &value);
+            Ok(RespValue::BulkString(value))
+        }
         None => Ok(RespValue::NullBulkString),
     }
 }
@@ -925,7 +1381,10 @@ fn handle_rpoplpush(args: &[RespValue], db: &Database) -> Result<RespValue> {
     let destination = args[2].as_string()?;
 
     match db.rpoplpush(&source, &destination)? {
-        Some(value) => Ok(RespValue::BulkString(value)),
+        Some(value) => {
+            job_taken(db, &source, &value);
+            Ok(RespValue::BulkString(value))
+        }
         None => Ok(RespValue::NullBulkString),
     }
 }
@@ -951,7 +1410,10 @@ async fn handle_brpoplpush(args: &[RespValue], db: &Database) -> Result<RespValu
     })?;
 
     match db.brpoplpush(&source, &destination, timeout_secs).await? {
-        Some(value) => Ok(RespValue::BulkString(value)),
+        Some(value) => {
+            job_taken(db, &source, &value);
+            Ok(RespValue::BulkString(value))
+        }
         None => Ok(RespValue::NullBulkString),
     }
 }
@@ -1314,6 +1776,10 @@ const PLAN_SCHEMA: &str = r#"{
   "type": "object",
   "required": ["plan_id", "tasks"],
   "properties": {
+    "schema_version": {
+      "type": "integer",
+      "minimum": 1
+    },
     "plan_id": {
       "type": "string",
       "minLength": 1,
@@ -1323,6 +1789,31 @@ const PLAN_SCHEMA: &str = r#"{
       "type": "string",
       "maxLength": 1024
     },
+    "on_failure": {
+      "type": "string",
+      "enum": ["cancel", "continue", "retry"]
+    },
+    "max_concurrency": {
+      "type": "integer",
+      "minimum": 1,
+      "maximum": 10000
+    },
+    "weight": {
+      "type": "integer",
+      "minimum": 1,
+      "maximum": 100
+    },
+    "env": {
+      "type": "object",
+      "maxProperties": 100,
+      "propertyNames": {
+        "pattern": "^[A-Za-z_][A-Za-z0-9_]{0,127}$"
+      },
+      "additionalProperties": {
+        "type": "string",
+        "maxLength": 65536
+      }
+    },
     "tasks": {
       "type": "array",
       "minItems": 1,
@@ -1358,6 +1849,95 @@ const PLAN_SCHEMA: &str = r#"{
             "type": "integer",
             "minimum": 1,
             "maximum": 100
+          },
+          "depends_on": {
+            "type": "array",
+            "maxItems": 100,
+            "items": {
+              "type": "integer",
+              "minimum": 1,
+              "maximum": 100
+            }
+          },
+          "priority": {
+            "type": "string",
+            "enum": ["high", "normal", "low"]
+          },
+          "stages": {
+            "type": "array",
+            "minItems": 1,
+            "maxItems": 16,
+            "items": {
+              "type": "object",
+              "required": ["command"],
+              "properties": {
+                "command": {
+                  "type": "string",
+                  "minLength": 1,
+                  "maxLength": 256
+                },
+                "args": {
+                  "type": "array",
+                  "maxItems": 100,
+                  "items": {
+                    "type": "string",
+                    "maxLength": 65536
+                  }
+                }
+              }
+            }
+          },
+          "max_attempts": {
+            "type": "integer",
+            "minimum": 1,
+            "maximum": 10
+          },
+          "backoff": {
+            "type": "object",
+            "properties": {
+              "strategy": {
+                "type": "string",
+                "enum": ["fixed", "linear", "exponential"]
+              },
+              "delay_secs": {
+                "type": "integer",
+                "minimum": 0,
+                "maximum": 3600
+              },
+              "max_delay_secs": {
+                "type": "integer",
+                "minimum": 0,
+                "maximum": 86400
+              }
+            }
+          },
+          "run_after_secs": {
+            "type": "integer",
+            "minimum": 0,
+            "maximum": 604800
+          },
+          "await_event": {
+            "type": "boolean"
+          },
+          "artifacts": {
+            "type": "array",
+            "maxItems": 100,
+            "items": {
+              "type": "string",
+              "pattern": "^[A-Za-z0-9_-][A-Za-z0-9._-]{0,127}$"
+            }
+          },
+          "tags": {
+            "type": "array",
+            "maxItems": 16,
+            "items": {
+              "type": "string",
+              "pattern": "^[A-Za-z0-9][A-Za-z0-9._:-]{0,63}$"
+            }
+          },
+          "map": {
+            "type": "string",
+            "enum": ["lines", "json"]
           }
         }
       }
@@ -1391,11 +1971,29 @@ static PLAN_SUBMIT_LIMITER: Lazy<
     >,
 > = Lazy::new(|| governor::RateLimiter::direct(Quota::per_minute(NonZeroU32::new(1000).unwrap())));
 
+/// Serializes idempotency key lookups with the submissions that record them,
+/// so two concurrent retries cannot both be queued
+static IDEMPOTENCY_LOCK: Lazy<std::sync::Mutex<()>> = Lazy::new(|| std::sync::Mutex::new(()));
+
+/// A PLAN.SUBMIT remembered under an idempotency key
+#[derive(Serialize, Deserialize)]
+struct IdempotencyRecord {
+    plan_id: String,
+    /// SHA-256 of the submitted plan JSON, hex-encoded
+    digest: String,
+}
+
 /// Handle PLAN.SUBMIT command
 ///
-/// Syntax: PLAN.SUBMIT <plan_json>
+/// Syntax: PLAN.SUBMIT <plan_json> [IDEMPOTENCY_KEY <key>]
 /// Returns: plan_id (unique identifier for the submitted plan)
 ///
+/// A submission with an idempotency key already used for the same plan JSON
+/// returns the original plan_id without queuing the plan again, so clients
+/// can safely retry after a network error. Keys are remembered for
+/// `settings.idempotency_ttl_secs`; reusing one for a different plan is an
+/// error.
+///
 /// # Security
 /// - Validates JSON schema against Plan specification
 /// - Enforces maximum plan size (1MB)
@@ -1407,7 +2005,7 @@ static PLAN_SUBMIT_LIMITER: Lazy<
 /// 2. Generate plan_id
 /// 3. Push to internal queue (agq:internal:plan.submit)
 /// 4. Return plan_id immediately (async processing)
-fn handle_plan_submit(args: &[RespValue], db: &Database) -> Result<RespValue> {
+fn handle_plan_submit(args: &[RespValue], db: &Database, settings: &Settings) -> Result<RespValue> {
     // Security: Check rate limit before processing
     if PLAN_SUBMIT_LIMITER.check().is_err() {
         warn!("PLAN.SUBMIT rate limit exceeded");
@@ -1417,11 +2015,19 @@ fn handle_plan_submit(args: &[RespValue], db: &Database) -> Result<RespValue> {
     }
 
     // Validate arguments
-    if args.len() != 2 {
-        return Err(Error::InvalidArguments(
-            "PLAN.SUBMIT requires exactly one argument (plan JSON)".to_string(),
-        ));
-    }
+    let idempotency_key = match args.len() {
+        2 => None,
+        4 if args[2].as_string()?.eq_ignore_ascii_case("IDEMPOTENCY_KEY") => {
+            let key = args[3].as_string()?;
+            validate_identifier(&key, "idempotency key")?;
+            Some(key)
+        }
+        _ => {
+            return Err(Error::InvalidArguments(
+                "PLAN.SUBMIT requires plan JSON and an optional IDEMPOTENCY_KEY <key>".to_string(),
+            ))
+        }
+    };
 
     let plan_json = args[1].as_string()?;
 
@@ -1437,24 +2043,36 @@ fn handle_plan_submit(args: &[RespValue], db: &Database) -> Result<RespValue> {
     let plan_value: serde_json::Value = serde_json::from_str(&plan_json)
         .map_err(|e| Error::InvalidArguments(format!("Invalid JSON: {}", e)))?;
 
-    // Validate against Plan schema (using lazy-compiled validator)
-    if let Err(errors) = PLAN_VALIDATOR.validate(&plan_value) {
-        let error_msgs: Vec<String> = errors.map(|e| format!("{}", e)).collect();
-        return Err(Error::InvalidArguments(format!(
-            "Plan validation failed: {}",
-            error_msgs.join(", ")
-        )));
+    let plan_id = validate_plan(plan_value)?;
+
+    let _guard = IDEMPOTENCY_LOCK
+        .lock()
+        .map_err(|_| Error::Protocol("Idempotency lock poisoned".to_string()))?;
+    let digest = hex::encode(ring::digest::digest(
+        &ring::digest::SHA256,
+        plan_json.as_bytes(),
+    ));
+    let record_key = idempotency_key
+        .as_ref()
+        .map(|key| format!("idempotency:plan.submit:{}", key));
+    if let Some(record_key) = &record_key {
+        if let Some(record) = db.get(record_key)? {
+            let record: IdempotencyRecord = serde_json::from_slice(&record)
+                .map_err(|e| Error::Protocol(format!("Invalid idempotency record: {}", e)))?;
+            if record.digest != digest {
+                return Err(Error::InvalidArguments(format!(
+                    "Idempotency key {} was already used for a different plan",
+                    idempotency_key.unwrap_or_default()
+                )));
+            }
+            info!(
+                "PLAN.SUBMIT retried with idempotency key, returning {}",
+                record.plan_id
+            );
+            return Ok(RespValue::BulkString(record.plan_id.into_bytes()));
+        }
     }
 
-    // Extract plan_id from JSON (required by schema)
-    let plan_id = plan_value["plan_id"]
-        .as_str()
-        .ok_or_else(|| Error::InvalidArguments("plan_id field is required".to_string()))?
-        .to_string();
-
-    // Validate plan_id format
-    validate_identifier(&plan_id, "plan_id")?;
-
     // Create internal job
     let internal_job = InternalJob {
         id: Uuid::new_v4().to_string(),
@@ -1473,12 +2091,191 @@ fn handle_plan_submit(args: &[RespValue], db: &Database) -> Result<RespValue> {
     // Push to internal queue
     db.lpush("agq:internal:plan.submit", &job_json)?;
 
+    if let Some(record_key) = &record_key {
+        let record = serde_json::to_vec(&IdempotencyRecord {
+            plan_id: plan_id.clone(),
+            digest,
+        })
+        .map_err(|e| Error::Protocol(format!("Failed to serialize idempotency record: {}", e)))?;
+        let expire_at = get_current_timestamp_secs()?.saturating_add(settings.idempotency_ttl_secs);
+        db.setex(record_key, &record, expire_at)?;
+    }
+
     debug!("PLAN.SUBMIT -> {} (queued for processing)", plan_id);
 
     // Return plan_id immediately (processing continues asynchronously)
     Ok(RespValue::BulkString(plan_id.into_bytes()))
 }
 
+/// Validate Plan JSON against AGQ's size limits and the shared Plan schema
+///
+/// Returns the Plan's `plan_id`, which is a valid identifier.
+fn validate_plan(plan_value: serde_json::Value) -> Result<String> {
+    // Validate against Plan schema (using lazy-compiled validator)
+    if let Err(errors) = PLAN_VALIDATOR.validate(&plan_value) {
+        let error_msgs: Vec<String> = errors.map(|e| format!("{}", e)).collect();
+        return Err(Error::InvalidArguments(format!(
+            "Plan validation failed: {}",
+            error_msgs.join(", ")
+        )));
+    }
+
+    // Fields, numbering, references, pipelines and map tasks, as checked
+    // by AGX before submitting
+    let plan = agenix_plan::validate(&plan_value).map_err(plan_schema_error)?;
+
+    // Extract plan_id from JSON (required by schema)
+    let plan_id = plan
+        .plan_id
+        .ok_or_else(|| Error::InvalidArguments("plan_id field is required".to_string()))?;
+
+    // Validate plan_id format
+    validate_identifier(&plan_id, "plan_id")?;
+    Ok(plan_id)
+}
+
+/// Report a Plan that breaks the shared Plan schema
+///
+/// The error lists every problem as a JSON array of `{"path", "message"}`
+/// objects, e.g. `{"path": "tasks[1].input_from_task", ...}`.
+fn plan_schema_error(errors: Vec<agenix_plan::SchemaError>) -> Error {
+    match serde_json::to_string(&errors) {
+        Ok(errors) => Error::InvalidArguments(format!("Plan validation failed: {}", errors)),
+        Err(e) => Error::Protocol(format!("Failed to serialize plan errors: {}", e)),
+    }
+}
+
+/// Handle PLAN.TEMPLATE.SAVE command
+///
+/// Usage: PLAN.TEMPLATE.SAVE <template_id> <plan_json>
+///
+/// Stores a Plan whose string values may contain `${NAME}` placeholders
+/// (see [`crate::template`]), replacing any template with the same ID. The
+/// template is checked by validating an instance with every placeholder
+/// filled in. Returns `{"template_id", "params"}` as JSON.
+fn handle_plan_template_save(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 3 {
+        return Err(Error::InvalidArguments(
+            "PLAN.TEMPLATE.SAVE requires a template_id and plan JSON".to_string(),
+        ));
+    }
+
+    let template_id = args[1].as_string()?;
+    validate_identifier(&template_id, "template_id")?;
+    if template_id.len() > template::MAX_TEMPLATE_ID_LEN {
+        return Err(Error::InvalidArguments(format!(
+            "template_id too long (max {} characters)",
+            template::MAX_TEMPLATE_ID_LEN
+        )));
+    }
+
+    let plan_json = args[2].as_string()?;
+    if plan_json.len() > MAX_PLAN_SIZE {
+        return Err(Error::InvalidArguments(format!(
+            "Plan JSON too large (max {} bytes)",
+            MAX_PLAN_SIZE
+        )));
+    }
+    let plan_value: serde_json::Value = serde_json::from_str(&plan_json)
+        .map_err(|e| Error::InvalidArguments(format!("Invalid JSON: {}", e)))?;
+
+    let mut template = PlanTemplate::new(template_id, plan_value, get_current_timestamp_secs()?)?;
+    let sample = template
+        .params
+        .iter()
+        .map(|name| (name.clone(), "x".to_string()))
+        .collect();
+    validate_plan(template.instantiate(&sample)?)?;
+
+    if PlanTemplate::load(db, &template.template_id)?.is_none()
+        && db.zcard(template::TEMPLATES_INDEX)? >= template::MAX_TEMPLATES
+    {
+        return Err(Error::Protocol(format!(
+            "Maximum template limit reached ({} templates)",
+            template::MAX_TEMPLATES
+        )));
+    }
+    template.save(db)?;
+
+    let response = serde_json::json!({
+        "template_id": template.template_id,
+        "params": template.params,
+    });
+    let response_json = serde_json::to_string(&response)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!(
+        "PLAN.TEMPLATE.SAVE {} ({} params)",
+        template.template_id,
+        template.params.len()
+    );
+    Ok(RespValue::BulkString(response_json.into_bytes()))
+}
+
+/// Handle PLAN.INSTANTIATE command
+///
+/// Usage: PLAN.INSTANTIATE <template_id> [params_json]
+///
+/// Fills in a template's placeholders from a JSON object of strings and
+/// submits the result as with PLAN.SUBMIT. Returns the instance's plan_id,
+/// which is the same whenever the template is instantiated with the same
+/// parameters.
+fn handle_plan_instantiate(
+    args: &[RespValue],
+    db: &Database,
+    settings: &Settings,
+) -> Result<RespValue> {
+    if !(2..=3).contains(&args.len()) {
+        return Err(Error::InvalidArguments(
+            "PLAN.INSTANTIATE requires a template_id and optional parameters JSON".to_string(),
+        ));
+    }
+
+    let template_id = args[1].as_string()?;
+    validate_identifier(&template_id, "template_id")?;
+    let params: BTreeMap<String, String> = match args.get(2) {
+        Some(params) => serde_json::from_str(&params.as_string()?).map_err(|e| {
+            Error::InvalidArguments(format!(
+                "Parameters must be a JSON object of strings: {}",
+                e
+            ))
+        })?,
+        None => BTreeMap::new(),
+    };
+
+    let template = PlanTemplate::load(db, &template_id)?.ok_or_else(|| {
+        Error::InvalidArguments(format!("Plan template not found: {}", template_id))
+    })?;
+    let plan_json = serde_json::to_string(&template.instantiate(&params)?)
+        .map_err(|e| Error::Protocol(format!("Failed to serialize plan: {}", e)))?;
+
+    debug!("PLAN.INSTANTIATE {}", template_id);
+    let submit = [
+        RespValue::BulkString(b"PLAN.SUBMIT".to_vec()),
+        RespValue::BulkString(plan_json.into_bytes()),
+    ];
+    handle_plan_submit(&submit, db, settings)
+}
+
+/// Handle PLAN.TEMPLATES command
+///
+/// Returns a JSON array of all templates, oldest first.
+fn handle_plan_templates(_args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let mut templates = Vec::new();
+    for (member, _created_at) in db.zrange(template::TEMPLATES_INDEX, 0, -1)? {
+        let template_id = String::from_utf8_lossy(&member);
+        if let Some(template) = PlanTemplate::load(db, &template_id)? {
+            templates.push(template);
+        }
+    }
+
+    let response = serde_json::to_string(&templates)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!("PLAN.TEMPLATES -> {} templates", templates.len());
+    Ok(RespValue::BulkString(response.into_bytes()))
+}
+
 /// Validate an identifier (plan_id, action_id, job_id, etc.)
 ///
 /// # Security
@@ -1493,7 +2290,7 @@ fn handle_plan_submit(args: &[RespValue], db: &Database) -> Result<RespValue> {
 ///
 /// # Returns
 /// Ok(()) if valid, Err with detailed message if invalid
-fn validate_identifier(id: &str, field_name: &str) -> Result<()> {
+pub(crate) fn validate_identifier(id: &str, field_name: &str) -> Result<()> {
     // Check length
     if id.is_empty() || id.len() > 64 {
         return Err(Error::InvalidArguments(format!(
@@ -1516,6 +2313,9 @@ fn validate_identifier(id: &str, field_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Maximum size of an EVENT.FIRE payload (64KB)
+pub(crate) const MAX_EVENT_PAYLOAD: usize = 65536;
+
 /// Maximum size for a single input in ACTION.SUBMIT (10MB)
 ///
 /// Prevents resource exhaustion attacks where large inputs bypass
@@ -1607,6 +2407,14 @@ fn handle_action_submit(args: &[RespValue], db: &Database) -> Result<RespValue>
     let action_value: serde_json::Value = serde_json::from_str(&action_json)
         .map_err(|e| Error::InvalidArguments(format!("Invalid JSON: {}", e)))?;
 
+    submit_action(&action_value, db)
+}
+
+/// Create the Jobs for a parsed Action and store its metadata
+///
+/// Shared by ACTION.SUBMIT and the plan scheduler, which creates Actions
+/// without going through the client rate limit.
+pub(crate) fn submit_action(action_value: &serde_json::Value, db: &Database) -> Result<RespValue> {
     // Extract required fields
     let action_id = action_value["action_id"]
         .as_str()
@@ -1672,14 +2480,25 @@ fn handle_action_submit(args: &[RespValue], db: &Database) -> Result<RespValue>
     let plan_json = std::str::from_utf8(&plan_json_bytes)
         .map_err(|e| Error::Protocol(format!("Plan JSON is not valid UTF-8: {}", e)))?;
     
-    let plan: Plan = serde_json::from_str(plan_json)
+    let plan_value: serde_json::Value = serde_json::from_str(plan_json)
         .map_err(|e| Error::Protocol(format!("Failed to parse Plan JSON: {}", e)))?;
+    // Plans stored by older versions may not meet the current schema
+    let plan: Plan = agenix_plan::validate(&plan_value).map_err(plan_schema_error)?;
 
     // Create Jobs (Tasks)
     let mut all_jobs = Vec::new();
     let mut job_ids = Vec::new();
 
-    for (_idx, input) in inputs.iter().enumerate() {
+    for (idx, input) in inputs.iter().enumerate() {
+        // The Plan's env, with secret references left for workers to
+        // resolve, under the input's own fields
+        let env = job::job_env(&plan, input).ok_or_else(|| {
+            Error::InvalidArguments(format!(
+                "Input {} must be a JSON object to take the Plan's env",
+                idx
+            ))
+        })?;
+
         // Map task_number -> job_id for this input iteration
         let mut task_job_map: HashMap<u32, String> = HashMap::new();
         let mut input_jobs = Vec::new();
@@ -1702,14 +2521,26 @@ fn handle_action_submit(args: &[RespValue], db: &Database) -> Result<RespValue>
                     dependencies.insert(dep_job_id.clone());
                 }
             }
+            // Tasks only waited for don't feed the job's stdin, unless the
+            // task also takes its input from them
+            let mut order_only = HashSet::new();
+            for dep_task_num in &task.depends_on {
+                if let Some(dep_job_id) = task_job_map.get(dep_task_num) {
+                    if dependencies.insert(dep_job_id.clone()) {
+                        order_only.insert(dep_job_id.clone());
+                    }
+                }
+            }
 
-            // Determine tags (simple logic for now, can be expanded)
-            // e.g., if command is "agx-ocr", add "gpu" tag
-            let mut tags = Vec::new();
-            if task.command.contains("ocr") || task.command.contains("gpu") {
-                tags.push("gpu".to_string());
-            } else {
-                tags.push("cpu".to_string());
+            // Tasks that declare no tags are tagged from their command,
+            // e.g. "agx-ocr" needs a GPU
+            let mut tags = task.tags.clone();
+            if tags.is_empty() {
+                if task.command.contains("ocr") || task.command.contains("gpu") {
+                    tags.push("gpu".to_string());
+                } else {
+                    tags.push("cpu".to_string());
+                }
             }
 
             let mut job = Job::new(
@@ -1719,11 +2550,25 @@ fn handle_action_submit(args: &[RespValue], db: &Database) -> Result<RespValue>
                 task.task_number,
                 task.command.clone(),
                 task.args.clone(),
-                input.clone(),
+                env.clone(),
                 tags,
             );
 
             job.dependencies = dependencies;
+            job.order_only = order_only;
+            job.timeout_secs = task.timeout_secs;
+            job.limits = task.limits;
+            job.artifacts = task.artifacts.clone();
+            job.max_attempts = task.max_attempts;
+            job.stages = task.stages.clone();
+            job.on_failure = plan.on_failure;
+            job.backoff = task.backoff;
+            job.run_after_secs = task.run_after_secs;
+            job.await_event = task.await_event;
+            job.priority = task.priority;
+            job.max_concurrency = plan.max_concurrency;
+            job.weight = plan.weight;
+            job.map = task.map;
             
             // Note: dependents will be filled by Orchestrator or we can do it here
             // For now, let's fill dependents here for completeness
@@ -2016,77 +2861,463 @@ fn handle_plans_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
     Ok(RespValue::BulkString(response.into_bytes()))
 }
 
-/// Handle ACTION.LIST command
+/// Handle PLAN.SCHEDULE command
 ///
-/// Usage: ACTION.LIST [status] [offset] [limit]
-/// - status: Optional status filter (pending/running/completed/failed)
-/// - offset: Start index (default: 0)
-/// - limit: Max actions to return (default: 100, max: 1000)
+/// Usage: PLAN.SCHEDULE <schedule_json>
 ///
-/// Returns list of all actions (optionally filtered by status) with job progress
-fn handle_actions_list(args: &[RespValue], db: &Database) -> Result<RespValue> {
-    const DEFAULT_LIMIT: i64 = 100;
-    const MAX_LIMIT: i64 = 1000;
-
-    // Parse optional status filter
-    let status_filter = if args.len() > 1 {
-        let status = args[1].as_string()?;
-        if !status.is_empty() && status != "all" {
-            // Validate status value
-            if !["pending", "running", "completed", "failed"].contains(&status.as_str()) {
-                return Err(Error::InvalidArguments(
-                    "status must be one of: pending, running, completed, failed, all".to_string(),
-                ));
-            }
-            Some(status)
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-
-    // Parse pagination arguments
-    let offset = if args.len() > 2 {
-        args[2].as_string()?.parse::<i64>().map_err(|_| {
-            Error::InvalidArguments("offset must be a non-negative integer".to_string())
-        })?
-    } else {
-        0
-    };
-
-    let limit = if args.len() > 3 {
-        let requested = args[3]
-            .as_string()?
-            .parse::<i64>()
-            .map_err(|_| Error::InvalidArguments("limit must be a positive integer".to_string()))?;
-        requested.min(MAX_LIMIT)
-    } else {
-        DEFAULT_LIMIT
-    };
-
-    if offset < 0 {
+/// Schedules Actions of a stored Plan, either once after `delay_secs` or
+/// repeatedly on a five-field `cron` expression (UTC):
+///
+/// ```json
+/// {"schedule_id": "nightly-ocr", "plan_id": "ocr", "cron": "0 2 * * *", "inputs": [{}]}
+/// ```
+///
+/// Returns the schedule ID and its first run time.
+fn handle_plan_schedule(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
         return Err(Error::InvalidArguments(
-            "offset must be non-negative".to_string(),
+            "PLAN.SCHEDULE requires exactly one argument (schedule JSON)".to_string(),
         ));
     }
 
-    if limit <= 0 {
+    let schedule_json = args[1].as_string()?;
+    if schedule_json.len() > MAX_PLAN_SIZE {
+        return Err(Error::InvalidArguments(format!(
+            "Schedule JSON too large (max {} bytes)",
+            MAX_PLAN_SIZE
+        )));
+    }
+
+    let request: serde_json::Value = serde_json::from_str(&schedule_json)
+        .map_err(|e| Error::InvalidArguments(format!("Invalid JSON: {}", e)))?;
+    let schedule = Schedule::from_request(&request, get_current_timestamp_secs()?)?;
+
+    validate_identifier(&schedule.schedule_id, "schedule_id")?;
+    validate_identifier(&schedule.plan_id, "plan_id")?;
+    if schedule.inputs.len() > 100 {
         return Err(Error::InvalidArguments(
-            "limit must be positive".to_string(),
+            "inputs array exceeds maximum of 100 inputs per Action".to_string(),
         ));
     }
 
-    // Get all actions from the sorted set (indexed by timestamp)
-    // Use checked arithmetic to prevent overflow
-    let stop = offset
-        .checked_add(limit)
-        .and_then(|sum| sum.checked_sub(1))
-        .ok_or_else(|| Error::InvalidArguments("Pagination range overflow".to_string()))?;
-
-    let action_entries = db.zrange("actions:all", offset, stop)?;
+    let plan_key = format!("plan:{}", schedule.plan_id);
+    if db.hget(&plan_key, "json")?.is_none() {
+        return Err(Error::InvalidArguments(format!(
+            "Plan not found: {}",
+            schedule.plan_id
+        )));
+    }
+    if Schedule::load(db, &schedule.schedule_id)?.is_some() {
+        return Err(Error::InvalidArguments(format!(
+            "Schedule ID already exists: {}",
+            schedule.schedule_id
+        )));
+    }
+    if db.zcard(schedule::DUE_INDEX)? >= schedule::MAX_SCHEDULES {
+        return Err(Error::Protocol(format!(
+            "Maximum schedule limit reached ({} schedules)",
+            schedule::MAX_SCHEDULES
+        )));
+    }
 
-    let mut actions: Vec<serde_json::Value> = Vec::new();
+    schedule.save(db)?;
+
+    let response = serde_json::json!({
+        "schedule_id": schedule.schedule_id,
+        "plan_id": schedule.plan_id,
+        "next_run_at": schedule.next_run_at,
+    });
+    let response_json = serde_json::to_string(&response)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!(
+        "PLAN.SCHEDULE {} -> {} (next run at {})",
+        schedule.plan_id, schedule.schedule_id, schedule.next_run_at
+    );
+    Ok(RespValue::BulkString(response_json.into_bytes()))
+}
+
+/// Handle PLAN.UNSCHEDULE command
+///
+/// Usage: PLAN.UNSCHEDULE <schedule_id>
+///
+/// Returns 1 if the schedule was removed, 0 if it did not exist.
+fn handle_plan_unschedule(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "PLAN.UNSCHEDULE requires exactly one argument (schedule_id)".to_string(),
+        ));
+    }
+
+    let schedule_id = args[1].as_string()?;
+    validate_identifier(&schedule_id, "schedule_id")?;
+
+    let removed = Schedule::remove(db, &schedule_id)?;
+    debug!("PLAN.UNSCHEDULE {} -> {}", schedule_id, removed);
+    Ok(RespValue::Integer(i64::from(removed)))
+}
+
+/// Handle PLAN.CANCEL command
+///
+/// Usage: PLAN.CANCEL <plan_id>
+///
+/// Cancels every unfinished job of the Plan's Actions (see JOB.CANCEL).
+/// Returns the number of jobs cancelled.
+fn handle_plan_cancel(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "PLAN.CANCEL requires exactly one argument (plan_id)".to_string(),
+        ));
+    }
+
+    let plan_id = args[1].as_string()?;
+    validate_identifier(&plan_id, "plan_id")?;
+    if !db.exists(&format!("plan:{}", plan_id))? {
+        return Err(Error::InvalidArguments(format!(
+            "Plan not found: {}",
+            plan_id
+        )));
+    }
+
+    let cancelled = Orchestrator::new(db).cancel_plan(&plan_id, get_current_timestamp_secs()?)?;
+    info!("PLAN.CANCEL {} -> {} jobs", plan_id, cancelled);
+    Ok(RespValue::Integer(cancelled as i64))
+}
+
+/// Handle PLAN.SCHEDULES command
+///
+/// Returns a JSON array of all schedules, soonest first, with their next
+/// and last run times and the Action created by the last run.
+fn handle_plan_schedules(_args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let mut schedules = Vec::new();
+    for (member, _next_run_at) in db.zrange(schedule::DUE_INDEX, 0, -1)? {
+        let schedule_id = String::from_utf8_lossy(&member);
+        if let Some(schedule) = Schedule::load(db, &schedule_id)? {
+            schedules.push(schedule);
+        }
+    }
+
+    let response = serde_json::to_string(&schedules)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!("PLAN.SCHEDULES -> {} schedules", schedules.len());
+    Ok(RespValue::BulkString(response.into_bytes()))
+}
+
+/// Handle PLAN.STATUS command
+///
+/// Usage: PLAN.STATUS <plan_id> [action_id]
+///
+/// Returns the aggregate status of one run of a plan: the overall state,
+/// counts per job status, and each task's state, timing, and exit code.
+/// Without an action_id, reports the plan's most recent action.
+fn handle_plan_status(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let (plan_id, action_id) = resolve_plan_action(args, db, "PLAN.STATUS")?;
+    let jobs = load_action_jobs(db, &action_id)?;
+
+    let mut summary: HashMap<String, u64> = HashMap::new();
+    let mut tasks = Vec::with_capacity(jobs.len());
+    for job in &jobs {
+        let status = reported_job_status(db, job)?;
+        *summary.entry(status.clone()).or_default() += 1;
+
+        let result = job_result_envelope(db, &job.id)?;
+        let exit_code = result
+            .as_ref()
+            .and_then(|r| r["exit_code"].as_i64())
+            .or(job.exit_code.map(i64::from));
+        let duration_ms = result.as_ref().and_then(|r| r["duration_ms"].as_u64());
+
+        tasks.push(serde_json::json!({
+            "job_id": job.id,
+            "task_number": job.task_number,
+            "command": job.command,
+            "status": status,
+            "exit_code": exit_code,
+            "worker_id": job.worker_id,
+            "created_at": job.created_at,
+            "started_at": job.started_at,
+            "completed_at": job.completed_at,
+            "duration_ms": duration_ms,
+            "retries": job.retries,
+            "cause": job.cause,
+        }));
+    }
+
+    let statuses: Vec<&str> = tasks
+        .iter()
+        .filter_map(|task| task["status"].as_str())
+        .collect();
+    let status = aggregate_plan_status(&statuses);
+
+    let response = serde_json::json!({
+        "plan_id": plan_id,
+        "action_id": action_id,
+        "status": status,
+        "created_at": jobs.iter().map(|job| job.created_at).min(),
+        "completed_at": if status == "completed" || status == "failed" {
+            jobs.iter().filter_map(|job| job.completed_at).max()
+        } else {
+            None
+        },
+        "summary": summary,
+        "tasks": tasks,
+    });
+
+    let response_json = serde_json::to_string(&response)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!(
+        "PLAN.STATUS {} ({}) -> {} ({} tasks)",
+        plan_id,
+        action_id,
+        status,
+        jobs.len()
+    );
+    Ok(RespValue::BulkString(response_json.into_bytes()))
+}
+
+/// Handle PLAN.RESULTS command
+///
+/// Usage: PLAN.RESULTS <plan_id> [action_id]
+///
+/// Returns the output of every task in one run of a plan, in task order:
+/// the worker's result envelope together with the stored stdout and stderr.
+/// Without an action_id, reports the plan's most recent action.
+fn handle_plan_results(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let (plan_id, action_id) = resolve_plan_action(args, db, "PLAN.RESULTS")?;
+    let jobs = load_action_jobs(db, &action_id)?;
+
+    let mut statuses = Vec::with_capacity(jobs.len());
+    let mut results = Vec::with_capacity(jobs.len());
+    for job in &jobs {
+        let status = reported_job_status(db, job)?;
+        let mut entry = serde_json::json!({
+            "job_id": job.id,
+            "task_number": job.task_number,
+            "status": status,
+            "result": job_result_envelope(db, &job.id)?,
+        });
+        for stream in ["stdout", "stderr"] {
+            let output = db
+                .get(&format!("job:{}:{}", job.id, stream))?
+                .map(|output| String::from_utf8_lossy(&output).into_owned());
+            let encoding = db
+                .get(&format!("job:{}:{}_encoding", job.id, stream))?
+                .map(|encoding| String::from_utf8_lossy(&encoding).into_owned())
+                .unwrap_or_else(|| "utf8".to_string());
+            entry[stream] = serde_json::json!(output);
+            entry[format!("{}_encoding", stream)] = serde_json::json!(encoding);
+            // Output over the inline limit is stored as an artifact
+            if let Some(hash) = db.get(&format!("job:{}:{}_artifact", job.id, stream))? {
+                entry[format!("{}_artifact", stream)] =
+                    serde_json::json!(String::from_utf8_lossy(&hash));
+            }
+        }
+        statuses.push(status);
+        results.push(entry);
+    }
+
+    let statuses: Vec<&str> = statuses.iter().map(String::as_str).collect();
+    let response = serde_json::json!({
+        "plan_id": plan_id,
+        "action_id": action_id,
+        "status": aggregate_plan_status(&statuses),
+        "results": results,
+    });
+
+    let response_json = serde_json::to_string(&response)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!(
+        "PLAN.RESULTS {} ({}) -> {} bytes",
+        plan_id,
+        action_id,
+        response_json.len()
+    );
+    Ok(RespValue::BulkString(response_json.into_bytes()))
+}
+
+/// Parse `<plan_id> [action_id]`, defaulting to the plan's latest action
+///
+/// # Errors
+/// Returns an error if the arguments are invalid, the plan has never been
+/// run, or the action belongs to a different plan
+fn resolve_plan_action(
+    args: &[RespValue],
+    db: &Database,
+    command: &str,
+) -> Result<(String, String)> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(Error::InvalidArguments(format!(
+            "{} requires a plan_id and an optional action_id",
+            command
+        )));
+    }
+
+    let plan_id = args[1].as_string()?;
+    validate_identifier(&plan_id, "plan_id")?;
+
+    let action_id = if let Some(arg) = args.get(2) {
+        let action_id = arg.as_string()?;
+        validate_identifier(&action_id, "action_id")?;
+        let owner = db.hget(&format!("action:{}", action_id), "plan_id")?;
+        if owner.as_deref() != Some(plan_id.as_bytes()) {
+            return Err(Error::InvalidArguments(format!(
+                "Action {} not found for plan {}",
+                action_id, plan_id
+            )));
+        }
+        action_id
+    } else {
+        // Actions are indexed with LPUSH, so the latest is at the head
+        let latest = db
+            .lrange(&format!("plan:{}:actions", plan_id), 0, 0)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Error::InvalidArguments(format!("No actions found for plan: {}", plan_id))
+            })?;
+        String::from_utf8(latest)
+            .map_err(|_| Error::Protocol("Invalid action_id encoding".to_string()))?
+    };
+
+    Ok((plan_id, action_id))
+}
+
+/// Load the jobs an action created, ordered by task number
+pub(crate) fn load_action_jobs(db: &Database, action_id: &str) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for job_id in db.lrange(&format!("action:{}:jobs", action_id), 0, -1)? {
+        let job_id = String::from_utf8(job_id)
+            .map_err(|_| Error::Protocol("Invalid job_id encoding".to_string()))?;
+        let Some(json) = db.get(&format!("job:{}", job_id))? else {
+            warn!("Action {} lists missing job {}", action_id, job_id);
+            continue;
+        };
+        let job: Job = serde_json::from_slice(&json)
+            .map_err(|e| Error::Protocol(format!("Failed to deserialize job: {}", e)))?;
+        jobs.push(job);
+    }
+    jobs.sort_by(|a, b| (a.task_number, &a.id).cmp(&(b.task_number, &b.id)));
+    Ok(jobs)
+}
+
+/// A job's status, preferring the final status posted by its worker
+pub(crate) fn reported_job_status(db: &Database, job: &Job) -> Result<String> {
+    if let Some(status) = db.get(&format!("job:{}:status", job.id))? {
+        return String::from_utf8(status)
+            .map_err(|_| Error::Protocol("Invalid job status encoding".to_string()));
+    }
+    serde_json::to_value(job.status)
+        .ok()
+        .and_then(|status| status.as_str().map(str::to_string))
+        .ok_or_else(|| Error::Protocol("Failed to serialize job status".to_string()))
+}
+
+/// The result envelope a worker posted for a job, if any
+fn job_result_envelope(db: &Database, job_id: &str) -> Result<Option<serde_json::Value>> {
+    Ok(db
+        .get(&format!("job:{}:result", job_id))?
+        .and_then(|json| serde_json::from_slice(&json).ok()))
+}
+
+/// Overall state of a plan run from the statuses of its jobs
+///
+/// - `completed`: every job completed
+/// - `failed`: every job finished, and at least one did not complete
+/// - `running`: some job has been queued or started
+/// - `pending`: no job has been queued yet
+pub(crate) fn aggregate_plan_status(statuses: &[&str]) -> &'static str {
+    let finished = |status: &str| {
+        matches!(
+            status,
+            "completed" | "failed" | "timeout" | "policy_violation" | "cancelled"
+        )
+    };
+
+    if statuses.iter().all(|status| *status == "completed") {
+        "completed"
+    } else if statuses.iter().all(|status| finished(status)) {
+        "failed"
+    } else if statuses.iter().all(|status| *status == "pending") {
+        "pending"
+    } else {
+        "running"
+    }
+}
+
+/// Handle ACTION.LIST command
+///
+/// Usage: ACTION.LIST [status] [offset] [limit]
+/// - status: Optional status filter (pending/running/completed/failed)
+/// - offset: Start index (default: 0)
+/// - limit: Max actions to return (default: 100, max: 1000)
+///
+/// Returns list of all actions (optionally filtered by status) with job progress
+fn handle_actions_list(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    const DEFAULT_LIMIT: i64 = 100;
+    const MAX_LIMIT: i64 = 1000;
+
+    // Parse optional status filter
+    let status_filter = if args.len() > 1 {
+        let status = args[1].as_string()?;
+        if !status.is_empty() && status != "all" {
+            // Validate status value
+            if !["pending", "running", "completed", "failed"].contains(&status.as_str()) {
+                return Err(Error::InvalidArguments(
+                    "status must be one of: pending, running, completed, failed, all".to_string(),
+                ));
+            }
+            Some(status)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    // Parse pagination arguments
+    let offset = if args.len() > 2 {
+        args[2].as_string()?.parse::<i64>().map_err(|_| {
+            Error::InvalidArguments("offset must be a non-negative integer".to_string())
+        })?
+    } else {
+        0
+    };
+
+    let limit = if args.len() > 3 {
+        let requested = args[3]
+            .as_string()?
+            .parse::<i64>()
+            .map_err(|_| Error::InvalidArguments("limit must be a positive integer".to_string()))?;
+        requested.min(MAX_LIMIT)
+    } else {
+        DEFAULT_LIMIT
+    };
+
+    if offset < 0 {
+        return Err(Error::InvalidArguments(
+            "offset must be non-negative".to_string(),
+        ));
+    }
+
+    if limit <= 0 {
+        return Err(Error::InvalidArguments(
+            "limit must be positive".to_string(),
+        ));
+    }
+
+    // Get all actions from the sorted set (indexed by timestamp)
+    // Use checked arithmetic to prevent overflow
+    let stop = offset
+        .checked_add(limit)
+        .and_then(|sum| sum.checked_sub(1))
+        .ok_or_else(|| Error::InvalidArguments("Pagination range overflow".to_string()))?;
+
+    let action_entries = db.zrange("actions:all", offset, stop)?;
+
+    let mut actions: Vec<serde_json::Value> = Vec::new();
 
     for (action_id_bytes, _score) in action_entries {
         let action_id = std::str::from_utf8(&action_id_bytes)
@@ -2331,9 +3562,12 @@ fn handle_actions_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
 
 /// Handle JOBS.LIST command
 ///
-/// Returns a simple array of job information.
+/// Returns a simple array of job information, newest first.
 /// Format: [job_id, status, created_at, job_id, status, created_at, ...]
 ///
+/// Kept for existing clients; JOB.LIST returns the same jobs as JSON and
+/// can filter them.
+///
 /// # Security
 /// - Requires authentication
 /// - Supports pagination to prevent DoS via large result sets
@@ -2341,26 +3575,17 @@ fn handle_actions_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
 /// # Arguments
 /// * `args` - RESP arguments: [command, offset?, limit?]
 /// * `db` - Database handle
-///
-/// # Note
-/// Currently AGQ doesn't maintain a global jobs:all index, so this implementation
-/// will need to be enhanced when such an index is added. For now, it returns
-/// an empty array as a placeholder.
-fn handle_jobs_list(args: &[RespValue], _db: &Database) -> Result<RespValue> {
-    const DEFAULT_LIMIT: u64 = 100;
-    const MAX_LIMIT: u64 = 1000;
-
+fn handle_jobs_list(args: &[RespValue], db: &Database) -> Result<RespValue> {
     // Parse optional offset and limit arguments
     // Using u64 enforces non-negativity at type level
-    let offset = if args.len() > 1 {
-        args[1].as_string()?.parse::<u64>().map_err(|_| {
+    let mut query = JobQuery::default();
+    if args.len() > 1 {
+        query.offset = args[1].as_string()?.parse::<u64>().map_err(|_| {
             Error::InvalidArguments("offset must be a non-negative integer".to_string())
-        })?
-    } else {
-        0
-    };
+        })?;
+    }
 
-    let limit = if args.len() > 2 {
+    if args.len() > 2 {
         let requested = args[2]
             .as_string()?
             .parse::<u64>()
@@ -2368,66 +3593,338 @@ fn handle_jobs_list(args: &[RespValue], _db: &Database) -> Result<RespValue> {
         if requested == 0 {
             return Err(Error::InvalidArguments("limit must be > 0".to_string()));
         }
-        requested.min(MAX_LIMIT) // Enforce maximum
-    } else {
-        DEFAULT_LIMIT
-    };
-
-    // NOTE: AGQ currently doesn't maintain a global jobs:all index
-    // Jobs are only indexed by action (action:{action_id}:jobs)
-    // This is a placeholder implementation that returns empty array
-    // TODO(#43): When jobs:all sorted set is added, implement proper listing
-    // See: https://github.com/agenix-sh/agq/issues/43#future-work
+        query.limit = requested.min(JobQuery::MAX_LIMIT); // Enforce maximum
+    }
 
-    let jobs: Vec<RespValue> = Vec::new();
+    let (jobs, _total) = list_jobs(db, &query)?;
+    let mut items = Vec::with_capacity(jobs.len() * 3);
+    for job in &jobs {
+        for field in ["job_id", "status", "created_at"] {
+            items.push(RespValue::BulkString(
+                match &job[field] {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                }
+                .into_bytes(),
+            ));
+        }
+    }
 
     debug!(
         "JOBS.LIST -> {} jobs (offset: {}, limit: {})",
         jobs.len(),
-        offset,
-        limit
+        query.offset,
+        query.limit
     );
-    Ok(RespValue::Array(jobs))
+    Ok(RespValue::Array(items))
 }
 
-/// Handle JOB.GET command
-///
-/// Returns job metadata including plan_id reference and input data.
-/// Workers use this to retrieve job details after popping from queue.
+/// Handle JOB.LIST command
 ///
-/// # Security
-/// - Requires authentication
-/// - Validates job_id format to prevent injection
+/// Usage: JOB.LIST [filter value]...
 ///
-/// # Arguments
-/// * `args` - RESP arguments: [command, job_id]
-/// * `db` - Database handle
+/// Lists jobs newest first, as a JSON object with the page of `jobs`, the
+/// `total` number of jobs matching the filters, and the `offset` and
+/// `limit` used. Filters, given as name/value pairs:
+/// - plan_id, action_id: jobs of a Plan or Action
+/// - status: reported status, e.g. running or failed
+/// - worker: ID of the worker that took the job
+/// - tag: a required worker tag, e.g. gpu
+/// - since, until: creation time range, in Unix seconds (inclusive)
+/// - offset: jobs to skip (default: 0)
+/// - limit: max jobs to return (default: 100, max: 1000)
 ///
-/// # Returns
-/// JSON object with job metadata:
-/// ```json
-/// {
-///   "job_id": "job_abc123",
-///   "plan_id": "plan_xyz789",
-///   "input": {...},
-///   "status": "pending",
-///   "created_at": 1234567890
-/// }
-/// ```
-fn handle_job_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
-    // Security: Check rate limit to prevent DoS from malicious/misconfigured workers
-    if JOB_GET_LIMITER.check().is_err() {
-        warn!("JOB.GET rate limit exceeded");
-        return Err(Error::Protocol(
-            "Rate limit exceeded for JOB.GET (max 100/second)".to_string(),
-        ));
-    }
+/// Example: `JOB.LIST plan_id ocr status failed limit 20`
+fn handle_job_list(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let query = JobQuery::parse(args)?;
+    let (jobs, total) = list_jobs(db, &query)?;
 
-    // Validate arguments
-    if args.len() != 2 {
-        return Err(Error::InvalidArguments(
-            "JOB.GET requires exactly one argument (job_id)".to_string(),
-        ));
+    let response = serde_json::json!({
+        "jobs": jobs,
+        "total": total,
+        "offset": query.offset,
+        "limit": query.limit,
+    });
+    let response = serde_json::to_string(&response)
+        .map_err(|_| Error::Protocol("Failed to serialize response".to_string()))?;
+
+    debug!("JOB.LIST -> {} of {} jobs", jobs.len(), total);
+    Ok(RespValue::BulkString(response.into_bytes()))
+}
+
+/// Filters and page of a job listing
+#[derive(Debug)]
+struct JobQuery {
+    plan_id: Option<String>,
+    action_id: Option<String>,
+    status: Option<String>,
+    worker: Option<String>,
+    tag: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    offset: u64,
+    limit: u64,
+}
+
+impl Default for JobQuery {
+    fn default() -> Self {
+        Self {
+            plan_id: None,
+            action_id: None,
+            status: None,
+            worker: None,
+            tag: None,
+            since: None,
+            until: None,
+            offset: 0,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl JobQuery {
+    const DEFAULT_LIMIT: u64 = 100;
+    const MAX_LIMIT: u64 = 1000;
+
+    /// Parse the name/value pairs following JOB.LIST
+    fn parse(args: &[RespValue]) -> Result<Self> {
+        let pairs = args.get(1..).unwrap_or_default();
+        if pairs.len() % 2 != 0 {
+            return Err(Error::InvalidArguments(
+                "JOB.LIST filters must be name/value pairs".to_string(),
+            ));
+        }
+
+        let number = |name: &str, value: &str| {
+            value.parse::<u64>().map_err(|_| {
+                Error::InvalidArguments(format!("{} must be a non-negative integer", name))
+            })
+        };
+
+        let mut query = Self::default();
+        for pair in pairs.chunks(2) {
+            let name = pair[0].as_string()?.to_lowercase();
+            let value = pair[1].as_string()?;
+            match name.as_str() {
+                "plan_id" | "action_id" | "worker" | "tag" => {
+                    validate_identifier(&value, &name)?;
+                    let field = match name.as_str() {
+                        "plan_id" => &mut query.plan_id,
+                        "action_id" => &mut query.action_id,
+                        "worker" => &mut query.worker,
+                        _ => &mut query.tag,
+                    };
+                    *field = Some(value);
+                }
+                "status" => {
+                    let known = serde_json::from_value::<JobStatus>(value.as_str().into());
+                    if known.is_err() {
+                        return Err(Error::InvalidArguments(format!(
+                            "Unknown job status: {}",
+                            value
+                        )));
+                    }
+                    query.status = Some(value);
+                }
+                "since" => query.since = Some(number(&name, &value)?),
+                "until" => query.until = Some(number(&name, &value)?),
+                "offset" => query.offset = number(&name, &value)?,
+                "limit" => {
+                    let limit = number(&name, &value)?;
+                    if limit == 0 {
+                        return Err(Error::InvalidArguments("limit must be > 0".to_string()));
+                    }
+                    query.limit = limit.min(Self::MAX_LIMIT);
+                }
+                _ => {
+                    return Err(Error::InvalidArguments(format!(
+                        "Unknown JOB.LIST filter: {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(query)
+    }
+
+    /// Whether a job's fields pass every filter
+    fn matches(&self, job: &Job, status: &str, worker: Option<&str>) -> bool {
+        self.plan_id.as_deref().is_none_or(|id| id == job.plan_id)
+            && self
+                .action_id
+                .as_deref()
+                .is_none_or(|id| id == job.action_id)
+            && self.status.as_deref().is_none_or(|wanted| wanted == status)
+            && self.worker.as_deref().is_none_or(|id| Some(id) == worker)
+            && self.tag.as_ref().is_none_or(|tag| job.tags.contains(tag))
+    }
+}
+
+/// Jobs matching a query, newest first, and how many match in total
+///
+/// Walks the `jobs:all` index within the query's time range, so the cost
+/// grows with the number of jobs in that range rather than the page size.
+fn list_jobs(db: &Database, query: &JobQuery) -> Result<(Vec<serde_json::Value>, u64)> {
+    let since = query.since.unwrap_or(0) as f64;
+    let until = query.until.unwrap_or(u64::MAX) as f64;
+
+    let mut jobs = Vec::new();
+    let mut total = 0;
+    let newest_first = db
+        .zrangebyscore(JOBS_INDEX, since, until)?
+        .into_iter()
+        .rev();
+    for (member, _created_at) in newest_first {
+        let job_id = String::from_utf8_lossy(&member);
+        let Some(json) = db.get(&format!("job:{}", job_id))? else {
+            continue;
+        };
+        let Ok(job) = serde_json::from_slice::<Job>(&json) else {
+            warn!("Skipping job {} with unreadable JSON", job_id);
+            continue;
+        };
+
+        let status = reported_job_status(db, &job)?;
+        let worker = match db.get(&format!("job:{}:worker", job.id))? {
+            Some(worker) => Some(String::from_utf8_lossy(&worker).into_owned()),
+            None => job.worker_id.clone(),
+        };
+        if !query.matches(&job, &status, worker.as_deref()) {
+            continue;
+        }
+
+        total += 1;
+        if total <= query.offset || jobs.len() as u64 >= query.limit {
+            continue;
+        }
+        jobs.push(serde_json::json!({
+            "job_id": job.id,
+            "plan_id": job.plan_id,
+            "action_id": job.action_id,
+            "task_number": job.task_number,
+            "command": job.command,
+            "status": status,
+            "worker_id": worker,
+            "tags": job.tags,
+            "priority": job.priority,
+            "created_at": job.created_at,
+            "started_at": job.started_at,
+            "completed_at": job.completed_at,
+        }));
+    }
+    Ok((jobs, total))
+}
+
+/// Handle JOB.PURGE command
+///
+/// Usage: JOB.PURGE <job_id>
+///        JOB.PURGE OLDER_THAN <seconds>
+///
+/// Deletes a finished job, or every job that finished at least `seconds`
+/// ago, with its results, logs, and status keys. An Action whose last job
+/// is deleted is removed too. Jobs still pending or running are kept.
+///
+/// Returns the number of jobs deleted.
+fn handle_job_purge(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let purged = match args.len() {
+        2 => {
+            let job_id = args[1].as_string()?;
+            validate_identifier(&job_id, "job_id")?;
+            match retention::purge_job(db, &job_id)? {
+                Some(true) => 1,
+                Some(false) => {
+                    return Err(Error::InvalidArguments(format!(
+                        "Job {} has not finished",
+                        job_id
+                    )))
+                }
+                None => 0,
+            }
+        }
+        3 if args[1].as_string()?.eq_ignore_ascii_case("OLDER_THAN") => {
+            let age = args[2].as_string()?.parse::<u64>().map_err(|_| {
+                Error::InvalidArguments("seconds must be a non-negative integer".to_string())
+            })?;
+            let cutoff = get_current_timestamp_secs()?.saturating_sub(age);
+            retention::purge_finished_before(db, cutoff)?
+        }
+        _ => {
+            return Err(Error::InvalidArguments(
+                "JOB.PURGE requires a job_id or OLDER_THAN <seconds>".to_string(),
+            ))
+        }
+    };
+
+    info!("JOB.PURGE -> {} jobs", purged);
+    Ok(RespValue::Integer(purged as i64))
+}
+
+/// Handle JOB.CANCEL command
+///
+/// Usage: JOB.CANCEL <job_id>
+///
+/// Cancels a job that has not finished, and every pending job downstream
+/// of it. A running job's claim is revoked, which tells its worker to stop
+/// it. Returns the number of jobs cancelled: 0 if the job had finished.
+fn handle_job_cancel(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "JOB.CANCEL requires exactly one argument (job_id)".to_string(),
+        ));
+    }
+
+    let job_id = args[1].as_string()?;
+    validate_identifier(&job_id, "job_id")?;
+    if !db.exists(&format!("job:{}", job_id))? {
+        return Err(Error::InvalidArguments(format!(
+            "Job not found: {}",
+            job_id
+        )));
+    }
+
+    let cancelled = Orchestrator::new(db).cancel_job(&job_id, get_current_timestamp_secs()?)?;
+    info!("JOB.CANCEL {} -> {} jobs", job_id, cancelled);
+    Ok(RespValue::Integer(cancelled as i64))
+}
+
+/// Handle JOB.GET command
+///
+/// Returns job metadata including plan_id reference and input data.
+/// Workers use this to retrieve job details after popping from queue.
+///
+/// # Security
+/// - Requires authentication
+/// - Validates job_id format to prevent injection
+///
+/// # Arguments
+/// * `args` - RESP arguments: [command, job_id]
+/// * `db` - Database handle
+///
+/// # Returns
+/// JSON object with job metadata:
+/// ```json
+/// {
+///   "job_id": "job_abc123",
+///   "plan_id": "plan_xyz789",
+///   "input": {...},
+///   "status": "pending",
+///   "created_at": 1234567890
+/// }
+/// ```
+fn handle_job_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    // Security: Check rate limit to prevent DoS from malicious/misconfigured workers
+    if JOB_GET_LIMITER.check().is_err() {
+        warn!("JOB.GET rate limit exceeded");
+        return Err(Error::Protocol(
+            "Rate limit exceeded for JOB.GET (max 100/second)".to_string(),
+        ));
+    }
+
+    // Validate arguments
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "JOB.GET requires exactly one argument (job_id)".to_string(),
+        ));
     }
 
     let job_id = args[1].as_string()?;
@@ -2447,25 +3944,213 @@ fn handle_job_get(args: &[RespValue], db: &Database) -> Result<RespValue> {
     Ok(RespValue::BulkString(job_json_bytes))
 }
 
+/// Handle JOB.ENV command
+///
+/// Usage: JOB.ENV <job_id>
+///
+/// Returns the job's environment as JSON with the `secret://` references
+/// AGQ holds values for resolved, for the worker about to run it. Jobs
+/// keep the references, so JOB.GET never shows the values. Only jobs that
+/// are ready or running have their environment resolved.
+fn handle_job_env(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "JOB.ENV requires exactly one argument (job_id)".to_string(),
+        ));
+    }
+
+    let job_id = args[1].as_string()?;
+    validate_identifier(&job_id, "job_id")?;
+
+    let job_json = db
+        .get(&format!("job:{}", job_id))?
+        .ok_or_else(|| Error::InvalidArguments(format!("Job not found: {}", job_id)))?;
+    let job: Job = serde_json::from_slice(&job_json)
+        .map_err(|e| Error::Protocol(format!("Failed to parse job JSON: {}", e)))?;
+    if !matches!(job.status, JobStatus::Ready | JobStatus::Running) {
+        return Err(Error::InvalidArguments(format!(
+            "Job {} is not ready or running",
+            job_id
+        )));
+    }
+
+    let env = secrets::resolve_env(db, &job.env)?;
+    Ok(RespValue::BulkString(env.to_string().into_bytes()))
+}
+
+/// Handle EVENT.FIRE command
+///
+/// Usage: EVENT.FIRE <token> [payload]
+///
+/// Releases the job waiting for the event the token names: a job of a task
+/// with `await_event` gets a token once it could run, shown as its
+/// `event_token`. A payload (max 64KB) is added to the job's env as
+/// `EVENT_PAYLOAD`, e.g. an approver's note or a CI build's result.
+///
+/// Returns the released job's ID, or nil if no job is waiting for the event.
+fn handle_event_fire(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(Error::InvalidArguments(
+            "EVENT.FIRE requires a token and an optional payload".to_string(),
+        ));
+    }
+
+    let token = args[1].as_string()?;
+    validate_identifier(&token, "token")?;
+    let payload = args.get(2).map(RespValue::as_string).transpose()?;
+    if payload
+        .as_ref()
+        .is_some_and(|p| p.len() > MAX_EVENT_PAYLOAD)
+    {
+        return Err(Error::InvalidArguments(format!(
+            "Event payload exceeds maximum of {} bytes",
+            MAX_EVENT_PAYLOAD
+        )));
+    }
+
+    match Orchestrator::new(db).fire_event(&token, payload.as_deref())? {
+        Some(job_id) => Ok(RespValue::BulkString(job_id.into_bytes())),
+        None => Ok(RespValue::NullBulkString),
+    }
+}
+
+/// Handle JOB.LOG command
+///
+/// Syntax: JOB.LOG <job_id> <stdout|stderr> <chunk>
+/// Returns: Number of chunks stored for the stream
+///
+/// Workers stream job output while it runs. Chunks are pushed onto the list
+/// `job:<job_id>:log:<stream>`; as lists grow at the head, read them back
+/// with `LRANGE` and reverse to get output in order.
+fn handle_job_log(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    /// Maximum size of a single log chunk (1MB)
+    const MAX_LOG_CHUNK_SIZE: usize = 1024 * 1024;
+
+    if args.len() != 4 {
+        return Err(Error::InvalidArguments(
+            "JOB.LOG requires exactly three arguments (job_id, stream, chunk)".to_string(),
+        ));
+    }
+
+    let job_id = args[1].as_string()?;
+    validate_identifier(&job_id, "job_id")?;
+
+    let stream = args[2].as_string()?;
+    if !matches!(stream.as_str(), "stdout" | "stderr") {
+        return Err(Error::InvalidArguments(format!(
+            "Invalid log stream '{}': must be stdout or stderr",
+            stream
+        )));
+    }
+
+    let RespValue::BulkString(chunk) = &args[3] else {
+        return Err(Error::InvalidArguments(
+            "JOB.LOG chunk must be a bulk string".to_string(),
+        ));
+    };
+
+    if chunk.len() > MAX_LOG_CHUNK_SIZE {
+        return Err(Error::InvalidArguments(format!(
+            "Log chunk too large: {} bytes (max {})",
+            chunk.len(),
+            MAX_LOG_CHUNK_SIZE
+        )));
+    }
+
+    let log_key = format!("job:{}:log:{}", job_id, stream);
+    let length = db.lpush(&log_key, chunk)?;
+    Ok(RespValue::Integer(length as i64))
+}
+
+/// Maximum size of a single artifact (must also fit in one RESP message)
+const MAX_ARTIFACT_SIZE: usize = 1024 * 1024;
+
+/// Handle ARTIFACT.PUT command
+///
+/// Syntax: ARTIFACT.PUT <data>
+/// Returns: Hex SHA-256 of the data
+///
+/// Artifacts are content-addressed (see [`crate::artifacts`]), so identical
+/// outputs from different jobs share one copy and a hash reference always
+/// resolves to the bytes it was computed from.
+fn handle_artifact_put(
+    args: &[RespValue],
+    db: &Database,
+    settings: &Settings,
+) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "ARTIFACT.PUT requires exactly one argument (data)".to_string(),
+        ));
+    }
+
+    let RespValue::BulkString(data) = &args[1] else {
+        return Err(Error::InvalidArguments(
+            "ARTIFACT.PUT data must be a bulk string".to_string(),
+        ));
+    };
+
+    if data.len() > MAX_ARTIFACT_SIZE {
+        return Err(Error::InvalidArguments(format!(
+            "Artifact too large: {} bytes (max {})",
+            data.len(),
+            MAX_ARTIFACT_SIZE
+        )));
+    }
+
+    // Storing the same data again keeps it from expiring
+    let hash = settings
+        .artifacts
+        .put(db, data, get_current_timestamp_secs()?)?;
+
+    debug!("ARTIFACT.PUT {} ({} bytes)", hash, data.len());
+    Ok(RespValue::BulkString(hash.into_bytes()))
+}
+
+/// Handle ARTIFACT.GET command
+///
+/// Syntax: ARTIFACT.GET <sha256>
+/// Returns: Artifact data, or nil if no artifact has that hash
+fn handle_artifact_get(
+    args: &[RespValue],
+    db: &Database,
+    settings: &Settings,
+) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "ARTIFACT.GET requires exactly one argument (sha256)".to_string(),
+        ));
+    }
+
+    let hash = args[1].as_string()?;
+    match settings.artifacts.get(db, &hash)? {
+        Some(data) => Ok(RespValue::BulkString(data)),
+        None => Ok(RespValue::NullBulkString),
+    }
+}
+
 /// Register or update worker heartbeat
 ///
 /// Creates/updates worker metadata with current timestamp and expiry time.
 ///
 /// Storage structure:
-/// - Hash: `worker:<worker_id>` with fields: last_seen, status, expire_at
+/// - Hash: `worker:<worker_id>` with fields: last_seen, status, expire_at,
+///   and metrics (JSON, if the worker reported any)
 /// - Sorted set: `workers:all` indexed by last_seen timestamp (for listing)
 /// - Workers expire after WORKER_HEARTBEAT_TTL_SECS (cleaned up on next WORKERS.LIST)
 ///
 /// # Arguments
 /// * `db` - Database handle
 /// * `worker_id` - Worker identifier
+/// * `metrics` - Optional host metrics JSON reported by the worker
 ///
 /// # Security
 /// - worker_id is validated before calling (alphanumeric + hyphens/underscores)
+/// - metrics are validated before calling (size-capped JSON object)
 ///
 /// # Errors
 /// Returns an error if database operations fail
-fn register_worker_heartbeat(db: &Database, worker_id: &str) -> Result<()> {
+fn register_worker_heartbeat(db: &Database, worker_id: &str, metrics: Option<&[u8]>) -> Result<()> {
     let worker_key = format!("worker:{}", worker_id);
     let timestamp = get_current_timestamp_secs()?;
 
@@ -2496,6 +4181,9 @@ fn register_worker_heartbeat(db: &Database, worker_id: &str) -> Result<()> {
     db.hset(&worker_key, "last_seen", timestamp.to_string().as_bytes())?;
     db.hset(&worker_key, "status", b"active")?;
     db.hset(&worker_key, "expire_at", expire_at.to_string().as_bytes())?;
+    if let Some(metrics) = metrics {
+        db.hset(&worker_key, "metrics", metrics)?;
+    }
 
     // Index worker in sorted set (for WORKERS.LIST)
     // Score = last_seen timestamp for sorting by activity
@@ -2625,79 +4313,510 @@ fn handle_workers_list(_args: &[RespValue], db: &Database) -> Result<RespValue>
                 .and_then(|t| std::str::from_utf8(t).ok())
                 .unwrap_or("");
 
-            // Parse last_seen with proper error handling
-            let last_seen_timestamp = last_seen_str.parse::<u64>().map_err(|e| {
-                Error::Protocol(format!(
-                    "Worker {} has invalid last_seen timestamp '{}': {}",
-                    worker_id, last_seen_str, e
-                ))
-            })?;
+            // Parse last_seen with proper error handling
+            let last_seen_timestamp = last_seen_str.parse::<u64>().map_err(|e| {
+                Error::Protocol(format!(
+                    "Worker {} has invalid last_seen timestamp '{}': {}",
+                    worker_id, last_seen_str, e
+                ))
+            })?;
+
+            // Get metrics from the latest heartbeat (optional field)
+            let metrics = db
+                .hget(&worker_key, "metrics")?
+                .and_then(|m| serde_json::from_slice::<serde_json::Value>(&m).ok())
+                .unwrap_or(serde_json::Value::Null);
+
+            // Build worker object as JSON
+            let worker_obj = serde_json::json!({
+                "worker_id": worker_id,
+                "last_seen": last_seen_timestamp,
+                "status": status_str,
+                "tools": tools_str,
+                "metrics": metrics
+            });
+
+            let worker_json = serde_json::to_string(&worker_obj)
+                .map_err(|_| Error::Protocol("Failed to serialize worker object".to_string()))?;
+
+            worker_objects.push(RespValue::BulkString(worker_json.into_bytes()));
+        }
+    }
+
+    debug!("WORKERS.LIST -> {} workers", worker_objects.len());
+    Ok(RespValue::Array(worker_objects))
+}
+
+/// Handle WORKER.QUEUES command
+///
+/// Usage: WORKER.QUEUES <worker_id>
+///
+/// Returns the ready queues whose required tags the worker registered at
+/// `worker:<id>:tags`, most specific first. Workers take jobs from these
+/// queues (and their priority sub-lists) in order.
+fn handle_worker_queues(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "WORKER.QUEUES requires exactly one argument".to_string(),
+        ));
+    }
+
+    let worker_id = args[1].as_string()?;
+    validate_identifier(&worker_id, "worker_id")?;
+
+    let queues = routing::worker_queues(db, &worker_id)?;
+    debug!("WORKER.QUEUES {} -> {:?}", worker_id, queues);
+    Ok(RespValue::Array(
+        queues
+            .into_iter()
+            .map(|queue| RespValue::BulkString(queue.into_bytes()))
+            .collect(),
+    ))
+}
+
+/// Handle TOOLS.REGISTER command
+///
+/// Usage: TOOLS.REGISTER <worker_id> <model_card_json>
+///
+/// Stores the `--describe` model card of one of the worker's tools, so it
+/// appears in TOOLS.CATALOG while the worker is live. Registering a card
+/// for the same tool again replaces it.
+///
+/// Returns the tool's name.
+fn handle_tools_register(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 3 {
+        return Err(Error::InvalidArguments(
+            "TOOLS.REGISTER requires a worker ID and a model card".to_string(),
+        ));
+    }
+
+    let worker_id = args[1].as_string()?;
+    validate_identifier(&worker_id, "worker_id")?;
+    let card = args[2].as_string()?;
+
+    let name = catalog::register(db, &worker_id, &card)?;
+    debug!("TOOLS.REGISTER {} -> {}", worker_id, name);
+    Ok(RespValue::BulkString(name.into_bytes()))
+}
+
+/// Handle TOOLS.CATALOG command
+///
+/// Usage: TOOLS.CATALOG
+///
+/// Returns a JSON array of the tools live workers have, sorted by name:
+/// each with its model card, if registered, and the workers that have it.
+/// Workers with different cards for a tool, e.g. different versions, get
+/// an entry per card.
+fn handle_tools_catalog(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "TOOLS.CATALOG takes no arguments".to_string(),
+        ));
+    }
+
+    cleanup_expired_workers(db)?;
+    let workers: Vec<String> = db
+        .zrange("workers:all", 0, -1)?
+        .into_iter()
+        .map(|(worker_id, _)| String::from_utf8_lossy(&worker_id).into_owned())
+        .collect();
+
+    let entries = catalog::catalog(db, &workers)?;
+    let json = serde_json::to_string(&entries)
+        .map_err(|e| Error::Protocol(format!("Failed to serialize catalog: {}", e)))?;
+    debug!("TOOLS.CATALOG -> {} tools", entries.len());
+    Ok(RespValue::BulkString(json.into_bytes()))
+}
+
+/// Handle TOOLS.STATS command
+///
+/// Usage: TOOLS.STATS [tool ...]
+///
+/// Returns a JSON array of the execution history of the given tools, or of
+/// every tool that has run, sorted by name: the completed and failed runs,
+/// and the average and longest execution time and average CPU time of the
+/// completed runs. Tools that have not run are left out.
+fn handle_tools_stats(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() > 1 + agenix_plan::MAX_TASKS {
+        return Err(Error::InvalidArguments(format!(
+            "TOOLS.STATS takes at most {} tools",
+            agenix_plan::MAX_TASKS
+        )));
+    }
+
+    let stats = if args.len() == 1 {
+        toolstats::all(db)?
+    } else {
+        let mut stats = Vec::new();
+        for arg in &args[1..] {
+            stats.extend(toolstats::stats(db, &arg.as_string()?)?);
+        }
+        stats
+    };
+    let json = serde_json::to_string(&stats)
+        .map_err(|e| Error::Protocol(format!("Failed to serialize tool statistics: {}", e)))?;
+    debug!("TOOLS.STATS -> {} tools", stats.len());
+    Ok(RespValue::BulkString(json.into_bytes()))
+}
+
+/// Handle QUEUE.STATS command
+///
+/// Returns queue statistics as a flat array of field-value pairs:
+/// [field1, value1, field2, value2, ...]
+///
+/// # Security
+/// - Requires authentication
+///
+/// # Arguments
+/// * `args` - RESP arguments: [command]
+/// * `db` - Database handle
+///
+/// # Statistics Returned
+/// - pending_jobs: Number of jobs in queue:ready
+/// - scheduled_jobs: Number of jobs in queue:scheduled (if exists)
+fn handle_queue_stats(_args: &[RespValue], db: &Database) -> Result<RespValue> {
+    // Get pending jobs count from queue:ready
+    let pending_jobs = db.llen("queue:ready")?;
+
+    // Get scheduled jobs count from queue:scheduled
+    // Note: AGQ doesn't currently use queue:scheduled, but we check for future compatibility
+    // If the list doesn't exist, llen returns 0 (not an error), but we handle actual errors
+    let scheduled_jobs = db.llen("queue:scheduled")?;
+
+    // Return as flat array: [field1, value1, field2, value2, ...]
+    // This matches Redis HGETALL format
+    //
+    // Resource bounds: Currently returns 2 fields (4 array elements).
+    // If additional stats are added in future, consider pagination or limits.
+    let stats = vec![
+        RespValue::BulkString(b"pending_jobs".to_vec()),
+        RespValue::BulkString(pending_jobs.to_string().into_bytes()),
+        RespValue::BulkString(b"scheduled_jobs".to_vec()),
+        RespValue::BulkString(scheduled_jobs.to_string().into_bytes()),
+    ];
+
+    debug!(
+        "QUEUE.STATS -> pending: {}, scheduled: {}",
+        pending_jobs, scheduled_jobs
+    );
+    Ok(RespValue::Array(stats))
+}
+
+/// Handle QUEUE.UNSCHEDULABLE command
+///
+/// Usage: QUEUE.UNSCHEDULABLE
+///
+/// Lists the jobs held because no live worker has every tag they require,
+/// oldest first, as a JSON array:
+///
+/// ```json
+/// [{"job_id": "job_abc", "plan_id": "ocr", "tags": ["gpu", "vram:24"], "held_since": 1700000000}]
+/// ```
+///
+/// Held jobs are queued once a worker with their tags sends a heartbeat.
+fn handle_queue_unschedulable(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "QUEUE.UNSCHEDULABLE takes no arguments".to_string(),
+        ));
+    }
+
+    let mut held = Vec::new();
+    for (member, held_since) in db.zrange(orchestrator::UNSCHEDULABLE_QUEUE, 0, -1)? {
+        let job_id = String::from_utf8_lossy(&member);
+        let Some(json) = db.get(&format!("job:{}", job_id))? else {
+            continue;
+        };
+        let Ok(job) = serde_json::from_slice::<Job>(&json) else {
+            continue;
+        };
+        held.push(serde_json::json!({
+            "job_id": job.id,
+            "plan_id": job.plan_id,
+            "tags": routing::requirements(&job.tags),
+            "held_since": held_since as u64,
+        }));
+    }
+
+    debug!("QUEUE.UNSCHEDULABLE -> {} jobs", held.len());
+    Ok(RespValue::BulkString(
+        serde_json::Value::Array(held).to_string().into_bytes(),
+    ))
+}
+
+/// Handle STATS.MEMORY command
+///
+/// Usage: STATS.MEMORY
+///
+/// Reports what storage is used for (see [`crate::memory`]) as JSON:
+///
+/// ```json
+/// {"stored_bytes": 52144, "allocated_bytes": 1069056, "fragmented_bytes": 4096,
+///  "high_water": 1073741824, "over_high_water": false,
+///  "namespaces": {"jobs": {"entries": 12, "bytes": 9310}, "results": {...}}}
+/// ```
+///
+/// `high_water` is null when no mark is set. Every stored entry is read,
+/// so this is slow on a large database.
+fn handle_stats_memory(
+    args: &[RespValue],
+    db: &Database,
+    settings: &Settings,
+) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "STATS.MEMORY takes no arguments".to_string(),
+        ));
+    }
+
+    let usage = memory::usage(db)?;
+    let high_water = &settings.memory_high_water;
+    let over_high_water = high_water.check(&usage);
+    let mut report = serde_json::to_value(&usage)
+        .map_err(|e| Error::Protocol(format!("Failed to serialize memory usage: {}", e)))?;
+    report["high_water"] = serde_json::json!(high_water.limit());
+    report["over_high_water"] = serde_json::Value::Bool(over_high_water);
+
+    debug!(
+        "STATS.MEMORY -> {} bytes in {} namespaces",
+        usage.stored_bytes,
+        usage.namespaces.len()
+    );
+    Ok(RespValue::BulkString(report.to_string().into_bytes()))
+}
+
+/// Handle AUDIT.QUERY command
+///
+/// Usage: AUDIT.QUERY [filter value]...
+///
+/// Reads the audit log (see [`crate::audit`]) oldest first, as a JSON
+/// object with the matching `records` and, if there may be more, the
+/// sequence number to pass as `after` for the next page in `next`.
+/// Filters, given as name/value pairs:
+/// - client: name of the client that ran the command, or `agq`
+/// - command: e.g. PLAN.SUBMIT
+/// - plan_id, action_id, job_id: records about a Plan, Action, or job
+/// - since, until: time range, in Unix seconds (inclusive)
+/// - after: only records after this sequence number (default: 0)
+/// - limit: max records to return (default: 100, max: 1000)
+///
+/// Example: `AUDIT.QUERY plan_id ocr since 1700000000`
+fn handle_audit_query(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    let pairs = args.get(1..).unwrap_or_default();
+    if pairs.len() % 2 != 0 {
+        return Err(Error::InvalidArguments(
+            "AUDIT.QUERY filters must be name/value pairs".to_string(),
+        ));
+    }
+    let number = |name: &str, value: &str| {
+        value.parse::<u64>().map_err(|_| {
+            Error::InvalidArguments(format!("{} must be a non-negative integer", name))
+        })
+    };
+
+    let mut query = audit::Query {
+        limit: audit::Query::DEFAULT_LIMIT,
+        ..audit::Query::default()
+    };
+    for pair in pairs.chunks(2) {
+        let name = pair[0].as_string()?.to_lowercase();
+        let value = pair[1].as_string()?;
+        match name.as_str() {
+            "command" => query.command = Some(value),
+            "client" | "plan_id" | "action_id" | "job_id" => {
+                validate_identifier(&value, &name)?;
+                let field = match name.as_str() {
+                    "client" => &mut query.client,
+                    "plan_id" => &mut query.plan_id,
+                    "action_id" => &mut query.action_id,
+                    _ => &mut query.job_id,
+                };
+                *field = Some(value);
+            }
+            "since" => query.since = Some(number(&name, &value)?),
+            "until" => query.until = Some(number(&name, &value)?),
+            "after" => query.after = number(&name, &value)?,
+            "limit" => {
+                let limit = number(&name, &value)?;
+                if limit == 0 {
+                    return Err(Error::InvalidArguments("limit must be > 0".to_string()));
+                }
+                query.limit = limit.min(audit::Query::MAX_LIMIT);
+            }
+            _ => {
+                return Err(Error::InvalidArguments(format!(
+                    "Unknown AUDIT.QUERY filter: {}",
+                    name
+                )))
+            }
+        }
+    }
+
+    let records = audit::query(db, &query)?;
+    let next = (records.len() as u64 == query.limit)
+        .then(|| records.last().map(|record| record.seq))
+        .flatten();
+    let response = serde_json::json!({"records": records, "next": next});
+
+    debug!("AUDIT.QUERY -> {} records", records.len());
+    Ok(RespValue::BulkString(response.to_string().into_bytes()))
+}
+
+/// Handle REPLICA.STATUS command
+///
+/// Usage: REPLICA.STATUS
+///
+/// Returns this AGQ's replication state as JSON. A leader reports `seq`,
+/// the number of its last committed change, and how many `standbys` are
+/// streaming changes. A standby reports its `leader`, whether it is
+/// `connected`, the `applied_seq` of the last change it applied, and
+/// `secs_since_contact` with the leader.
+fn handle_replica_status(
+    args: &[RespValue],
+    db: &Database,
+    settings: &Settings,
+) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "REPLICA.STATUS takes no arguments".to_string(),
+        ));
+    }
+
+    let status = settings.replication.status(db);
+    Ok(RespValue::BulkString(status.to_string().into_bytes()))
+}
+
+/// Handle REPLICA.PROMOTE command
+///
+/// Usage: REPLICA.PROMOTE
+///
+/// Makes a standby stop following its leader and take writes, and starts
+/// the background work only a leader does. The old leader must not take
+/// writes afterwards; restart it as a standby of this AGQ.
+///
+/// Returns OK, or an error if this AGQ is already a leader.
+fn handle_replica_promote(args: &[RespValue], settings: &Settings) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "REPLICA.PROMOTE takes no arguments".to_string(),
+        ));
+    }
+
+    if !settings.replication.promote() {
+        return Err(Error::InvalidArguments(
+            "This AGQ is already a leader".to_string(),
+        ));
+    }
+    Ok(RespValue::SimpleString("OK".to_string()))
+}
 
-            // Build worker object as JSON
-            let worker_obj = serde_json::json!({
-                "worker_id": worker_id,
-                "last_seen": last_seen_timestamp,
-                "status": status_str,
-                "tools": tools_str
-            });
+/// Handle SECRET.SET command
+///
+/// Usage: SECRET.SET <name> <value>
+///
+/// Stores a secret for Plans to refer to as `secret://<name>`, replacing
+/// any value it had.
+///
+/// Returns OK.
+fn handle_secret_set(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 3 {
+        return Err(Error::InvalidArguments(
+            "SECRET.SET requires a name and a value".to_string(),
+        ));
+    }
 
-            let worker_json = serde_json::to_string(&worker_obj)
-                .map_err(|_| Error::Protocol("Failed to serialize worker object".to_string()))?;
+    let name = args[1].as_string()?;
+    validate_identifier(&name, "secret name")?;
+    let RespValue::BulkString(value) = &args[2] else {
+        return Err(Error::InvalidArguments(
+            "SECRET.SET value must be a bulk string".to_string(),
+        ));
+    };
+    secrets::set(db, &name, value)?;
+    Ok(RespValue::SimpleString("OK".to_string()))
+}
 
-            worker_objects.push(RespValue::BulkString(worker_json.into_bytes()));
-        }
+/// Handle SECRET.DEL command
+///
+/// Usage: SECRET.DEL <name>
+///
+/// Returns 1 if the secret was removed, 0 if there was none.
+fn handle_secret_del(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 {
+        return Err(Error::InvalidArguments(
+            "SECRET.DEL requires exactly one argument (name)".to_string(),
+        ));
     }
 
-    debug!("WORKERS.LIST -> {} workers", worker_objects.len());
-    Ok(RespValue::Array(worker_objects))
+    let name = args[1].as_string()?;
+    validate_identifier(&name, "secret name")?;
+    Ok(RespValue::Integer(i64::from(secrets::delete(db, &name)?)))
 }
 
-/// Handle QUEUE.STATS command
+/// Handle SECRET.LIST command
 ///
-/// Returns queue statistics as a flat array of field-value pairs:
-/// [field1, value1, field2, value2, ...]
+/// Usage: SECRET.LIST
 ///
-/// # Security
-/// - Requires authentication
+/// Returns the names of the stored secrets, sorted; never their values.
+fn handle_secret_list(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 1 {
+        return Err(Error::InvalidArguments(
+            "SECRET.LIST takes no arguments".to_string(),
+        ));
+    }
+
+    let names = secrets::names(db)?
+        .into_iter()
+        .map(|name| RespValue::BulkString(name.into_bytes()))
+        .collect();
+    Ok(RespValue::Array(names))
+}
+
+/// Handle QUEUE.LIMIT command
 ///
-/// # Arguments
-/// * `args` - RESP arguments: [command]
-/// * `db` - Database handle
+/// Usage: QUEUE.LIMIT <queue> [max_jobs]
 ///
-/// # Statistics Returned
-/// - pending_jobs: Number of jobs in queue:ready
-/// - scheduled_jobs: Number of jobs in queue:scheduled (if exists)
-fn handle_queue_stats(_args: &[RespValue], db: &Database) -> Result<RespValue> {
-    // Get pending jobs count from queue:ready
-    let pending_jobs = db.llen("queue:ready")?;
+/// Caps how many jobs of a ready queue (e.g. `queue:default` or `queue:gpu`,
+/// across all priorities) may be queued or running at once. Jobs over the
+/// cap wait in `queue:throttled` until running jobs finish. A `max_jobs` of
+/// 0 removes the cap.
+///
+/// Returns the queue's cap, or nil if it has none.
+fn handle_queue_limit(args: &[RespValue], db: &Database) -> Result<RespValue> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(Error::InvalidArguments(
+            "QUEUE.LIMIT requires a queue name and an optional limit".to_string(),
+        ));
+    }
 
-    // Get scheduled jobs count from queue:scheduled
-    // Note: AGQ doesn't currently use queue:scheduled, but we check for future compatibility
-    // If the list doesn't exist, llen returns 0 (not an error), but we handle actual errors
-    let scheduled_jobs = db.llen("queue:scheduled")?;
+    let queue = args[1].as_string()?;
+    if routing::queue_requirements(&queue).is_none() {
+        return Err(Error::InvalidArguments(format!(
+            "Unknown queue: {} (expected {} or a queue:tags: queue)",
+            queue,
+            orchestrator::READY_QUEUES.join(", ")
+        )));
+    }
 
-    // Return as flat array: [field1, value1, field2, value2, ...]
-    // This matches Redis HGETALL format
-    //
-    // Resource bounds: Currently returns 2 fields (4 array elements).
-    // If additional stats are added in future, consider pagination or limits.
-    let stats = vec![
-        RespValue::BulkString(b"pending_jobs".to_vec()),
-        RespValue::BulkString(pending_jobs.to_string().into_bytes()),
-        RespValue::BulkString(b"scheduled_jobs".to_vec()),
-        RespValue::BulkString(scheduled_jobs.to_string().into_bytes()),
-    ];
+    let orchestrator = Orchestrator::new(db);
+    if let Some(limit) = args.get(2) {
+        let limit: u32 = limit.as_string()?.parse().map_err(|_| {
+            Error::InvalidArguments("Limit must be a non-negative integer".to_string())
+        })?;
+        orchestrator.set_queue_limit(&queue, limit)?;
+        info!("QUEUE.LIMIT {} set to {}", queue, limit);
+    }
 
-    debug!(
-        "QUEUE.STATS -> pending: {}, scheduled: {}",
-        pending_jobs, scheduled_jobs
-    );
-    Ok(RespValue::Array(stats))
+    Ok(match orchestrator.queue_limit(&queue)? {
+        Some(limit) => RespValue::Integer(i64::from(limit)),
+        None => RespValue::NullBulkString,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::acl::Role;
     use tempfile::TempDir;
 
     fn test_db() -> (Database, TempDir) {
@@ -2707,74 +4826,308 @@ mod tests {
         (db, temp_dir)
     }
 
+    #[test]
+    fn test_job_log_appends_chunks() {
+        let (db, _temp) = test_db();
+
+        for chunk in [b"line 1\n".as_slice(), b"line 2\n".as_slice()] {
+            let args = vec![
+                RespValue::BulkString(b"JOB.LOG".to_vec()),
+                RespValue::BulkString(b"job_abc".to_vec()),
+                RespValue::BulkString(b"stdout".to_vec()),
+                RespValue::BulkString(chunk.to_vec()),
+            ];
+            handle_job_log(&args, &db).unwrap();
+        }
+
+        let chunks = db.lrange("job:job_abc:log:stdout", 0, -1).unwrap();
+        assert_eq!(chunks, vec![b"line 2\n".to_vec(), b"line 1\n".to_vec()]);
+    }
+
+    #[test]
+    fn test_job_log_rejects_invalid_stream() {
+        let (db, _temp) = test_db();
+
+        let args = vec![
+            RespValue::BulkString(b"JOB.LOG".to_vec()),
+            RespValue::BulkString(b"job_abc".to_vec()),
+            RespValue::BulkString(b"status".to_vec()),
+            RespValue::BulkString(b"done".to_vec()),
+        ];
+
+        assert!(handle_job_log(&args, &db).is_err());
+    }
+
+    #[test]
+    fn test_plan_status_and_results() {
+        let (db, _temp) = test_db();
+        let plan = r#"{"plan_id":"plan_s","tasks":[
+            {"task_number":1,"command":"echo","args":["hi"]},
+            {"task_number":2,"command":"wc","args":[],"input_from_task":1}
+        ]}"#;
+        db.hset("plan:plan_s", "json", plan.as_bytes()).unwrap();
+        let submit = vec![
+            RespValue::BulkString(b"ACTION.SUBMIT".to_vec()),
+            RespValue::BulkString(
+                br#"{"action_id":"action_s","plan_id":"plan_s","inputs":[{}]}"#.to_vec(),
+            ),
+        ];
+        handle_action_submit(&submit, &db).unwrap();
+
+        let first = load_action_jobs(&db, "action_s").unwrap()[0].id.clone();
+        db.set(&format!("job:{}:status", first), b"completed")
+            .unwrap();
+        db.set(&format!("job:{}:stdout", first), b"hi\n").unwrap();
+        db.set(
+            &format!("job:{}:result", first),
+            br#"{"exit_code":0,"duration_ms":12}"#,
+        )
+        .unwrap();
+
+        let query = |command: &[u8]| {
+            let args = vec![
+                RespValue::BulkString(command.to_vec()),
+                RespValue::BulkString(b"plan_s".to_vec()),
+            ];
+            let handler = if command == b"PLAN.STATUS" {
+                handle_plan_status
+            } else {
+                handle_plan_results
+            };
+            let RespValue::BulkString(json) = handler(&args, &db).unwrap() else {
+                panic!("expected a bulk string");
+            };
+            serde_json::from_slice::<serde_json::Value>(&json).unwrap()
+        };
+
+        let status = query(b"PLAN.STATUS");
+        assert_eq!(status["action_id"], "action_s");
+        assert_eq!(status["status"], "running");
+        assert_eq!(status["summary"]["completed"], 1);
+        assert_eq!(status["summary"]["pending"], 1);
+        assert_eq!(status["tasks"][0]["exit_code"], 0);
+        assert_eq!(status["tasks"][0]["duration_ms"], 12);
+        assert_eq!(status["tasks"][1]["command"], "wc");
+
+        let results = query(b"PLAN.RESULTS");
+        assert_eq!(results["results"][0]["stdout"], "hi\n");
+        assert_eq!(results["results"][0]["stdout_encoding"], "utf8");
+        assert!(results["results"][1]["result"].is_null());
+    }
+
+    #[test]
+    fn test_action_submit_waits_for_depends_on() {
+        let (db, _temp) = test_db();
+        let plan = serde_json::json!({"plan_id": "plan_d", "tasks": [
+            {"task_number": 1, "command": "curl", "args": ["-o", "a.html"]},
+            {"task_number": 2, "command": "curl", "args": ["-o", "b.html"]},
+            {"task_number": 3, "command": "wc", "input_from_task": 1, "depends_on": [1, 2]}
+        ]});
+        validate_plan(plan.clone()).unwrap();
+        db.hset("plan:plan_d", "json", plan.to_string().as_bytes())
+            .unwrap();
+        let submit = vec![
+            RespValue::BulkString(b"ACTION.SUBMIT".to_vec()),
+            RespValue::BulkString(
+                br#"{"action_id":"action_d","plan_id":"plan_d","inputs":[{}]}"#.to_vec(),
+            ),
+        ];
+        handle_action_submit(&submit, &db).unwrap();
+
+        let mut jobs = load_action_jobs(&db, "action_d").unwrap();
+        jobs.sort_by_key(|job| job.task_number);
+        let ids = |ids: &[&Job]| ids.iter().map(|job| job.id.clone()).collect::<HashSet<_>>();
+        assert!(jobs[0].dependencies.is_empty() && jobs[1].dependencies.is_empty());
+        assert_eq!(jobs[2].dependencies, ids(&[&jobs[0], &jobs[1]]));
+        // Task 1 also feeds task 3's stdin, so only task 2 is waited for alone
+        assert_eq!(jobs[2].order_only, ids(&[&jobs[1]]));
+    }
+
+    #[test]
+    fn test_action_submit_copies_task_limits() {
+        let (db, _temp) = test_db();
+        let plan = serde_json::json!({"plan_id": "plan_l", "tasks": [
+            {"task_number": 1, "command": "sort", "limits": {"memory_mb": 256, "cpu_secs": 10}},
+            {"task_number": 2, "command": "uniq", "input_from_task": 1}
+        ]});
+        validate_plan(plan.clone()).unwrap();
+        db.hset("plan:plan_l", "json", plan.to_string().as_bytes())
+            .unwrap();
+        let submit = vec![
+            RespValue::BulkString(b"ACTION.SUBMIT".to_vec()),
+            RespValue::BulkString(
+                br#"{"action_id":"action_l","plan_id":"plan_l","inputs":[{}]}"#.to_vec(),
+            ),
+        ];
+        handle_action_submit(&submit, &db).unwrap();
+
+        let mut jobs = load_action_jobs(&db, "action_l").unwrap();
+        jobs.sort_by_key(|job| job.task_number);
+        // Workers read the limits from the job's JSON
+        let job = serde_json::to_value(&jobs[0]).unwrap();
+        assert_eq!(job["limits"], serde_json::json!({"memory_mb": 256, "cpu_secs": 10}));
+        assert!(jobs[1].limits.is_unlimited());
+        assert!(serde_json::to_value(&jobs[1]).unwrap().get("limits").is_none());
+    }
+
+    #[test]
+    fn test_plan_status_rejects_foreign_action() {
+        let (db, _temp) = test_db();
+        db.hset("action:action_x", "plan_id", b"plan_other")
+            .unwrap();
+
+        let args = vec![
+            RespValue::BulkString(b"PLAN.STATUS".to_vec()),
+            RespValue::BulkString(b"plan_s".to_vec()),
+            RespValue::BulkString(b"action_x".to_vec()),
+        ];
+        assert!(handle_plan_status(&args, &db).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_plan_status() {
+        assert_eq!(
+            aggregate_plan_status(&["completed", "completed"]),
+            "completed"
+        );
+        assert_eq!(aggregate_plan_status(&["completed", "cancelled"]), "failed");
+        assert_eq!(aggregate_plan_status(&["pending", "pending"]), "pending");
+        assert_eq!(aggregate_plan_status(&["failed", "ready"]), "running");
+    }
+
+    #[test]
+    fn test_artifact_put_and_get() {
+        let (db, _temp) = test_db();
+        let settings = Settings::default();
+
+        let put = vec![
+            RespValue::BulkString(b"ARTIFACT.PUT".to_vec()),
+            RespValue::BulkString(b"hello".to_vec()),
+        ];
+        let hash = handle_artifact_put(&put, &db, &settings).unwrap();
+        let expected = b"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_vec();
+        assert_eq!(hash, RespValue::BulkString(expected.clone()));
+
+        // Storing the same content again yields the same reference
+        assert_eq!(handle_artifact_put(&put, &db, &settings).unwrap(), hash);
+
+        let get = vec![
+            RespValue::BulkString(b"ARTIFACT.GET".to_vec()),
+            RespValue::BulkString(expected),
+        ];
+        assert_eq!(
+            handle_artifact_get(&get, &db, &settings).unwrap(),
+            RespValue::BulkString(b"hello".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_set_limits_inline_job_output() {
+        let (db, _temp) = test_db();
+        let settings = Settings {
+            max_inline_result: 8,
+            ..Settings::default()
+        };
+        let set = |key: &str, value: &[u8]| {
+            let args = vec![
+                RespValue::BulkString(b"SET".to_vec()),
+                RespValue::BulkString(key.as_bytes().to_vec()),
+                RespValue::BulkString(value.to_vec()),
+            ];
+            handle_set(&args, &db, &settings)
+        };
+
+        assert!(set("job:j1:stdout", b"12345678").is_ok());
+        assert!(set("job:j1:stdout", b"123456789").is_err());
+        assert!(set("job:j1:result", b"123456789").is_err());
+        assert!(set("job:j1:stdout_artifact", b"123456789").is_ok());
+        assert!(set("plan:p1", b"123456789").is_ok());
+    }
+
+    #[test]
+    fn test_artifact_get_rejects_invalid_hash() {
+        let (db, _temp) = test_db();
+
+        let args = vec![
+            RespValue::BulkString(b"ARTIFACT.GET".to_vec()),
+            RespValue::BulkString(b"../job:abc".to_vec()),
+        ];
+        assert!(handle_artifact_get(&args, &db, &Settings::default()).is_err());
+    }
+
     #[tokio::test]
     async fn test_auth_handler_success() {
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"test_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
 
         let args = vec![
             RespValue::BulkString(b"AUTH".to_vec()),
             RespValue::BulkString(b"test_key".to_vec()),
         ];
 
-        let result = handle_auth(&args, &mut authenticated, &session_key).unwrap();
+        let result = handle_auth(&args, &mut client, &acl).unwrap();
 
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
-        assert!(authenticated);
+        assert!(client.is_some());
     }
 
     #[tokio::test]
     async fn test_auth_handler_wrong_key() {
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"correct_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
 
         let args = vec![
             RespValue::BulkString(b"AUTH".to_vec()),
             RespValue::BulkString(b"wrong_key".to_vec()),
         ];
 
-        let result = handle_auth(&args, &mut authenticated, &session_key);
+        let result = handle_auth(&args, &mut client, &acl);
 
         assert!(result.is_err());
-        assert!(!authenticated);
+        assert!(client.is_none());
     }
 
     #[tokio::test]
     async fn test_auth_handler_empty_key() {
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"test_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
 
         let args = vec![
             RespValue::BulkString(b"AUTH".to_vec()),
             RespValue::BulkString(b"".to_vec()),
         ];
 
-        let result = handle_auth(&args, &mut authenticated, &session_key);
+        let result = handle_auth(&args, &mut client, &acl);
 
         assert!(result.is_err());
-        assert!(!authenticated);
+        assert!(client.is_none());
     }
 
     #[tokio::test]
     async fn test_auth_handler_missing_argument() {
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"test_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
 
         let args = vec![RespValue::BulkString(b"AUTH".to_vec())];
 
-        let result = handle_auth(&args, &mut authenticated, &session_key);
+        let result = handle_auth(&args, &mut client, &acl);
 
         assert!(result.is_err());
-        assert!(!authenticated);
+        assert!(client.is_none());
     }
 
     #[tokio::test]
     async fn test_auth_handler_hex_encoded() {
-        let mut authenticated = false;
+        let mut client = None;
         // 32-byte key
         let session_key =
             hex::decode("4f90ccd2c864cee924523ec901c450f543753103b3c0da793561b1f9e3eaf579")
                 .unwrap();
+        let acl = Acl::with_session_key(session_key.clone());
 
         // Client sends hex-encoded string (64 chars)
         let args = vec![
@@ -2784,10 +5137,10 @@ mod tests {
             ),
         ];
 
-        let result = handle_auth(&args, &mut authenticated, &session_key).unwrap();
+        let result = handle_auth(&args, &mut client, &acl).unwrap();
 
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
-        assert!(authenticated);
+        assert!(client.is_some());
     }
 
     #[tokio::test]
@@ -2819,6 +5172,43 @@ mod tests {
         assert_eq!(workers[0].0, b"worker_test123");
     }
 
+    #[tokio::test]
+    async fn test_ping_handler_with_metrics() {
+        let (db, _temp) = test_db();
+        let args = vec![
+            RespValue::BulkString(b"PING".to_vec()),
+            RespValue::BulkString(b"worker_metrics1".to_vec()),
+            RespValue::BulkString(br#"{"cpu_count":4,"current_jobs":1}"#.to_vec()),
+        ];
+
+        let result = handle_ping(&args, &db).unwrap();
+        assert_eq!(result, RespValue::BulkString(b"worker_metrics1".to_vec()));
+
+        let list = handle_workers_list(&[], &db).unwrap();
+        let RespValue::Array(workers) = list else {
+            panic!("expected array");
+        };
+        let RespValue::BulkString(json) = &workers[0] else {
+            panic!("expected bulk string");
+        };
+        let worker: serde_json::Value = serde_json::from_slice(json).unwrap();
+        assert_eq!(worker["metrics"]["cpu_count"], 4);
+        assert_eq!(worker["metrics"]["current_jobs"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_ping_handler_rejects_invalid_metrics() {
+        let (db, _temp) = test_db();
+        for metrics in [b"not json".to_vec(), b"[1,2]".to_vec(), vec![b' '; 5000]] {
+            let args = vec![
+                RespValue::BulkString(b"PING".to_vec()),
+                RespValue::BulkString(b"worker_metrics2".to_vec()),
+                RespValue::BulkString(metrics),
+            ];
+            assert!(handle_ping(&args, &db).is_err());
+        }
+    }
+
     #[tokio::test]
     async fn test_ping_handler_too_many_args() {
         let (db, _temp) = test_db();
@@ -2826,6 +5216,7 @@ mod tests {
             RespValue::BulkString(b"PING".to_vec()),
             RespValue::BulkString(b"arg1".to_vec()),
             RespValue::BulkString(b"arg2".to_vec()),
+            RespValue::BulkString(b"arg3".to_vec()),
         ];
 
         let result = handle_ping(&args, &db);
@@ -2835,38 +5226,316 @@ mod tests {
 
     #[tokio::test]
     async fn test_command_requires_auth() {
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"test_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
         let (db, _temp) = test_db();
 
         let args = vec![RespValue::BulkString(b"PING".to_vec())];
         let value = RespValue::Array(args);
 
-        let result = handle_command(value, &mut authenticated, &session_key, &db).await;
+        let result = handle_command(value, &mut client, &acl, &Settings::default(), &db).await;
 
         assert!(matches!(result, Err(Error::NoAuth)));
     }
 
     #[tokio::test]
     async fn test_unknown_command() {
-        let mut authenticated = true;
+        let mut client = Some(Client {
+            name: "admin".to_string(),
+            role: Role::Admin,
+        });
         let session_key = b"test_key".to_vec();
+        let acl = Acl::with_session_key(session_key.clone());
         let (db, _temp) = test_db();
 
         let args = vec![RespValue::BulkString(b"UNKNOWN".to_vec())];
         let value = RespValue::Array(args);
 
-        let result = handle_command(value, &mut authenticated, &session_key, &db).await;
+        let result = handle_command(value, &mut client, &acl, &Settings::default(), &db).await;
 
         assert!(matches!(result, Err(Error::UnknownCommand(_))));
     }
 
+    /// Run a command given as strings
+    async fn run(
+        client: &mut Option<Client>,
+        acl: &Acl,
+        db: &Database,
+        parts: &[&str],
+    ) -> Result<RespValue> {
+        let value = RespValue::Array(
+            parts
+                .iter()
+                .map(|p| RespValue::BulkString(p.as_bytes().to_vec()))
+                .collect(),
+        );
+        handle_command(value, client, acl, &Settings::default(), db).await
+    }
+
+    #[tokio::test]
+    async fn test_command_checks_client_role() {
+        let (db, _temp) = test_db();
+        let mut acl = Acl::with_session_key(b"admin_key_0123456789".to_vec());
+        acl.add(
+            "ui".to_string(),
+            b"submit_key_0123456789".to_vec(),
+            Role::Submit,
+        )
+        .unwrap();
+
+        let mut client = None;
+        let auth = ["AUTH", "ui", "admin_key_0123456789"];
+        assert!(run(&mut client, &acl, &db, &auth).await.is_err());
+        let auth = ["AUTH", "ui", "submit_key_0123456789"];
+        run(&mut client, &acl, &db, &auth).await.unwrap();
+        assert_eq!(client.as_ref().unwrap().role, Role::Submit);
+
+        let result = run(&mut client, &acl, &db, &["JOB.PURGE", "job_1"]).await;
+        assert!(matches!(result, Err(Error::NoPerm { .. })));
+        let result = run(&mut client, &acl, &db, &["SET", "k", "v"]).await;
+        assert!(matches!(result, Err(Error::NoPerm { .. })));
+        assert!(run(&mut client, &acl, &db, &["JOB.LIST"]).await.is_ok());
+
+        let auth = ["AUTH", "admin_key_0123456789"];
+        run(&mut client, &acl, &db, &auth).await.unwrap();
+        assert_eq!(client.as_ref().unwrap().name, "default");
+        let result = run(&mut client, &acl, &db, &["JOB.PURGE", "job_1"]).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_command_rate_limited_per_client() {
+        let (db, _temp) = test_db();
+        let acl = Acl::with_session_key(b"test_key".to_vec());
+        let settings = Settings {
+            rate_limits: RateLimits::new(Some("0.001/2".parse().unwrap()), None),
+            ..Settings::default()
+        };
+        let submit = |client: &str, plan_id: &str| {
+            let mut client = Some(Client {
+                name: client.to_string(),
+                role: Role::Admin,
+            });
+            let plan = format!(
+                r#"{{"plan_id": "{}", "tasks": [{{"task_number": 1, "command": "sort"}}]}}"#,
+                plan_id
+            );
+            let value = RespValue::Array(vec![
+                RespValue::BulkString(b"PLAN.SUBMIT".to_vec()),
+                RespValue::BulkString(plan.into_bytes()),
+            ]);
+            let (acl, settings, db) = (&acl, &settings, &db);
+            async move { handle_command(value, &mut client, acl, settings, db).await }
+        };
+
+        assert!(submit("ui", "p1").await.is_ok());
+        assert!(submit("ui", "p2").await.is_ok());
+        let result = submit("ui", "p3").await;
+        assert!(matches!(result, Err(Error::RateLimited { .. })));
+        assert!(result
+            .unwrap_err()
+            .to_resp_error()
+            .starts_with("-ERR RATE_LIMITED client 'ui'"));
+        assert!(submit("cli", "p3").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_standby_refuses_writes_until_promoted() {
+        let (db, _temp) = test_db();
+        let acl = Acl::with_session_key(b"test_key".to_vec());
+        let settings = Settings {
+            replication: Replication::standby("10.0.0.1:6380"),
+            ..Settings::default()
+        };
+        let run = |args: &[&str]| {
+            let mut client = Some(Client {
+                name: "ops".to_string(),
+                role: Role::Admin,
+            });
+            let value = RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec()))
+                    .collect(),
+            );
+            let (acl, settings, db) = (&acl, &settings, &db);
+            async move { handle_command(value, &mut client, acl, settings, db).await }
+        };
+
+        let result = run(&["SET", "k", "v"]).await;
+        assert_eq!(
+            result.unwrap_err().to_resp_error(),
+            "-ERR READONLY this AGQ is a standby of 10.0.0.1:6380\r\n"
+        );
+        assert!(run(&["GET", "k"]).await.is_ok());
+        let status = run(&["REPLICA.STATUS"]).await.unwrap();
+        assert!(status.as_string().unwrap().contains(r#""role":"standby""#));
+
+        assert!(run(&["REPLICA.PROMOTE"]).await.is_ok());
+        assert!(run(&["SET", "k", "v"]).await.is_ok());
+        assert!(run(&["REPLICA.PROMOTE"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plan_env_resolves_secrets_only_for_workers() {
+        let (db, _temp) = test_db();
+        let acl = Acl::with_session_key(b"test_key".to_vec());
+        let settings = Settings::default();
+        let run = |args: &[&str]| {
+            let mut client = Some(Client {
+                name: "ops".to_string(),
+                role: Role::Admin,
+            });
+            let value = RespValue::Array(
+                args.iter()
+                    .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec()))
+                    .collect(),
+            );
+            let (acl, settings, db) = (&acl, &settings, &db);
+            async move { handle_command(value, &mut client, acl, settings, db).await }
+        };
+
+        run(&["SECRET.SET", "db_password", "hunter2"])
+            .await
+            .unwrap();
+        let plan = r#"{"plan_id": "p1", "env": {"DB_PASSWORD": "secret://db_password"},
+            "tasks": [{"task_number": 1, "command": "psql"}]}"#;
+        validate_plan(serde_json::from_str(plan).unwrap()).unwrap();
+        db.hset("plan:p1", "json", plan.as_bytes()).unwrap();
+        let action = r#"{"action_id": "a1", "plan_id": "p1", "inputs": [{"DB": "prod"}]}"#;
+        let response = run(&["ACTION.SUBMIT", action]).await.unwrap();
+        let response: serde_json::Value =
+            serde_json::from_str(&response.as_string().unwrap()).unwrap();
+        let job_id = response["job_ids"][0].as_str().unwrap();
+
+        let job = run(&["JOB.GET", job_id])
+            .await
+            .unwrap()
+            .as_string()
+            .unwrap();
+        assert!(job.contains("secret://db_password") && !job.contains("hunter2"));
+        let env = run(&["JOB.ENV", job_id])
+            .await
+            .unwrap()
+            .as_string()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&env).unwrap(),
+            serde_json::json!({"DB_PASSWORD": "hunter2", "DB": "prod"})
+        );
+
+        let list = run(&["SECRET.LIST"]).await.unwrap();
+        assert_eq!(
+            list,
+            RespValue::Array(vec![RespValue::BulkString(b"db_password".to_vec())])
+        );
+        assert!(run(&["HGETALL", "secret:values"]).await.is_err());
+        assert!(run(&["DEL", "secret:values"]).await.is_err());
+        let audit = run(&["AUDIT.QUERY"]).await.unwrap().as_string().unwrap();
+        assert!(audit.contains("SECRET.SET") && !audit.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_plan_submit_idempotency_key() {
+        let (db, _temp) = test_db();
+        let settings = Settings::default();
+        let submit = |plan: &str, key: &str| {
+            let args = vec![
+                RespValue::BulkString(b"PLAN.SUBMIT".to_vec()),
+                RespValue::BulkString(plan.as_bytes().to_vec()),
+                RespValue::BulkString(b"IDEMPOTENCY_KEY".to_vec()),
+                RespValue::BulkString(key.as_bytes().to_vec()),
+            ];
+            handle_plan_submit(&args, &db, &settings)
+        };
+        let plan = r#"{"plan_id": "p1", "tasks": [{"task_number": 1, "command": "sort"}]}"#;
+        let other = r#"{"plan_id": "p2", "tasks": [{"task_number": 1, "command": "uniq"}]}"#;
+
+        let plan_id = RespValue::BulkString(b"p1".to_vec());
+        assert_eq!(submit(plan, "retry-1").unwrap(), plan_id);
+        assert_eq!(submit(plan, "retry-1").unwrap(), plan_id);
+        assert_eq!(db.llen("agq:internal:plan.submit").unwrap(), 1);
+        assert!(submit(other, "retry-1").is_err());
+
+        assert!(submit(plan, "retry 2").is_err());
+        submit(plan, "retry-2").unwrap();
+        assert_eq!(db.llen("agq:internal:plan.submit").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_validate_plan_checks_schema_version() {
+        let plan = |version: u32| {
+            serde_json::json!({
+                "schema_version": version,
+                "plan_id": "p1",
+                "tasks": [{"task_number": 1, "command": "sort"}],
+            })
+        };
+
+        validate_plan(plan(1)).unwrap();
+        let error = validate_plan(plan(2)).unwrap_err().to_string();
+        assert!(
+            error.contains(
+                r#"{"path":"schema_version","message":"is 2, but only version 1 is supported"#
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_validate_plan_applies_shared_schema() {
+        let error = validate_plan(serde_json::json!({
+            "plan_id": "p1",
+            "tasks": [
+                {"task_number": 1, "command": "sort", "input_from": 2},
+                {"task_number": 2, "command": "uniq", "input_from_task": 3, "map": "lines"}
+            ],
+        }))
+        .unwrap_err()
+        .to_string();
+        let errors: Vec<serde_json::Value> =
+            serde_json::from_str(error.split_once("failed: ").unwrap().1).unwrap();
+        let paths: Vec<&str> = errors.iter().map(|e| e["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, ["tasks[0]"], "{}", error);
+
+        let error = validate_plan(serde_json::json!({
+            "plan_id": "p1",
+            "tasks": [
+                {"task_number": 1, "command": "sort"},
+                {"task_number": 2, "command": "uniq", "input_from_task": 3, "stages": [{"command": "wc"}]}
+            ],
+        }))
+        .unwrap_err()
+        .to_string();
+        assert!(
+            error.contains(r#""path":"tasks[1].input_from_task""#),
+            "{}",
+            error
+        );
+        assert!(error.contains(r#""path":"tasks[1].stages""#), "{}", error);
+
+        assert_eq!(
+            validate_plan(serde_json::json!({
+                "plan_id": "p1",
+                "on_failure": "retry",
+                "tasks": [
+                    {"task_number": 1, "command": "ls", "priority": "high"},
+                    {"task_number": 2, "command": "agx-ocr", "input_from_task": 1, "map": "lines"}
+                ],
+            }))
+            .unwrap(),
+            "p1"
+        );
+    }
+
     #[tokio::test]
     async fn test_constant_time_comparison() {
         use std::time::Instant;
 
-        let mut authenticated = false;
+        let mut client = None;
         let session_key = b"a".repeat(32);
+        let acl = Acl::with_session_key(session_key.clone());
 
         // Warm up to avoid cold start timing differences
         for _ in 0..100 {
@@ -2874,19 +5543,19 @@ mod tests {
                 RespValue::BulkString(b"AUTH".to_vec()),
                 RespValue::BulkString(session_key.clone()),
             ];
-            let _ = handle_auth(&args, &mut authenticated, &session_key);
+            let _ = handle_auth(&args, &mut client, &acl);
         }
 
         // Test 1: Matching keys (averaged over multiple runs)
         let mut total_match = std::time::Duration::ZERO;
         for _ in 0..1000 {
-            authenticated = false;
+            client = None;
             let args = vec![
                 RespValue::BulkString(b"AUTH".to_vec()),
                 RespValue::BulkString(session_key.clone()),
             ];
             let start = Instant::now();
-            let _ = handle_auth(&args, &mut authenticated, &session_key);
+            let _ = handle_auth(&args, &mut client, &acl);
             total_match += start.elapsed();
         }
 
@@ -2899,13 +5568,13 @@ mod tests {
 
         let mut total_no_match = std::time::Duration::ZERO;
         for _ in 0..1000 {
-            authenticated = false;
+            client = None;
             let args = vec![
                 RespValue::BulkString(b"AUTH".to_vec()),
                 RespValue::BulkString(wrong_key.clone()),
             ];
             let start = Instant::now();
-            let _ = handle_auth(&args, &mut authenticated, &session_key);
+            let _ = handle_auth(&args, &mut client, &acl);
             total_no_match += start.elapsed();
         }
 
@@ -2945,7 +5614,7 @@ mod tests {
             RespValue::BulkString(b"myvalue".to_vec()),
         ];
 
-        let result = handle_set(&set_args, &db).unwrap();
+        let result = handle_set(&set_args, &db, &Settings::default()).unwrap();
         assert_eq!(result, RespValue::SimpleString("OK".to_string()));
 
         // GET key
@@ -2968,7 +5637,7 @@ mod tests {
             RespValue::BulkString(b"mykey".to_vec()),
             RespValue::BulkString(b"myvalue".to_vec()),
         ];
-        handle_set(&set_args, &db).unwrap();
+        handle_set(&set_args, &db, &Settings::default()).unwrap();
 
         // DEL key
         let del_args = vec![
@@ -3003,7 +5672,7 @@ mod tests {
             RespValue::BulkString(b"mykey".to_vec()),
             RespValue::BulkString(b"myvalue".to_vec()),
         ];
-        handle_set(&set_args, &db).unwrap();
+        handle_set(&set_args, &db, &Settings::default()).unwrap();
 
         // EXISTS on existing key
         let result = handle_exists(&exists_args, &db).unwrap();
@@ -3041,7 +5710,7 @@ mod tests {
             RespValue::BulkString(b"key".to_vec()),
         ];
 
-        let result = handle_set(&args, &db);
+        let result = handle_set(&args, &db, &Settings::default());
         assert!(result.is_err());
 
         // Too many args
@@ -3052,7 +5721,80 @@ mod tests {
             RespValue::BulkString(b"extra".to_vec()),
         ];
 
-        let result = handle_set(&args, &db);
+        let result = handle_set(&args, &db, &Settings::default());
         assert!(result.is_err());
     }
+
+    fn job_list(db: &Database, filters: &[&str]) -> serde_json::Value {
+        let mut args = vec![RespValue::BulkString(b"JOB.LIST".to_vec())];
+        args.extend(
+            filters
+                .iter()
+                .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec())),
+        );
+        match handle_job_list(&args, db).unwrap() {
+            RespValue::BulkString(json) => serde_json::from_slice(&json).unwrap(),
+            other => panic!("unexpected JOB.LIST response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_job_list_filters() {
+        let (db, _temp) = test_db();
+        let jobs = (1..=5)
+            .map(|n| {
+                let mut job = Job::new(
+                    format!("job_{}", n),
+                    "action_1".to_string(),
+                    if n <= 3 { "plan_a" } else { "plan_b" }.to_string(),
+                    n,
+                    "echo".to_string(),
+                    Vec::new(),
+                    serde_json::json!({}),
+                    vec![if n == 5 { "gpu" } else { "cpu" }.to_string()],
+                );
+                job.created_at = 100 + u64::from(n);
+                job
+            })
+            .collect();
+        Orchestrator::new(&db).submit_jobs(jobs).unwrap();
+        db.set("job:job_2:status", b"failed").unwrap();
+        db.set("job:job_2:worker", b"worker-1").unwrap();
+
+        let ids = |listing: &serde_json::Value| -> Vec<String> {
+            listing["jobs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|job| job["job_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let all = job_list(&db, &[]);
+        assert_eq!(all["total"], 5);
+        assert_eq!(ids(&all), ["job_5", "job_4", "job_3", "job_2", "job_1"]);
+
+        let page = job_list(&db, &["plan_id", "plan_a", "offset", "1", "limit", "1"]);
+        assert_eq!(page["total"], 3);
+        assert_eq!(ids(&page), ["job_2"]);
+        assert_eq!(page["jobs"][0]["worker_id"], "worker-1");
+
+        assert_eq!(ids(&job_list(&db, &["status", "failed"])), ["job_2"]);
+        assert_eq!(ids(&job_list(&db, &["worker", "worker-1"])), ["job_2"]);
+        assert_eq!(ids(&job_list(&db, &["tag", "gpu"])), ["job_5"]);
+        assert_eq!(
+            ids(&job_list(&db, &["since", "102", "until", "103"])),
+            ["job_3", "job_2"]
+        );
+
+        for filters in [&["status"][..], &["status", "done"], &["owner", "x"]] {
+            let mut args = vec![RespValue::BulkString(b"JOB.LIST".to_vec())];
+            args.extend(
+                filters
+                    .iter()
+                    .map(|arg| RespValue::BulkString(arg.as_bytes().to_vec())),
+            );
+            assert!(handle_job_list(&args, &db).is_err());
+        }
+    }
 }
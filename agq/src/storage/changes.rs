@@ -0,0 +1,303 @@
+//! Log of the writes made to a database, for replicas
+//!
+//! Every write operation is deterministic given the database it runs on,
+//! so a replica that starts from a copy of the database and applies the
+//! same writes in the same order ends up with the same data. Writes are
+//! numbered in commit order and broadcast to the replicas streaming them;
+//! nothing is stored, so a replica that falls behind starts over from a
+//! fresh copy.
+
+use crate::{Error, Result};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Writes buffered per replica before a slow one falls behind
+const CHANGE_BUFFER: usize = 4096;
+
+/// A write operation, as applied to a database
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Set {
+        key: String,
+        value: Vec<u8>,
+    },
+    SetEx {
+        key: String,
+        value: Vec<u8>,
+        expire_at: u64,
+    },
+    Del {
+        key: String,
+    },
+    LPush {
+        key: String,
+        value: Vec<u8>,
+    },
+    RPop {
+        key: String,
+    },
+    RPopLPush {
+        source: String,
+        destination: String,
+    },
+    LRem {
+        key: String,
+        count: i64,
+        element: Vec<u8>,
+    },
+    ZAdd {
+        key: String,
+        score: f64,
+        member: Vec<u8>,
+    },
+    ZRem {
+        key: String,
+        member: Vec<u8>,
+    },
+    HSet {
+        key: String,
+        field: String,
+        value: Vec<u8>,
+    },
+    HDel {
+        key: String,
+        field: String,
+    },
+    HIncrBy {
+        key: String,
+        field: String,
+        increment: i64,
+    },
+}
+
+impl Change {
+    /// The change as a command name and its arguments, e.g.
+    /// `["HSET", key, field, value]`
+    #[must_use]
+    pub fn to_args(&self) -> Vec<Vec<u8>> {
+        let text = |s: &str| s.as_bytes().to_vec();
+        match self {
+            Self::Set { key, value } => vec![text("SET"), text(key), value.clone()],
+            Self::SetEx {
+                key,
+                value,
+                expire_at,
+            } => vec![
+                text("SETEX"),
+                text(key),
+                text(&expire_at.to_string()),
+                value.clone(),
+            ],
+            Self::Del { key } => vec![text("DEL"), text(key)],
+            Self::LPush { key, value } => vec![text("LPUSH"), text(key), value.clone()],
+            Self::RPop { key } => vec![text("RPOP"), text(key)],
+            Self::RPopLPush {
+                source,
+                destination,
+            } => vec![text("RPOPLPUSH"), text(source), text(destination)],
+            Self::LRem {
+                key,
+                count,
+                element,
+            } => vec![
+                text("LREM"),
+                text(key),
+                text(&count.to_string()),
+                element.clone(),
+            ],
+            Self::ZAdd { key, score, member } => vec![
+                text("ZADD"),
+                text(key),
+                text(&score.to_string()),
+                member.clone(),
+            ],
+            Self::ZRem { key, member } => vec![text("ZREM"), text(key), member.clone()],
+            Self::HSet { key, field, value } => {
+                vec![text("HSET"), text(key), text(field), value.clone()]
+            }
+            Self::HDel { key, field } => vec![text("HDEL"), text(key), text(field)],
+            Self::HIncrBy {
+                key,
+                field,
+                increment,
+            } => vec![
+                text("HINCRBY"),
+                text(key),
+                text(field),
+                text(&increment.to_string()),
+            ],
+        }
+    }
+
+    /// Parse a change from the form [`Change::to_args`] produces
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command is unknown or its arguments are
+    /// malformed
+    pub fn from_args(args: Vec<Vec<u8>>) -> Result<Self> {
+        let invalid = |reason: &str| Error::Protocol(format!("Invalid change: {reason}"));
+        let mut args = args.into_iter();
+        let op = args.next().ok_or_else(|| invalid("empty"))?;
+        let rest: Vec<Vec<u8>> = args.collect();
+        let text = |index: usize| {
+            rest.get(index)
+                .ok_or_else(|| invalid("missing argument"))
+                .and_then(|arg| {
+                    String::from_utf8(arg.clone()).map_err(|_| invalid("argument is not UTF-8"))
+                })
+        };
+        let bytes = |index: usize| {
+            rest.get(index)
+                .cloned()
+                .ok_or_else(|| invalid("missing argument"))
+        };
+        let number = |index: usize| {
+            text(index)?
+                .parse::<i64>()
+                .map_err(|_| invalid("argument is not an integer"))
+        };
+
+        let expected = match op.as_slice() {
+            b"SET" | b"LPUSH" | b"RPOPLPUSH" | b"ZREM" | b"HDEL" => 2,
+            b"SETEX" | b"LREM" | b"ZADD" | b"HSET" | b"HINCRBY" => 3,
+            b"DEL" | b"RPOP" => 1,
+            _ => return Err(invalid("unknown command")),
+        };
+        if rest.len() != expected {
+            return Err(invalid("wrong number of arguments"));
+        }
+
+        Ok(match op.as_slice() {
+            b"SET" => Self::Set {
+                key: text(0)?,
+                value: bytes(1)?,
+            },
+            b"SETEX" => Self::SetEx {
+                key: text(0)?,
+                expire_at: text(1)?
+                    .parse()
+                    .map_err(|_| invalid("expiry is not a timestamp"))?,
+                value: bytes(2)?,
+            },
+            b"DEL" => Self::Del { key: text(0)? },
+            b"LPUSH" => Self::LPush {
+                key: text(0)?,
+                value: bytes(1)?,
+            },
+            b"RPOP" => Self::RPop { key: text(0)? },
+            b"RPOPLPUSH" => Self::RPopLPush {
+                source: text(0)?,
+                destination: text(1)?,
+            },
+            b"LREM" => Self::LRem {
+                key: text(0)?,
+                count: number(1)?,
+                element: bytes(2)?,
+            },
+            b"ZADD" => Self::ZAdd {
+                key: text(0)?,
+                score: text(1)?
+                    .parse()
+                    .map_err(|_| invalid("score is not a number"))?,
+                member: bytes(2)?,
+            },
+            b"ZREM" => Self::ZRem {
+                key: text(0)?,
+                member: bytes(1)?,
+            },
+            b"HSET" => Self::HSet {
+                key: text(0)?,
+                field: text(1)?,
+                value: bytes(2)?,
+            },
+            b"HDEL" => Self::HDel {
+                key: text(0)?,
+                field: text(1)?,
+            },
+            _ => Self::HIncrBy {
+                key: text(0)?,
+                field: text(1)?,
+                increment: number(2)?,
+            },
+        })
+    }
+}
+
+/// A change and its position in the log
+pub type Numbered = (u64, Arc<Change>);
+
+/// Numbers committed changes and broadcasts them to replicas
+#[derive(Debug)]
+pub(crate) struct ChangeLog {
+    /// Number of the last change committed
+    seq: u64,
+    sender: broadcast::Sender<Numbered>,
+}
+
+impl Default for ChangeLog {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            sender: broadcast::channel(CHANGE_BUFFER).0,
+        }
+    }
+}
+
+impl ChangeLog {
+    /// Number of the last change committed
+    pub(crate) fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Record a committed change, building it only if a replica is
+    /// streaming changes
+    pub(crate) fn record(&mut self, change: impl FnOnce() -> Change) {
+        self.seq += 1;
+        if self.sender.receiver_count() > 0 {
+            // Sending only fails when every replica has just gone
+            let _ = self.sender.send((self.seq, Arc::new(change())));
+        }
+    }
+
+    /// Receive the changes committed from now on
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Numbered> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_round_trips_as_args() {
+        let changes = [
+            Change::SetEx {
+                key: "k".to_string(),
+                value: b"\x00v".to_vec(),
+                expire_at: 1_700_000_000,
+            },
+            Change::LRem {
+                key: "queue:processing".to_string(),
+                count: -1,
+                element: b"job_1".to_vec(),
+            },
+            Change::ZAdd {
+                key: "jobs:all".to_string(),
+                score: 0.1 + 0.2,
+                member: b"job_1".to_vec(),
+            },
+            Change::HIncrBy {
+                key: "stats".to_string(),
+                field: "done".to_string(),
+                increment: 3,
+            },
+        ];
+        for change in changes {
+            assert_eq!(Change::from_args(change.to_args()).unwrap(), change);
+        }
+        assert!(Change::from_args(vec![b"FLUSHALL".to_vec()]).is_err());
+        assert!(Change::from_args(vec![b"SET".to_vec(), b"k".to_vec()]).is_err());
+    }
+}
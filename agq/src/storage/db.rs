@@ -1,44 +1,19 @@
-//! Database wrapper for redb embedded storage
+//! Database wrapper over the storage engines
 
-use crate::storage::{HashOps, ListOps, SortedSetOps, StringOps};
+use crate::storage::changes::{Change, ChangeLog, Numbered};
+use crate::storage::engine::{Engine, ReadTxn, Table, WriteTxn};
+use crate::storage::redb_engine::RedbEngine;
+use crate::storage::sqlite::SqliteEngine;
+use crate::storage::{Backend, HashOps, ListOps, SortedSetOps, StringOps};
 use crate::{Error, Result};
 use async_trait::async_trait;
-use redb::{Database as RedbDatabase, ReadableTable, TableDefinition};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::Notify;
+use tokio::sync::{broadcast, Notify};
 use tokio::time::{sleep, Duration};
 use tracing::{debug, info};
 
-/// Table for key-value string storage
-const KV_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("kv");
-
-/// Table for list metadata (head/tail pointers)
-/// Key: list name, Value: (head_index: i64, tail_index: i64) as 16 bytes
-const LIST_META_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("list_meta");
-
-/// Table for list elements
-/// Key: "{list_name}:{index}", Value: element bytes
-const LIST_DATA_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("list_data");
-
-/// Table for sorted set member-to-score mapping
-/// Key: "{zset_name}:{member}", Value: score (8 bytes, f64)
-const ZSET_MEMBER_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("zset_member");
-
-/// Table for sorted set score-to-member mapping (for range queries)
-/// Key: "{zset_name}:{score_bytes}:{member}", Value: empty
-/// The score is encoded as a sortable byte representation
-const ZSET_SCORE_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("zset_score");
-
-/// Table for hash field-value storage
-/// Key: "{hash_name}:{field}", Value: field value bytes
-const HASH_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("hash");
-
-/// Table for key expiry times
-/// Key: key name, Value: Unix timestamp (seconds) as 8 bytes (u64)
-const EXPIRY_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("expiry");
-
 /// Maximum number of fields allowed in a single hash
 /// Prevents DoS attacks through unbounded hash growth
 const MAX_HASH_FIELDS: u64 = 10_000;
@@ -61,19 +36,21 @@ const MAX_LREM_ELEMENT_SIZE: usize = 10_485_760; // 10MB
 
 /// AGQ Database wrapper
 ///
-/// Provides ACID-compliant embedded storage using redb.
-/// All operations are thread-safe and support concurrent reads.
+/// Provides ACID-compliant embedded storage on one of the engines in
+/// [`Backend`]. All operations are thread-safe and support concurrent reads.
 #[derive(Clone)]
 pub struct Database {
-    db: Arc<RedbDatabase>,
+    engine: Arc<dyn Engine>,
     /// Notifications for list changes (used by BRPOP)
     /// Key format: list key name
     /// Uses std::sync::Mutex because we need to access it from both sync (LPUSH) and async (BRPOP) contexts
     list_notifiers: Arc<std::sync::Mutex<HashMap<String, Arc<Notify>>>>,
+    /// Committed writes, numbered in commit order for replicas
+    changes: Arc<std::sync::Mutex<ChangeLog>>,
 }
 
 impl Database {
-    /// Open or create a database at the given path
+    /// Open or create a redb database at the given path
     ///
     /// # Security
     /// - Creates parent directories if they don't exist
@@ -95,205 +72,438 @@ impl Database {
 
         info!("Opening database at: {}", path.display());
 
-        let db = RedbDatabase::create(path)
-            .map_err(|e| Error::Protocol(format!("Failed to open database: {e}")))?;
+        Self::init(RedbEngine::create(path)?)
+    }
 
-        // Initialize tables
-        let write_txn = db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-        {
-            let _kv_table = write_txn
-                .open_table(KV_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-            let _expiry_table = write_txn
-                .open_table(EXPIRY_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
-            let _list_meta_table = write_txn
-                .open_table(LIST_META_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-            let _list_data_table = write_txn
-                .open_table(LIST_DATA_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-            let _zset_member_table = write_txn
-                .open_table(ZSET_MEMBER_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset member table: {e}")))?;
-            let _zset_score_table = write_txn
-                .open_table(ZSET_SCORE_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
-            let _hash_table = write_txn
-                .open_table(HASH_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
+    /// Open or create a SQLite database at the given path
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the parent directory cannot be created or the
+    /// file cannot be opened as a SQLite database
+    pub fn open_sqlite<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit initialization: {e}")))?;
 
+        info!("Opening SQLite database at: {}", path.display());
+
+        Self::init(SqliteEngine::open(path)?)
+    }
+
+    /// Create an empty database held entirely in memory
+    ///
+    /// Nothing is written to disk, so the data is lost when the process
+    /// exits unless it is saved with [`Database::snapshot`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tables cannot be created
+    pub fn open_in_memory() -> Result<Self> {
+        info!("Opening in-memory database");
+
+        Self::init(RedbEngine::in_memory()?)
+    }
+
+    /// Open a database with the given backend
+    ///
+    /// `path` is not used by [`Backend::Memory`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened
+    pub fn open_with<P: AsRef<Path>>(backend: Backend, path: P) -> Result<Self> {
+        match backend {
+            Backend::File => Self::open(path),
+            Backend::Sqlite => Self::open_sqlite(path),
+            Backend::Memory => Self::open_in_memory(),
+        }
+    }
+
+    /// Wrap an opened engine
+    fn init(engine: impl Engine + 'static) -> Result<Self> {
         info!("Database initialized successfully");
 
         Ok(Self {
-            db: Arc::new(db),
+            engine: Arc::new(engine),
             list_notifiers: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            changes: Arc::new(std::sync::Mutex::new(ChangeLog::default())),
         })
     }
+
+    /// Write a point-in-time copy of the database to a new file
+    ///
+    /// All tables are copied in a single read transaction, so the snapshot
+    /// is consistent while writes continue. Snapshots are redb files
+    /// whatever the backend. The copy is written to a temporary file and
+    /// renamed into place, so a crash never leaves a partial snapshot at
+    /// `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the database or writing the snapshot fails
+    pub fn snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let read_txn = self.engine.begin_read()?;
+        write_snapshot(read_txn.as_ref(), path.as_ref())
+    }
+
+    /// Write a snapshot for a new replica, returning the number of the last
+    /// change it includes and the changes committed after it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading the database or writing the snapshot fails
+    pub fn snapshot_for_replica<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(u64, broadcast::Receiver<Numbered>)> {
+        // Nothing commits while the log is locked, so the snapshot holds
+        // exactly the changes up to `seq`
+        let (seq, changes, read_txn) = {
+            let log = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+            let read_txn = self.engine.begin_read()?;
+            (log.seq(), log.subscribe(), read_txn)
+        };
+        write_snapshot(read_txn.as_ref(), path.as_ref())?;
+        Ok((seq, changes))
+    }
+
+    /// Number of the last write committed since the database was opened
+    #[must_use]
+    pub fn change_seq(&self) -> u64 {
+        self.changes.lock().unwrap_or_else(|e| e.into_inner()).seq()
+    }
+
+    /// Replace the contents of this database with a snapshot file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot cannot be read or the copy fails
+    pub fn restore_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let snapshot = RedbEngine::open(path)
+            .map_err(|e| Error::Protocol(format!("Failed to open snapshot: {e}")))?;
+        let read_txn = snapshot.begin_read()?;
+        let write_txn = self.engine.begin_write()?;
+
+        for table in Table::ALL {
+            write_txn.clear(table)?;
+        }
+        copy_tables(read_txn.as_ref(), write_txn.as_ref())?;
+
+        write_txn
+            .commit()
+            .map_err(|e| Error::Protocol(format!("Failed to commit snapshot restore: {e}")))?;
+
+        info!("Restored database from snapshot: {}", path.display());
+        Ok(())
+    }
+
+    /// Apply a change recorded by another database
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write fails
+    pub fn apply(&self, change: &Change) -> Result<()> {
+        match change {
+            Change::Set { key, value } => self.set(key, value),
+            Change::SetEx {
+                key,
+                value,
+                expire_at,
+            } => self.setex(key, value, *expire_at),
+            Change::Del { key } => self.del(key).map(drop),
+            Change::LPush { key, value } => self.lpush(key, value).map(drop),
+            Change::RPop { key } => self.rpop(key).map(drop),
+            Change::RPopLPush {
+                source,
+                destination,
+            } => self.rpoplpush(source, destination).map(drop),
+            Change::LRem {
+                key,
+                count,
+                element,
+            } => self.lrem(key, *count, element).map(drop),
+            Change::ZAdd { key, score, member } => self.zadd(key, *score, member).map(drop),
+            Change::ZRem { key, member } => self.zrem(key, member).map(drop),
+            Change::HSet { key, field, value } => self.hset(key, field, value).map(drop),
+            Change::HDel { key, field } => self.hdel(key, field).map(drop),
+            Change::HIncrBy {
+                key,
+                field,
+                increment,
+            } => self.hincrby(key, field, *increment).map(drop),
+        }
+    }
+
+    /// Commit a write transaction and record the change it made
+    ///
+    /// The change log stays locked from commit until the change is
+    /// recorded, so changes are numbered in the order they were committed.
+    fn commit(
+        &self,
+        write_txn: Box<dyn WriteTxn + '_>,
+        change: impl FnOnce() -> Change,
+    ) -> Result<()> {
+        let mut log = self.changes.lock().unwrap_or_else(|e| e.into_inner());
+        write_txn.commit()?;
+        log.record(change);
+        Ok(())
+    }
+
+    /// Copy the contents of a snapshot file into this database
+    ///
+    /// Keys in the snapshot overwrite existing keys; other keys are kept, so
+    /// this is meant for loading a snapshot into a new, empty database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot cannot be read or the copy fails
+    pub fn load_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let snapshot = RedbEngine::open(path)
+            .map_err(|e| Error::Protocol(format!("Failed to open snapshot: {e}")))?;
+        let read_txn = snapshot.begin_read()?;
+        let write_txn = self.engine.begin_write()?;
+
+        copy_tables(read_txn.as_ref(), write_txn.as_ref())?;
+
+        write_txn
+            .commit()
+            .map_err(|e| Error::Protocol(format!("Failed to commit snapshot load: {e}")))?;
+
+        info!("Loaded database snapshot from: {}", path.display());
+        Ok(())
+    }
+
+    /// Delete every key whose expiry time has passed
+    ///
+    /// Expired keys are otherwise only removed when they are next read, so
+    /// keys nobody reads again (idempotency records, worker heartbeats)
+    /// stay stored until this runs. Returns how many keys were deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a transaction fails
+    pub fn purge_expired(&self, now: u64) -> Result<usize> {
+        let expired = {
+            let read_txn = self.engine.begin_read()?;
+            let mut expired = Vec::new();
+            read_txn.range(Table::Expiry, "", None, &mut |key, expire_at| {
+                if expiry_time(expire_at).is_some_and(|expire_at| expire_at <= now) {
+                    expired.push(key.to_string());
+                }
+                true
+            })?;
+            expired
+        };
+
+        let mut purged = 0;
+        for key in expired {
+            let write_txn = self.engine.begin_write()?;
+            // The key may have been set again since the scan
+            let still_expired = write_txn
+                .get(Table::Expiry, &key)?
+                .and_then(|expire_at| expiry_time(&expire_at))
+                .is_some_and(|expire_at| expire_at <= now);
+            if still_expired {
+                write_txn.remove(Table::Kv, &key)?;
+                write_txn.remove(Table::Expiry, &key)?;
+                self.commit(write_txn, || Change::Del { key })?;
+                purged += 1;
+            }
+        }
+
+        if purged > 0 {
+            debug!("Purged {} expired keys", purged);
+        }
+        Ok(purged)
+    }
+
+    /// Visit every stored entry with the bytes its key and value take up
+    ///
+    /// Strings and list metadata are visited under their own key. List
+    /// elements, hash fields and sorted set members are entries of their
+    /// own, visited as `<key>:<index|field|member>`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a table cannot be read
+    pub fn for_each_entry(&self, mut visit: impl FnMut(&str, usize)) -> Result<()> {
+        let read_txn = self.engine.begin_read()?;
+        for table in Table::ALL {
+            read_txn.range(table, "", None, &mut |key, value| {
+                visit(key, key.len() + value.len());
+                true
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Space the database takes up, including pages freed but not reused
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the statistics cannot be read
+    pub fn storage_stats(&self) -> Result<StorageStats> {
+        self.engine.stats()
+    }
+
+    /// Whether `key` has an expiry time that has passed
+    fn is_expired(txn: &dyn ReadTxn, key: &str) -> Result<bool> {
+        let expire_at = txn.get(Table::Expiry, key)?;
+        let Some(expire_at) = expire_at.as_deref().and_then(expiry_time) else {
+            return Ok(false);
+        };
+        Ok(expire_at <= now()?)
+    }
+}
+
+/// Space taken up by a database, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Pages allocated to the database
+    pub allocated_bytes: u64,
+    /// Keys and values stored
+    pub stored_bytes: u64,
+    /// Allocated space holding neither data nor metadata
+    pub fragmented_bytes: u64,
+}
+
+/// Decode an expiry time from the expiry table
+fn expiry_time(bytes: &[u8]) -> Option<u64> {
+    bytes.try_into().ok().map(u64::from_le_bytes)
+}
+
+/// Current Unix time in seconds
+fn now() -> Result<u64> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| Error::Protocol(format!("System time error: {e}")))?
+        .as_secs())
+}
+
+/// Copy every table from one database to another
+fn copy_tables(source: &dyn ReadTxn, dest: &dyn WriteTxn) -> Result<()> {
+    for table in Table::ALL {
+        let mut written = Ok(());
+        source.range(table, "", None, &mut |key, value| {
+            written = dest.insert(table, key, value);
+            written.is_ok()
+        })?;
+        written?;
+    }
+    Ok(())
+}
+
+/// Write the contents of a read transaction to a new redb file
+///
+/// The copy is written to a temporary file and renamed into place.
+fn write_snapshot(read_txn: &dyn ReadTxn, path: &Path) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    create_private_file(&tmp_path)?;
+    {
+        let snapshot = RedbEngine::create(&tmp_path)
+            .map_err(|e| Error::Protocol(format!("Failed to create snapshot: {e}")))?;
+        let write_txn = snapshot.begin_write()?;
+
+        copy_tables(read_txn, write_txn.as_ref())?;
+        write_txn
+            .commit()
+            .map_err(|e| Error::Protocol(format!("Failed to commit snapshot: {e}")))?;
+    }
+    std::fs::rename(&tmp_path, path)?;
+
+    info!("Wrote database snapshot to: {}", path.display());
+    Ok(())
+}
+
+/// Create an empty file only this user can read, replacing any left over
+///
+/// Snapshots hold everything in the database, secrets included.
+fn create_private_file(path: &Path) -> Result<()> {
+    let _ = std::fs::remove_file(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?;
+    Ok(())
 }
 
 impl StringOps for Database {
     fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let kv_table = read_txn
-            .open_table(KV_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-        let expiry_table = read_txn
-            .open_table(EXPIRY_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Check if key has expired
-        if let Ok(Some(expire_bytes)) = expiry_table.get(key) {
-            if expire_bytes.value().len() == 8 {
-                let expire_at = u64::from_le_bytes(
-                    expire_bytes
-                        .value()
-                        .try_into()
-                        .map_err(|_| Error::Protocol("Invalid expiry format".to_string()))?,
-                );
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| Error::Protocol(format!("System time error: {e}")))?
-                    .as_secs();
-
-                if expire_at <= now {
-                    // Key has expired, return None (lazy expiration)
-                    debug!("GET {} -> (expired)", key);
-                    return Ok(None);
-                }
-            }
+        if Self::is_expired(read_txn.as_ref(), key)? {
+            // Key has expired, return None (lazy expiration)
+            debug!("GET {} -> (expired)", key);
+            return Ok(None);
         }
 
-        match kv_table.get(key) {
-            Ok(Some(value)) => {
-                let bytes = value.value().to_vec();
+        match read_txn.get(Table::Kv, key)? {
+            Some(bytes) => {
                 debug!("GET {} -> {} bytes", key, bytes.len());
                 Ok(Some(bytes))
             }
-            Ok(None) => {
+            None => {
                 debug!("GET {} -> (nil)", key);
                 Ok(None)
             }
-            Err(e) => Err(Error::Protocol(format!("Failed to get key: {e}"))),
         }
     }
 
     fn set(&self, key: &str, value: &[u8]) -> Result<()> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
+        let write_txn = self.engine.begin_write()?;
 
-        {
-            let mut kv_table = write_txn
-                .open_table(KV_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-            let mut expiry_table = write_txn
-                .open_table(EXPIRY_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
-
-            kv_table
-                .insert(key, value)
-                .map_err(|e| Error::Protocol(format!("Failed to insert key: {e}")))?;
-
-            // Remove any existing expiry entry (SET without expiry clears expiration)
-            let _ = expiry_table.remove(key);
-        }
+        write_txn.insert(Table::Kv, key, value)?;
+        // Remove any existing expiry entry (SET without expiry clears expiration)
+        write_txn.remove(Table::Expiry, key)?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        self.commit(write_txn, || Change::Set {
+            key: key.to_string(),
+            value: value.to_vec(),
+        })?;
 
         debug!("SET {} -> {} bytes", key, value.len());
         Ok(())
     }
 
     fn del(&self, key: &str) -> Result<bool> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let deleted = {
-            let mut kv_table = write_txn
-                .open_table(KV_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-            let mut expiry_table = write_txn
-                .open_table(EXPIRY_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
-
-            let result = kv_table
-                .remove(key)
-                .map_err(|e| Error::Protocol(format!("Failed to delete key: {e}")))?;
-
-            // Also remove expiry entry if it exists
-            let _ = expiry_table.remove(key);
-
-            result.is_some()
-        };
+        let write_txn = self.engine.begin_write()?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        let deleted = write_txn.remove(Table::Kv, key)?;
+        // Also remove expiry entry if it exists
+        write_txn.remove(Table::Expiry, key)?;
+
+        self.commit(write_txn, || Change::Del {
+            key: key.to_string(),
+        })?;
 
         debug!("DEL {} -> {}", key, deleted);
         Ok(deleted)
     }
 
     fn exists(&self, key: &str) -> Result<bool> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let kv_table = read_txn
-            .open_table(KV_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-        let expiry_table = read_txn
-            .open_table(EXPIRY_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Check if key exists in KV table
-        let key_exists = kv_table
-            .get(key)
-            .map_err(|e| Error::Protocol(format!("Failed to check key: {e}")))?
-            .is_some();
-
-        if !key_exists {
+        if read_txn.get(Table::Kv, key)?.is_none() {
             debug!("EXISTS {} -> false (not found)", key);
             return Ok(false);
         }
 
         // Check if key has expired
-        if let Ok(Some(expire_bytes)) = expiry_table.get(key) {
-            if expire_bytes.value().len() == 8 {
-                let expire_at = u64::from_le_bytes(
-                    expire_bytes
-                        .value()
-                        .try_into()
-                        .map_err(|_| Error::Protocol("Invalid expiry format".to_string()))?,
-                );
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| Error::Protocol(format!("System time error: {e}")))?
-                    .as_secs();
-
-                if expire_at <= now {
-                    // Key has expired, return false (lazy expiration check)
-                    debug!("EXISTS {} -> false (expired)", key);
-                    return Ok(false);
-                }
-            }
+        if Self::is_expired(read_txn.as_ref(), key)? {
+            // Key has expired, return false (lazy expiration check)
+            debug!("EXISTS {} -> false (expired)", key);
+            return Ok(false);
         }
 
         debug!("EXISTS {} -> true", key);
@@ -301,34 +511,17 @@ impl StringOps for Database {
     }
 
     fn setex(&self, key: &str, value: &[u8], expire_at: u64) -> Result<()> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
+        let write_txn = self.engine.begin_write()?;
 
-        {
-            let mut kv_table = write_txn
-                .open_table(KV_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-            let mut expiry_table = write_txn
-                .open_table(EXPIRY_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
-
-            // Set the key-value pair
-            kv_table
-                .insert(key, value)
-                .map_err(|e| Error::Protocol(format!("Failed to insert key: {e}")))?;
-
-            // Set the expiry time
-            let expire_bytes = expire_at.to_le_bytes();
-            expiry_table
-                .insert(key, &expire_bytes[..])
-                .map_err(|e| Error::Protocol(format!("Failed to set expiry: {e}")))?;
-        }
+        // Set the key-value pair and its expiry time
+        write_txn.insert(Table::Kv, key, value)?;
+        write_txn.insert(Table::Expiry, key, &expire_at.to_le_bytes())?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        self.commit(write_txn, || Change::SetEx {
+            key: key.to_string(),
+            value: value.to_vec(),
+            expire_at,
+        })?;
 
         debug!(
             "SETEX {} -> {} bytes, expires at {}",
@@ -340,89 +533,43 @@ impl StringOps for Database {
     }
 
     fn ttl(&self, key: &str) -> Result<Option<i64>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let kv_table = read_txn
-            .open_table(KV_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open KV table: {e}")))?;
-        let expiry_table = read_txn
-            .open_table(EXPIRY_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open expiry table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Check if key exists
-        if kv_table
-            .get(key)
-            .map_err(|e| Error::Protocol(format!("Failed to check key: {e}")))?
-            .is_none()
-        {
+        if read_txn.get(Table::Kv, key)?.is_none() {
             // Key doesn't exist
             return Ok(None);
         }
 
         // Check if it has an expiry time
-        match expiry_table.get(key) {
-            Ok(Some(expire_bytes)) => {
-                if expire_bytes.value().len() != 8 {
-                    return Err(Error::Protocol("Invalid expiry time format".to_string()));
-                }
-                let expire_at = u64::from_le_bytes(
-                    expire_bytes
-                        .value()
-                        .try_into()
-                        .map_err(|_| Error::Protocol("Invalid expiry format".to_string()))?,
-                );
+        let Some(expire_bytes) = read_txn.get(Table::Expiry, key)? else {
+            // Key exists but has no expiry
+            debug!("TTL {} -> no expiry", key);
+            return Ok(Some(-1)); // Redis convention: -1 for keys without expiry
+        };
+        drop(read_txn);
 
-                // Get current time
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map_err(|e| Error::Protocol(format!("System time error: {e}")))?
-                    .as_secs();
-
-                if expire_at <= now {
-                    // Key has expired - perform lazy cleanup
-                    debug!("TTL {} -> expired (cleaning up)", key);
-
-                    // Drop read transaction before opening write transaction
-                    drop(expiry_table);
-                    drop(kv_table);
-                    drop(read_txn);
-
-                    // Clean up expired key (idempotent - safe if key already deleted)
-                    let write_txn = self.db.begin_write().map_err(|e| {
-                        Error::Protocol(format!("Failed to begin write transaction: {e}"))
-                    })?;
-                    {
-                        let mut kv_table = write_txn.open_table(KV_TABLE).map_err(|e| {
-                            Error::Protocol(format!("Failed to open KV table: {e}"))
-                        })?;
-                        let mut expiry_table = write_txn.open_table(EXPIRY_TABLE).map_err(|e| {
-                            Error::Protocol(format!("Failed to open expiry table: {e}"))
-                        })?;
-
-                        // Idempotent removes - ignore if key doesn't exist
-                        let _ = kv_table.remove(key);
-                        let _ = expiry_table.remove(key);
-                    }
-                    write_txn
-                        .commit()
-                        .map_err(|e| Error::Protocol(format!("Failed to commit cleanup: {e}")))?;
+        let expire_at = expiry_time(&expire_bytes)
+            .ok_or_else(|| Error::Protocol("Invalid expiry time format".to_string()))?;
+        let now = now()?;
 
-                    Ok(Some(-2)) // Redis convention: -2 for expired keys
-                } else {
-                    let ttl = (expire_at - now) as i64;
-                    debug!("TTL {} -> {} seconds", key, ttl);
-                    Ok(Some(ttl))
-                }
-            }
-            Ok(None) => {
-                // Key exists but has no expiry
-                debug!("TTL {} -> no expiry", key);
-                Ok(Some(-1)) // Redis convention: -1 for keys without expiry
-            }
-            Err(e) => Err(Error::Protocol(format!("Failed to get expiry: {e}"))),
+        if expire_at <= now {
+            // Key has expired - perform lazy cleanup
+            debug!("TTL {} -> expired (cleaning up)", key);
+
+            // Clean up expired key (idempotent - safe if key already deleted)
+            let write_txn = self.engine.begin_write()?;
+            write_txn.remove(Table::Kv, key)?;
+            write_txn.remove(Table::Expiry, key)?;
+            self.commit(write_txn, || Change::Del {
+                key: key.to_string(),
+            })?;
+
+            Ok(Some(-2)) // Redis convention: -2 for expired keys
+        } else {
+            let ttl = (expire_at - now) as i64;
+            debug!("TTL {} -> {} seconds", key, ttl);
+            Ok(Some(ttl))
         }
     }
 }
@@ -444,6 +591,13 @@ fn decode_list_meta(bytes: &[u8]) -> Result<(i64, i64)> {
     Ok((head, tail))
 }
 
+/// Head and tail of a list, or `None` if it doesn't exist
+fn list_meta(txn: &dyn ReadTxn, key: &str) -> Result<Option<(i64, i64)>> {
+    txn.get(Table::ListMeta, key)?
+        .map(|meta| decode_list_meta(&meta))
+        .transpose()
+}
+
 fn list_element_key(list_key: &str, index: i64) -> String {
     format!("{}:{}", list_key, index)
 }
@@ -542,52 +696,52 @@ fn hash_field_key(hash: &str, field: &str) -> String {
     format!("{}:{}", hash, field)
 }
 
+/// Count the entries of a table whose keys start with `prefix`
+fn count_prefixed(txn: &dyn ReadTxn, table: Table, prefix: &str) -> Result<u64> {
+    let mut count = 0u64;
+    txn.range(table, prefix, None, &mut |key, _| {
+        if !key.starts_with(prefix) {
+            return false;
+        }
+        count += 1;
+        true
+    })?;
+    Ok(count)
+}
+
+/// Range of score-table keys belonging to one sorted set
+///
+/// ';' is the character after ':', so the range ends past every key
+/// starting with "{key}:".
+fn zset_range(key: &str) -> (String, String) {
+    (format!("{}:", key), format!("{};", key))
+}
+
 #[async_trait]
 impl ListOps for Database {
     fn lpush(&self, key: &str, value: &[u8]) -> Result<u64> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let new_len = {
-            let mut meta_table = write_txn
-                .open_table(LIST_META_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-            let mut data_table = write_txn
-                .open_table(LIST_DATA_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-
-            // Get current head/tail or initialize
-            let (head, tail) = match meta_table.get(key) {
-                Ok(Some(meta)) => decode_list_meta(meta.value())?,
-                Ok(None) => (0, -1), // Empty list: head=0, tail=-1
-                Err(e) => return Err(Error::Protocol(format!("Failed to get list metadata: {e}"))),
-            };
+        let write_txn = self.engine.begin_write()?;
 
-            // Push to head (left side)
-            let new_head = head - 1;
-            let element_key = list_element_key(key, new_head);
-            data_table
-                .insert(element_key.as_str(), value)
-                .map_err(|e| Error::Protocol(format!("Failed to insert list element: {e}")))?;
+        // Get current head/tail or initialize
+        let (head, tail) = list_meta(write_txn.as_ref(), key)?.unwrap_or((0, -1)); // Empty list: head=0, tail=-1
 
-            // Update tail if list was empty
-            let new_tail = if tail < head { new_head } else { tail };
+        // Push to head (left side)
+        let new_head = head - 1;
+        write_txn.insert(Table::ListData, &list_element_key(key, new_head), value)?;
 
-            // Update metadata
-            let meta_bytes = encode_list_meta(new_head, new_tail);
-            meta_table
-                .insert(key, &meta_bytes[..])
-                .map_err(|e| Error::Protocol(format!("Failed to update list metadata: {e}")))?;
+        // Update tail if list was empty
+        let new_tail = if tail < head { new_head } else { tail };
 
-            // Calculate new length
-            (new_tail - new_head + 1) as u64
-        };
+        // Update metadata
+        write_txn.insert(Table::ListMeta, key, &encode_list_meta(new_head, new_tail))?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        // Calculate new length
+        let new_len = (new_tail - new_head + 1) as u64;
+
+        self.commit(write_txn, || Change::LPush {
+            key: key.to_string(),
+            value: value.to_vec(),
+        })?;
 
         // Notify any waiting BRPOP calls
         // Uses std::sync::Mutex::lock() which blocks until the lock is available.
@@ -603,87 +757,47 @@ impl ListOps for Database {
     }
 
     fn rpop(&self, key: &str) -> Result<Option<Vec<u8>>> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let value = {
-            let mut meta_table = write_txn
-                .open_table(LIST_META_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-            let mut data_table = write_txn
-                .open_table(LIST_DATA_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-
-            // Get current head/tail
-            let (head, tail) = match meta_table.get(key) {
-                Ok(Some(meta)) => decode_list_meta(meta.value())?,
-                Ok(None) => {
-                    // List doesn't exist
-                    return Ok(None);
-                }
-                Err(e) => return Err(Error::Protocol(format!("Failed to get list metadata: {e}"))),
-            };
+        let write_txn = self.engine.begin_write()?;
 
-            if tail < head {
-                // Empty list
-                return Ok(None);
-            }
+        // Get current head/tail
+        let Some((head, tail)) = list_meta(write_txn.as_ref(), key)? else {
+            // List doesn't exist
+            return Ok(None);
+        };
 
-            // Pop from tail (right side)
-            let element_key = list_element_key(key, tail);
-            let value = data_table
-                .remove(element_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to remove list element: {e}")))?
-                .map(|v| v.value().to_vec());
-
-            // Update metadata
-            let new_tail = tail - 1;
-            if new_tail < head {
-                // List is now empty, remove metadata
-                meta_table
-                    .remove(key)
-                    .map_err(|e| Error::Protocol(format!("Failed to remove list metadata: {e}")))?;
-            } else {
-                let meta_bytes = encode_list_meta(head, new_tail);
-                meta_table
-                    .insert(key, &meta_bytes[..])
-                    .map_err(|e| Error::Protocol(format!("Failed to update list metadata: {e}")))?;
-            }
+        if tail < head {
+            // Empty list
+            return Ok(None);
+        }
 
-            value
-        };
+        // Pop from tail (right side)
+        let element_key = list_element_key(key, tail);
+        let value = write_txn.get(Table::ListData, &element_key)?;
+        write_txn.remove(Table::ListData, &element_key)?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        // Update metadata
+        let new_tail = tail - 1;
+        if new_tail < head {
+            // List is now empty, remove metadata
+            write_txn.remove(Table::ListMeta, key)?;
+        } else {
+            write_txn.insert(Table::ListMeta, key, &encode_list_meta(head, new_tail))?;
+        }
+
+        self.commit(write_txn, || Change::RPop {
+            key: key.to_string(),
+        })?;
 
         debug!("RPOP {} -> {:?}", key, value.is_some());
         Ok(value)
     }
 
     fn llen(&self, key: &str) -> Result<u64> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let meta_table = read_txn
-            .open_table(LIST_META_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-
-        let len = match meta_table.get(key) {
-            Ok(Some(meta)) => {
-                let (head, tail) = decode_list_meta(meta.value())?;
-                if tail < head {
-                    0
-                } else {
-                    (tail - head + 1) as u64
-                }
-            }
-            Ok(None) => 0,
-            Err(e) => return Err(Error::Protocol(format!("Failed to get list metadata: {e}"))),
+        let read_txn = self.engine.begin_read()?;
+
+        let len = match list_meta(read_txn.as_ref(), key)? {
+            Some((head, tail)) if tail >= head => (tail - head + 1) as u64,
+            _ => 0,
         };
 
         debug!("LLEN {} -> {}", key, len);
@@ -691,22 +805,10 @@ impl ListOps for Database {
     }
 
     fn lrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<Vec<u8>>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let meta_table = read_txn
-            .open_table(LIST_META_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-        let data_table = read_txn
-            .open_table(LIST_DATA_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-
-        let (head, tail) = match meta_table.get(key) {
-            Ok(Some(meta)) => decode_list_meta(meta.value())?,
-            Ok(None) => return Ok(vec![]), // List doesn't exist
-            Err(e) => return Err(Error::Protocol(format!("Failed to get list metadata: {e}"))),
+        let read_txn = self.engine.begin_read()?;
+
+        let Some((head, tail)) = list_meta(read_txn.as_ref(), key)? else {
+            return Ok(vec![]); // List doesn't exist
         };
 
         if tail < head {
@@ -736,12 +838,10 @@ impl ListOps for Database {
         let mut result = Vec::new();
         for i in start_idx..=stop_idx {
             let actual_index = head + i;
-            let element_key = list_element_key(key, actual_index);
-            if let Some(value) = data_table
-                .get(element_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to get list element: {e}")))?
+            if let Some(value) =
+                read_txn.get(Table::ListData, &list_element_key(key, actual_index))?
             {
-                result.push(value.value().to_vec());
+                result.push(value);
             }
         }
 
@@ -816,122 +916,67 @@ impl ListOps for Database {
 
     fn rpoplpush(&self, source: &str, destination: &str) -> Result<Option<Vec<u8>>> {
         // Begin atomic write transaction
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let element = {
-            let mut meta_table = write_txn
-                .open_table(LIST_META_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-            let mut data_table = write_txn
-                .open_table(LIST_DATA_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-
-            // Step 1: RPOP from source
-            // First, read the source metadata and extract values
-            let source_meta = match meta_table.get(source) {
-                Ok(Some(meta_bytes)) => {
-                    let (head, tail) = decode_list_meta(meta_bytes.value())?;
-                    Some((head, tail))
-                }
-                Ok(None) => None,
-                Err(e) => {
-                    return Err(Error::Protocol(format!(
-                        "Failed to read source metadata: {e}"
-                    )));
-                }
-            }; // AccessGuard is dropped here
-
-            let element = if let Some((head, tail)) = source_meta {
-                if head > tail {
-                    // List is empty
-                    None
+        let write_txn = self.engine.begin_write()?;
+
+        // Step 1: RPOP from source
+        let element = match list_meta(write_txn.as_ref(), source)? {
+            Some((head, tail)) if head <= tail => {
+                // Get tail element
+                let tail_key = list_element_key(source, tail);
+                let element = write_txn.get(Table::ListData, &tail_key)?.ok_or_else(|| {
+                    Error::Protocol("Tail element not found (data corruption)".to_string())
+                })?;
+
+                // Remove tail element
+                write_txn.remove(Table::ListData, &tail_key)?;
+
+                // Update tail pointer with checked arithmetic
+                let new_tail = tail
+                    .checked_sub(1)
+                    .ok_or_else(|| Error::Protocol("Tail index underflow".to_string()))?;
+                if new_tail < head {
+                    // List is now empty, remove metadata
+                    write_txn.remove(Table::ListMeta, source)?;
                 } else {
-                    // Get tail element
-                    let tail_key = list_element_key(source, tail);
-                    let element = data_table
-                        .get(tail_key.as_str())
-                        .map_err(|e| Error::Protocol(format!("Failed to get tail element: {e}")))?
-                        .ok_or_else(|| {
-                            Error::Protocol("Tail element not found (data corruption)".to_string())
-                        })?
-                        .value()
-                        .to_vec();
-
-                    // Remove tail element
-                    data_table.remove(tail_key.as_str()).map_err(|e| {
-                        Error::Protocol(format!("Failed to remove tail element: {e}"))
-                    })?;
-
-                    // Update tail pointer with checked arithmetic
-                    let new_tail = tail
-                        .checked_sub(1)
-                        .ok_or_else(|| Error::Protocol("Tail index underflow".to_string()))?;
-                    if new_tail < head {
-                        // List is now empty, remove metadata
-                        meta_table.remove(source).map_err(|e| {
-                            Error::Protocol(format!("Failed to remove source metadata: {e}"))
-                        })?;
-                    } else {
-                        // Update metadata with new tail
-                        let new_meta = encode_list_meta(head, new_tail);
-                        meta_table.insert(source, &new_meta[..]).map_err(|e| {
-                            Error::Protocol(format!("Failed to update source metadata: {e}"))
-                        })?;
-                    }
-
-                    Some(element)
+                    // Update metadata with new tail
+                    write_txn.insert(Table::ListMeta, source, &encode_list_meta(head, new_tail))?;
                 }
-            } else {
-                // Source list doesn't exist
-                None
-            };
 
-            // Step 2: If we got an element, LPUSH to destination
-            if let Some(ref element) = element {
-                // Read destination metadata first
-                let dest_meta = match meta_table.get(destination) {
-                    Ok(Some(meta_bytes)) => {
-                        let (head, tail) = decode_list_meta(meta_bytes.value())?;
-                        (head, tail)
-                    }
-                    Ok(None) => (0i64, -1i64), // New list
-                    Err(e) => {
-                        return Err(Error::Protocol(format!(
-                            "Failed to read destination metadata: {e}"
-                        )));
-                    }
-                }; // AccessGuard is dropped here
-
-                let (head, tail) = dest_meta;
-
-                // Calculate new head index
-                let new_head = head
-                    .checked_sub(1)
-                    .ok_or_else(|| Error::Protocol("Head index underflow".to_string()))?;
-
-                // Insert element at new head
-                let head_key = list_element_key(destination, new_head);
-                data_table
-                    .insert(head_key.as_str(), element.as_slice())
-                    .map_err(|e| Error::Protocol(format!("Failed to insert at head: {e}")))?;
-
-                // Update destination metadata
-                let new_meta = encode_list_meta(new_head, tail);
-                meta_table
-                    .insert(destination, &new_meta[..])
-                    .map_err(|e| Error::Protocol(format!("Failed to update dest metadata: {e}")))?;
+                Some(element)
             }
-
-            element
+            // Source list doesn't exist or is empty
+            _ => None,
         };
 
+        // Step 2: If we got an element, LPUSH to destination
+        if let Some(ref element) = element {
+            let (head, tail) = list_meta(write_txn.as_ref(), destination)?.unwrap_or((0, -1)); // New list
+
+            // Calculate new head index
+            let new_head = head
+                .checked_sub(1)
+                .ok_or_else(|| Error::Protocol("Head index underflow".to_string()))?;
+
+            // Insert element at new head
+            write_txn.insert(
+                Table::ListData,
+                &list_element_key(destination, new_head),
+                element,
+            )?;
+
+            // Update destination metadata
+            write_txn.insert(
+                Table::ListMeta,
+                destination,
+                &encode_list_meta(new_head, tail),
+            )?;
+        }
+
         // Commit the transaction atomically
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        self.commit(write_txn, || Change::RPopLPush {
+            source: source.to_string(),
+            destination: destination.to_string(),
+        })?;
 
         // Notify any BRPOP/BRPOPLPUSH waiters on the destination list
         if element.is_some() {
@@ -1056,153 +1101,126 @@ impl ListOps for Database {
         }
 
         // Begin write transaction
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let removed_count = {
-            let mut meta_table = write_txn
-                .open_table(LIST_META_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list meta table: {e}")))?;
-            let mut data_table = write_txn
-                .open_table(LIST_DATA_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open list data table: {e}")))?;
-
-            // Get current head/tail, or return 0 if list doesn't exist or is empty
-            let (head, tail) = match meta_table.get(key) {
-                Ok(Some(meta)) => decode_list_meta(meta.value())?,
-                Ok(None) => {
-                    // List doesn't exist - return 0 removed count
-                    (0, -1) // Empty list markers (head > tail)
-                }
-                Err(e) => return Err(Error::Protocol(format!("Failed to get list metadata: {e}"))),
-            };
+        let write_txn = self.engine.begin_write()?;
 
-            // If list is empty, return 0
-            if head > tail {
-                0 // No elements to remove
-            } else {
-                // Collect elements and their indices, with capacity pre-allocation
-                let list_size = (tail - head + 1) as usize;
-                let mut elements = Vec::with_capacity(list_size);
-                for idx in head..=tail {
-                    let element_key = list_element_key(key, idx);
-                    if let Ok(Some(value)) = data_table.get(element_key.as_str()) {
-                        elements.push((idx, value.value().to_vec()));
-                    }
-                }
+        // Get current head/tail, or treat a missing list as empty
+        let (head, tail) = list_meta(write_txn.as_ref(), key)?.unwrap_or((0, -1)); // Empty list markers (head > tail)
 
-                // Determine which elements to remove and collect remaining elements in a single pass
-                let mut removed_count = 0i64;
-                let indices_to_remove: std::collections::HashSet<i64>;
-
-                if count == 0 {
-                    // Remove all occurrences
-                    indices_to_remove = elements
-                        .iter()
-                        .filter_map(|(idx, value)| {
-                            if value == element {
-                                removed_count += 1;
-                                Some(*idx)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                } else if count > 0 {
-                    // Remove first N occurrences (head to tail)
-                    let mut count_left = count;
-                    indices_to_remove = elements
-                        .iter()
-                        .filter_map(|(idx, value)| {
-                            if count_left > 0 && value == element {
-                                count_left -= 1;
-                                removed_count += 1;
-                                Some(*idx)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                } else {
-                    // Remove last N occurrences (tail to head)
-                    let mut count_left = count.abs();
-                    indices_to_remove = elements
-                        .iter()
-                        .rev()
-                        .filter_map(|(idx, value)| {
-                            if count_left > 0 && value == element {
-                                count_left -= 1;
-                                removed_count += 1;
-                                Some(*idx)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
+        // If list is empty, nothing is removed
+        let removed_count = if head > tail {
+            0
+        } else {
+            // Collect elements and their indices, with capacity pre-allocation
+            let list_size = (tail - head + 1) as usize;
+            let mut elements = Vec::with_capacity(list_size);
+            for idx in head..=tail {
+                if let Some(value) = write_txn.get(Table::ListData, &list_element_key(key, idx))? {
+                    elements.push((idx, value));
                 }
+            }
 
-                // If we removed elements, we need to re-compact the list
-                if removed_count > 0 {
-                    // Collect remaining elements in order (single pass - no double scan)
-                    let remaining_elements: Vec<Vec<u8>> = elements
-                        .into_iter()
-                        .filter_map(|(idx, value)| {
-                            if !indices_to_remove.contains(&idx) {
-                                Some(value)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-
-                    if remaining_elements.is_empty() {
-                        // List is now empty, remove metadata
-                        meta_table.remove(key).map_err(|e| {
-                            Error::Protocol(format!("Failed to remove metadata: {e}"))
-                        })?;
-                    } else {
-                        // Re-write the list with new indices starting from head
-                        let new_tail = head
-                            .checked_add(remaining_elements.len() as i64 - 1)
-                            .ok_or_else(|| Error::Protocol("Index overflow".to_string()))?;
-
-                        // First, remove all old elements
-                        for idx in head..=tail {
-                            let element_key = list_element_key(key, idx);
-                            let _ = data_table.remove(element_key.as_str());
+            // Determine which elements to remove and collect remaining elements in a single pass
+            let mut removed_count = 0i64;
+            let indices_to_remove: std::collections::HashSet<i64> = if count == 0 {
+                // Remove all occurrences
+                elements
+                    .iter()
+                    .filter_map(|(idx, value)| {
+                        if value == element {
+                            removed_count += 1;
+                            Some(*idx)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            } else if count > 0 {
+                // Remove first N occurrences (head to tail)
+                let mut count_left = count;
+                elements
+                    .iter()
+                    .filter_map(|(idx, value)| {
+                        if count_left > 0 && value == element {
+                            count_left -= 1;
+                            removed_count += 1;
+                            Some(*idx)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            } else {
+                // Remove last N occurrences (tail to head)
+                let mut count_left = count.abs();
+                elements
+                    .iter()
+                    .rev()
+                    .filter_map(|(idx, value)| {
+                        if count_left > 0 && value == element {
+                            count_left -= 1;
+                            removed_count += 1;
+                            Some(*idx)
+                        } else {
+                            None
                         }
+                    })
+                    .collect()
+            };
 
-                        // Write remaining elements with new indices
-                        for (offset, value) in remaining_elements.iter().enumerate() {
-                            let new_idx = head
-                                .checked_add(offset as i64)
-                                .ok_or_else(|| Error::Protocol("Index overflow".to_string()))?;
-                            let element_key = list_element_key(key, new_idx);
-                            data_table
-                                .insert(element_key.as_str(), value.as_slice())
-                                .map_err(|e| {
-                                    Error::Protocol(format!("Failed to reinsert element: {e}"))
-                                })?;
+            // If we removed elements, we need to re-compact the list
+            if removed_count > 0 {
+                // Collect remaining elements in order (single pass - no double scan)
+                let remaining_elements: Vec<Vec<u8>> = elements
+                    .into_iter()
+                    .filter_map(|(idx, value)| {
+                        if !indices_to_remove.contains(&idx) {
+                            Some(value)
+                        } else {
+                            None
                         }
+                    })
+                    .collect();
 
-                        // Update metadata with new tail
-                        let new_meta = encode_list_meta(head, new_tail);
-                        meta_table.insert(key, &new_meta[..]).map_err(|e| {
-                            Error::Protocol(format!("Failed to update metadata: {e}"))
-                        })?;
+                if remaining_elements.is_empty() {
+                    // List is now empty, remove metadata
+                    write_txn.remove(Table::ListMeta, key)?;
+                } else {
+                    // Re-write the list with new indices starting from head
+                    let new_tail = head
+                        .checked_add(remaining_elements.len() as i64 - 1)
+                        .ok_or_else(|| Error::Protocol("Index overflow".to_string()))?;
+
+                    // First, remove all old elements
+                    for idx in head..=tail {
+                        write_txn.remove(Table::ListData, &list_element_key(key, idx))?;
+                    }
+
+                    // Write remaining elements with new indices
+                    for (offset, value) in remaining_elements.iter().enumerate() {
+                        let new_idx = head
+                            .checked_add(offset as i64)
+                            .ok_or_else(|| Error::Protocol("Index overflow".to_string()))?;
+                        write_txn.insert(
+                            Table::ListData,
+                            &list_element_key(key, new_idx),
+                            value,
+                        )?;
                     }
-                }
 
-                removed_count
+                    // Update metadata with new tail
+                    write_txn.insert(Table::ListMeta, key, &encode_list_meta(head, new_tail))?;
+                }
             }
+
+            removed_count
         };
 
         // Commit transaction
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        self.commit(write_txn, || Change::LRem {
+            key: key.to_string(),
+            count,
+            element: element.to_vec(),
+        })?;
 
         debug!("LREM {} {} -> {} removed", key, count, removed_count);
 
@@ -1219,87 +1237,67 @@ impl SortedSetOps for Database {
             ));
         }
 
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let added = {
-            let mut member_table = write_txn
-                .open_table(ZSET_MEMBER_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset member table: {e}")))?;
-            let mut score_table = write_txn
-                .open_table(ZSET_SCORE_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
-
-            let member_key = zset_member_key(key, member);
-            let member_key_str = String::from_utf8_lossy(&member_key);
-
-            // Check if member already exists
-            let is_new =
-                if let Ok(Some(old_score_bytes)) = member_table.get(member_key_str.as_ref()) {
-                    // Member exists, remove old score index entry
-                    let old_score = decode_score(old_score_bytes.value())?;
-                    let old_score_key = zset_score_key(key, old_score, member);
-                    score_table.remove(old_score_key.as_str()).map_err(|e| {
-                        Error::Protocol(format!("Failed to remove old score entry: {e}"))
-                    })?;
-                    false // Not a new member
-                } else {
-                    true // New member
-                };
-
-            // Add/update member-to-score mapping
-            let score_bytes = encode_score(score);
-            member_table
-                .insert(member_key_str.as_ref(), &score_bytes[..])
-                .map_err(|e| Error::Protocol(format!("Failed to insert member: {e}")))?;
-
-            // Add score-to-member index entry
-            let score_key = zset_score_key(key, score, member);
-            score_table
-                .insert(score_key.as_str(), &b""[..])
-                .map_err(|e| Error::Protocol(format!("Failed to insert score index: {e}")))?;
-
-            u64::from(is_new)
+        let write_txn = self.engine.begin_write()?;
+
+        let member_key = zset_member_key(key, member);
+        let member_key_str = String::from_utf8_lossy(&member_key);
+
+        // Check if member already exists
+        let is_new = match write_txn.get(Table::ZsetMember, &member_key_str)? {
+            Some(old_score_bytes) => {
+                // Member exists, remove old score index entry
+                let old_score = decode_score(&old_score_bytes)?;
+                write_txn.remove(Table::ZsetScore, &zset_score_key(key, old_score, member))?;
+                false // Not a new member
+            }
+            None => true, // New member
         };
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        // Add/update member-to-score mapping
+        write_txn.insert(Table::ZsetMember, &member_key_str, &encode_score(score))?;
+
+        // Add score-to-member index entry
+        write_txn.insert(Table::ZsetScore, &zset_score_key(key, score, member), b"")?;
+
+        let added = u64::from(is_new);
+
+        self.commit(write_txn, || Change::ZAdd {
+            key: key.to_string(),
+            score,
+            member: member.to_vec(),
+        })?;
 
         debug!("ZADD {} {} -> added: {}", key, score, added);
         Ok(added)
     }
 
     fn zrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<(Vec<u8>, f64)>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let score_table = read_txn
-            .open_table(ZSET_SCORE_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Collect all members for this sorted set using range query
         // Use range query for O(M) performance where M = members in this set
         // Instead of O(N) where N = all members across all sets
-        let start_key = format!("{}:", key);
-        let end_key = format!("{};", key); // ';' is ASCII next after ':'
+        let (start_key, end_key) = zset_range(key);
         let mut members: Vec<(Vec<u8>, f64)> = Vec::new();
+        let mut parsed = Ok(());
 
         // Range query: only scan keys for this specific sorted set
-        for item in score_table
-            .range(start_key.as_str()..end_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?
-        {
-            let (k, _) =
-                item.map_err(|e| Error::Protocol(format!("Failed to read score entry: {e}")))?;
-            let key_str = k.value();
-            let (member, score) = parse_score_key(key_str, key.len())?;
-            members.push((member, score));
-        }
+        read_txn.range(
+            Table::ZsetScore,
+            &start_key,
+            Some(&end_key),
+            &mut |score_key, _| match parse_score_key(score_key, key.len()) {
+                Ok(entry) => {
+                    members.push(entry);
+                    true
+                }
+                Err(e) => {
+                    parsed = Err(e);
+                    false
+                }
+            },
+        )?;
+        parsed?;
 
         if members.is_empty() {
             return Ok(vec![]);
@@ -1360,33 +1358,32 @@ impl SortedSetOps for Database {
             ));
         }
 
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let score_table = read_txn
-            .open_table(ZSET_SCORE_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Use range query for O(M) performance where M = members in this set
-        let start_key = format!("{}:", key);
-        let end_key = format!("{};", key); // ';' is ASCII next after ':'
+        let (start_key, end_key) = zset_range(key);
         let mut members: Vec<(Vec<u8>, f64)> = Vec::new();
+        let mut parsed = Ok(());
 
         // Range query: only scan keys for this specific sorted set, then filter by score
-        for item in score_table
-            .range(start_key.as_str()..end_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?
-        {
-            let (k, _) =
-                item.map_err(|e| Error::Protocol(format!("Failed to read score entry: {e}")))?;
-            let key_str = k.value();
-            let (member, score) = parse_score_key(key_str, key.len())?;
-            if score >= min_score && score <= max_score {
-                members.push((member, score));
-            }
-        }
+        read_txn.range(
+            Table::ZsetScore,
+            &start_key,
+            Some(&end_key),
+            &mut |score_key, _| match parse_score_key(score_key, key.len()) {
+                Ok((member, score)) => {
+                    if score >= min_score && score <= max_score {
+                        members.push((member, score));
+                    }
+                    true
+                }
+                Err(e) => {
+                    parsed = Err(e);
+                    false
+                }
+            },
+        )?;
+        parsed?;
 
         debug!(
             "ZRANGEBYSCORE {} {} {} -> {} members",
@@ -1399,105 +1396,54 @@ impl SortedSetOps for Database {
     }
 
     fn zrem(&self, key: &str, member: &[u8]) -> Result<u64> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let removed = {
-            let mut member_table = write_txn
-                .open_table(ZSET_MEMBER_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset member table: {e}")))?;
-            let mut score_table = write_txn
-                .open_table(ZSET_SCORE_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
-
-            let member_key = zset_member_key(key, member);
-            let member_key_str = String::from_utf8_lossy(&member_key);
-
-            // Get the score first
-            let score_bytes = match member_table.get(member_key_str.as_ref()) {
-                Ok(Some(bytes)) => bytes.value().to_vec(),
-                Ok(None) => return Ok(0), // Member doesn't exist
-                Err(e) => return Err(Error::Protocol(format!("Failed to get member: {e}"))),
-            };
-
-            let score = decode_score(&score_bytes)?;
-
-            // Remove member-to-score mapping
-            member_table
-                .remove(member_key_str.as_ref())
-                .map_err(|e| Error::Protocol(format!("Failed to remove member: {e}")))?;
+        let write_txn = self.engine.begin_write()?;
 
-            // Remove score-to-member index
-            let score_key = zset_score_key(key, score, member);
-            score_table
-                .remove(score_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to remove score index: {e}")))?;
+        let member_key = zset_member_key(key, member);
+        let member_key_str = String::from_utf8_lossy(&member_key);
 
-            1u64
+        // Get the score first
+        let Some(score_bytes) = write_txn.get(Table::ZsetMember, &member_key_str)? else {
+            return Ok(0); // Member doesn't exist
         };
+        let score = decode_score(&score_bytes)?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        // Remove member-to-score mapping and score-to-member index
+        write_txn.remove(Table::ZsetMember, &member_key_str)?;
+        write_txn.remove(Table::ZsetScore, &zset_score_key(key, score, member))?;
+
+        self.commit(write_txn, || Change::ZRem {
+            key: key.to_string(),
+            member: member.to_vec(),
+        })?;
 
-        debug!("ZREM {} -> removed: {}", key, removed);
-        Ok(removed)
+        debug!("ZREM {} -> removed: {}", key, 1);
+        Ok(1)
     }
 
     fn zscore(&self, key: &str, member: &[u8]) -> Result<Option<f64>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let member_table = read_txn
-            .open_table(ZSET_MEMBER_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open zset member table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         let member_key = zset_member_key(key, member);
         let member_key_str = String::from_utf8_lossy(&member_key);
 
-        match member_table.get(member_key_str.as_ref()) {
-            Ok(Some(score_bytes)) => {
-                let score = decode_score(score_bytes.value())?;
+        match read_txn.get(Table::ZsetMember, &member_key_str)? {
+            Some(score_bytes) => {
+                let score = decode_score(&score_bytes)?;
                 debug!("ZSCORE {} -> {}", key, score);
                 Ok(Some(score))
             }
-            Ok(None) => {
+            None => {
                 debug!("ZSCORE {} -> (nil)", key);
                 Ok(None)
             }
-            Err(e) => Err(Error::Protocol(format!("Failed to get score: {e}"))),
         }
     }
 
     fn zcard(&self, key: &str) -> Result<u64> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let score_table = read_txn
-            .open_table(ZSET_SCORE_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open zset score table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Use range query for O(M) performance where M = members in this set
-        let start_key = format!("{}:", key);
-        let end_key = format!("{};", key); // ';' is ASCII next after ':'
-        let mut count = 0u64;
-
-        for item in score_table
-            .range(start_key.as_str()..end_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?
-        {
-            let _ =
-                item.map_err(|e| Error::Protocol(format!("Failed to read score entry: {e}")))?;
-            count = count
-                .checked_add(1)
-                .ok_or_else(|| Error::Protocol("Count overflow".to_string()))?;
-        }
+        let count = count_prefixed(read_txn.as_ref(), Table::ZsetScore, &format!("{}:", key))?;
 
         debug!("ZCARD {} -> {}", key, count);
         Ok(count)
@@ -1524,165 +1470,98 @@ impl HashOps for Database {
             )));
         }
 
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let is_new = {
-            let mut table = write_txn
-                .open_table(HASH_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
-
-            let field_key = hash_field_key(key, field);
-
-            // Check if field already exists
-            let is_new = table
-                .get(field_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to check field: {e}")))?
-                .is_none();
-
-            // Security: If adding a new field, check hash size limit
-            if is_new {
-                // Count existing fields in this hash
-                let start_key = format!("{}:", key);
-                let end_key = format!("{};\u{0}", key); // ';' is next after ':' in ASCII
-                let mut count = 0u64;
-                for item in table
-                    .range(start_key.as_str()..end_key.as_str())
-                    .map_err(|e| Error::Protocol(format!("Failed to count fields: {e}")))?
-                {
-                    let _ =
-                        item.map_err(|e| Error::Protocol(format!("Failed to read field: {e}")))?;
-                    count = count
-                        .checked_add(1)
-                        .ok_or_else(|| Error::Protocol("Field count overflow".to_string()))?;
-                }
+        let write_txn = self.engine.begin_write()?;
 
-                // Check if adding this field would exceed the limit
-                if count >= MAX_HASH_FIELDS {
-                    return Err(Error::Protocol(format!(
-                        "Hash field limit exceeded: {} (max: {})",
-                        count, MAX_HASH_FIELDS
-                    )));
-                }
-            }
+        let field_key = hash_field_key(key, field);
 
-            table
-                .insert(field_key.as_str(), value)
-                .map_err(|e| Error::Protocol(format!("Failed to set field: {e}")))?;
+        // Check if field already exists
+        let is_new = write_txn.get(Table::Hash, &field_key)?.is_none();
 
-            u64::from(is_new)
-        };
+        // Security: If adding a new field, check hash size limit
+        if is_new {
+            let count = count_prefixed(write_txn.as_ref(), Table::Hash, &format!("{}:", key))?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+            // Check if adding this field would exceed the limit
+            if count >= MAX_HASH_FIELDS {
+                return Err(Error::Protocol(format!(
+                    "Hash field limit exceeded: {} (max: {})",
+                    count, MAX_HASH_FIELDS
+                )));
+            }
+        }
+
+        write_txn.insert(Table::Hash, &field_key, value)?;
+        let is_new = u64::from(is_new);
+
+        self.commit(write_txn, || Change::HSet {
+            key: key.to_string(),
+            field: field.to_string(),
+            value: value.to_vec(),
+        })?;
 
         debug!("HSET {} {} -> new: {}", key, field, is_new);
         Ok(is_new)
     }
 
     fn hget(&self, key: &str, field: &str) -> Result<Option<Vec<u8>>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let table = read_txn
-            .open_table(HASH_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
-
-        let field_key = hash_field_key(key, field);
+        let read_txn = self.engine.begin_read()?;
 
-        match table.get(field_key.as_str()) {
-            Ok(Some(value)) => {
-                let bytes = value.value().to_vec();
+        match read_txn.get(Table::Hash, &hash_field_key(key, field))? {
+            Some(bytes) => {
                 debug!("HGET {} {} -> {} bytes", key, field, bytes.len());
                 Ok(Some(bytes))
             }
-            Ok(None) => {
+            None => {
                 debug!("HGET {} {} -> (nil)", key, field);
                 Ok(None)
             }
-            Err(e) => Err(Error::Protocol(format!("Failed to get field: {e}"))),
         }
     }
 
     fn hdel(&self, key: &str, field: &str) -> Result<u64> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let deleted = {
-            let mut table = write_txn
-                .open_table(HASH_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
-
-            let field_key = hash_field_key(key, field);
-
-            let result = table
-                .remove(field_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to delete field: {e}")))?;
-            u64::from(result.is_some())
-        };
+        let write_txn = self.engine.begin_write()?;
 
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        let deleted = u64::from(write_txn.remove(Table::Hash, &hash_field_key(key, field))?);
+
+        self.commit(write_txn, || Change::HDel {
+            key: key.to_string(),
+            field: field.to_string(),
+        })?;
 
         debug!("HDEL {} {} -> deleted: {}", key, field, deleted);
         Ok(deleted)
     }
 
     fn hgetall(&self, key: &str) -> Result<Vec<(String, Vec<u8>)>> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let table = read_txn
-            .open_table(HASH_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Use range query for O(M) performance where M = fields in this hash
         let start_key = format!("{}:", key);
         let end_key = format!("{};", key); // ';' is ASCII next after ':'
         let mut fields: Vec<(String, Vec<u8>)> = Vec::new();
 
-        for item in table
-            .range(start_key.as_str()..end_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?
-        {
-            let (k, v) = item.map_err(|e| Error::Protocol(format!("Failed to read field: {e}")))?;
-            let key_str = k.value();
-
-            // Extract field name from "{hash}:{field}"
-            if let Some(field) = key_str.strip_prefix(&start_key) {
-                fields.push((field.to_string(), v.value().to_vec()));
-            }
-        }
+        read_txn.range(
+            Table::Hash,
+            &start_key,
+            Some(&end_key),
+            &mut |field_key, value| {
+                // Extract field name from "{hash}:{field}"
+                if let Some(field) = field_key.strip_prefix(&start_key) {
+                    fields.push((field.to_string(), value.to_vec()));
+                }
+                true
+            },
+        )?;
 
         debug!("HGETALL {} -> {} fields", key, fields.len());
         Ok(fields)
     }
 
     fn hexists(&self, key: &str, field: &str) -> Result<bool> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
-        let table = read_txn
-            .open_table(HASH_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
-
-        let field_key = hash_field_key(key, field);
-
-        let exists = table
-            .get(field_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to check field: {e}")))?
+        let exists = read_txn
+            .get(Table::Hash, &hash_field_key(key, field))?
             .is_some();
 
         debug!("HEXISTS {} {} -> {}", key, field, exists);
@@ -1690,118 +1569,60 @@ impl HashOps for Database {
     }
 
     fn hlen(&self, key: &str) -> Result<u64> {
-        let read_txn = self
-            .db
-            .begin_read()
-            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
-
-        let table = read_txn
-            .open_table(HASH_TABLE)
-            .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
+        let read_txn = self.engine.begin_read()?;
 
         // Use range query for O(M) performance
-        let start_key = format!("{}:", key);
-        let end_key = format!("{};", key);
-        let mut count = 0u64;
-
-        for item in table
-            .range(start_key.as_str()..end_key.as_str())
-            .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?
-        {
-            let _ = item.map_err(|e| Error::Protocol(format!("Failed to read field: {e}")))?;
-            count = count
-                .checked_add(1)
-                .ok_or_else(|| Error::Protocol("Count overflow".to_string()))?;
-        }
+        let count = count_prefixed(read_txn.as_ref(), Table::Hash, &format!("{}:", key))?;
 
         debug!("HLEN {} -> {}", key, count);
         Ok(count)
     }
 
     fn hincrby(&self, key: &str, field: &str, increment: i64) -> Result<i64> {
-        let write_txn = self
-            .db
-            .begin_write()
-            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
-
-        let new_value = {
-            let mut table = write_txn
-                .open_table(HASH_TABLE)
-                .map_err(|e| Error::Protocol(format!("Failed to open hash table: {e}")))?;
-
-            let hash_key = format!("{}:{}", key, field);
-
-            // Get current value (default to 0 if not exists)
-            let existing_value = table
-                .get(hash_key.as_str())
-                .map_err(|e| Error::Protocol(format!("Failed to read hash field: {e}")))?;
-
-            let is_new_field = existing_value.is_none();
-
-            let current_value: i64 = existing_value
-                .as_ref()
-                .map(|v| {
-                    let bytes = v.value();
-                    std::str::from_utf8(bytes)
-                        .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in hash field: {e}")))?
-                        .parse::<i64>()
-                        .map_err(|e| Error::Protocol(format!("Hash field is not an integer: {e}")))
-                })
-                .transpose()?
-                .unwrap_or(0);
-
-            // Drop existing_value to release borrow before doing range query
-            drop(existing_value);
-
-            // If field doesn't exist, check field count limit before creating
-            if is_new_field {
-                let prefix = format!("{}:", key);
-                let mut count: u64 = 0;
-
-                for item in table
-                    .range(prefix.as_str()..)
-                    .map_err(|e| Error::Protocol(format!("Failed to count fields: {e}")))?
-                {
-                    let (k, _) =
-                        item.map_err(|e| Error::Protocol(format!("Failed to read field: {e}")))?;
-                    let key_str = k.value();
-
-                    // Stop counting if we've moved past this hash's fields
-                    if !key_str.starts_with(&prefix) {
-                        break;
-                    }
-
-                    count = count
-                        .checked_add(1)
-                        .ok_or_else(|| Error::Protocol("Field count overflow".to_string()))?;
-                }
-
-                // Check if adding this field would exceed the limit
-                if count >= MAX_HASH_FIELDS {
-                    return Err(Error::Protocol(format!(
-                        "Hash field limit exceeded: {} (max: {})",
-                        count, MAX_HASH_FIELDS
-                    )));
-                }
+        let write_txn = self.engine.begin_write()?;
+
+        let hash_key = hash_field_key(key, field);
+
+        // Get current value (default to 0 if not exists)
+        let existing_value = write_txn.get(Table::Hash, &hash_key)?;
+        let is_new_field = existing_value.is_none();
+
+        let current_value: i64 = existing_value
+            .map(|bytes| {
+                std::str::from_utf8(&bytes)
+                    .map_err(|e| Error::Protocol(format!("Invalid UTF-8 in hash field: {e}")))?
+                    .parse::<i64>()
+                    .map_err(|e| Error::Protocol(format!("Hash field is not an integer: {e}")))
+            })
+            .transpose()?
+            .unwrap_or(0);
+
+        // If field doesn't exist, check field count limit before creating
+        if is_new_field {
+            let count = count_prefixed(write_txn.as_ref(), Table::Hash, &format!("{}:", key))?;
+
+            // Check if adding this field would exceed the limit
+            if count >= MAX_HASH_FIELDS {
+                return Err(Error::Protocol(format!(
+                    "Hash field limit exceeded: {} (max: {})",
+                    count, MAX_HASH_FIELDS
+                )));
             }
+        }
 
-            // Calculate new value with overflow check
-            let new_value = current_value
-                .checked_add(increment)
-                .ok_or_else(|| Error::Protocol("Integer overflow in HINCRBY".to_string()))?;
+        // Calculate new value with overflow check
+        let new_value = current_value
+            .checked_add(increment)
+            .ok_or_else(|| Error::Protocol("Integer overflow in HINCRBY".to_string()))?;
 
-            // Store new value
-            let value_str = new_value.to_string();
-            table
-                .insert(hash_key.as_str(), value_str.as_bytes())
-                .map_err(|e| Error::Protocol(format!("Failed to write hash field: {e}")))?;
+        // Store new value
+        write_txn.insert(Table::Hash, &hash_key, new_value.to_string().as_bytes())?;
 
-            new_value
-        };
-
-        write_txn
-            .commit()
-            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))?;
+        self.commit(write_txn, || Change::HIncrBy {
+            key: key.to_string(),
+            field: field.to_string(),
+            increment,
+        })?;
 
         debug!("HINCRBY {} {} {} -> {}", key, field, increment, new_value);
         Ok(new_value)
@@ -1828,6 +1649,72 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_in_memory_backend() {
+        let db = Database::open_in_memory().unwrap();
+        db.set("key1", b"value1").unwrap();
+        db.lpush("list", b"a").unwrap();
+        db.zadd("zset", 1.0, b"m").unwrap();
+        db.hset("hash", "f", b"v").unwrap();
+
+        assert_eq!(db.get("key1").unwrap(), Some(b"value1".to_vec()));
+        assert_eq!(db.rpop("list").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(db.zscore("zset", b"m").unwrap(), Some(1.0));
+        assert_eq!(db.hget("hash", "f").unwrap(), Some(b"v".to_vec()));
+    }
+
+    #[test]
+    fn test_sqlite_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("data.sqlite3");
+        {
+            let db = Database::open_with(Backend::Sqlite, &db_path).unwrap();
+            db.set("key1", b"value1").unwrap();
+            db.setex("gone", b"v", 1).unwrap();
+            db.lpush("list", b"a").unwrap();
+            db.lpush("list", b"b").unwrap();
+            db.lpush("list", b"a").unwrap();
+            assert_eq!(db.lrem("list", 0, b"a").unwrap(), 2);
+            assert_eq!(db.rpoplpush("list", "done").unwrap(), Some(b"b".to_vec()));
+            db.zadd("zset", 2.5, b"m2").unwrap();
+            db.zadd("zset", -1.0, b"m1").unwrap();
+            db.zadd("zset", 7.0, b"m3").unwrap();
+            db.zadd("zset", 0.5, b"m3").unwrap();
+            db.hset("hash", "f", b"v").unwrap();
+            db.hincrby("hash", "n", 5).unwrap();
+            assert_eq!(db.purge_expired(2).unwrap(), 1);
+        }
+
+        // Everything committed is there after reopening
+        let db = Database::open_with(Backend::Sqlite, &db_path).unwrap();
+        assert_eq!(db.get("key1").unwrap(), Some(b"value1".to_vec()));
+        assert!(!db.exists("gone").unwrap());
+        assert_eq!(db.llen("list").unwrap(), 0);
+        assert_eq!(db.lrange("done", 0, -1).unwrap(), [b"b".to_vec()]);
+        assert_eq!(
+            db.zrange("zset", 0, -1).unwrap(),
+            [
+                (b"m1".to_vec(), -1.0),
+                (b"m3".to_vec(), 0.5),
+                (b"m2".to_vec(), 2.5)
+            ]
+        );
+        assert_eq!(db.zcard("zset").unwrap(), 3);
+        assert_eq!(db.hlen("hash").unwrap(), 2);
+        assert_eq!(db.hget("hash", "n").unwrap(), Some(b"5".to_vec()));
+        assert!(db.storage_stats().unwrap().stored_bytes > 0);
+
+        // Snapshots are redb files, so they move between backends
+        let snapshot = temp_dir.path().join("snapshot.redb");
+        db.snapshot(&snapshot).unwrap();
+        let memory = Database::open_in_memory().unwrap();
+        memory.load_snapshot(&snapshot).unwrap();
+        assert_eq!(memory.hgetall("hash").unwrap().len(), 2);
+        db.set("key1", b"changed").unwrap();
+        db.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(db.get("key1").unwrap(), Some(b"value1".to_vec()));
+    }
+
     #[test]
     fn test_set_and_get() {
         let (db, _temp) = test_db();
@@ -1943,6 +1830,28 @@ mod tests {
         assert_eq!(ttl, Some(-2));
     }
 
+    #[test]
+    fn test_purge_expired_deletes_unread_keys() {
+        let (db, _temp) = test_db();
+
+        db.setex("idem:old", b"plan_a", 100).unwrap();
+        db.setex("idem:new", b"plan_b", 2000).unwrap();
+        db.set("plain", b"value").unwrap();
+
+        assert_eq!(db.purge_expired(1000).unwrap(), 1);
+        assert_eq!(db.purge_expired(1000).unwrap(), 0);
+
+        let mut keys = Vec::new();
+        db.for_each_entry(|key, bytes| keys.push((key.to_string(), bytes)))
+            .unwrap();
+        assert!(keys.contains(&("plain".to_string(), 10)));
+        assert!(keys.contains(&("idem:new".to_string(), 16)));
+        assert!(!keys.iter().any(|(key, _)| key == "idem:old"));
+
+        let stats = db.storage_stats().unwrap();
+        assert!(stats.allocated_bytes >= stats.stored_bytes);
+    }
+
     #[test]
     fn test_ttl_no_expiry() {
         let (db, _temp) = test_db();
@@ -2832,7 +2741,7 @@ mod tests {
             scores.iter().map(|&s| (encode_score(s), s)).collect();
 
         // Encoded bytes should sort in same order as original scores
-        encoded.sort_by(|a, b| a.0.cmp(&b.0));
+        encoded.sort_by_key(|a| a.0);
 
         for i in 0..encoded.len() {
             // Verify decode gives back original
@@ -0,0 +1,152 @@
+//! Storage engines the database can run on
+//!
+//! [`Database`](super::Database) keeps every data type in seven ordered
+//! key-value tables and only needs transactions that read, write and scan
+//! them. An engine provides those transactions; the Redis-style commands,
+//! expiry, change log and snapshots are built on top and behave the same on
+//! every engine.
+
+use crate::Result;
+
+/// One of the tables the database keeps its data in
+///
+/// Keys are strings compared byte by byte; values are raw bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    /// String values
+    /// Key: key name, Value: value bytes
+    Kv,
+    /// Key expiry times
+    /// Key: key name, Value: Unix timestamp (seconds) as 8 bytes (u64)
+    Expiry,
+    /// List metadata (head/tail pointers)
+    /// Key: list name, Value: (head_index: i64, tail_index: i64) as 16 bytes
+    ListMeta,
+    /// List elements
+    /// Key: "{list_name}:{index}", Value: element bytes
+    ListData,
+    /// Sorted set member-to-score mapping
+    /// Key: "{zset_name}:{member}", Value: score (8 bytes, f64)
+    ZsetMember,
+    /// Sorted set score-to-member mapping (for range queries)
+    /// Key: "{zset_name}:{score_hex}:{member_hex}", Value: empty
+    /// The score is encoded as a sortable byte representation
+    ZsetScore,
+    /// Hash field-value storage
+    /// Key: "{hash_name}:{field}", Value: field value bytes
+    Hash,
+}
+
+impl Table {
+    /// Every table, in the order snapshots copy them
+    pub const ALL: [Table; 7] = [
+        Table::Kv,
+        Table::Expiry,
+        Table::ListMeta,
+        Table::ListData,
+        Table::ZsetMember,
+        Table::ZsetScore,
+        Table::Hash,
+    ];
+
+    /// Name of the table in the engine
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::Kv => "kv",
+            Table::Expiry => "expiry",
+            Table::ListMeta => "list_meta",
+            Table::ListData => "list_data",
+            Table::ZsetMember => "zset_member",
+            Table::ZsetScore => "zset_score",
+            Table::Hash => "hash",
+        }
+    }
+}
+
+/// An engine holding the database's tables
+pub trait Engine: Send + Sync {
+    /// Start a read transaction, which sees the tables as they were when
+    /// it started for as long as it lives
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction cannot be started
+    fn begin_read(&self) -> Result<Box<dyn ReadTxn + '_>>;
+
+    /// Start a write transaction
+    ///
+    /// Write transactions run one at a time; this waits for the current one
+    /// to finish. A transaction dropped without being committed is rolled
+    /// back.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction cannot be started
+    fn begin_write(&self) -> Result<Box<dyn WriteTxn + '_>>;
+
+    /// Space the tables take up
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the statistics cannot be read
+    fn stats(&self) -> Result<super::StorageStats>;
+}
+
+/// Reads within a transaction
+pub trait ReadTxn {
+    /// Get the value stored under `key`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be read
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Visit the entries with keys from `start` up to but not including
+    /// `end`, or to the end of the table, in key order
+    ///
+    /// The scan stops early when `visit` returns `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be read
+    fn range(
+        &self,
+        table: Table,
+        start: &str,
+        end: Option<&str>,
+        visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+    ) -> Result<()>;
+}
+
+/// Reads and writes within a transaction
+pub trait WriteTxn: ReadTxn {
+    /// Store `value` under `key`, replacing any existing value
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be written
+    fn insert(&self, table: Table, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Remove `key`, returning whether it was stored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be written
+    fn remove(&self, table: Table, key: &str) -> Result<bool>;
+
+    /// Remove every entry of a table
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be written
+    fn clear(&self, table: Table) -> Result<()>;
+
+    /// Make the transaction's writes durable and visible to new transactions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the commit fails, in which case none of the
+    /// writes are kept
+    fn commit(self: Box<Self>) -> Result<()>;
+}
@@ -1,4 +1,4 @@
-//! Storage layer for AGQ using redb embedded database
+//! Storage layer for AGQ on an embedded database
 //!
 //! Provides persistent storage for:
 //! - Plans (JSON)
@@ -6,9 +6,46 @@
 //! - Queues (ready, scheduled)
 //! - Workers (heartbeats, capabilities)
 
+mod changes;
 mod db;
+mod engine;
+mod redb_engine;
+mod sqlite;
 
-pub use db::Database;
+pub use changes::{Change, Numbered};
+pub use db::{Database, StorageStats};
+
+/// Where the database keeps its data
+///
+/// Every backend stores the same tables through the engine trait in
+/// `engine`, so every command behaves the same way on each. Snapshots are
+/// redb files whichever backend wrote them, so a snapshot taken on one
+/// backend can be restored on another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// A redb file in the data directory, durable across restarts and not
+    /// limited by memory
+    #[default]
+    File,
+    /// A SQLite file in the data directory, for deployments that want to
+    /// inspect or back up the data with SQLite tools
+    Sqlite,
+    /// Memory only, for small or throwaway deployments; state is lost on
+    /// exit except what was saved in snapshots
+    Memory,
+}
+
+impl Backend {
+    /// Name of the database file in the data directory, for backends that
+    /// keep one
+    #[must_use]
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Backend::File | Backend::Memory => "data.redb",
+            Backend::Sqlite => "data.sqlite3",
+        }
+    }
+}
 
 use crate::Result;
 use async_trait::async_trait;
@@ -0,0 +1,222 @@
+//! redb storage engine, on a file or in memory
+
+use crate::storage::engine::{Engine, ReadTxn, Table, WriteTxn};
+use crate::storage::StorageStats;
+use crate::{Error, Result};
+use redb::backends::InMemoryBackend;
+use redb::{
+    Database as RedbDatabase, ReadOnlyTable, ReadTransaction, ReadableTable, TableDefinition,
+    WriteTransaction,
+};
+use std::path::Path;
+
+/// The redb table definition of a table
+fn definition(table: Table) -> TableDefinition<'static, &'static str, &'static [u8]> {
+    TableDefinition::new(table.name())
+}
+
+/// A redb database
+pub struct RedbEngine {
+    db: RedbDatabase,
+}
+
+impl RedbEngine {
+    /// Open or create a redb file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or is not a redb
+    /// database
+    pub fn create(path: &Path) -> Result<Self> {
+        let db = RedbDatabase::create(path)
+            .map_err(|e| Error::Protocol(format!("Failed to open database: {e}")))?;
+        Self::init(db)
+    }
+
+    /// Open an existing redb file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist or is not a redb database
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = RedbDatabase::open(path)
+            .map_err(|e| Error::Protocol(format!("Failed to open database: {e}")))?;
+        Self::init(db)
+    }
+
+    /// Create an empty database held entirely in memory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tables cannot be created
+    pub fn in_memory() -> Result<Self> {
+        let db = RedbDatabase::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(|e| Error::Protocol(format!("Failed to open database: {e}")))?;
+        Self::init(db)
+    }
+
+    /// Create any missing tables
+    fn init(db: RedbDatabase) -> Result<Self> {
+        let write_txn = db
+            .begin_write()
+            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
+        for table in Table::ALL {
+            write_txn
+                .open_table(definition(table))
+                .map_err(|e| table_error(table, e))?;
+        }
+        write_txn
+            .commit()
+            .map_err(|e| Error::Protocol(format!("Failed to commit initialization: {e}")))?;
+        Ok(Self { db })
+    }
+}
+
+impl Engine for RedbEngine {
+    fn begin_read(&self) -> Result<Box<dyn ReadTxn + '_>> {
+        let txn = self
+            .db
+            .begin_read()
+            .map_err(|e| Error::Protocol(format!("Failed to begin read transaction: {e}")))?;
+        Ok(Box::new(txn))
+    }
+
+    fn begin_write(&self) -> Result<Box<dyn WriteTxn + '_>> {
+        let txn = self
+            .db
+            .begin_write()
+            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
+        Ok(Box::new(txn))
+    }
+
+    fn stats(&self) -> Result<StorageStats> {
+        let write_txn = self
+            .db
+            .begin_write()
+            .map_err(|e| Error::Protocol(format!("Failed to begin write transaction: {e}")))?;
+        let stats = write_txn
+            .stats()
+            .map_err(|e| Error::Protocol(format!("Failed to read database stats: {e}")))?;
+        write_txn
+            .abort()
+            .map_err(|e| Error::Protocol(format!("Failed to abort transaction: {e}")))?;
+        Ok(StorageStats {
+            allocated_bytes: stats.allocated_pages() * stats.page_size() as u64,
+            stored_bytes: stats.stored_bytes(),
+            fragmented_bytes: stats.fragmented_bytes(),
+        })
+    }
+}
+
+fn table_error(table: Table, e: impl std::fmt::Display) -> Error {
+    Error::Protocol(format!("Failed to open {} table: {e}", table.name()))
+}
+
+fn get<T: ReadableTable<&'static str, &'static [u8]>>(
+    table: &T,
+    key: &str,
+) -> Result<Option<Vec<u8>>> {
+    Ok(table
+        .get(key)
+        .map_err(|e| Error::Protocol(format!("Failed to get key: {e}")))?
+        .map(|value| value.value().to_vec()))
+}
+
+fn range<T: ReadableTable<&'static str, &'static [u8]>>(
+    table: &T,
+    start: &str,
+    end: Option<&str>,
+    visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+) -> Result<()> {
+    let entries = match end {
+        Some(end) => table.range::<&str>(start..end),
+        None => table.range::<&str>(start..),
+    }
+    .map_err(|e| Error::Protocol(format!("Failed to create range query: {e}")))?;
+    for entry in entries {
+        let (key, value) =
+            entry.map_err(|e| Error::Protocol(format!("Failed to read entry: {e}")))?;
+        if !visit(key.value(), value.value()) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl ReadTxn for ReadTransaction {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        let opened: ReadOnlyTable<&str, &[u8]> = self
+            .open_table(definition(table))
+            .map_err(|e| table_error(table, e))?;
+        get(&opened, key)
+    }
+
+    fn range(
+        &self,
+        table: Table,
+        start: &str,
+        end: Option<&str>,
+        visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+    ) -> Result<()> {
+        let opened: ReadOnlyTable<&str, &[u8]> = self
+            .open_table(definition(table))
+            .map_err(|e| table_error(table, e))?;
+        range(&opened, start, end, visit)
+    }
+}
+
+impl ReadTxn for WriteTransaction {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        let opened = self
+            .open_table(definition(table))
+            .map_err(|e| table_error(table, e))?;
+        get(&opened, key)
+    }
+
+    fn range(
+        &self,
+        table: Table,
+        start: &str,
+        end: Option<&str>,
+        visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+    ) -> Result<()> {
+        let opened = self
+            .open_table(definition(table))
+            .map_err(|e| table_error(table, e))?;
+        range(&opened, start, end, visit)
+    }
+}
+
+impl WriteTxn for WriteTransaction {
+    fn insert(&self, table: Table, key: &str, value: &[u8]) -> Result<()> {
+        self.open_table(definition(table))
+            .map_err(|e| table_error(table, e))?
+            .insert(key, value)
+            .map_err(|e| Error::Protocol(format!("Failed to insert key: {e}")))?;
+        Ok(())
+    }
+
+    fn remove(&self, table: Table, key: &str) -> Result<bool> {
+        let removed = self
+            .open_table(definition(table))
+            .map_err(|e| table_error(table, e))?
+            .remove(key)
+            .map_err(|e| Error::Protocol(format!("Failed to delete key: {e}")))?
+            .is_some();
+        Ok(removed)
+    }
+
+    fn clear(&self, table: Table) -> Result<()> {
+        self.delete_table(definition(table))
+            .map_err(|e| Error::Protocol(format!("Failed to clear {} table: {e}", table.name())))?;
+        self.open_table(definition(table))
+            .map_err(|e| table_error(table, e))?;
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> Result<()> {
+        WriteTransaction::commit(*self)
+            .map_err(|e| Error::Protocol(format!("Failed to commit transaction: {e}")))
+    }
+}
@@ -0,0 +1,391 @@
+//! SQLite storage engine
+//!
+//! Each table is a SQLite table of `(key TEXT PRIMARY KEY, value BLOB)`.
+//! The database runs in WAL mode with full sync, so a commit is durable
+//! when it returns and readers never block the writer. Writes go through
+//! one connection, taken for the length of a write transaction; reads use
+//! connections of their own, kept in a small pool.
+
+use crate::storage::engine::{Engine, ReadTxn, Table, WriteTxn};
+use crate::storage::StorageStats;
+use crate::{Error, Result};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Longest a connection waits for another process holding the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Idle read connections kept open for reuse
+const MAX_IDLE_READERS: usize = 8;
+
+fn sqlite_error(what: &str) -> impl Fn(rusqlite::Error) -> Error + '_ {
+    move |e| Error::Protocol(format!("Failed to {what}: {e}"))
+}
+
+/// A SQLite database file
+pub struct SqliteEngine {
+    path: PathBuf,
+    /// Declared before the writer so it is closed first: the last
+    /// connection to close folds the write-ahead log into the database
+    /// file, which read-only connections cannot do
+    readers: Mutex<Vec<Connection>>,
+    writer: Mutex<Connection>,
+}
+
+impl SqliteEngine {
+    /// Open or create a SQLite database file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or is not a SQLite
+    /// database
+    pub fn open(path: &Path) -> Result<Self> {
+        let writer = Connection::open(path).map_err(sqlite_error("open database"))?;
+        writer
+            .busy_timeout(BUSY_TIMEOUT)
+            .map_err(sqlite_error("set busy timeout"))?;
+        // Reading the journal mode is the first access to the file, so an
+        // unreadable database fails here
+        let mode: String = writer
+            .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+            .map_err(sqlite_error("open database"))?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            return Err(Error::Protocol(format!(
+                "Failed to open database: journal mode is {mode}, not WAL"
+            )));
+        }
+        writer
+            .execute_batch("PRAGMA synchronous = FULL")
+            .map_err(sqlite_error("set synchronous mode"))?;
+        for table in Table::ALL {
+            writer
+                .execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS {} \
+                     (key TEXT PRIMARY KEY NOT NULL, value BLOB NOT NULL) WITHOUT ROWID",
+                    table.name()
+                ))
+                .map_err(|e| {
+                    Error::Protocol(format!("Failed to create {} table: {e}", table.name()))
+                })?;
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            readers: Mutex::new(Vec::new()),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Take an idle read connection, or open a new one
+    fn reader(&self) -> Result<Connection> {
+        if let Some(conn) = self.readers.lock().unwrap_or_else(|e| e.into_inner()).pop() {
+            return Ok(conn);
+        }
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(sqlite_error("open database"))?;
+        conn.busy_timeout(BUSY_TIMEOUT)
+            .map_err(sqlite_error("set busy timeout"))?;
+        Ok(conn)
+    }
+}
+
+impl Engine for SqliteEngine {
+    fn begin_read(&self) -> Result<Box<dyn ReadTxn + '_>> {
+        let conn = self.reader()?;
+        conn.execute_batch("BEGIN")
+            .map_err(sqlite_error("begin read transaction"))?;
+        let txn = SqliteRead {
+            engine: self,
+            conn: Some(conn),
+        };
+        // A transaction only takes its snapshot at its first read, so read
+        // now to pin the tables as they are when this returns
+        txn.conn()
+            .query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+            .map_err(sqlite_error("begin read transaction"))?;
+        Ok(Box::new(txn))
+    }
+
+    fn begin_write(&self) -> Result<Box<dyn WriteTxn + '_>> {
+        let conn = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        conn.execute_batch("BEGIN IMMEDIATE")
+            .map_err(sqlite_error("begin write transaction"))?;
+        Ok(Box::new(SqliteWrite {
+            conn,
+            committed: false,
+        }))
+    }
+
+    fn stats(&self) -> Result<StorageStats> {
+        let conn = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let pragma = |name: &str| -> Result<u64> {
+            conn.query_row(&format!("PRAGMA {name}"), [], |row| row.get(0))
+                .map_err(sqlite_error("read database stats"))
+        };
+        let page_size = pragma("page_size")?;
+        let page_count = pragma("page_count")?;
+        let freelist_count = pragma("freelist_count")?;
+        let mut stored_bytes = 0u64;
+        for table in Table::ALL {
+            let bytes: u64 = conn
+                .query_row(
+                    &format!(
+                        "SELECT coalesce(sum(length(CAST(key AS BLOB)) + length(value)), 0) \
+                         FROM {}",
+                        table.name()
+                    ),
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(sqlite_error("read database stats"))?;
+            stored_bytes += bytes;
+        }
+        Ok(StorageStats {
+            allocated_bytes: page_count * page_size,
+            stored_bytes,
+            fragmented_bytes: freelist_count * page_size,
+        })
+    }
+}
+
+fn get(conn: &Connection, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+    conn.prepare_cached(&format!(
+        "SELECT value FROM {} WHERE key = ?1",
+        table.name()
+    ))
+    .and_then(|mut stmt| stmt.query_row(params![key], |row| row.get(0)).optional())
+    .map_err(sqlite_error("get key"))
+}
+
+fn range(
+    conn: &Connection,
+    table: Table,
+    start: &str,
+    end: Option<&str>,
+    visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+) -> Result<()> {
+    let sql = match end {
+        Some(_) => format!(
+            "SELECT key, value FROM {} WHERE key >= ?1 AND key < ?2 ORDER BY key",
+            table.name()
+        ),
+        None => format!(
+            "SELECT key, value FROM {} WHERE key >= ?1 ORDER BY key",
+            table.name()
+        ),
+    };
+    let mut stmt = conn
+        .prepare_cached(&sql)
+        .map_err(sqlite_error("create range query"))?;
+    let mut rows = match end {
+        Some(end) => stmt.query(params![start, end]),
+        None => stmt.query(params![start]),
+    }
+    .map_err(sqlite_error("create range query"))?;
+    while let Some(row) = rows.next().map_err(sqlite_error("read entry"))? {
+        let key = row.get_ref(0).map_err(sqlite_error("read entry"))?;
+        let value = row.get_ref(1).map_err(sqlite_error("read entry"))?;
+        let (Ok(key), Ok(value)) = (key.as_str(), value.as_blob()) else {
+            return Err(Error::Protocol(format!(
+                "Invalid entry in {} table",
+                table.name()
+            )));
+        };
+        if !visit(key, value) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// A read transaction on a pooled connection
+struct SqliteRead<'a> {
+    engine: &'a SqliteEngine,
+    /// Only `None` while being returned to the pool
+    conn: Option<Connection>,
+}
+
+impl SqliteRead<'_> {
+    fn conn(&self) -> &Connection {
+        self.conn.as_ref().expect("read connection taken")
+    }
+}
+
+impl Drop for SqliteRead<'_> {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else {
+            return;
+        };
+        // A connection that cannot end its transaction is closed instead
+        if conn.execute_batch("COMMIT").is_ok() {
+            let mut readers = self
+                .engine
+                .readers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if readers.len() < MAX_IDLE_READERS {
+                readers.push(conn);
+            }
+        }
+    }
+}
+
+impl ReadTxn for SqliteRead<'_> {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        get(self.conn(), table, key)
+    }
+
+    fn range(
+        &self,
+        table: Table,
+        start: &str,
+        end: Option<&str>,
+        visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+    ) -> Result<()> {
+        range(self.conn(), table, start, end, visit)
+    }
+}
+
+/// A write transaction holding the write connection
+struct SqliteWrite<'a> {
+    conn: MutexGuard<'a, Connection>,
+    committed: bool,
+}
+
+impl Drop for SqliteWrite<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.conn.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+impl ReadTxn for SqliteWrite<'_> {
+    fn get(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>> {
+        get(&self.conn, table, key)
+    }
+
+    fn range(
+        &self,
+        table: Table,
+        start: &str,
+        end: Option<&str>,
+        visit: &mut dyn FnMut(&str, &[u8]) -> bool,
+    ) -> Result<()> {
+        range(&self.conn, table, start, end, visit)
+    }
+}
+
+impl WriteTxn for SqliteWrite<'_> {
+    fn insert(&self, table: Table, key: &str, value: &[u8]) -> Result<()> {
+        self.conn
+            .prepare_cached(&format!(
+                "INSERT OR REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+                table.name()
+            ))
+            .and_then(|mut stmt| stmt.execute(params![key, value]))
+            .map_err(sqlite_error("insert key"))?;
+        Ok(())
+    }
+
+    fn remove(&self, table: Table, key: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .prepare_cached(&format!("DELETE FROM {} WHERE key = ?1", table.name()))
+            .and_then(|mut stmt| stmt.execute(params![key]))
+            .map_err(sqlite_error("delete key"))?;
+        Ok(removed > 0)
+    }
+
+    fn clear(&self, table: Table) -> Result<()> {
+        self.conn
+            .execute_batch(&format!("DELETE FROM {}", table.name()))
+            .map_err(|e| Error::Protocol(format!("Failed to clear {} table: {e}", table.name())))
+    }
+
+    fn commit(mut self: Box<Self>) -> Result<()> {
+        self.conn
+            .execute_batch("COMMIT")
+            .map_err(sqlite_error("commit transaction"))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn keys(txn: &dyn ReadTxn, start: &str, end: Option<&str>) -> Vec<String> {
+        let mut keys = Vec::new();
+        txn.range(Table::Kv, start, end, &mut |key, _| {
+            keys.push(key.to_string());
+            true
+        })
+        .unwrap();
+        keys
+    }
+
+    #[test]
+    fn test_range_is_ordered_by_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SqliteEngine::open(&temp_dir.path().join("data.sqlite3")).unwrap();
+        let txn = engine.begin_write().unwrap();
+        for key in ["b:2", "a;", "b:10", "b;", "b:", "B:1", "b:é"] {
+            txn.insert(Table::Kv, key, b"").unwrap();
+        }
+        txn.commit().unwrap();
+
+        let txn = engine.begin_read().unwrap();
+        assert_eq!(
+            keys(txn.as_ref(), "b:", Some("b;")),
+            ["b:", "b:10", "b:2", "b:é"]
+        );
+        assert_eq!(keys(txn.as_ref(), "b:2", None), ["b:2", "b:é", "b;"]);
+    }
+
+    #[test]
+    fn test_uncommitted_writes_are_rolled_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SqliteEngine::open(&temp_dir.path().join("data.sqlite3")).unwrap();
+        let txn = engine.begin_write().unwrap();
+        txn.insert(Table::Hash, "h:f", b"v").unwrap();
+        assert_eq!(txn.get(Table::Hash, "h:f").unwrap(), Some(b"v".to_vec()));
+        drop(txn);
+
+        let txn = engine.begin_read().unwrap();
+        assert_eq!(txn.get(Table::Hash, "h:f").unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_sees_tables_as_of_its_start() {
+        let temp_dir = TempDir::new().unwrap();
+        let engine = SqliteEngine::open(&temp_dir.path().join("data.sqlite3")).unwrap();
+        let before = engine.begin_read().unwrap();
+
+        let txn = engine.begin_write().unwrap();
+        txn.insert(Table::Kv, "key", b"value").unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(before.get(Table::Kv, "key").unwrap(), None);
+        let after = engine.begin_read().unwrap();
+        assert_eq!(
+            after.get(Table::Kv, "key").unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_non_sqlite_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data.sqlite3");
+        std::fs::write(&path, vec![0xAB; 8192]).unwrap();
+        assert!(SqliteEngine::open(&path).is_err());
+    }
+}
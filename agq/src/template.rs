@@ -0,0 +1,304 @@
+//! Parameterized Plans
+//!
+//! A Plan template is a Plan whose string values may contain `${NAME}`
+//! placeholders, e.g. `"args": ["${FILE}"]`. Templates are stored once as
+//! JSON at `plan_template:<id>` and indexed in the `plan_templates:all`
+//! sorted set; instantiating one substitutes a set of parameters and submits
+//! the result like any other Plan. `$${` stands for a literal `${`.
+//! Worker variables, `${env.KEY}` and `${TASK_N_OUTPUT}`, are left for the
+//! worker to resolve when the job runs.
+//!
+//! Substitution happens inside parsed JSON strings, so a parameter value can
+//! never change the structure of the Plan. An instance's `plan_id` is derived
+//! from the template and its parameters, so instantiating a template with
+//! the same parameters again yields the same Plan rather than a copy.
+
+use crate::error::{Error, Result};
+use crate::storage::{Database, SortedSetOps, StringOps};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Sorted set of template IDs, scored by when they were first saved
+pub const TEMPLATES_INDEX: &str = "plan_templates:all";
+
+/// Maximum number of templates
+pub const MAX_TEMPLATES: u64 = 1000;
+
+/// Maximum length of a template ID, leaving room for the suffix instances
+/// add to form their `plan_id`
+pub const MAX_TEMPLATE_ID_LEN: usize = 48;
+
+/// Maximum length of a parameter name
+const MAX_PARAM_NAME_LEN: usize = 64;
+
+/// A Plan with `${NAME}` placeholders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanTemplate {
+    pub template_id: String,
+
+    /// The Plan, with placeholders in its string values
+    pub plan: serde_json::Value,
+
+    /// Names of the placeholders, sorted
+    pub params: Vec<String>,
+
+    /// Timestamp when first saved
+    pub created_at: u64,
+
+    /// Timestamp when last saved
+    pub updated_at: u64,
+}
+
+impl PlanTemplate {
+    /// Create a template, collecting the parameters its placeholders name
+    ///
+    /// Any `plan_id` in the Plan is dropped, since each instance gets its own.
+    ///
+    /// # Errors
+    /// Returns an error if the Plan is not a JSON object or a placeholder is
+    /// malformed
+    pub fn new(template_id: String, plan: serde_json::Value, now: u64) -> Result<Self> {
+        let mut plan = plan;
+        let fields = plan.as_object_mut().ok_or_else(|| {
+            Error::InvalidArguments("Plan template must be a JSON object".to_string())
+        })?;
+        // Instances get their own plan_id
+        fields.remove("plan_id");
+
+        let mut params = BTreeSet::new();
+        visit_strings(&mut plan, &mut |text| {
+            render(text, &mut |name| {
+                params.insert(name.to_string());
+                Ok(String::new())
+            })
+            .map(|_| ())
+        })?;
+
+        Ok(Self {
+            template_id,
+            plan,
+            params: params.into_iter().collect(),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// The Plan with every placeholder replaced by its parameter
+    ///
+    /// # Errors
+    /// Returns an error if a parameter is missing or the template has no
+    /// placeholder for it
+    pub fn instantiate(&self, params: &BTreeMap<String, String>) -> Result<serde_json::Value> {
+        if let Some(unknown) = params.keys().find(|name| !self.params.contains(name)) {
+            return Err(Error::InvalidArguments(format!(
+                "Template {} has no parameter {}",
+                self.template_id, unknown
+            )));
+        }
+        let missing: Vec<&str> = self
+            .params
+            .iter()
+            .filter(|name| !params.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::InvalidArguments(format!(
+                "Template {} requires parameters: {}",
+                self.template_id,
+                missing.join(", ")
+            )));
+        }
+
+        let mut plan = self.plan.clone();
+        visit_strings(&mut plan, &mut |text| {
+            *text = render(text, &mut |name| Ok(params[name].clone()))?;
+            Ok(())
+        })?;
+        plan["plan_id"] = serde_json::Value::String(self.instance_plan_id(params));
+        Ok(plan)
+    }
+
+    /// `plan_id` of the instance for a set of parameters
+    fn instance_plan_id(&self, params: &BTreeMap<String, String>) -> String {
+        let input = serde_json::json!({"plan": self.plan, "params": params}).to_string();
+        let digest = ring::digest::digest(&ring::digest::SHA256, input.as_bytes());
+        format!("{}-{}", self.template_id, &hex::encode(digest)[..12])
+    }
+
+    /// Store the template, keeping when it was first saved
+    ///
+    /// # Errors
+    /// Returns an error if the database operation fails
+    pub fn save(&mut self, db: &Database) -> Result<()> {
+        if let Some(existing) = Self::load(db, &self.template_id)? {
+            self.created_at = existing.created_at;
+        }
+        let json = serde_json::to_string(self)
+            .map_err(|e| Error::Protocol(format!("Failed to serialize template: {}", e)))?;
+        db.set(&template_key(&self.template_id), json.as_bytes())?;
+        db.zadd(
+            TEMPLATES_INDEX,
+            self.created_at as f64,
+            self.template_id.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Load a template by ID
+    ///
+    /// # Errors
+    /// Returns an error if the database operation fails or the stored JSON
+    /// is invalid
+    pub fn load(db: &Database, template_id: &str) -> Result<Option<Self>> {
+        let Some(json) = db.get(&template_key(template_id))? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(|e| Error::Protocol(format!("Failed to deserialize template: {}", e)))
+    }
+}
+
+fn template_key(template_id: &str) -> String {
+    format!("plan_template:{}", template_id)
+}
+
+/// Apply `f` to every string value in a JSON document (object keys excluded)
+fn visit_strings(
+    value: &mut serde_json::Value,
+    f: &mut impl FnMut(&mut String) -> Result<()>,
+) -> Result<()> {
+    match value {
+        serde_json::Value::String(text) => f(text),
+        serde_json::Value::Array(items) => {
+            items.iter_mut().try_for_each(|item| visit_strings(item, f))
+        }
+        serde_json::Value::Object(fields) => fields
+            .values_mut()
+            .try_for_each(|field| visit_strings(field, f)),
+        _ => Ok(()),
+    }
+}
+
+/// Replace each `${NAME}` in a string with `lookup(NAME)` and each `$${`
+/// with `${`, leaving worker variables as they are
+fn render(text: &str, lookup: &mut impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| {
+                Error::InvalidArguments(format!("Unterminated placeholder in {:?}", text))
+            })?;
+            let name = &after[..end];
+            if is_worker_variable(name) {
+                output.push_str(&rest[..end + 3]);
+                rest = &after[end + 1..];
+                continue;
+            }
+            if !is_valid_param_name(name) {
+                return Err(Error::InvalidArguments(format!(
+                    "Invalid placeholder ${{{}}}: names are letters, digits and underscores",
+                    name
+                )));
+            }
+            output.push_str(&lookup(name)?);
+            rest = &after[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Whether a placeholder is a job variable the worker substitutes, i.e.
+/// `env.KEY` or `TASK_N_OUTPUT`
+fn is_worker_variable(name: &str) -> bool {
+    name.starts_with("env.")
+        || name
+            .strip_prefix("TASK_")
+            .and_then(|rest| rest.strip_suffix("_OUTPUT"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+fn is_valid_param_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= MAX_PARAM_NAME_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_instantiate_substitutes_strings() {
+        let plan = serde_json::json!({
+            "plan_id": "ignored",
+            "plan_description": "OCR + evaluate ${FILE}",
+            "tasks": [
+                {"task_number": 1, "command": "agx-ocr", "args": ["${FILE}", "--lang=${LANG}"]},
+                {"task_number": 2, "command": "sh", "args": ["-c", "echo $${HOME} $1"]},
+                {"task_number": 3, "command": "cp", "args": ["${TASK_1_OUTPUT}", "${env.dest}"]}
+            ]
+        });
+        let template = PlanTemplate::new("ocr".to_string(), plan, 100).unwrap();
+        assert_eq!(template.params, ["FILE", "LANG"]);
+
+        let first = template
+            .instantiate(&params(&[("FILE", "a \"b\".png"), ("LANG", "eng")]))
+            .unwrap();
+        assert_eq!(first["plan_description"], "OCR + evaluate a \"b\".png");
+        assert_eq!(
+            first["tasks"][0]["args"],
+            serde_json::json!(["a \"b\".png", "--lang=eng"])
+        );
+        assert_eq!(first["tasks"][1]["args"][1], "echo ${HOME} $1");
+        assert_eq!(
+            first["tasks"][2]["args"],
+            serde_json::json!(["${TASK_1_OUTPUT}", "${env.dest}"])
+        );
+
+        let plan_id = first["plan_id"].as_str().unwrap();
+        assert!(plan_id.starts_with("ocr-") && plan_id.len() == 16);
+        let again = template
+            .instantiate(&params(&[("FILE", "a \"b\".png"), ("LANG", "eng")]))
+            .unwrap();
+        assert_eq!(again["plan_id"], plan_id);
+        let other = template
+            .instantiate(&params(&[("FILE", "c.png"), ("LANG", "eng")]))
+            .unwrap();
+        assert_ne!(other["plan_id"], plan_id);
+
+        assert!(template.instantiate(&params(&[("FILE", "c.png")])).is_err());
+        let extra = params(&[("FILE", "c.png"), ("LANG", "eng"), ("X", "1")]);
+        assert!(template.instantiate(&extra).is_err());
+    }
+
+    #[test]
+    fn test_malformed_placeholders() {
+        for text in ["${FILE", "${}", "${1ST}", "${A-B}"] {
+            let plan = serde_json::json!({"tasks": [{"command": text}]});
+            assert!(
+                PlanTemplate::new("t".to_string(), plan, 0).is_err(),
+                "{text}"
+            );
+        }
+        assert!(PlanTemplate::new("t".to_string(), serde_json::json!([]), 0).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Execution history of each tool, for TOOLS.STATS
+//!
+//! When a worker posts a job's result to `job:<id>:result`, the run is
+//! added to the statistics of the job's command in the `tool:<name>:stats`
+//! hash: how many runs completed and failed, the total and longest
+//! execution time of the completed ones, and their CPU time where the
+//! worker measured it. Tools with statistics are kept in the `tools:stats`
+//! sorted set, scored by when they last ran. Planners use the statistics
+//! to estimate how long a plan will take before submitting it.
+
+use crate::error::Result;
+use crate::storage::{Database, HashOps, SortedSetOps, StringOps};
+use serde::Serialize;
+use tracing::warn;
+
+/// Sorted set of the tools with statistics
+const TOOLS_KEY: &str = "tools:stats";
+
+/// Execution history of a tool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolStats {
+    pub tool: String,
+    /// Runs that completed
+    pub runs: u64,
+    /// Runs that failed or timed out
+    pub failed: u64,
+    /// Average execution time of the completed runs
+    pub mean_duration_ms: u64,
+    /// Longest execution time of a completed run
+    pub max_duration_ms: u64,
+    /// Average CPU time of the completed runs that measured it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean_cpu_ms: Option<u64>,
+}
+
+fn stats_key(tool: &str) -> String {
+    format!("tool:{}:stats", tool)
+}
+
+/// Record a job's result from a key a worker wrote with SET
+///
+/// Keys other than a job result are ignored. Errors are logged, since they
+/// must not fail the worker's SET.
+pub fn observe_set(db: &Database, key: &str, value: &[u8], now: u64) {
+    let Some(job_id) = key
+        .strip_prefix("job:")
+        .and_then(|rest| rest.strip_suffix(":result"))
+    else {
+        return;
+    };
+    if let Err(e) = record(db, job_id, value, now) {
+        warn!("Failed to record tool statistics for job {}: {}", job_id, e);
+    }
+}
+
+fn record(db: &Database, job_id: &str, result: &[u8], now: u64) -> Result<()> {
+    let Ok(result) = serde_json::from_slice::<serde_json::Value>(result) else {
+        return Ok(());
+    };
+    let Some(job) = db
+        .get(&format!("job:{}", job_id))?
+        .and_then(|json| serde_json::from_slice::<serde_json::Value>(&json).ok())
+    else {
+        return Ok(());
+    };
+    // A pipeline's time belongs to its stages together, not one tool
+    let has_stages = job["stages"]
+        .as_array()
+        .is_some_and(|stages| !stages.is_empty());
+    let Some(tool) = job["command"].as_str().filter(|_| !has_stages) else {
+        return Ok(());
+    };
+
+    let key = stats_key(tool);
+    if result["status"].as_str() == Some("completed") {
+        let duration_ms = result["duration_ms"].as_u64().unwrap_or(0);
+        db.hincrby(&key, "runs", 1)?;
+        db.hincrby(&key, "duration_ms_total", clamp(duration_ms))?;
+        if duration_ms > field(db, &key, "duration_ms_max")? {
+            db.hset(&key, "duration_ms_max", duration_ms.to_string().as_bytes())?;
+        }
+        let usage = &result["resource_usage"];
+        if let (Some(user), Some(system)) = (
+            usage["cpu_user_ms"].as_u64(),
+            usage["cpu_system_ms"].as_u64(),
+        ) {
+            db.hincrby(&key, "cpu_runs", 1)?;
+            db.hincrby(&key, "cpu_ms_total", clamp(user.saturating_add(system)))?;
+        }
+    } else {
+        db.hincrby(&key, "failed", 1)?;
+    }
+    db.zadd(TOOLS_KEY, now as f64, tool.as_bytes())?;
+    Ok(())
+}
+
+fn clamp(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+fn field(db: &Database, key: &str, name: &str) -> Result<u64> {
+    Ok(db
+        .hget(key, name)?
+        .and_then(|value| String::from_utf8_lossy(&value).parse().ok())
+        .unwrap_or(0))
+}
+
+/// Statistics of a tool, if it has run
+///
+/// # Errors
+/// Returns an error if the database operation fails
+pub fn stats(db: &Database, tool: &str) -> Result<Option<ToolStats>> {
+    let key = stats_key(tool);
+    let runs = field(db, &key, "runs")?;
+    let failed = field(db, &key, "failed")?;
+    if runs == 0 && failed == 0 {
+        return Ok(None);
+    }
+
+    let cpu_runs = field(db, &key, "cpu_runs")?;
+    Ok(Some(ToolStats {
+        tool: tool.to_string(),
+        runs,
+        failed,
+        mean_duration_ms: field(db, &key, "duration_ms_total")?
+            .checked_div(runs)
+            .unwrap_or(0),
+        max_duration_ms: field(db, &key, "duration_ms_max")?,
+        mean_cpu_ms: field(db, &key, "cpu_ms_total")?.checked_div(cpu_runs),
+    }))
+}
+
+/// Statistics of every tool that has run, sorted by name
+///
+/// # Errors
+/// Returns an error if the database operation fails
+pub fn all(db: &Database) -> Result<Vec<ToolStats>> {
+    let mut tools: Vec<String> = db
+        .zrange(TOOLS_KEY, 0, -1)?
+        .into_iter()
+        .map(|(tool, _)| String::from_utf8_lossy(&tool).into_owned())
+        .collect();
+    tools.sort();
+
+    let mut all = Vec::new();
+    for tool in tools {
+        all.extend(stats(db, &tool)?);
+    }
+    Ok(all)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn finish(db: &Database, job_id: &str, command: &str, result: serde_json::Value) {
+        let job = serde_json::json!({"id": job_id, "command": command, "stages": []});
+        db.set(&format!("job:{}", job_id), job.to_string().as_bytes())
+            .unwrap();
+        let key = format!("job:{}:result", job_id);
+        observe_set(db, &key, result.to_string().as_bytes(), 100);
+    }
+
+    #[test]
+    fn test_results_add_up_per_tool() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::open(temp.path().join("test.redb")).unwrap();
+
+        let usage = serde_json::json!({"cpu_user_ms": 300, "cpu_system_ms": 100});
+        finish(
+            &db,
+            "j1",
+            "sort",
+            serde_json::json!({"status": "completed", "duration_ms": 1000, "resource_usage": usage}),
+        );
+        finish(
+            &db,
+            "j2",
+            "sort",
+            serde_json::json!({"status": "completed", "duration_ms": 3000}),
+        );
+        finish(
+            &db,
+            "j3",
+            "sort",
+            serde_json::json!({"status": "timeout", "duration_ms": 60000}),
+        );
+        finish(
+            &db,
+            "j4",
+            "uniq",
+            serde_json::json!({"status": "failed", "duration_ms": 5}),
+        );
+        observe_set(&db, "job:j1:stdout", b"ignored", 100);
+
+        assert_eq!(
+            stats(&db, "sort").unwrap(),
+            Some(ToolStats {
+                tool: "sort".to_string(),
+                runs: 2,
+                failed: 1,
+                mean_duration_ms: 2000,
+                max_duration_ms: 3000,
+                mean_cpu_ms: Some(400),
+            })
+        );
+        let tools: Vec<(String, u64)> = all(&db)
+            .unwrap()
+            .into_iter()
+            .map(|stats| (stats.tool, stats.runs))
+            .collect();
+        assert_eq!(tools, [("sort".to_string(), 2), ("uniq".to_string(), 0)]);
+        assert_eq!(stats(&db, "grep").unwrap(), None);
+    }
+}
@@ -4,12 +4,34 @@
 //! push jobs to internal queues, and worker threads process them asynchronously.
 
 use crate::error::{Error, Result};
-use crate::storage::{Database, HashOps, ListOps, SortedSetOps};
+use crate::orchestrator::{Orchestrator, PROCESSING_QUEUE, RUNNING_JOBS};
+use crate::storage::{Database, HashOps, ListOps, SortedSetOps, StringOps};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info, warn};
 
+/// Times a job is requeued after losing its worker before it is failed
+const MAX_RECLAIMS: u32 = 3;
+
+/// How often the reaper looks for jobs held by dead workers
+const REAPER_INTERVAL_SECS: u64 = 30;
+
+/// How often the timeout enforcer looks for jobs running past their timeout
+const TIMEOUT_INTERVAL_SECS: u64 = 5;
+
+/// How long jobs may run before AGQ takes them back from their worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    /// Timeout for jobs whose task sets none, or `None` to let them run
+    /// for as long as they take
+    pub default_secs: Option<u64>,
+    /// Time a job is given past its timeout for the worker, which enforces
+    /// the timeout itself, to report the result
+    pub grace_secs: u64,
+}
+
 /// Internal job structure for queue-based operations
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InternalJob {
@@ -63,6 +85,343 @@ pub async fn start_plan_worker(db: Arc<Database>) {
     }
 }
 
+/// Start the retry scheduler thread
+///
+/// Once a second, moves jobs whose delay or retry backoff has passed from
+/// `queue:delayed` onto their ready queues, queues jobs held in
+/// `queue:throttled` once their concurrency limits have room, queues jobs
+/// held in `queue:unschedulable` once a worker with their tags is live, and
+/// queues jobs waiting their turn for a ready queue its Plan has room in.
+pub async fn start_retry_scheduler(db: Arc<Database>) {
+    info!("Starting retry scheduler");
+
+    loop {
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        let orchestrator = Orchestrator::new(&db);
+        match orchestrator.promote_delayed(now) {
+            Ok(0) => {}
+            Ok(promoted) => debug!("Queued {} delayed jobs", promoted),
+            Err(e) => error!("Error in retry scheduler: {}", e),
+        }
+        match orchestrator.promote_throttled() {
+            Ok(0) => {}
+            Ok(promoted) => debug!("Released {} jobs held by concurrency limits", promoted),
+            Err(e) => error!("Error releasing throttled jobs: {}", e),
+        }
+        match orchestrator.promote_unschedulable(now) {
+            Ok(0) => {}
+            Ok(promoted) => info!(
+                "Released {} jobs now that eligible workers are live",
+                promoted
+            ),
+            Err(e) => error!("Error releasing unschedulable jobs: {}", e),
+        }
+        match orchestrator.promote_fair() {
+            Ok(0) => {}
+            Ok(promoted) => debug!("Released {} jobs waiting their turn", promoted),
+            Err(e) => error!("Error releasing jobs waiting their turn: {}", e),
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Start the plan scheduler thread
+///
+/// Once a second, creates Actions for schedules whose run time has come
+/// (see [`crate::schedule`]).
+pub async fn start_plan_scheduler(db: Arc<Database>) {
+    info!("Starting plan scheduler");
+
+    loop {
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        match crate::schedule::run_due(&db, now) {
+            Ok(0) => {}
+            Ok(ran) => debug!("Ran {} scheduled plans", ran),
+            Err(e) => error!("Error in plan scheduler: {}", e),
+        }
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Start the snapshot thread
+///
+/// Every `interval_secs`, writes a snapshot of the database to
+/// `snapshot_dir`, keeping the newest `keep` (see [`crate::recovery`]).
+pub async fn start_snapshotter(
+    db: Arc<Database>,
+    snapshot_dir: PathBuf,
+    interval_secs: u64,
+    keep: usize,
+) {
+    info!(
+        "Starting snapshots every {}s in {}",
+        interval_secs,
+        snapshot_dir.display()
+    );
+
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        let snapshot_db = Arc::clone(&db);
+        let dir = snapshot_dir.clone();
+        // Copying the database is blocking file I/O
+        let written = tokio::task::spawn_blocking(move || {
+            crate::recovery::write_snapshot(&snapshot_db, &dir, keep, now)
+        })
+        .await;
+        match written {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Error writing snapshot: {}", e),
+            Err(e) => error!("Snapshot task failed: {}", e),
+        }
+    }
+}
+
+/// Start the retention sweeper thread
+///
+/// Every `interval_secs`, deletes finished jobs, results, artifacts, and
+/// dead-letter entries older than the policy keeps them (see
+/// [`crate::retention`]).
+pub async fn start_retention_sweeper(
+    db: Arc<Database>,
+    policy: crate::retention::Policy,
+    artifacts: crate::artifacts::ArtifactStore,
+    interval_secs: u64,
+) {
+    info!("Starting retention sweeper: {:?}", policy);
+
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        let sweep_db = Arc::clone(&db);
+        let artifacts = artifacts.clone();
+        // A sweep can touch many keys, so keep it off the async workers
+        let swept = tokio::task::spawn_blocking(move || {
+            crate::retention::sweep(&sweep_db, &policy, &artifacts, now)
+        })
+        .await;
+        match swept {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Error in retention sweeper: {}", e),
+            Err(e) => error!("Retention sweep task failed: {}", e),
+        }
+    }
+}
+
+/// Start the compactor thread
+///
+/// Every `interval_secs`, deletes keys whose expiry has passed but that
+/// nobody has read since, then measures storage use (see
+/// [`crate::memory`]), publishes it as metrics, and checks it against the
+/// high-water mark.
+pub async fn start_compactor(
+    db: Arc<Database>,
+    high_water: crate::memory::HighWater,
+    interval_secs: u64,
+) {
+    info!("Starting compactor every {}s", interval_secs);
+
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        let compact_db = Arc::clone(&db);
+        let high_water = high_water.clone();
+        // Measuring reads every entry, so keep it off the async workers
+        let compacted = tokio::task::spawn_blocking(move || -> Result<()> {
+            compact_db.purge_expired(now)?;
+            let usage = crate::memory::usage(&compact_db)?;
+            let over = high_water.check(&usage);
+            crate::metrics::METRICS.observe_usage(&usage, over);
+            Ok(())
+        })
+        .await;
+        match compacted {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Error in compactor: {}", e),
+            Err(e) => error!("Compactor task failed: {}", e),
+        }
+    }
+}
+
+/// Start the worker reaper thread
+///
+/// Periodically requeues jobs held in `queue:processing` by workers whose
+/// heartbeats have expired, so work claimed by a crashed worker is not lost.
+pub async fn start_worker_reaper(db: Arc<Database>) {
+    info!("Starting worker reaper");
+
+    loop {
+        sleep(Duration::from_secs(REAPER_INTERVAL_SECS)).await;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        match reap_orphaned_jobs(&db, now) {
+            Ok(0) => {}
+            Ok(reaped) => info!("Reclaimed {} jobs from dead workers", reaped),
+            Err(e) => error!("Error in worker reaper: {}", e),
+        }
+    }
+}
+
+/// Start the timeout enforcer thread
+///
+/// Takes back jobs that run past their timeout without a result, so a hung
+/// worker cannot hold a job forever even if it never reports.
+pub async fn start_timeout_enforcer(db: Arc<Database>, policy: TimeoutPolicy) {
+    info!("Starting timeout enforcer: {:?}", policy);
+
+    loop {
+        sleep(Duration::from_secs(TIMEOUT_INTERVAL_SECS)).await;
+        let now = crate::server::get_current_timestamp_secs().unwrap_or(0);
+        match enforce_timeouts(&db, &policy, now) {
+            Ok(0) => {}
+            Ok(timed_out) => warn!("Took back {} jobs that ran past their timeout", timed_out),
+            Err(e) => error!("Error in timeout enforcer: {}", e),
+        }
+    }
+}
+
+/// Track when the jobs in `queue:processing` started and take back those
+/// past their deadline
+///
+/// A job first seen in the processing queue is marked running, and
+/// `jobs:running` records its deadline: its timeout plus the policy's grace
+/// period from now. A job still unfinished at its deadline is removed from
+/// the processing queue and its claim revoked, which tells the worker to
+/// stop running it, then counts as a failed attempt (see
+/// [`Orchestrator::time_out_job`]).
+///
+/// Returns the number of jobs taken back.
+fn enforce_timeouts(db: &Database, policy: &TimeoutPolicy, now: u64) -> Result<usize> {
+    let orchestrator = Orchestrator::new(db);
+    let processing = db.lrange(PROCESSING_QUEUE, 0, -1)?;
+
+    // Forget jobs that left the processing queue, so a job that runs again
+    // gets a new deadline
+    for (job_id_bytes, _deadline) in db.zrange(RUNNING_JOBS, 0, -1)? {
+        if !processing.contains(&job_id_bytes) {
+            db.zrem(RUNNING_JOBS, &job_id_bytes)?;
+        }
+    }
+
+    for job_id_bytes in &processing {
+        if db.zscore(RUNNING_JOBS, job_id_bytes)?.is_some() {
+            continue;
+        }
+        let job_id = String::from_utf8_lossy(job_id_bytes).into_owned();
+        let worker_id = db
+            .get(&format!("job:{}:worker", job_id))?
+            .map(|owner| String::from_utf8_lossy(&owner).into_owned());
+        let job = match orchestrator.mark_running(&job_id, worker_id, now) {
+            Ok(job) => job,
+            Err(e) => {
+                debug!("Not tracking job {}: {}", job_id, e);
+                continue;
+            }
+        };
+        let deadline = job
+            .timeout_secs
+            .map(u64::from)
+            .or(policy.default_secs)
+            .map_or(f64::MAX, |timeout| {
+                now.saturating_add(timeout)
+                    .saturating_add(policy.grace_secs) as f64
+            });
+        db.zadd(RUNNING_JOBS, deadline, job_id_bytes)?;
+    }
+
+    let mut timed_out = 0;
+    for (job_id_bytes, _deadline) in db.zrangebyscore(RUNNING_JOBS, 0.0, now as f64)? {
+        db.zrem(RUNNING_JOBS, &job_id_bytes)?;
+        let job_id = String::from_utf8_lossy(&job_id_bytes).into_owned();
+        if has_finished(db, &job_id)? {
+            continue;
+        }
+
+        db.lrem(PROCESSING_QUEUE, 0, &job_id_bytes)?;
+        db.del(&format!("job:{}:worker", job_id))?;
+        timed_out += 1;
+
+        match orchestrator.time_out_job(&job_id, now) {
+            Ok(true) => {}
+            Ok(false) => {
+                // Lets workers waiting on this job as a dependency see it failed
+                db.set(&format!("job:{}:status", job_id), b"timeout")?;
+                error!("Job {} ran past its timeout", job_id);
+            }
+            Err(e) => warn!("Could not time out job {}: {}", job_id, e),
+        }
+    }
+
+    Ok(timed_out)
+}
+
+/// Reclaim the jobs in `queue:processing` whose worker is no longer alive
+///
+/// Workers record their claim on a job as `job:<id>:worker`. Jobs without a
+/// claim are left alone, since the reaper cannot tell who is running them.
+/// Jobs that already finished are only removed from the processing queue.
+///
+/// Returns the number of jobs removed from the processing queue.
+fn reap_orphaned_jobs(db: &Database, now: u64) -> Result<usize> {
+    let orchestrator = Orchestrator::new(db);
+    let mut reaped = 0;
+
+    for job_id_bytes in db.lrange(PROCESSING_QUEUE, 0, -1)? {
+        let job_id = String::from_utf8_lossy(&job_id_bytes).into_owned();
+        let claim_key = format!("job:{}:worker", job_id);
+
+        let Some(owner) = db.get(&claim_key)? else {
+            continue;
+        };
+        if is_worker_alive(db, &String::from_utf8_lossy(&owner), now)? {
+            continue;
+        }
+
+        db.lrem(PROCESSING_QUEUE, 0, &job_id_bytes)?;
+        db.del(&claim_key)?;
+        db.zrem(RUNNING_JOBS, &job_id_bytes)?;
+        reaped += 1;
+
+        if has_finished(db, &job_id)? {
+            debug!("Job {} finished before its worker died", job_id);
+            continue;
+        }
+
+        match orchestrator.reclaim_job(&job_id, MAX_RECLAIMS) {
+            Ok(true) => {}
+            Ok(false) => {
+                // Lets workers waiting on this job as a dependency see it failed
+                db.set(&format!("job:{}:status", job_id), b"failed")?;
+                error!(
+                    "Job {} failed after losing its worker {} times",
+                    job_id,
+                    MAX_RECLAIMS + 1
+                );
+            }
+            Err(e) => warn!("Could not reclaim job {}: {}", job_id, e),
+        }
+    }
+
+    Ok(reaped)
+}
+
+/// Whether a worker has sent a heartbeat within its TTL
+fn is_worker_alive(db: &Database, worker_id: &str, now: u64) -> Result<bool> {
+    let expire_at = db
+        .hget(&format!("worker:{}", worker_id), "expire_at")?
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|s| s.parse::<u64>().ok());
+    Ok(expire_at.is_some_and(|expire_at| now < expire_at))
+}
+
+/// Whether a worker already posted a final result for a job
+fn has_finished(db: &Database, job_id: &str) -> Result<bool> {
+    let status = db.get(&format!("job:{}:status", job_id))?;
+    Ok(matches!(
+        status.as_deref(),
+        Some(b"completed" | b"failed" | b"timeout" | b"policy_violation")
+    ))
+}
+
 /// Process a single plan submission job
 ///
 /// Returns Ok(true) if a job was processed, Ok(false) if timeout (no jobs available)
@@ -263,4 +622,105 @@ mod tests {
         let processing_len = db.llen("agq:internal:plan.submit:processing").unwrap();
         assert_eq!(processing_len, 0);
     }
+
+    #[test]
+    fn test_reap_orphaned_jobs() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let jobs = ["job_dead", "job_alive"].map(|id| {
+            crate::job::Job::new(
+                id.to_string(),
+                "action".to_string(),
+                "plan".to_string(),
+                1,
+                "echo".to_string(),
+                Vec::new(),
+                serde_json::Value::Null,
+                vec!["cpu".to_string()],
+            )
+        });
+        orchestrator.submit_jobs(jobs.to_vec()).unwrap();
+        db.hset("worker:dead", "expire_at", b"100").unwrap();
+        db.hset("worker:alive", "expire_at", b"1000").unwrap();
+
+        let claim = |job_id: &str, worker_id: &str| {
+            db.lrem("queue:default", 0, job_id.as_bytes()).unwrap();
+            db.lpush(PROCESSING_QUEUE, job_id.as_bytes()).unwrap();
+            db.set(&format!("job:{}:worker", job_id), worker_id.as_bytes())
+                .unwrap();
+        };
+        claim("job_dead", "dead");
+        claim("job_alive", "alive");
+
+        assert_eq!(reap_orphaned_jobs(&db, 200).unwrap(), 1);
+        assert_eq!(db.lrange("queue:default", 0, -1).unwrap(), [b"job_dead"]);
+        assert_eq!(db.lrange(PROCESSING_QUEUE, 0, -1).unwrap(), [b"job_alive"]);
+
+        // Fails for good once the job has been reclaimed MAX_RECLAIMS times
+        for _ in 0..MAX_RECLAIMS {
+            claim("job_dead", "dead");
+            reap_orphaned_jobs(&db, 200).unwrap();
+        }
+        assert_eq!(db.llen("queue:default").unwrap(), 0);
+        assert_eq!(
+            db.get("job:job_dead:status").unwrap().as_deref(),
+            Some(&b"failed"[..])
+        );
+    }
+
+    #[test]
+    fn test_enforce_timeouts() {
+        let (db, _temp) = test_db();
+        let orchestrator = Orchestrator::new(&db);
+        let jobs = [("job_slow", Some(10)), ("job_unbounded", None)].map(|(id, timeout)| {
+            let mut job = crate::job::Job::new(
+                id.to_string(),
+                "action".to_string(),
+                "plan".to_string(),
+                1,
+                "sleep".to_string(),
+                Vec::new(),
+                serde_json::Value::Null,
+                vec!["cpu".to_string()],
+            );
+            job.timeout_secs = timeout;
+            job
+        });
+        orchestrator.submit_jobs(jobs.to_vec()).unwrap();
+        for job_id in ["job_slow", "job_unbounded"] {
+            db.lrem("queue:default", 0, job_id.as_bytes()).unwrap();
+            db.lpush(PROCESSING_QUEUE, job_id.as_bytes()).unwrap();
+            db.set(&format!("job:{}:worker", job_id), b"hung").unwrap();
+        }
+        let policy = TimeoutPolicy {
+            default_secs: None,
+            grace_secs: 5,
+        };
+
+        assert_eq!(enforce_timeouts(&db, &policy, 100).unwrap(), 0);
+        let job: crate::job::Job =
+            serde_json::from_slice(&db.get("job:job_slow").unwrap().unwrap()).unwrap();
+        assert_eq!(job.status, crate::job::JobStatus::Running);
+        assert_eq!(job.started_at, Some(100));
+        assert_eq!(job.worker_id.as_deref(), Some("hung"));
+
+        assert_eq!(enforce_timeouts(&db, &policy, 114).unwrap(), 0);
+        assert_eq!(enforce_timeouts(&db, &policy, 115).unwrap(), 1);
+        assert_eq!(
+            db.lrange(PROCESSING_QUEUE, 0, -1).unwrap(),
+            [b"job_unbounded"]
+        );
+        assert!(db.get("job:job_slow:worker").unwrap().is_none());
+        assert_eq!(
+            db.get("job:job_slow:status").unwrap().as_deref(),
+            Some(&b"timeout"[..])
+        );
+        let job: crate::job::Job =
+            serde_json::from_slice(&db.get("job:job_slow").unwrap().unwrap()).unwrap();
+        assert_eq!(job.status, crate::job::JobStatus::Timeout);
+
+        // Without a timeout a job runs until the default, if any
+        assert_eq!(enforce_timeouts(&db, &policy, 100_000).unwrap(), 0);
+        assert_eq!(db.zcard(RUNNING_JOBS).unwrap(), 1);
+    }
 }
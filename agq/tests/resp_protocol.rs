@@ -80,7 +80,7 @@ async fn setup_authenticated_connection() -> (TcpStream, tokio::task::JoinHandle
     let mut stream = loop {
         match TcpStream::connect(format!("127.0.0.1:{port}")).await {
             Ok(s) => break s,
-            Err(e) if retries > 0 => {
+            Err(_) if retries > 0 => {
                 retries -= 1;
                 tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
             }
@@ -2099,6 +2099,69 @@ async fn test_plan_submit_valid() {
     );
 }
 
+#[tokio::test]
+async fn test_plan_submit_idempotency_key_replays_plan_id() {
+    let (_handle, port) = start_test_server().await;
+    let mut stream = TcpStream::connect(format!("127.0.0.1:{port}"))
+        .await
+        .expect("Failed to connect");
+
+    let auth_cmd = b"*2\r\n$4\r\nAUTH\r\n$32\r\ntest_session_key_32_bytes_long!!\r\n";
+    send_resp_command(&mut stream, auth_cmd).await;
+
+    let plan_json = r#"{"plan_id":"plan_idem1","tasks":[{"task_number":1,"command":"echo"}]}"#;
+    let cmd = format!(
+        "*4\r\n$11\r\nPLAN.SUBMIT\r\n${}\r\n{}\r\n$15\r\nIDEMPOTENCY_KEY\r\n$7\r\nretry-1\r\n",
+        plan_json.len(),
+        plan_json
+    );
+
+    // A retried submission gets the same answer
+    let first = send_resp_command(&mut stream, cmd.as_bytes()).await;
+    let second = send_resp_command(&mut stream, cmd.as_bytes()).await;
+    assert_eq!(first, b"$10\r\nplan_idem1\r\n");
+    assert_eq!(second, first);
+}
+
+#[tokio::test]
+async fn test_plan_template_instantiate() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+    let command = |parts: &[&str]| {
+        let mut command = format!("*{}\r\n", parts.len());
+        for part in parts {
+            command.push_str(&format!("${}\r\n{}\r\n", part.len(), part));
+        }
+        command.into_bytes()
+    };
+
+    let template = r#"{"plan_description":"OCR ${FILE}","tasks":[{"task_number":1,"command":"agx-ocr","args":["${FILE}"]}]}"#;
+    let response = send_resp_command(
+        &mut stream,
+        &command(&["PLAN.TEMPLATE.SAVE", "ocr", template]),
+    )
+    .await;
+    assert!(
+        String::from_utf8_lossy(&response).contains(r#""params":["FILE"]"#),
+        "{}",
+        String::from_utf8_lossy(&response)
+    );
+
+    let params = r#"{"FILE":"scan.png"}"#;
+    let first =
+        send_resp_command(&mut stream, &command(&["PLAN.INSTANTIATE", "ocr", params])).await;
+    let second =
+        send_resp_command(&mut stream, &command(&["PLAN.INSTANTIATE", "ocr", params])).await;
+    assert!(
+        first.starts_with(b"$16\r\nocr-"),
+        "{}",
+        String::from_utf8_lossy(&first)
+    );
+    assert_eq!(second, first);
+
+    let response = send_resp_command(&mut stream, &command(&["PLAN.INSTANTIATE", "ocr"])).await;
+    assert!(String::from_utf8_lossy(&response).contains("requires parameters: FILE"));
+}
+
 #[tokio::test]
 async fn test_plan_submit_invalid_json() {
     let (_handle, port) = start_test_server().await;
@@ -2998,6 +3061,31 @@ async fn test_jobs_list_with_pagination() {
     assert!(response_str.starts_with("*0") || response_str == "*0\r\n");
 }
 
+#[tokio::test]
+async fn test_job_list_empty() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    let cmd = b"*3\r\n$8\r\nJOB.LIST\r\n$6\r\nstatus\r\n$6\r\nfailed\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.starts_with('$'));
+    assert!(response_str.contains(r#""jobs":[]"#));
+    assert!(response_str.contains(r#""total":0"#));
+}
+
+#[tokio::test]
+async fn test_job_list_rejects_unknown_filter() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    let cmd = b"*3\r\n$8\r\nJOB.LIST\r\n$5\r\nowner\r\n$3\r\nbob\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.starts_with('-'));
+    assert!(response_str.contains("Unknown JOB.LIST filter"));
+}
+
 /// Test WORKERS.LIST command
 #[tokio::test]
 async fn test_workers_list_requires_auth() {
@@ -3424,3 +3512,191 @@ async fn test_workers_list_sorted_by_last_seen() {
         "Workers should be sorted by last_seen (most recent first)"
     );
 }
+
+/// Test SUBSCRIBE command
+#[tokio::test]
+async fn test_subscribe_requires_auth() {
+    let (_handle, port) = start_test_server().await;
+    let mut stream = TcpStream::connect(format!("127.0.0.1:{port}"))
+        .await
+        .expect("Failed to connect");
+
+    let cmd = b"*2\r\n$9\r\nSUBSCRIBE\r\n$1\r\n*\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.contains("NOAUTH"));
+}
+
+#[tokio::test]
+async fn test_subscribe_confirms_channel() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    // Invalid plan IDs are rejected and the connection stays usable
+    let cmd = b"*2\r\n$9\r\nSUBSCRIBE\r\n$7\r\nbad id!\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+    assert!(response.starts_with(b"-"));
+
+    let cmd = b"*2\r\n$9\r\nSUBSCRIBE\r\n$10\r\nplan_watch\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+    assert_eq!(
+        response,
+        b"*3\r\n$9\r\nsubscribe\r\n$10\r\nplan_watch\r\n:1\r\n".to_vec()
+    );
+}
+
+#[tokio::test]
+async fn test_plan_submit_rejects_dependency_cycle() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    let plan_json = r#"{"plan_id":"plan_cycle","tasks":[{"task_number":1,"command":"sort","input_from_task":2},{"task_number":2,"command":"uniq","input_from_task":1}]}"#;
+    let submit_cmd = format!(
+        "*2\r\n$11\r\nPLAN.SUBMIT\r\n${}\r\n{}\r\n",
+        plan_json.len(),
+        plan_json
+    );
+    let response = send_resp_command(&mut stream, submit_cmd.as_bytes()).await;
+
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.starts_with("-ERR Plan validation failed"));
+    assert!(response_str.contains("\"path\":\"tasks[0].input_from_task\""));
+}
+
+#[tokio::test]
+async fn test_hello_inline_and_pipelined_commands() {
+    let (_handle, port) = start_test_server().await;
+    let mut stream = TcpStream::connect(format!("127.0.0.1:{port}"))
+        .await
+        .expect("Failed to connect");
+
+    let response = send_resp_command(&mut stream, b"HELLO 4\r\n").await;
+    assert!(response.starts_with(b"-NOPROTO"));
+    let response = send_resp_command(&mut stream, b"HELLO 3\r\n").await;
+    assert!(std::str::from_utf8(&response).unwrap().contains("NOAUTH"));
+
+    // Inline HELLO authenticates and switches to RESP3
+    let hello = b"HELLO 3 AUTH default test_session_key_32_bytes_long!!\r\n";
+    let response = send_resp_command(&mut stream, hello).await;
+    assert!(response.starts_with(b"%7\r\n$6\r\nserver\r\n$3\r\nagq\r\n"));
+
+    // Pipelined commands, split mid-message across writes, get their
+    // replies in order
+    stream
+        .write_all(b"*3\r\n$3\r\nSET\r\n$7\r\npipekey\r\n$1\r\nv\r\n*2\r\n$3\r\nGET\r\n$7\r")
+        .await
+        .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    stream
+        .write_all(b"\npipekey\r\n*2\r\n$3\r\nGET\r\n$7\r\nmissing\r\n")
+        .await
+        .unwrap();
+
+    let expected = b"+OK\r\n$1\r\nv\r\n_\r\n";
+    let mut response = Vec::new();
+    while response.len() < expected.len() {
+        let mut chunk = [0u8; 256];
+        let n = stream.read(&mut chunk).await.expect("Failed to read");
+        assert!(n > 0, "connection closed");
+        response.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(response, expected);
+
+    // Invalid data gets an error before the connection is closed
+    let response = send_resp_command(&mut stream, b"*1\r\n?\r\n").await;
+    assert!(response.starts_with(b"-ERR Protocol error"));
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown() {
+    use agq::{Database, Server};
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open(temp_dir.path().join("test.redb")).unwrap();
+    let server = Server::new("127.0.0.1:0", TEST_SESSION_KEY.to_vec(), db)
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let handle = tokio::spawn(server.run_until(async {
+        let _ = stopped.await;
+    }));
+
+    let auth = b"AUTH test_session_key_32_bytes_long!!\r\n";
+    let mut idle = TcpStream::connect(addr).await.unwrap();
+    assert_eq!(send_resp_command(&mut idle, auth).await, b"+OK\r\n");
+    let mut busy = TcpStream::connect(addr).await.unwrap();
+    assert_eq!(send_resp_command(&mut busy, auth).await, b"+OK\r\n");
+
+    // A blocking pop is in flight when the shutdown starts, with a
+    // submission pipelined behind it
+    busy.write_all(b"BRPOP queue:empty 1\r\nPLAN.SUBMIT {}\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    stop.send(()).unwrap();
+
+    // The idle connection is closed at once
+    let mut chunk = [0u8; 256];
+    let n = tokio::time::timeout(
+        tokio::time::Duration::from_millis(500),
+        idle.read(&mut chunk),
+    )
+    .await
+    .expect("idle connection was not closed")
+    .unwrap();
+    assert_eq!(n, 0);
+
+    // The busy one gets its replies, the submission refused, then is closed
+    let mut response = Vec::new();
+    loop {
+        let n = busy.read(&mut chunk).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(response, b"$-1\r\n-SHUTDOWN AGQ is shutting down\r\n");
+
+    handle.await.unwrap().unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn test_audit_query_records_writes() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    let cmd = b"*3\r\n$3\r\nSET\r\n$13\r\njob:j1:status\r\n$7\r\nrunning\r\n";
+    send_resp_command(&mut stream, cmd).await;
+    let cmd = b"*3\r\n$11\r\nAUDIT.QUERY\r\n$6\r\njob_id\r\n$2\r\nj1\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.starts_with('$'));
+    assert!(response_str.contains(r#""command":"SET""#));
+    assert!(response_str.contains(r#""after":"running""#));
+    assert!(response_str.contains(r#""next":null"#));
+
+    let cmd = b"*2\r\n$3\r\nDEL\r\n$9\r\naudit:log\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.contains("The audit log is read-only"));
+}
+
+#[tokio::test]
+async fn test_tools_catalog_lists_registered_cards() {
+    let (mut stream, _handle) = setup_authenticated_connection().await;
+
+    let cmd = b"*2\r\n$4\r\nPING\r\n$12\r\nworker_test1\r\n";
+    send_resp_command(&mut stream, cmd).await;
+    let cmd = b"*3\r\n$14\r\nTOOLS.REGISTER\r\n$12\r\nworker_test1\r\n$79\r\n{\"name\":\"agx-ocr\",\"version\":\"0.1.0\",\"description\":\"OCR\",\"capabilities\":[\"ocr\"]}\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+    assert_eq!(response, b"$7\r\nagx-ocr\r\n");
+
+    let cmd = b"*1\r\n$13\r\nTOOLS.CATALOG\r\n";
+    let response = send_resp_command(&mut stream, cmd).await;
+    let response_str = std::str::from_utf8(&response).unwrap();
+    assert!(response_str.contains(r#""name":"agx-ocr""#));
+    assert!(response_str.contains(r#""capabilities":["ocr"]"#));
+    assert!(response_str.contains(r#""workers":["worker_test1"]"#));
+}
@@ -0,0 +1,221 @@
+use crate::config::Config;
+use crate::error::{AgwError, AgwResult};
+use crate::plan::Job;
+use crate::result::JobResult;
+use crate::sandbox::{Sandbox, SandboxConfig, SandboxFactory};
+use crate::worker::{JobHooks, Worker};
+use std::sync::Arc;
+
+/// Builder for embedding a worker in another Rust program
+///
+/// Settings not given to the builder are read the same way the `agw` binary
+/// reads them: from environment variables and the config file, falling back
+/// to built-in defaults. Start from [`WorkerBuilder::with_config`] to supply
+/// every setting explicitly instead.
+///
+/// ```no_run
+/// # async fn run() -> agw::error::AgwResult<()> {
+/// let worker = agw::WorkerBuilder::new()
+///     .agq_address("agq.internal:6379")
+///     .session_key("secret")
+///     .tags(["gpu"])
+///     .pre_job(|job| {
+///         if job.command == "rm" {
+///             return Err("not on this worker".to_string());
+///         }
+///         Ok(())
+///     })
+///     .post_job(|result| println!("{} finished: {}", result.job_id, result.status))
+///     .build()
+///     .await?;
+/// worker.run().await
+/// # }
+/// ```
+#[derive(Default)]
+pub struct WorkerBuilder {
+    config: Option<Config>,
+    agq_address: Option<String>,
+    session_key: Option<String>,
+    worker_id: Option<String>,
+    name: Option<String>,
+    tools: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    hooks: JobHooks,
+    sandbox: Option<SandboxFactory>,
+}
+
+impl WorkerBuilder {
+    /// Start from the settings the `agw` binary would use
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from an explicit configuration
+    #[must_use]
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config: Some(config),
+            ..Self::default()
+        }
+    }
+
+    /// AGQ server address (host:port)
+    #[must_use]
+    pub fn agq_address(mut self, address: impl Into<String>) -> Self {
+        self.agq_address = Some(address.into());
+        self
+    }
+
+    /// Session key used to authenticate with AGQ
+    #[must_use]
+    pub fn session_key(mut self, key: impl Into<String>) -> Self {
+        self.session_key = Some(key.into());
+        self
+    }
+
+    /// Fixed worker ID, needed to recover jobs after a restart
+    #[must_use]
+    pub fn worker_id(mut self, id: impl Into<String>) -> Self {
+        self.worker_id = Some(id.into());
+        self
+    }
+
+    /// Human-readable worker name
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Tools registered with AGQ as available on this worker
+    #[must_use]
+    pub fn tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Capability tags registered with AGQ for task routing
+    #[must_use]
+    pub fn tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Run a check before each job; returning an error rejects the job
+    ///
+    /// The job has its variables substituted and has passed the command
+    /// policy. Rejected jobs are reported to AGQ as failed with the reason.
+    #[must_use]
+    pub fn pre_job<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&Job) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.hooks.pre_job = Some(Arc::new(hook));
+        self
+    }
+
+    /// Observe every result the worker reports, including rejected jobs
+    #[must_use]
+    pub fn post_job<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&JobResult) + Send + Sync + 'static,
+    {
+        self.hooks.post_job = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run jobs in a custom sandbox instead of the configured backend
+    ///
+    /// The function is called once per job with its sandbox settings,
+    /// including resource limits, working directory, and environment.
+    #[must_use]
+    pub fn sandbox<F>(mut self, build: F) -> Self
+    where
+        F: Fn(&SandboxConfig) -> Box<dyn Sandbox> + Send + Sync + 'static,
+    {
+        self.sandbox = Some(SandboxFactory::new(build));
+        self
+    }
+
+    /// Resolve the worker's configuration without connecting to AGQ
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no configuration was given and the config file or
+    /// session key file cannot be read
+    pub fn config(&self) -> AgwResult<Config> {
+        let mut config = match &self.config {
+            Some(config) => config.clone(),
+            None => {
+                Config::load_from(["agw"]).map_err(|e| AgwError::InvalidConfig(e.to_string()))?
+            }
+        };
+
+        if let Some(address) = &self.agq_address {
+            config.agq_address.clone_from(address);
+        }
+        if let Some(key) = &self.session_key {
+            config.session_key = Some(key.clone());
+        }
+        if let Some(id) = &self.worker_id {
+            config.worker_id = Some(id.clone());
+        }
+        if let Some(name) = &self.name {
+            config.name = Some(name.clone());
+        }
+        if let Some(tools) = &self.tools {
+            config.tools = Some(tools.clone());
+        }
+        if let Some(tags) = &self.tags {
+            config.tags = Some(tags.clone());
+        }
+
+        Ok(config)
+    }
+
+    /// Validate the configuration, connect to AGQ, and register the worker
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration is invalid, or connecting or
+    /// authenticating to AGQ fails
+    pub async fn build(self) -> AgwResult<Worker> {
+        let config = self.config()?;
+        Worker::with_extensions(config, self.hooks, self.sandbox).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_overrides_config() {
+        let base = Config::load_from(["agw", "--tags", "cpu"]).unwrap();
+        let config = WorkerBuilder::with_config(base)
+            .agq_address("agq.internal:6380")
+            .session_key("key")
+            .worker_id("embedded-1")
+            .tags(["gpu", "high-memory"])
+            .config()
+            .unwrap();
+
+        assert_eq!(config.agq_address, "agq.internal:6380");
+        assert_eq!(config.session_key.as_deref(), Some("key"));
+        assert_eq!(config.worker_id.as_deref(), Some("embedded-1"));
+        assert_eq!(
+            config.tags,
+            Some(vec!["gpu".to_string(), "high-memory".to_string()])
+        );
+        assert_eq!(config.tools, None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Per-job cgroups enforcing process limits
+//!
+//! `RLIMIT_NPROC` counts every process of the worker's user rather than the
+//! job's, and root ignores it, so the native sandboxes cap a job's processes
+//! with the cgroup v2 `pids` controller instead. Each run gets a cgroup under
+//! the worker's own, which the job enters before `exec`, so everything it
+//! forks is counted.
+
+use nix::libc;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tracing::debug;
+
+/// Mount point of the cgroup v2 hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Leaf cgroup the worker moves into when its own cgroup must hand the
+/// `pids` controller to job cgroups
+const WORKER_CGROUP: &str = "agw-worker";
+
+/// Cgroup of a single job run, killed and removed when dropped
+#[derive(Debug)]
+pub struct JobCgroup {
+    path: PathBuf,
+    /// `cgroup.procs`, opened before the fork so entering is a single write
+    procs: File,
+}
+
+impl JobCgroup {
+    /// Create a cgroup allowing at most `max_processes` processes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if cgroup v2 or its `pids` controller is not
+    /// available to the worker, or the cgroup cannot be set up
+    pub fn create(max_processes: u64) -> io::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let path = parent()?.join(format!(
+            "agw-job-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir(&path)?;
+
+        let setup = fs::write(path.join("pids.max"), max_processes.to_string()).and_then(|()| {
+            OpenOptions::new()
+                .write(true)
+                .open(path.join("cgroup.procs"))
+        });
+        match setup {
+            Ok(procs) => Ok(Self { path, procs }),
+            Err(e) => {
+                let _ = fs::remove_dir(&path);
+                Err(e)
+            }
+        }
+    }
+
+    /// Move the calling process into the cgroup; called in the child before
+    /// `exec`, and only makes a `write` syscall
+    ///
+    /// # Errors
+    ///
+    /// Returns the OS error of the write
+    pub fn enter(&self) -> io::Result<()> {
+        // Writing 0 moves the writing process
+        (&self.procs).write_all(b"0")
+    }
+}
+
+impl Drop for JobCgroup {
+    fn drop(&mut self) {
+        // Take down anything the job left behind; `cgroup.kill` needs Linux 5.14
+        let _ = fs::write(self.path.join("cgroup.kill"), "1");
+
+        // Killed processes leave the cgroup asynchronously
+        for _ in 0..50 {
+            match fs::remove_dir(&self.path) {
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    std::thread::sleep(std::time::Duration::from_millis(2));
+                }
+                result => {
+                    if let Err(e) = result {
+                        debug!("Failed to remove cgroup {}: {e}", self.path.display());
+                    }
+                    return;
+                }
+            }
+        }
+        debug!("Cgroup {} is still busy, leaving it", self.path.display());
+    }
+}
+
+/// Whether per-job cgroups can be created on this host
+#[must_use]
+pub fn available() -> bool {
+    parent().is_ok()
+}
+
+/// Cgroup job cgroups are created in, set up on first use
+fn parent() -> io::Result<&'static Path> {
+    static PARENT: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+    PARENT
+        .get_or_init(|| delegate().map_err(|e| e.to_string()))
+        .as_deref()
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// Find the worker's cgroup and enable the `pids` controller for its children
+///
+/// A cgroup other than the root cannot hand controllers to children while
+/// it has processes of its own, so the worker first moves into a leaf child.
+fn delegate() -> io::Result<PathBuf> {
+    let own = fs::read_to_string("/proc/self/cgroup")?
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+        .ok_or_else(|| io::Error::other("the worker is not in a cgroup v2 hierarchy"))?;
+
+    let controllers = fs::read_to_string(own.join("cgroup.controllers"))
+        .map_err(|_| io::Error::other(format!("cgroup v2 is not mounted at {CGROUP_ROOT}")))?;
+    if !has_controller(&controllers, "pids") {
+        return Err(io::Error::other(format!(
+            "the pids controller is not available in cgroup {}",
+            own.display()
+        )));
+    }
+
+    let subtree_control = own.join("cgroup.subtree_control");
+    if has_controller(&fs::read_to_string(&subtree_control)?, "pids") {
+        return Ok(own);
+    }
+    if let Err(e) = fs::write(&subtree_control, "+pids") {
+        if e.raw_os_error() != Some(libc::EBUSY) {
+            return Err(e);
+        }
+        let leaf = own.join(WORKER_CGROUP);
+        if let Err(e) = fs::create_dir(&leaf) {
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+        }
+        fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())?;
+        fs::write(&subtree_control, "+pids")?;
+        debug!("Moved the worker to cgroup {}", leaf.display());
+    }
+
+    Ok(own)
+}
+
+/// Whether a space-separated controller list such as `cgroup.controllers`
+/// names `controller`
+fn has_controller(list: &str, controller: &str) -> bool {
+    list.split_whitespace().any(|name| name == controller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_controller() {
+        assert!(has_controller("cpu io memory pids\n", "pids"));
+        assert!(!has_controller("cpu io memory\n", "pids"));
+        assert!(!has_controller("", "pids"));
+    }
+}
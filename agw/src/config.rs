@@ -1,6 +1,22 @@
-use clap::Parser;
+use crate::output::DEFAULT_MAX_OUTPUT_BYTES;
+use crate::policy::CommandPolicy;
+use crate::recovery::RecoveryMode;
+use crate::sandbox::{
+    validate_limits, ContainerSettings, ResourceLimits, SandboxConfig, SandboxKind,
+    DEFAULT_CONTAINER_IMAGE,
+};
+use crate::secrets::is_valid_env_name;
+use crate::workspace::DEFAULT_MAX_ARTIFACT_BYTES;
+use anyhow::Context;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+/// Config file loaded when `--config` is not given, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/agw/config.toml";
+
 /// AGW - Agentic Worker for the AGX ecosystem
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -14,9 +30,18 @@ pub struct Config {
     )]
     pub agq_address: String,
 
+    /// Path to a TOML config file (default: /etc/agw/config.toml if present)
+    /// File values apply unless overridden by CLI flags or env vars
+    #[arg(short = 'c', long, env = "AGW_CONFIG")]
+    pub config: Option<PathBuf>,
+
     /// Session key for authentication
     #[arg(short = 'k', long, env = "AGQ_SESSION_KEY")]
-    pub session_key: String,
+    pub session_key: Option<String>,
+
+    /// File containing the session key (used if no session key is given)
+    #[arg(long, env = "AGQ_SESSION_KEY_FILE")]
+    pub session_key_file: Option<PathBuf>,
 
     /// Worker ID (generated if not provided)
     #[arg(short = 'w', long, env = "WORKER_ID")]
@@ -39,18 +64,234 @@ pub struct Config {
     #[arg(long, env = "WORKER_TOOLS", value_delimiter = ',')]
     pub tools: Option<Vec<String>>,
 
+    /// Seconds between health checks of the tools; broken tools are reported
+    /// in heartbeats and de-registered until they pass again. 0 disables them
+    #[arg(long, env = "AGW_TOOL_HEALTH_INTERVAL", default_value = "300")]
+    pub tool_health_interval: u64,
+
     /// Comma-separated list of worker capabilities/tags (e.g., "gpu,high-memory")
     /// Used for task routing
     #[arg(long, env = "WORKER_TAGS", value_delimiter = ',')]
     pub tags: Option<Vec<String>>,
 
+    /// Detect NVIDIA and Apple GPUs at startup and register `gpu`,
+    /// `gpu:<model>`, and `vram:<GB>` tags alongside the configured ones
+    #[arg(
+        long,
+        env = "AGW_DETECT_GPUS",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub detect_gpus: bool,
+
     /// Shutdown timeout in seconds (maximum wait for job completion during shutdown)
     /// If not specified, waits indefinitely for job completion
     #[arg(long, env = "SHUTDOWN_TIMEOUT")]
     pub shutdown_timeout: Option<u64>,
+
+    /// Fail jobs whose `${...}` argument variables cannot be resolved
+    /// When disabled, unresolved variables expand to empty strings
+    #[arg(
+        long,
+        env = "AGW_STRICT_VARIABLES",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub strict_variables: bool,
+
+    /// Maximum consecutive reconnection attempts after losing the AGQ connection
+    /// Set to 0 to retry indefinitely
+    #[arg(long, env = "AGW_MAX_RECONNECT_ATTEMPTS", default_value = "10")]
+    pub max_reconnect_attempts: u32,
+
+    /// Maximum job execution time in seconds
+    /// Longer job timeouts are clamped to this, and jobs without a timeout
+    /// get it as their timeout. If not set, jobs without a timeout run unbounded
+    #[arg(long, env = "AGW_MAX_JOB_TIMEOUT")]
+    pub max_job_timeout: Option<u32>,
+
+    /// Default per-job memory limit in megabytes (Linux only)
+    /// Jobs may request a lower limit but never a higher one
+    #[arg(long, env = "AGW_JOB_MEMORY_MB")]
+    pub job_memory_mb: Option<u64>,
+
+    /// Default per-job CPU time limit in seconds (Linux only)
+    #[arg(long, env = "AGW_JOB_CPU_SECS")]
+    pub job_cpu_secs: Option<u64>,
+
+    /// Default per-job process limit (cgroup v2 on Linux, or the container sandbox)
+    #[arg(long, env = "AGW_JOB_MAX_PROCESSES")]
+    pub job_max_processes: Option<u64>,
+
+    /// Sandbox backend used to run jobs
+    #[arg(long, env = "AGW_SANDBOX", value_enum, default_value_t = SandboxKind::Native)]
+    pub sandbox: SandboxKind,
+
+    /// Block kernel administration syscalls (mount, ptrace, bpf, ...) in jobs
+    /// Only applies to the namespace sandbox
+    #[arg(
+        long,
+        env = "AGW_SANDBOX_SECCOMP",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub sandbox_seccomp: bool,
+
+    /// Container runtime binary for the container sandbox (docker or podman)
+    #[arg(long, env = "AGW_CONTAINER_RUNTIME", default_value = "docker")]
+    pub container_runtime: String,
+
+    /// Image jobs run in when using the container sandbox
+    #[arg(long, env = "AGW_CONTAINER_IMAGE", default_value = DEFAULT_CONTAINER_IMAGE)]
+    pub container_image: String,
+
+    /// Comma-separated bind mounts for the container sandbox
+    /// (e.g., "/srv/data:/data:ro")
+    #[arg(long, env = "AGW_CONTAINER_MOUNTS", value_delimiter = ',')]
+    pub container_mounts: Vec<String>,
+
+    /// Stream job stdout/stderr to AGQ while the job runs
+    #[arg(
+        long,
+        env = "AGW_STREAM_LOGS",
+        default_value_t = true,
+        action = clap::ArgAction::Set
+    )]
+    pub stream_logs: bool,
+
+    /// Maximum bytes of stdout and of stderr kept per job
+    /// Output beyond this is dropped and replaced with a truncation marker
+    #[arg(long, env = "AGW_MAX_OUTPUT_BYTES", default_value_t = DEFAULT_MAX_OUTPUT_BYTES)]
+    pub max_output_bytes: usize,
+
+    /// Directory under which per-job workspaces are created
+    /// Defaults to the system temporary directory
+    #[arg(long, env = "AGW_WORKSPACE_ROOT")]
+    pub workspace_root: Option<PathBuf>,
+
+    /// Upload files left in the job workspace to AGQ as artifacts
+    #[arg(
+        long,
+        env = "AGW_UPLOAD_ARTIFACTS",
+        default_value_t = false,
+        action = clap::ArgAction::Set
+    )]
+    pub upload_artifacts: bool,
+
+    /// Maximum total bytes of artifacts uploaded per job
+    #[arg(long, env = "AGW_MAX_ARTIFACT_BYTES", default_value_t = DEFAULT_MAX_ARTIFACT_BYTES)]
+    pub max_artifact_bytes: u64,
+
+    /// Address to serve Prometheus metrics on (e.g. "0.0.0.0:9100")
+    /// The endpoint is disabled if not set
+    #[arg(long, env = "AGW_METRICS_ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Unix socket accepting `drain`, `pause`, and `resume` commands
+    /// The control socket is disabled if not set
+    #[arg(long, env = "AGW_CONTROL_SOCKET")]
+    pub control_socket: Option<PathBuf>,
+
+    /// Commands jobs may run (comma-separated); all others are rejected
+    /// Every command is allowed if not set
+    #[arg(long, env = "AGW_ALLOWED_COMMANDS", value_delimiter = ',')]
+    pub allowed_commands: Vec<String>,
+
+    /// Commands jobs may never run (comma-separated)
+    #[arg(long, env = "AGW_DENIED_COMMANDS", value_delimiter = ',')]
+    pub denied_commands: Vec<String>,
+
+    /// Regex matched against each job's full command line; matching jobs
+    /// are rejected. Repeat the flag or use a config file array for several
+    #[arg(long = "denied-command-pattern", env = "AGW_DENIED_COMMAND_PATTERN")]
+    pub denied_command_patterns: Vec<String>,
+
+    /// Environment variables set for every job, as KEY=VALUE (comma-separated)
+    /// Values may reference secrets as `secret://<name>`
+    #[arg(long, env = "AGW_JOB_ENV", value_delimiter = ',')]
+    pub job_env: Vec<String>,
+
+    /// TOML file of `name = "value"` secrets that job environment values can
+    /// reference as `secret://<name>`
+    #[arg(long, env = "AGW_SECRETS_FILE")]
+    pub secrets_file: Option<PathBuf>,
+
+    /// On startup, requeue or resume jobs this worker claimed before a crash
+    /// Needs a fixed worker ID to find them
+    #[arg(long, env = "AGW_RECOVER_JOBS", value_enum, default_value_t = RecoveryMode::Requeue)]
+    pub recover_jobs: RecoveryMode,
 }
 
 impl Config {
+    /// Load configuration from CLI flags, env vars, and the config file
+    ///
+    /// Exits the process on `--help`, `--version`, or invalid arguments,
+    /// like `Config::parse()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config file or session key file cannot be read
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(std::env::args_os()).map_err(|e| match e.downcast::<clap::Error>() {
+            Ok(clap_err) => clap_err.exit(),
+            Err(e) => e,
+        })
+    }
+
+    /// Load configuration from the given arguments
+    ///
+    /// Precedence, highest first: CLI flags, environment variables, config
+    /// file, built-in defaults. Config file values are installed as argument
+    /// defaults, so clap applies the rest of the precedence as usual.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if arguments are invalid, or the config file or
+    /// session key file cannot be read or parsed
+    pub fn load_from<I, T>(args: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+        // First pass only locates the config file
+        let initial = Self::try_parse_from(&args)?;
+        let path = initial.config.or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG_PATH);
+            default.exists().then_some(default)
+        });
+
+        let mut command = Self::command();
+        if let Some(path) = path {
+            for (id, value) in read_config_file(&path)? {
+                command = command.mut_arg(id, |arg| arg.default_value(value).required(false));
+            }
+        }
+
+        let matches = command.try_get_matches_from(&args)?;
+        let mut config = Self::from_arg_matches(&matches)?;
+
+        if config.session_key.is_none() {
+            if let Some(ref key_file) = config.session_key_file {
+                let key = std::fs::read_to_string(key_file).with_context(|| {
+                    format!("Failed to read session key file {}", key_file.display())
+                })?;
+                config.session_key = Some(key.trim().to_string());
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Get the session key
+    ///
+    /// Empty if none was configured; `validate()` rejects that case.
+    #[must_use]
+    pub fn session_key(&self) -> &str {
+        self.session_key.as_deref().unwrap_or_default()
+    }
+
     /// Validate configuration
     ///
     /// # Errors
@@ -63,7 +304,12 @@ impl Config {
         }
 
         // Validate session key
-        validate_session_key(&self.session_key)?;
+        if self.session_key.is_none() {
+            anyhow::bail!(
+                "Session key is required (--session-key, AGQ_SESSION_KEY, or session_key_file)"
+            );
+        }
+        validate_session_key(self.session_key())?;
 
         // Validate worker ID if provided
         if let Some(ref id) = self.worker_id {
@@ -91,22 +337,138 @@ impl Config {
             anyhow::bail!("Connection timeout must be greater than 0");
         }
 
+        if self.max_job_timeout == Some(0) {
+            anyhow::bail!("Max job timeout must be greater than 0");
+        }
+
+        if self.max_output_bytes == 0 {
+            anyhow::bail!("Max output bytes must be greater than 0");
+        }
+
+        if let Some(ref root) = self.workspace_root {
+            if !root.is_absolute() {
+                anyhow::bail!("Workspace root must be an absolute path");
+            }
+        }
+
+        // Validate default resource limits
+        validate_limits(&self.job_limits())?;
+
+        // Only Linux cgroups and containers can count a job's processes
+        if self.job_max_processes.is_some()
+            && self.sandbox == SandboxKind::Native
+            && !cfg!(target_os = "linux")
+        {
+            anyhow::bail!(
+                "The job process limit is only enforced by the container sandbox on this platform"
+            );
+        }
+
+        if self.sandbox == SandboxKind::Namespace && !cfg!(target_os = "linux") {
+            anyhow::bail!("The namespace sandbox is only supported on Linux");
+        }
+
+        if self.sandbox_seccomp && self.sandbox != SandboxKind::Namespace {
+            anyhow::bail!("Seccomp filtering requires the namespace sandbox");
+        }
+
+        // Validate container sandbox settings
+        if self.sandbox == SandboxKind::Container {
+            validate_container_runtime(&self.container_runtime)?;
+            validate_container_image(&self.container_image)?;
+            for mount in &self.container_mounts {
+                validate_container_mount(mount)?;
+            }
+        }
+
+        // Validate command policy patterns
+        self.command_policy()?;
+
+        for entry in &self.job_env {
+            validate_job_env_entry(entry)?;
+        }
+
         Ok(())
     }
 
+    /// Get the policy deciding which commands jobs may run
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a denied command pattern is not a valid regex
+    pub fn command_policy(&self) -> anyhow::Result<CommandPolicy> {
+        CommandPolicy::new(
+            &self.allowed_commands,
+            &self.denied_commands,
+            &self.denied_command_patterns,
+        )
+        .context("Invalid denied command pattern")
+    }
+
     /// Get heartbeat interval as Duration
     #[must_use]
     pub fn heartbeat_duration(&self) -> Duration {
         Duration::from_secs(self.heartbeat_interval)
     }
 
+    /// Get tool health check interval as Duration (if enabled)
+    #[must_use]
+    pub fn tool_health_duration(&self) -> Option<Duration> {
+        (self.tool_health_interval > 0).then(|| Duration::from_secs(self.tool_health_interval))
+    }
+
     /// Get connection timeout as Duration
     #[must_use]
-    #[allow(dead_code)]
     pub fn connection_timeout_duration(&self) -> Duration {
         Duration::from_secs(self.connection_timeout)
     }
 
+    /// Get the timeout to enforce for a job requesting `requested` seconds
+    #[must_use]
+    pub fn job_timeout(&self, requested: Option<u32>) -> Option<u32> {
+        match (requested, self.max_job_timeout) {
+            (Some(requested), Some(max)) => Some(requested.min(max)),
+            (requested, max) => requested.or(max),
+        }
+    }
+
+    /// Get the default resource limits applied to every job
+    #[must_use]
+    pub fn job_limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            memory_mb: self.job_memory_mb,
+            cpu_secs: self.job_cpu_secs,
+            max_processes: self.job_max_processes,
+        }
+    }
+
+    /// Get the sandbox configuration for a job (before per-job overrides)
+    #[must_use]
+    pub fn sandbox_config(&self) -> SandboxConfig {
+        SandboxConfig {
+            kind: self.sandbox,
+            container: ContainerSettings {
+                runtime: self.container_runtime.clone(),
+                image: self.container_image.clone(),
+                mounts: self.container_mounts.clone(),
+            },
+            limits: self.job_limits(),
+            workdir: None,
+            env: Vec::new(),
+            seccomp: self.sandbox_seccomp,
+            job_id: None,
+            factory: None,
+        }
+    }
+
+    /// Get the directory under which job workspaces are created
+    #[must_use]
+    pub fn workspace_root(&self) -> PathBuf {
+        self.workspace_root
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+    }
+
     /// Get shutdown timeout as Duration (if configured)
     #[must_use]
     pub fn shutdown_timeout_duration(&self) -> Option<Duration> {
@@ -114,6 +476,77 @@ impl Config {
     }
 }
 
+/// Read a TOML config file into `(argument id, value)` pairs
+///
+/// Keys are `Config` field names (e.g. `agq_address`). Arrays become
+/// comma-separated lists, matching the env var format.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed, or contains an
+/// unknown key or unsupported value
+fn read_config_file(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+    parse_config_file(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// Parse config file contents into `(argument id, value)` pairs
+fn parse_config_file(contents: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let document: toml_edit::DocumentMut = contents.parse()?;
+    let command = Config::command();
+
+    let mut values = Vec::new();
+    for (key, item) in document.iter() {
+        let known = command
+            .get_arguments()
+            .any(|arg| arg.get_id() == key && arg.get_long().is_some());
+        if !known || key == "config" {
+            anyhow::bail!("Unknown config key '{key}'");
+        }
+
+        let value = match item.as_value() {
+            Some(toml_edit::Value::Array(array)) => array
+                .iter()
+                .map(|v| config_value_to_string(key, v))
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .join(","),
+            Some(value) => config_value_to_string(key, value)?,
+            None => anyhow::bail!("Config key '{key}' must be a value, not a table"),
+        };
+
+        values.push((key.to_string(), value));
+    }
+
+    Ok(values)
+}
+
+/// Convert a scalar TOML value to its command-line form
+fn config_value_to_string(key: &str, value: &toml_edit::Value) -> anyhow::Result<String> {
+    match value {
+        toml_edit::Value::String(s) => Ok(s.value().clone()),
+        toml_edit::Value::Integer(i) => Ok(i.value().to_string()),
+        toml_edit::Value::Boolean(b) => Ok(b.value().to_string()),
+        _ => anyhow::bail!("Unsupported value type for config key '{key}'"),
+    }
+}
+
+/// Validate a `KEY=VALUE` job environment entry
+///
+/// # Errors
+///
+/// Returns an error if the entry has no `=` or the key is not a valid
+/// environment variable name
+pub fn validate_job_env_entry(entry: &str) -> anyhow::Result<()> {
+    let Some((key, _)) = entry.split_once('=') else {
+        anyhow::bail!("Job environment entry '{entry}' must be in format KEY=VALUE");
+    };
+    if !is_valid_env_name(key) {
+        anyhow::bail!("Invalid job environment variable name '{key}'");
+    }
+    Ok(())
+}
+
 /// Validate session key format
 ///
 /// # Errors
@@ -250,6 +683,87 @@ pub fn validate_worker_tag(tag: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Validate container runtime binary
+///
+/// Only Docker and Podman (by name or absolute path) are supported.
+///
+/// # Errors
+///
+/// Returns an error if the runtime is not docker or podman
+pub fn validate_container_runtime(runtime: &str) -> anyhow::Result<()> {
+    let name = std::path::Path::new(runtime)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+
+    if !matches!(name, "docker" | "podman") {
+        anyhow::bail!("Container runtime must be docker or podman");
+    }
+
+    if runtime.contains('/') && !runtime.starts_with('/') {
+        anyhow::bail!("Container runtime path must be absolute");
+    }
+
+    Ok(())
+}
+
+/// Validate container image reference
+///
+/// # Errors
+///
+/// Returns an error if the image reference is empty, too long, or could be
+/// interpreted as a runtime option
+pub fn validate_container_image(image: &str) -> anyhow::Result<()> {
+    if image.is_empty() {
+        anyhow::bail!("Container image cannot be empty");
+    }
+
+    if image.len() > 255 {
+        anyhow::bail!("Container image cannot exceed 255 characters");
+    }
+
+    // A leading hyphen would be parsed as a runtime flag
+    if image.starts_with('-') {
+        anyhow::bail!("Container image cannot start with a hyphen");
+    }
+
+    if !image
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '/' | ':' | '@'))
+    {
+        anyhow::bail!("Container image contains invalid characters");
+    }
+
+    Ok(())
+}
+
+/// Validate container bind mount in `host:container[:ro|rw]` form
+///
+/// # Errors
+///
+/// Returns an error if either path is not absolute, contains traversal, or the
+/// mode is not `ro` or `rw`
+pub fn validate_container_mount(mount: &str) -> anyhow::Result<()> {
+    let parts: Vec<&str> = mount.split(':').collect();
+
+    let (host, container) = match parts.as_slice() {
+        [host, container] | [host, container, "ro" | "rw"] => (*host, *container),
+        _ => anyhow::bail!("Container mount must be in format host:container[:ro|rw]"),
+    };
+
+    for path in [host, container] {
+        if !path.starts_with('/') {
+            anyhow::bail!("Container mount paths must be absolute");
+        }
+
+        if path.contains("..") || path.chars().any(char::is_control) {
+            anyhow::bail!("Container mount path contains invalid characters");
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -356,4 +870,191 @@ mod tests {
         assert!(validate_worker_name("worker|cat").is_err());
         assert!(validate_worker_name("worker&whoami").is_err());
     }
+
+    #[test]
+    fn test_validate_container_runtime() {
+        assert!(validate_container_runtime("docker").is_ok());
+        assert!(validate_container_runtime("podman").is_ok());
+        assert!(validate_container_runtime("/usr/bin/podman").is_ok());
+        assert!(validate_container_runtime("sh").is_err());
+        assert!(validate_container_runtime("bin/docker").is_err());
+        assert!(validate_container_runtime("").is_err());
+    }
+
+    #[test]
+    fn test_validate_container_image() {
+        assert!(validate_container_image("debian:stable-slim").is_ok());
+        assert!(validate_container_image("ghcr.io/agenix-sh/tools@sha256:abc123").is_ok());
+        assert!(validate_container_image("").is_err());
+        assert!(validate_container_image("--privileged").is_err());
+        assert!(validate_container_image("alpine; rm -rf /").is_err());
+    }
+
+    #[test]
+    fn test_validate_container_mount() {
+        assert!(validate_container_mount("/srv/data:/data").is_ok());
+        assert!(validate_container_mount("/srv/data:/data:ro").is_ok());
+        assert!(validate_container_mount("data:/data").is_err());
+        assert!(validate_container_mount("/srv/data:/data:z").is_err());
+        assert!(validate_container_mount("/srv/../etc:/data").is_err());
+        assert!(validate_container_mount("/srv/data").is_err());
+    }
+
+    fn write_temp_file(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "agw-config-test-{}.toml",
+            uuid::Uuid::new_v4().simple()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_config_file_values() {
+        let path = write_temp_file(
+            r#"
+session_key = "file-session-key"
+heartbeat_interval = 15
+tags = ["gpu", "high-memory"]
+stream_logs = false
+sandbox = "container"
+"#,
+        );
+
+        let config = Config::load_from(["agw", "--config", path.to_str().unwrap()]).unwrap();
+        assert_eq!(config.session_key(), "file-session-key");
+        assert_eq!(config.heartbeat_interval, 15);
+        assert_eq!(
+            config.tags,
+            Some(vec!["gpu".to_string(), "high-memory".to_string()])
+        );
+        assert!(!config.stream_logs);
+        assert_eq!(config.sandbox, SandboxKind::Container);
+        assert!(config.validate().is_ok());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_cli_overrides_config_file() {
+        let path = write_temp_file(
+            "session_key = \"file-session-key\"\nagq_address = \"10.0.0.1:6379\"\n",
+        );
+
+        let config = Config::load_from([
+            "agw",
+            "--config",
+            path.to_str().unwrap(),
+            "--agq-address",
+            "10.0.0.2:6379",
+        ])
+        .unwrap();
+        assert_eq!(config.agq_address, "10.0.0.2:6379");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_env_overrides_config_file() {
+        let path = write_temp_file("connection_timeout = 5\n");
+
+        std::env::set_var("CONNECTION_TIMEOUT", "20");
+        let config = Config::load_from(["agw", "--config", path.to_str().unwrap()]);
+        std::env::remove_var("CONNECTION_TIMEOUT");
+
+        assert_eq!(config.unwrap().connection_timeout, 20);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_config_file_rejects_unknown_keys() {
+        assert!(parse_config_file("bogus = 1").is_err());
+        assert!(parse_config_file("config = \"/etc/other.toml\"").is_err());
+        assert!(parse_config_file("[tags]\ngpu = true").is_err());
+    }
+
+    #[test]
+    fn test_session_key_file() {
+        let path = write_temp_file("secret-session-key\n");
+
+        let config =
+            Config::load_from(["agw", "--session-key-file", path.to_str().unwrap()]).unwrap();
+        assert_eq!(config.session_key(), "secret-session-key");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_session_key_fails_validation() {
+        let config = Config::load_from(["agw"]).unwrap();
+        assert!(config.session_key.is_none());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_process_limit_needs_linux_or_container_sandbox() {
+        let args = [
+            "agw",
+            "--session-key",
+            "valid-session-key",
+            "--job-max-processes",
+            "64",
+        ];
+        let config = Config::load_from(args).unwrap();
+        assert_eq!(config.validate().is_ok(), cfg!(target_os = "linux"));
+
+        let config = Config::load_from(args.into_iter().chain(["--sandbox", "container"])).unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_job_timeout_clamped_to_max() {
+        let config = Config::load_from(["agw", "--max-job-timeout", "60"]).unwrap();
+        assert_eq!(config.job_timeout(Some(30)), Some(30));
+        assert_eq!(config.job_timeout(Some(3600)), Some(60));
+        assert_eq!(config.job_timeout(None), Some(60));
+
+        let config = Config::load_from(["agw"]).unwrap();
+        assert_eq!(config.job_timeout(Some(30)), Some(30));
+        assert_eq!(config.job_timeout(None), None);
+    }
+
+    #[test]
+    fn test_validate_job_env_entry() {
+        assert!(validate_job_env_entry("HTTP_PROXY=http://proxy:3128").is_ok());
+        assert!(validate_job_env_entry("API_KEY=secret://payments-key").is_ok());
+        assert!(validate_job_env_entry("EMPTY=").is_ok());
+        assert!(validate_job_env_entry("NO_VALUE").is_err());
+        assert!(validate_job_env_entry("BAD-NAME=x").is_err());
+    }
+
+    #[test]
+    fn test_command_policy_from_flags() {
+        let config = Config::load_from([
+            "agw",
+            "--allowed-commands",
+            "jq,sort",
+            "--denied-command-pattern",
+            r"\bsudo\b",
+        ])
+        .unwrap();
+        let policy = config.command_policy().unwrap();
+        assert!(policy.check("jq", &[]).is_ok());
+        assert!(policy.check("rm", &[]).is_err());
+
+        let mut config = Config::load_from(["agw"]).unwrap();
+        config.denied_command_patterns = vec!["(".to_string()];
+        assert!(config.command_policy().is_err());
+    }
+
+    #[test]
+    fn test_recover_jobs_mode() {
+        let config = Config::load_from(["agw"]).unwrap();
+        assert_eq!(config.recover_jobs, RecoveryMode::Requeue);
+
+        let config = Config::load_from(["agw", "--recover-jobs", "resume"]).unwrap();
+        assert_eq!(config.recover_jobs, RecoveryMode::Resume);
+
+        assert!(Config::load_from(["agw", "--recover-jobs", "later"]).is_err());
+    }
 }
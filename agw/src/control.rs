@@ -0,0 +1,194 @@
+use crate::error::{AgwError, AgwResult};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Operator commands accepted on the control socket
+///
+/// Commands are sent one per line, e.g. `echo drain | nc -U /run/agw.sock`.
+/// The worker answers `OK` once the command is queued, or `ERR <reason>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Finish the current job, stop fetching, and exit
+    Drain,
+    /// Stop fetching new jobs; the current job keeps running
+    Pause,
+    /// Start fetching jobs again after a pause
+    Resume,
+}
+
+impl ControlCommand {
+    /// Name of the command as sent on the socket
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Drain => "drain",
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+        }
+    }
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "drain" => Ok(Self::Drain),
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            other => Err(format!("unknown command: {other:?}")),
+        }
+    }
+}
+
+/// Running control socket server
+///
+/// The server is stopped and the socket file removed when this is dropped.
+#[derive(Debug)]
+pub struct ControlServer {
+    path: PathBuf,
+    handle: JoinHandle<()>,
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listen for control commands on a Unix socket at `path`
+///
+/// A stale socket file left by a previous run is replaced. The socket is only
+/// accessible to the worker's user.
+///
+/// # Errors
+///
+/// Returns an error if the socket cannot be bound
+#[cfg(unix)]
+pub fn spawn(path: &Path, commands: mpsc::Sender<ControlCommand>) -> AgwResult<ControlServer> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(AgwError::InvalidConfig(format!(
+                "Control socket path {} exists and is not a socket",
+                path.display()
+            )));
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    tracing::info!("Listening for control commands on {}", path.display());
+
+    Ok(ControlServer {
+        path: path.to_path_buf(),
+        handle: tokio::spawn(serve(listener, commands)),
+    })
+}
+
+/// Listen for control commands on a Unix socket at `path`
+///
+/// # Errors
+///
+/// Always fails; control sockets require Unix domain sockets
+#[cfg(not(unix))]
+pub fn spawn(path: &Path, _commands: mpsc::Sender<ControlCommand>) -> AgwResult<ControlServer> {
+    Err(AgwError::InvalidConfig(format!(
+        "Control socket {} is not supported on this platform",
+        path.display()
+    )))
+}
+
+#[cfg(unix)]
+async fn serve(listener: tokio::net::UnixListener, commands: mpsc::Sender<ControlCommand>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let commands = commands.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, &commands).await {
+                        tracing::debug!("Control connection failed: {e}");
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("Failed to accept control connection: {e}"),
+        }
+    }
+}
+
+/// Answer each command line on the connection until it is closed
+#[cfg(unix)]
+async fn handle_connection(
+    stream: tokio::net::UnixStream,
+    commands: &mpsc::Sender<ControlCommand>,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match line.parse::<ControlCommand>() {
+            Ok(command) => {
+                tracing::info!("Received control command: {}", command.as_str());
+                match commands.send(command).await {
+                    Ok(()) => "OK\n".to_string(),
+                    Err(_) => "ERR worker is shutting down\n".to_string(),
+                }
+            }
+            Err(e) => format!("ERR {e}\n"),
+        };
+        writer.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_control_command() {
+        assert_eq!("drain".parse(), Ok(ControlCommand::Drain));
+        assert_eq!(" Pause\n".parse(), Ok(ControlCommand::Pause));
+        assert_eq!("resume".parse(), Ok(ControlCommand::Resume));
+        assert!("restart".parse::<ControlCommand>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_control_socket_forwards_commands() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let path = std::env::temp_dir().join(format!("agw-control-{}.sock", uuid::Uuid::new_v4()));
+        let (tx, mut rx) = mpsc::channel(4);
+        let server = spawn(&path, tx).unwrap();
+
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut replies = BufReader::new(reader).lines();
+
+        writer.write_all(b"pause\nbogus\n").await.unwrap();
+        assert_eq!(replies.next_line().await.unwrap().unwrap(), "OK");
+        assert!(replies
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap()
+            .starts_with("ERR unknown command"));
+        assert_eq!(rx.recv().await, Some(ControlCommand::Pause));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+}
@@ -18,7 +18,6 @@ pub enum AgwError {
     RespProtocol(String),
 
     #[error("Worker error: {0}")]
-    #[allow(dead_code)]
     Worker(String),
 
     #[error("Executor error: {0}")]
@@ -31,4 +30,17 @@ pub enum AgwError {
     Redis(#[from] redis::RedisError),
 }
 
+impl AgwError {
+    /// Whether this error indicates a lost or unusable connection to AGQ
+    ///
+    /// These errors are recoverable by re-establishing the session.
+    #[must_use]
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_) | Self::RespProtocol(_) | Self::Redis(_)
+        )
+    }
+}
+
 pub type AgwResult<T> = Result<T, AgwError>;
@@ -2,7 +2,13 @@
 #![allow(clippy::module_name_repetitions)]
 
 use crate::error::{AgwError, AgwResult};
-use crate::plan::Plan;
+use crate::plan::{Plan, Stage};
+use crate::output::{OutputCapture, OutputEncoding, ResultEncoding, ResultTruncation};
+use crate::retry::TransientFailure;
+use crate::sandbox::{Sandbox, SandboxConfig};
+use crate::system::ResourceUsage;
+use std::future::Future;
+use std::process::Output;
 use tracing::{debug, error, info, warn};
 
 /// Result of a single task execution
@@ -10,16 +16,26 @@ use tracing::{debug, error, info, warn};
 pub struct TaskResult {
     /// Task number that was executed
     pub task_number: u32,
-    /// Standard output from the command
+    /// Standard output from the command (base64 if not valid UTF-8)
     pub stdout: String,
-    /// Standard error from the command
+    /// Standard error from the command (base64 if not valid UTF-8)
     pub stderr: String,
+    /// How `stdout` and `stderr` are encoded
+    pub encoding: ResultEncoding,
+    /// Whether `stdout` and `stderr` were cut off at the capture cap
+    pub truncated: ResultTruncation,
     /// Exit code (0 = success)
     pub exit_code: i32,
     /// Whether execution was successful (exit code 0)
     pub success: bool,
+    /// Whether the task was killed for exceeding its timeout
+    pub timed_out: bool,
     /// Execution time in milliseconds
     pub execution_time_ms: u64,
+    /// CPU time used by the task, where the platform reports it
+    pub resource_usage: Option<ResourceUsage>,
+    /// Infrastructure failure that kept the task from running properly
+    pub transient_failure: Option<TransientFailure>,
 }
 
 /// Result of entire plan execution
@@ -43,9 +59,14 @@ impl TaskResult {
             task_number,
             stdout,
             stderr,
+            encoding: ResultEncoding::default(),
+            truncated: ResultTruncation::default(),
             exit_code,
             success: exit_code == 0,
+            timed_out: false,
             execution_time_ms: 0,
+            resource_usage: None,
+            transient_failure: None,
         }
     }
 }
@@ -107,9 +128,10 @@ impl PlanResult {
 ///
 /// Note: This function will halt on first failure and return partial results
 pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
+    let plan_id = plan.plan_id.clone().unwrap_or_default();
     info!(
         "Executing plan {} (job {}) with {} tasks",
-        plan.plan_id,
+        plan_id,
         job_id,
         plan.tasks.len()
     );
@@ -129,9 +151,11 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
         match execute_task(
             &task.command,
             &task.args,
-            input.as_deref(),
+            input.as_deref().map(str::as_bytes),
             task.timeout_secs,
             task.task_number,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
         )
         .await
         {
@@ -159,11 +183,11 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
         }
     }
 
-    let plan_result = PlanResult::new(job_id.to_string(), plan.plan_id.clone(), task_results);
+    let plan_result = PlanResult::new(job_id.to_string(), plan_id, task_results);
 
     info!(
         "Plan {} completed: {} tasks executed, success={}",
-        plan.plan_id,
+        plan_result.plan_id,
         plan_result.task_results.len(),
         plan_result.success
     );
@@ -171,15 +195,6 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
     Ok(plan_result)
 }
 
-/// Execute a single task as a subprocess
-///
-/// # Errors
-///
-/// Returns an error if:
-/// - Command spawning fails
-/// - IO operations fail while reading stdout/stderr
-/// - Timeout is exceeded
-/// - Process cannot be killed after timeout
 /// Execute a single task as a subprocess
 ///
 /// # Errors
@@ -192,9 +207,11 @@ pub async fn execute_plan(job_id: &str, plan: &Plan) -> AgwResult<PlanResult> {
 pub async fn execute_task(
     command: &str,
     args: &[String],
-    stdin_input: Option<&str>,
+    stdin_input: Option<&[u8]>,
     timeout_secs: Option<u32>,
     task_number: u32,
+    sandbox_config: &SandboxConfig,
+    capture: &OutputCapture,
 ) -> AgwResult<TaskResult> {
     debug!("Command: {} with args: {:?}", command, args);
 
@@ -204,33 +221,163 @@ pub async fn execute_task(
     }
 
     // Create sandbox
-    let sandbox = crate::sandbox::create_sandbox();
+    let sandbox = crate::sandbox::create_sandbox(sandbox_config);
+
+    // Execute command in sandbox, piping any upstream output into stdin
+    let run_future = sandbox.run(command, args, &sandbox_config.env, stdin_input, capture);
+
+    finish_task(
+        run_future,
+        &[command],
+        timeout_secs,
+        task_number,
+        sandbox_config,
+        capture,
+    )
+    .await
+}
+
+/// Execute a pipeline task, running its stages in order in one sandbox
+///
+/// Each stage's stdout is buffered by the worker and piped into the next
+/// stage's stdin, so a `sort | uniq | wc` plan runs as a single job. Only the
+/// last stage's stdout is streamed and posted; stderr from every stage is
+/// kept. A stage that exits non-zero ends the pipeline with its exit code.
+///
+/// # Errors
+///
+/// Returns an error if the pipeline has no stages
+pub async fn execute_pipeline(
+    stages: &[Stage],
+    stdin_input: Option<&[u8]>,
+    timeout_secs: Option<u32>,
+    task_number: u32,
+    sandbox_config: &SandboxConfig,
+    capture: &OutputCapture,
+) -> AgwResult<TaskResult> {
+    debug!("Pipeline: {:?}", stages);
+
+    if stages.is_empty() || stages.iter().any(|stage| stage.command.is_empty()) {
+        return Err(AgwError::Executor(
+            "Pipeline stages cannot be empty".to_string(),
+        ));
+    }
 
+    let sandbox = crate::sandbox::create_sandbox(sandbox_config);
+    let run_future = run_pipeline(
+        sandbox.as_ref(),
+        stages,
+        &sandbox_config.env,
+        stdin_input,
+        capture,
+    );
+
+    let commands: Vec<&str> = stages.iter().map(|stage| stage.command.as_str()).collect();
+    finish_task(
+        run_future,
+        &commands,
+        timeout_secs,
+        task_number,
+        sandbox_config,
+        capture,
+    )
+    .await
+}
+
+/// Run pipeline stages one after another, feeding each the previous stdout
+async fn run_pipeline(
+    sandbox: &dyn Sandbox,
+    stages: &[Stage],
+    env: &[(String, String)],
+    stdin_input: Option<&[u8]>,
+    capture: &OutputCapture,
+) -> AgwResult<Output> {
+    // Intermediate output feeds the next stage rather than the job's log
+    let intermediate = OutputCapture {
+        max_bytes: capture.max_bytes,
+        sink: None,
+    };
+
+    let mut input = stdin_input.map(<[u8]>::to_vec);
+    let mut stderr = Vec::new();
+    for (i, stage) in stages.iter().enumerate() {
+        let last = i + 1 == stages.len();
+        let output = sandbox
+            .run(
+                &stage.command,
+                &stage.args,
+                env,
+                input.as_deref(),
+                if last { capture } else { &intermediate },
+            )
+            .await?;
+        stderr.extend(output.stderr);
+
+        if last {
+            return Ok(Output {
+                status: output.status,
+                stdout: output.stdout,
+                stderr,
+            });
+        }
+        if !output.status.success() {
+            debug!("Pipeline stage {} ({}) failed", i + 1, stage.command);
+            return Ok(Output {
+                status: output.status,
+                stdout: Vec::new(),
+                stderr,
+            });
+        }
+        // A truncated stream would silently corrupt the next stage's input
+        if intermediate.truncated(&output.stdout) {
+            return Err(AgwError::Executor(format!(
+                "Pipeline stage {} ({}) produced more than {} bytes",
+                i + 1,
+                stage.command,
+                capture.max_bytes
+            )));
+        }
+        input = Some(output.stdout);
+    }
+
+    Err(AgwError::Executor(
+        "Pipeline stages cannot be empty".to_string(),
+    ))
+}
+
+/// Wait for a started task under its timeout and build its result
+///
+/// `commands` are the programs the task launches, used to classify a
+/// failure to start them.
+async fn finish_task(
+    run_future: impl Future<Output = AgwResult<Output>>,
+    commands: &[&str],
+    timeout_secs: Option<u32>,
+    task_number: u32,
+    sandbox_config: &SandboxConfig,
+    capture: &OutputCapture,
+) -> AgwResult<TaskResult> {
     let start_time = std::time::Instant::now();
+    let usage_before = ResourceUsage::children();
 
-    // Prepare environment (if needed)
-    let env = vec![];
-
-    // Execute command in sandbox
-    // TODO: Pass stdin_input and timeout_secs to sandbox.run if supported
-    // For now, we ignore stdin/timeout in the sandbox trait signature, 
-    // but we should update the trait to support them.
-    // Or we can wrap the sandbox call in a timeout here.
-    
-    let run_future = sandbox.run(command, args, &env);
-    
     let output_result = if let Some(timeout) = timeout_secs {
         let duration = std::time::Duration::from_secs(u64::from(timeout));
         match tokio::time::timeout(duration, run_future).await {
             Ok(res) => res,
             Err(_) => {
+                warn!("Task {} timed out after {}s", task_number, timeout);
                 return Ok(TaskResult {
                     task_number,
                     success: false,
+                    timed_out: true,
                     exit_code: -1,
                     stdout: String::new(),
                     stderr: format!("Task timed out after {}s", timeout),
+                    encoding: ResultEncoding::default(),
+                    truncated: ResultTruncation::default(),
                     execution_time_ms: start_time.elapsed().as_millis() as u64,
+                    resource_usage: None,
+                    transient_failure: None,
                 });
             }
         }
@@ -244,10 +391,18 @@ pub async fn execute_task(
             return Ok(TaskResult {
                 task_number,
                 success: false,
+                timed_out: false,
                 exit_code: -1,
                 stdout: String::new(),
                 stderr: format!("Sandbox execution failed: {}", e),
+                encoding: ResultEncoding::default(),
+                truncated: ResultTruncation::default(),
                 execution_time_ms: start_time.elapsed().as_millis() as u64,
+                resource_usage: None,
+                // Executor errors are the task's own, such as an oversized
+                // pipeline stage; others mean the sandbox could not start it
+                transient_failure: (!matches!(e, AgwError::Executor(_)))
+                    .then(|| TransientFailure::launch(commands, sandbox_config)),
             });
         }
     };
@@ -255,10 +410,22 @@ pub async fn execute_task(
     let duration = start_time.elapsed();
     let execution_time_ms = duration.as_millis() as u64;
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
     let exit_code = output.status.code().unwrap_or(-1);
     let success = output.status.success();
+    let transient_failure = if success {
+        None
+    } else {
+        TransientFailure::from_exit(output.status, sandbox_config)
+    };
+    let resource_usage = usage_before
+        .zip(ResourceUsage::children())
+        .map(|(before, after)| after.since(before));
+    let truncated = ResultTruncation {
+        stdout: capture.truncated(&output.stdout),
+        stderr: capture.truncated(&output.stderr),
+    };
+    let (stdout, stdout_encoding) = OutputEncoding::encode(output.stdout);
+    let (stderr, stderr_encoding) = OutputEncoding::encode(output.stderr);
 
     info!(
         "Task {} execution completed in {}ms (exit code: {})",
@@ -268,10 +435,18 @@ pub async fn execute_task(
     Ok(TaskResult {
         task_number,
         success,
+        timed_out: false,
         exit_code,
         stdout,
         stderr,
+        encoding: ResultEncoding {
+            stdout: stdout_encoding,
+            stderr: stderr_encoding,
+        },
+        truncated,
         execution_time_ms,
+        resource_usage,
+        transient_failure,
     })
 }
 
@@ -280,11 +455,12 @@ pub async fn execute_task(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plan::Task;
 
     #[tokio::test]
     async fn test_execute_task_plan() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![Task {
                 task_number: 1,
@@ -292,7 +468,9 @@ mod tests {
                 args: vec!["hello".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(30),
+                ..Task::default()
             }],
+            ..Plan::default()
         };
 
         let result = execute_plan("job-123", &plan).await.unwrap();
@@ -307,7 +485,7 @@ mod tests {
     #[tokio::test]
     async fn test_execute_multi_step_plan() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: Some("Multi-step test".to_string()),
             tasks: vec![
                 Task {
@@ -316,6 +494,7 @@ mod tests {
                     args: vec!["line1\nline2\nline3".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -323,8 +502,10 @@ mod tests {
                     args: vec!["-l".to_string()],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
         let result = execute_plan("job-123", &plan).await.unwrap();
@@ -337,7 +518,7 @@ mod tests {
     #[tokio::test]
     async fn test_execute_plan_with_failure() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![
                 Task {
@@ -346,6 +527,7 @@ mod tests {
                     args: vec!["-c".to_string(), "exit 42".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -353,8 +535,10 @@ mod tests {
                     args: vec!["should not run".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
         let result = execute_plan("job-123", &plan).await.unwrap();
@@ -368,7 +552,7 @@ mod tests {
     #[tokio::test]
     async fn test_execute_plan_with_timeout() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![Task {
                 task_number: 1,
@@ -376,19 +560,22 @@ mod tests {
                 args: vec!["10".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(1),
+                ..Task::default()
             }],
+            ..Plan::default()
         };
 
         let result = execute_plan("job-123", &plan).await.unwrap();
         assert_eq!(result.task_results.len(), 1);
         assert!(!result.task_results[0].success);
+        assert!(result.task_results[0].timed_out);
         assert!(!result.success);
     }
 
     #[tokio::test]
     async fn test_execute_plan_with_stdin_piping() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![
                 Task {
@@ -397,6 +584,7 @@ mod tests {
                     args: vec!["foo\nbar\nfoo".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -404,6 +592,7 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 3,
@@ -411,8 +600,10 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(2),
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
         let result = execute_plan("job-123", &plan).await.unwrap();
@@ -428,7 +619,7 @@ mod tests {
     #[tokio::test]
     async fn test_execute_invalid_command() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![Task {
                 task_number: 1,
@@ -436,11 +627,147 @@ mod tests {
                 args: vec![],
                 input_from_task: None,
                 timeout_secs: None,
+                ..Task::default()
             }],
+            ..Plan::default()
         };
 
-        let result = execute_plan("job-123", &plan).await;
-        assert!(result.is_err());
+        // Spawn failures inside the sandbox are reported as a failed task
+        let result = execute_plan("job-123", &plan).await.unwrap();
+        assert_eq!(result.task_results.len(), 1);
+        assert!(!result.success);
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_with_stdin() {
+        let result = execute_task(
+            "wc",
+            &["-l".to_string()],
+            Some(b"a\nb\nc\n"),
+            Some(30),
+            1,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout.trim(), "3");
+    }
+
+    #[tokio::test]
+    async fn test_execute_pipeline() {
+        let stages = [
+            Stage {
+                command: "sort".to_string(),
+                args: vec![],
+            },
+            Stage {
+                command: "uniq".to_string(),
+                args: vec![],
+            },
+            Stage {
+                command: "wc".to_string(),
+                args: vec!["-l".to_string()],
+            },
+        ];
+        let result = execute_pipeline(
+            &stages,
+            Some(b"b\na\nb\nc\na\n"),
+            Some(30),
+            1,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout.trim(), "3");
+    }
+
+    #[tokio::test]
+    async fn test_execute_pipeline_stops_at_failed_stage() {
+        let stages = [
+            Stage {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), "echo oops >&2; exit 4".to_string()],
+            },
+            Stage {
+                command: "echo".to_string(),
+                args: vec!["unreachable".to_string()],
+            },
+        ];
+        let result = execute_pipeline(
+            &stages,
+            None,
+            Some(30),
+            1,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.exit_code, 4);
+        assert_eq!(result.stdout, "");
+        assert_eq!(result.stderr, "oops\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_without_stdin_sees_eof() {
+        // `cat` with no stdin must terminate instead of blocking on the worker's input
+        let result = execute_task(
+            "cat",
+            &[],
+            None,
+            Some(5),
+            1,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout, "");
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_binary_output_is_base64() {
+        let result = execute_task(
+            "printf",
+            &["\\211PNG".to_string()],
+            None,
+            Some(5),
+            1,
+            &SandboxConfig::default(),
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.encoding.stdout, OutputEncoding::Base64);
+        assert_eq!(result.stdout, "iVBORw==");
+        assert_eq!(result.encoding.stderr, OutputEncoding::Utf8);
+    }
+
+    #[tokio::test]
+    async fn test_execute_task_with_env() {
+        let sandbox = SandboxConfig {
+            env: vec![("GREETING".to_string(), "hello".to_string())],
+            ..SandboxConfig::default()
+        };
+        let result = execute_task(
+            "printenv",
+            &["GREETING".to_string()],
+            None,
+            Some(5),
+            1,
+            &sandbox,
+            &OutputCapture::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout, "hello\n");
     }
 
     #[test]
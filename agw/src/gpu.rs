@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Maximum time to wait for a GPU probe at startup
+///
+/// `system_profiler` can take a few seconds on a cold start.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tag registered by every worker with at least one GPU
+pub const GPU_TAG: &str = "gpu";
+
+/// GPU vendors the worker can detect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuVendor {
+    Nvidia,
+    Apple,
+}
+
+/// A GPU found on the worker host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GpuInfo {
+    pub vendor: GpuVendor,
+    /// Model name as reported by the driver, e.g. `NVIDIA GeForce RTX 4090`
+    pub model: String,
+    /// Dedicated memory in MiB; absent for GPUs sharing system memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vram_mb: Option<u64>,
+}
+
+/// Detect the host's GPUs
+///
+/// NVIDIA GPUs are found with `nvidia-smi`, and Apple GPUs on macOS with
+/// `system_profiler`, which lists the Metal devices. A missing or failing
+/// probe means no GPUs of that kind.
+pub async fn detect() -> Vec<GpuInfo> {
+    let mut gpus = match probe("nvidia-smi", NVIDIA_SMI_ARGS).await {
+        Some(output) => parse_nvidia_smi(&output),
+        None => Vec::new(),
+    };
+
+    if cfg!(target_os = "macos") {
+        if let Some(output) = probe("system_profiler", &["SPDisplaysDataType", "-json"]).await {
+            gpus.extend(parse_system_profiler(&output));
+        }
+    }
+
+    for gpu in &gpus {
+        info!("Detected GPU: {} ({:?} MiB)", gpu.model, gpu.vram_mb);
+    }
+    gpus
+}
+
+/// Routing tags for detected GPUs: `gpu`, `gpu:<model>`, and `vram:<GB>`
+#[must_use]
+pub fn capability_tags(gpus: &[GpuInfo]) -> Vec<String> {
+    if gpus.is_empty() {
+        return Vec::new();
+    }
+
+    let mut tags = vec![GPU_TAG.to_string()];
+    for gpu in gpus {
+        let model = format!("{GPU_TAG}:{}", model_slug(&gpu.model));
+        let vram = gpu
+            .vram_mb
+            .map(|mb| format!("vram:{}", mb.saturating_add(512) / 1024));
+        for tag in std::iter::once(model).chain(vram) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+const NVIDIA_SMI_ARGS: &[&str] = &[
+    "--query-gpu=name,memory.total",
+    "--format=csv,noheader,nounits",
+];
+
+/// Run a probe command, returning its stdout if it succeeded in time
+async fn probe(program: &str, args: &[&str]) -> Option<String> {
+    let query = tokio::process::Command::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(PROBE_TIMEOUT, query).await {
+        Ok(Ok(output)) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(Ok(_)) | Ok(Err(_)) => {
+            debug!("GPU probe {program} unavailable");
+            None
+        }
+        Err(_) => {
+            debug!("GPU probe {program} timed out");
+            None
+        }
+    }
+}
+
+/// Parse `name, memory.total` lines of `nvidia-smi` CSV output
+fn parse_nvidia_smi(output: &str) -> Vec<GpuInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, memory) = line.rsplit_once(',')?;
+            let model = name.trim();
+            (!model.is_empty()).then(|| GpuInfo {
+                vendor: GpuVendor::Nvidia,
+                model: model.to_string(),
+                vram_mb: memory.trim().parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Parse Apple GPUs from `system_profiler SPDisplaysDataType -json`
+fn parse_system_profiler(output: &str) -> Vec<GpuInfo> {
+    let Ok(report) = serde_json::from_str::<serde_json::Value>(output) else {
+        return Vec::new();
+    };
+    let Some(displays) = report["SPDisplaysDataType"].as_array() else {
+        return Vec::new();
+    };
+
+    displays
+        .iter()
+        .filter_map(|display| {
+            let model = display["sppci_model"].as_str()?.trim();
+            let vendor = display["spdisplays_vendor"].as_str().unwrap_or_default();
+            if !model.starts_with("Apple") && !vendor.contains("Apple") {
+                return None;
+            }
+            Some(GpuInfo {
+                vendor: GpuVendor::Apple,
+                model: model.to_string(),
+                // Apple silicon shares system memory, so no VRAM is listed
+                vram_mb: display["spdisplays_vram"]
+                    .as_str()
+                    .and_then(parse_memory_size),
+            })
+        })
+        .collect()
+}
+
+/// Parse a `system_profiler` memory size such as `8 GB` or `1536 MB` into MiB
+fn parse_memory_size(size: &str) -> Option<u64> {
+    let (amount, unit) = size.trim().split_once(' ')?;
+    let amount: u64 = amount.parse().ok()?;
+    match unit.trim() {
+        "GB" => amount.checked_mul(1024),
+        "MB" => Some(amount),
+        _ => None,
+    }
+}
+
+/// Lowercase a model name into a tag-safe slug, e.g. `apple-m2-max`
+fn model_slug(model: &str) -> String {
+    model
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nvidia_smi() {
+        let gpus = parse_nvidia_smi("NVIDIA GeForce RTX 4090, 24564\nTesla T4, [N/A]\n");
+        assert_eq!(
+            gpus,
+            vec![
+                GpuInfo {
+                    vendor: GpuVendor::Nvidia,
+                    model: "NVIDIA GeForce RTX 4090".to_string(),
+                    vram_mb: Some(24564),
+                },
+                GpuInfo {
+                    vendor: GpuVendor::Nvidia,
+                    model: "Tesla T4".to_string(),
+                    vram_mb: None,
+                },
+            ]
+        );
+        assert!(parse_nvidia_smi("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_system_profiler() {
+        let output = r#"{"SPDisplaysDataType": [
+            {"sppci_model": "Apple M2 Max", "spdisplays_vendor": "sppci_vendor_Apple"},
+            {"sppci_model": "AMD Radeon Pro 5500M", "spdisplays_vram": "8 GB"}
+        ]}"#;
+        assert_eq!(
+            parse_system_profiler(output),
+            vec![GpuInfo {
+                vendor: GpuVendor::Apple,
+                model: "Apple M2 Max".to_string(),
+                vram_mb: None,
+            }]
+        );
+        assert!(parse_system_profiler("not json").is_empty());
+    }
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("8 GB"), Some(8192));
+        assert_eq!(parse_memory_size("1536 MB"), Some(1536));
+        assert_eq!(parse_memory_size("lots"), None);
+    }
+
+    #[test]
+    fn test_capability_tags() {
+        assert!(capability_tags(&[]).is_empty());
+
+        let gpu = GpuInfo {
+            vendor: GpuVendor::Nvidia,
+            model: "NVIDIA GeForce RTX 4090".to_string(),
+            vram_mb: Some(24564),
+        };
+        assert_eq!(
+            capability_tags(&[gpu.clone(), gpu]),
+            vec!["gpu", "gpu:nvidia-geforce-rtx-4090", "vram:24"]
+        );
+    }
+}
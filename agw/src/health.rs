@@ -0,0 +1,249 @@
+//! Periodic self-checks of the worker's tools
+//!
+//! A tool registered at startup can break while the worker runs, e.g. when
+//! an AU's model files are deleted. The worker re-checks its tools on an
+//! interval, reports the result in heartbeats, and de-registers broken
+//! tools until they pass again:
+//!
+//! - tools whose model card has `"health": true` run `<tool> --health`,
+//!   which exits 0 when the tool can take jobs and non-zero otherwise, with
+//!   the reason on the last line of stderr
+//! - other tools with a model card must still print one for `--describe`
+//! - tools without a card must still be found on `PATH`
+
+use crate::manifest::{self, ModelCard};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Maximum time to wait for `<tool> --health`
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest reason reported for a broken tool, to keep heartbeats small
+const MAX_REASON_CHARS: usize = 120;
+
+/// Health of one tool, as reported in heartbeats
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolHealth {
+    pub healthy: bool,
+
+    /// Why the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl ToolHealth {
+    fn healthy() -> Self {
+        Self {
+            healthy: true,
+            reason: None,
+        }
+    }
+
+    fn broken(reason: &str) -> Self {
+        let mut reason = reason.trim().to_string();
+        if let Some((end, _)) = reason.char_indices().nth(MAX_REASON_CHARS) {
+            reason.truncate(end);
+            reason.push_str("...");
+        }
+        Self {
+            healthy: false,
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Check one tool; `card` is its model card, if it registered one
+pub async fn check(tool: &str, card: Option<&ModelCard>) -> ToolHealth {
+    match card {
+        Some(card) if card.health => run_health(tool).await,
+        Some(_) => {
+            if manifest::describe_tool(tool).await.is_some() {
+                ToolHealth::healthy()
+            } else {
+                ToolHealth::broken("--describe no longer prints a model card")
+            }
+        }
+        None => {
+            if find_executable(tool, std::env::var_os("PATH").as_deref()).is_some() {
+                ToolHealth::healthy()
+            } else {
+                ToolHealth::broken("not found on PATH")
+            }
+        }
+    }
+}
+
+/// Check every tool
+pub async fn check_all(tools: &[String], cards: &[ModelCard]) -> BTreeMap<String, ToolHealth> {
+    let mut report = BTreeMap::new();
+    for tool in tools {
+        let card = cards.iter().find(|card| card.tool == *tool);
+        report.insert(tool.clone(), check(tool, card).await);
+    }
+    report
+}
+
+/// Check the tools every `interval`, sending each report to `reports`
+///
+/// The first check runs one interval after startup, since the tools were
+/// just described. The task stops when the receiver is dropped.
+pub fn spawn(
+    tools: Vec<String>,
+    cards: Vec<ModelCard>,
+    interval: Duration,
+    reports: mpsc::Sender<BTreeMap<String, ToolHealth>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now() + interval;
+        let mut ticks = tokio::time::interval_at(start, interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let report = check_all(&tools, &cards).await;
+            if reports.send(report).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+async fn run_health(tool: &str) -> ToolHealth {
+    let query = tokio::process::Command::new(tool)
+        .arg("--health")
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(HEALTH_TIMEOUT, query).await {
+        Ok(Ok(output)) if output.status.success() => ToolHealth::healthy(),
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+                Some(line) => ToolHealth::broken(line),
+                None => ToolHealth::broken(&format!("--health exited with {}", output.status)),
+            }
+        }
+        Ok(Err(e)) => {
+            debug!("Could not run {tool} --health: {e}");
+            ToolHealth::broken(&format!("could not run: {e}"))
+        }
+        Err(_) => ToolHealth::broken(&format!(
+            "--health timed out after {}s",
+            HEALTH_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// Where `tool` would be run from, searching `path` for bare command names
+fn find_executable(tool: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    if tool.contains('/') {
+        let tool = Path::new(tool);
+        return is_executable(tool).then(|| tool.to_path_buf());
+    }
+    std::env::split_paths(path?)
+        .map(|dir| dir.join(tool))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn script(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn card(tool: &str, health: bool) -> ModelCard {
+        ModelCard {
+            tool: tool.to_string(),
+            name: "agx-ocr".to_string(),
+            json: "{}".to_string(),
+            health,
+        }
+    }
+
+    #[test]
+    fn test_find_executable() {
+        let dir = std::env::temp_dir().join(format!("agw-health-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = script(&dir, "agx-ocr", "exit 0");
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let path = std::env::join_paths(["/nonexistent", dir.to_str().unwrap()]).unwrap();
+        assert_eq!(
+            find_executable("agx-ocr", Some(&path)),
+            Some(PathBuf::from(&tool))
+        );
+        assert_eq!(find_executable(&tool, None), Some(PathBuf::from(&tool)));
+        assert_eq!(find_executable("notes.txt", Some(&path)), None);
+        assert_eq!(find_executable("agx-ocr", None), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_contract() {
+        let dir = std::env::temp_dir().join(format!("agw-health-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ready = script(&dir, "ready", "exit 0");
+        let broken = script(
+            &dir,
+            "broken",
+            "echo 'loading model' >&2\necho 'model.safetensors not found' >&2\nexit 1",
+        );
+        let silent = script(&dir, "silent", "exit 3");
+
+        assert_eq!(
+            check(&ready, Some(&card(&ready, true))).await,
+            ToolHealth::healthy()
+        );
+        assert_eq!(
+            check(&broken, Some(&card(&broken, true))).await,
+            ToolHealth::broken("model.safetensors not found")
+        );
+        let health = check(&silent, Some(&card(&silent, true))).await;
+        assert!(!health.healthy);
+        assert!(health.reason.unwrap().starts_with("--health exited with"));
+
+        // Without the contract, the tool must still describe itself
+        let health = check(&ready, Some(&card(&ready, false))).await;
+        assert_eq!(
+            health.reason.as_deref(),
+            Some("--describe no longer prints a model card")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_reason_is_truncated() {
+        let health = ToolHealth::broken(&"x".repeat(500));
+        assert_eq!(health.reason.unwrap().len(), MAX_REASON_CHARS + 3);
+        assert_eq!(
+            serde_json::to_string(&ToolHealth::healthy()).unwrap(),
+            r#"{"healthy":true}"#
+        );
+    }
+}
@@ -1,8 +1,30 @@
 // Public exports for library usage
+pub mod builder;
+#[cfg(target_os = "linux")]
+pub mod cgroup;
 pub mod config;
+pub mod control;
 pub mod error;
 pub mod executor;
+pub mod gpu;
+pub mod health;
+pub mod manifest;
+pub mod metrics;
+#[cfg(target_os = "linux")]
+pub mod namespace;
+pub mod output;
 pub mod plan;
+pub mod policy;
+pub mod recovery;
 pub mod resp;
+pub mod result;
+pub mod retry;
 pub mod sandbox;
+pub mod secrets;
+pub mod shutdown;
+pub mod system;
 pub mod worker;
+pub mod workspace;
+
+pub use builder::WorkerBuilder;
+pub use worker::{JobHooks, Worker};
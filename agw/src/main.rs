@@ -1,18 +1,9 @@
 use anyhow::Result;
-use clap::Parser;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
-mod config;
-mod error;
-mod executor;
-mod plan;
-mod resp;
-mod sandbox;
-mod worker;
-
-use config::Config;
-use worker::Worker;
+use agw::config::Config;
+use agw::Worker;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -23,7 +14,7 @@ async fn main() -> Result<()> {
     tracing::subscriber::set_global_default(subscriber)?;
 
     // Parse CLI arguments
-    let config = Config::parse();
+    let config = Config::load()?;
 
     info!("AGW v{} starting...", env!("CARGO_PKG_VERSION"));
 
@@ -0,0 +1,96 @@
+//! Model cards of the worker's Agentic Units
+//!
+//! Agentic Units print a JSON model card when run with `--describe`. The
+//! worker collects the cards of its tools at startup and registers them
+//! with AGQ, which serves the fleet's tools as a catalog for planning.
+//! Tools without a card, such as `sort`, are registered by name only.
+//! A card with `"health": true` declares that the tool supports the
+//! `--health` contract used by the worker's periodic self-checks.
+
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// Maximum time to wait for a tool to describe itself
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `--describe` model card of one of the worker's tools
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelCard {
+    /// Command the card was read from
+    pub tool: String,
+    /// Name the card registers under in AGQ
+    pub name: String,
+    /// The card as compact JSON
+    pub json: String,
+    /// Whether the tool implements the `--health` contract
+    pub health: bool,
+}
+
+/// Collect the model cards of the tools that print one
+pub async fn describe(tools: &[String]) -> Vec<ModelCard> {
+    let mut cards = Vec::new();
+    for tool in tools {
+        if let Some(card) = describe_tool(tool).await {
+            info!("Found model card for {tool}");
+            cards.push(card);
+        }
+    }
+    cards
+}
+
+/// Read a tool's model card, if it prints one
+pub async fn describe_tool(tool: &str) -> Option<ModelCard> {
+    let query = tokio::process::Command::new(tool)
+        .arg("--describe")
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(DESCRIBE_TIMEOUT, query).await {
+        Ok(Ok(output)) if output.status.success() => parse_card(tool, &output.stdout),
+        Ok(Ok(_)) | Ok(Err(_)) => {
+            debug!("{tool} has no model card");
+            None
+        }
+        Err(_) => {
+            debug!("{tool} --describe timed out");
+            None
+        }
+    }
+}
+
+/// A model card, if the output is a JSON object
+///
+/// `name` falls back to the command for cards without one, which AGQ
+/// rejects when they are registered.
+fn parse_card(tool: &str, output: &[u8]) -> Option<ModelCard> {
+    let card = serde_json::from_slice::<serde_json::Value>(output)
+        .ok()
+        .filter(serde_json::Value::is_object)?;
+    Some(ModelCard {
+        tool: tool.to_string(),
+        name: card["name"].as_str().unwrap_or(tool).to_string(),
+        json: card.to_string(),
+        health: card["health"].as_bool().unwrap_or(false),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_card() {
+        let output = b"{\n  \"name\": \"agx-ocr\",\n  \"capabilities\": [\"ocr\"]\n}\n";
+        let card = parse_card("agx-ocr", output).unwrap();
+        assert_eq!(card.name, "agx-ocr");
+        assert_eq!(card.json, r#"{"capabilities":["ocr"],"name":"agx-ocr"}"#);
+        assert!(!card.health);
+        assert!(parse_card("agx-ocr", b"--describe\n").is_none());
+        assert!(parse_card("agx-ocr", b"[1, 2]").is_none());
+
+        let card = parse_card("/opt/bin/agx-ocr", br#"{"health": true}"#).unwrap();
+        assert_eq!(card.name, "/opt/bin/agx-ocr");
+        assert!(card.health);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::{AgwError, AgwResult};
+use agenix_metrics::{DURATION_BUCKETS, QUEUE_WAIT_BUCKETS};
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, Registry, TextEncoder};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::warn;
+
+pub use agenix_metrics::bind;
+
+/// Prometheus metrics describing worker health
+///
+/// Metrics are always recorded; they are only exposed over HTTP when
+/// `metrics_addr` is configured.
+#[derive(Debug, Clone)]
+pub struct WorkerMetrics {
+    registry: Registry,
+    pub jobs_fetched: IntCounter,
+    pub jobs_succeeded: IntCounter,
+    pub jobs_failed: IntCounter,
+    pub jobs_retried: IntCounter,
+    pub job_duration_seconds: Histogram,
+    pub queue_wait_seconds: Histogram,
+    pub heartbeat_failures: IntCounter,
+}
+
+impl WorkerMetrics {
+    /// Create and register all worker metrics
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a metric cannot be registered
+    pub fn new() -> AgwResult<Self> {
+        let registry = Registry::new_custom(Some("agw".to_string()), None).map_err(metric_error)?;
+
+        let jobs_fetched =
+            IntCounter::new("jobs_fetched_total", "Jobs fetched from AGQ").map_err(metric_error)?;
+        let jobs_succeeded =
+            IntCounter::new("jobs_succeeded_total", "Jobs that completed successfully")
+                .map_err(metric_error)?;
+        let jobs_failed = IntCounter::new(
+            "jobs_failed_total",
+            "Jobs that failed during preparation or execution",
+        )
+        .map_err(metric_error)?;
+        let jobs_retried = IntCounter::new(
+            "jobs_retried_total",
+            "Jobs requeued after a transient infrastructure failure",
+        )
+        .map_err(metric_error)?;
+        let job_duration_seconds = Histogram::with_opts(
+            HistogramOpts::new("job_duration_seconds", "Job execution time in seconds")
+                .buckets(DURATION_BUCKETS.to_vec()),
+        )
+        .map_err(metric_error)?;
+        let queue_wait_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "queue_wait_seconds",
+                "Time between job creation and pickup in seconds",
+            )
+            .buckets(QUEUE_WAIT_BUCKETS.to_vec()),
+        )
+        .map_err(metric_error)?;
+        let heartbeat_failures =
+            IntCounter::new("heartbeat_failures_total", "Heartbeats that failed to send")
+                .map_err(metric_error)?;
+
+        registry
+            .register(Box::new(jobs_fetched.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(jobs_succeeded.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(jobs_failed.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(jobs_retried.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(job_duration_seconds.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(queue_wait_seconds.clone()))
+            .map_err(metric_error)?;
+        registry
+            .register(Box::new(heartbeat_failures.clone()))
+            .map_err(metric_error)?;
+
+        Ok(Self {
+            registry,
+            jobs_fetched,
+            jobs_succeeded,
+            jobs_failed,
+            jobs_retried,
+            job_duration_seconds,
+            queue_wait_seconds,
+            heartbeat_failures,
+        })
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            warn!("Failed to encode metrics: {e}");
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
+
+fn metric_error(e: prometheus::Error) -> AgwError {
+    AgwError::Worker(format!("Failed to create metrics: {e}"))
+}
+
+/// Serve `GET /metrics` on the listener until the task is dropped
+pub async fn serve(listener: TcpListener, metrics: Arc<WorkerMetrics>) {
+    agenix_metrics::serve(listener, move || Some(metrics.encode())).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_encode_includes_worker_metrics() {
+        let metrics = WorkerMetrics::new().unwrap();
+        metrics.jobs_fetched.inc();
+        metrics.job_duration_seconds.observe(1.5);
+
+        let text = metrics.encode();
+        assert!(text.contains("agw_jobs_fetched_total 1"));
+        assert!(text.contains("agw_jobs_failed_total 0"));
+        assert!(text.contains("agw_jobs_retried_total 0"));
+        assert!(text.contains("agw_job_duration_seconds_count 1"));
+        assert!(text.contains("agw_heartbeat_failures_total 0"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics_over_http() {
+        let metrics = Arc::new(WorkerMetrics::new().unwrap());
+        metrics.jobs_succeeded.inc();
+
+        let listener = bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, Arc::clone(&metrics)));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("agw_jobs_succeeded_total 1"));
+        server.abort();
+    }
+}
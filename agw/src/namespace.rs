@@ -0,0 +1,344 @@
+//! In-process Linux namespace isolation
+//!
+//! This does what `prlimit ... unshare -m -p -f --mount-proc` does, without
+//! needing util-linux on the worker host. Everything here runs in the forked
+//! child between `fork` and `exec`, where the parent may have held locks in
+//! other threads, so the hook only makes raw syscalls on data prepared
+//! before the fork and never allocates.
+
+use crate::sandbox::ResourceLimits;
+use nix::libc;
+use std::ffi::CStr;
+use std::io;
+
+/// Namespaces created for every job: mount, PID, IPC, and hostname
+///
+/// The network namespace is shared so jobs can still reach the network.
+const NAMESPACE_FLAGS: libc::c_int =
+    libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS;
+
+/// Syscalls a filtered job may not make; they fail with `EPERM`
+///
+/// These administer the kernel or escape the sandbox rather than do work.
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_userfaultfd,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+];
+
+/// `AUDIT_ARCH_*` value of the architecture the filter is written for
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Syscall numbers with this bit set use the x32 ABI, which the filter
+/// does not list separately
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Type of the resource argument to `setrlimit`
+#[cfg(target_env = "gnu")]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type RlimitResource = libc::c_int;
+
+/// Offsets of fields in `struct seccomp_data`
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+/// Everything the child needs to isolate itself, prepared before the fork
+#[derive(Debug, Clone)]
+pub struct NamespaceSetup {
+    rlimits: Vec<(RlimitResource, libc::rlim_t)>,
+    /// `uid_map` and `gid_map` contents when a user namespace is needed
+    id_maps: Option<(Vec<u8>, Vec<u8>)>,
+    seccomp: Option<Vec<libc::sock_filter>>,
+}
+
+impl NamespaceSetup {
+    /// Prepare isolation for a job with the given limits
+    ///
+    /// When the worker is not root, a user namespace maps its user to root
+    /// inside the sandbox so the other namespaces can be created without
+    /// privileges. Seccomp filtering is skipped with a warning on
+    /// architectures the filter does not cover.
+    #[must_use]
+    pub fn new(limits: &ResourceLimits, seccomp: bool) -> Self {
+        let mut rlimits = Vec::new();
+        if let Some(mb) = limits.memory_mb {
+            rlimits.push((libc::RLIMIT_AS, mb.saturating_mul(1024 * 1024)));
+        }
+        if let Some(secs) = limits.cpu_secs {
+            rlimits.push((libc::RLIMIT_CPU, secs));
+        }
+        // `max_processes` is enforced with a cgroup, see `crate::cgroup`
+
+        let euid = nix::unistd::geteuid();
+        let id_maps = (!euid.is_root()).then(|| {
+            let egid = nix::unistd::getegid();
+            (
+                format!("0 {euid} 1\n").into_bytes(),
+                format!("0 {egid} 1\n").into_bytes(),
+            )
+        });
+
+        let seccomp = if seccomp {
+            let filter = seccomp_filter();
+            if filter.is_none() {
+                tracing::warn!("Seccomp filtering is not supported on this architecture");
+            }
+            filter
+        } else {
+            None
+        };
+
+        Self {
+            rlimits,
+            id_maps,
+            seccomp,
+        }
+    }
+
+    /// Isolate the current process; called in the child before `exec`
+    ///
+    /// The calling process forks once more so the job becomes PID 1 of the
+    /// new PID namespace. The intermediate process waits for it and exits
+    /// with its status; if the intermediate is killed (e.g. on timeout), the
+    /// job and everything it started are killed with it.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called in a freshly forked child, as a `pre_exec` hook.
+    ///
+    /// # Errors
+    ///
+    /// Returns the OS error of the first setup step that fails
+    pub unsafe fn enter(&self) -> io::Result<()> {
+        for &(resource, limit) in &self.rlimits {
+            let rlimit = libc::rlimit {
+                rlim_cur: limit,
+                rlim_max: limit,
+            };
+            check(libc::setrlimit(resource, &rlimit))?;
+        }
+
+        match &self.id_maps {
+            Some((uid_map, gid_map)) => {
+                check(libc::unshare(libc::CLONE_NEWUSER | NAMESPACE_FLAGS))?;
+                write_file(c"/proc/self/setgroups", b"deny")?;
+                write_file(c"/proc/self/uid_map", uid_map)?;
+                write_file(c"/proc/self/gid_map", gid_map)?;
+            }
+            None => {
+                check(libc::unshare(NAMESPACE_FLAGS))?;
+            }
+        }
+
+        // Keep the /proc mount below from propagating to the host
+        check(libc::mount(
+            std::ptr::null(),
+            c"/".as_ptr(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        ))?;
+
+        let pid = check(libc::fork())?;
+        if pid > 0 {
+            wait_and_exit(pid);
+        }
+
+        // Now PID 1 of the new namespace
+        check(libc::prctl(
+            libc::PR_SET_PDEATHSIG,
+            libc::SIGKILL as libc::c_ulong,
+        ))?;
+        check(libc::mount(
+            c"proc".as_ptr(),
+            c"/proc".as_ptr(),
+            c"proc".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            std::ptr::null(),
+        ))?;
+
+        if let Some(filter) = &self.seccomp {
+            let program = libc::sock_fprog {
+                // The filter is a few dozen instructions
+                #[allow(clippy::cast_possible_truncation)]
+                len: filter.len() as libc::c_ushort,
+                filter: filter.as_ptr().cast_mut(),
+            };
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            check(libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER,
+                &program as *const libc::sock_fprog,
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wait for the job process and exit with its status, never returning
+///
+/// Descriptors other than stdio are closed first: the parent learns that
+/// `exec` succeeded when every copy of its status pipe is closed, and this
+/// process would otherwise hold one until the job finishes.
+unsafe fn wait_and_exit(pid: libc::pid_t) -> ! {
+    if libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) != 0 {
+        for fd in 3..1024 {
+            libc::close(fd);
+        }
+    }
+
+    let mut status = 0;
+    while libc::waitpid(pid, &mut status, 0) < 0 {
+        if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            libc::_exit(1);
+        }
+    }
+
+    if libc::WIFEXITED(status) {
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+    libc::_exit(128 + libc::WTERMSIG(status));
+}
+
+/// Write a whole buffer to a file such as `/proc/self/uid_map`
+unsafe fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+    let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+    let written = libc::write(fd, data.as_ptr().cast(), data.len());
+    libc::close(fd);
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Turn a `-1` syscall return into the current OS error
+fn check<T: PartialOrd + Default>(ret: T) -> io::Result<T> {
+    if ret < T::default() {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Build a seccomp program denying [`DENIED_SYSCALLS`] with `EPERM`
+///
+/// Syscalls from any other architecture (e.g. 32-bit compat calls, which
+/// have different numbers) kill the process.
+fn seccomp_filter() -> Option<Vec<libc::sock_filter>> {
+    let arch = AUDIT_ARCH?;
+    let errno = libc::SECCOMP_RET_ERRNO | (libc::EPERM as u32);
+
+    let mut filter = vec![
+        bpf_stmt(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            SECCOMP_DATA_ARCH,
+        ),
+        bpf_jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, arch, 1, 0),
+        bpf_stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, SECCOMP_DATA_NR),
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    filter.extend([
+        bpf_jump(
+            libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+            X32_SYSCALL_BIT,
+            0,
+            1,
+        ),
+        bpf_stmt(libc::BPF_RET | libc::BPF_K, errno),
+    ]);
+
+    for &nr in DENIED_SYSCALLS {
+        // Syscall numbers are small and non-negative
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let nr = nr as u32;
+        filter.push(bpf_jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            nr,
+            0,
+            1,
+        ));
+        filter.push(bpf_stmt(libc::BPF_RET | libc::BPF_K, errno));
+    }
+
+    filter.push(bpf_stmt(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ALLOW,
+    ));
+    Some(filter)
+}
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    bpf_jump(code, k, 0, 0)
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        // BPF opcodes fit in 16 bits
+        #[allow(clippy::cast_possible_truncation)]
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_converts_limits() {
+        let limits = ResourceLimits {
+            memory_mb: Some(256),
+            cpu_secs: Some(5),
+            max_processes: None,
+        };
+        let setup = NamespaceSetup::new(&limits, false);
+        assert_eq!(
+            setup.rlimits,
+            vec![(libc::RLIMIT_AS, 256 * 1024 * 1024), (libc::RLIMIT_CPU, 5)]
+        );
+        assert!(setup.seccomp.is_none());
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn test_seccomp_filter_ends_with_allow() {
+        let filter = seccomp_filter().unwrap();
+        let last = filter.last().unwrap();
+        assert_eq!(last.k, libc::SECCOMP_RET_ALLOW);
+        assert!(filter.len() > DENIED_SYSCALLS.len() * 2);
+    }
+}
@@ -0,0 +1,336 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::mpsc;
+
+/// Default cap on captured output per stream (10MB)
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Size of each read from a child's output pipe
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Output stream of a running job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    /// Stream name as used in AGQ log keys
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// How captured output is represented when posted to AGQ
+///
+/// Output that is valid UTF-8 is posted as-is. Anything else (images, other
+/// binary AU outputs) is base64-encoded rather than lossily converted, and the
+/// encoding is stored next to the output so consumers can decode it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "base64")]
+    Base64,
+}
+
+impl OutputEncoding {
+    /// Encoding name as stored in AGQ
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Base64 => "base64",
+        }
+    }
+
+    /// Parse an encoding name stored in AGQ, treating unknown names as UTF-8
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        if name == Self::Base64.as_str() {
+            Self::Base64
+        } else {
+            Self::Utf8
+        }
+    }
+
+    /// Convert captured bytes to text, choosing the encoding that preserves them
+    #[must_use]
+    pub fn encode(data: Vec<u8>) -> (String, Self) {
+        match String::from_utf8(data) {
+            Ok(text) => (text, Self::Utf8),
+            Err(e) => (
+                base64::engine::general_purpose::STANDARD.encode(e.as_bytes()),
+                Self::Base64,
+            ),
+        }
+    }
+
+    /// Recover the original bytes from text produced by `encode`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if base64 text is malformed
+    pub fn decode(self, text: String) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Self::Utf8 => Ok(text.into_bytes()),
+            Self::Base64 => base64::engine::general_purpose::STANDARD.decode(text),
+        }
+    }
+}
+
+/// Encodings of a job's posted stdout and stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultEncoding {
+    pub stdout: OutputEncoding,
+    pub stderr: OutputEncoding,
+}
+
+/// Whether a job's stdout and stderr were cut off at the capture cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultTruncation {
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+/// A chunk of job output, streamed while the job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogChunk {
+    pub stream: LogStream,
+    pub data: Vec<u8>,
+}
+
+/// How a job's output is captured
+///
+/// Each stream keeps at most `max_bytes`; anything beyond that is dropped
+/// and replaced with a truncation marker, so a chatty job cannot exhaust the
+/// worker's memory. The same cap applies to chunks sent to `sink`.
+#[derive(Debug, Clone)]
+pub struct OutputCapture {
+    pub max_bytes: usize,
+    pub sink: Option<mpsc::Sender<LogChunk>>,
+}
+
+impl Default for OutputCapture {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            sink: None,
+        }
+    }
+}
+
+impl OutputCapture {
+    /// Read a child output stream to EOF, capturing and streaming it
+    ///
+    /// Output past the cap is still drained so the child never blocks on a
+    /// full pipe.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from the pipe fails
+    pub async fn capture<R>(&self, mut reader: R, stream: LogStream) -> std::io::Result<Vec<u8>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut captured = Vec::new();
+        let mut truncated = false;
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+
+            if truncated {
+                continue;
+            }
+
+            let mut keep = n.min(self.max_bytes - captured.len());
+            if keep < n {
+                // Don't let truncation turn text output into invalid UTF-8
+                keep = utf8_boundary(&buf[..n], keep);
+            }
+            captured.extend_from_slice(&buf[..keep]);
+            self.emit(stream, &buf[..keep]).await;
+
+            if keep < n {
+                truncated = true;
+                let marker = truncation_marker(self.max_bytes);
+                captured.extend_from_slice(marker.as_bytes());
+                self.emit(stream, marker.as_bytes()).await;
+            }
+        }
+
+        Ok(captured)
+    }
+
+    /// Whether output returned by [`OutputCapture::capture`] was truncated
+    ///
+    /// Kept output never exceeds `max_bytes`, so only a truncation marker
+    /// can take it past the cap.
+    #[must_use]
+    pub fn truncated(&self, captured: &[u8]) -> bool {
+        captured.len() > self.max_bytes
+    }
+
+    /// Forward a chunk to the sink, if any
+    async fn emit(&self, stream: LogStream, data: &[u8]) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+
+        if data.is_empty() {
+            return;
+        }
+
+        // A closed receiver only means nobody is listening anymore; the
+        // output is still captured for the final result.
+        let _ = sink
+            .send(LogChunk {
+                stream,
+                data: data.to_vec(),
+            })
+            .await;
+    }
+}
+
+/// Move a cut point back so it does not split a UTF-8 multi-byte sequence
+///
+/// Only the last few bytes before `cut` are inspected, so binary data is
+/// shortened by at most three bytes.
+fn utf8_boundary(data: &[u8], cut: usize) -> usize {
+    let start = cut.saturating_sub(3);
+    for i in (start..cut).rev() {
+        let byte = data[i];
+        if byte & 0xC0 == 0x80 {
+            // Continuation byte, keep looking for the sequence start
+            continue;
+        }
+        let len = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if i + len > cut { i } else { cut };
+    }
+    cut
+}
+
+/// Marker appended to output that exceeded the capture cap
+fn truncation_marker(max_bytes: usize) -> String {
+    format!("\n[agw: output truncated after {max_bytes} bytes]\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_capture_under_limit() {
+        let capture = OutputCapture::default();
+        let output = capture
+            .capture(&b"hello\n"[..], LogStream::Stdout)
+            .await
+            .unwrap();
+        assert_eq!(output, b"hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_capture_reports_truncation() {
+        let capture = OutputCapture {
+            max_bytes: 4,
+            sink: None,
+        };
+        let full = capture
+            .capture(&b"abcd"[..], LogStream::Stdout)
+            .await
+            .unwrap();
+        assert!(!capture.truncated(&full));
+        let cut = capture
+            .capture(&b"abcdef"[..], LogStream::Stdout)
+            .await
+            .unwrap();
+        assert!(capture.truncated(&cut));
+    }
+
+    #[tokio::test]
+    async fn test_capture_truncates_at_limit() {
+        let capture = OutputCapture {
+            max_bytes: 4,
+            sink: None,
+        };
+        let output = capture
+            .capture(&b"abcdefgh"[..], LogStream::Stdout)
+            .await
+            .unwrap();
+
+        let expected = format!("abcd{}", truncation_marker(4));
+        assert_eq!(output, expected.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_capture_truncation_keeps_utf8_intact() {
+        let capture = OutputCapture {
+            max_bytes: 4,
+            sink: None,
+        };
+        // "ab" followed by a 3-byte character straddling the cap
+        let output = capture
+            .capture("ab\u{20ac}cd".as_bytes(), LogStream::Stdout)
+            .await
+            .unwrap();
+
+        let expected = format!("ab{}", truncation_marker(4));
+        assert_eq!(output, expected.as_bytes());
+    }
+
+    #[test]
+    fn test_output_encoding_round_trip() {
+        let (text, encoding) = OutputEncoding::encode(b"hello".to_vec());
+        assert_eq!((text.as_str(), encoding), ("hello", OutputEncoding::Utf8));
+
+        let png_header = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let (text, encoding) = OutputEncoding::encode(png_header.clone());
+        assert_eq!(encoding, OutputEncoding::Base64);
+        assert_eq!(text, "iVBORw0KGgo=");
+        assert_eq!(
+            OutputEncoding::from_name(encoding.as_str())
+                .decode(text)
+                .unwrap(),
+            png_header
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_streams_chunks_to_sink() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let capture = OutputCapture {
+            max_bytes: 4,
+            sink: Some(tx),
+        };
+
+        capture
+            .capture(&b"abcdefgh"[..], LogStream::Stderr)
+            .await
+            .unwrap();
+        drop(capture);
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.stream, LogStream::Stderr);
+        assert_eq!(first.data, b"abcd");
+
+        let marker = rx.recv().await.unwrap();
+        assert_eq!(marker.data, truncation_marker(4).as_bytes());
+        assert!(rx.recv().await.is_none());
+    }
+}
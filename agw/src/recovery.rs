@@ -0,0 +1,70 @@
+use crate::error::AgwResult;
+use crate::resp::RespClient;
+
+/// What a worker does on startup with jobs it claimed before a crash
+///
+/// Claims are matched by worker ID, so recovery only finds jobs when the
+/// worker is restarted with the same fixed `WORKER_ID`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RecoveryMode {
+    /// Push the jobs back onto the ready queue for any worker to pick up
+    #[default]
+    Requeue,
+    /// Run the jobs again on this worker before fetching new ones
+    Resume,
+    /// Leave the jobs in the processing queue
+    Off,
+}
+
+/// AGQ key recording which worker claimed a job from the ready queue
+#[must_use]
+pub fn claim_key(job_id_raw: &str) -> String {
+    format!("job:{job_id_raw}:worker")
+}
+
+/// Whether a job status means the job's result was already posted
+#[must_use]
+pub fn is_terminal_status(status: &str) -> bool {
+    matches!(
+        status,
+        "completed" | "failed" | "timeout" | "policy_violation"
+    )
+}
+
+/// Find the jobs in a processing queue that were claimed by a worker
+///
+/// # Errors
+///
+/// Returns an error if the queue or a claim cannot be read from AGQ
+pub async fn claimed_jobs(
+    client: &mut RespClient,
+    processing_queue: &str,
+    worker_id: &str,
+) -> AgwResult<Vec<String>> {
+    let mut claimed = Vec::new();
+    for job_id_raw in client.lrange(processing_queue, 0, -1).await? {
+        let owner = client.get(&claim_key(&job_id_raw)).await?;
+        if owner.as_deref() == Some(worker_id) && !claimed.contains(&job_id_raw) {
+            claimed.push(job_id_raw);
+        }
+    }
+    Ok(claimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_key() {
+        assert_eq!(claim_key("job_42"), "job:job_42:worker");
+    }
+
+    #[test]
+    fn test_is_terminal_status() {
+        assert!(is_terminal_status("completed"));
+        assert!(is_terminal_status("policy_violation"));
+        assert!(!is_terminal_status("running"));
+        assert!(!is_terminal_status("pending"));
+    }
+}
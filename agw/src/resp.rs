@@ -2,9 +2,18 @@
 #![allow(clippy::module_name_repetitions)]
 
 use crate::error::{AgwError, AgwResult};
+use crate::result::JobResult;
+use crate::system::SystemMetrics;
+use crate::workspace::Artifact;
 use redis::{aio::ConnectionManager, Client, Cmd};
 use tracing::{debug, info};
 
+/// Largest stdout or stderr stored inline at `job:<id>:<stream>` (256 KiB)
+///
+/// Matches AGQ's default `--max-inline-result`. Larger output is stored with
+/// ARTIFACT.PUT and its hash is stored at `job:<id>:<stream>_artifact`.
+pub const INLINE_OUTPUT_LIMIT: usize = 256 * 1024;
+
 /// RESP client for communicating with AGQ
 ///
 /// Clone is safe and efficient because `ConnectionManager` uses Arc internally,
@@ -72,15 +81,26 @@ impl RespClient {
 
     /// Send a heartbeat to AGQ
     ///
+    /// When `metrics` is given it is sent along as a JSON payload so AGQ can
+    /// track worker health.
+    ///
     /// # Errors
     ///
     /// Returns an error if the RESP protocol command fails
-    pub async fn heartbeat(&mut self, worker_id: &str) -> AgwResult<()> {
+    pub async fn heartbeat(
+        &mut self,
+        worker_id: &str,
+        metrics: Option<&SystemMetrics>,
+    ) -> AgwResult<()> {
         debug!("Sending heartbeat for worker {worker_id}");
 
-        let response: String = Cmd::new()
-            .arg("PING")
-            .arg(worker_id)
+        let mut cmd = Cmd::new();
+        cmd.arg("PING").arg(worker_id);
+        if let Some(metrics) = metrics {
+            cmd.arg(metrics.to_json());
+        }
+
+        let response: String = cmd
             .query_async(&mut self.connection)
             .await
             .map_err(|e| AgwError::RespProtocol(format!("PING failed: {e}")))?;
@@ -93,6 +113,7 @@ impl RespClient {
     ///
     /// Stores the tool list in the `worker:<id>:tools` key as a comma-separated string.
     /// This enables AGQ to perform capability-based job routing in the future.
+    /// An empty list clears the key.
     ///
     /// # Errors
     ///
@@ -101,9 +122,11 @@ impl RespClient {
         const MAX_TOOLS: usize = 100;
         const MAX_TOOL_NAME_LENGTH: usize = 64;
 
+        let key = format!("worker:{worker_id}:tools");
+
         if tools.is_empty() {
-            debug!("No tools to register for worker {}", worker_id);
-            return Ok(());
+            debug!("Clearing registered tools for worker {worker_id}");
+            return self.set(&key, "").await;
         }
 
         // Validate number of tools
@@ -144,7 +167,6 @@ impl RespClient {
             }
         }
 
-        let key = format!("worker:{worker_id}:tools");
         let value = tools.join(",");
 
         info!(
@@ -184,6 +206,65 @@ impl RespClient {
         Ok(())
     }
 
+    /// Ready queues this worker can take jobs from, most specific first
+    ///
+    /// AGQ matches the worker's registered tags against the requirements of
+    /// each ready queue. Priority sub-lists (`:high`, `:low`) are not listed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn worker_queues(&mut self, worker_id: &str) -> AgwResult<Vec<String>> {
+        let queues: Vec<String> = Cmd::new()
+            .arg("WORKER.QUEUES")
+            .arg(worker_id)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("WORKER.QUEUES failed: {e}")))?;
+
+        debug!("Worker {worker_id} takes jobs from {queues:?}");
+        Ok(queues)
+    }
+
+    /// Register the `--describe` model card of one of the worker's tools
+    ///
+    /// AGQ stores it at `worker:<id>:manifests` and lists it in its tool
+    /// catalog while the worker is live.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails or AGQ rejects the card
+    pub async fn register_manifest(&mut self, worker_id: &str, card: &str) -> AgwResult<()> {
+        let name: String = Cmd::new()
+            .arg("TOOLS.REGISTER")
+            .arg(worker_id)
+            .arg(card)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("TOOLS.REGISTER failed: {e}")))?;
+
+        debug!("Registered model card for {name} on worker {worker_id}");
+        Ok(())
+    }
+
+    /// Remove a model card registered with [`RespClient::register_manifest`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn unregister_manifest(&mut self, worker_id: &str, name: &str) -> AgwResult<()> {
+        let _removed: i64 = Cmd::new()
+            .arg("HDEL")
+            .arg(format!("worker:{worker_id}:manifests"))
+            .arg(name)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("HDEL failed: {e}")))?;
+
+        debug!("Removed model card for {name} on worker {worker_id}");
+        Ok(())
+    }
+
     /// Blocking pop from queue using BRPOP
     ///
     /// Blocks until a job is available in the queue or timeout is reached.
@@ -195,7 +276,6 @@ impl RespClient {
     /// # Errors
     ///
     /// Returns an error if the RESP protocol command fails or queue name doesn't match
-    #[allow(dead_code)]
     pub async fn brpop(&mut self, queue: &str, timeout: u64) -> AgwResult<Option<String>> {
         debug!(
             "Blocking pop from queue {} with timeout {}s",
@@ -274,6 +354,34 @@ impl RespClient {
         }
     }
 
+    /// Atomically pop from the tail of one list and push onto another
+    ///
+    /// The non-blocking form of [`Self::brpoplpush`], used to check
+    /// higher-priority queues before blocking on the default one. Returns
+    /// `None` if the source list is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn rpoplpush(
+        &mut self,
+        source: &str,
+        destination: &str,
+    ) -> AgwResult<Option<String>> {
+        let result: Option<String> = Cmd::new()
+            .arg("RPOPLPUSH")
+            .arg(source)
+            .arg(destination)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("RPOPLPUSH failed: {e}")))?;
+
+        if result.is_some() {
+            debug!("Received job from {} (moved to {})", source, destination);
+        }
+        Ok(result)
+    }
+
     /// Remove count occurrences of element from list
     ///
     /// Used to remove successfully completed jobs from the processing queue.
@@ -304,6 +412,51 @@ impl RespClient {
         Ok(removed_count)
     }
 
+    /// Get a range of elements from a list
+    ///
+    /// Indices are inclusive and may be negative to count from the tail, so
+    /// `0, -1` returns the whole list.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn lrange(&mut self, key: &str, start: i64, stop: i64) -> AgwResult<Vec<String>> {
+        debug!("Reading range {}..={} of list {}", start, stop, key);
+
+        let elements: Vec<String> = Cmd::new()
+            .arg("LRANGE")
+            .arg(key)
+            .arg(start)
+            .arg(stop)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("LRANGE failed: {e}")))?;
+
+        Ok(elements)
+    }
+
+    /// Push an element onto the head of a list
+    ///
+    /// Used to requeue jobs that hit a transient failure. Returns the new
+    /// length of the list.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn lpush(&mut self, key: &str, element: &str) -> AgwResult<i64> {
+        debug!("Pushing element onto list {}", key);
+
+        let length: i64 = Cmd::new()
+            .arg("LPUSH")
+            .arg(key)
+            .arg(element)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("LPUSH failed: {e}")))?;
+
+        Ok(length)
+    }
+
     /// Get job metadata from AGQ
     ///
     /// Fetches job information including job_id, plan_id, input data, and status.
@@ -326,6 +479,27 @@ impl RespClient {
         Ok(json)
     }
 
+    /// Get a job's environment from AGQ, with the secrets AGQ holds resolved
+    ///
+    /// Secret references AGQ has no value for are left for the worker's own
+    /// secrets file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails, the job is not
+    /// ready or running, or the reply is not JSON
+    pub async fn job_env(&mut self, job_id: &str) -> AgwResult<serde_json::Value> {
+        let json: String = Cmd::new()
+            .arg("JOB.ENV")
+            .arg(job_id)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("JOB.ENV failed: {e}")))?;
+
+        serde_json::from_str(&json)
+            .map_err(|e| AgwError::RespProtocol(format!("Invalid JOB.ENV reply: {e}")))
+    }
+
     /// Get plan from AGQ
     ///
     /// Fetches plan template including tasks.
@@ -348,6 +522,26 @@ impl RespClient {
         Ok(json)
     }
 
+    /// Get a value from AGQ
+    ///
+    /// Returns None if the key does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the RESP protocol command fails
+    pub async fn get(&mut self, key: &str) -> AgwResult<Option<String>> {
+        debug!("Getting key: {}", key);
+
+        let value: Option<String> = Cmd::new()
+            .arg("GET")
+            .arg(key)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("GET failed: {e}")))?;
+
+        Ok(value)
+    }
+
     /// Set a key-value pair in AGQ
     ///
     /// # Errors
@@ -376,7 +570,10 @@ impl RespClient {
 
     /// Post job execution results to AGQ with retry logic
     ///
-    /// Stores stdout, stderr, and status for the given job ID.
+    /// Stores stdout, stderr, their encodings (`job:<id>:stdout_encoding`,
+    /// `job:<id>:stderr_encoding`), the structured result envelope
+    /// (`job:<id>:result`), and status for the given job ID. Output larger
+    /// than [`INLINE_OUTPUT_LIMIT`] goes to the artifact store instead.
     /// Retries up to 3 times with exponential backoff on failure to ensure
     /// results are not lost due to transient network issues.
     ///
@@ -394,7 +591,7 @@ impl RespClient {
         job_id: &str,
         stdout: &str,
         stderr: &str,
-        status: &str,
+        result: &JobResult,
     ) -> AgwResult<()> {
         const MAX_RETRIES: u32 = 3;
         const INITIAL_BACKOFF_MS: u64 = 100;
@@ -403,7 +600,7 @@ impl RespClient {
 
         for attempt in 0..MAX_RETRIES {
             match self
-                .post_job_result_once(job_id, stdout, stderr, status)
+                .post_job_result_once(job_id, stdout, stderr, result)
                 .await
             {
                 Ok(()) => return Ok(()),
@@ -436,8 +633,9 @@ impl RespClient {
         job_id: &str,
         stdout: &str,
         stderr: &str,
-        status: &str,
+        result: &JobResult,
     ) -> AgwResult<()> {
+        let status = result.status.as_str();
         debug!("Posting results for job {}", job_id);
 
         // Validate job ID to prevent Redis key injection
@@ -454,19 +652,33 @@ impl RespClient {
         }
 
         // Validate status is one of the expected values
-        if !matches!(status, "completed" | "failed" | "pending" | "running") {
+        if !matches!(
+            status,
+            "completed" | "failed" | "timeout" | "policy_violation" | "pending" | "running"
+        ) {
             return Err(AgwError::RespProtocol(format!(
                 "Invalid job status: {status}"
             )));
         }
 
-        // Set stdout
-        let stdout_key = format!("job:{}:stdout", job_id);
-        self.set(&stdout_key, stdout).await?;
-
-        // Set stderr
-        let stderr_key = format!("job:{}:stderr", job_id);
-        self.set(&stderr_key, stderr).await?;
+        // Set stdout and stderr
+        self.set_output(job_id, "stdout", stdout).await?;
+        self.set_output(job_id, "stderr", stderr).await?;
+
+        // Set encodings and the result envelope, before status so they are
+        // in place once the job is seen as finished
+        self.set(
+            &format!("job:{}:stdout_encoding", job_id),
+            result.stdout.encoding.as_str(),
+        )
+        .await?;
+        self.set(
+            &format!("job:{}:stderr_encoding", job_id),
+            result.stderr.encoding.as_str(),
+        )
+        .await?;
+        self.set(&format!("job:{}:result", job_id), &result.to_json())
+            .await?;
 
         // Set status
         let status_key = format!("job:{}:status", job_id);
@@ -476,8 +688,134 @@ impl RespClient {
         Ok(())
     }
 
+    /// Store one output stream of a job, offloading it if it is too large
+    ///
+    /// Offloaded output leaves `job:<id>:<stream>` empty and stores the
+    /// artifact hash at `job:<id>:<stream>_artifact`.
+    async fn set_output(&mut self, job_id: &str, stream: &str, output: &str) -> AgwResult<()> {
+        let key = format!("job:{job_id}:{stream}");
+        if output.len() <= INLINE_OUTPUT_LIMIT {
+            return self.set(&key, output).await;
+        }
+
+        let hash = self.artifact_put(output.as_bytes()).await?;
+        debug!(
+            "Stored {} bytes of {stream} for job {job_id} as artifact {hash}",
+            output.len()
+        );
+        self.set(&format!("{key}_artifact"), &hash).await?;
+        self.set(&key, "").await
+    }
+
+    /// Fetch one output stream of a finished job
+    ///
+    /// Follows `job:<id>:<stream>_artifact` to the artifact store when the
+    /// output was too large to store inline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a command fails or the offloaded output is missing
+    pub async fn job_output(&mut self, job_id: &str, stream: &str) -> AgwResult<String> {
+        let key = format!("job:{job_id}:{stream}");
+        let Some(hash) = self.get(&format!("{key}_artifact")).await? else {
+            return Ok(self.get(&key).await?.unwrap_or_default());
+        };
+
+        let data = self.artifact_get(&hash).await?.ok_or_else(|| {
+            AgwError::RespProtocol(format!("Artifact {hash} for {key} is missing"))
+        })?;
+        String::from_utf8(data)
+            .map_err(|e| AgwError::RespProtocol(format!("Artifact {hash} is not text: {e}")))
+    }
+
+    /// Append a chunk of a running job's output to its AGQ log
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the job ID is invalid or the JOB.LOG command fails
+    pub async fn job_log(&mut self, job_id: &str, stream: &str, chunk: &[u8]) -> AgwResult<()> {
+        if job_id.is_empty() || job_id.contains(':') {
+            return Err(AgwError::RespProtocol(format!(
+                "Invalid job ID for log streaming: {job_id}"
+            )));
+        }
+
+        let _: i64 = Cmd::new()
+            .arg("JOB.LOG")
+            .arg(job_id)
+            .arg(stream)
+            .arg(chunk)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("JOB.LOG failed: {e}")))?;
+
+        Ok(())
+    }
+
+    /// Store an artifact in AGQ's content-addressed artifact store
+    ///
+    /// Returns the SHA-256 hash AGQ stores the data under.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ARTIFACT.PUT command fails
+    pub async fn artifact_put(&mut self, data: &[u8]) -> AgwResult<String> {
+        Cmd::new()
+            .arg("ARTIFACT.PUT")
+            .arg(data)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("ARTIFACT.PUT failed: {e}")))
+    }
+
+    /// Fetch an artifact from AGQ's content-addressed artifact store
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ARTIFACT.GET command fails
+    pub async fn artifact_get(&mut self, hash: &str) -> AgwResult<Option<Vec<u8>>> {
+        Cmd::new()
+            .arg("ARTIFACT.GET")
+            .arg(hash)
+            .query_async(&mut self.connection)
+            .await
+            .map_err(|e| AgwError::RespProtocol(format!("ARTIFACT.GET failed: {e}")))
+    }
+
+    /// Upload files a job left in its workspace
+    ///
+    /// Each artifact is stored with ARTIFACT.PUT, and a JSON manifest of
+    /// `{name, sha256, size}` references is stored at `job:<id>:artifacts`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the job ID is invalid or an upload fails
+    pub async fn post_artifacts(&mut self, job_id: &str, artifacts: &[Artifact]) -> AgwResult<()> {
+        if job_id.is_empty() || job_id.contains(':') {
+            return Err(AgwError::RespProtocol(format!(
+                "Invalid job ID for artifact upload: {job_id}"
+            )));
+        }
+
+        let mut manifest = Vec::with_capacity(artifacts.len());
+        for artifact in artifacts {
+            let sha256 = self.artifact_put(&artifact.data).await?;
+            manifest.push(serde_json::json!({
+                "name": artifact.name,
+                "sha256": sha256,
+                "size": artifact.data.len(),
+            }));
+        }
+
+        let manifest = serde_json::Value::Array(manifest).to_string();
+        self.set(&format!("job:{job_id}:artifacts"), &manifest)
+            .await?;
+
+        info!("Uploaded {} artifacts for job {job_id}", artifacts.len());
+        Ok(())
+    }
+
     /// Get the underlying connection (for future operations)
-    #[allow(dead_code)]
     pub fn connection(&mut self) -> &mut ConnectionManager {
         &mut self.connection
     }
@@ -778,7 +1116,7 @@ mod tests {
 
         // Timeout should allow heartbeats to continue
         assert_eq!(TIMEOUT, 5);
-        assert!(TIMEOUT > 0); // Not blocking forever
-        assert!(TIMEOUT < 60); // Short enough for responsive heartbeats
+        const { assert!(TIMEOUT > 0) }; // Not blocking forever
+        const { assert!(TIMEOUT < 60) }; // Short enough for responsive heartbeats
     }
 }
@@ -0,0 +1,192 @@
+use crate::executor::TaskResult;
+use crate::output::OutputEncoding;
+use crate::sandbox::{ResourceLimits, SandboxConfig, SandboxKind};
+use crate::system::ResourceUsage;
+use serde::{Deserialize, Serialize};
+
+/// Version of the result envelope format
+///
+/// Bumped when a field changes meaning or is removed; new optional fields
+/// do not change the version.
+pub const RESULT_VERSION: u32 = 1;
+
+/// Structured outcome of a job, posted to AGQ as `job:<id>:result`
+///
+/// The raw output stays in `job:<id>:stdout` and `job:<id>:stderr`, which
+/// the envelope references, so large output is not stored twice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobResult {
+    /// Envelope format version, see [`RESULT_VERSION`]
+    pub version: u32,
+    pub job_id: String,
+    /// Terminal status: `completed`, `failed`, `timeout`, or `policy_violation`
+    pub status: String,
+    /// Process exit code, absent if the process never exited normally
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    pub stdout: OutputRef,
+    pub stderr: OutputRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_usage: Option<ResourceUsage>,
+    /// Isolation the job ran under, absent if it never started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxPolicy>,
+    /// Why the worker could not run the job, for failures before execution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where a job's output stream is stored and how to read it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputRef {
+    /// AGQ key holding the output
+    pub key: String,
+    pub encoding: OutputEncoding,
+    /// Length of the stored (possibly encoded) output in bytes
+    pub bytes: usize,
+    /// Whether output beyond the worker's cap was dropped
+    pub truncated: bool,
+}
+
+/// Sandbox settings applied to a job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SandboxPolicy {
+    pub kind: SandboxKind,
+    pub limits: ResourceLimits,
+    pub seccomp: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u32>,
+}
+
+impl JobResult {
+    /// Envelope for a job that ran to completion, failure, or timeout
+    #[must_use]
+    pub fn from_task(
+        job_id: &str,
+        status: &str,
+        result: &TaskResult,
+        sandbox: &SandboxConfig,
+        timeout_secs: Option<u32>,
+    ) -> Self {
+        Self {
+            version: RESULT_VERSION,
+            job_id: job_id.to_string(),
+            status: status.to_string(),
+            exit_code: (result.exit_code >= 0).then_some(result.exit_code),
+            duration_ms: result.execution_time_ms,
+            stdout: OutputRef::new(
+                job_id,
+                "stdout",
+                &result.stdout,
+                result.encoding.stdout,
+                result.truncated.stdout,
+            ),
+            stderr: OutputRef::new(
+                job_id,
+                "stderr",
+                &result.stderr,
+                result.encoding.stderr,
+                result.truncated.stderr,
+            ),
+            resource_usage: result.resource_usage,
+            sandbox: Some(SandboxPolicy {
+                kind: sandbox.kind,
+                limits: sandbox.limits,
+                seccomp: sandbox.seccomp,
+                timeout_secs,
+            }),
+            error: None,
+        }
+    }
+
+    /// Envelope for a job the worker could not run
+    ///
+    /// The error message is also posted as the job's stderr.
+    #[must_use]
+    pub fn failure(job_id: &str, status: &str, error: &str) -> Self {
+        Self {
+            version: RESULT_VERSION,
+            job_id: job_id.to_string(),
+            status: status.to_string(),
+            exit_code: None,
+            duration_ms: 0,
+            stdout: OutputRef::new(job_id, "stdout", "", OutputEncoding::Utf8, false),
+            stderr: OutputRef::new(job_id, "stderr", error, OutputEncoding::Utf8, false),
+            resource_usage: None,
+            sandbox: None,
+            error: Some(error.to_string()),
+        }
+    }
+
+    /// Serialize for posting to AGQ
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+impl OutputRef {
+    fn new(
+        job_id: &str,
+        stream: &str,
+        output: &str,
+        encoding: OutputEncoding,
+        truncated: bool,
+    ) -> Self {
+        Self {
+            key: format!("job:{job_id}:{stream}"),
+            encoding,
+            bytes: output.len(),
+            truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_task_result_envelope() {
+        let mut task = TaskResult::new(1, "hi\n".to_string(), String::new(), 0);
+        task.execution_time_ms = 42;
+        task.truncated.stdout = true;
+        let sandbox = SandboxConfig {
+            limits: ResourceLimits {
+                memory_mb: Some(512),
+                ..ResourceLimits::default()
+            },
+            ..SandboxConfig::default()
+        };
+
+        let result = JobResult::from_task("job_1", "completed", &task, &sandbox, Some(30));
+        let value: serde_json::Value = serde_json::from_str(&result.to_json()).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "version": RESULT_VERSION,
+                "job_id": "job_1",
+                "status": "completed",
+                "exit_code": 0,
+                "duration_ms": 42,
+                "stdout": {"key": "job:job_1:stdout", "encoding": "utf-8", "bytes": 3, "truncated": true},
+                "stderr": {"key": "job:job_1:stderr", "encoding": "utf-8", "bytes": 0, "truncated": false},
+                "sandbox": {"kind": "native", "limits": {"memory_mb": 512}, "seccomp": false, "timeout_secs": 30},
+            })
+        );
+    }
+
+    #[test]
+    fn test_failure_envelope() {
+        let result = JobResult::failure("job_1", "policy_violation", "command 'rm' is denied");
+        assert_eq!(result.exit_code, None);
+        assert_eq!(result.error.as_deref(), Some("command 'rm' is denied"));
+        assert_eq!(result.stderr.bytes, 22);
+        assert!(result.sandbox.is_none());
+
+        let parsed: JobResult = serde_json::from_str(&result.to_json()).unwrap();
+        assert_eq!(parsed, result);
+    }
+}
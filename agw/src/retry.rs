@@ -0,0 +1,197 @@
+use crate::sandbox::{SandboxConfig, SandboxKind};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::ExitStatus;
+
+/// Signal the kernel OOM killer and container runtimes use
+const SIGKILL: i32 = 9;
+
+/// Exit status wrapping sandboxes report for a child killed by `SIGKILL`
+const SIGKILL_EXIT_CODE: i32 = 128 + SIGKILL;
+
+/// A failure caused by the worker's environment rather than the job itself
+///
+/// Jobs that fail this way may succeed on another attempt, possibly on
+/// another worker, so they are requeued instead of reported as failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransientFailure {
+    /// The job's command is not installed on this worker
+    MissingBinary,
+    /// The sandbox or container runtime could not start the job
+    SandboxSetup,
+    /// The job was killed while running under a memory limit
+    OutOfMemory,
+}
+
+impl TransientFailure {
+    /// Classify a job whose commands could not be launched
+    #[must_use]
+    pub fn launch(commands: &[&str], sandbox: &SandboxConfig) -> Self {
+        // Containers resolve the command inside the image, so a launch
+        // failure there is always the runtime's
+        if sandbox.kind != SandboxKind::Container
+            && !commands.iter().all(|command| command_exists(command))
+        {
+            Self::MissingBinary
+        } else {
+            Self::SandboxSetup
+        }
+    }
+
+    /// Classify a job that ran and exited
+    ///
+    /// A `SIGKILL` under a memory limit is taken to be an OOM kill; nothing
+    /// else the worker can observe after exit is treated as transient.
+    #[must_use]
+    pub fn from_exit(status: ExitStatus, sandbox: &SandboxConfig) -> Option<Self> {
+        let killed = status.code() == Some(SIGKILL_EXIT_CODE) || killed_by_signal(status);
+        (killed && sandbox.limits.memory_mb.is_some()).then_some(Self::OutOfMemory)
+    }
+}
+
+impl std::fmt::Display for TransientFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::MissingBinary => "command not found on worker",
+            Self::SandboxSetup => "sandbox setup failed",
+            Self::OutOfMemory => "killed for exceeding its memory limit",
+        })
+    }
+}
+
+/// Retry metadata posted to AGQ as `job:<id>:retry` when a job is requeued
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryHint {
+    /// Attempts that have failed so far
+    pub attempts: u32,
+    /// Attempts the job spec allows in total
+    pub max_attempts: u32,
+    /// Why the last attempt failed
+    pub reason: TransientFailure,
+}
+
+impl RetryHint {
+    /// Hint for requeuing a job after a transient failure
+    ///
+    /// `previous` is the hint left by an earlier attempt, if any. Returns
+    /// `None` once the job has used all the attempts its spec allows.
+    #[must_use]
+    pub fn next(
+        previous: Option<&Self>,
+        max_attempts: Option<u32>,
+        reason: TransientFailure,
+    ) -> Option<Self> {
+        let max_attempts = max_attempts.unwrap_or(1);
+        let attempts = previous.map_or(0, |hint| hint.attempts).saturating_add(1);
+        (attempts < max_attempts).then_some(Self {
+            attempts,
+            max_attempts,
+            reason,
+        })
+    }
+
+    /// Parse a hint previously posted to AGQ
+    #[must_use]
+    pub fn from_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    /// Serialize for posting to AGQ
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Whether a command resolves to a file, either directly or via `PATH`
+fn command_exists(command: &str) -> bool {
+    if command.contains('/') {
+        return Path::new(command).is_file();
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(command).is_file()))
+}
+
+#[cfg(unix)]
+fn killed_by_signal(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(SIGKILL)
+}
+
+#[cfg(not(unix))]
+fn killed_by_signal(_status: ExitStatus) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::ResourceLimits;
+
+    #[test]
+    fn test_launch_failure_classification() {
+        let native = SandboxConfig::default();
+        assert_eq!(
+            TransientFailure::launch(&["sort", "definitely-not-installed-xyz"], &native),
+            TransientFailure::MissingBinary
+        );
+        assert_eq!(
+            TransientFailure::launch(&["sh"], &native),
+            TransientFailure::SandboxSetup
+        );
+
+        let container = SandboxConfig {
+            kind: SandboxKind::Container,
+            ..SandboxConfig::default()
+        };
+        assert_eq!(
+            TransientFailure::launch(&["definitely-not-installed-xyz"], &container),
+            TransientFailure::SandboxSetup
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_oom_kill_requires_memory_limit() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let limited = SandboxConfig {
+            limits: ResourceLimits {
+                memory_mb: Some(64),
+                ..ResourceLimits::default()
+            },
+            ..SandboxConfig::default()
+        };
+        let unlimited = SandboxConfig::default();
+        let killed = ExitStatus::from_raw(SIGKILL);
+        let wrapped = ExitStatus::from_raw(SIGKILL_EXIT_CODE << 8);
+        let failed = ExitStatus::from_raw(1 << 8);
+
+        assert_eq!(
+            TransientFailure::from_exit(killed, &limited),
+            Some(TransientFailure::OutOfMemory)
+        );
+        assert_eq!(
+            TransientFailure::from_exit(wrapped, &limited),
+            Some(TransientFailure::OutOfMemory)
+        );
+        assert_eq!(TransientFailure::from_exit(killed, &unlimited), None);
+        assert_eq!(TransientFailure::from_exit(failed, &limited), None);
+    }
+
+    #[test]
+    fn test_retry_hint_respects_max_attempts() {
+        let reason = TransientFailure::MissingBinary;
+        assert_eq!(RetryHint::next(None, None, reason), None);
+        assert_eq!(RetryHint::next(None, Some(1), reason), None);
+
+        let first = RetryHint::next(None, Some(3), reason).unwrap();
+        assert_eq!(first.attempts, 1);
+        let second = RetryHint::next(Some(&first), Some(3), reason).unwrap();
+        assert_eq!(second.attempts, 2);
+        assert_eq!(RetryHint::next(Some(&second), Some(3), reason), None);
+
+        assert_eq!(RetryHint::from_json(&second.to_json()), Some(second));
+    }
+}
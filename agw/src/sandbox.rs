@@ -1,24 +1,283 @@
 use crate::error::{AgwError, AgwResult};
-use std::process::Output;
+use crate::output::{LogStream, OutputCapture};
+use crate::workspace::WORKDIR_ENV;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tracing::{debug, info};
 
+/// Resource limits applied to a sandboxed job, as defined by the shared
+/// Plan schema
+///
+/// Unset fields leave the corresponding limit at the value inherited from
+/// the worker process. The native Linux sandboxes enforce `max_processes`
+/// with a cgroup, see [`crate::cgroup`].
+pub use agenix_plan::ResourceLimits;
+
+/// Validate that all set limits are non-zero
+///
+/// # Errors
+///
+/// Returns an error naming the first limit that is zero
+pub fn validate_limits(limits: &ResourceLimits) -> AgwResult<()> {
+    for (name, value) in [
+        ("memory_mb", limits.memory_mb),
+        ("cpu_secs", limits.cpu_secs),
+        ("max_processes", limits.max_processes),
+    ] {
+        if value == Some(0) {
+            return Err(AgwError::Worker(format!(
+                "Resource limit {name} must be greater than 0"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Arguments for the `prlimit` wrapper enforcing the memory and CPU limits
+#[cfg(target_os = "linux")]
+fn prlimit_args(limits: &ResourceLimits) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(mb) = limits.memory_mb {
+        args.push(format!("--as={}", mb.saturating_mul(1024 * 1024)));
+    }
+    if let Some(secs) = limits.cpu_secs {
+        args.push(format!("--cpu={secs}"));
+    }
+    args
+}
+
+/// Create the cgroup enforcing a job's process limit, if it has one
+///
+/// A job whose limit cannot be enforced fails rather than running without it.
+#[cfg(target_os = "linux")]
+fn job_cgroup(limits: &ResourceLimits) -> AgwResult<Option<Arc<crate::cgroup::JobCgroup>>> {
+    limits
+        .max_processes
+        .map(|max| {
+            crate::cgroup::JobCgroup::create(max)
+                .map(Arc::new)
+                .map_err(|e| {
+                    AgwError::Worker(format!(
+                        "Cannot enforce the job's limit of {max} processes: {e}"
+                    ))
+                })
+        })
+        .transpose()
+}
+
 /// Trait for sandbox implementations
 #[async_trait::async_trait]
 pub trait Sandbox: Send + Sync {
     /// Run a command within the sandbox
-    async fn run(&self, command: &str, args: &[String], env: &[(String, String)]) -> AgwResult<Output>;
+    ///
+    /// If `stdin` is provided it is written to the child's standard input,
+    /// which is then closed so the command sees EOF. Output is collected
+    /// according to `capture`.
+    async fn run(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: Option<&[u8]>,
+        capture: &OutputCapture,
+    ) -> AgwResult<Output>;
+}
+
+/// Sandbox backend used to run jobs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxKind {
+    /// Platform-native process isolation (namespaces on Linux)
+    #[default]
+    Native,
+    /// Linux namespaces set up in-process, without `unshare` or `prlimit`
+    Namespace,
+    /// Docker/Podman container per job
+    Container,
+}
+
+/// Default image for the container sandbox
+pub const DEFAULT_CONTAINER_IMAGE: &str = "debian:stable-slim";
+
+/// Settings for the container sandbox backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerSettings {
+    /// Container runtime binary (`docker` or `podman`)
+    pub runtime: String,
+    /// Image jobs run in
+    pub image: String,
+    /// Bind mounts in `host:container[:ro|rw]` form
+    pub mounts: Vec<String>,
+}
+
+impl Default for ContainerSettings {
+    fn default() -> Self {
+        Self {
+            runtime: "docker".to_string(),
+            image: DEFAULT_CONTAINER_IMAGE.to_string(),
+            mounts: Vec::new(),
+        }
+    }
+}
+
+/// Everything needed to construct a sandbox for one job
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SandboxConfig {
+    pub kind: SandboxKind,
+    pub container: ContainerSettings,
+    pub limits: ResourceLimits,
+    /// Working directory for the job, exposed to it as `AGW_WORKDIR`
+    pub workdir: Option<PathBuf>,
+    /// Environment variables for the job, which may include resolved secrets
+    pub env: Vec<(String, String)>,
+    /// Deny kernel administration syscalls (namespace sandbox only)
+    pub seccomp: bool,
+    /// Job run in the sandbox, used to name its container
+    pub job_id: Option<String>,
+    /// Custom sandbox constructor, used instead of `kind` when set
+    pub factory: Option<SandboxFactory>,
+}
+
+/// Constructor for a custom sandbox, injected by programs embedding a worker
+///
+/// It receives the job's full configuration, including limits, working
+/// directory, and environment.
+#[derive(Clone)]
+pub struct SandboxFactory(Arc<BuildSandbox>);
+
+type BuildSandbox = dyn Fn(&SandboxConfig) -> Box<dyn Sandbox> + Send + Sync;
+
+impl SandboxFactory {
+    /// Wrap a function building the sandbox for a job
+    pub fn new<F>(build: F) -> Self
+    where
+        F: Fn(&SandboxConfig) -> Box<dyn Sandbox> + Send + Sync + 'static,
+    {
+        Self(Arc::new(build))
+    }
+}
+
+impl std::fmt::Debug for SandboxFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SandboxFactory(..)")
+    }
+}
+
+impl PartialEq for SandboxFactory {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
 }
 
-/// Factory to create the appropriate sandbox for the current platform
-pub fn create_sandbox() -> Box<dyn Sandbox> {
+impl Eq for SandboxFactory {}
+
+/// Mount point of the job workspace inside a container
+const CONTAINER_WORKDIR: &str = "/workspace";
+
+/// Spawn a prepared command, feed optional stdin, and collect its output
+///
+/// Stdin is written while stdout and stderr are read, so a child producing
+/// output before consuming its input cannot deadlock on a full pipe.
+async fn spawn_with_stdin(
+    mut cmd: Command,
+    stdin: Option<&[u8]>,
+    capture: &OutputCapture,
+) -> std::io::Result<Output> {
+    cmd.stdin(if stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    // Dropping the run future (e.g. on timeout) kills the spawned process.
+    // Its descendants are only taken down by the sandbox: the namespace
+    // sandboxes kill everything in the job's PID namespace with it, and the
+    // container sandbox removes the container separately.
+    cmd.kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+
+    let stdin_pipe = child.stdin.take();
+    let (Some(stdout_pipe), Some(stderr_pipe)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(std::io::Error::other("child output pipes unavailable"));
+    };
+
+    let write_stdin = async {
+        if let (Some(data), Some(mut pipe)) = (stdin, stdin_pipe) {
+            // A child that exits without reading its input closes the pipe early;
+            // that is not an execution failure, so a broken pipe is ignored here.
+            if let Err(e) = pipe.write_all(data).await {
+                if e.kind() != std::io::ErrorKind::BrokenPipe {
+                    return Err(e);
+                }
+            }
+            // Dropping the handle closes stdin so the child sees EOF
+        }
+        Ok(())
+    };
+
+    let (stdin_result, stdout, stderr) = tokio::join!(
+        write_stdin,
+        capture.capture(stdout_pipe, LogStream::Stdout),
+        capture.capture(stderr_pipe, LogStream::Stderr),
+    );
+    stdin_result?;
+
+    Ok(Output {
+        status: child.wait().await?,
+        stdout: stdout?,
+        stderr: stderr?,
+    })
+}
+
+/// Factory to create the configured sandbox for the current platform
+///
+/// A custom factory in the config takes precedence over `kind`.
+pub fn create_sandbox(config: &SandboxConfig) -> Box<dyn Sandbox> {
+    if let Some(factory) = &config.factory {
+        return (factory.0)(config);
+    }
+
+    let limits = config.limits;
+    let workdir = config.workdir.clone();
+
+    if config.kind == SandboxKind::Container {
+        return Box::new(
+            ContainerSandbox::new(config.container.clone(), limits)
+                .with_workdir(workdir)
+                .with_job_id(config.job_id.as_deref()),
+        );
+    }
+
     #[cfg(target_os = "linux")]
     {
-        Box::new(LinuxSandbox::new())
+        if config.kind == SandboxKind::Namespace {
+            return Box::new(NamespaceSandbox::new(limits, config.seccomp).with_workdir(workdir));
+        }
+        Box::new(LinuxSandbox::with_limits(limits).with_workdir(workdir))
     }
     #[cfg(not(target_os = "linux"))]
     {
-        Box::new(MacOsSandbox::new())
+        if !limits.is_unlimited() {
+            tracing::warn!(
+                "Resource limits are not supported on this platform and will be ignored"
+            );
+        }
+        Box::new(MacOsSandbox::with_limits(limits).with_workdir(workdir))
+    }
+}
+
+/// Run a native command in the job's working directory, if it has one
+fn apply_workdir(cmd: &mut Command, workdir: Option<&Path>) {
+    if let Some(dir) = workdir {
+        cmd.current_dir(dir);
+        cmd.env(WORKDIR_ENV, dir);
     }
 }
 
@@ -26,19 +285,49 @@ pub fn create_sandbox() -> Box<dyn Sandbox> {
 ///
 /// On macOS, we don't have unshare/namespaces easily accessible without
 /// complex C bindings or external tools. We rely on basic process isolation.
-pub struct MacOsSandbox;
+/// Jobs with a process limit fail, since nothing here can enforce it.
+#[derive(Default)]
+pub struct MacOsSandbox {
+    limits: ResourceLimits,
+    workdir: Option<PathBuf>,
+}
 
 impl MacOsSandbox {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    pub fn with_limits(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            workdir: None,
+        }
+    }
+
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
     }
 }
 
 #[async_trait::async_trait]
 impl Sandbox for MacOsSandbox {
-    async fn run(&self, command: &str, args: &[String], env: &[(String, String)]) -> AgwResult<Output> {
+    async fn run(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: Option<&[u8]>,
+        capture: &OutputCapture,
+    ) -> AgwResult<Output> {
         debug!("Running command in MacOsSandbox: {} {:?}", command, args);
 
+        if let Some(max) = self.limits.max_processes {
+            return Err(AgwError::Worker(format!(
+                "Cannot enforce the job's limit of {max} processes on this platform; use the container sandbox"
+            )));
+        }
+
         let mut cmd = Command::new(command);
         cmd.args(args);
         
@@ -47,11 +336,12 @@ impl Sandbox for MacOsSandbox {
         for (k, v) in env {
             cmd.env(k, v);
         }
+        apply_workdir(&mut cmd, self.workdir.as_deref());
 
         // TODO: Add resource limits via `ulimit` wrapper if needed?
         // For now, just run the process
         
-        let output = cmd.output().await.map_err(|e| {
+        let output = spawn_with_stdin(cmd, stdin, capture).await.map_err(|e| {
             AgwError::Worker(format!("Failed to execute command '{}': {}", command, e))
         })?;
 
@@ -60,20 +350,42 @@ impl Sandbox for MacOsSandbox {
 }
 
 /// Linux Sandbox Implementation (Namespaces)
+///
+/// Memory and CPU limits are enforced with `prlimit`, which sets rlimits on
+/// the `unshare` process so they are inherited by the job and all its
+/// children. The process limit is enforced with a per-run cgroup.
 #[cfg(target_os = "linux")]
-pub struct LinuxSandbox;
+pub struct LinuxSandbox {
+    limits: ResourceLimits,
+    workdir: Option<PathBuf>,
+}
 
 #[cfg(target_os = "linux")]
 impl LinuxSandbox {
-    pub fn new() -> Self {
-        Self
+    pub fn with_limits(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            workdir: None,
+        }
+    }
+
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
     }
 }
 
 #[cfg(target_os = "linux")]
 #[async_trait::async_trait]
 impl Sandbox for LinuxSandbox {
-    async fn run(&self, command: &str, args: &[String], env: &[(String, String)]) -> AgwResult<Output> {
+    async fn run(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: Option<&[u8]>,
+        capture: &OutputCapture,
+    ) -> AgwResult<Output> {
         debug!("Running command in LinuxSandbox: {} {:?}", command, args);
 
         // We use `unshare` to create new namespaces
@@ -89,15 +401,26 @@ impl Sandbox for LinuxSandbox {
         // Let's try the `unshare` command wrapper approach first as it's robust.
         // If `unshare` is not available, we fall back to standard execution with a warning.
         
-        let mut cmd = Command::new("unshare");
+        let prlimit_args = prlimit_args(&self.limits);
+        let mut cmd = if prlimit_args.is_empty() {
+            Command::new("unshare")
+        } else {
+            debug!("Applying resource limits: {:?}", self.limits);
+            let mut wrapper = Command::new("prlimit");
+            wrapper.args(prlimit_args);
+            wrapper.arg("unshare");
+            wrapper
+        };
         
         // Flags:
         // -m: Mount namespace
         // -p: PID namespace
         // -f: Fork (required for PID namespace)
         // --mount-proc: Mount /proc
+        // --kill-child: SIGKILL the job when `unshare` dies, e.g. when the run is dropped on timeout;
+        //   as PID 1 of the namespace, its death takes every other job process with it
         // -n: Network namespace (optional, maybe we want network?) -> Let's keep network for now as tasks might need it
-        cmd.args(&["-m", "-p", "-f", "--mount-proc"]);
+        cmd.args(["-m", "-p", "-f", "--kill-child", "--mount-proc"]);
         
         // The actual command
         cmd.arg(command);
@@ -107,11 +430,665 @@ impl Sandbox for LinuxSandbox {
         for (k, v) in env {
             cmd.env(k, v);
         }
+        apply_workdir(&mut cmd, self.workdir.as_deref());
+
+        // Kept until the run ends, when dropping it removes the cgroup
+        let cgroup = job_cgroup(&self.limits)?;
+        if let Some(cgroup) = cgroup.clone() {
+            // SAFETY: `enter` only makes a `write` syscall on a descriptor
+            // opened before the fork
+            unsafe {
+                cmd.pre_exec(move || cgroup.enter());
+            }
+        }
 
-        let output = cmd.output().await.map_err(|e| {
+        let output = spawn_with_stdin(cmd, stdin, capture).await.map_err(|e| {
             AgwError::Worker(format!("Failed to execute sandbox command: {}", e))
         })?;
 
         Ok(output)
     }
 }
+
+/// Linux Sandbox Implementation (in-process namespaces)
+///
+/// Isolates jobs like [`LinuxSandbox`], but sets up namespaces and resource
+/// limits itself in a `pre_exec` hook, so it works on minimal images without
+/// util-linux. Unprivileged workers get a user namespace mapping them to
+/// root inside the sandbox.
+#[cfg(target_os = "linux")]
+pub struct NamespaceSandbox {
+    setup: std::sync::Arc<crate::namespace::NamespaceSetup>,
+    limits: ResourceLimits,
+    workdir: Option<PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl NamespaceSandbox {
+    pub fn new(limits: ResourceLimits, seccomp: bool) -> Self {
+        Self {
+            setup: std::sync::Arc::new(crate::namespace::NamespaceSetup::new(&limits, seccomp)),
+            limits,
+            workdir: None,
+        }
+    }
+
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[async_trait::async_trait]
+impl Sandbox for NamespaceSandbox {
+    async fn run(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: Option<&[u8]>,
+        capture: &OutputCapture,
+    ) -> AgwResult<Output> {
+        debug!("Running command in NamespaceSandbox: {command} {args:?}");
+
+        let mut cmd = Command::new(command);
+        cmd.args(args);
+        cmd.env_clear();
+        for (k, v) in env {
+            cmd.env(k, v);
+        }
+        apply_workdir(&mut cmd, self.workdir.as_deref());
+
+        let setup = std::sync::Arc::clone(&self.setup);
+        // Kept until the run ends, when dropping it removes the cgroup
+        let cgroup = job_cgroup(&self.limits)?;
+        let hook_cgroup = cgroup.clone();
+        // SAFETY: both `enter`s only make raw syscalls on data prepared
+        // before the fork, which is safe in the child of a multithreaded
+        // process. The cgroup is entered first so the job's whole process
+        // tree is counted.
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(cgroup) = &hook_cgroup {
+                    cgroup.enter()?;
+                }
+                setup.enter()
+            });
+        }
+
+        let output = spawn_with_stdin(cmd, stdin, capture).await.map_err(|e| {
+            AgwError::Worker(format!(
+                "Failed to execute '{command}' in namespace sandbox: {e}"
+            ))
+        })?;
+
+        Ok(output)
+    }
+}
+
+/// Container Sandbox Implementation (Docker/Podman)
+///
+/// Each job runs in a fresh `--rm` container with all capabilities dropped,
+/// so isolation does not depend on host kernel features. This is the strong
+/// option on macOS, where the native sandbox is a bare process.
+///
+/// Containers are named `agw-<job_id>`. Killing the runtime client does not
+/// stop its container, so a run dropped before the container exits (e.g. on
+/// timeout) kills and removes the container by name.
+pub struct ContainerSandbox {
+    settings: ContainerSettings,
+    limits: ResourceLimits,
+    workdir: Option<PathBuf>,
+    name: String,
+}
+
+impl ContainerSandbox {
+    pub fn new(settings: ContainerSettings, limits: ResourceLimits) -> Self {
+        Self {
+            settings,
+            limits,
+            workdir: None,
+            name: format!("agw-{}", uuid::Uuid::new_v4().simple()),
+        }
+    }
+
+    /// Name the container after the job it runs
+    pub fn with_job_id(mut self, job_id: Option<&str>) -> Self {
+        if let Some(id) = job_id {
+            // Container names only allow `[a-zA-Z0-9_.-]`
+            let id: String = id
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                        c
+                    } else {
+                        '-'
+                    }
+                })
+                .collect();
+            self.name = format!("agw-{id}");
+        }
+        self
+    }
+
+    /// Mount the job workspace into the container and run there
+    pub fn with_workdir(mut self, workdir: Option<PathBuf>) -> Self {
+        self.workdir = workdir;
+        self
+    }
+
+    /// Arguments passed to the container runtime
+    fn runtime_args(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        interactive: bool,
+    ) -> Vec<String> {
+        let mut run_args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            format!("--name={}", self.name),
+            "--cap-drop=ALL".to_string(),
+            "--security-opt=no-new-privileges".to_string(),
+        ];
+
+        // Only attach stdin when there is input, so commands otherwise see EOF
+        if interactive {
+            run_args.push("--interactive".to_string());
+        }
+
+        if let Some(mb) = self.limits.memory_mb {
+            run_args.push(format!("--memory={mb}m"));
+        }
+        if let Some(secs) = self.limits.cpu_secs {
+            run_args.push(format!("--ulimit=cpu={secs}:{secs}"));
+        }
+        if let Some(procs) = self.limits.max_processes {
+            run_args.push(format!("--pids-limit={procs}"));
+        }
+
+        for mount in &self.settings.mounts {
+            run_args.push(format!("--volume={mount}"));
+        }
+
+        if let Some(dir) = &self.workdir {
+            run_args.push(format!("--volume={}:{CONTAINER_WORKDIR}", dir.display()));
+            run_args.push(format!("--workdir={CONTAINER_WORKDIR}"));
+            run_args.push(format!("--env={WORKDIR_ENV}={CONTAINER_WORKDIR}"));
+        }
+
+        // Values, which may be secrets, are passed in the runtime's own
+        // environment rather than on its command line, where any local user
+        // could read them
+        for (k, _) in env {
+            run_args.push(format!("--env={k}"));
+        }
+
+        run_args.push(self.settings.image.clone());
+        run_args.push(command.to_string());
+        run_args.extend(args.iter().cloned());
+        run_args
+    }
+}
+
+#[async_trait::async_trait]
+impl Sandbox for ContainerSandbox {
+    async fn run(
+        &self,
+        command: &str,
+        args: &[String],
+        env: &[(String, String)],
+        stdin: Option<&[u8]>,
+        capture: &OutputCapture,
+    ) -> AgwResult<Output> {
+        debug!(
+            "Running command in ContainerSandbox ({} {}): {} {:?}",
+            self.settings.runtime, self.settings.image, command, args
+        );
+
+        let mut cmd = Command::new(&self.settings.runtime);
+        cmd.args(self.runtime_args(command, args, env, stdin.is_some()));
+        cmd.envs(env.iter().map(|(k, v)| (k, v)));
+
+        let mut guard = ContainerGuard {
+            runtime: self.settings.runtime.clone(),
+            name: Some(self.name.clone()),
+        };
+        let output = spawn_with_stdin(cmd, stdin, capture).await;
+        // The runtime client only returns once the container has exited
+        guard.name = None;
+
+        output.map_err(|e| {
+            AgwError::Worker(format!(
+                "Failed to run container runtime '{}': {e}",
+                self.settings.runtime
+            ))
+        })
+    }
+}
+
+/// Kills and removes a running container when its run is dropped
+struct ContainerGuard {
+    runtime: String,
+    /// Container still running, until the run completes
+    name: Option<String>,
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        let Some(name) = self.name.take() else {
+            return;
+        };
+        info!("Removing container {name} of an abandoned job");
+
+        // `--rm` normally removes the killed container; `rm -f` covers a
+        // runtime that did not get to it
+        let runtime = std::mem::take(&mut self.runtime);
+        let commands = [
+            vec!["kill".to_string(), name.clone()],
+            vec!["rm".to_string(), "-f".to_string(), name],
+        ];
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            for args in &commands {
+                let _ = std::process::Command::new(&runtime)
+                    .args(args)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
+            }
+            return;
+        };
+        handle.spawn(async move {
+            for args in &commands {
+                let status = Command::new(&runtime)
+                    .args(args)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .await;
+                debug!("{runtime} {args:?}: {status:?}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_prlimit_args() {
+        let limits = ResourceLimits {
+            memory_mb: Some(256),
+            cpu_secs: Some(5),
+            max_processes: Some(64),
+        };
+        // The process limit is per user, not per job, so it is not set
+        assert_eq!(prlimit_args(&limits), vec!["--as=268435456", "--cpu=5"]);
+        assert!(prlimit_args(&ResourceLimits::default()).is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_limits() {
+        assert!(validate_limits(&ResourceLimits::default()).is_ok());
+        let limits = ResourceLimits {
+            max_processes: Some(0),
+            ..ResourceLimits::default()
+        };
+        assert!(validate_limits(&limits).is_err());
+    }
+
+    /// Run a job limited to 4 processes and check it lands in a cgroup with
+    /// that limit, or fails where cgroups cannot enforce it
+    #[cfg(target_os = "linux")]
+    async fn assert_process_limit(sandbox: &dyn Sandbox) {
+        let script = r#"cat "/sys/fs/cgroup$(sed -n 's/^0:://p' /proc/self/cgroup)/pids.max""#;
+        let result = sandbox
+            .run(
+                "sh",
+                &["-c".to_string(), script.to_string()],
+                &[],
+                None,
+                &OutputCapture::default(),
+            )
+            .await;
+
+        if crate::cgroup::available() {
+            let output = result.unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+        } else {
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("limit of 4 processes"), "{error}");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_linux_sandbox_enforces_process_limit() {
+        assert_process_limit(&LinuxSandbox::with_limits(ResourceLimits {
+            max_processes: Some(4),
+            ..ResourceLimits::default()
+        }))
+        .await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_namespace_sandbox_enforces_process_limit() {
+        let limits = ResourceLimits {
+            max_processes: Some(4),
+            ..ResourceLimits::default()
+        };
+        assert_process_limit(&NamespaceSandbox::new(limits, false)).await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_linux_sandbox_applies_cpu_limit() {
+        let sandbox = LinuxSandbox::with_limits(ResourceLimits {
+            cpu_secs: Some(7),
+            ..ResourceLimits::default()
+        });
+
+        let output = sandbox
+            .run(
+                "sh",
+                &["-c".to_string(), "ulimit -t".to_string()],
+                &[],
+                None,
+                &OutputCapture::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "7");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_namespace_sandbox_isolates_pids_and_applies_limits() {
+        let sandbox = NamespaceSandbox::new(
+            ResourceLimits {
+                cpu_secs: Some(7),
+                ..ResourceLimits::default()
+            },
+            false,
+        );
+
+        let output = sandbox
+            .run(
+                "sh",
+                &["-c".to_string(), "echo $$; ulimit -t; exit 3".to_string()],
+                &[],
+                Some(b"ignored"),
+                &OutputCapture::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n7\n");
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[tokio::test]
+    async fn test_namespace_sandbox_seccomp_denies_mount() {
+        let sandbox = NamespaceSandbox::new(ResourceLimits::default(), true);
+
+        let output = sandbox
+            .run(
+                "sh",
+                &[
+                    "-c".to_string(),
+                    "mount -t tmpfs none /tmp 2>/dev/null && echo mounted || echo denied"
+                        .to_string(),
+                ],
+                &[],
+                None,
+                &OutputCapture::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "denied\n");
+    }
+
+    #[test]
+    fn test_container_runtime_args() {
+        let sandbox = ContainerSandbox::new(
+            ContainerSettings {
+                runtime: "podman".to_string(),
+                image: "alpine:3".to_string(),
+                mounts: vec!["/data:/data:ro".to_string()],
+            },
+            ResourceLimits {
+                memory_mb: Some(128),
+                cpu_secs: Some(10),
+                max_processes: Some(32),
+            },
+        )
+        .with_job_id(Some("job_1"));
+
+        let args = sandbox.runtime_args(
+            "sort",
+            &["-r".to_string()],
+            &[("LANG".to_string(), "C".to_string())],
+            true,
+        );
+
+        assert_eq!(
+            args,
+            vec![
+                "run",
+                "--rm",
+                "--name=agw-job_1",
+                "--cap-drop=ALL",
+                "--security-opt=no-new-privileges",
+                "--interactive",
+                "--memory=128m",
+                "--ulimit=cpu=10:10",
+                "--pids-limit=32",
+                "--volume=/data:/data:ro",
+                "--env=LANG",
+                "alpine:3",
+                "sort",
+                "-r",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_runtime_args_mount_workdir() {
+        let sandbox =
+            ContainerSandbox::new(ContainerSettings::default(), ResourceLimits::default())
+                .with_workdir(Some(PathBuf::from("/tmp/agw-job_1")));
+        let args = sandbox.runtime_args("ls", &[], &[], false);
+        assert!(args.contains(&"--volume=/tmp/agw-job_1:/workspace".to_string()));
+        assert!(args.contains(&"--workdir=/workspace".to_string()));
+        assert!(args.contains(&"--env=AGW_WORKDIR=/workspace".to_string()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_linux_sandbox_runs_in_workdir() {
+        let dir = std::env::temp_dir().join(format!("agw-sandbox-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let sandbox =
+            LinuxSandbox::with_limits(ResourceLimits::default()).with_workdir(Some(dir.clone()));
+        let output = sandbox
+            .run(
+                "sh",
+                &["-c".to_string(), "pwd; echo $AGW_WORKDIR".to_string()],
+                &[],
+                None,
+                &OutputCapture::default(),
+            )
+            .await
+            .unwrap();
+
+        let expected = format!("{}\n{}\n", dir.display(), dir.display());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_container_runtime_args_without_stdin() {
+        let sandbox =
+            ContainerSandbox::new(ContainerSettings::default(), ResourceLimits::default());
+        let args = sandbox.runtime_args("echo", &[], &[], false);
+        assert!(!args.contains(&"--interactive".to_string()));
+        assert_eq!(args[args.len() - 2], DEFAULT_CONTAINER_IMAGE);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_container_env_values_stay_off_argv() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A fake runtime printing its arguments and the value it was handed
+        let dir = std::env::temp_dir().join(format!("agw-sandbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let runtime = dir.join("runtime");
+        std::fs::write(&runtime, "#!/bin/sh\necho \"$@\"\necho \"$API_TOKEN\"\n").unwrap();
+        std::fs::set_permissions(&runtime, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let sandbox = ContainerSandbox::new(
+            ContainerSettings {
+                runtime: runtime.to_string_lossy().into_owned(),
+                ..ContainerSettings::default()
+            },
+            ResourceLimits::default(),
+        );
+        let env = [("API_TOKEN".to_string(), "s3cr3t".to_string())];
+        let output = sandbox
+            .run("env", &[], &env, None, &OutputCapture::default())
+            .await
+            .unwrap();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (argv, value) = stdout.trim_end().split_once('\n').unwrap();
+        assert!(argv.contains("--env=API_TOKEN "));
+        assert!(!argv.contains("s3cr3t"));
+        assert_eq!(value, "s3cr3t");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Whether a process with `arg` on its command line is running
+    #[cfg(target_os = "linux")]
+    fn process_running(arg: &str) -> bool {
+        std::fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+            std::fs::read(entry.path().join("cmdline"))
+                .is_ok_and(|cmdline| cmdline.split(|&b| b == 0).any(|a| a == arg.as_bytes()))
+        })
+    }
+
+    /// Time out a job that left `sleep` running and check the sleep is gone
+    #[cfg(target_os = "linux")]
+    async fn assert_timeout_kills_children(sandbox: &dyn Sandbox) {
+        // A unique duration tells this sleep apart from any other
+        let marker = format!("300.{}", uuid::Uuid::new_v4().as_u128() % 1_000_000_000);
+        let args = ["-c".to_string(), format!("sleep {marker} & wait")];
+        let capture = OutputCapture::default();
+        let mut run = Box::pin(sandbox.run("sh", &args, &[], None, &capture));
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(10);
+        tokio::select! {
+            result = &mut run => panic!("job finished early: {result:?}"),
+            () = async {
+                while !process_running(&marker) {
+                    assert!(tokio::time::Instant::now() < deadline, "sleep never started");
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+            } => {}
+        }
+
+        // Dropping the run is what the executor does on timeout
+        drop(run);
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while process_running(&marker) {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "sleep outlived its job"
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_linux_sandbox_timeout_kills_children() {
+        assert_timeout_kills_children(&LinuxSandbox::with_limits(ResourceLimits::default())).await;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_namespace_sandbox_timeout_kills_children() {
+        assert_timeout_kills_children(&NamespaceSandbox::new(ResourceLimits::default(), false))
+            .await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_container_sandbox_removes_container_on_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A fake runtime logging its invocations, whose `run` never ends
+        let dir = std::env::temp_dir().join(format!("agw-sandbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("calls.log");
+        let runtime = dir.join("runtime");
+        std::fs::write(
+            &runtime,
+            format!(
+                "#!/bin/sh\necho \"$1 $2 $3\" >> {}\n[ \"$1\" = run ] && exec sleep 30\nexit 0\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&runtime, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let sandbox = ContainerSandbox::new(
+            ContainerSettings {
+                runtime: runtime.to_string_lossy().into_owned(),
+                ..ContainerSettings::default()
+            },
+            ResourceLimits::default(),
+        )
+        .with_job_id(Some("job_timeout"));
+        let args = ["300".to_string()];
+        let capture = OutputCapture::default();
+        let run = sandbox.run("sleep", &args, &[], None, &capture);
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(300), run)
+                .await
+                .is_err()
+        );
+
+        let calls = || std::fs::read_to_string(&log).unwrap_or_default();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !calls().contains("rm -f agw-job_timeout") {
+            assert!(tokio::time::Instant::now() < deadline, "calls: {}", calls());
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            calls().lines().collect::<Vec<_>>(),
+            [
+                "run --rm --name=agw-job_timeout",
+                "kill agw-job_timeout ",
+                "rm -f agw-job_timeout"
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+use crate::error::{AgwError, AgwResult};
+use crate::workspace::WORKDIR_ENV;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+/// Prefix marking an environment value as a reference to a worker secret
+pub const SECRET_SCHEME: &str = "secret://";
+
+/// Secrets available to jobs on this worker, keyed by name
+///
+/// Loaded from a TOML file of `name = "value"` pairs that only the worker's
+/// operator controls. Jobs never see the file; they reference entries as
+/// `secret://<name>` in environment values.
+#[derive(Clone, Default)]
+pub struct SecretStore {
+    values: HashMap<String, String>,
+}
+
+impl std::fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print secret values
+        let mut names: Vec<_> = self.values.keys().collect();
+        names.sort();
+        f.debug_struct("SecretStore")
+            .field("names", &names)
+            .finish()
+    }
+}
+
+impl SecretStore {
+    /// Load secrets from a TOML file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, is not valid TOML, or has
+    /// a value that is not a string
+    pub fn load(path: &Path) -> AgwResult<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AgwError::InvalidConfig(format!(
+                "Failed to read secrets file {}: {e}",
+                path.display()
+            ))
+        })?;
+        Self::parse(&contents).map_err(|e| {
+            AgwError::InvalidConfig(format!("Invalid secrets file {}: {e}", path.display()))
+        })
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let document: toml_edit::DocumentMut = contents.parse().map_err(|e| format!("{e}"))?;
+
+        let mut values = HashMap::new();
+        for (name, item) in document.iter() {
+            let value = item
+                .as_str()
+                .ok_or_else(|| format!("secret '{name}' must be a string"))?;
+            values.insert(name.to_string(), value.to_string());
+        }
+
+        Ok(Self { values })
+    }
+
+    /// Resolve a `secret://<name>` reference, passing other values through
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value references an unknown secret
+    pub fn resolve(&self, value: &str) -> AgwResult<String> {
+        match value.strip_prefix(SECRET_SCHEME) {
+            Some(name) => self
+                .values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| AgwError::Worker(format!("Unknown secret '{name}'"))),
+            None => Ok(value.to_string()),
+        }
+    }
+}
+
+/// Build the environment a job runs with
+///
+/// String values in the job's `env` are injected first, then the worker's
+/// configured `KEY=VALUE` entries, which win on conflict so operators can pin
+/// variables. Values of either kind may be secret references. Keys that are
+/// not valid variable names, or that could hijack the dynamic loader, are
+/// skipped.
+///
+/// # Errors
+///
+/// Returns an error if a value references an unknown secret
+pub fn job_environment(
+    job_env: &serde_json::Value,
+    worker_env: &[String],
+    secrets: &SecretStore,
+) -> AgwResult<Vec<(String, String)>> {
+    let mut env: Vec<(String, String)> = Vec::new();
+    let mut set = |key: &str, value: &str| -> AgwResult<()> {
+        if !is_injectable_env_name(key) {
+            warn!("Skipping job environment variable {key:?}");
+            return Ok(());
+        }
+        let value = secrets.resolve(value)?;
+        env.retain(|(k, _)| k != key);
+        env.push((key.to_string(), value));
+        Ok(())
+    };
+
+    if let Some(vars) = job_env.as_object() {
+        for (key, value) in vars {
+            if let Some(value) = value.as_str() {
+                set(key, value)?;
+            }
+        }
+    }
+
+    for entry in worker_env {
+        if let Some((key, value)) = entry.split_once('=') {
+            set(key, value)?;
+        }
+    }
+
+    Ok(env)
+}
+
+/// Whether any string value in a job's `env` is a secret reference
+///
+/// AGQ resolves the references it holds values for only when asked, so
+/// jobs without any skip the round trip.
+#[must_use]
+pub fn has_references(job_env: &serde_json::Value) -> bool {
+    job_env.as_object().is_some_and(|vars| {
+        vars.values()
+            .filter_map(serde_json::Value::as_str)
+            .any(|value| value.starts_with(SECRET_SCHEME))
+    })
+}
+
+/// Check that a name is a conventional environment variable name
+#[must_use]
+pub fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Names jobs may set: valid, and not reserved by the worker or loader
+fn is_injectable_env_name(name: &str) -> bool {
+    is_valid_env_name(name)
+        && name != WORKDIR_ENV
+        && !name.starts_with("LD_")
+        && !name.starts_with("DYLD_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store() -> SecretStore {
+        SecretStore::parse("payments-key = \"sk_live_123\"\n").unwrap()
+    }
+
+    #[test]
+    fn test_resolve_secret_reference() {
+        let secrets = store();
+        assert_eq!(
+            secrets.resolve("secret://payments-key").unwrap(),
+            "sk_live_123"
+        );
+        assert_eq!(secrets.resolve("plain").unwrap(), "plain");
+        assert!(secrets.resolve("secret://missing").is_err());
+    }
+
+    #[test]
+    fn test_secrets_file_rejects_non_string_values() {
+        assert!(SecretStore::parse("retries = 3\n").is_err());
+    }
+
+    #[test]
+    fn test_debug_hides_secret_values() {
+        assert!(!format!("{:?}", store()).contains("sk_live_123"));
+    }
+
+    #[test]
+    fn test_job_environment() {
+        let job_env = json!({
+            "API_KEY": "secret://payments-key",
+            "REGION": "eu",
+            "LD_PRELOAD": "/tmp/evil.so",
+            "count": 3,
+        });
+        let worker_env = [
+            "REGION=us".to_string(),
+            "HTTP_PROXY=http://proxy:3128".to_string(),
+        ];
+
+        let env = job_environment(&job_env, &worker_env, &store()).unwrap();
+        assert_eq!(
+            env,
+            vec![
+                ("API_KEY".to_string(), "sk_live_123".to_string()),
+                ("REGION".to_string(), "us".to_string()),
+                ("HTTP_PROXY".to_string(), "http://proxy:3128".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_job_environment_unknown_secret() {
+        let job_env = json!({"TOKEN": "secret://nope"});
+        assert!(job_environment(&job_env, &[], &store()).is_err());
+    }
+
+    #[test]
+    fn test_has_references() {
+        assert!(has_references(
+            &json!({"A": "1", "TOKEN": "secret://token"})
+        ));
+        assert!(!has_references(&json!({"A": "1", "N": 2})));
+        assert!(!has_references(&json!("secret://token")));
+    }
+
+    #[test]
+    fn test_is_valid_env_name() {
+        assert!(is_valid_env_name("API_KEY"));
+        assert!(is_valid_env_name("_x1"));
+        assert!(!is_valid_env_name("1X"));
+        assert!(!is_valid_env_name("A-B"));
+        assert!(!is_valid_env_name(""));
+    }
+}
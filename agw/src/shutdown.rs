@@ -0,0 +1,88 @@
+use crate::error::{AgwError, AgwResult};
+
+/// OS signals that request a graceful worker shutdown
+///
+/// On Unix these are SIGTERM and SIGINT; on Windows, Ctrl+C and Ctrl+Break
+/// (which service managers and CI agents send to console processes). Either
+/// way the worker stops fetching jobs and drains the current one.
+pub struct ShutdownSignals {
+    #[cfg(unix)]
+    sigterm: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    sigint: tokio::signal::unix::Signal,
+    #[cfg(windows)]
+    ctrl_c: tokio::signal::windows::CtrlC,
+    #[cfg(windows)]
+    ctrl_break: tokio::signal::windows::CtrlBreak,
+}
+
+impl ShutdownSignals {
+    /// Install the platform's shutdown signal handlers
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a signal handler cannot be registered
+    #[cfg(unix)]
+    pub fn install() -> AgwResult<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            sigterm: signal(SignalKind::terminate())
+                .map_err(|e| AgwError::Worker(format!("Failed to setup SIGTERM handler: {e}")))?,
+            sigint: signal(SignalKind::interrupt())
+                .map_err(|e| AgwError::Worker(format!("Failed to setup SIGINT handler: {e}")))?,
+        })
+    }
+
+    /// Install the platform's shutdown signal handlers
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a console control handler cannot be registered
+    #[cfg(windows)]
+    pub fn install() -> AgwResult<Self> {
+        use tokio::signal::windows::{ctrl_break, ctrl_c};
+
+        Ok(Self {
+            ctrl_c: ctrl_c()
+                .map_err(|e| AgwError::Worker(format!("Failed to setup Ctrl+C handler: {e}")))?,
+            ctrl_break: ctrl_break().map_err(|e| {
+                AgwError::Worker(format!("Failed to setup Ctrl+Break handler: {e}"))
+            })?,
+        })
+    }
+
+    /// Install the platform's shutdown signal handlers
+    ///
+    /// # Errors
+    ///
+    /// Never fails; this platform has no shutdown signals
+    #[cfg(not(any(unix, windows)))]
+    pub fn install() -> AgwResult<Self> {
+        Ok(Self {})
+    }
+
+    /// Wait for the next shutdown signal and return its name
+    #[cfg(unix)]
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.sigterm.recv() => "SIGTERM",
+            _ = self.sigint.recv() => "SIGINT (Ctrl+C)",
+        }
+    }
+
+    /// Wait for the next shutdown signal and return its name
+    #[cfg(windows)]
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.ctrl_c.recv() => "Ctrl+C",
+            _ = self.ctrl_break.recv() => "Ctrl+Break",
+        }
+    }
+
+    /// Wait for the next shutdown signal and return its name
+    #[cfg(not(any(unix, windows)))]
+    pub async fn recv(&mut self) -> &'static str {
+        std::future::pending().await
+    }
+}
@@ -0,0 +1,284 @@
+use crate::gpu::GpuInfo;
+use crate::health::ToolHealth;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Maximum time to wait for `nvidia-smi` before reporting no GPU data
+const GPU_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Snapshot of worker host health, sent with each heartbeat
+///
+/// Metrics that cannot be read on the current platform are omitted, so AGQ
+/// can distinguish "unknown" from "zero".
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SystemMetrics {
+    /// 1-minute load average
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_avg_1m: Option<f64>,
+
+    /// Number of CPUs available to the worker
+    pub cpu_count: usize,
+
+    /// Available memory in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_available_bytes: Option<u64>,
+
+    /// Total memory in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_total_bytes: Option<u64>,
+
+    /// Free space in bytes on the filesystem holding job workspaces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_free_bytes: Option<u64>,
+
+    /// Utilization of each GPU in percent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gpu_utilization: Vec<u32>,
+
+    /// GPUs detected at startup
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gpus: Vec<GpuInfo>,
+
+    /// Result of the latest health check of each tool
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tools: BTreeMap<String, ToolHealth>,
+
+    /// Number of jobs currently executing
+    pub current_jobs: u32,
+}
+
+impl SystemMetrics {
+    /// Collect metrics for the host
+    ///
+    /// `disk_path` is the directory whose filesystem free space is reported.
+    pub async fn collect(disk_path: &Path, current_jobs: u32) -> Self {
+        let (memory_available_bytes, memory_total_bytes) = read_memory();
+
+        Self {
+            load_avg_1m: read_load_avg(),
+            cpu_count: std::thread::available_parallelism().map_or(1, usize::from),
+            memory_available_bytes,
+            memory_total_bytes,
+            disk_free_bytes: read_disk_free(disk_path),
+            gpu_utilization: query_gpu_utilization().await,
+            gpus: Vec::new(),
+            tools: BTreeMap::new(),
+            current_jobs,
+        }
+    }
+
+    /// Serialize for the heartbeat payload
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// CPU time used by a job
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU time spent in user mode, in milliseconds
+    pub cpu_user_ms: u64,
+    /// CPU time spent in the kernel, in milliseconds
+    pub cpu_system_ms: u64,
+}
+
+impl ResourceUsage {
+    /// CPU time of all terminated child processes of the worker, if known
+    ///
+    /// The worker runs one job at a time, so the difference between two
+    /// readings taken around a job is (approximately) that job's usage.
+    #[must_use]
+    pub fn children() -> Option<Self> {
+        read_children_usage()
+    }
+
+    /// Usage accumulated since an earlier reading
+    #[must_use]
+    pub fn since(self, earlier: Self) -> Self {
+        Self {
+            cpu_user_ms: self.cpu_user_ms.saturating_sub(earlier.cpu_user_ms),
+            cpu_system_ms: self.cpu_system_ms.saturating_sub(earlier.cpu_system_ms),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_children_usage() -> Option<ResourceUsage> {
+    use nix::libc;
+
+    #[allow(clippy::useless_conversion)] // timeval field types vary by target
+    fn millis(tv: libc::timeval) -> u64 {
+        let ms = i64::from(tv.tv_sec) * 1000 + i64::from(tv.tv_usec) / 1000;
+        u64::try_from(ms).unwrap_or(0)
+    }
+
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage fully initializes `usage` when it returns 0
+    let usage = unsafe {
+        if libc::getrusage(libc::RUSAGE_CHILDREN, usage.as_mut_ptr()) != 0 {
+            return None;
+        }
+        usage.assume_init()
+    };
+
+    Some(ResourceUsage {
+        cpu_user_ms: millis(usage.ru_utime),
+        cpu_system_ms: millis(usage.ru_stime),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_children_usage() -> Option<ResourceUsage> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn read_load_avg() -> Option<f64> {
+    let contents = std::fs::read_to_string("/proc/loadavg").ok()?;
+    parse_load_avg(&contents)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_load_avg() -> Option<f64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn read_memory() -> (Option<u64>, Option<u64>) {
+    std::fs::read_to_string("/proc/meminfo")
+        .map(|contents| parse_meminfo(&contents))
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_memory() -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+#[cfg(target_os = "linux")]
+#[allow(clippy::useless_conversion)] // statvfs field types vary by target
+fn read_disk_free(path: &Path) -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    Some(u64::from(stat.blocks_available()).saturating_mul(u64::from(stat.fragment_size())))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_disk_free(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Query NVIDIA GPU utilization, returning nothing if no GPU is available
+async fn query_gpu_utilization() -> Vec<u32> {
+    let query = tokio::process::Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=utilization.gpu",
+            "--format=csv,noheader,nounits",
+        ])
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(GPU_QUERY_TIMEOUT, query).await {
+        Ok(Ok(output)) if output.status.success() => {
+            parse_gpu_utilization(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the first field of `/proc/loadavg`
+fn parse_load_avg(contents: &str) -> Option<f64> {
+    contents.split_whitespace().next()?.parse().ok()
+}
+
+/// Parse `MemAvailable` and `MemTotal` from `/proc/meminfo` into bytes
+fn parse_meminfo(contents: &str) -> (Option<u64>, Option<u64>) {
+    let field = |name: &str| {
+        contents
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| {
+                rest.trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .map(|kb| kb.saturating_mul(1024))
+    };
+
+    (field("MemAvailable:"), field("MemTotal:"))
+}
+
+/// Parse one utilization percentage per line of `nvidia-smi` CSV output
+fn parse_gpu_utilization(output: &str) -> Vec<u32> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_load_avg() {
+        assert_eq!(parse_load_avg("0.52 0.58 0.59 1/467 12345\n"), Some(0.52));
+        assert_eq!(parse_load_avg(""), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = "MemTotal:       16384000 kB\nMemFree:         1024000 kB\nMemAvailable:    8192000 kB\n";
+        assert_eq!(
+            parse_meminfo(meminfo),
+            (Some(8_192_000 * 1024), Some(16_384_000 * 1024))
+        );
+        assert_eq!(parse_meminfo(""), (None, None));
+    }
+
+    #[test]
+    fn test_parse_gpu_utilization() {
+        assert_eq!(parse_gpu_utilization("45\n 0\n"), vec![45, 0]);
+        assert!(parse_gpu_utilization("").is_empty());
+    }
+
+    #[test]
+    fn test_metrics_json_omits_unknown_values() {
+        let metrics = SystemMetrics {
+            cpu_count: 4,
+            current_jobs: 1,
+            ..SystemMetrics::default()
+        };
+        assert_eq!(metrics.to_json(), r#"{"cpu_count":4,"current_jobs":1}"#);
+    }
+
+    #[test]
+    fn test_resource_usage_since() {
+        let before = ResourceUsage {
+            cpu_user_ms: 100,
+            cpu_system_ms: 50,
+        };
+        let after = ResourceUsage {
+            cpu_user_ms: 350,
+            cpu_system_ms: 60,
+        };
+        assert_eq!(
+            after.since(before),
+            ResourceUsage {
+                cpu_user_ms: 250,
+                cpu_system_ms: 10,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_reports_current_jobs() {
+        let metrics = SystemMetrics::collect(&std::env::temp_dir(), 1).await;
+        assert_eq!(metrics.current_jobs, 1);
+        assert!(metrics.cpu_count >= 1);
+    }
+}
@@ -1,18 +1,150 @@
 use crate::config::Config;
+use crate::control::{self, ControlCommand};
 use crate::error::{AgwError, AgwResult};
 use crate::executor;
-
+use crate::gpu::{self, GpuInfo};
+use crate::health::{self, ToolHealth};
+use crate::manifest::{self, ModelCard};
+use crate::metrics::{self, WorkerMetrics};
+
+use crate::output::{LogChunk, OutputCapture, OutputEncoding};
+use crate::plan::{Job, JobPriority};
+use crate::policy::CommandPolicy;
+use crate::recovery::{self, RecoveryMode};
 use crate::resp::RespClient;
+use crate::result::JobResult;
+use crate::retry::{RetryHint, TransientFailure};
+use crate::sandbox::{SandboxConfig, SandboxFactory};
+use crate::secrets::{self, SecretStore};
+use crate::shutdown::ShutdownSignals;
+use crate::system::SystemMetrics;
+use crate::workspace::Workspace;
+use agenix_queue::DEFAULT_QUEUE;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Processing queue holding jobs claimed by workers
+const QUEUE_PROCESSING: &str = "queue:processing";
+
+/// Delay before the first reconnection attempt
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Upper bound on the delay between reconnection attempts
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Output chunks buffered between a running job and the log forwarder
+const LOG_CHANNEL_CAPACITY: usize = 64;
+
+/// Operator commands buffered between the control socket and the main loop
+const CONTROL_CHANNEL_CAPACITY: usize = 8;
+
+/// Largest heartbeat metrics payload AGQ accepts
+const MAX_HEARTBEAT_METRICS_BYTES: usize = 4 * 1024;
+
+/// How often a running job's claim is checked
+const CLAIM_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A fetched job with variables substituted, ready for execution
+struct PreparedJob {
+    job: Job,
+    /// Raw job ID as popped from the queue (used to LREM from processing)
+    job_id_raw: String,
+    /// Combined stdout of the job's dependencies, piped to stdin
+    stdin: Option<Vec<u8>>,
+    /// Sandbox for the job, with worker default limits tightened by the job
+    sandbox: SandboxConfig,
+    /// Execution timeout, clamped to the worker's maximum
+    timeout_secs: Option<u32>,
+    /// Worker-wide execution settings
+    settings: ExecutionSettings,
+}
+
+/// Worker configuration needed while a job executes
+struct ExecutionSettings {
+    /// Whether to stream output to AGQ while the job runs
+    stream_logs: bool,
+    /// Cap on captured stdout and stderr
+    max_output_bytes: usize,
+    /// Directory under which the job's workspace is created
+    workspace_root: PathBuf,
+    /// Whether to upload every workspace file for jobs that declare no artifacts
+    upload_all_artifacts: bool,
+    /// Cap on the total size of uploaded artifacts
+    max_artifact_bytes: u64,
+}
+
+impl ExecutionSettings {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            stream_logs: config.stream_logs,
+            max_output_bytes: config.max_output_bytes,
+            workspace_root: config.workspace_root(),
+            upload_all_artifacts: config.upload_artifacts,
+            max_artifact_bytes: config.max_artifact_bytes,
+        }
+    }
+}
+
+/// Called before a job runs; an error rejects the job with that reason
+pub type PreJobHook = Arc<dyn Fn(&Job) -> Result<(), String> + Send + Sync>;
+
+/// Called with every result the worker reports for a job
+pub type PostJobHook = Arc<dyn Fn(&JobResult) + Send + Sync>;
+
+/// Callbacks around job execution, set through `WorkerBuilder`
+#[derive(Clone, Default)]
+pub struct JobHooks {
+    /// Runs after a job is prepared and passes the command policy
+    pub pre_job: Option<PreJobHook>,
+    /// Runs after a job's result is posted, or fails to post
+    pub post_job: Option<PostJobHook>,
+}
+
+impl JobHooks {
+    fn after_job(&self, result: &JobResult) {
+        if let Some(hook) = &self.post_job {
+            hook(result);
+        }
+    }
+}
+
+/// Output of a completed dependency job
+struct DependencyOutput {
+    task_number: u32,
+    /// Position among the item jobs of a map task, if it is one
+    item_index: Option<u32>,
+    /// Raw stdout bytes, decoded if the dependency posted binary output
+    stdout: Vec<u8>,
+}
+
 /// AGW Worker
 pub struct Worker {
     config: Config,
     id: String,
     name: String,
     client: RespClient,
+    metrics: Arc<WorkerMetrics>,
+    policy: CommandPolicy,
+    secrets: SecretStore,
+    /// Jobs claimed before a crash, run ahead of new ones when resuming
+    recovered: VecDeque<String>,
+    hooks: Arc<JobHooks>,
+    sandbox_factory: Option<SandboxFactory>,
+    /// GPUs detected at startup, registered as tags and sent in heartbeats
+    gpus: Vec<GpuInfo>,
+    /// Model cards of the worker's tools, collected at startup
+    cards: Vec<ModelCard>,
+    /// Latest health check of each tool; broken tools are de-registered
+    tool_health: BTreeMap<String, ToolHealth>,
+    /// Ready queues jobs are taken from, most specific first, as listed by
+    /// AGQ for the worker's tags
+    queues: Vec<String>,
 }
 
 impl Worker {
@@ -23,10 +155,30 @@ impl Worker {
     /// Returns an error if configuration validation fails, connection to AGQ fails,
     /// or authentication fails
     pub async fn new(config: Config) -> AgwResult<Self> {
+        Self::with_extensions(config, JobHooks::default(), None).await
+    }
+
+    /// Create a worker with job hooks and an optional custom sandbox
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`Worker::new`]
+    pub(crate) async fn with_extensions(
+        config: Config,
+        hooks: JobHooks,
+        sandbox_factory: Option<SandboxFactory>,
+    ) -> AgwResult<Self> {
         // Validate configuration
         config
             .validate()
             .map_err(|e| AgwError::InvalidConfig(e.to_string()))?;
+        let policy = config
+            .command_policy()
+            .map_err(|e| AgwError::InvalidConfig(e.to_string()))?;
+        let secrets = match config.secrets_file {
+            Some(ref path) => SecretStore::load(path)?,
+            None => SecretStore::default(),
+        };
 
         // Generate or use provided worker ID
         let worker_id = config
@@ -47,11 +199,54 @@ impl Worker {
             worker_id, worker_name
         );
 
+        let gpus = if config.detect_gpus {
+            gpu::detect().await
+        } else {
+            Vec::new()
+        };
+
+        let tools = config.tools.clone().unwrap_or_default();
+        let cards = manifest::describe(&tools).await;
+        let tool_health = BTreeMap::new();
+
+        let (client, queues) =
+            Self::open_session(&config, &worker_id, &gpus, &cards, &tool_health).await?;
+
+        Ok(Self {
+            config,
+            id: worker_id,
+            name: worker_name,
+            client,
+            metrics: Arc::new(WorkerMetrics::new()?),
+            policy,
+            secrets,
+            recovered: VecDeque::new(),
+            hooks: Arc::new(hooks),
+            sandbox_factory,
+            gpus,
+            cards,
+            tool_health,
+            queues,
+        })
+    }
+
+    /// Connect to AGQ, authenticate, and register tools, model cards, and tags
+    ///
+    /// Used both at startup and when re-establishing a lost session, since
+    /// AGQ forgets authentication state when the connection drops. Returns
+    /// the client and the ready queues AGQ routes the worker's tags to.
+    async fn open_session(
+        config: &Config,
+        worker_id: &str,
+        gpus: &[GpuInfo],
+        cards: &[ModelCard],
+        tool_health: &BTreeMap<String, ToolHealth>,
+    ) -> AgwResult<(RespClient, Vec<String>)> {
         // Connect to AGQ
         let mut client = RespClient::connect(&config.agq_address).await?;
 
         // Authenticate
-        client.authenticate(&config.session_key).await?;
+        client.authenticate(config.session_key()).await?;
 
         // Register available tools with AGQ
         let tools = config.tools.clone().unwrap_or_else(|| {
@@ -59,62 +254,106 @@ impl Worker {
             vec![]
         });
 
-        if !tools.is_empty() {
-            client.register_tools(&worker_id, &tools).await?;
-        }
+        register_healthy_tools(&mut client, worker_id, &tools, cards, tool_health).await?;
 
         // Register tags with AGQ
-        let tags = config.tags.clone().unwrap_or_else(|| {
+        let mut tags = config.tags.clone().unwrap_or_else(|| {
             // Default to "cpu" tag if none specified
             vec!["cpu".to_string()]
         });
+        for tag in gpu::capability_tags(gpus) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
 
         if !tags.is_empty() {
-            client.register_tags(&worker_id, &tags).await?;
+            client.register_tags(worker_id, &tags).await?;
         }
 
-        Ok(Self {
-            config,
-            id: worker_id,
-            name: worker_name,
-            client,
-        })
+        let queues = ready_queues(&mut client, worker_id).await;
+        Ok((client, queues))
     }
 
     /// Run the worker main loop
     ///
     /// # Errors
     ///
-    /// Returns an error if heartbeat fails, job fetch fails, or connection to AGQ is lost
+    /// Returns an error if the connection to AGQ is lost and cannot be
+    /// re-established within `max_reconnect_attempts`, or if job fetch fails
+    /// for a reason other than a connection error
     pub async fn run(mut self) -> AgwResult<()> {
         info!("Worker {} starting main loop", self.id);
 
         // Setup signal handlers for graceful shutdown
-        #[cfg(unix)]
-        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .map_err(|e| AgwError::Worker(format!("Failed to setup SIGTERM handler: {e}")))?;
-
-        #[cfg(unix)]
-        let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())
-            .map_err(|e| AgwError::Worker(format!("Failed to setup SIGINT handler: {e}")))?;
+        let mut signals = ShutdownSignals::install()?;
+
+        // Serve Prometheus metrics if configured
+        let metrics_server = match self.config.metrics_addr {
+            Some(addr) => {
+                let listener = metrics::bind(addr).await?;
+                Some(tokio::spawn(metrics::serve(
+                    listener,
+                    Arc::clone(&self.metrics),
+                )))
+            }
+            None => None,
+        };
+
+        // Accept drain/pause/resume commands if configured. The sender is kept
+        // for the whole loop so the receiver never reports a closed channel.
+        let (control_tx, mut control_rx) = mpsc::channel(CONTROL_CHANNEL_CAPACITY);
+        let control_server = match self.config.control_socket.as_deref() {
+            Some(path) => Some(control::spawn(path, control_tx.clone())?),
+            None => None,
+        };
+
+        // Re-check the tools if configured. As above, the sender is kept so
+        // the receiver never reports a closed channel.
+        let tools = self.config.tools.clone().unwrap_or_default();
+        let (health_tx, mut health_rx) = mpsc::channel(1);
+        let health_checks = match self.config.tool_health_duration() {
+            Some(interval) if !tools.is_empty() => Some(health::spawn(
+                tools,
+                self.cards.clone(),
+                interval,
+                health_tx.clone(),
+            )),
+            _ => None,
+        };
 
         // Main loop: fetch jobs and send heartbeats
         let mut heartbeat_interval = tokio::time::interval(self.config.heartbeat_duration());
 
         // Consume the first tick (which completes immediately) and send initial heartbeat
         heartbeat_interval.tick().await;
-        self.send_heartbeat().await?;
+        self.send_heartbeat(0).await?;
+
+        // Jobs stranded in the processing queue by a previous crash
+        if let Err(e) = self.recover_claimed_jobs().await {
+            error!("Failed to recover jobs from a previous run: {e}");
+        }
 
         // Track currently executing job (if any)
         let mut current_job: Option<JoinHandle<()>> = None;
 
-        // Shutdown flag (Unix only - Windows doesn't have signal handlers yet)
-        #[cfg(unix)]
+        // Set once a shutdown signal or drain command arrives; the loop exits
+        // when no job is running
         let mut shutdown_requested = false;
 
+        // Set by the pause command; no new jobs are fetched until resumed
+        let mut paused = false;
+
         loop {
-            // Check if shutdown was requested and no job is running (Unix only)
-            #[cfg(unix)]
+            // Apply commands that arrived while a job fetch was in flight
+            while let Ok(command) = control_rx.try_recv() {
+                apply_control_command(command, &mut paused, &mut shutdown_requested);
+            }
+            while let Ok(report) = health_rx.try_recv() {
+                self.apply_tool_health(report).await?;
+            }
+
+            // Check if shutdown was requested and no job is running
             if shutdown_requested && current_job.is_none() {
                 info!("Shutdown complete - no jobs running");
                 break;
@@ -134,54 +373,63 @@ impl Worker {
                 }
             }
 
+            let fetching = current_job.is_none() && !shutdown_requested && !paused;
+
             // Use tokio::select with biased mode to prioritize heartbeats
             // This prevents DoS when jobs are continuously available
-            #[cfg(unix)]
-            {
-                tokio::select! {
-                    biased;
-
-                    // Signal handlers - highest priority
-                    _ = sigterm.recv() => {
-                        info!("Received SIGTERM, initiating graceful shutdown");
-                        shutdown_requested = true;
-                        if current_job.is_some() {
-                            info!("Waiting for current job to complete before shutdown");
-                        }
+            tokio::select! {
+                biased;
+
+                // Signal handlers - highest priority
+                signal = signals.recv() => {
+                    info!("Received {signal}, initiating graceful shutdown");
+                    shutdown_requested = true;
+                    if current_job.is_some() {
+                        info!("Waiting for current job to complete before shutdown");
                     }
+                }
 
-                    _ = sigint.recv() => {
-                        info!("Received SIGINT (Ctrl+C), initiating graceful shutdown");
-                        shutdown_requested = true;
-                        if current_job.is_some() {
-                            info!("Waiting for current job to complete before shutdown");
-                        }
-                    }
+                // Control commands wake the loop only when no fetch is in
+                // flight, so a blocking pop is never abandoned midway
+                Some(command) = control_rx.recv(), if !fetching => {
+                    apply_control_command(command, &mut paused, &mut shutdown_requested);
+                }
 
-                    // Heartbeat tick
-                    _ = heartbeat_interval.tick() => {
-                        match self.send_heartbeat().await {
-                            Ok(()) => {
-                                debug!("Heartbeat sent successfully for worker {}", self.id);
-                            }
-                            Err(e) => {
-                                error!("Failed to send heartbeat: {e}");
-                                return Err(e);
-                            }
+                // Likewise for tool health reports
+                Some(report) = health_rx.recv(), if !fetching => {
+                    self.apply_tool_health(report).await?;
+                }
+
+                // Heartbeat tick
+                _ = heartbeat_interval.tick() => {
+                    match self.send_heartbeat(u32::from(current_job.is_some())).await {
+                        Ok(()) => {
+                            debug!("Heartbeat sent successfully for worker {}", self.id);
+                        }
+                        Err(e) => {
+                            error!("Failed to send heartbeat: {e}");
+                            self.recover(e).await?;
                         }
                     }
+                }
 
-                    // Job fetch and preparation
-                    job_result = self.fetch_job(), if current_job.is_none() && !shutdown_requested => {
+                // Job fetch and preparation
+                job_result = self.fetch_job(), if fetching => {
                     match job_result {
-                        Ok(Some((job, job_id_raw))) => {
-                            debug!("Prepared job {} (task {})", job.id, job.task_number);
+                        Ok(Some(prepared)) => {
+                            debug!("Prepared job {} (task {})", prepared.job.id, prepared.job.task_number);
 
                             // Clone client for the spawned task
                             let client = self.client.clone();
 
                             // Spawn task execution
-                            let task_handle = tokio::spawn(Self::handle_task_execution(job, job_id_raw, client));
+                            let task_handle = tokio::spawn(Self::handle_task_execution(
+                                prepared,
+                                client,
+                                self.id.clone(),
+                                Arc::clone(&self.metrics),
+                                Arc::clone(&self.hooks),
+                            ));
 
                             current_job = Some(task_handle);
                         }
@@ -191,51 +439,7 @@ impl Worker {
                         }
                         Err(e) => {
                             error!("Failed to fetch job: {e}");
-                            return Err(e);
-                        }
-                    }
-                }
-                }
-            }
-
-            // Non-Unix platforms (Windows) - no signal handling available yet
-            #[cfg(not(unix))]
-            {
-                tokio::select! {
-                    biased;
-
-                    // Heartbeat tick
-                    _ = heartbeat_interval.tick() => {
-                        match self.send_heartbeat().await {
-                            Ok(()) => {
-                                debug!("Heartbeat sent successfully for worker {}", self.id);
-                            }
-                            Err(e) => {
-                                error!("Failed to send heartbeat: {e}");
-                                return Err(e);
-                            }
-                        }
-                    }
-
-                    // Job fetch and preparation (no shutdown handling on Windows yet)
-                    job_result = self.fetch_job(), if current_job.is_none() => {
-                        match job_result {
-                            Ok(Some((job, job_id_raw))) => {
-                                debug!("Prepared job {} (task {})", job.id, job.task_number);
-
-                                let client = self.client.clone();
-
-                                let task_handle = tokio::spawn(Self::handle_task_execution(job, job_id_raw, client));
-
-                                current_job = Some(task_handle);
-                            }
-                            Ok(None) => {
-                                debug!("Job fetch timeout, continuing...");
-                            }
-                            Err(e) => {
-                                error!("Failed to fetch job: {e}");
-                                return Err(e);
-                            }
+                            self.recover(e).await?;
                         }
                     }
                 }
@@ -272,6 +476,16 @@ impl Worker {
             }
         }
 
+        if let Some(server) = metrics_server {
+            server.abort();
+        }
+        drop(control_server);
+        drop(control_tx);
+        if let Some(checks) = health_checks {
+            checks.abort();
+        }
+        drop(health_tx);
+
         info!("Worker {} shutting down gracefully", self.id);
         Ok(())
     }
@@ -279,119 +493,775 @@ impl Worker {
     /// Fetch a job for execution
     ///
     /// New workflow (Task-Based):
-    /// 1. Pop job_id from queue (BRPOPLPUSH for reliability)
+    /// 1. Pop job_id from queue (BRPOPLPUSH for reliability), or take a job
+    ///    recovered from a previous run
     /// 2. Fetch job metadata (JOB.GET) - contains full task details
-    /// 3. Substitute input variables (if any)
+    /// 3. Resolve dependency outputs and substitute variables
     ///
-    /// Returns (job, job_id_raw) tuple
-    async fn fetch_job(&mut self) -> AgwResult<Option<(crate::plan::Job, String)>> {
-        use crate::plan::Job;
-
-        // TODO: Support tagged queues based on config
-        const QUEUE_READY: &str = "queue:default";
-        const QUEUE_PROCESSING: &str = "queue:processing";
+    /// Jobs that cannot be prepared (unresolved variables, incomplete
+    /// dependencies, validation failures after substitution) are reported to
+    /// AGQ as failed rather than stopping the worker.
+    async fn fetch_job(&mut self) -> AgwResult<Option<PreparedJob>> {
         const TIMEOUT: u64 = 5; // 5 second timeout to allow heartbeats
 
-        // Step 1: Pop job_id from queue
-        match self
+        // Step 1: Take a recovered job, or pop job_id from queue
+        let job_id_raw = match self.recovered.pop_front() {
+            Some(job_id_raw) => {
+                info!("Resuming job {job_id_raw} claimed before a restart");
+                job_id_raw
+            }
+            None => match self.pop_ready_job(TIMEOUT).await? {
+                Some(job_id_raw) => {
+                    info!("Received job_id from queue (moved to processing)");
+                    self.claim_job(&job_id_raw).await;
+                    job_id_raw
+                }
+                None => {
+                    // Queues for new sets of requirements appear as jobs
+                    // are routed to them. A failure here resurfaces on the
+                    // next fetch, so the current list is kept meanwhile.
+                    if let Ok(queues) = self.client.worker_queues(&self.id).await {
+                        if !queues.is_empty() {
+                            self.queues = queues;
+                        }
+                    }
+                    return Ok(None);
+                }
+            },
+        };
+
+        // Step 2: Get job metadata
+        let job_json = self.client.job_get(&job_id_raw).await.map_err(|e| {
+            AgwError::Worker(format!(
+                "Failed to fetch job metadata for '{}': {}",
+                job_id_raw, e
+            ))
+        })?;
+
+        let job = Job::from_json(&job_json).map_err(|e| {
+            AgwError::Worker(format!(
+                "Failed to parse job JSON for '{}': {}",
+                job_id_raw, e
+            ))
+        })?;
+
+        info!("Fetched job {} (task {})", job.id, job.task_number);
+        self.metrics.jobs_fetched.inc();
+        if let Some(wait) = job.created_at.and_then(queue_wait) {
+            self.metrics.queue_wait_seconds.observe(wait.as_secs_f64());
+        }
+
+        // Step 3: Resolve dependencies and substitute variables
+        match self.prepare_job(&job).await {
+            Ok((job, stdin, env)) => {
+                // Enforce the policy on the substituted commands, since
+                // variables can change what actually runs
+                let checked = job
+                    .invocations()
+                    .into_iter()
+                    .try_for_each(|(command, args)| self.policy.check(command, args));
+                if let Err(violation) = checked {
+                    warn!("Job {} rejected by command policy: {violation}", job.id);
+                    Self::fail_job(
+                        &mut self.client,
+                        &self.metrics,
+                        &self.hooks,
+                        &job.id,
+                        &job_id_raw,
+                        &format!("Policy violation: {violation}"),
+                        "policy_violation",
+                    )
+                    .await;
+                    return Ok(None);
+                }
+
+                if let Some(hook) = &self.hooks.pre_job {
+                    if let Err(reason) = hook(&job) {
+                        warn!("Job {} rejected by pre-job hook: {reason}", job.id);
+                        Self::fail_job(
+                            &mut self.client,
+                            &self.metrics,
+                            &self.hooks,
+                            &job.id,
+                            &job_id_raw,
+                            &format!("Rejected: {reason}"),
+                            "failed",
+                        )
+                        .await;
+                        return Ok(None);
+                    }
+                }
+
+                let mut sandbox = self.config.sandbox_config();
+                sandbox.limits = sandbox.limits.restrict(&job.limits);
+                sandbox.env = env;
+                sandbox.job_id = Some(job.id.clone());
+                sandbox.factory = self.sandbox_factory.clone();
+                let timeout_secs = self.config.job_timeout(job.timeout_secs);
+                Ok(Some(PreparedJob {
+                    job,
+                    job_id_raw,
+                    stdin,
+                    sandbox,
+                    timeout_secs,
+                    settings: ExecutionSettings::from_config(&self.config),
+                }))
+            }
+            Err(e) => {
+                warn!("Job {} could not be prepared: {e}", job.id);
+                Self::fail_job(
+                    &mut self.client,
+                    &self.metrics,
+                    &self.hooks,
+                    &job.id,
+                    &job_id_raw,
+                    &format!("Preparation error: {e}"),
+                    "failed",
+                )
+                .await;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Move the next ready job to the processing queue
+    ///
+    /// The worker's queues are tried most specific first, so a GPU worker
+    /// takes GPU jobs before jobs any worker can run, and each queue's lists
+    /// highest priority first. When every list is empty, blocks on the most
+    /// specific queue's normal-priority list: for up to `timeout` seconds if
+    /// it is the only queue, otherwise for a second so the other queues are
+    /// polled again soon.
+    async fn pop_ready_job(&mut self, timeout: u64) -> AgwResult<Option<String>> {
+        for queue in &self.queues {
+            for priority in JobPriority::ORDER {
+                let list = priority.queue_name(queue);
+                if let Some(job_id_raw) = self.client.rpoplpush(&list, QUEUE_PROCESSING).await? {
+                    debug!("Took job {job_id_raw} from {list}");
+                    return Ok(Some(job_id_raw));
+                }
+            }
+        }
+        let (queue, timeout) = match self.queues.as_slice() {
+            [] => (DEFAULT_QUEUE, timeout),
+            [only] => (only.as_str(), timeout),
+            [first, ..] => (first.as_str(), timeout.min(1)),
+        };
+        self.client
+            .brpoplpush(queue, QUEUE_PROCESSING, timeout)
+            .await
+    }
+
+    /// Record this worker as the owner of a job it moved to processing
+    ///
+    /// The claim lets a restarted worker find jobs stranded by a crash. A
+    /// failed write only loses that recovery, so it does not stop the job.
+    async fn claim_job(&mut self, job_id_raw: &str) {
+        if let Err(e) = self
             .client
-            .brpoplpush(QUEUE_READY, QUEUE_PROCESSING, TIMEOUT)
-            .await?
+            .set(&recovery::claim_key(job_id_raw), &self.id)
+            .await
         {
-            Some(job_id_raw) => {
-                info!("Received job_id from queue (moved to processing)");
-
-                // Step 2: Get job metadata
-                let job_json = self.client.job_get(&job_id_raw).await.map_err(|e| {
-                    AgwError::Worker(format!(
-                        "Failed to fetch job metadata for '{}': {}",
-                        job_id_raw, e
-                    ))
-                })?;
-
-                let mut job = Job::from_json(&job_json).map_err(|e| {
-                    AgwError::Worker(format!(
-                        "Failed to parse job JSON for '{}': {}",
-                        job_id_raw, e
-                    ))
-                })?;
-
-                job.validate().map_err(|e| {
-                    AgwError::Worker(format!("Job validation failed for '{}': {}", job.id, e))
-                })?;
-
-                info!("Fetched job {} (task {})", job.id, job.task_number);
-
-                // Step 3: Substitute input variables
-                // TODO: Implement substitution using job.env
-                // For now, we assume args are already substituted or we implement it here
-                // job.args = substitute_variables(&job.args, &job.env)?;
-
-                Ok(Some((job, job_id_raw)))
+            warn!("Failed to record claim on job {job_id_raw}: {e}");
+        }
+    }
+
+    /// Requeue or resume jobs this worker claimed before a crash
+    ///
+    /// Jobs whose result was already posted are only removed from the
+    /// processing queue, whatever the configured mode.
+    async fn recover_claimed_jobs(&mut self) -> AgwResult<()> {
+        let mode = self.config.recover_jobs;
+        if mode == RecoveryMode::Off {
+            return Ok(());
+        }
+
+        let claimed = recovery::claimed_jobs(&mut self.client, QUEUE_PROCESSING, &self.id).await?;
+        for job_id_raw in claimed {
+            let status = self.client.get(&format!("job:{job_id_raw}:status")).await?;
+            if status.as_deref().is_some_and(recovery::is_terminal_status) {
+                info!(
+                    "Job {job_id_raw} finished before the restart, removing from processing queue"
+                );
+                self.client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await?;
+                continue;
+            }
+
+            match mode {
+                RecoveryMode::Resume => self.recovered.push_back(job_id_raw),
+                RecoveryMode::Requeue => {
+                    // Back to the queue for its tags, so only workers that
+                    // meet them take it
+                    let queue = match self.client.job_get(&job_id_raw).await {
+                        Ok(json) => Job::from_json(&json)
+                            .map_or_else(|_| DEFAULT_QUEUE.to_string(), |job| job.ready_queue()),
+                        Err(e) => {
+                            warn!("Failed to fetch job {job_id_raw} to requeue it: {e}");
+                            DEFAULT_QUEUE.to_string()
+                        }
+                    };
+                    // Pushed before removal so the job is never lost
+                    self.client.lpush(&queue, &job_id_raw).await?;
+                    self.client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await?;
+                    info!("Requeued job {job_id_raw} claimed before the restart");
+                }
+                RecoveryMode::Off => {}
+            }
+        }
+
+        if !self.recovered.is_empty() {
+            info!(
+                "Resuming {} job(s) claimed before the restart",
+                self.recovered.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Resolve dependency outputs, substitute variables, and validate a job
+    ///
+    /// Returns the substituted job, the stdin to pipe into it, and its
+    /// environment with secret references resolved.
+    async fn prepare_job(
+        &mut self,
+        job: &Job,
+    ) -> AgwResult<(Job, Option<Vec<u8>>, Vec<(String, String)>)> {
+        job.validate_dependencies()?;
+
+        let outputs = Self::fetch_dependency_outputs(job, &mut self.client).await?;
+
+        // The item jobs of a map task share a task number, so their
+        // outputs are joined in item order
+        let mut task_outputs: HashMap<u32, String> = HashMap::new();
+        for o in &outputs {
+            task_outputs
+                .entry(o.task_number)
+                .or_default()
+                .push_str(&String::from_utf8_lossy(&o.stdout));
+        }
+
+        let job = job.substitute_variables(&task_outputs, self.config.strict_variables)?;
+
+        job.validate().map_err(|e| {
+            AgwError::Worker(format!("Job validation failed for '{}': {}", job.id, e))
+        })?;
+
+        let stdin = if outputs.is_empty() {
+            None
+        } else {
+            Some(outputs.into_iter().flat_map(|o| o.stdout).collect())
+        };
+
+        // AGQ resolves the secrets it holds; references left are the
+        // worker's own
+        let job_env = if secrets::has_references(&job.env) {
+            self.client.job_env(&job.id).await?
+        } else {
+            job.env.clone()
+        };
+        let env = secrets::job_environment(&job_env, &self.config.job_env, &self.secrets)?;
+
+        Ok((job, stdin, env))
+    }
+
+    /// Fetch the outputs of a job's dependencies
+    ///
+    /// Outputs are ordered by task number, then by item for the jobs of a map
+    /// task, so jobs with several inputs see a deterministic concatenation on
+    /// stdin. Dependencies the job only waited for are left out. A dependency
+    /// that has not completed is an error: running without its output would
+    /// silently break the plan's piping semantics.
+    async fn fetch_dependency_outputs(
+        job: &Job,
+        client: &mut RespClient,
+    ) -> AgwResult<Vec<DependencyOutput>> {
+        let mut outputs = Vec::with_capacity(job.dependencies.len());
+
+        for dep_id in &job.dependencies {
+            if job.order_only.contains(dep_id) {
+                continue;
+            }
+            let status = client.get(&format!("job:{dep_id}:status")).await?;
+            if status.as_deref() != Some("completed") {
+                return Err(AgwError::Worker(format!(
+                    "Dependency {dep_id} of job {} has not completed (status: {})",
+                    job.id,
+                    status.as_deref().unwrap_or("unknown")
+                )));
+            }
+
+            let dep_json = client.job_get(dep_id).await?;
+            let dep = Job::from_json(&dep_json).map_err(|e| {
+                AgwError::Worker(format!("Failed to parse dependency job '{dep_id}': {e}"))
+            })?;
+
+            let encoding = client
+                .get(&format!("job:{dep_id}:stdout_encoding"))
+                .await?
+                .map_or(OutputEncoding::Utf8, |name| {
+                    OutputEncoding::from_name(&name)
+                });
+            let stdout = client.job_output(dep_id, "stdout").await?;
+            let stdout = encoding.decode(stdout).map_err(|e| {
+                AgwError::Worker(format!("Dependency {dep_id} has malformed output: {e}"))
+            })?;
+            debug!(
+                "Dependency {dep_id} (task {}) produced {} bytes for job {}",
+                dep.task_number,
+                stdout.len(),
+                job.id
+            );
+
+            outputs.push(DependencyOutput {
+                task_number: dep.task_number,
+                item_index: dep.item_index,
+                stdout,
+            });
+        }
+
+        outputs.sort_by_key(|o| (o.task_number, o.item_index));
+        Ok(outputs)
+    }
+
+    /// Post a failed result for a job and remove it from the processing queue
+    ///
+    /// `status` is the terminal status to report, normally `failed`.
+    async fn fail_job(
+        client: &mut RespClient,
+        metrics: &WorkerMetrics,
+        hooks: &JobHooks,
+        job_id: &str,
+        job_id_raw: &str,
+        error_msg: &str,
+        status: &str,
+    ) {
+        metrics.jobs_failed.inc();
+
+        let result = JobResult::failure(job_id, status, error_msg);
+        let posted = client.post_job_result(job_id, "", error_msg, &result).await;
+        hooks.after_job(&result);
+        if let Err(post_err) = posted {
+            error!("Failed to post error for job {}: {post_err}", job_id);
+            return;
+        }
+
+        info!("Job failed but results posted, removing from processing queue");
+        if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, job_id_raw).await {
+            error!("Failed to remove job {} from processing queue: {e}", job_id);
+        }
+    }
+
+    /// Put a job that hit a transient failure back on the ready queue
+    ///
+    /// The attempt count is carried in the job's `job:<id>:retry` hint.
+    /// Returns false, leaving the caller to report the failure, once the job
+    /// has used the attempts its spec allows or if AGQ cannot be updated.
+    async fn requeue_job(
+        client: &mut RespClient,
+        job: &Job,
+        job_id_raw: &str,
+        failure: TransientFailure,
+    ) -> bool {
+        let hint_key = format!("job:{}:retry", job.id);
+        let previous = match client.get(&hint_key).await {
+            Ok(value) => value.as_deref().and_then(RetryHint::from_json),
+            Err(e) => {
+                error!("Failed to read retry hint for job {}: {e}", job.id);
+                return false;
+            }
+        };
+
+        let Some(hint) = RetryHint::next(previous.as_ref(), job.max_attempts, failure) else {
+            info!("Job {} failed ({failure}) with no attempts left", job.id);
+            return false;
+        };
+
+        if let Err(e) = client.set(&hint_key, &hint.to_json()).await {
+            error!("Failed to post retry hint for job {}: {e}", job.id);
+            return false;
+        }
+        if let Err(e) = client.lpush(&job.ready_queue(), job_id_raw).await {
+            error!("Failed to requeue job {}: {e}", job.id);
+            return false;
+        }
+        warn!(
+            "Job {} failed ({failure}), requeued after attempt {}/{}",
+            job.id, hint.attempts, hint.max_attempts
+        );
+
+        // Pushed before removal, so a lost connection here can duplicate the
+        // job but never drop it
+        if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, job_id_raw).await {
+            error!("Failed to remove job {} from processing queue: {e}", job.id);
+        }
+        true
+    }
+
+    /// Send a heartbeat message to AGQ with current system metrics
+    async fn send_heartbeat(&mut self, current_jobs: u32) -> AgwResult<()> {
+        let mut system = SystemMetrics::collect(&self.config.workspace_root(), current_jobs).await;
+        system.gpus.clone_from(&self.gpus);
+        system.tools.clone_from(&self.tool_health);
+        // Many broken tools could push the payload past AGQ's limit: drop
+        // the reasons first, then the report
+        if system.to_json().len() > MAX_HEARTBEAT_METRICS_BYTES {
+            for health in system.tools.values_mut() {
+                health.reason = None;
             }
-            None => Ok(None),
         }
+        if system.to_json().len() > MAX_HEARTBEAT_METRICS_BYTES {
+            system.tools.clear();
+        }
+        let result = self.client.heartbeat(&self.id, Some(&system)).await;
+        if result.is_err() {
+            self.metrics.heartbeat_failures.inc();
+        }
+        result
     }
 
-    /// Send a heartbeat message to AGQ
-    async fn send_heartbeat(&mut self) -> AgwResult<()> {
-        self.client.heartbeat(&self.id).await
+    /// Record a tool health report, updating the registration of tools
+    /// whose health changed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if AGQ cannot be updated and the connection cannot
+    /// be re-established
+    async fn apply_tool_health(&mut self, report: BTreeMap<String, ToolHealth>) -> AgwResult<()> {
+        let mut changed = false;
+        for (tool, health) in &report {
+            match (is_healthy(&self.tool_health, tool), health.healthy) {
+                (true, false) => {
+                    let reason = health.reason.as_deref().unwrap_or("unknown reason");
+                    warn!("Tool {tool} failed its health check, de-registering it: {reason}");
+                    changed = true;
+                }
+                (false, true) => {
+                    info!("Tool {tool} passed its health check again, re-registering it");
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        self.tool_health = report;
+        if !changed {
+            return Ok(());
+        }
+
+        let tools = self.config.tools.clone().unwrap_or_default();
+        let result = register_healthy_tools(
+            &mut self.client,
+            &self.id,
+            &tools,
+            &self.cards,
+            &self.tool_health,
+        )
+        .await;
+        if let Err(e) = result {
+            // A reconnect registers the tools from the new health report
+            error!("Failed to update registered tools: {e}");
+            self.recover(e).await?;
+        }
+        Ok(())
+    }
+
+    /// Recover from a failed AGQ operation in the main loop
+    ///
+    /// Connection errors trigger a reconnect; any other error is returned as-is.
+    async fn recover(&mut self, err: AgwError) -> AgwResult<()> {
+        if !err.is_connection_error() {
+            return Err(err);
+        }
+
+        self.reconnect().await
+    }
+
+    /// Re-establish the AGQ session with exponential backoff
+    ///
+    /// On success the worker re-authenticates, re-registers its tools and tags,
+    /// and sends a heartbeat so AGQ sees it as alive again. Jobs claimed before
+    /// the disconnect stay in the processing queue; fetching resumes normally.
+    ///
+    /// # Errors
+    ///
+    /// Returns the last error once `max_reconnect_attempts` consecutive
+    /// attempts have failed (0 retries indefinitely)
+    async fn reconnect(&mut self) -> AgwResult<()> {
+        let max_attempts = self.config.max_reconnect_attempts;
+        let mut attempt: u32 = 0;
+
+        loop {
+            attempt = attempt.saturating_add(1);
+            let delay = reconnect_delay(attempt);
+            warn!("Reconnecting to AGQ in {delay:?} (attempt {attempt})");
+            tokio::time::sleep(delay).await;
+
+            let session = Self::open_session(
+                &self.config,
+                &self.id,
+                &self.gpus,
+                &self.cards,
+                &self.tool_health,
+            )
+            .await;
+            let result = match session {
+                Ok((mut client, queues)) => client
+                    .heartbeat(&self.id, None)
+                    .await
+                    .map(|()| (client, queues)),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok((client, queues)) => {
+                    self.client = client;
+                    self.queues = queues;
+                    info!("Reconnected to AGQ after {attempt} attempt(s)");
+                    return Ok(());
+                }
+                Err(e) if max_attempts != 0 && attempt >= max_attempts => {
+                    error!("Giving up on AGQ after {attempt} reconnection attempts: {e}");
+                    return Err(e);
+                }
+                Err(e) => {
+                    warn!("Reconnection attempt {attempt} failed: {e}");
+                }
+            }
+        }
     }
 
     /// Get the worker ID
     #[must_use]
-    #[allow(dead_code)]
     pub fn id(&self) -> &str {
         &self.id
     }
 
     /// Get the worker name
     #[must_use]
-    #[allow(dead_code)]
     pub fn name(&self) -> &str {
         &self.name
     }
 
+    /// Forward streamed output chunks to AGQ until the job's output closes
+    ///
+    /// Streaming is best effort: after the first failure the remaining chunks
+    /// are drained and dropped, and the job's full result is still posted.
+    async fn forward_logs(
+        mut client: RespClient,
+        job_id: String,
+        mut rx: mpsc::Receiver<LogChunk>,
+    ) {
+        let mut streaming = true;
+
+        while let Some(chunk) = rx.recv().await {
+            if !streaming {
+                continue;
+            }
+
+            if let Err(e) = client
+                .job_log(&job_id, chunk.stream.as_str(), &chunk.data)
+                .await
+            {
+                warn!("Failed to stream logs for job {job_id}, continuing without streaming: {e}");
+                streaming = false;
+            }
+        }
+    }
+
+    /// Upload a job's artifacts to AGQ
+    ///
+    /// Jobs that declare artifacts get exactly those uploaded; otherwise every
+    /// workspace file is uploaded if the worker is configured to do so.
+    /// Failures are logged but do not fail the job.
+    async fn upload_artifacts(
+        client: &mut RespClient,
+        job: &Job,
+        workspace: &Workspace,
+        settings: &ExecutionSettings,
+    ) {
+        let job_id = &job.id;
+        let collected = if !job.artifacts.is_empty() {
+            workspace.declared_artifacts(&job.artifacts, settings.max_artifact_bytes)
+        } else if settings.upload_all_artifacts {
+            workspace.artifacts(settings.max_artifact_bytes)
+        } else {
+            return;
+        };
+
+        let artifacts = match collected {
+            Ok(artifacts) => artifacts,
+            Err(e) => {
+                warn!("Failed to collect artifacts for job {job_id}: {e}");
+                return;
+            }
+        };
+
+        if artifacts.is_empty() {
+            return;
+        }
+
+        if let Err(e) = client.post_artifacts(job_id, &artifacts).await {
+            warn!("Failed to upload artifacts for job {job_id}: {e}");
+        }
+    }
+
+    /// Wait until AGQ takes a job back from this worker
+    ///
+    /// AGQ revokes a worker's claim on a job that runs past its timeout
+    /// without a result. Only a claim this worker was seen to hold counts as
+    /// revoked, and errors reading it are ignored, so a job keeps running if
+    /// its claim could not be recorded or AGQ cannot be reached.
+    async fn claim_revoked(mut client: RespClient, job_id_raw: &str, worker_id: &str) {
+        let claim_key = recovery::claim_key(job_id_raw);
+        let mut held = false;
+        loop {
+            tokio::time::sleep(CLAIM_CHECK_INTERVAL).await;
+            match client.get(&claim_key).await {
+                Ok(owner) => {
+                    let ours = owner.as_deref() == Some(worker_id);
+                    if held && !ours {
+                        return;
+                    }
+                    held |= ours;
+                }
+                Err(e) => debug!("Failed to check claim on job {job_id_raw}: {e}"),
+            }
+        }
+    }
+
     /// Handle task execution
+    ///
+    /// The task is killed if AGQ takes the job back while it runs, in which
+    /// case no result is posted.
     async fn handle_task_execution(
-        job: crate::plan::Job,
-        job_id_raw: String,
+        prepared: PreparedJob,
         mut client: RespClient,
+        worker_id: String,
+        metrics: Arc<WorkerMetrics>,
+        hooks: Arc<JobHooks>,
     ) {
-        const QUEUE_PROCESSING: &str = "queue:processing";
+        let PreparedJob {
+            job,
+            job_id_raw,
+            stdin,
+            mut sandbox,
+            timeout_secs,
+            settings,
+        } = prepared;
+
+        // Give the job its own working directory, removed when this returns
+        let workspace = match Workspace::create(&settings.workspace_root, &job.id) {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                error!("Failed to create workspace for job {}: {e}", job.id);
+                Self::fail_job(
+                    &mut client,
+                    &metrics,
+                    &hooks,
+                    &job.id,
+                    &job_id_raw,
+                    &format!("Workspace error: {e}"),
+                    "failed",
+                )
+                .await;
+                return;
+            }
+        };
+        sandbox.workdir = Some(workspace.path().to_path_buf());
+
+        // Stream output to AGQ while the job runs
+        let mut capture = OutputCapture {
+            max_bytes: settings.max_output_bytes,
+            sink: None,
+        };
+        let log_forwarder = settings.stream_logs.then(|| {
+            let (tx, rx) = mpsc::channel(LOG_CHANNEL_CAPACITY);
+            capture.sink = Some(tx);
+            tokio::spawn(Self::forward_logs(client.clone(), job.id.clone(), rx))
+        });
 
         // Execute the task
-        // TODO: Handle stdin input from dependencies (if passed in env or via AGQ)
-        match executor::execute_task(
-            &job.command,
-            &job.args,
-            None, // stdin
-            None, // timeout (could be in job)
-            job.task_number,
-        ).await {
+        let started = Instant::now();
+        let run = async {
+            if job.is_pipeline() {
+                executor::execute_pipeline(
+                    &job.stages,
+                    stdin.as_deref(),
+                    timeout_secs,
+                    job.task_number,
+                    &sandbox,
+                    &capture,
+                )
+                .await
+            } else {
+                executor::execute_task(
+                    &job.command,
+                    &job.args,
+                    stdin.as_deref(),
+                    timeout_secs,
+                    job.task_number,
+                    &sandbox,
+                    &capture,
+                )
+                .await
+            }
+        };
+        // Dropping the execution kills the task's processes
+        let execution = tokio::select! {
+            execution = run => execution,
+            () = Self::claim_revoked(client.clone(), &job_id_raw, &worker_id) => {
+                warn!("AGQ took job {} back after it ran past its timeout, stopping it", job.id);
+                metrics.jobs_failed.inc();
+                return;
+            }
+        };
+
+        // Close the log stream and let it flush before posting the final result
+        drop(capture);
+        if let Some(forwarder) = log_forwarder {
+            if let Err(e) = forwarder.await {
+                error!("Log forwarding task panicked: {e}");
+            }
+        }
+
+        match execution {
             Ok(result) => {
                 info!(
                     "Job {} (task {}) completed: exit_code={}",
-                    job.id,
-                    job.task_number,
-                    result.exit_code
+                    job.id, job.task_number, result.exit_code
                 );
 
+                if let Some(failure) = result.transient_failure {
+                    if Self::requeue_job(&mut client, &job, &job_id_raw, failure).await {
+                        metrics.jobs_retried.inc();
+                        return;
+                    }
+                }
+
+                metrics
+                    .job_duration_seconds
+                    .observe(started.elapsed().as_secs_f64());
                 let status = if result.success {
+                    metrics.jobs_succeeded.inc();
                     "completed"
+                } else if result.timed_out {
+                    metrics.jobs_failed.inc();
+                    "timeout"
                 } else {
+                    metrics.jobs_failed.inc();
                     "failed"
                 };
 
-                if let Err(e) = client
-                    .post_job_result(
-                        &job.id,
-                        &result.stdout,
-                        &result.stderr,
-                        status,
-                    )
-                    .await
-                {
+                // Upload artifacts before the result so they are in place
+                // by the time the job is seen as finished
+                Self::upload_artifacts(&mut client, &job, &workspace, &settings).await;
+
+                let envelope =
+                    JobResult::from_task(&job.id, status, &result, &sandbox, timeout_secs);
+                let posted = client
+                    .post_job_result(&job.id, &result.stdout, &result.stderr, &envelope)
+                    .await;
+                hooks.after_job(&envelope);
+                if let Err(e) = posted {
                     error!("Failed to post results for job {}: {e}", job.id);
                     return;
                 }
@@ -399,37 +1269,159 @@ impl Worker {
                 // Remove job from processing queue
                 info!("Job completed successfully, removing from processing queue");
                 if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await {
-                    error!(
-                        "Failed to remove job {} from processing queue: {e}",
-                        job.id
-                    );
+                    error!("Failed to remove job {} from processing queue: {e}", job.id);
                 }
             }
             Err(e) => {
                 error!("Failed to execute job {}: {e}", job.id);
+                Self::fail_job(
+                    &mut client,
+                    &metrics,
+                    &hooks,
+                    &job.id,
+                    &job_id_raw,
+                    &format!("Execution error: {e}"),
+                    "failed",
+                )
+                .await;
+            }
+        }
+    }
+}
 
-                let error_msg = format!("Execution error: {e}");
-                if let Err(post_err) = client
-                    .post_job_result(&job.id, "", &error_msg, "failed")
-                    .await
-                {
-                    error!("Failed to post error for job {}: {post_err}", job.id);
-                    return;
-                }
+/// Whether a tool passed its latest health check; unchecked tools count
+fn is_healthy(tool_health: &BTreeMap<String, ToolHealth>, tool: &str) -> bool {
+    tool_health.get(tool).is_none_or(|health| health.healthy)
+}
 
-                info!("Job failed but results posted, removing from processing queue");
-                if let Err(e) = client.lrem(QUEUE_PROCESSING, 1, &job_id_raw).await {
-                    error!("Failed to remove job {} from processing queue: {e}", job.id);
-                }
-            }
+/// Register the tools that passed their latest health check with their
+/// model cards, and remove the cards of the others
+async fn register_healthy_tools(
+    client: &mut RespClient,
+    worker_id: &str,
+    tools: &[String],
+    cards: &[ModelCard],
+    tool_health: &BTreeMap<String, ToolHealth>,
+) -> AgwResult<()> {
+    if !tools.is_empty() {
+        let healthy: Vec<String> = tools
+            .iter()
+            .filter(|tool| is_healthy(tool_health, tool))
+            .cloned()
+            .collect();
+        client.register_tools(worker_id, &healthy).await?;
+    }
+    for card in cards {
+        let result = if is_healthy(tool_health, &card.tool) {
+            client.register_manifest(worker_id, &card.json).await
+        } else {
+            client.unregister_manifest(worker_id, &card.name).await
+        };
+        // An AGQ without a tool catalog still runs jobs
+        if let Err(e) = result {
+            warn!("Could not update model card of {}: {e}", card.tool);
         }
     }
+    Ok(())
+}
+
+/// Update worker state for an operator command from the control socket
+fn apply_control_command(command: ControlCommand, paused: &mut bool, draining: &mut bool) {
+    match command {
+        ControlCommand::Drain => {
+            info!("Draining: finishing current job, then shutting down");
+            *draining = true;
+        }
+        ControlCommand::Pause => {
+            info!("Paused: no new jobs will be fetched");
+            *paused = true;
+        }
+        ControlCommand::Resume if *draining => {
+            warn!("Ignoring resume: worker is draining");
+        }
+        ControlCommand::Resume => {
+            info!("Resumed: fetching jobs");
+            *paused = false;
+        }
+    }
+}
+
+/// Time a job spent queued, given its creation timestamp in Unix seconds
+///
+/// Returns `None` if the timestamp is in the future (clock skew with AGQ).
+fn queue_wait(created_at: u64) -> Option<Duration> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    now.checked_sub(created_at).map(Duration::from_secs)
+}
+
+/// Ready queues AGQ routes the worker's tags to, most specific first
+///
+/// Falls back to the default queue if AGQ cannot list them, e.g. an AGQ
+/// that predates tag routing.
+async fn ready_queues(client: &mut RespClient, worker_id: &str) -> Vec<String> {
+    match client.worker_queues(worker_id).await {
+        Ok(queues) if !queues.is_empty() => queues,
+        Ok(_) => vec![DEFAULT_QUEUE.to_string()],
+        Err(e) => {
+            warn!("Could not list ready queues, taking jobs from {DEFAULT_QUEUE}: {e}");
+            vec![DEFAULT_QUEUE.to_string()]
+        }
+    }
+}
+
+/// Backoff delay before the given (1-based) reconnection attempt
+fn reconnect_delay(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    RECONNECT_BASE_DELAY
+        .saturating_mul(1 << exponent)
+        .min(RECONNECT_MAX_DELAY)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_reconnect_delay_backoff() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(4), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(6), RECONNECT_MAX_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_apply_control_command() {
+        let (mut paused, mut draining) = (false, false);
+
+        apply_control_command(ControlCommand::Pause, &mut paused, &mut draining);
+        assert!(paused);
+        apply_control_command(ControlCommand::Resume, &mut paused, &mut draining);
+        assert!(!paused);
+
+        apply_control_command(ControlCommand::Drain, &mut paused, &mut draining);
+        apply_control_command(ControlCommand::Resume, &mut paused, &mut draining);
+        assert!(draining);
+    }
+
+    #[test]
+    fn test_queue_wait() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(queue_wait(now - 30).unwrap() >= Duration::from_secs(30));
+        assert_eq!(queue_wait(now + 3600), None);
+    }
+
+    #[test]
+    fn test_connection_errors_are_recoverable() {
+        assert!(AgwError::Connection("refused".to_string()).is_connection_error());
+        assert!(AgwError::RespProtocol("PING failed".to_string()).is_connection_error());
+        assert!(!AgwError::Worker("bad job".to_string()).is_connection_error());
+        assert!(!AgwError::InvalidConfig("bad".to_string()).is_connection_error());
+    }
+
     #[test]
     fn test_worker_id_generation() {
         // Test that generated worker IDs follow the pattern
@@ -0,0 +1,263 @@
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Environment variable exposing the workspace path to jobs
+pub const WORKDIR_ENV: &str = "AGW_WORKDIR";
+
+/// Default cap on the total size of uploaded artifacts (10MB)
+pub const DEFAULT_MAX_ARTIFACT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Maximum number of artifacts uploaded per job
+const MAX_ARTIFACTS: usize = 100;
+
+/// Isolated working directory for a single job
+///
+/// The directory is removed when the workspace is dropped, so it is cleaned
+/// up however job handling ends.
+#[derive(Debug)]
+pub struct Workspace {
+    path: PathBuf,
+}
+
+/// A file produced by a job in its workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl Workspace {
+    /// Create a fresh workspace for a job under `root`
+    ///
+    /// A random suffix keeps retried or duplicate jobs from sharing a
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be created
+    pub fn create(root: &Path, job_id: &str) -> std::io::Result<Self> {
+        let path = root.join(format!("agw-{job_id}-{}", Uuid::new_v4().simple()));
+        std::fs::create_dir_all(root)?;
+        std::fs::create_dir(&path)?;
+
+        // Other local users have no business reading job data
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o700))?;
+        }
+
+        debug!("Created workspace {}", path.display());
+        Ok(Self { path })
+    }
+
+    /// Path of the workspace directory
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Collect regular files at the top level of the workspace
+    ///
+    /// Symlinks and subdirectories are skipped so a job cannot use artifact
+    /// upload to read files outside its workspace. Collection stops once
+    /// `max_bytes` in total or the artifact count limit would be exceeded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the workspace cannot be read
+    pub fn artifacts(&self, max_bytes: u64) -> std::io::Result<Vec<Artifact>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            match entry?.file_name().into_string() {
+                Ok(name) => names.push(name),
+                Err(_) => warn!("Skipping artifact with non-UTF-8 name"),
+            }
+        }
+        names.sort();
+
+        self.read_artifacts(&names, max_bytes)
+    }
+
+    /// Collect the artifacts a job declared, in declaration order
+    ///
+    /// Declared files that are missing, or are not regular files, are skipped
+    /// with a warning. The same limits as [`Workspace::artifacts`] apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a declared file cannot be read
+    pub fn declared_artifacts(
+        &self,
+        names: &[String],
+        max_bytes: u64,
+    ) -> std::io::Result<Vec<Artifact>> {
+        self.read_artifacts(names, max_bytes)
+    }
+
+    /// Read the named top-level files, skipping anything unsafe to upload
+    fn read_artifacts(&self, names: &[String], max_bytes: u64) -> std::io::Result<Vec<Artifact>> {
+        let mut artifacts = Vec::new();
+        let mut total: u64 = 0;
+
+        for name in names {
+            if !is_valid_artifact_name(name) {
+                warn!("Skipping artifact with invalid name: {name:?}");
+                continue;
+            }
+
+            // symlink_metadata does not follow symlinks
+            let path = self.path.join(name);
+            let metadata = match std::fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    warn!("Artifact {name} not found in {}", self.path.display());
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let size = metadata.len();
+            if artifacts.len() >= MAX_ARTIFACTS || total.saturating_add(size) > max_bytes {
+                warn!(
+                    "Artifact limit reached, skipping remaining files in {}",
+                    self.path.display()
+                );
+                break;
+            }
+
+            total += size;
+            artifacts.push(Artifact {
+                name: name.clone(),
+                data: std::fs::read(path)?,
+            });
+        }
+
+        Ok(artifacts)
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => debug!("Removed workspace {}", self.path.display()),
+            Err(e) => warn!("Failed to remove workspace {}: {e}", self.path.display()),
+        }
+    }
+}
+
+/// Check that an artifact name is a plain file name from a safe charset
+///
+/// Names become part of AGQ keys and are joined onto the workspace path, so
+/// separators, `..`, and hidden files are rejected.
+#[must_use]
+pub fn is_valid_artifact_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_root() -> PathBuf {
+        std::env::temp_dir().join(format!("agw-workspace-test-{}", Uuid::new_v4().simple()))
+    }
+
+    #[test]
+    fn test_workspace_created_and_removed_on_drop() {
+        let root = test_root();
+        let workspace = Workspace::create(&root, "job_1").unwrap();
+        let path = workspace.path().to_path_buf();
+        assert!(path.is_dir());
+        assert!(path.starts_with(&root));
+
+        drop(workspace);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_workspaces_are_unique_per_job_run() {
+        let root = test_root();
+        let a = Workspace::create(&root, "job_1").unwrap();
+        let b = Workspace::create(&root, "job_1").unwrap();
+        assert_ne!(a.path(), b.path());
+        drop((a, b));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_artifacts_collects_top_level_files_only() {
+        let root = test_root();
+        let workspace = Workspace::create(&root, "job_1").unwrap();
+        std::fs::write(workspace.path().join("result.txt"), b"done").unwrap();
+        std::fs::write(workspace.path().join(".hidden"), b"x").unwrap();
+        std::fs::create_dir(workspace.path().join("nested")).unwrap();
+        std::fs::write(workspace.path().join("nested/inner.txt"), b"x").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc/passwd", workspace.path().join("passwd")).unwrap();
+
+        let artifacts = workspace.artifacts(DEFAULT_MAX_ARTIFACT_BYTES).unwrap();
+        assert_eq!(
+            artifacts,
+            vec![Artifact {
+                name: "result.txt".to_string(),
+                data: b"done".to_vec(),
+            }]
+        );
+
+        drop(workspace);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_declared_artifacts() {
+        let root = test_root();
+        let workspace = Workspace::create(&root, "job_1").unwrap();
+        std::fs::write(workspace.path().join("chart.png"), b"png").unwrap();
+        std::fs::write(workspace.path().join("scratch.txt"), b"tmp").unwrap();
+
+        let names = [
+            "chart.png".to_string(),
+            "missing.txt".to_string(),
+            "../escape".to_string(),
+        ];
+        let artifacts = workspace
+            .declared_artifacts(&names, DEFAULT_MAX_ARTIFACT_BYTES)
+            .unwrap();
+        assert_eq!(
+            artifacts,
+            vec![Artifact {
+                name: "chart.png".to_string(),
+                data: b"png".to_vec(),
+            }]
+        );
+
+        drop(workspace);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_artifacts_respects_size_limit() {
+        let root = test_root();
+        let workspace = Workspace::create(&root, "job_1").unwrap();
+        std::fs::write(workspace.path().join("a.bin"), [0u8; 8]).unwrap();
+        std::fs::write(workspace.path().join("b.bin"), [0u8; 8]).unwrap();
+
+        let artifacts = workspace.artifacts(10).unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].name, "a.bin");
+
+        drop(workspace);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
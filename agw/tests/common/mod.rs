@@ -0,0 +1,130 @@
+//! AGQ fixtures shared by the end-to-end tests
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use agq::{start_plan_worker, Database, Server};
+use redis::aio::MultiplexedConnection;
+use tempfile::TempDir;
+
+pub const SESSION_KEY: &str = "routing_test_session_key_0123456";
+
+/// Start AGQ on a free port, returning the port and its data directory
+pub async fn start_agq() -> (u16, TempDir) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open(temp_dir.path().join("agq.redb")).unwrap();
+    tokio::spawn(start_plan_worker(Arc::new(db.clone())));
+    let server = Server::new(
+        &format!("127.0.0.1:{port}"),
+        SESSION_KEY.as_bytes().to_vec(),
+        db,
+    )
+    .await
+    .unwrap();
+    tokio::spawn(server.run());
+
+    (port, temp_dir)
+}
+
+pub async fn connect(port: u16) -> MultiplexedConnection {
+    let client = redis::Client::open(format!("redis://127.0.0.1:{port}")).unwrap();
+    let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+    let _: String = redis::cmd("AUTH")
+        .arg(SESSION_KEY)
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    conn
+}
+
+/// Poll `check` every 100ms until it returns a value, for up to `secs`
+pub async fn wait_for<T, F, Fut>(secs: u64, what: &str, mut check: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(secs);
+    loop {
+        if let Some(value) = check().await {
+            return value;
+        }
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "timed out waiting for {what}"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Wait until the worker's first heartbeat is recorded, which makes it live
+pub async fn wait_for_worker(conn: &MultiplexedConnection, worker_id: &str) {
+    wait_for(10, "the worker's heartbeat", || {
+        let mut conn = conn.clone();
+        let key = format!("worker:{worker_id}");
+        async move {
+            let status: Option<String> = redis::cmd("HGET")
+                .arg(key)
+                .arg("status")
+                .query_async(&mut conn)
+                .await
+                .unwrap();
+            status
+        }
+    })
+    .await;
+}
+
+/// Submit a one-task plan as `plan_id`, run it once, and wait for the job
+///
+/// Returns the job's final status and its stdout.
+pub async fn run_task(conn: &MultiplexedConnection, plan_id: &str, task: &str) -> (String, String) {
+    let plan = format!(r#"{{"plan_id":"{plan_id}","tasks":[{task}]}}"#);
+    let _: String = redis::cmd("PLAN.SUBMIT")
+        .arg(plan)
+        .query_async(&mut conn.clone())
+        .await
+        .unwrap();
+
+    // Plans are stored asynchronously after PLAN.SUBMIT returns
+    let action =
+        format!(r#"{{"action_id":"action_{plan_id}","plan_id":"{plan_id}","inputs":[{{}}]}}"#);
+    let response: String = wait_for(10, "the plan to be stored", || {
+        let mut conn = conn.clone();
+        let action = action.clone();
+        async move {
+            redis::cmd("ACTION.SUBMIT")
+                .arg(action)
+                .query_async::<_, String>(&mut conn)
+                .await
+                .ok()
+        }
+    })
+    .await;
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    let job_id = response["job_ids"][0].as_str().unwrap().to_string();
+
+    let status = wait_for(30, "the job to finish", || {
+        let mut conn = conn.clone();
+        let key = format!("job:{job_id}:status");
+        async move {
+            let status: Option<String> = redis::cmd("GET")
+                .arg(key)
+                .query_async(&mut conn)
+                .await
+                .unwrap();
+            status.filter(|status| status != "pending" && status != "running")
+        }
+    })
+    .await;
+
+    let stdout: Option<String> = redis::cmd("GET")
+        .arg(format!("job:{job_id}:stdout"))
+        .query_async(&mut conn.clone())
+        .await
+        .unwrap();
+    (status, stdout.unwrap_or_default())
+}
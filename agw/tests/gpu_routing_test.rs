@@ -0,0 +1,66 @@
+//! GPU detection end to end: an OCR job reaches a worker whose GPU was
+//! detected rather than configured
+//!
+//! The test puts fake `nvidia-smi` and `agx-ocr` first on `PATH`, so it
+//! lives in its own test binary.
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use common::{connect, run_task, start_agq, wait_for_worker, SESSION_KEY};
+
+const WORKER_ID: &str = "agw-detected-gpu";
+
+fn script(dir: &Path, name: &str, body: &str) {
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ocr_job_reaches_detected_gpu() {
+    let bin = tempfile::TempDir::new().unwrap();
+    script(
+        bin.path(),
+        "nvidia-smi",
+        r#"case "$1" in
+  --query-gpu=utilization.gpu) echo 12 ;;
+  *) echo "NVIDIA GeForce RTX 4090, 24564" ;;
+esac"#,
+    );
+    script(bin.path(), "agx-ocr", "echo 'ocr ran on the detected gpu'");
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let path = std::env::join_paths(
+        std::iter::once(bin.path().to_path_buf()).chain(std::env::split_paths(&path)),
+    )
+    .unwrap();
+    std::env::set_var("PATH", path);
+
+    let (port, _data) = start_agq().await;
+    let conn = connect(port).await;
+
+    // No tags are configured: `gpu` comes from detecting the GPU
+    let worker = agw::WorkerBuilder::new()
+        .agq_address(format!("127.0.0.1:{port}"))
+        .session_key(SESSION_KEY)
+        .worker_id(WORKER_ID)
+        .build()
+        .await
+        .unwrap();
+    tokio::spawn(worker.run());
+    wait_for_worker(&conn, WORKER_ID).await;
+
+    // AGQ tags a task without tags `gpu` when its command runs OCR. Jobs
+    // run with a cleared environment, so the tool is named by its path.
+    let ocr = bin.path().join("agx-ocr");
+    let task = format!(
+        r#"{{"task_number":1,"command":"{}","args":[]}}"#,
+        ocr.display()
+    );
+    let (status, stdout) = run_task(&conn, "plan_detected_gpu", &task).await;
+    assert_eq!(status, "completed");
+    assert_eq!(stdout.trim(), "ocr ran on the detected gpu");
+}
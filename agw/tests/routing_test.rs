@@ -0,0 +1,35 @@
+//! AGQ and AGW end to end: jobs routed by tag reach a worker with the tags
+
+mod common;
+
+use common::{connect, run_task, start_agq, wait_for_worker, SESSION_KEY};
+
+const WORKER_ID: &str = "agw-routing-tagged";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tagged_job_reaches_worker_with_tags() {
+    let (port, _data) = start_agq().await;
+    let conn = connect(port).await;
+
+    let worker = agw::WorkerBuilder::new()
+        .agq_address(format!("127.0.0.1:{port}"))
+        .session_key(SESSION_KEY)
+        .worker_id(WORKER_ID)
+        .tags(["gpu", "high-memory"])
+        .build()
+        .await
+        .unwrap();
+    tokio::spawn(worker.run());
+    wait_for_worker(&conn, WORKER_ID).await;
+
+    // Routed to queue:tags:high-memory, which AGQ only lists for the worker
+    // once the job has been routed there
+    let (status, stdout) = run_task(
+        &conn,
+        "plan_routing_tagged",
+        r#"{"task_number":1,"command":"echo","args":["ran with high memory"],"tags":["high-memory"]}"#,
+    )
+    .await;
+    assert_eq!(status, "completed");
+    assert_eq!(stdout.trim(), "ran with high memory");
+}
@@ -0,0 +1,125 @@
+// src/audit.rs
+//
+// Audit records for compliance: one JSON line per evaluation with
+// everything needed to replay it.
+
+use crate::parser::EvaluationResult;
+use crate::prompt::Prompt;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Everything about one evaluation, filled in as it runs
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    /// When the evaluation started (RFC 3339)
+    pub timestamp: String,
+    pub model: String,
+    pub backend: String,
+    pub temperature: f32,
+    pub max_tokens: usize,
+    /// SHA-256 of the data read from stdin, before any redaction
+    pub input_sha256: Option<String>,
+    /// Number of distinct values redacted from the data
+    pub redacted: usize,
+    /// The prompt exactly as sent to the LLM
+    pub prompt: Option<Prompt>,
+    /// The LLM's answer before parsing
+    pub raw_response: Option<String>,
+    pub status: String,
+    pub result: Option<EvaluationResult>,
+    pub error_code: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: u128,
+}
+
+impl AuditRecord {
+    /// Start a record for an evaluation with the given model settings
+    pub fn new(model: &str, temperature: f32, max_tokens: usize) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            model: model.to_string(),
+            backend: "ollama".to_string(),
+            temperature,
+            max_tokens,
+            input_sha256: None,
+            redacted: 0,
+            prompt: None,
+            raw_response: None,
+            status: "error".to_string(),
+            result: None,
+            error_code: None,
+            error: None,
+            duration_ms: 0,
+        }
+    }
+
+    /// Record the hash of the evaluated data
+    pub fn set_input(&mut self, data: &str) {
+        self.input_sha256 = Some(format!("{:x}", Sha256::digest(data.as_bytes())));
+    }
+
+    /// Append the record to `path` as one JSON line, creating the file if needed
+    ///
+    /// The line is written with a single call, so concurrent evaluations
+    /// appending to one file don't interleave their records.
+    pub fn append_to(&self, path: &Path) -> Result<()> {
+        let mut line = serde_json::to_string(self).context("Failed to serialize audit record")?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("Failed to open audit file {}", path.display()))?;
+        file.write_all(line.as_bytes())
+            .context(format!("Failed to append to audit file {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_input_hash_is_sha256_hex() {
+        let mut record = AuditRecord::new("qwen2.5:1.5b", 0.1, 500);
+        record.set_input("abc");
+        assert_eq!(
+            record.input_sha256.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn test_records_are_appended_as_json_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+
+        let mut record = AuditRecord::new("qwen2.5:1.5b", 0.1, 500);
+        record.prompt = Some(Prompt::Text("Evaluate".to_string()));
+        record.raw_response = Some("{\"decision\":\"yes\"}".to_string());
+        record.status = "success".to_string();
+        record.append_to(&path).unwrap();
+
+        record.status = "error".to_string();
+        record.error_code = Some("parse_error".to_string());
+        record.append_to(&path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["status"], "success");
+        assert_eq!(lines[0]["prompt"]["text"], "Evaluate");
+        assert_eq!(lines[0]["raw_response"], "{\"decision\":\"yes\"}");
+        assert_eq!(lines[1]["error_code"], "parse_error");
+    }
+}
@@ -3,6 +3,9 @@
 // Public library interface for agx-eval
 // Exposes modules for testing and potential library usage
 
+pub mod audit;
 pub mod llm;
 pub mod parser;
 pub mod prompt;
+pub mod redact;
+pub mod score;
@@ -42,6 +42,75 @@ struct GenerateResponse {
     done: Option<bool>,
 }
 
+/// Request payload for Ollama /api/chat endpoint
+#[derive(Debug, Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    options: GenerateOptions,
+}
+
+/// One message of a chat, e.g. the system prompt
+#[derive(Debug, Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+/// Response from Ollama /api/chat endpoint
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    message: ChatMessage,
+}
+
+/// Request payload for Ollama /api/show endpoint
+#[derive(Debug, Serialize)]
+struct ShowRequest {
+    model: String,
+}
+
+/// Request payload for Ollama /api/pull endpoint
+#[derive(Debug, Serialize)]
+struct PullRequest {
+    model: String,
+    stream: bool,
+}
+
+/// One line of the progress Ollama streams while pulling a model
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl PullProgress {
+    /// Percentage of the current layer downloaded, if it is downloading
+    pub fn percent(&self) -> Option<u64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed * 100 / total),
+            _ => None,
+        }
+    }
+
+    /// Progress as a line for stderr, e.g. `pulling 6a0746a1ec1a: 42% (402 MB / 958 MB)`
+    pub fn describe(&self) -> String {
+        match (self.percent(), self.completed, self.total) {
+            (Some(percent), Some(completed), Some(total)) => format!(
+                "{}: {}% ({} MB / {} MB)",
+                self.status,
+                percent,
+                completed / 1_000_000,
+                total / 1_000_000
+            ),
+            _ => self.status.clone(),
+        }
+    }
+}
+
 impl OllamaClient {
     /// Create a new OllamaClient
     ///
@@ -164,6 +233,171 @@ impl OllamaClient {
         Ok(generate_response.response)
     }
 
+    /// Generate a response from the LLM for a system and a user message,
+    /// using the model's chat template
+    ///
+    /// # Errors
+    /// Returns error if:
+    /// - Connection to Ollama fails
+    /// - Request times out
+    /// - Response is malformed
+    /// - Response missing required fields
+    pub async fn chat(&self, system: &str, user: &str) -> Result<String> {
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: system.to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: user.to_string(),
+                },
+            ],
+            stream: false,
+            options: GenerateOptions {
+                temperature: self.temperature,
+                num_predict: self.max_tokens,
+            },
+        };
+
+        let url = format!("{}/api/chat", self.endpoint);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .context(format!(
+                "Failed to connect to Ollama at {}. Is Ollama running?",
+                self.endpoint
+            ))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Ollama API returned error status {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            );
+        }
+
+        let chat_response: ChatResponse = response
+            .json()
+            .await
+            .context("Failed to parse Ollama response as JSON")?;
+
+        Ok(chat_response.message.content)
+    }
+
+    /// Check whether the model has been pulled into Ollama
+    ///
+    /// # Errors
+    /// Returns error if:
+    /// - Connection to Ollama fails
+    /// - Ollama answers with an error other than 404 (not found)
+    pub async fn has_model(&self) -> Result<bool> {
+        let url = format!("{}/api/show", self.endpoint);
+        let response = self
+            .client
+            .post(&url)
+            .json(&ShowRequest {
+                model: self.model.clone(),
+            })
+            .send()
+            .await
+            .context(format!(
+                "Failed to connect to Ollama at {}. Is Ollama running?",
+                self.endpoint
+            ))?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            reqwest::StatusCode::NOT_FOUND => Ok(false),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                anyhow::bail!(
+                    "Ollama API returned error status {}: {}",
+                    status,
+                    body.chars().take(200).collect::<String>()
+                );
+            }
+        }
+    }
+
+    /// Pull the model into Ollama, passing each progress update to `on_progress`
+    ///
+    /// Pulling can take far longer than a generation, so only connecting is
+    /// timed out.
+    ///
+    /// # Errors
+    /// Returns error if:
+    /// - Connection to Ollama fails
+    /// - Ollama reports an error, e.g. for an unknown model
+    /// - The progress stream ends before the pull succeeds
+    pub async fn pull(&self, mut on_progress: impl FnMut(&PullProgress)) -> Result<()> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .context("Failed to build HTTP client")?;
+        let url = format!("{}/api/pull", self.endpoint);
+
+        let mut response = client
+            .post(&url)
+            .json(&PullRequest {
+                model: self.model.clone(),
+                stream: true,
+            })
+            .send()
+            .await
+            .context(format!(
+                "Failed to connect to Ollama at {}. Is Ollama running?",
+                self.endpoint
+            ))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Ollama API returned error status {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            );
+        }
+
+        // Progress arrives as JSON lines, which chunks may split
+        let mut pending = Vec::new();
+        let mut succeeded = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("Failed to read pull progress from Ollama")?
+        {
+            pending.extend_from_slice(&chunk);
+            while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                let progress: PullProgress = serde_json::from_slice(&line)
+                    .context("Failed to parse Ollama pull progress as JSON")?;
+                if let Some(error) = &progress.error {
+                    anyhow::bail!("Ollama could not pull {}: {}", self.model, error);
+                }
+                succeeded |= progress.status == "success";
+                on_progress(&progress);
+            }
+        }
+
+        if !succeeded {
+            anyhow::bail!("Ollama stopped pulling {} before it finished", self.model);
+        }
+        Ok(())
+    }
+
     /// Get the configured endpoint
     #[allow(dead_code)] // Part of public API, used in tests
     pub fn endpoint(&self) -> &str {
@@ -362,6 +596,124 @@ mod tests {
         assert_eq!(response.response, "Hello, world!");
     }
 
+    /// Answer one HTTP request with `status` and `body`, returning the endpoint
+    async fn serve_once(status: &'static str, body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = stream.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        endpoint
+    }
+
+    #[tokio::test]
+    async fn test_chat_returns_assistant_message() {
+        let endpoint = serve_once(
+            "200 OK",
+            r#"{"model":"qwen2.5:1.5b","message":{"role":"assistant","content":"{\"decision\":\"yes\"}"},"done":true}"#,
+        )
+        .await;
+        let client = OllamaClient::new(&endpoint, "qwen2.5:1.5b", 0.1, 500).unwrap();
+
+        let response = client.chat("Evaluate", "data").await.unwrap();
+        assert_eq!(response, r#"{"decision":"yes"}"#);
+    }
+
+    #[test]
+    fn test_chat_request_serialization() {
+        let request = ChatRequest {
+            model: "qwen2.5:1.5b".to_string(),
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "Evaluate".to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: "data".to_string(),
+                },
+            ],
+            stream: false,
+            options: GenerateOptions {
+                temperature: 0.1,
+                num_predict: 500,
+            },
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["messages"][0]["role"], "system");
+        assert_eq!(json["messages"][1]["content"], "data");
+        assert_eq!(json["stream"], false);
+        assert_eq!(json["options"]["num_predict"], 500);
+    }
+
+    #[tokio::test]
+    async fn test_has_model_treats_404_as_missing() {
+        let endpoint = serve_once("404 Not Found", r#"{"error":"model not found"}"#).await;
+        let client = OllamaClient::new(&endpoint, "qwen2.5:1.5b", 0.1, 500).unwrap();
+        assert!(!client.has_model().await.unwrap());
+
+        let endpoint = serve_once("200 OK", r#"{"modelfile":""}"#).await;
+        let client = OllamaClient::new(&endpoint, "qwen2.5:1.5b", 0.1, 500).unwrap();
+        assert!(client.has_model().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_pull_reports_progress_until_success() {
+        let endpoint = serve_once(
+            "200 OK",
+            concat!(
+                "{\"status\":\"pulling manifest\"}\n",
+                "{\"status\":\"pulling 6a0746a1ec1a\",\"digest\":\"sha256:6a0746a1ec1a\",\"total\":958000000,\"completed\":402000000}\n",
+                "{\"status\":\"success\"}\n"
+            ),
+        )
+        .await;
+        let client = OllamaClient::new(&endpoint, "qwen2.5:1.5b", 0.1, 500).unwrap();
+
+        let mut lines = Vec::new();
+        client
+            .pull(|progress| lines.push(progress.describe()))
+            .await
+            .unwrap();
+        assert_eq!(
+            lines,
+            [
+                "pulling manifest",
+                "pulling 6a0746a1ec1a: 41% (402 MB / 958 MB)",
+                "success"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pull_fails_on_reported_error() {
+        let endpoint = serve_once(
+            "200 OK",
+            "{\"status\":\"pulling manifest\"}\n{\"error\":\"pull model manifest: file does not exist\"}\n",
+        )
+        .await;
+        let client = OllamaClient::new(&endpoint, "no-such-model", 0.1, 500).unwrap();
+
+        let err = client.pull(|_| {}).await.unwrap_err().to_string();
+        assert!(
+            err.contains("Ollama could not pull no-such-model: pull model manifest"),
+            "Unexpected error: {}",
+            err
+        );
+    }
+
     #[test]
     fn test_generate_response_minimal() {
         // Ollama might return minimal response
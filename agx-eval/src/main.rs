@@ -1,31 +1,41 @@
 // agx-eval: Generic LLM evaluation Agentic Unit
 //
-// Main orchestration: stdin → prompt → LLM → parse → stdout
+// Main orchestration: stdin → prompt → LLM → parse → stdout, plus the
+// `score` subcommand for checking predictions against golden labels
 
+mod audit;
 mod llm;
 mod parser;
 mod prompt;
+mod redact;
+mod score;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use audit::AuditRecord;
+use clap::{Parser, Subcommand};
 use llm::{get_ollama_endpoint, OllamaClient};
 use parser::{parse_llm_response, EvaluationResult};
-use prompt::PromptBuilder;
+use prompt::{Prompt, PromptBuilder};
 use serde::{Deserialize, Serialize};
 use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 #[derive(Parser, Debug, Clone)]
 #[command(name = "agx-eval")]
 #[command(about = "Generic LLM evaluation Agentic Unit", long_about = None)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Context: background information, criteria, domain knowledge
     #[arg(long, required = true)]
-    context: String,
+    context: Option<String>,
 
     /// Prompt: evaluation question/instruction
     #[arg(long, required = true)]
-    prompt: String,
+    prompt: Option<String>,
 
     /// LLM model to use
     #[arg(long, default_value = "qwen2.5:1.5b")]
@@ -39,11 +49,51 @@ struct Cli {
     #[arg(long, default_value = "500")]
     max_tokens: usize,
 
+    /// Pull the model through Ollama first if it is missing
+    #[arg(long)]
+    auto_pull: bool,
+
+    /// Prompt through /api/chat, with the context and task as the system
+    /// message and the data as the user message
+    #[arg(long)]
+    chat_mode: bool,
+
+    /// Comma-separated paths of JSON fields to redact before sending the
+    /// data to the LLM, e.g. customer.ssn,applicants.email
+    #[arg(long, value_delimiter = ',')]
+    redact_fields: Vec<String>,
+
+    /// Mask email addresses, card numbers and SSNs in the data before
+    /// sending it to the LLM
+    #[arg(long)]
+    redact_pii: bool,
+
+    /// Append an audit record of each evaluation to this JSON lines file
+    #[arg(long)]
+    audit_file: Option<PathBuf>,
+
     /// Output format (json or text)
     #[arg(long, default_value = "json")]
     format: String,
 }
 
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Score predictions against golden labels
+    Score {
+        /// Predictions JSONL: agx-eval output or audit records, or lines
+        /// with decision and confidence, keyed by id or line number
+        predictions: PathBuf,
+
+        /// Golden labels JSONL: lines with label, keyed the same way
+        labels: PathBuf,
+
+        /// Output format (json or text)
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+}
+
 /// Output structure for evaluation results
 #[derive(Debug, Serialize, Deserialize)]
 struct Output {
@@ -62,6 +112,9 @@ struct Metadata {
     model: String,
     backend: String,
     latency_ms: u128,
+    /// Number of distinct values redacted from the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    redacted: Option<usize>,
 }
 
 /// Error information
@@ -94,24 +147,45 @@ fn read_stdin() -> Result<String> {
 }
 
 /// Main evaluation pipeline
-async fn evaluate(args: Cli) -> Result<Output> {
+///
+/// `audit` is filled in as the evaluation goes, so a failed evaluation is
+/// recorded as far as it got.
+async fn evaluate(args: Cli, audit: &mut AuditRecord) -> Result<Output> {
     let start = Instant::now();
 
     // 1. Read stdin data
     tracing::debug!("Reading stdin data");
     let data = read_stdin().context("Failed to read input data")?;
     tracing::debug!("Read {} bytes from stdin", data.len());
+    audit.set_input(&data);
+
+    let (data, redactions) = redact::redact(&data, &args.redact_fields, args.redact_pii)
+        .context("Failed to redact input data")?;
+    if !redactions.is_empty() {
+        tracing::info!("Redacted {} values before prompting", redactions.len());
+    }
+    audit.redacted = redactions.len();
 
     // 2. Build prompt
     tracing::debug!("Building evaluation prompt");
-    let prompt_text = PromptBuilder::new()
-        .with_context(&args.context)
+    let builder = PromptBuilder::new()
+        .with_context(args.context.as_deref().unwrap_or_default())
         .with_data(&data)
-        .with_instruction(&args.prompt)
-        .build()
-        .context("Failed to build prompt")?;
-
-    tracing::debug!("Prompt built: {} chars", prompt_text.len());
+        .with_instruction(args.prompt.as_deref().unwrap_or_default());
+    let prompt = if args.chat_mode {
+        let chat_prompt = builder.build_chat().context("Failed to build prompt")?;
+        tracing::debug!(
+            "Chat prompt built: {} chars system, {} chars user",
+            chat_prompt.system.len(),
+            chat_prompt.user.len()
+        );
+        Prompt::Chat(chat_prompt)
+    } else {
+        let prompt_text = builder.build().context("Failed to build prompt")?;
+        tracing::debug!("Prompt built: {} chars", prompt_text.len());
+        Prompt::Text(prompt_text)
+    };
+    audit.prompt = Some(prompt.clone());
 
     // 3. Call LLM
     tracing::info!("Calling LLM: model={}", args.model);
@@ -119,16 +193,35 @@ async fn evaluate(args: Cli) -> Result<Output> {
     let client = OllamaClient::new(&endpoint, &args.model, args.temperature, args.max_tokens)
         .context("Failed to create LLM client")?;
 
-    let llm_response = client
-        .generate(&prompt_text)
-        .await
-        .context("LLM inference failed")?;
+    if args.auto_pull && !client.has_model().await? {
+        tracing::info!("Model {} is missing; pulling it", args.model);
+        pull_model(&client)
+            .await
+            .context(format!("Failed to pull model {}", args.model))?;
+    }
+
+    let llm_response = match &prompt {
+        Prompt::Text(prompt_text) => client.generate(prompt_text).await,
+        Prompt::Chat(chat_prompt) => client.chat(&chat_prompt.system, &chat_prompt.user).await,
+    }
+    .context("LLM inference failed")?;
 
     tracing::debug!("LLM response: {} chars", llm_response.len());
+    audit.raw_response = Some(llm_response.clone());
 
     // 4. Parse response
     tracing::debug!("Parsing LLM response");
-    let result = parse_llm_response(&llm_response).context("Failed to parse LLM response")?;
+    let mut result = parse_llm_response(&llm_response).context("Failed to parse LLM response")?;
+
+    // Name redacted values again now the answer is back on this machine
+    if !redactions.is_empty() {
+        result.decision = result.decision.map(|d| redactions.restore(&d));
+        result.result = result.result.map(|r| redactions.restore(&r));
+        result.reasoning = redactions.restore(&result.reasoning);
+        for evidence in &mut result.evidence {
+            *evidence = redactions.restore(evidence);
+        }
+    }
 
     let latency = start.elapsed().as_millis();
     tracing::info!("Evaluation complete in {}ms", latency);
@@ -141,11 +234,49 @@ async fn evaluate(args: Cli) -> Result<Output> {
             model: args.model.clone(),
             backend: "ollama".to_string(),
             latency_ms: latency,
+            redacted: (!redactions.is_empty()).then(|| redactions.len()),
         }),
         error: None,
     })
 }
 
+/// Pull the client's model, reporting progress on stderr
+///
+/// Download progress is reported each time it moves 10%, so a pull into a
+/// log file stays readable.
+async fn pull_model(client: &OllamaClient) -> Result<()> {
+    let mut last_status = String::new();
+    let mut last_percent = None;
+    client
+        .pull(|progress| {
+            let percent = progress.percent();
+            let report = progress.status != last_status
+                || matches!(
+                    (last_percent, percent),
+                    (Some(last), Some(now)) if now >= last + 10 || (now == 100 && last < 100)
+                );
+            if report {
+                eprintln!("{}", progress.describe());
+                last_status = progress.status.clone();
+                last_percent = percent;
+            }
+        })
+        .await
+}
+
+/// Score predictions against golden labels, printing the scores
+fn run_score(predictions: &Path, labels: &Path, format: &str) -> Result<()> {
+    let predictions = score::read_predictions(predictions)?;
+    let labels = score::read_labels(labels)?;
+    let scores = score::score(&labels, &predictions);
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&scores)?),
+        "text" => print!("{}", scores.render()),
+        _ => anyhow::bail!("Unsupported output format: {}", format),
+    }
+    Ok(())
+}
+
 /// Format output based on requested format
 fn format_output(output: &Output, format: &str) -> Result<String> {
     match format {
@@ -173,12 +304,19 @@ fn error_to_output(error: anyhow::Error) -> Output {
     let error_msg = error.to_string();
     let code = if error_msg.contains("required") || error_msg.contains("cannot be empty") {
         "invalid_arguments"
-    } else if error_msg.contains("Failed to read") || error_msg.contains("too large") {
+    } else if error_msg.contains("Failed to write audit record") {
+        "audit_failed"
+    } else if error_msg.contains("Failed to read")
+        || error_msg.contains("Failed to redact")
+        || error_msg.contains("too large")
+    {
         "input_error"
     } else if error_msg.contains("Failed to build prompt") {
         "prompt_error"
     } else if error_msg.contains("Failed to create LLM client") {
         "llm_client_error"
+    } else if error_msg.contains("Failed to pull model") {
+        "model_pull_failed"
     } else if error_msg.contains("LLM inference failed") || error_msg.contains("connect") {
         "llm_connection_failed"
     } else if error_msg.contains("Failed to parse") {
@@ -212,6 +350,19 @@ async fn main() {
         .with_writer(std::io::stderr)
         .init();
 
+    if let Some(Command::Score {
+        predictions,
+        labels,
+        format,
+    }) = &args.command
+    {
+        if let Err(error) = run_score(predictions, labels, format) {
+            eprintln!("Error: {:#}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     tracing::info!("agx-eval v0.1.0 starting");
     tracing::debug!(
         "Arguments: model={}, temperature={}, max_tokens={}",
@@ -220,11 +371,14 @@ async fn main() {
         args.max_tokens
     );
 
-    // Extract format before moving args
+    // Extract format and audit file before moving args
     let format = args.format.clone();
+    let audit_file = args.audit_file.clone();
+    let mut audit = AuditRecord::new(&args.model, args.temperature, args.max_tokens);
+    let start = Instant::now();
 
     // Run evaluation and handle errors
-    let output = match evaluate(args).await {
+    let mut output = match evaluate(args, &mut audit).await {
         Ok(output) => output,
         Err(error) => {
             tracing::error!("Evaluation failed: {:#}", error);
@@ -232,6 +386,22 @@ async fn main() {
         }
     };
 
+    // Record the evaluation; without its record it must not count as done
+    if let Some(path) = audit_file {
+        audit.status = output.status.clone();
+        audit.result = output.result.clone();
+        audit.error_code = output.error.as_ref().map(|e| e.code.clone());
+        audit.error = output.error.as_ref().and_then(|e| e.details.clone());
+        audit.duration_ms = start.elapsed().as_millis();
+        if let Err(error) = audit
+            .append_to(&path)
+            .context("Failed to write audit record")
+        {
+            tracing::error!("{:#}", error);
+            output = error_to_output(error);
+        }
+    }
+
     // Format and print output (only to stdout)
     match format_output(&output, &format) {
         Ok(formatted) => {
@@ -4,6 +4,31 @@
 // Combines user context, data, and instruction into a structured prompt.
 
 use anyhow::Result;
+use serde::Serialize;
+
+/// Instructions for the shape of the model's answer
+const RESPONSE_FORMAT: &str = r#"Provide your response in JSON format with:
+- "decision" or "result": Your evaluation
+- "reasoning": Explain step-by-step
+- "confidence": 0-1 score
+- "evidence": Key facts supporting your decision"#;
+
+/// A prompt split into chat messages for chat-tuned models
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatPrompt {
+    /// Context, task and response format
+    pub system: String,
+    /// The data to evaluate
+    pub user: String,
+}
+
+/// The prompt sent to the LLM, as one text or as chat messages
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Prompt {
+    Text(String),
+    Chat(ChatPrompt),
+}
 
 /// Builder for constructing evaluation prompts
 #[derive(Debug, Clone, Default)]
@@ -39,6 +64,60 @@ impl PromptBuilder {
 
     /// Build the final prompt string
     pub fn build(self) -> Result<String> {
+        self.validate()?;
+
+        // Construct the generic prompt template
+        let prompt = format!(
+            r#"# Context
+{}
+
+# Data to Evaluate
+{}
+
+# Task
+{}
+
+{}
+
+Response:"#,
+            self.context.trim(),
+            self.data.trim(),
+            self.instruction.trim(),
+            RESPONSE_FORMAT
+        );
+
+        Ok(prompt)
+    }
+
+    /// Build a system message carrying the context, task and response
+    /// format, and a user message carrying the data
+    pub fn build_chat(self) -> Result<ChatPrompt> {
+        self.validate()?;
+
+        let system = format!(
+            r#"You evaluate data against the context and task below.
+
+# Context
+{}
+
+# Task
+{}
+
+{}"#,
+            self.context.trim(),
+            self.instruction.trim(),
+            RESPONSE_FORMAT
+        );
+
+        Ok(ChatPrompt {
+            system,
+            user: self.data.trim().to_string(),
+        })
+    }
+
+    /// Check that every field is present, within size limits, and free of
+    /// null bytes
+    fn validate(&self) -> Result<()> {
         // Validate that all required fields are provided
         if self.context.trim().is_empty() {
             anyhow::bail!("Context cannot be empty");
@@ -88,30 +167,7 @@ impl PromptBuilder {
             anyhow::bail!("Instruction contains null bytes");
         }
 
-        // Construct the generic prompt template
-        let prompt = format!(
-            r#"# Context
-{}
-
-# Data to Evaluate
-{}
-
-# Task
-{}
-
-Provide your response in JSON format with:
-- "decision" or "result": Your evaluation
-- "reasoning": Explain step-by-step
-- "confidence": 0-1 score
-- "evidence": Key facts supporting your decision
-
-Response:"#,
-            self.context.trim(),
-            self.data.trim(),
-            self.instruction.trim()
-        );
-
-        Ok(prompt)
+        Ok(())
     }
 }
 
@@ -269,6 +325,40 @@ mod tests {
         assert!(prompt.contains("evidence"));
     }
 
+    #[test]
+    fn test_chat_prompt_keeps_data_in_the_user_message() {
+        let prompt = PromptBuilder::new()
+            .with_context("  Job: Senior Rust developer ")
+            .with_data("\nCandidate has 5 years Rust experience\n")
+            .with_instruction("Does candidate meet requirements?")
+            .build_chat()
+            .unwrap();
+
+        assert!(prompt
+            .system
+            .contains("# Context\nJob: Senior Rust developer\n"));
+        assert!(prompt.system.contains("Does candidate meet requirements?"));
+        assert!(prompt
+            .system
+            .contains("Provide your response in JSON format"));
+        assert!(!prompt.system.contains("Candidate has 5 years"));
+        assert_eq!(prompt.user, "Candidate has 5 years Rust experience");
+    }
+
+    #[test]
+    fn test_chat_prompt_is_validated_like_plain_prompt() {
+        let result = PromptBuilder::new()
+            .with_context("Some context")
+            .with_data("   ")
+            .with_instruction("Some instruction")
+            .build_chat();
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Data cannot be empty"));
+    }
+
     #[test]
     fn test_prompt_builder_handles_special_characters() {
         let prompt = PromptBuilder::new()
@@ -0,0 +1,240 @@
+// src/redact.rs
+//
+// Redaction of sensitive values before data is sent to the LLM.
+// Values are swapped for placeholders such as [EMAIL_1]; the map from
+// placeholders back to values stays on this machine so the output can
+// name them again.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Values taken out of the data, in placeholder order
+#[derive(Debug, Default)]
+pub struct RedactionMap {
+    entries: Vec<(String, String)>,
+}
+
+impl RedactionMap {
+    /// The placeholder for `value`, reusing the one given to an equal value
+    /// so the LLM can still tell repeated values apart from distinct ones
+    fn placeholder(&mut self, kind: &str, value: &str) -> String {
+        if let Some((placeholder, _)) = self.entries.iter().find(|(_, v)| v == value) {
+            return placeholder.clone();
+        }
+        let placeholder = format!("[{}_{}]", kind, self.entries.len() + 1);
+        self.entries.push((placeholder.clone(), value.to_string()));
+        placeholder
+    }
+
+    /// Number of distinct values redacted
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing was redacted
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Replace placeholders in `text` with the values they stand for
+    pub fn restore(&self, text: &str) -> String {
+        self.entries
+            .iter()
+            .fold(text.to_string(), |text, (placeholder, value)| {
+                text.replace(placeholder, value)
+            })
+    }
+}
+
+/// Patterns masked in free text: email addresses, card numbers and SSNs
+fn patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        vec![
+            (
+                "EMAIL",
+                Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+            ),
+            ("CARD", Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap()),
+            ("SSN", Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap()),
+        ]
+    })
+}
+
+/// Whether the digits in `number` pass the Luhn check card numbers carry
+fn passes_luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Mask email addresses, card numbers and SSNs in `text`
+pub fn mask_patterns(text: &str, map: &mut RedactionMap) -> String {
+    patterns()
+        .iter()
+        .fold(text.to_string(), |text, (kind, pattern)| {
+            pattern
+                .replace_all(&text, |captures: &regex::Captures| {
+                    let value = &captures[0];
+                    if *kind == "CARD" && !passes_luhn(value) {
+                        value.to_string()
+                    } else {
+                        map.placeholder(kind, value)
+                    }
+                })
+                .into_owned()
+        })
+}
+
+/// Replace the value at each dot-separated path in `json` with a placeholder
+///
+/// A path segment that meets an array applies to every element, so
+/// `applicants.email` redacts the email of each applicant; a numeric
+/// segment picks one element. Paths the data doesn't have are skipped.
+pub fn redact_fields(json: &mut Value, fields: &[String], map: &mut RedactionMap) {
+    for field in fields {
+        let path: Vec<&str> = field.split('.').collect();
+        redact_path(json, &path, map);
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[&str], map: &mut RedactionMap) {
+    let Some((segment, rest)) = path.split_first() else {
+        let original = match &*value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        *value = Value::String(map.placeholder("REDACTED", &original));
+        return;
+    };
+
+    match value {
+        Value::Object(object) => {
+            if let Some(child) = object.get_mut(*segment) {
+                redact_path(child, rest, map);
+            }
+        }
+        Value::Array(items) => match segment.parse::<usize>() {
+            Ok(index) => {
+                if let Some(item) = items.get_mut(index) {
+                    redact_path(item, rest, map);
+                }
+            }
+            Err(_) => {
+                for item in items {
+                    redact_path(item, path, map);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Redact `data` before it leaves the machine
+///
+/// `fields` need `data` to be JSON; `mask_pii` masks patterns in any text.
+pub fn redact(data: &str, fields: &[String], mask_pii: bool) -> Result<(String, RedactionMap)> {
+    let mut map = RedactionMap::default();
+
+    let mut data = if fields.is_empty() {
+        data.to_string()
+    } else {
+        let mut json: Value = serde_json::from_str(data)
+            .context("--redact-fields needs JSON input, but stdin is not valid JSON")?;
+        redact_fields(&mut json, fields, &mut map);
+        serde_json::to_string_pretty(&json).context("Failed to serialize redacted data")?
+    };
+
+    if mask_pii {
+        data = mask_patterns(&data, &mut map);
+    }
+
+    Ok((data, map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_fields_nested_and_in_arrays() {
+        let mut value = json!({
+            "customer": {"name": "Ada", "ssn": "123-45-6789"},
+            "applicants": [
+                {"email": "a@example.com", "score": 7},
+                {"email": "b@example.com", "score": 9}
+            ],
+            "accounts": [{"balance": 10}, {"balance": 20}]
+        });
+        let mut map = RedactionMap::default();
+        let fields = [
+            "customer.ssn".to_string(),
+            "applicants.email".to_string(),
+            "accounts.1.balance".to_string(),
+            "missing.field".to_string(),
+        ];
+
+        redact_fields(&mut value, &fields, &mut map);
+
+        assert_eq!(value["customer"]["ssn"], "[REDACTED_1]");
+        assert_eq!(value["customer"]["name"], "Ada");
+        assert_eq!(value["applicants"][0]["email"], "[REDACTED_2]");
+        assert_eq!(value["applicants"][1]["email"], "[REDACTED_3]");
+        assert_eq!(value["applicants"][1]["score"], 9);
+        assert_eq!(value["accounts"][0]["balance"], 10);
+        assert_eq!(value["accounts"][1]["balance"], "[REDACTED_4]");
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn test_mask_patterns_in_free_text() {
+        let mut map = RedactionMap::default();
+        let text = "Contact jo.doe@example.com, SSN 123-45-6789, card 4111 1111 1111 1111, \
+                    order 1234567890123, again jo.doe@example.com";
+
+        let masked = mask_patterns(text, &mut map);
+
+        assert_eq!(
+            masked,
+            "Contact [EMAIL_1], SSN [SSN_3], card [CARD_2], \
+             order 1234567890123, again [EMAIL_1]"
+        );
+        assert_eq!(map.restore(&masked), text);
+    }
+
+    #[test]
+    fn test_luhn_check() {
+        assert!(passes_luhn("4111-1111-1111-1111"));
+        assert!(passes_luhn("5500 0000 0000 0004"));
+        assert!(!passes_luhn("4111 1111 1111 1112"));
+    }
+
+    #[test]
+    fn test_redact_requires_json_for_fields() {
+        let err = redact("plain text", &["a.b".to_string()], false).unwrap_err();
+        assert!(err.to_string().contains("--redact-fields needs JSON input"));
+
+        let (data, map) = redact("mail me at x@y.org", &[], true).unwrap();
+        assert_eq!(data, "mail me at [EMAIL_1]");
+        assert_eq!(map.restore("[EMAIL_1] is valid"), "x@y.org is valid");
+    }
+
+    #[test]
+    fn test_redact_without_options_is_unchanged() {
+        let (data, map) = redact("{\"email\": \"x@y.org\"}", &[], false).unwrap();
+        assert_eq!(data, "{\"email\": \"x@y.org\"}");
+        assert!(map.is_empty());
+    }
+}
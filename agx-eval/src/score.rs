@@ -0,0 +1,412 @@
+// src/score.rs
+//
+// Scoring of evaluation predictions against golden labels, so prompt and
+// model changes can be compared by numbers rather than by eye.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Confidence thresholds swept when scoring
+const THRESHOLDS: [f64; 10] = [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9];
+
+/// One evaluation's answer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    /// `None` if the evaluation failed
+    pub decision: Option<String>,
+    pub confidence: f64,
+}
+
+/// Scores of one decision class
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassScore {
+    pub class: String,
+    /// Examples labeled with this class
+    pub support: usize,
+    /// Examples predicted as this class
+    pub predicted: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// Accuracy on the predictions at or above a confidence threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdScore {
+    pub threshold: f64,
+    pub kept: usize,
+    /// Share of labeled examples kept
+    pub coverage: f64,
+    /// `None` if no prediction is confident enough
+    pub accuracy: Option<f64>,
+}
+
+/// Scores of a set of predictions against golden labels
+#[derive(Debug, Clone, Serialize)]
+pub struct Score {
+    /// Labeled examples
+    pub total: usize,
+    /// Labeled examples without a prediction, or whose evaluation failed;
+    /// they count as wrong
+    pub missing: usize,
+    /// Predictions without a golden label, which are ignored
+    pub unlabeled: usize,
+    pub accuracy: f64,
+    pub macro_f1: f64,
+    pub classes: Vec<ClassScore>,
+    pub thresholds: Vec<ThresholdScore>,
+}
+
+/// Decisions are compared ignoring case and surrounding whitespace
+fn normalize(decision: &str) -> String {
+    decision.trim().to_lowercase()
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// The object holding an evaluation's fields: `result` in agx-eval output
+/// and audit records, or the line itself
+fn result_of(line: &Value) -> &Value {
+    match line.get("result") {
+        Some(result) if result.is_object() => result,
+        _ => line,
+    }
+}
+
+/// The decision of a line, from `decision` or `result`
+fn decision_of(line: &Value) -> Option<String> {
+    let result = result_of(line);
+    result
+        .get("decision")
+        .or_else(|| result.get("result"))
+        .and_then(Value::as_str)
+        .map(normalize)
+}
+
+/// Read a JSON lines file, keying each line by its `id` or, without one,
+/// by its line number
+fn read_lines(path: &Path) -> Result<Vec<(String, Value)>> {
+    let contents =
+        std::fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+
+    let mut lines = Vec::new();
+    let mut seen = BTreeSet::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).context(format!(
+            "Failed to parse {} line {} as JSON",
+            path.display(),
+            index + 1
+        ))?;
+        let id = match value.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            Some(id) => id.to_string(),
+            None => (index + 1).to_string(),
+        };
+        if !seen.insert(id.clone()) {
+            anyhow::bail!("Duplicate id {} in {}", id, path.display());
+        }
+        lines.push((id, value));
+    }
+    Ok(lines)
+}
+
+/// Read predictions: agx-eval output or audit records, or lines with
+/// `decision` and `confidence`
+pub fn read_predictions(path: &Path) -> Result<HashMap<String, Prediction>> {
+    Ok(read_lines(path)?
+        .into_iter()
+        .map(|(id, line)| {
+            let prediction = Prediction {
+                decision: decision_of(&line),
+                confidence: result_of(&line)
+                    .get("confidence")
+                    .and_then(Value::as_f64)
+                    .unwrap_or(0.0),
+            };
+            (id, prediction)
+        })
+        .collect())
+}
+
+/// Read golden labels: lines with `label` (or `decision`)
+pub fn read_labels(path: &Path) -> Result<Vec<(String, String)>> {
+    read_lines(path)?
+        .into_iter()
+        .map(|(id, line)| {
+            let label = match line.get("label").and_then(Value::as_str) {
+                Some(label) => normalize(label),
+                None => decision_of(&line).context(format!(
+                    "Golden label {} in {} has no label",
+                    id,
+                    path.display()
+                ))?,
+            };
+            Ok((id, label))
+        })
+        .collect()
+}
+
+/// Score `predictions` against `labels`
+pub fn score(labels: &[(String, String)], predictions: &HashMap<String, Prediction>) -> Score {
+    let pairs: Vec<(&str, Option<&Prediction>)> = labels
+        .iter()
+        .map(|(id, label)| (label.as_str(), predictions.get(id)))
+        .collect();
+    let total = pairs.len();
+    let predicted = |prediction: Option<&Prediction>| prediction.and_then(|p| p.decision.clone());
+
+    let missing = pairs
+        .iter()
+        .filter(|(_, prediction)| predicted(*prediction).is_none())
+        .count();
+    let correct = pairs
+        .iter()
+        .filter(|(label, prediction)| predicted(*prediction).as_deref() == Some(*label))
+        .count();
+    let unlabeled = predictions
+        .keys()
+        .filter(|id| !labels.iter().any(|(label_id, _)| label_id == *id))
+        .count();
+
+    let class_names: BTreeSet<String> = pairs
+        .iter()
+        .map(|(label, _)| label.to_string())
+        .chain(
+            pairs
+                .iter()
+                .filter_map(|(_, prediction)| predicted(*prediction)),
+        )
+        .collect();
+    let classes: Vec<ClassScore> = class_names
+        .into_iter()
+        .map(|class| {
+            let support = pairs.iter().filter(|(label, _)| *label == class).count();
+            let predicted_count = pairs
+                .iter()
+                .filter(|(_, prediction)| predicted(*prediction).as_deref() == Some(&class))
+                .count();
+            let true_positives = pairs
+                .iter()
+                .filter(|(label, prediction)| {
+                    *label == class && predicted(*prediction).as_deref() == Some(*label)
+                })
+                .count();
+            let precision = ratio(true_positives, predicted_count);
+            let recall = ratio(true_positives, support);
+            let f1 = if precision + recall > 0.0 {
+                2.0 * precision * recall / (precision + recall)
+            } else {
+                0.0
+            };
+            ClassScore {
+                class,
+                support,
+                predicted: predicted_count,
+                precision,
+                recall,
+                f1,
+            }
+        })
+        .collect();
+    let macro_f1 = if classes.is_empty() {
+        0.0
+    } else {
+        classes.iter().map(|class| class.f1).sum::<f64>() / classes.len() as f64
+    };
+
+    let thresholds = THRESHOLDS
+        .iter()
+        .map(|&threshold| {
+            let kept: Vec<_> = pairs
+                .iter()
+                .filter_map(|(label, prediction)| {
+                    let prediction = (*prediction)?;
+                    let decision = prediction.decision.as_deref()?;
+                    (prediction.confidence >= threshold).then_some(decision == *label)
+                })
+                .collect();
+            let kept_correct = kept.iter().filter(|&&correct| correct).count();
+            ThresholdScore {
+                threshold,
+                kept: kept.len(),
+                coverage: ratio(kept.len(), total),
+                accuracy: (!kept.is_empty()).then(|| ratio(kept_correct, kept.len())),
+            }
+        })
+        .collect();
+
+    Score {
+        total,
+        missing,
+        unlabeled,
+        accuracy: ratio(correct, total),
+        macro_f1,
+        classes,
+        thresholds,
+    }
+}
+
+impl Score {
+    /// Render the scores as tables for a terminal
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "Scored {} examples ({} without a prediction, {} predictions unlabeled)\n\
+             Accuracy: {:.3}   Macro F1: {:.3}\n\n",
+            self.total, self.missing, self.unlabeled, self.accuracy, self.macro_f1
+        );
+
+        let width = self
+            .classes
+            .iter()
+            .map(|class| class.class.len())
+            .chain(std::iter::once("class".len()))
+            .max()
+            .unwrap_or(0);
+        out.push_str(&format!(
+            "{:<width$}  support  predicted  precision  recall     f1\n",
+            "class"
+        ));
+        for class in &self.classes {
+            out.push_str(&format!(
+                "{:<width$}  {:>7}  {:>9}  {:>9.3}  {:>6.3}  {:>5.3}\n",
+                class.class,
+                class.support,
+                class.predicted,
+                class.precision,
+                class.recall,
+                class.f1
+            ));
+        }
+
+        out.push_str("\nconfidence >=  kept  coverage  accuracy\n");
+        for threshold in &self.thresholds {
+            let accuracy = threshold
+                .accuracy
+                .map(|accuracy| format!("{:.3}", accuracy))
+                .unwrap_or_else(|| "-".to_string());
+            out.push_str(&format!(
+                "{:>13.1}  {:>4}  {:>8.3}  {:>8}\n",
+                threshold.threshold, threshold.kept, threshold.coverage, accuracy
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn jsonl(lines: &[&str]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn test_reads_output_audit_and_flat_predictions() {
+        let file = jsonl(&[
+            r#"{"id": "a", "status": "success", "result": {"decision": "Accept", "reasoning": "", "confidence": 0.9}}"#,
+            r#"{"id": 2, "decision": "reject", "confidence": 0.4}"#,
+            r#"{"id": "c", "status": "error", "result": null, "error_code": "parse_error"}"#,
+        ]);
+
+        let predictions = read_predictions(file.path()).unwrap();
+
+        assert_eq!(predictions["a"].decision.as_deref(), Some("accept"));
+        assert_eq!(predictions["a"].confidence, 0.9);
+        assert_eq!(predictions["2"].decision.as_deref(), Some("reject"));
+        assert_eq!(predictions["c"].decision, None);
+    }
+
+    #[test]
+    fn test_lines_without_ids_are_keyed_by_line_number() {
+        let file = jsonl(&[r#"{"label": "yes"}"#, "", r#"{"decision": "No"}"#]);
+        assert_eq!(
+            read_labels(file.path()).unwrap(),
+            [
+                ("1".to_string(), "yes".to_string()),
+                ("3".to_string(), "no".to_string())
+            ]
+        );
+
+        let duplicate = jsonl(&[
+            r#"{"id": 1, "label": "yes"}"#,
+            r#"{"id": 1, "label": "no"}"#,
+        ]);
+        let err = read_labels(duplicate.path()).unwrap_err().to_string();
+        assert!(err.contains("Duplicate id 1"), "Unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_score_per_class_and_thresholds() {
+        let labels: Vec<(String, String)> = [
+            ("1", "accept"),
+            ("2", "accept"),
+            ("3", "reject"),
+            ("4", "reject"),
+        ]
+        .iter()
+        .map(|(id, label)| (id.to_string(), label.to_string()))
+        .collect();
+        let prediction = |decision: Option<&str>, confidence| Prediction {
+            decision: decision.map(str::to_string),
+            confidence,
+        };
+        let predictions = HashMap::from([
+            ("1".to_string(), prediction(Some("accept"), 0.95)),
+            ("2".to_string(), prediction(Some("reject"), 0.55)),
+            ("3".to_string(), prediction(Some("reject"), 0.8)),
+            ("4".to_string(), prediction(None, 0.0)),
+            ("5".to_string(), prediction(Some("accept"), 0.7)),
+        ]);
+
+        let score = score(&labels, &predictions);
+
+        assert_eq!(score.total, 4);
+        assert_eq!(score.missing, 1);
+        assert_eq!(score.unlabeled, 1);
+        assert_eq!(score.accuracy, 0.5);
+
+        let accept = &score.classes[0];
+        assert_eq!(accept.class, "accept");
+        assert_eq!((accept.support, accept.predicted), (2, 1));
+        assert_eq!((accept.precision, accept.recall), (1.0, 0.5));
+        let reject = &score.classes[1];
+        assert_eq!((reject.precision, reject.recall), (0.5, 0.5));
+        assert_eq!(reject.f1, 0.5);
+
+        let at = |threshold: f64| {
+            score
+                .thresholds
+                .iter()
+                .find(|t| (t.threshold - threshold).abs() < 1e-9)
+                .unwrap()
+        };
+        assert_eq!((at(0.0).kept, at(0.0).accuracy), (3, Some(2.0 / 3.0)));
+        assert_eq!((at(0.6).kept, at(0.6).accuracy), (2, Some(1.0)));
+        assert_eq!((at(0.9).kept, at(0.9).coverage), (1, 0.25));
+
+        let text = score.render();
+        assert!(
+            text.contains("Scored 4 examples (1 without a prediction, 1 predictions unlabeled)")
+        );
+        assert!(text.contains("Accuracy: 0.500"));
+    }
+}
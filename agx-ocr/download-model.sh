@@ -67,6 +67,26 @@ echo ""
 echo "==> Renaming model weights..."
 mv "$MODEL_DIR/model-00001-of-000001.safetensors" "$MODEL_DIR/model.safetensors"
 
+# Record the SHA-256 checksums Hugging Face publishes for the large files,
+# so agx-ocr can detect a corrupt or truncated download on startup
+echo ""
+echo "==> Recording checksums..."
+CHECKSUMS="$MODEL_DIR/checksums.sha256"
+if command -v curl &> /dev/null && command -v python3 &> /dev/null; then
+    curl -fsSL "https://huggingface.co/api/models/$HF_REPO/tree/main" | python3 -c '
+import json, sys
+renames = {"model-00001-of-000001.safetensors": "model.safetensors"}
+for entry in json.load(sys.stdin):
+    path = entry.get("path")
+    if path in ("config.json", "tokenizer.json", "model-00001-of-000001.safetensors") and entry.get("lfs"):
+        print(entry["lfs"]["oid"] + "  " + renames.get(path, path))
+' > "$CHECKSUMS" || echo "    Could not fetch checksums from Hugging Face; skipping"
+    [ -s "$CHECKSUMS" ] || rm -f "$CHECKSUMS"
+    rm -f "$MODEL_DIR/checksums.verified"
+else
+    echo "    curl or python3 not found; skipping (truncated weights are still detected)"
+fi
+
 echo ""
 echo "=== Download Complete! ==="
 echo ""
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Instructions for the post-correction pass. The model must fix recognition
+/// errors only, never rewrite the text.
+const CORRECTION_PROMPT: &str = "The text below was transcribed from an image by an OCR model \
+and may contain recognition errors: misread letters, wrongly split or merged words, and \
+garbled punctuation. Fix only those errors. Do not rephrase, summarize, translate or add \
+anything, keep the original line breaks and any [illegible] markers, and reply with the \
+corrected text only.";
+
+#[derive(Debug, Deserialize)]
+struct GenerateResponse {
+    response: String,
+}
+
+/// Ask a local Ollama model to fix recognition errors in OCR output.
+pub fn correct_text(text: &str, model: &str, endpoint: &str) -> Result<String> {
+    if text.trim().is_empty() {
+        return Ok(text.to_string());
+    }
+
+    let url = format!("{}/api/generate", endpoint.trim_end_matches('/'));
+    let request = serde_json::json!({
+        "model": model,
+        "prompt": format!("{CORRECTION_PROMPT}\n\nText:\n{text}"),
+        "stream": false,
+        "options": { "temperature": 0.0 },
+    });
+
+    let response: GenerateResponse = ureq::post(&url)
+        .timeout(Duration::from_secs(300))
+        .send_json(request)
+        .with_context(|| {
+            format!("Post-correction with {model} failed; is Ollama running at {endpoint}?")
+        })?
+        .into_json()
+        .context("Failed to parse Ollama post-correction response")?;
+
+    Ok(response.response.trim().to_string())
+}
@@ -39,6 +39,41 @@ pub fn print_model_card() -> Result<()> {
                 "type": "string",
                 "description": "Filesystem path to DeepSeek GGUF model file.",
                 "default": null
+            },
+            "preset": {
+                "type": "string",
+                "enum": ["document", "handwriting"],
+                "description": "Prompt and decode settings for the kind of input.",
+                "default": "document"
+            },
+            "mode": {
+                "type": "string",
+                "enum": ["text", "form"],
+                "description": "What to extract: all text, or form fields as a fields map of label to value with per-field confidence.",
+                "default": "text"
+            },
+            "roi": {
+                "type": "array",
+                "items": { "type": "string", "pattern": "^\\d+,\\d+,\\d+,\\d+$" },
+                "description": "Regions of interest as x,y,w,h pixels; each is read separately and reported in regions.",
+                "default": []
+            },
+            "correct-with": {
+                "type": "string",
+                "description": "Local Ollama model for a post-correction pass; the uncorrected text is returned as raw_text.",
+                "default": null
+            },
+            "verify-against": {
+                "type": "string",
+                "description": "Expected text file; the output is compared word by word and a similarity score and mismatched spans are returned as verification.",
+                "default": null
+            },
+            "min-similarity": {
+                "type": "number",
+                "minimum": 0,
+                "maximum": 1,
+                "description": "With verify-against, fail when similarity is below this threshold.",
+                "default": null
             }
         }),
     };
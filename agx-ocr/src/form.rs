@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+/// Prompt for `--mode form`: one `Label: Value` line per field, which is
+/// easier for the OCR model to produce reliably than JSON.
+pub const FORM_PROMPT: &str = "<image>\nExtract every field of this form as key-value pairs: \
+each printed label or field name, and the value filled in next to it. Write one field per \
+line as `Label: Value`, with the value exactly as written. Leave the value empty if the field \
+is blank. Include dates, totals and reference numbers. Do not add anything else.";
+
+/// Fields read from a form, with a confidence score per field.
+#[derive(Debug, Default, PartialEq)]
+pub struct FormFields {
+    pub fields: BTreeMap<String, String>,
+    pub confidence: BTreeMap<String, f32>,
+}
+
+/// Parse `Label: Value` lines from the model's output.
+///
+/// Lines without a label continue the previous value, as with multi-line
+/// addresses. The engine gives no token probabilities, so confidence is
+/// judged from the text: values the model marked unreadable, labels seen
+/// more than once, and values that are mostly symbols score lower; blank
+/// fields score 0.5 since "not filled in" is often a misread.
+pub fn parse_fields(text: &str) -> FormFields {
+    let mut order: Vec<(String, String, bool)> = Vec::new();
+
+    for line in text.lines() {
+        let line = line
+            .trim()
+            .trim_start_matches(['-', '*', '•'])
+            .replace("**", "");
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('|') {
+            continue;
+        }
+
+        let split = line
+            .split_once(':')
+            .or_else(|| line.split_once('：'))
+            .filter(|(label, _)| is_label(label));
+        match split {
+            Some((label, value)) => {
+                let label = label.trim().to_string();
+                let duplicate = order.iter().any(|(seen, _, _)| *seen == label);
+                let label = if duplicate {
+                    let count = order
+                        .iter()
+                        .filter(|(seen, _, _)| {
+                            *seen == label || seen.starts_with(&format!("{label} ("))
+                        })
+                        .count();
+                    format!("{label} ({})", count + 1)
+                } else {
+                    label
+                };
+                order.push((label, value.trim().to_string(), duplicate));
+            }
+            None => {
+                if let Some((_, value, _)) = order.last_mut() {
+                    if !value.is_empty() {
+                        value.push('\n');
+                    }
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+
+    let mut form = FormFields::default();
+    for (label, value, duplicate) in order {
+        form.confidence
+            .insert(label.clone(), confidence(&value, duplicate));
+        form.fields.insert(label, value);
+    }
+    form
+}
+
+/// A label is short and has at least one letter, so times like `12:30`
+/// aren't mistaken for fields.
+fn is_label(label: &str) -> bool {
+    let label = label.trim();
+    !label.is_empty() && label.chars().count() <= 80 && label.chars().any(char::is_alphabetic)
+}
+
+fn confidence(value: &str, duplicate: bool) -> f32 {
+    if value.is_empty() {
+        return 0.5;
+    }
+    let mut score: f32 = 0.9;
+    if value.contains("[illegible]") || value.contains('?') || value.contains('\u{FFFD}') {
+        score -= 0.4;
+    }
+    if duplicate {
+        score -= 0.2;
+    }
+    let symbols = value
+        .chars()
+        .filter(|c| !c.is_alphanumeric() && !c.is_whitespace())
+        .count();
+    if symbols * 2 > value.chars().count() {
+        score -= 0.2;
+    }
+    score.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_parsed_from_label_value_lines() {
+        let form = parse_fields(
+            "**Invoice Number:** INV-2041\n\
+             - Date: 2024-03-01\n\
+             Time: 12:30\n\
+             Billing Address: 1 Main St\n\
+             Springfield\n\
+             Signature:\n\
+             Total: $1,204.50\n",
+        );
+
+        assert_eq!(form.fields["Invoice Number"], "INV-2041");
+        assert_eq!(form.fields["Date"], "2024-03-01");
+        assert_eq!(form.fields["Time"], "12:30");
+        assert_eq!(form.fields["Billing Address"], "1 Main St\nSpringfield");
+        assert_eq!(form.fields["Signature"], "");
+        assert_eq!(form.fields["Total"], "$1,204.50");
+        assert_eq!(form.confidence["Invoice Number"], 0.9);
+        assert_eq!(form.confidence["Signature"], 0.5);
+    }
+
+    #[test]
+    fn doubtful_fields_score_lower() {
+        let form = parse_fields("Name: J. [illegible]\nName: Jane Doe\nRef: --/--\n");
+
+        assert_eq!(form.fields["Name"], "J. [illegible]");
+        assert_eq!(form.fields["Name (2)"], "Jane Doe");
+        assert!((form.confidence["Name"] - 0.5).abs() < 1e-6);
+        assert!((form.confidence["Name (2)"] - 0.7).abs() < 1e-6);
+        assert!((form.confidence["Ref"] - 0.7).abs() < 1e-6);
+    }
+}
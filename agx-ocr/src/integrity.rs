@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+/// Manifest of expected checksums in the model directory, in `sha256sum` format.
+pub const MANIFEST: &str = "checksums.sha256";
+
+/// Files already verified against the manifest, so startup doesn't rehash
+/// gigabytes of weights every run. Each line is `<sha256>  <size>  <mtime>  <file>`.
+const VERIFIED: &str = "checksums.verified";
+
+/// Check the model files before the engine loads them.
+///
+/// A safetensors weights file is always checked for truncation, which is
+/// cheap. If the directory has a `checksums.sha256` manifest, every file it
+/// lists must also match its SHA-256.
+pub fn verify_model_files(model_dir: &Path, weights_path: &Path) -> Result<()> {
+    if weights_path
+        .extension()
+        .is_some_and(|ext| ext == "safetensors")
+    {
+        check_safetensors_length(weights_path)?;
+    }
+
+    let manifest_path = model_dir.join(MANIFEST);
+    if manifest_path.exists() {
+        verify_manifest(model_dir, &manifest_path)?;
+    }
+    Ok(())
+}
+
+/// Fail if a safetensors file is shorter than its header says it should be.
+fn check_safetensors_length(path: &Path) -> Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let actual = file.metadata()?.len();
+    let truncated = |expected: u64| {
+        anyhow::anyhow!(
+            "{} is truncated: expected at least {} bytes, found {}. \
+             Was the download interrupted? Re-run download-model.sh.",
+            path.display(),
+            expected,
+            actual
+        )
+    };
+
+    let mut len_bytes = [0u8; 8];
+    if actual < 8 {
+        return Err(truncated(8));
+    }
+    file.read_exact(&mut len_bytes)?;
+    let header_len = u64::from_le_bytes(len_bytes);
+    if header_len > 100 * 1024 * 1024 {
+        bail!(
+            "{} is not a valid safetensors file: header claims {} bytes",
+            path.display(),
+            header_len
+        );
+    }
+    if actual < 8 + header_len {
+        return Err(truncated(8 + header_len));
+    }
+
+    let mut header = vec![0u8; header_len as usize];
+    file.read_exact(&mut header)?;
+    let header: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&header)
+        .with_context(|| {
+            format!(
+                "{} is not a valid safetensors file: its header is not JSON",
+                path.display()
+            )
+        })?;
+
+    // The data section ends where the last tensor does
+    let data_len = header
+        .iter()
+        .filter(|(name, _)| name.as_str() != "__metadata__")
+        .filter_map(|(_, tensor)| tensor.get("data_offsets")?.get(1)?.as_u64())
+        .max()
+        .unwrap_or(0);
+    let expected = 8 + header_len + data_len;
+    if actual < expected {
+        return Err(truncated(expected));
+    }
+    Ok(())
+}
+
+/// Hash every file listed in the manifest and compare.
+fn verify_manifest(model_dir: &Path, manifest_path: &Path) -> Result<()> {
+    let manifest = std::fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+
+    let verified_path = model_dir.join(VERIFIED);
+    let previously_verified = read_verified(&verified_path);
+    let mut verified = Vec::new();
+
+    for (index, line) in manifest.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((expected, name)) = line.split_once(char::is_whitespace) else {
+            bail!(
+                "{} line {} is not `<sha256>  <file>`",
+                manifest_path.display(),
+                index + 1
+            );
+        };
+        let expected = expected.to_ascii_lowercase();
+        // `sha256sum` marks files hashed in binary mode with `*`
+        let name = name.trim_start().trim_start_matches('*');
+
+        let path = model_dir.join(name);
+        let metadata = std::fs::metadata(&path).with_context(|| {
+            format!(
+                "{} is listed in {} but missing",
+                path.display(),
+                manifest_path.display()
+            )
+        })?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let record = format!("{expected}  {size}  {mtime}  {name}");
+        if previously_verified.get(name) == Some(&record) {
+            verified.push(record);
+            continue;
+        }
+
+        let actual = sha256_file(&path)?;
+        if actual != expected {
+            bail!(
+                "{} is corrupt or incomplete: its SHA-256 is {} but {} records {}. \
+                 Re-run download-model.sh to fetch it again.",
+                path.display(),
+                actual,
+                MANIFEST,
+                expected
+            );
+        }
+        verified.push(record);
+    }
+
+    // Best effort: a read-only model directory just means hashing again next run
+    let _ = std::fs::write(&verified_path, verified.join("\n") + "\n");
+    Ok(())
+}
+
+/// Previously verified records by file name.
+fn read_verified(path: &Path) -> HashMap<String, String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let name = line.splitn(4, "  ").nth(3)?;
+            Some((name.to_string(), line.to_string()))
+        })
+        .collect()
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A safetensors file with one 2-element F32 tensor, cut to `keep` bytes.
+    fn write_safetensors(path: &Path, keep: Option<usize>) {
+        let header = br#"{"w":{"dtype":"F32","shape":[2],"data_offsets":[0,8]}}"#;
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&[0u8; 8]);
+        bytes.truncate(keep.unwrap_or(bytes.len()));
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn truncated_weights_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let weights = dir.path().join("model.safetensors");
+
+        write_safetensors(&weights, None);
+        verify_model_files(dir.path(), &weights).unwrap();
+
+        write_safetensors(&weights, Some(68));
+        let err = verify_model_files(dir.path(), &weights)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("is truncated: expected at least 70 bytes, found 68"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn manifest_checksums_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let weights = dir.path().join("model.safetensors");
+        write_safetensors(&weights, None);
+        std::fs::write(dir.path().join("config.json"), "{}").unwrap();
+        let good = format!(
+            "{}  model.safetensors\n{} *config.json\n",
+            sha256_file(&weights).unwrap(),
+            // sha256 of "{}"
+            "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        std::fs::write(dir.path().join(MANIFEST), &good).unwrap();
+
+        verify_model_files(dir.path(), &weights).unwrap();
+        assert!(dir.path().join(VERIFIED).exists());
+
+        std::fs::write(dir.path().join("config.json"), "{ }").unwrap();
+        let err = verify_model_files(dir.path(), &weights)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("config.json is corrupt or incomplete"),
+            "{err}"
+        );
+
+        std::fs::remove_file(dir.path().join("config.json")).unwrap();
+        let err = verify_model_files(dir.path(), &weights)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("config.json is listed in"), "{err}");
+    }
+}
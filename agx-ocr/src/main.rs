@@ -2,14 +2,31 @@ use std::io::{self, Read};
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 mod ocr;
 mod model;
 mod describe;
+mod integrity;
+mod correct;
+mod form;
+mod preset;
+mod roi;
+mod verify;
 mod types;
 
 use crate::model::ModelConfig;
+use crate::preset::Preset;
+use crate::roi::Roi;
+
+/// What to extract from the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// All text, as one string
+    Text,
+    /// Form fields, as a `fields` map of label to filled-in value
+    Form,
+}
 
 /// agx-ocr: DeepSeek OCR Agentic Unit
 #[derive(Parser, Debug)]
@@ -33,6 +50,41 @@ struct Cli {
     /// Example: agx-ocr "Extract chart data as JSON" < chart.png
     #[arg(value_name = "PROMPT")]
     prompt_positional: Option<String>,
+
+    /// Prompt and decode settings for the kind of input
+    #[arg(long = "preset", value_enum, default_value_t = Preset::Document)]
+    preset: Preset,
+
+    /// What to extract: all text, or form fields as key-value pairs
+    #[arg(long = "mode", value_enum, default_value_t = Mode::Text)]
+    mode: Mode,
+
+    /// Region of interest as x,y,w,h in pixels; repeat for several regions.
+    /// Each region is read separately and reported in `regions`
+    #[arg(long = "roi", value_name = "X,Y,W,H")]
+    roi: Vec<Roi>,
+
+    /// Ollama model for a post-correction pass over the recognized text
+    /// Example: agx-ocr --preset handwriting --correct-with qwen2.5:7b < note.jpg
+    #[arg(long = "correct-with", value_name = "MODEL")]
+    correct_with: Option<String>,
+
+    /// Ollama endpoint used for post-correction
+    #[arg(
+        long = "ollama-endpoint",
+        env = "OLLAMA_ENDPOINT",
+        default_value = "http://localhost:11434"
+    )]
+    ollama_endpoint: String,
+
+    /// Compare the output with the expected text in this file, reporting a
+    /// similarity score and mismatched spans in `verification`
+    #[arg(long = "verify-against", value_name = "FILE")]
+    verify_against: Option<PathBuf>,
+
+    /// With --verify-against, exit non-zero if similarity is below this (0-1)
+    #[arg(long = "min-similarity", requires = "verify_against")]
+    min_similarity: Option<f64>,
 }
 
 fn main() -> Result<()> {
@@ -45,8 +97,11 @@ fn main() -> Result<()> {
 
     let cfg = ModelConfig::from_cli(cli.model_path)?;
 
-    // Determine prompt: --prompt flag takes precedence, then positional arg, then default
-    let prompt_str = cli.prompt.or(cli.prompt_positional);
+    // Determine prompt: --prompt flag takes precedence, then positional arg, then the
+    // form-mode prompt, then the preset's default
+    let prompt_str = cli.prompt.or(cli.prompt_positional).or_else(|| {
+        (cli.mode == Mode::Form).then(|| form::FORM_PROMPT.to_string())
+    });
     let prompt = prompt_str.as_deref();
 
     // Read binary input from stdin
@@ -55,12 +110,40 @@ fn main() -> Result<()> {
         .read_to_end(&mut buf)
         .context("Failed to read image bytes from stdin")?;
 
-    let result = ocr::run_ocr(&buf, &cfg, prompt)?;
+    let mut result = ocr::run_ocr(&buf, &cfg, prompt, cli.preset, &cli.roi)?;
+
+    // Optional post-correction: keep what the OCR model saw alongside the fix
+    if let Some(model) = cli.correct_with {
+        let corrected = correct::correct_text(&result.text, &model, &cli.ollama_endpoint)?;
+        result.raw_text = Some(std::mem::replace(&mut result.text, corrected));
+        result.corrected_by = Some(model);
+    }
+
+    if cli.mode == Mode::Form {
+        let form = form::parse_fields(&result.text);
+        result.fields = Some(form.fields);
+        result.field_confidence = Some(form.confidence);
+    }
+
+    if let Some(path) = &cli.verify_against {
+        result.verification = Some(verify::verify_against(&result.text, path)?);
+    }
 
     // Write structured JSON to stdout
     let json = serde_json::to_string_pretty(&result)
         .context("Failed to serialize OCR result to JSON")?;
     println!("{}", json);
 
+    // Fail regression checks after the output is written, so the mismatches
+    // can still be inspected
+    if let (Some(min), Some(verification)) = (cli.min_similarity, &result.verification) {
+        anyhow::ensure!(
+            verification.similarity >= min,
+            "OCR output similarity {:.3} is below the required {:.3}",
+            verification.similarity,
+            min
+        );
+    }
+
     Ok(())
 }
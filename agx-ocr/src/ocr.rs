@@ -1,38 +1,73 @@
 use anyhow::{Context, Result};
 use image::DynamicImage;
 
+use crate::integrity;
 use crate::model::ModelConfig;
-use crate::types::OcrResult;
+use crate::preset::Preset;
+use crate::roi::Roi;
+use crate::types::{OcrRegion, OcrResult};
 
 // DeepSeek OCR engine imports
 use candle_core::{DType, Device};
-use deepseek_ocr_core::inference::{
-    DecodeParameters, ModelKind, ModelLoadArgs, VisionSettings,
-};
+use deepseek_ocr_core::inference::{ModelKind, ModelLoadArgs};
 use deepseek_ocr_infer_deepseek::load_model;
 use tokenizers::Tokenizer;
 
-/// Default prompt used when no custom prompt is provided
-const DEFAULT_PROMPT: &str = "<image>\nExtract all text from this image.";
-
-pub fn run_ocr(image_bytes: &[u8], cfg: &ModelConfig, custom_prompt: Option<&str>) -> Result<OcrResult> {
+pub fn run_ocr(
+    image_bytes: &[u8],
+    cfg: &ModelConfig,
+    custom_prompt: Option<&str>,
+    preset: Preset,
+    rois: &[Roi],
+) -> Result<OcrResult> {
     // Decode image from bytes
     let img = image::load_from_memory(image_bytes)
         .context("Failed to decode image bytes from stdin")?;
 
+    // Check regions before spending time on loading the model
+    for roi in rois {
+        roi.check_within(img.width(), img.height())?;
+    }
+
+    // Without regions of interest the whole image is read; with them, each
+    // crop is read separately
+    let images: Vec<DynamicImage> = if rois.is_empty() {
+        vec![img]
+    } else {
+        rois.iter()
+            .map(|roi| img.crop_imm(roi.x, roi.y, roi.w, roi.h))
+            .collect()
+    };
+
     // Delegate to DeepSeek engine with custom prompt if provided
-    let text = run_engine(&img, &cfg.model_path, custom_prompt)?;
+    let texts = run_engine(&images, &cfg.model_path, custom_prompt, preset)?;
+
+    // The DeepSeek engine doesn't expose bounding boxes in its current API,
+    // so regions are only reported for caller-supplied regions of interest
+    let regions: Vec<OcrRegion> = rois
+        .iter()
+        .zip(&texts)
+        .map(|(roi, text)| OcrRegion {
+            text: text.clone(),
+            confidence: None,
+            bbox: roi.bbox(),
+        })
+        .collect();
 
-    // For now, we only return the full OCR text without region-level details
-    // The DeepSeek engine doesn't expose bounding boxes in its current API
     Ok(OcrResult {
-        text,
-        regions: vec![], // TODO: Add region detection if needed
+        text: texts.join("\n\n"),
+        regions,
         model: format!("deepseek-ocr ({})", cfg.model_path.display()),
+        raw_text: None,
+        corrected_by: None,
+        fields: None,
+        field_confidence: None,
+        verification: None,
     })
 }
 
-/// Runs the DeepSeek OCR engine on the provided image.
+/// Runs the DeepSeek OCR engine on each of the provided images, loading the
+/// model once.
 ///
 /// The model_path should point to a directory containing:
 /// - config.json: Model configuration
@@ -41,7 +76,13 @@ pub fn run_ocr(image_bytes: &[u8], cfg: &ModelConfig, custom_prompt: Option<&str
 ///
 /// The custom_prompt parameter allows specifying task-specific instructions.
 /// Use <image> token to denote where the image should be placed in the prompt.
-fn run_engine(img: &DynamicImage, model_path: &std::path::Path, custom_prompt: Option<&str>) -> Result<String> {
+/// The preset supplies the default prompt, vision settings and decode parameters.
+fn run_engine(
+    images: &[DynamicImage],
+    model_path: &std::path::Path,
+    custom_prompt: Option<&str>,
+    preset: Preset,
+) -> Result<Vec<String>> {
     // Validate that model_path is a directory
     anyhow::ensure!(
         model_path.is_dir(),
@@ -76,6 +117,9 @@ fn run_engine(img: &DynamicImage, model_path: &std::path::Path, custom_prompt: O
         tokenizer_path.display()
     );
 
+    // Catch truncated or corrupt downloads before the engine trips over them
+    integrity::verify_model_files(model_path, &weights_path)?;
+
     // Select device (prefer Metal on macOS, fallback to CPU)
     let device = Device::new_metal(0).unwrap_or(Device::Cpu);
 
@@ -103,28 +147,12 @@ fn run_engine(img: &DynamicImage, model_path: &std::path::Path, custom_prompt: O
     let tokenizer = Tokenizer::from_file(&tokenizer_path)
         .map_err(|e| anyhow::anyhow!("Failed to load tokenizer from {}: {}", tokenizer_path.display(), e))?;
 
-    // Prepare vision settings (using defaults from DeepSeek OCR CLI)
-    let vision_settings = VisionSettings {
-        base_size: 2,
-        image_size: 640,
-        crop_mode: false,
-    };
-
-    // Prepare decode parameters (conservative defaults)
-    let decode_params = DecodeParameters {
-        max_new_tokens: 4096,
-        do_sample: false,
-        temperature: 0.0,
-        top_p: None,
-        top_k: None,
-        repetition_penalty: 1.0,
-        no_repeat_ngram_size: None,
-        seed: None,
-        use_cache: true,
-    };
+    // Vision settings and decode parameters come from the preset
+    let vision_settings = preset.vision_settings();
+    let decode_params = preset.decode_parameters();
 
-    // Use custom prompt if provided, otherwise use default
-    let prompt = custom_prompt.unwrap_or(DEFAULT_PROMPT);
+    // Use custom prompt if provided, otherwise the preset's
+    let prompt = custom_prompt.unwrap_or(preset.default_prompt());
 
     // Ensure prompt contains <image> token
     anyhow::ensure!(
@@ -133,17 +161,21 @@ fn run_engine(img: &DynamicImage, model_path: &std::path::Path, custom_prompt: O
         prompt
     );
 
-    // Run OCR inference
-    let outcome = model
-        .decode(
-            &tokenizer,
-            prompt,
-            &[img.clone()],
-            vision_settings,
-            &decode_params,
-            None, // No streaming callback
-        )
-        .context("OCR inference failed")?;
-
-    Ok(outcome.text)
+    // Run OCR inference, one image at a time
+    images
+        .iter()
+        .map(|img| {
+            let outcome = model
+                .decode(
+                    &tokenizer,
+                    prompt,
+                    &[img.clone()],
+                    vision_settings,
+                    &decode_params,
+                    None, // No streaming callback
+                )
+                .context("OCR inference failed")?;
+            Ok(outcome.text)
+        })
+        .collect()
 }
@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+use deepseek_ocr_core::inference::{DecodeParameters, VisionSettings};
+
+/// Prompt and decode settings tuned for a kind of input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Printed documents, screenshots, charts and tables
+    #[default]
+    Document,
+    /// Handwritten notes, forms and letters
+    Handwriting,
+}
+
+impl Preset {
+    /// Prompt used when no custom prompt is given.
+    pub fn default_prompt(self) -> &'static str {
+        match self {
+            Preset::Document => "<image>\nExtract all text from this image.",
+            Preset::Handwriting => {
+                "<image>\nTranscribe the handwritten text in this image exactly as written, \
+                 line by line. Keep the original line breaks, spelling and punctuation. \
+                 Write [illegible] for any word you cannot read."
+            }
+        }
+    }
+
+    pub fn vision_settings(self) -> VisionSettings {
+        match self {
+            Preset::Document => VisionSettings {
+                base_size: 2,
+                image_size: 640,
+                crop_mode: false,
+            },
+            // Strokes are thin and irregular: look at the page at full
+            // resolution and in local crops
+            Preset::Handwriting => VisionSettings {
+                base_size: 1024,
+                image_size: 640,
+                crop_mode: true,
+            },
+        }
+    }
+
+    pub fn decode_parameters(self) -> DecodeParameters {
+        let document = DecodeParameters {
+            max_new_tokens: 4096,
+            do_sample: false,
+            temperature: 0.0,
+            top_p: None,
+            top_k: None,
+            repetition_penalty: 1.0,
+            no_repeat_ngram_size: None,
+            seed: None,
+            use_cache: true,
+        };
+        match self {
+            Preset::Document => document,
+            // Uncertain handwriting makes greedy decoding prone to looping
+            // on a misread word
+            Preset::Handwriting => DecodeParameters {
+                repetition_penalty: 1.05,
+                no_repeat_ngram_size: Some(20),
+                ..document
+            },
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+/// A region of interest in image pixels: top-left corner, width and height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Roi {
+    /// Fail unless the region lies inside a `width`×`height` image.
+    pub fn check_within(&self, width: u32, height: u32) -> Result<()> {
+        let fits = |start: u32, len: u32, limit: u32| {
+            start.checked_add(len).is_some_and(|end| end <= limit)
+        };
+        if !fits(self.x, self.w, width) || !fits(self.y, self.h, height) {
+            bail!("ROI {self} extends past the {width}x{height} image");
+        }
+        Ok(())
+    }
+
+    /// `[x1, y1, x2, y2]`, as in `OcrRegion::bbox`.
+    pub fn bbox(&self) -> [f32; 4] {
+        [
+            self.x as f32,
+            self.y as f32,
+            (self.x + self.w) as f32,
+            (self.y + self.h) as f32,
+        ]
+    }
+}
+
+impl FromStr for Roi {
+    type Err = String;
+
+    /// Parse `x,y,w,h`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(',')
+            .map(|part| part.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("expected x,y,w,h as whole pixels, got '{s}'"))?;
+        let [x, y, w, h] = parts[..] else {
+            return Err(format!("expected four numbers x,y,w,h, got '{s}'"));
+        };
+        if w == 0 || h == 0 {
+            return Err(format!("region '{s}' has zero width or height"));
+        }
+        Ok(Roi { x, y, w, h })
+    }
+}
+
+impl fmt::Display for Roi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.w, self.h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_parsed_and_checked() {
+        let roi: Roi = "10, 20,300,40".parse().unwrap();
+        assert_eq!(
+            roi,
+            Roi {
+                x: 10,
+                y: 20,
+                w: 300,
+                h: 40
+            }
+        );
+        assert_eq!(roi.bbox(), [10.0, 20.0, 310.0, 60.0]);
+
+        assert!(roi.check_within(310, 60).is_ok());
+        let err = roi.check_within(300, 60).unwrap_err().to_string();
+        assert_eq!(err, "ROI 10,20,300,40 extends past the 300x60 image");
+
+        assert!("10,20,300".parse::<Roi>().is_err());
+        assert!("10,20,0,40".parse::<Roi>().is_err());
+        assert!("-1,0,5,5".parse::<Roi>().is_err());
+    }
+}
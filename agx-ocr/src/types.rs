@@ -1,12 +1,18 @@
+use std::collections::BTreeMap;
+
 use serde::Serialize;
 
+use crate::verify::Verification;
+
 /// High-level OCR output structure returned by this AU.
 /// This does not need to mirror the deepseek-ocr engine types exactly;
 /// it is the stable contract for AGEniX pipelines.
 #[derive(Debug, Serialize)]
 pub struct OcrRegion {
     pub text: String,
-    pub confidence: f32,
+    /// Absent when the engine gives no confidence, as for regions of interest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
     /// [x1, y1, x2, y2] in image coordinates
     pub bbox: [f32; 4],
 }
@@ -16,4 +22,19 @@ pub struct OcrResult {
     pub text: String,
     pub regions: Vec<OcrRegion>,
     pub model: String,
+    /// Text as recognized, before LLM post-correction replaced `text`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_text: Option<String>,
+    /// Ollama model that post-corrected `text`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corrected_by: Option<String>,
+    /// Form fields by label, in `--mode form`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, String>>,
+    /// Confidence (0-1) of each form field, by label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_confidence: Option<BTreeMap<String, f32>>,
+    /// Comparison with expected text, in `--verify-against`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
 }
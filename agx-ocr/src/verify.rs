@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+
+/// Largest word-by-word table diffed, about 200 MB; beyond that the texts
+/// are too different in size to be the same scan anyway.
+const MAX_CELLS: usize = 50_000_000;
+
+/// A run of words where the OCR output differs from the expected text.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Mismatch {
+    /// Index of the first differing word in the expected text
+    pub expected_word: usize,
+    /// Index of the first differing word in the OCR output
+    pub actual_word: usize,
+    /// Expected words missing from the output; empty for an insertion
+    pub expected: String,
+    /// Output words not in the expected text; empty for a deletion
+    pub actual: String,
+}
+
+/// OCR output compared with expected text.
+#[derive(Debug, Serialize)]
+pub struct Verification {
+    pub expected_file: String,
+    /// 2 × matching words / total words, from 0 (nothing in common) to 1
+    pub similarity: f64,
+    pub expected_words: usize,
+    pub actual_words: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Compare OCR output with the text in `expected_path`.
+pub fn verify_against(actual: &str, expected_path: &Path) -> Result<Verification> {
+    let expected = std::fs::read_to_string(expected_path)
+        .with_context(|| format!("Failed to read expected text {}", expected_path.display()))?;
+    let mut verification = compare(&expected, actual)?;
+    verification.expected_file = expected_path.display().to_string();
+    Ok(verification)
+}
+
+/// Diff two texts word by word; whitespace differences are ignored.
+pub fn compare(expected: &str, actual: &str) -> Result<Verification> {
+    let a: Vec<&str> = expected.split_whitespace().collect();
+    let b: Vec<&str> = actual.split_whitespace().collect();
+
+    // Matching ends don't need the table
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let cells = (mid_a.len() + 1) * (mid_b.len() + 1);
+    if cells > MAX_CELLS {
+        bail!(
+            "Texts are too long to compare ({} and {} differing words)",
+            mid_a.len(),
+            mid_b.len()
+        );
+    }
+
+    // lcs[i][j]: longest common subsequence of mid_a[i..] and mid_b[j..]
+    let width = mid_b.len() + 1;
+    let mut lcs = vec![0u32; cells];
+    for i in (0..mid_a.len()).rev() {
+        for j in (0..mid_b.len()).rev() {
+            lcs[i * width + j] = if mid_a[i] == mid_b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut mismatches: Vec<Mismatch> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut open: Option<(usize, usize, Vec<&str>, Vec<&str>)> = None;
+    let mut close = |open: &mut Option<(usize, usize, Vec<&str>, Vec<&str>)>| {
+        if let Some((expected_word, actual_word, expected, actual)) = open.take() {
+            mismatches.push(Mismatch {
+                expected_word: prefix + expected_word,
+                actual_word: prefix + actual_word,
+                expected: expected.join(" "),
+                actual: actual.join(" "),
+            });
+        }
+    };
+    while i < mid_a.len() || j < mid_b.len() {
+        if i < mid_a.len() && j < mid_b.len() && mid_a[i] == mid_b[j] {
+            close(&mut open);
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let span = open.get_or_insert_with(|| (i, j, Vec::new(), Vec::new()));
+        if j == mid_b.len()
+            || (i < mid_a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            span.2.push(mid_a[i]);
+            i += 1;
+        } else {
+            span.3.push(mid_b[j]);
+            j += 1;
+        }
+    }
+    close(&mut open);
+
+    let matched = prefix + suffix + lcs[0] as usize;
+    let total = a.len() + b.len();
+    let similarity = if total == 0 {
+        1.0
+    } else {
+        2.0 * matched as f64 / total as f64
+    };
+
+    Ok(Verification {
+        expected_file: String::new(),
+        similarity,
+        expected_words: a.len(),
+        actual_words: b.len(),
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_text_ignoring_whitespace_matches() {
+        let v = compare("Total:  $12.50\nThank you", "Total: $12.50 Thank\tyou\n").unwrap();
+        assert_eq!(v.similarity, 1.0);
+        assert!(v.mismatches.is_empty());
+    }
+
+    #[test]
+    fn mismatched_spans_are_reported() {
+        let v = compare(
+            "Invoice 2041 Total $12.50 Thank you for your order",
+            "Invoice 2O41 Total $12.50 Thank you your order today",
+        )
+        .unwrap();
+
+        assert_eq!(
+            v.mismatches,
+            [
+                Mismatch {
+                    expected_word: 1,
+                    actual_word: 1,
+                    expected: "2041".to_string(),
+                    actual: "2O41".to_string(),
+                },
+                Mismatch {
+                    expected_word: 6,
+                    actual_word: 6,
+                    expected: "for".to_string(),
+                    actual: String::new(),
+                },
+                Mismatch {
+                    expected_word: 9,
+                    actual_word: 8,
+                    expected: String::new(),
+                    actual: "today".to_string(),
+                },
+            ]
+        );
+        // 7 of 9 expected and 7 of 9 actual words match
+        assert!((v.similarity - 7.0 / 9.0).abs() < 1e-9);
+    }
+}
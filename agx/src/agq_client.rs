@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader, Read, Write};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
 use std::net::TcpStream;
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, SystemTime};
 
 #[derive(Debug, Clone)]
@@ -29,6 +31,8 @@ impl AgqConfig {
 
 pub struct AgqClient {
     config: AgqConfig,
+    /// Authenticated connection kept between requests
+    connection: Mutex<Option<BufReader<TcpStream>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +66,162 @@ pub struct ActionEnvelope {
     pub job_ids: Vec<String>,
 }
 
+/// One run of a Plan, as reported by PLAN.STATUS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanStatus {
+    pub plan_id: String,
+    pub action_id: String,
+    pub status: String,
+    #[serde(default)]
+    pub summary: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub tasks: Vec<TaskStatus>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskStatus {
+    pub job_id: String,
+    pub task_number: u32,
+    pub command: String,
+    pub status: String,
+    pub exit_code: Option<i64>,
+    pub worker_id: Option<String>,
+    pub started_at: Option<u64>,
+    pub completed_at: Option<u64>,
+    pub duration_ms: Option<u64>,
+    #[serde(default)]
+    pub retries: u32,
+}
+
+/// Output of one run of a Plan, as reported by PLAN.RESULTS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanResults {
+    pub status: String,
+    #[serde(default)]
+    pub results: Vec<TaskOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskOutput {
+    pub job_id: String,
+    pub task_number: u32,
+    pub status: String,
+    pub stdout: Option<String>,
+    #[serde(default = "default_encoding")]
+    pub stdout_encoding: String,
+    pub stderr: Option<String>,
+    #[serde(default = "default_encoding")]
+    pub stderr_encoding: String,
+}
+
+/// A job listed by JOBS.LIST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub job_id: String,
+    pub status: String,
+    pub created_at: String,
+}
+
+/// A job listed by JOB.LIST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobEntry {
+    pub job_id: String,
+    pub plan_id: String,
+    pub action_id: String,
+    pub task_number: u32,
+    pub command: String,
+    pub status: String,
+    #[serde(default)]
+    pub worker_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Unix time the job was submitted
+    pub created_at: u64,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+}
+
+/// A page of jobs from JOB.LIST, and how many match in all
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobPage {
+    pub jobs: Vec<JobEntry>,
+    pub total: u64,
+}
+
+/// Filters for JOB.LIST; unset fields match every job
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobFilter {
+    pub plan_id: Option<String>,
+    pub status: Option<String>,
+    pub worker: Option<String>,
+    pub limit: Option<u64>,
+}
+
+/// A live worker, as reported by WORKERS.LIST
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerInfo {
+    pub worker_id: String,
+    /// Unix time of the worker's last heartbeat
+    pub last_seen: u64,
+    pub status: String,
+    /// Comma-separated tools the worker can run
+    #[serde(default)]
+    pub tools: String,
+    /// Metrics from the last heartbeat, if it sent any
+    #[serde(default)]
+    pub metrics: serde_json::Value,
+}
+
+/// A job as stored by AGQ, from JOB.GET
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub action_id: String,
+    pub plan_id: String,
+    pub task_number: u32,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub status: String,
+    #[serde(default)]
+    pub worker_id: Option<String>,
+    /// Jobs that must complete before this one starts
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+/// A tool live workers can run, as reported by TOOLS.CATALOG
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub name: String,
+    /// The tool's `--describe` model card, if its workers registered one
+    #[serde(default)]
+    pub card: Option<serde_json::Value>,
+    #[serde(default)]
+    pub workers: Vec<String>,
+}
+
+/// Execution history of a tool, as reported by TOOLS.STATS
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolStats {
+    pub tool: String,
+    /// Runs that completed
+    pub runs: u64,
+    /// Runs that failed or timed out
+    pub failed: u64,
+    pub mean_duration_ms: u64,
+    pub max_duration_ms: u64,
+    /// Average CPU time, if workers measured it
+    #[serde(default)]
+    pub mean_cpu_ms: Option<u64>,
+}
+
+fn default_encoding() -> String {
+    "utf8".to_string()
+}
+
 impl ActionEnvelope {
     /// Validate that jobs_created matches job_ids length
     /// Prevents silent failures from AGQ data inconsistencies
@@ -79,23 +239,14 @@ impl ActionEnvelope {
 
 impl AgqClient {
     pub fn new(config: AgqConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            connection: Mutex::new(None),
+        }
     }
 
     pub fn submit_plan(&self, plan_json: &str) -> Result<SubmissionResult, String> {
-        let mut reader = self.connect_and_auth()?;
-
-        let submit = resp_array(&["PLAN.SUBMIT", plan_json]);
-        {
-            let stream = reader.get_mut();
-            stream
-                .write_all(&submit)
-                .map_err(|e| format!("failed to send PLAN.SUBMIT: {e}"))?;
-        }
-
-        let response = read_resp_value(&mut reader)?;
-
-        match response {
+        match self.request(&["PLAN.SUBMIT", plan_json])? {
             RespValue::SimpleString(s) | RespValue::BulkString(s) => Ok(SubmissionResult {
                 job_id: s,
                 submitted_at: SystemTime::now(),
@@ -106,28 +257,11 @@ impl AgqClient {
     }
 
     pub fn submit_action(&self, action_json: &str) -> Result<ActionEnvelope, String> {
-        let mut reader = self.connect_and_auth()?;
-
-        let submit = resp_array(&["ACTION.SUBMIT", action_json]);
-        {
-            let stream = reader.get_mut();
-            stream
-                .write_all(&submit)
-                .map_err(|e| format!("failed to send ACTION.SUBMIT: {e}"))?;
-        }
-
-        let response = read_resp_value(&mut reader)?;
-
-        match response {
-            RespValue::BulkString(s) => {
-                let envelope: ActionEnvelope = serde_json::from_str(&s)
-                    .map_err(|e| format!("failed to parse ACTION.SUBMIT response: {e}"))?;
-                envelope.validate()?;
-                Ok(envelope)
-            }
-            RespValue::Error(msg) => Err(format!("AGQ error: {msg}")),
-            other => Err(format!("unexpected AGQ response: {:?}", other)),
-        }
+        let json_str = self.bulk_query(&["ACTION.SUBMIT", action_json])?;
+        let envelope: ActionEnvelope = serde_json::from_str(&json_str)
+            .map_err(|e| format!("failed to parse ACTION.SUBMIT response: {e}"))?;
+        envelope.validate()?;
+        Ok(envelope)
     }
 
     pub fn list_jobs(&self) -> Result<OpsResponse, String> {
@@ -142,18 +276,93 @@ impl AgqClient {
         self.simple_query("QUEUE.STATS", OpsResponse::QueueStats)
     }
 
-    pub fn list_plans(&self) -> Result<Vec<PlanSummary>, String> {
-        let mut reader = self.connect_and_auth()?;
-        let command = resp_array(&["PLAN.LIST"]);
-        {
-            let stream = reader.get_mut();
-            stream
-                .write_all(&command)
-                .map_err(|e| format!("failed to send PLAN.LIST: {e}"))?;
+    /// Recent jobs, newest first, as reported by JOBS.LIST
+    pub fn jobs(&self) -> Result<Vec<JobSummary>, String> {
+        let OpsResponse::Jobs(fields) = self.list_jobs()? else {
+            return Err("unexpected JOBS.LIST response".to_string());
+        };
+        // Each job is sent as three fields: id, status, and creation time
+        if fields.len() % 3 != 0 {
+            return Err(format!(
+                "malformed JOBS.LIST response: {} fields is not a multiple of 3",
+                fields.len()
+            ));
+        }
+        Ok(fields
+            .chunks(3)
+            .map(|job| JobSummary {
+                job_id: job[0].clone(),
+                status: job[1].clone(),
+                created_at: job[2].clone(),
+            })
+            .collect())
+    }
+
+    /// Live workers, most recently seen first, as reported by WORKERS.LIST
+    pub fn workers(&self) -> Result<Vec<WorkerInfo>, String> {
+        let OpsResponse::Workers(items) = self.list_workers()? else {
+            return Err("unexpected WORKERS.LIST response".to_string());
+        };
+        items
+            .iter()
+            .map(|item| {
+                serde_json::from_str(item).map_err(|e| format!("failed to parse worker: {e}"))
+            })
+            .collect()
+    }
+
+    /// Jobs matching a filter, newest first, as reported by JOB.LIST
+    pub fn job_list(&self, filter: &JobFilter) -> Result<JobPage, String> {
+        let limit = filter.limit.map(|limit| limit.to_string());
+        let mut args = vec!["JOB.LIST"];
+        for (name, value) in [
+            ("plan_id", &filter.plan_id),
+            ("status", &filter.status),
+            ("worker", &filter.worker),
+            ("limit", &limit),
+        ] {
+            if let Some(value) = value {
+                args.extend([name, value.as_str()]);
+            }
         }
+        let json_str = self.bulk_query(&args)?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse job list: {e}"))
+    }
+
+    /// Cancel a job and the pending jobs downstream of it, returning how
+    /// many were cancelled
+    pub fn cancel_job(&self, job_id: &str) -> Result<u64, String> {
+        validate_id(job_id, "job_id")?;
+        self.integer_query(&["JOB.CANCEL", job_id])
+    }
+
+    /// Cancel every unfinished job of a Plan, returning how many were
+    /// cancelled
+    pub fn cancel_plan(&self, plan_id: &str) -> Result<u64, String> {
+        validate_id(plan_id, "plan_id")?;
+        self.integer_query(&["PLAN.CANCEL", plan_id])
+    }
+
+    /// Delete a finished job, returning 1, or 0 if it did not exist
+    pub fn purge_job(&self, job_id: &str) -> Result<u64, String> {
+        validate_id(job_id, "job_id")?;
+        self.integer_query(&["JOB.PURGE", job_id])
+    }
+
+    /// Delete every job that finished at least `secs` ago, returning how
+    /// many were deleted
+    pub fn purge_older_than(&self, secs: u64) -> Result<u64, String> {
+        self.integer_query(&["JOB.PURGE", "OLDER_THAN", &secs.to_string()])
+    }
+
+    pub fn get_job(&self, job_id: &str) -> Result<JobRecord, String> {
+        validate_id(job_id, "job_id")?;
+        let json_str = self.bulk_query(&["JOB.GET", job_id])?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse job: {e}"))
+    }
 
-        let response = read_resp_value(&mut reader)?;
-        match response {
+    pub fn list_plans(&self) -> Result<Vec<PlanSummary>, String> {
+        match self.request(&["PLAN.LIST"])? {
             RespValue::Array(items) => {
                 let mut plans = Vec::new();
                 for item in items {
@@ -180,40 +389,72 @@ impl AgqClient {
 
     pub fn get_plan(&self, plan_id: &str) -> Result<crate::plan::WorkflowPlan, String> {
         // Validate plan_id to prevent RESP injection and ensure reasonable length
-        if !plan_id
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
-        {
-            return Err(
-                "invalid plan_id: must contain only alphanumeric characters, underscore, or dash"
-                    .to_string(),
-            );
-        }
+        validate_id(plan_id, "plan_id")?;
 
-        if plan_id.is_empty() {
-            return Err("plan_id cannot be empty".to_string());
-        }
+        let json_str = self.bulk_query(&["PLAN.GET", plan_id])?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse plan: {e}"))
+    }
+
+    /// Status of a Plan's latest run, or of the given Action
+    pub fn plan_status(
+        &self,
+        plan_id: &str,
+        action_id: Option<&str>,
+    ) -> Result<PlanStatus, String> {
+        let json_str = self.plan_query("PLAN.STATUS", plan_id, action_id)?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse plan status: {e}"))
+    }
+
+    /// Output of a Plan's latest run, or of the given Action
+    pub fn plan_results(
+        &self,
+        plan_id: &str,
+        action_id: Option<&str>,
+    ) -> Result<PlanResults, String> {
+        let json_str = self.plan_query("PLAN.RESULTS", plan_id, action_id)?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse plan results: {e}"))
+    }
 
-        if plan_id.len() > 128 {
-            return Err("plan_id too long (max 128 characters)".to_string());
+    fn plan_query(
+        &self,
+        command: &str,
+        plan_id: &str,
+        action_id: Option<&str>,
+    ) -> Result<String, String> {
+        validate_id(plan_id, "plan_id")?;
+        let mut args = vec![command, plan_id];
+        if let Some(action_id) = action_id {
+            validate_id(action_id, "action_id")?;
+            args.push(action_id);
         }
+        self.bulk_query(&args)
+    }
 
-        let mut reader = self.connect_and_auth()?;
-        let command = resp_array(&["PLAN.GET", plan_id]);
-        {
-            let stream = reader.get_mut();
-            stream
-                .write_all(&command)
-                .map_err(|e| format!("failed to send PLAN.GET: {e}"))?;
+    pub fn tools_catalog(&self) -> Result<Vec<CatalogEntry>, String> {
+        let json_str = self.bulk_query(&["TOOLS.CATALOG"])?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse tool catalog: {e}"))
+    }
+
+    /// Execution history of the given tools; tools that have not run are
+    /// left out
+    pub fn tool_stats(&self, tools: &[&str]) -> Result<Vec<ToolStats>, String> {
+        let mut args = vec!["TOOLS.STATS"];
+        args.extend_from_slice(tools);
+        let json_str = self.bulk_query(&args)?;
+        serde_json::from_str(&json_str).map_err(|e| format!("failed to parse tool statistics: {e}"))
+    }
+
+    fn bulk_query(&self, args: &[&str]) -> Result<String, String> {
+        match self.request(args)? {
+            RespValue::BulkString(json_str) => Ok(json_str),
+            RespValue::Error(msg) => Err(format!("AGQ error: {msg}")),
+            other => Err(format!("unexpected AGQ response: {:?}", other)),
         }
+    }
 
-        let response = read_resp_value(&mut reader)?;
-        match response {
-            RespValue::BulkString(json_str) => {
-                let plan: crate::plan::WorkflowPlan = serde_json::from_str(&json_str)
-                    .map_err(|e| format!("failed to parse plan: {e}"))?;
-                Ok(plan)
-            }
+    fn integer_query(&self, args: &[&str]) -> Result<u64, String> {
+        match self.request(args)? {
+            RespValue::Integer(count) => Ok(count.max(0) as u64),
             RespValue::Error(msg) => Err(format!("AGQ error: {msg}")),
             other => Err(format!("unexpected AGQ response: {:?}", other)),
         }
@@ -223,17 +464,7 @@ impl AgqClient {
     where
         F: Fn(Vec<String>) -> OpsResponse,
     {
-        let mut reader = self.connect_and_auth()?;
-        let command_resp = resp_array(&[command]);
-        {
-            let stream = reader.get_mut();
-            stream
-                .write_all(&command_resp)
-                .map_err(|e| format!("failed to send {command}: {e}"))?;
-        }
-
-        let response = read_resp_value(&mut reader)?;
-        match response {
+        match self.request(&[command])? {
             RespValue::Array(items) => {
                 let strings = items
                     .into_iter()
@@ -251,6 +482,32 @@ impl AgqClient {
         }
     }
 
+    /// Send one command and read its reply
+    ///
+    /// The connection is kept open for the next request, so a client that
+    /// polls, like WATCH, authenticates once. AGQ may close a kept
+    /// connection while it is idle; if it is closed before replying, the
+    /// command is sent again on a new connection.
+    fn request(&self, args: &[&str]) -> Result<RespValue, String> {
+        let mut connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(mut reader) = connection.take() {
+            if let Some(value) = exchange(&mut reader, args)? {
+                *connection = Some(reader);
+                return Ok(value);
+            }
+        }
+
+        let mut reader = self.connect_and_auth()?;
+        let value = exchange(&mut reader, args)?
+            .ok_or_else(|| "empty response from AGQ".to_string())?;
+        *connection = Some(reader);
+        Ok(value)
+    }
+
     fn connect_and_auth(&self) -> Result<BufReader<TcpStream>, String> {
         let stream =
             TcpStream::connect(&self.config.addr).map_err(|e| format!("connect error: {e}"))?;
@@ -264,15 +521,8 @@ impl AgqClient {
         let mut reader = BufReader::new(stream);
 
         if let Some(ref key) = self.config.session_key {
-            let auth = resp_array(&["AUTH", key]);
-            {
-                let stream = reader.get_mut();
-                stream
-                    .write_all(&auth)
-                    .map_err(|e| format!("failed to send AUTH: {e}"))?;
-            }
-
-            let auth_response = read_resp_value(&mut reader)?;
+            let auth_response = exchange(&mut reader, &["AUTH", key])?
+                .ok_or_else(|| "empty response from AGQ".to_string())?;
             match auth_response {
                 RespValue::SimpleString(_) | RespValue::BulkString(_) => {}
                 RespValue::Error(msg) => return Err(format!("AUTH failed: {msg}")),
@@ -284,6 +534,55 @@ impl AgqClient {
     }
 }
 
+/// Write a command and read its reply, or `None` if the connection was
+/// closed before anything was read back
+fn exchange(
+    reader: &mut BufReader<TcpStream>,
+    args: &[&str],
+) -> Result<Option<RespValue>, String> {
+    let command = args[0];
+    if let Err(e) = reader.get_mut().write_all(&resp_array(args)) {
+        if is_closed(&e) {
+            return Ok(None);
+        }
+        return Err(format!("failed to send {command}: {e}"));
+    }
+
+    match reader.fill_buf() {
+        Ok([]) => return Ok(None),
+        Ok(_) => {}
+        Err(e) if is_closed(&e) => return Ok(None),
+        Err(e) => return Err(format!("failed to read RESP: {e}")),
+    }
+    read_resp_value(reader).map(Some)
+}
+
+fn is_closed(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+    )
+}
+
+/// Reject IDs that could break out of a RESP argument
+fn validate_id(id: &str, name: &str) -> Result<(), String> {
+    if id.is_empty() {
+        return Err(format!("{name} cannot be empty"));
+    }
+    if id.len() > 128 {
+        return Err(format!("{name} too long (max 128 characters)"));
+    }
+    if !id
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "invalid {name}: must contain only alphanumeric characters, underscore, or dash"
+        ));
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq)]
 enum RespValue {
     SimpleString(String),
@@ -901,4 +1200,125 @@ mod tests {
         assert_eq!(response.job_ids.len(), 1);
         assert_eq!(response.job_ids[0], "job_xyz789");
     }
+
+    #[test]
+    fn reuses_one_connection_for_typed_listings() {
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(_) => return,
+        };
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            // Only one connection is accepted, so both requests must share it
+            let mut stream = listener.accept().unwrap().0;
+            let mut reader = BufReader::new(&mut stream);
+
+            let auth = read_resp_value(&mut reader).expect("read auth request");
+            assert!(matches!(auth, RespValue::Array(items) if items[0] == RespValue::BulkString("AUTH".to_string())));
+            reader.get_mut().write_all(b"+OK\r\n").unwrap();
+
+            let jobs = read_resp_value(&mut reader).expect("read jobs request");
+            assert_eq!(
+                jobs,
+                RespValue::Array(vec![RespValue::BulkString("JOBS.LIST".to_string())])
+            );
+            reader
+                .get_mut()
+                .write_all(b"*3\r\n$5\r\njob-1\r\n$7\r\nrunning\r\n:1700000000\r\n")
+                .unwrap();
+
+            let _workers = read_resp_value(&mut reader).expect("read workers request");
+            let worker = r#"{"worker_id":"w1","last_seen":1700000000,"status":"idle","tools":"sort,uniq","metrics":null}"#;
+            reader
+                .get_mut()
+                .write_all(format!("*1\r\n${}\r\n{}\r\n", worker.len(), worker).as_bytes())
+                .unwrap();
+        });
+
+        let client = AgqClient::new(AgqConfig {
+            addr: addr.to_string(),
+            session_key: Some("secret".to_string()),
+            timeout: Duration::from_secs(2),
+        });
+
+        let jobs = client.jobs().expect("jobs should parse");
+        assert_eq!(
+            jobs,
+            [JobSummary {
+                job_id: "job-1".to_string(),
+                status: "running".to_string(),
+                created_at: "1700000000".to_string(),
+            }]
+        );
+        let workers = client.workers().expect("workers should parse");
+        assert_eq!(workers[0].worker_id, "w1");
+        assert_eq!(workers[0].tools, "sort,uniq");
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn large_plans_are_sent_whole() {
+        let plan = format!("{{\"tasks\": [], \"note\": \"{}\"}}", "é".repeat(100_000));
+        let encoded = resp_array(&["PLAN.SUBMIT", &plan]);
+        let decoded = read_resp_value(&mut BufReader::new(encoded.as_slice())).unwrap();
+        assert_eq!(
+            decoded,
+            RespValue::Array(vec![
+                RespValue::BulkString("PLAN.SUBMIT".to_string()),
+                RespValue::BulkString(plan),
+            ])
+        );
+    }
+
+    #[test]
+    fn plan_status_sends_action_id_and_parses_tasks() {
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(_) => return,
+        };
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut reader = BufReader::new(&mut stream);
+
+            let request = read_resp_value(&mut reader).expect("read status request");
+            assert_eq!(
+                request,
+                RespValue::Array(vec![
+                    RespValue::BulkString("PLAN.STATUS".to_string()),
+                    RespValue::BulkString("plan_abc".to_string()),
+                    RespValue::BulkString("action_1".to_string()),
+                ])
+            );
+
+            let status = r#"{"plan_id":"plan_abc","action_id":"action_1","status":"running","summary":{"completed":1,"running":1},"tasks":[{"job_id":"job_1","task_number":1,"command":"sort","status":"completed","exit_code":0,"worker_id":"w1","created_at":1,"started_at":2,"completed_at":3,"duration_ms":12,"retries":0,"cause":null},{"job_id":"job_2","task_number":2,"command":"uniq","status":"running","exit_code":null,"worker_id":"w1","created_at":1,"started_at":3,"completed_at":null,"duration_ms":null,"retries":1,"cause":null}]}"#;
+            reader
+                .get_mut()
+                .write_all(format!("${}\r\n{}\r\n", status.len(), status).as_bytes())
+                .expect("write status");
+        });
+
+        let client = AgqClient::new(AgqConfig {
+            addr: addr.to_string(),
+            session_key: None,
+            timeout: Duration::from_secs(2),
+        });
+
+        let status = client
+            .plan_status("plan_abc", Some("action_1"))
+            .expect("status should parse");
+        assert_eq!(status.status, "running");
+        assert_eq!(status.summary["completed"], 1);
+        assert_eq!(status.tasks.len(), 2);
+        assert_eq!(status.tasks[0].duration_ms, Some(12));
+        assert_eq!(status.tasks[1].retries, 1);
+
+        server.join().unwrap();
+
+        let invalid = client.plan_status("plan_abc", Some("action\r\nDEL"));
+        assert!(invalid.unwrap_err().contains("invalid action_id"));
+    }
 }
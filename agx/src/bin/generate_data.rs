@@ -1,7 +1,50 @@
-use agx::planner::{OllamaBackend, ModelBackend, PlanContext, ToolInfo};
+use agx::plan::WorkflowPlan;
+use agx::planner::{ModelBackend, OllamaBackend, PlanContext, ToolInfo};
 use agx::registry::ToolRegistry;
+use clap::Parser;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
 use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CATEGORIES: &[&str] = &[
+    "File manipulation (sorting, deduplicating, counting)",
+    "Data extraction (grep, cut, tr)",
+    "JSON processing (jq)",
+    "Complex pipelines (chaining multiple tools)",
+];
+
+/// Rounds of scenario generation per category before settling for fewer
+/// examples than asked for
+const MAX_ROUNDS: usize = 3;
+
+/// Generate planner training data from a teacher model
+#[derive(Parser, Debug)]
+struct Args {
+    /// JSONL file the examples are appended to
+    #[arg(short, long, default_value = "dataset.jsonl")]
+    output: PathBuf,
+
+    /// Examples to generate per category
+    #[arg(short = 'n', long, default_value_t = 5)]
+    count: usize,
+
+    /// Category of instructions to generate; repeat for several
+    #[arg(short, long = "category")]
+    categories: Vec<String>,
+
+    /// File listing categories, one per line
+    #[arg(long)]
+    categories_file: Option<PathBuf>,
+
+    /// Word overlap (0-1) at which two instructions count as duplicates
+    #[arg(long, default_value_t = 0.8)]
+    similarity: f64,
+
+    /// Continue an interrupted run from its checkpoint
+    #[arg(long)]
+    resume: bool,
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ChatMessage {
@@ -14,104 +57,287 @@ struct TrainingExample {
     messages: Vec<ChatMessage>,
 }
 
+/// Progress of a run, saved next to the output after every example
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Checkpoint {
+    /// Categories that are finished
+    completed: Vec<String>,
+    /// Examples written so far for each category
+    #[serde(default)]
+    kept: BTreeMap<String, usize>,
+    /// Every instruction seen so far, kept or not, so they aren't retried
+    instructions: Vec<String>,
+}
+
+impl Checkpoint {
+    fn path(output: &Path) -> PathBuf {
+        let mut name = output.as_os_str().to_owned();
+        name.push(".checkpoint");
+        PathBuf::from(name)
+    }
+
+    fn load(output: &Path) -> anyhow::Result<Self> {
+        let path = Self::path(output);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid checkpoint {}: {}", path.display(), e))
+    }
+
+    fn save(&self, output: &Path) -> anyhow::Result<()> {
+        std::fs::write(Self::path(output), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Lowercased words of an instruction, ignoring punctuation
+fn words(instruction: &str) -> HashSet<String> {
+    instruction
+        .split(|c: char| !c.is_alphanumeric() && c != '.' && c != '_' && c != '-')
+        .map(|word| word.trim_matches('.').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Jaccard similarity of the two instructions' words
+fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+fn is_duplicate(instruction: &str, seen: &[String], threshold: f64) -> bool {
+    seen.iter()
+        .any(|other| similarity(instruction, other) >= threshold)
+}
+
+/// Parse a teacher's plan and check it against the Plan schema and the
+/// tool registry
+fn validate_plan(response: &str, registry: &ToolRegistry) -> Result<WorkflowPlan, String> {
+    let plan = WorkflowPlan::from_str(response).map_err(|e| format!("not a plan: {}", e))?;
+    plan.check_schema()?;
+    let report = agx::dry_run::check(&plan, registry);
+    if report.has_errors() {
+        let findings: Vec<String> = report.findings.iter().map(ToString::to_string).collect();
+        return Err(findings.join("; "));
+    }
+    Ok(plan)
+}
+
+fn load_categories(args: &Args) -> anyhow::Result<Vec<String>> {
+    let mut categories = args.categories.clone();
+    if let Some(path) = &args.categories_file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        categories.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from),
+        );
+    }
+    if categories.is_empty() {
+        categories = DEFAULT_CATEGORIES.iter().map(|c| c.to_string()).collect();
+    }
+    Ok(categories)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
     println!("Initializing Synthetic Data Generator...");
 
-    // 1. Setup
-    let registry = ToolRegistry::new();
-    // ... (rest of setup) ...
-    let tools = registry.tools();
+    let registry = ToolRegistry::load().map_err(anyhow::Error::msg)?;
     let tools_desc = registry.describe_for_planner();
-    
+    let categories = load_categories(&args)?;
+
     let provider = std::env::var("AGX_TEACHER_PROVIDER").unwrap_or_else(|_| "ollama".to_string());
-    let teacher_model = std::env::var("AGX_TEACHER_MODEL").unwrap_or_else(|_| "qwen2.5:7b".to_string());
-    
+    let teacher_model =
+        std::env::var("AGX_TEACHER_MODEL").unwrap_or_else(|_| "qwen2.5:7b".to_string());
+
     println!("Using Teacher Provider: {}", provider);
     println!("Using Teacher Model: {}", teacher_model);
-    
+
     let backend: Box<dyn ModelBackend> = match provider.as_str() {
         "openai" => Box::new(agx::planner::OpenAIBackend::new(teacher_model)),
         _ => Box::new(OllamaBackend::new(teacher_model)),
     };
 
-    let categories = vec![
-        "File manipulation (sorting, deduplicating, counting)",
-        "Data extraction (grep, cut, tr)",
-        "JSON processing (jq)",
-        "Complex pipelines (chaining multiple tools)",
-    ];
+    let mut checkpoint = if args.resume {
+        let checkpoint = Checkpoint::load(&args.output)?;
+        println!(
+            "Resuming: {} categories done, {} instructions seen",
+            checkpoint.completed.len(),
+            checkpoint.instructions.len()
+        );
+        checkpoint
+    } else {
+        Checkpoint::default()
+    };
 
-    let mut examples = Vec::new();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(args.resume)
+        .write(true)
+        .truncate(!args.resume)
+        .open(&args.output)?;
 
-    for category in categories {
-        // ... (generation loop) ...
+    let context = PlanContext {
+        tool_registry: registry
+            .tools()
+            .iter()
+            .map(|t| ToolInfo::new(&t.id, &t.description))
+            .collect(),
+        ..PlanContext::default()
+    };
+    let system_prompt = agx::planner::prompts::build_system_prompt(&context);
+
+    let mut generated = 0;
+    let mut rejected = 0;
+
+    for category in &categories {
+        if checkpoint.completed.contains(category) {
+            println!("Skipping completed category: {}", category);
+            continue;
+        }
         println!("Generating scenarios for: {}", category);
-        
-        let prompt = format!(
-            "You are a synthetic data generator. \
-             Generate 5 diverse, realistic user instructions for a CLI agent that can use these tools:\n\
-             {}\n\
-             \n\
-             The instructions should be related to: {}\n\
-             \n\
-             Output ONLY a JSON array of strings. Example: [\"Sort file.txt\", \"Count lines in data.log\"]",
-            tools_desc, category
-        );
 
-        let context = PlanContext::default();
-        let history = vec![agx::planner::ChatMessage::user(prompt)];
-        let response = backend.chat(&history, &context).await?;
-        
-        let clean_json = response.trim()
-            .trim_start_matches("```json")
-            .trim_start_matches("```")
-            .trim_end_matches("```")
-            .trim();
-
-        let instructions: Vec<String> = serde_json::from_str(clean_json)
-            .unwrap_or_else(|e| {
+        let mut kept = checkpoint.kept.get(category).copied().unwrap_or(0);
+        for _ in 0..MAX_ROUNDS {
+            if kept >= args.count {
+                break;
+            }
+
+            let prompt = format!(
+                "You are a synthetic data generator. \
+                 Generate {} diverse, realistic user instructions for a CLI agent that can use these tools:\n\
+                 {}\n\
+                 \n\
+                 The instructions should be related to: {}\n\
+                 \n\
+                 Output ONLY a JSON array of strings. Example: [\"Sort file.txt\", \"Count lines in data.log\"]",
+                args.count - kept,
+                tools_desc,
+                category
+            );
+
+            let history = vec![agx::planner::ChatMessage::user(prompt)];
+            let response = backend.chat(&history, &PlanContext::default()).await?;
+
+            let clean_json = response
+                .trim()
+                .trim_start_matches("```json")
+                .trim_start_matches("```")
+                .trim_end_matches("```")
+                .trim();
+
+            let instructions: Vec<String> = serde_json::from_str(clean_json).unwrap_or_else(|e| {
                 println!("Failed to parse scenarios: {}", e);
                 vec![]
             });
 
-        for instruction in instructions {
-            println!("  Processing: {}", instruction);
-            
-            let context = PlanContext {
-                tool_registry: registry.tools().iter().map(|t| ToolInfo::new(t.id, t.description)).collect(),
-                ..PlanContext::default()
-            };
-            
-            let system_prompt = agx::planner::prompts::build_system_prompt(&context);
-            let user_prompt = agx::planner::prompts::build_user_prompt(&instruction, &context);
-            
-            let plan_prompt = format!("{}\n\n{}", system_prompt, user_prompt);
-            
-            let history = vec![agx::planner::ChatMessage::user(plan_prompt)];
-            let plan_response = backend.chat(&history, &context).await?;
-            
-            if let Ok(_) = serde_json::from_str::<serde_json::Value>(&plan_response) {
-                let example = TrainingExample {
-                    messages: vec![
-                        ChatMessage { role: "system".to_string(), content: system_prompt },
-                        ChatMessage { role: "user".to_string(), content: instruction },
-                        ChatMessage { role: "assistant".to_string(), content: plan_response },
-                    ],
-                };
-                examples.push(example);
+            for instruction in instructions {
+                if kept >= args.count {
+                    break;
+                }
+                if is_duplicate(&instruction, &checkpoint.instructions, args.similarity) {
+                    println!("  Skipping duplicate: {}", instruction);
+                    continue;
+                }
+                checkpoint.instructions.push(instruction.clone());
+                println!("  Processing: {}", instruction);
+
+                let user_prompt = agx::planner::prompts::build_user_prompt(&instruction, &context);
+                let plan_prompt = format!("{}\n\n{}", system_prompt, user_prompt);
+                let history = vec![agx::planner::ChatMessage::user(plan_prompt)];
+                let plan_response = backend.chat(&history, &context).await?;
+
+                match validate_plan(&plan_response, &registry) {
+                    Ok(plan) => {
+                        let example = TrainingExample {
+                            messages: vec![
+                                ChatMessage {
+                                    role: "system".to_string(),
+                                    content: system_prompt.clone(),
+                                },
+                                ChatMessage {
+                                    role: "user".to_string(),
+                                    content: instruction,
+                                },
+                                ChatMessage {
+                                    role: "assistant".to_string(),
+                                    content: serde_json::to_string(&plan)?,
+                                },
+                            ],
+                        };
+                        writeln!(file, "{}", serde_json::to_string(&example)?)?;
+                        file.flush()?;
+                        kept += 1;
+                        generated += 1;
+                        checkpoint.kept.insert(category.clone(), kept);
+                    }
+                    Err(error) => {
+                        println!("  Rejected plan: {}", error);
+                        rejected += 1;
+                    }
+                }
+                checkpoint.save(&args.output)?;
             }
         }
+
+        if kept < args.count {
+            println!(
+                "  Only {} of {} examples for this category",
+                kept, args.count
+            );
+        }
+        checkpoint.completed.push(category.clone());
+        checkpoint.save(&args.output)?;
     }
 
+    println!(
+        "Generated {} examples in {} ({} plans rejected)",
+        generated,
+        args.output.display(),
+        rejected
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // 5. Save to file
-    let mut file = std::fs::File::create("dataset.jsonl")?;
-    for example in examples {
-        let json = serde_json::to_string(&example)?;
-        writeln!(file, "{}", json)?;
+    #[test]
+    fn near_identical_instructions_are_duplicates() {
+        let seen = vec!["Sort file.txt and remove duplicates".to_string()];
+        assert!(is_duplicate(
+            "sort file.txt, and remove duplicates!",
+            &seen,
+            0.8
+        ));
+        assert!(!is_duplicate("Count lines in data.log", &seen, 0.8));
     }
 
-    println!("Generated {} examples in dataset.jsonl", file.metadata()?.len());
-    Ok(())
+    #[test]
+    fn plans_are_checked_against_the_registry() {
+        let registry = ToolRegistry::new();
+        let valid = r#"{"tasks": [{"task_number": 1, "command": "sort"}]}"#;
+        assert!(validate_plan(valid, &registry).is_ok());
+
+        let unknown = r#"{"tasks": [{"task_number": 1, "command": "frobnicate"}]}"#;
+        let error = validate_plan(unknown, &registry).unwrap_err();
+        assert!(
+            error.contains("`frobnicate` is not a tool in the registry"),
+            "{error}"
+        );
+
+        assert!(validate_plan("not json", &registry).is_err());
+    }
 }
@@ -1,25 +1,69 @@
 // Version from Cargo.toml - automatically synchronized with releases
 const DISPLAY_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+use std::path::PathBuf;
+
+use crate::graph::GraphFormat;
+use crate::planner::ModelRole;
+
 const HELP_TEXT: &str = "\
 AGX - Agentic planner CLI (Phase 1)\n\
 \n\
 Usage:\n\
     agx [OPTIONS]            Start interactive REPL mode (default).\n\
+    agx [OPTIONS] CHAT [--context-file <path>]...\n\
+                             Chat with Echo to build a plan; /attach <path> adds\n\
+                             files during the conversation.\n\
+    agx [OPTIONS] RUN [--yes] [--json] <goal>\n\
+                             Plan a goal with Delta, check and review it, submit and\n\
+                             run it on AGQ, and follow it to its result. --json prints\n\
+                             task events and a final result event, one per line.\n\
+    agx [OPTIONS] DELTA [--yes] [--json] <goal>\n\
+                             Plan a goal with Delta, review it, and submit to AGQ.\n\
+    agx [OPTIONS] DELTA --dry-run [--execute] [--json] <goal>\n\
+                             Plan a goal and check it without submitting; --execute\n\
+                             also runs it locally with agw's executor.\n\
+                             RUN and DELTA take --context-file <path> (repeatable):\n\
+                             the file's columns, JSON shape, or first lines are given\n\
+                             to Delta so the plan can use them.\n\
     agx [OPTIONS] PLAN <subcommand>\n\
     agx [OPTIONS] ACTION submit --plan-id <ID> [--input <json>] [--inputs-file <path>] [--json]\n\
-    agx [OPTIONS] JOBS list [--json]\n\
-    agx [OPTIONS] WORKERS list [--json]\n\
+    agx [OPTIONS] JOBS [list] [--plan <ID>] [--status <status>] [--worker <ID>] [--limit <n>] [--json]\n\
+    agx [OPTIONS] WORKERS [list] [--json]\n\
     agx [OPTIONS] QUEUE stats [--json]\n\
+    agx [OPTIONS] CANCEL plan|job <ID> [--json]\n\
+    agx [OPTIONS] PURGE <job-id> | --older-than <age> [--json]\n\
+    agx [OPTIONS] WATCH <plan-id> [--action-id <ID>] [--interval <secs>] [--json]\n\
+                             [--repair] [--max-repairs <n>]\n\
+    agx [OPTIONS] MODELS list|remove|verify [--json]\n\
+    agx [OPTIONS] INDEX <dir>\n\
+                             Embed the text files under <dir> so Echo and Delta plan\n\
+                             with the passages relevant to a goal. Indexing a\n\
+                             directory again only embeds the files that changed.\n\
+    agx [OPTIONS] SERVE-PLANNER [--role echo|delta]\n\
+                             Keep the Candle models loaded and serve them over a\n\
+                             local socket until Ctrl-C; Echo and Delta use it instead\n\
+                             of loading their model. Serves both roles unless --role\n\
+                             is given.\n\
 \n\
 PLAN subcommands:\n\
     PLAN new                 Reset the persisted plan buffer.\n\
     PLAN add \"<instruction>\"  Append planner-generated steps. Reads STDIN when piped.\n\
     PLAN validate            Run Delta model validation on current plan.\n\
     PLAN preview             Pretty-print the current JSON plan buffer.\n\
-    PLAN submit [--json]     Validate the plan and submit to AGQ.\n\
+    PLAN submit [--json] [--yes]\n\
+                             Validate the plan and submit to AGQ. A plan refined by\n\
+                             Delta (AGX_AUTO_VALIDATE) is shown for review first;\n\
+                             --yes submits it unreviewed.\n\
     PLAN list [--json]       List all stored plans from AGQ.\n\
     PLAN get <plan-id>       View details of a specific plan.\n\
+    PLAN show <plan-id> [--format mermaid|dot|ascii] [--action-id <ID>] [--json]\n\
+                             Draw the plan's task graph, colored by the status of\n\
+                             its latest run (or the given Action). Default: ascii.\n\
+    PLAN diff <id1> <id2> [--json]\n\
+                             Show the tasks added, removed, and changed from one\n\
+                             stored plan to another, with removed and added args\n\
+                             marked [-arg-] and {+arg+}.\n\
 \n\
 ACTION subcommands:\n\
     ACTION submit            Execute a plan with data inputs.\n\
@@ -28,19 +72,50 @@ ACTION subcommands:\n\
       --inputs-file <path>   Path to file containing JSON input data (mutually exclusive with --input).\n\
       --json                 Output result as JSON (default: human-readable).\n\
 \n\
-Ops commands:\n\
-    JOBS list                List jobs from AGQ (add --json for machine output).\n\
-    WORKERS list             List workers and capabilities (add --json for machine output).\n\
-    QUEUE stats              Show queue statistics (add --json for machine output).\n\
+Ops commands (add --json for machine output):\n\
+    JOBS                     List jobs, newest first, as a table.\n\
+      --plan <ID>            Only the jobs of this Plan.\n\
+      --status <status>      Only jobs with this status, e.g. running or failed.\n\
+      --worker <ID>          Only jobs taken by this worker.\n\
+      --limit <n>            Jobs to show (default: 100).\n\
+    WORKERS                  List live workers, their status, and their tools.\n\
+    QUEUE stats              Show queue statistics.\n\
+    CANCEL plan <ID>         Cancel every unfinished job of a Plan.\n\
+    CANCEL job <ID>          Cancel a job and the pending jobs downstream of it;\n\
+                             a running job is stopped by its worker.\n\
+    PURGE <job-id>           Delete a finished job with its results and logs.\n\
+    PURGE --older-than <age> Delete every job finished at least <age> ago, in\n\
+                             seconds or with a unit, e.g. 90m, 12h or 7d.\n\
+\n\
+WATCH <plan-id>             Follow a Plan's latest run, printing task status and output\n\
+                             as jobs finish. Exits non-zero if the run fails.\n\
+      --action-id <ID>       Follow this Action instead of the latest run.\n\
+      --interval <secs>      Seconds between polls (default: 2).\n\
+      --json                 Print one JSON event per line.\n\
+      --repair               When a task fails, ask Delta for a corrected plan from\n\
+                             the failure and the plan's goal; after review it is\n\
+                             submitted, run with no inputs, and followed.\n\
+      --max-repairs <n>      Repair attempts before giving up (default: 3; implies --repair).\n\
+\n\
+MODELS subcommands:\n\
+    MODELS list [--json]     List cached models with their files and sizes.\n\
+    MODELS remove <repo>     Delete a cached model, e.g. Qwen/Qwen2.5-1.5B-Instruct-GGUF.\n\
+    MODELS verify [<repo>]   Check cached files against the sizes and checksums on\n\
+                             Hugging Face (all models by default). Exits non-zero\n\
+                             on a mismatch.\n\
 \n\
 Options:\n\
     -h, --help        Print this help text.\n\
     -v, --version     Show the version and this help output.\n\
     -d, --debug       Enable verbose logging to stderr.\n\
+    --json            Print machine-readable JSON on stdout for any command but\n\
+                      CHAT and the REPL, the same as the command's own --json.\n\
+                      Progress goes to stderr; errors are printed as\n\
+                      {\"status\": \"error\", \"error\": ...} and exit non-zero.\n\
 \n\
 Environment variables:\n\
     AGX_PLAN_PATH       Override the plan buffer location (default: $TMPDIR/agx-plan.json).\n\
-    AGX_BACKEND         Planner backend (ollama or candle).\n\
+    AGX_BACKEND         Planner backend (ollama, candle, anthropic, or gemini).\n\
     AGX_MODEL_ROLE      Model role (echo or delta, default: echo).\n\
     AGX_AUTO_VALIDATE   Auto-run Delta validation before submit (true/false, default: false).\n\
     AGX_OLLAMA_MODEL    Ollama model to run when using the Ollama backend (default: phi3:mini).\n\
@@ -49,16 +124,127 @@ Environment variables:\n\
     AGQ_ADDR            AGQ TCP address (default: 127.0.0.1:6380).\n\
     AGQ_SESSION_KEY     Session key for AGQ (optional).\n\
     AGQ_TIMEOUT_SECS    Network timeout in seconds (default: 5).\n\
+    AGX_EMBED_MODEL     Embedding model for INDEX (default: sentence-transformers/all-MiniLM-L6-v2).\n\
+    AGX_INDEX_PATH      Document index location (default: ~/.local/share/agenix/index.json).\n\
+    AGX_PLANNER_SOCKET_DIR\n\
+                        Directory of the SERVE-PLANNER sockets\n\
+                        (default: $XDG_RUNTIME_DIR/agx, or $TMPDIR/agx).\n\
+    AGX_POLICY_CONFIG   Tool policy file checked before submission\n\
+                        (default: policy.toml in the agx config directory).\n\
+    HF_HOME             Hugging Face home; models are cached in its hub directory\n\
+                        (default: ~/.cache/huggingface).\n\
 ";
 
 #[derive(Debug, Clone)]
 pub enum Command {
     Repl,
-    Chat,
-    Run { goal: String },
+    Chat {
+        /// Files attached to the conversation from the start
+        context_files: Vec<PathBuf>,
+    },
+    Run {
+        goal: String,
+        yes: bool,
+        dry_run: bool,
+        execute: bool,
+        /// Files summarized for Delta as planning context
+        context_files: Vec<PathBuf>,
+        /// Print task events and the result as JSON lines
+        json: bool,
+        /// Follow the run to its result after submitting (RUN, not DELTA)
+        follow: bool,
+    },
     Plan(PlanCommand),
     Action(ActionCommand),
     Ops(OpsCommand),
+    Watch {
+        plan_id: String,
+        action_id: Option<String>,
+        interval_secs: u64,
+        json: bool,
+        /// Repair attempts allowed when the run fails; `None` without `--repair`
+        repair: Option<u32>,
+    },
+    Models(ModelsCommand),
+    /// Embed the documents under a directory for planning context
+    Index {
+        dir: PathBuf,
+    },
+    /// Keep the Candle models for these roles loaded for Echo and Delta
+    ServePlanner {
+        roles: Vec<ModelRole>,
+    },
+}
+
+impl Command {
+    /// Whether the command prints JSON on stdout
+    pub fn json(&self) -> bool {
+        match self {
+            Command::Repl
+            | Command::Chat { .. }
+            | Command::Index { .. }
+            | Command::ServePlanner { .. } => false,
+            Command::Run { json, .. } | Command::Watch { json, .. } => *json,
+            Command::Plan(command) => match command {
+                PlanCommand::Submit { json, .. }
+                | PlanCommand::List { json }
+                | PlanCommand::Show { json, .. }
+                | PlanCommand::Diff { json, .. } => *json,
+                // The plan buffer commands only print JSON
+                _ => true,
+            },
+            Command::Action(ActionCommand::Submit { json, .. }) => *json,
+            Command::Ops(OpsCommand::Jobs { json, .. })
+            | Command::Ops(OpsCommand::Workers { json })
+            | Command::Ops(OpsCommand::Queue { json })
+            | Command::Ops(OpsCommand::Cancel { json, .. })
+            | Command::Ops(OpsCommand::Purge { json, .. }) => *json,
+            Command::Models(ModelsCommand::List { json })
+            | Command::Models(ModelsCommand::Remove { json, .. })
+            | Command::Models(ModelsCommand::Verify { json, .. }) => *json,
+        }
+    }
+
+    /// The command with JSON output, for the global `--json`
+    pub fn with_json(mut self) -> Result<Self, String> {
+        match &mut self {
+            Command::Repl | Command::Chat { .. } => {
+                return Err("--json is not supported by CHAT or the REPL.".to_string())
+            }
+            Command::Index { .. } => {
+                return Err("--json is not supported by INDEX.".to_string())
+            }
+            Command::ServePlanner { .. } => {
+                return Err("--json is not supported by SERVE-PLANNER.".to_string())
+            }
+            Command::Watch {
+                repair: Some(_), ..
+            } => {
+                return Err(
+                    "--repair asks for confirmation and cannot be combined with --json"
+                        .to_string(),
+                )
+            }
+            Command::Run { json, .. } | Command::Watch { json, .. } => *json = true,
+            Command::Plan(command) => match command {
+                PlanCommand::Submit { json, .. }
+                | PlanCommand::List { json }
+                | PlanCommand::Show { json, .. }
+                | PlanCommand::Diff { json, .. } => *json = true,
+                _ => {}
+            },
+            Command::Action(ActionCommand::Submit { json, .. }) => *json = true,
+            Command::Ops(OpsCommand::Jobs { json, .. })
+            | Command::Ops(OpsCommand::Workers { json })
+            | Command::Ops(OpsCommand::Queue { json })
+            | Command::Ops(OpsCommand::Cancel { json, .. })
+            | Command::Ops(OpsCommand::Purge { json, .. }) => *json = true,
+            Command::Models(ModelsCommand::List { json })
+            | Command::Models(ModelsCommand::Remove { json, .. })
+            | Command::Models(ModelsCommand::Verify { json, .. }) => *json = true,
+        }
+        Ok(self)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -67,9 +253,21 @@ pub enum PlanCommand {
     Add { instruction: String },
     Validate,
     Preview,
-    Submit { json: bool },
+    Submit { json: bool, yes: bool },
     List { json: bool },
     Get { plan_id: String },
+    Show {
+        plan_id: String,
+        action_id: Option<String>,
+        format: GraphFormat,
+        /// Print the tasks, their edges, and statuses as JSON instead of drawing
+        json: bool,
+    },
+    Diff {
+        from: String,
+        to: String,
+        json: bool,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -84,9 +282,47 @@ pub enum ActionCommand {
 
 #[derive(Debug, Clone)]
 pub enum OpsCommand {
-    Jobs { json: bool },
-    Workers { json: bool },
-    Queue { json: bool },
+    Jobs {
+        filter: crate::agq_client::JobFilter,
+        json: bool,
+    },
+    Workers {
+        json: bool,
+    },
+    Queue {
+        json: bool,
+    },
+    Cancel {
+        target: CancelTarget,
+        json: bool,
+    },
+    Purge {
+        target: PurgeTarget,
+        json: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelTarget {
+    /// Every unfinished job of a Plan
+    Plan(String),
+    /// A job and the pending jobs downstream of it
+    Job(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PurgeTarget {
+    Job(String),
+    /// Every job that finished at least this many seconds ago
+    OlderThan(u64),
+}
+
+#[derive(Debug, Clone)]
+pub enum ModelsCommand {
+    List { json: bool },
+    Remove { repo: String, json: bool },
+    /// Verify one cached model, or all of them
+    Verify { repo: Option<String>, json: bool },
 }
 
 #[derive(Debug)]
@@ -110,6 +346,7 @@ impl CliConfig {
         let mut show_help = false;
         let mut show_version = false;
         let mut debug = false;
+        let mut json = false;
         let mut command_tokens: Vec<String> = Vec::new();
 
         let mut iter = args.into_iter();
@@ -126,6 +363,9 @@ impl CliConfig {
                 "--debug" | "-d" => {
                     debug = true;
                 }
+                "--json" => {
+                    json = true;
+                }
                 _ => {
                     command_tokens.push(argument);
                     command_tokens.extend(iter);
@@ -135,6 +375,9 @@ impl CliConfig {
         }
 
         let command = if command_tokens.is_empty() {
+            if json && !show_help {
+                return Err("--json needs a command; the REPL is interactive.".to_string());
+            }
             // No command means enter REPL mode (unless showing help/version)
             if !show_help && !show_version {
                 Some(Command::Repl)
@@ -142,7 +385,8 @@ impl CliConfig {
                 None
             }
         } else {
-            Some(parse_command(&command_tokens)?)
+            let command = parse_command(&command_tokens)?;
+            Some(if json { command.with_json()? } else { command })
         };
 
         Ok(Self {
@@ -162,17 +406,15 @@ fn parse_command(tokens: &[String]) -> Result<Command, String> {
     let kind = tokens[0].to_uppercase();
 
     match kind.as_str() {
-        "CHAT" => Ok(Command::Chat),
-        "RUN" => {
-            if tokens.len() < 2 {
-                return Err("RUN requires a goal string.".to_string());
-            }
-            let goal = tokens[1..].join(" ");
-            Ok(Command::Run { goal })
-        }
+        "CHAT" => parse_chat_command(&tokens[1..]),
+        "RUN" | "DELTA" => parse_run_command(&kind, &tokens[1..]),
         "PLAN" => parse_plan_command(&tokens[1..]),
         "ACTION" => parse_action_command(&tokens[1..]),
-        "JOBS" | "WORKERS" | "QUEUE" => parse_ops_command(&tokens),
+        "JOBS" | "WORKERS" | "QUEUE" | "CANCEL" | "PURGE" => parse_ops_command(tokens),
+        "WATCH" => parse_watch_command(&tokens[1..]),
+        "MODELS" => parse_models_command(&tokens[1..]),
+        "INDEX" => parse_index_command(&tokens[1..]),
+        "SERVE-PLANNER" => parse_serve_planner_command(&tokens[1..]),
         _ => Err(format!(
             "unknown command: {}. Run `agx --help` for usage.",
             tokens[0]
@@ -180,6 +422,75 @@ fn parse_command(tokens: &[String]) -> Result<Command, String> {
     }
 }
 
+fn parse_run_command(kind: &str, tokens: &[String]) -> Result<Command, String> {
+    let mut yes = false;
+    let mut dry_run = false;
+    let mut execute = false;
+    let mut json = false;
+    let mut context_files = Vec::new();
+    let mut words = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i].as_str() {
+            "--yes" | "-y" => yes = true,
+            "--dry-run" => dry_run = true,
+            "--execute" => execute = true,
+            "--json" => json = true,
+            "--context-file" => {
+                context_files.push(context_file_value(tokens.get(i + 1))?);
+                i += 1;
+            }
+            word => words.push(word),
+        }
+        i += 1;
+    }
+
+    if words.is_empty() {
+        return Err(format!("{kind} requires a goal string."));
+    }
+    if execute && !dry_run {
+        return Err("--execute is only valid with --dry-run.".to_string());
+    }
+    let follow = kind == "RUN";
+
+    Ok(Command::Run {
+        goal: words.join(" "),
+        yes,
+        dry_run,
+        execute,
+        context_files,
+        json,
+        follow,
+    })
+}
+
+fn parse_chat_command(tokens: &[String]) -> Result<Command, String> {
+    let mut context_files = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i].as_str() {
+            "--context-file" => {
+                context_files.push(context_file_value(tokens.get(i + 1))?);
+                i += 2;
+            }
+            other => {
+                return Err(format!("unexpected argument after `CHAT`: {}", other));
+            }
+        }
+    }
+
+    Ok(Command::Chat { context_files })
+}
+
+fn context_file_value(value: Option<&String>) -> Result<PathBuf, String> {
+    value
+        .filter(|value| !value.trim().is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| "--context-file requires a path.".to_string())
+}
+
 fn parse_plan_command(tokens: &[String]) -> Result<Command, String> {
     if tokens.is_empty() {
         return Err("PLAN requires a subcommand (new, add, validate, preview, submit).".to_string());
@@ -220,6 +531,7 @@ fn parse_plan_command(tokens: &[String]) -> Result<Command, String> {
         }
         "submit" => {
             let mut json = false;
+            let mut yes = false;
             let mut i = 1;
 
             while i < tokens.len() {
@@ -228,6 +540,10 @@ fn parse_plan_command(tokens: &[String]) -> Result<Command, String> {
                         json = true;
                         i += 1;
                     }
+                    "--yes" | "-y" => {
+                        yes = true;
+                        i += 1;
+                    }
                     _ => {
                         return Err(format!(
                             "unexpected argument after `PLAN submit`: {}",
@@ -237,7 +553,7 @@ fn parse_plan_command(tokens: &[String]) -> Result<Command, String> {
                 }
             }
 
-            Ok(Command::Plan(PlanCommand::Submit { json }))
+            Ok(Command::Plan(PlanCommand::Submit { json, yes }))
         }
         "add" => {
             if tokens.len() < 2 {
@@ -283,13 +599,80 @@ fn parse_plan_command(tokens: &[String]) -> Result<Command, String> {
             let plan_id = tokens[1].clone();
             Ok(Command::Plan(PlanCommand::Get { plan_id }))
         }
+        "show" => parse_plan_show(&tokens[1..]),
+        "diff" => parse_plan_diff(&tokens[1..]),
         _ => Err(format!(
-            "unknown PLAN subcommand: {}. Expected new/add/validate/preview/submit/list/get.",
+            "unknown PLAN subcommand: {}. Expected new/add/validate/preview/submit/list/get/show/diff.",
             tokens[0]
         )),
     }
 }
 
+fn parse_plan_show(tokens: &[String]) -> Result<Command, String> {
+    let mut plan_id = None;
+    let mut action_id = None;
+    let mut format = GraphFormat::Ascii;
+    let mut json = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i].as_str() {
+            "--format" => {
+                let value = tokens
+                    .get(i + 1)
+                    .ok_or_else(|| "--format requires mermaid, dot, or ascii.".to_string())?;
+                format = GraphFormat::parse(value)?;
+                i += 2;
+            }
+            "--action-id" => {
+                let value = tokens
+                    .get(i + 1)
+                    .filter(|value| !value.trim().is_empty())
+                    .ok_or_else(|| "--action-id requires a non-empty value.".to_string())?;
+                action_id = Some(value.clone());
+                i += 2;
+            }
+            "--json" => {
+                json = true;
+                i += 1;
+            }
+            other if plan_id.is_none() && !other.starts_with("--") => {
+                plan_id = Some(other.to_string());
+                i += 1;
+            }
+            other => {
+                return Err(format!(
+                    "unexpected argument after `PLAN show <plan-id>`: {}",
+                    other
+                ));
+            }
+        }
+    }
+
+    let plan_id = plan_id.ok_or_else(|| "PLAN show requires a plan-id.".to_string())?;
+    Ok(Command::Plan(PlanCommand::Show {
+        plan_id,
+        action_id,
+        format,
+        json,
+    }))
+}
+
+fn parse_plan_diff(tokens: &[String]) -> Result<Command, String> {
+    let json = tokens.iter().any(|token| token == "--json");
+    let ids: Vec<&String> = tokens.iter().filter(|token| *token != "--json").collect();
+    match ids.as_slice() {
+        [from, to] if !from.starts_with("--") && !to.starts_with("--") => {
+            Ok(Command::Plan(PlanCommand::Diff {
+                from: from.to_string(),
+                to: to.to_string(),
+                json,
+            }))
+        }
+        _ => Err("PLAN diff requires two plan-ids, e.g. `agx PLAN diff <id1> <id2>`.".to_string()),
+    }
+}
+
 fn parse_action_command(tokens: &[String]) -> Result<Command, String> {
     if tokens.is_empty() {
         return Err("ACTION requires a subcommand (submit).".to_string());
@@ -368,7 +751,7 @@ fn parse_action_command(tokens: &[String]) -> Result<Command, String> {
 
 fn parse_ops_command(tokens: &[String]) -> Result<Command, String> {
     if tokens.is_empty() {
-        return Err("an Ops command is required (JOBS/WORKERS/QUEUE).".to_string());
+        return Err("an Ops command is required (JOBS/WORKERS/QUEUE/CANCEL/PURGE).".to_string());
     }
 
     let main = tokens[0].to_uppercase();
@@ -380,21 +763,46 @@ fn parse_ops_command(tokens: &[String]) -> Result<Command, String> {
         sub_tokens.retain(|t| t != "--json");
     }
 
+    // `list` is optional for JOBS and WORKERS
+    if matches!(main.as_str(), "JOBS" | "WORKERS")
+        && sub_tokens
+            .first()
+            .is_some_and(|sub| sub.eq_ignore_ascii_case("list"))
+    {
+        sub_tokens.remove(0);
+    }
+
     match main.as_str() {
-        "JOBS" => {
-            if sub_tokens.get(0).map(|s| s.to_lowercase()) == Some("list".to_string()) {
-                Ok(Command::Ops(OpsCommand::Jobs { json }))
-            } else {
-                Err("JOBS requires subcommand: list".to_string())
-            }
-        }
+        "JOBS" => parse_jobs_filter(&sub_tokens)
+            .map(|filter| Command::Ops(OpsCommand::Jobs { filter, json })),
         "WORKERS" => {
-            if sub_tokens.get(0).map(|s| s.to_lowercase()) == Some("list".to_string()) {
+            if sub_tokens.is_empty() {
                 Ok(Command::Ops(OpsCommand::Workers { json }))
             } else {
-                Err("WORKERS requires subcommand: list".to_string())
+                Err(format!("unexpected argument to WORKERS: {}", sub_tokens[0]))
             }
         }
+        "CANCEL" => {
+            let target = match sub_tokens.as_slice() {
+                [kind, id] if kind.eq_ignore_ascii_case("plan") => CancelTarget::Plan(id.clone()),
+                [kind, id] if kind.eq_ignore_ascii_case("job") => CancelTarget::Job(id.clone()),
+                _ => return Err("CANCEL requires `plan <plan-id>` or `job <job-id>`".to_string()),
+            };
+            Ok(Command::Ops(OpsCommand::Cancel { target, json }))
+        }
+        "PURGE" => {
+            let target = match sub_tokens.as_slice() {
+                [flag, age] if flag == "--older-than" => PurgeTarget::OlderThan(parse_age(age)?),
+                [job_id] if !job_id.starts_with('-') => PurgeTarget::Job(job_id.clone()),
+                _ => {
+                    return Err(
+                        "PURGE requires a job ID or --older-than <age>, e.g. `agx PURGE --older-than 7d`"
+                            .to_string(),
+                    )
+                }
+            };
+            Ok(Command::Ops(OpsCommand::Purge { target, json }))
+        }
         "QUEUE" => {
             if sub_tokens.get(0).map(|s| s.to_lowercase()) == Some("stats".to_string()) {
                 Ok(Command::Ops(OpsCommand::Queue { json }))
@@ -406,6 +814,207 @@ fn parse_ops_command(tokens: &[String]) -> Result<Command, String> {
     }
 }
 
+fn parse_jobs_filter(tokens: &[String]) -> Result<crate::agq_client::JobFilter, String> {
+    let mut filter = crate::agq_client::JobFilter::default();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let flag = tokens[i].as_str();
+        let Some(value) = tokens.get(i + 1) else {
+            return Err(format!("{flag} requires a value"));
+        };
+        match flag {
+            "--plan" => filter.plan_id = Some(value.clone()),
+            "--status" => filter.status = Some(value.to_lowercase()),
+            "--worker" => filter.worker = Some(value.clone()),
+            "--limit" => {
+                filter.limit = Some(
+                    value
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or_else(|| format!("--limit must be a positive number, got {value}"))?,
+                )
+            }
+            other => return Err(format!("unexpected argument to JOBS: {other}")),
+        }
+        i += 2;
+    }
+
+    Ok(filter)
+}
+
+/// Seconds in an age like `90`, `30s`, `15m`, `12h` or `7d`
+fn parse_age(age: &str) -> Result<u64, String> {
+    let (number, unit) = match age.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => age.split_at(split),
+        None => (age, "s"),
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid age {age}: use seconds or s, m, h, d, e.g. 7d"
+            ))
+        }
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(scale))
+        .ok_or_else(|| format!("invalid age {age}: use seconds or s, m, h, d, e.g. 7d"))
+}
+
+fn parse_models_command(tokens: &[String]) -> Result<Command, String> {
+    let Some(subcommand) = tokens.first() else {
+        return Err("MODELS requires subcommand: list, remove, or verify".to_string());
+    };
+    let json = tokens[1..].iter().any(|token| token == "--json");
+    let args: Vec<&String> = tokens[1..].iter().filter(|token| *token != "--json").collect();
+
+    match subcommand.to_lowercase().as_str() {
+        "list" => match args[..] {
+            [] => Ok(Command::Models(ModelsCommand::List { json })),
+            _ => Err("MODELS list accepts only --json".to_string()),
+        },
+        "remove" => match args[..] {
+            [repo] if !repo.starts_with('-') => Ok(Command::Models(ModelsCommand::Remove {
+                repo: repo.clone(),
+                json,
+            })),
+            _ => Err(
+                "MODELS remove requires one repository, e.g. Qwen/Qwen2.5-1.5B-Instruct-GGUF"
+                    .to_string(),
+            ),
+        },
+        "verify" => match args[..] {
+            [] => Ok(Command::Models(ModelsCommand::Verify { repo: None, json })),
+            [repo] if !repo.starts_with('-') => Ok(Command::Models(ModelsCommand::Verify {
+                repo: Some(repo.clone()),
+                json,
+            })),
+            _ => Err("MODELS verify accepts at most one repository".to_string()),
+        },
+        other => Err(format!(
+            "unknown MODELS subcommand: {other}. Expected list, remove, or verify."
+        )),
+    }
+}
+
+fn parse_index_command(tokens: &[String]) -> Result<Command, String> {
+    match tokens {
+        [dir] if !dir.starts_with('-') => Ok(Command::Index {
+            dir: PathBuf::from(dir),
+        }),
+        _ => Err("INDEX requires one directory, e.g. `agx INDEX ./deploy`".to_string()),
+    }
+}
+
+fn parse_serve_planner_command(tokens: &[String]) -> Result<Command, String> {
+    let roles = match tokens {
+        [] => vec![ModelRole::Echo, ModelRole::Delta],
+        [flag, role] if flag == "--role" => match role.to_lowercase().as_str() {
+            "echo" => vec![ModelRole::Echo],
+            "delta" => vec![ModelRole::Delta],
+            _ => return Err(format!("unknown role: {role}. Expected echo or delta.")),
+        },
+        _ => return Err("SERVE-PLANNER accepts only --role echo|delta".to_string()),
+    };
+    Ok(Command::ServePlanner { roles })
+}
+
+fn parse_watch_command(tokens: &[String]) -> Result<Command, String> {
+    let mut plan_id = None;
+    let mut action_id = None;
+    let mut interval_secs = 2;
+    let mut json = false;
+    let mut repair = None;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i].as_str() {
+            "--repair" => {
+                repair.get_or_insert(crate::repair::DEFAULT_MAX_REPAIRS);
+                i += 1;
+            }
+            "--max-repairs" => {
+                if i + 1 >= tokens.len() {
+                    return Err("--max-repairs requires a number".to_string());
+                }
+                repair = Some(
+                    tokens[i + 1]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|attempts| *attempts > 0)
+                        .ok_or_else(|| {
+                            format!(
+                                "--max-repairs must be a positive number, got {}",
+                                tokens[i + 1]
+                            )
+                        })?,
+                );
+                i += 2;
+            }
+            "--action-id" => {
+                if i + 1 >= tokens.len() {
+                    return Err("--action-id requires a value".to_string());
+                }
+                action_id = Some(tokens[i + 1].clone());
+                i += 2;
+            }
+            "--interval" => {
+                if i + 1 >= tokens.len() {
+                    return Err("--interval requires a number of seconds".to_string());
+                }
+                interval_secs = tokens[i + 1]
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or_else(|| {
+                        format!(
+                            "--interval must be a positive number of seconds, got {}",
+                            tokens[i + 1]
+                        )
+                    })?;
+                i += 2;
+            }
+            "--json" => {
+                json = true;
+                i += 1;
+            }
+            other if other.starts_with("--") => {
+                return Err(format!("unexpected argument: {}", other));
+            }
+            other => {
+                if plan_id.is_some() {
+                    return Err(format!(
+                        "unexpected argument after `WATCH <plan-id>`: {}",
+                        other
+                    ));
+                }
+                plan_id = Some(other.to_string());
+                i += 1;
+            }
+        }
+    }
+
+    let plan_id = plan_id.ok_or_else(|| "WATCH requires a plan-id.".to_string())?;
+    if repair.is_some() && json {
+        return Err("--repair asks for confirmation and cannot be combined with --json".to_string());
+    }
+
+    Ok(Command::Watch {
+        plan_id,
+        action_id,
+        interval_secs,
+        json,
+        repair,
+    })
+}
+
 pub fn print_help() {
     println!("{HELP_TEXT}");
 }
@@ -484,7 +1093,10 @@ mod tests {
             CliConfig::from_args(vec!["PLAN".to_string(), "submit".to_string()]).expect("valid");
 
         match config.command {
-            Some(Command::Plan(PlanCommand::Submit { json: false })) => {}
+            Some(Command::Plan(PlanCommand::Submit {
+                json: false,
+                yes: false,
+            })) => {}
             other => panic!("unexpected command: {other:?}"),
         }
     }
@@ -499,9 +1111,194 @@ mod tests {
         .expect("valid");
 
         match config.command {
-            Some(Command::Plan(PlanCommand::Submit { json: true })) => {}
+            Some(Command::Plan(PlanCommand::Submit {
+                json: true,
+                yes: false,
+            })) => {}
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_run_and_submit_with_yes() {
+        let config = CliConfig::from_args(vec![
+            "RUN".to_string(),
+            "--yes".to_string(),
+            "count".to_string(),
+            "words".to_string(),
+        ])
+        .expect("valid");
+        match config.command {
+            Some(Command::Run {
+                goal,
+                yes,
+                dry_run: false,
+                ..
+            }) => {
+                assert_eq!(goal, "count words");
+                assert!(yes);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let config = CliConfig::from_args(vec![
+            "PLAN".to_string(),
+            "submit".to_string(),
+            "-y".to_string(),
+        ])
+        .expect("valid");
+        match config.command {
+            Some(Command::Plan(PlanCommand::Submit { json: false, yes })) => assert!(yes),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let result = CliConfig::from_args(vec!["RUN".to_string(), "--yes".to_string()]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_run_follows_and_delta_submits() {
+        let config = CliConfig::from_args(vec![
+            "RUN".to_string(),
+            "--json".to_string(),
+            "--yes".to_string(),
+            "dedupe".to_string(),
+            "lines".to_string(),
+        ])
+        .expect("valid");
+        match config.command {
+            Some(Command::Run {
+                goal,
+                yes: true,
+                json: true,
+                follow: true,
+                ..
+            }) => assert_eq!(goal, "dedupe lines"),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let config =
+            CliConfig::from_args(vec!["DELTA".to_string(), "dedupe".to_string()]).expect("valid");
+        assert!(matches!(
+            config.command,
+            Some(Command::Run { follow: false, .. })
+        ));
+
+        let config = CliConfig::from_args(vec![
+            "DELTA".to_string(),
+            "--json".to_string(),
+            "dedupe".to_string(),
+        ])
+        .expect("valid");
+        assert!(matches!(
+            config.command,
+            Some(Command::Run {
+                follow: false,
+                json: true,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn global_json_flag_applies_to_the_command() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command.expect("a command"))
+        };
+
+        let command = parse(&["--json", "PLAN", "list"]).unwrap();
+        assert!(matches!(command, Command::Plan(PlanCommand::List { json: true })));
+        assert!(command.json());
+
+        let command = parse(&["--json", "models", "verify", "org/model"]).unwrap();
+        assert!(matches!(
+            command,
+            Command::Models(ModelsCommand::Verify { json: true, .. })
+        ));
+        assert!(parse(&["--json", "DELTA", "--dry-run", "dedupe"])
+            .unwrap()
+            .json());
+        assert!(!parse(&["WATCH", "plan-1"]).unwrap().json());
+        assert!(parse(&["PLAN", "preview"]).unwrap().json());
+
+        assert!(parse(&["--json", "CHAT"]).is_err());
+        assert!(parse(&["--json", "WATCH", "plan-1", "--repair"]).is_err());
+        assert!(CliConfig::from_args(vec!["--json".to_string()]).is_err());
+    }
+
+    #[test]
+    fn parse_context_files() {
+        let config = CliConfig::from_args(vec![
+            "DELTA".to_string(),
+            "--context-file".to_string(),
+            "users.csv".to_string(),
+            "dedupe".to_string(),
+            "--context-file".to_string(),
+            "events.json".to_string(),
+        ])
+        .expect("valid");
+        match config.command {
+            Some(Command::Run {
+                goal,
+                context_files,
+                ..
+            }) => {
+                assert_eq!(goal, "dedupe");
+                assert_eq!(
+                    context_files,
+                    [PathBuf::from("users.csv"), PathBuf::from("events.json")]
+                );
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let config = CliConfig::from_args(vec![
+            "CHAT".to_string(),
+            "--context-file".to_string(),
+            "users.csv".to_string(),
+        ])
+        .expect("valid");
+        assert!(matches!(
+            config.command,
+            Some(Command::Chat { context_files }) if context_files.len() == 1
+        ));
+
+        let result = CliConfig::from_args(vec!["CHAT".to_string(), "--context-file".to_string()]);
+        assert!(result.unwrap_err().contains("requires a path"));
+    }
+
+    #[test]
+    fn parse_delta_dry_run() {
+        let config = CliConfig::from_args(vec![
+            "delta".to_string(),
+            "--dry-run".to_string(),
+            "--execute".to_string(),
+            "dedupe lines".to_string(),
+        ])
+        .expect("valid");
+        match config.command {
+            Some(Command::Run {
+                goal,
+                yes: false,
+                dry_run: true,
+                execute: true,
+                json: false,
+                follow: false,
+                context_files,
+            }) => {
+                assert_eq!(goal, "dedupe lines");
+                assert!(context_files.is_empty());
+            }
             other => panic!("unexpected command: {other:?}"),
         }
+
+        let result = CliConfig::from_args(vec![
+            "DELTA".to_string(),
+            "--execute".to_string(),
+            "dedupe lines".to_string(),
+        ]);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -560,6 +1357,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_plan_show() {
+        let config = CliConfig::from_args(vec![
+            "PLAN".to_string(),
+            "show".to_string(),
+            "plan_abc123".to_string(),
+            "--format".to_string(),
+            "mermaid".to_string(),
+        ])
+        .expect("valid");
+
+        match config.command {
+            Some(Command::Plan(PlanCommand::Show {
+                plan_id,
+                action_id: None,
+                format: GraphFormat::Mermaid,
+                json: false,
+            })) => assert_eq!(plan_id, "plan_abc123"),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let result = CliConfig::from_args(vec![
+            "PLAN".to_string(),
+            "show".to_string(),
+            "plan_abc123".to_string(),
+            "--format".to_string(),
+            "png".to_string(),
+        ]);
+        assert!(result.unwrap_err().contains("unknown graph format"));
+    }
+
+    #[test]
+    fn parse_plan_diff() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command)
+        };
+
+        match parse(&["PLAN", "diff", "plan_a", "plan_b", "--json"]) {
+            Ok(Some(Command::Plan(PlanCommand::Diff { from, to, json }))) => {
+                assert_eq!((from.as_str(), to.as_str(), json), ("plan_a", "plan_b", true))
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["PLAN", "diff", "plan_a"]).is_err());
+        assert!(parse(&["PLAN", "diff", "plan_a", "--yes"]).is_err());
+    }
+
     #[test]
     fn plan_get_requires_plan_id() {
         let result = CliConfig::from_args(vec!["PLAN".to_string(), "get".to_string()]);
@@ -593,7 +1438,7 @@ mod tests {
         .expect("valid");
 
         match config.command {
-            Some(Command::Ops(OpsCommand::Jobs { json })) => assert!(json),
+            Some(Command::Ops(OpsCommand::Jobs { json, .. })) => assert!(json),
             other => panic!("unexpected: {other:?}"),
         }
     }
@@ -609,6 +1454,124 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_cluster_admin_commands() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command)
+        };
+
+        match parse(&[
+            "jobs", "--status", "Failed", "--plan", "p1", "--limit", "20",
+        ]) {
+            Ok(Some(Command::Ops(OpsCommand::Jobs { filter, json }))) => {
+                assert!(!json);
+                assert_eq!(filter.status.as_deref(), Some("failed"));
+                assert_eq!(filter.plan_id.as_deref(), Some("p1"));
+                assert_eq!(filter.limit, Some(20));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["JOBS", "--limit", "0"]).is_err());
+        assert!(parse(&["JOBS", "--plan"]).is_err());
+        assert!(matches!(
+            parse(&["workers", "--json"]),
+            Ok(Some(Command::Ops(OpsCommand::Workers { json: true })))
+        ));
+
+        match parse(&["--json", "CANCEL", "plan", "p1"]) {
+            Ok(Some(Command::Ops(OpsCommand::Cancel { target, json }))) => {
+                assert!(json);
+                assert_eq!(target, CancelTarget::Plan("p1".to_string()));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["CANCEL", "p1"]).is_err());
+        assert!(parse(&["CANCEL", "action", "a1"]).is_err());
+
+        match parse(&["PURGE", "--older-than", "7d"]) {
+            Ok(Some(Command::Ops(OpsCommand::Purge { target, .. }))) => {
+                assert_eq!(target, PurgeTarget::OlderThan(7 * 24 * 60 * 60));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        match parse(&["PURGE", "job-1"]) {
+            Ok(Some(Command::Ops(OpsCommand::Purge { target, .. }))) => {
+                assert_eq!(target, PurgeTarget::Job("job-1".to_string()));
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["PURGE"]).is_err());
+        assert!(parse(&["PURGE", "--older-than", "7w"]).is_err());
+        assert_eq!(parse_age("90"), Ok(90));
+        assert_eq!(parse_age("15m"), Ok(900));
+    }
+
+    #[test]
+    fn parse_models_subcommands() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command)
+        };
+
+        match parse(&["models", "list", "--json"]) {
+            Ok(Some(Command::Models(ModelsCommand::List { json }))) => assert!(json),
+            other => panic!("unexpected: {other:?}"),
+        }
+        match parse(&["MODELS", "remove", "Qwen/Qwen2.5-1.5B-Instruct-GGUF"]) {
+            Ok(Some(Command::Models(ModelsCommand::Remove { repo, .. }))) => {
+                assert_eq!(repo, "Qwen/Qwen2.5-1.5B-Instruct-GGUF")
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        match parse(&["models", "verify"]) {
+            Ok(Some(Command::Models(ModelsCommand::Verify { repo, .. }))) => assert!(repo.is_none()),
+            other => panic!("unexpected: {other:?}"),
+        }
+
+        assert!(parse(&["models"]).is_err());
+        assert!(parse(&["models", "remove"]).is_err());
+        assert!(parse(&["models", "prune"]).is_err());
+    }
+
+    #[test]
+    fn parse_index_command() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command)
+        };
+
+        match parse(&["index", "./deploy"]) {
+            Ok(Some(Command::Index { dir })) => assert_eq!(dir, PathBuf::from("./deploy")),
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["INDEX"]).is_err());
+        assert!(parse(&["INDEX", "a", "b"]).is_err());
+        assert!(parse(&["--json", "INDEX", "docs"]).is_err());
+    }
+
+    #[test]
+    fn parse_serve_planner_command() {
+        let parse = |args: &[&str]| {
+            CliConfig::from_args(args.iter().map(|arg| arg.to_string()))
+                .map(|config| config.command)
+        };
+
+        match parse(&["serve-planner"]) {
+            Ok(Some(Command::ServePlanner { roles })) => {
+                assert_eq!(roles, [ModelRole::Echo, ModelRole::Delta])
+            }
+            other => panic!("unexpected: {other:?}"),
+        }
+        match parse(&["SERVE-PLANNER", "--role", "Delta"]) {
+            Ok(Some(Command::ServePlanner { roles })) => assert_eq!(roles, [ModelRole::Delta]),
+            other => panic!("unexpected: {other:?}"),
+        }
+        assert!(parse(&["SERVE-PLANNER", "--role", "gamma"]).is_err());
+        assert!(parse(&["SERVE-PLANNER", "echo"]).is_err());
+        assert!(parse(&["--json", "SERVE-PLANNER"]).is_err());
+    }
+
     #[test]
     fn parse_queue_stats_unknown_subcommand_errors() {
         let res = CliConfig::from_args(vec![
@@ -619,6 +1582,75 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn parse_watch_with_options() {
+        let config = CliConfig::from_args(vec![
+            "watch".to_string(),
+            "plan-123".to_string(),
+            "--interval".to_string(),
+            "5".to_string(),
+            "--json".to_string(),
+        ])
+        .expect("valid");
+
+        match config.command {
+            Some(Command::Watch {
+                plan_id,
+                action_id,
+                interval_secs,
+                json,
+                repair,
+            }) => {
+                assert_eq!(plan_id, "plan-123");
+                assert_eq!(action_id, None);
+                assert_eq!(interval_secs, 5);
+                assert!(json);
+                assert_eq!(repair, None);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_watch_repair() {
+        let args = |extra: &[&str]| {
+            let mut args = vec!["WATCH".to_string(), "plan-123".to_string()];
+            args.extend(extra.iter().map(|arg| arg.to_string()));
+            CliConfig::from_args(args)
+        };
+        let repair_of = |config: CliConfig| match config.command {
+            Some(Command::Watch { repair, .. }) => repair,
+            other => panic!("unexpected command: {other:?}"),
+        };
+
+        assert_eq!(repair_of(args(&["--repair"]).unwrap()), Some(3));
+        assert_eq!(repair_of(args(&["--max-repairs", "5"]).unwrap()), Some(5));
+        assert_eq!(
+            repair_of(args(&["--max-repairs", "1", "--repair"]).unwrap()),
+            Some(1)
+        );
+        assert!(args(&["--max-repairs", "0"])
+            .unwrap_err()
+            .contains("positive number"));
+        assert!(args(&["--repair", "--json"])
+            .unwrap_err()
+            .contains("cannot be combined with --json"));
+    }
+
+    #[test]
+    fn watch_requires_plan_id_and_valid_interval() {
+        let result = CliConfig::from_args(vec!["WATCH".to_string()]);
+        assert!(result.unwrap_err().contains("requires a plan-id"));
+
+        let result = CliConfig::from_args(vec![
+            "WATCH".to_string(),
+            "plan-123".to_string(),
+            "--interval".to_string(),
+            "0".to_string(),
+        ]);
+        assert!(result.unwrap_err().contains("positive number"));
+    }
+
     #[test]
     fn parse_action_submit_with_plan_id() {
         let config = CliConfig::from_args(vec![
@@ -0,0 +1,268 @@
+//! Files attached as planning context
+//!
+//! `--context-file` and Echo's `/attach` read a file and summarize it for
+//! the planner, so a plan can use the real column names and structure
+//! instead of guessing them. CSV and TSV files are summarized by their
+//! columns and first rows, JSON by its shape, and other text by its first
+//! lines. Only the start of a large file is read.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+/// Most bytes read from a file for its summary
+const MAX_READ_BYTES: u64 = 64 * 1024;
+
+/// Largest JSON file parsed for its shape; larger ones are shown as text
+const MAX_JSON_BYTES: u64 = 1024 * 1024;
+
+/// Lines of a text file shown
+const TEXT_LINES: usize = 20;
+
+/// Rows of a CSV file shown after its header
+const CSV_ROWS: usize = 5;
+
+/// Longest line shown, in characters
+const MAX_LINE_CHARS: usize = 200;
+
+/// Keys listed for a JSON object
+const MAX_JSON_KEYS: usize = 30;
+
+/// Summaries of `paths`, joined, or `None` if there are none
+pub fn summarize_all(paths: &[PathBuf]) -> Result<Option<String>, String> {
+    let summaries = paths
+        .iter()
+        .map(|path| summarize(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(join(&summaries))
+}
+
+/// Summaries joined for the planner, or `None` if there are none
+pub fn join(summaries: &[String]) -> Option<String> {
+    if summaries.is_empty() {
+        None
+    } else {
+        Some(summaries.join("\n\n"))
+    }
+}
+
+/// Summarize one file for the planner
+pub fn summarize(path: &Path) -> Result<String, String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+        .len();
+    let file = File::open(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut head = Vec::new();
+    file.take(MAX_READ_BYTES)
+        .read_to_end(&mut head)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    let mut summary = format!("File: {} ({} bytes", path.display(), size);
+    if head.contains(&0) {
+        summary.push_str(", binary)");
+        return Ok(summary);
+    }
+    let lines = count_lines(path)?;
+    summary.push_str(&format!(", {} lines)\n", lines));
+
+    let text = String::from_utf8_lossy(&head);
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    let details = match extension.as_deref() {
+        Some("csv") => delimited(&text, ',', lines),
+        Some("tsv") => delimited(&text, '\t', lines),
+        Some("json") if size <= MAX_JSON_BYTES => std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .map(|value| json_shape(&value))
+            .unwrap_or_else(|| text_head(&text)),
+        _ => text_head(&text),
+    };
+    summary.push_str(&details);
+    Ok(summary.trim_end().to_string())
+}
+
+fn count_lines(path: &Path) -> Result<usize, String> {
+    let file = File::open(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    let mut lines = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            return Ok(lines);
+        }
+        lines += 1;
+    }
+}
+
+fn text_head(text: &str) -> String {
+    let mut out = String::from("First lines:\n");
+    for line in text.lines().take(TEXT_LINES) {
+        out.push_str(&format!("  {}\n", clip(line)));
+    }
+    out
+}
+
+/// Columns and first rows of a CSV or TSV file
+fn delimited(text: &str, delimiter: char, lines: usize) -> String {
+    let mut rows = text.lines();
+    let Some(header) = rows.next() else {
+        return String::new();
+    };
+    let columns = split_row(header, delimiter);
+
+    let mut out = format!("Columns ({}): {}\n", columns.len(), columns.join(", "));
+    out.push_str(&format!("Rows: {}\n", lines.saturating_sub(1)));
+    out.push_str("First rows:\n");
+    for row in rows.take(CSV_ROWS) {
+        out.push_str(&format!("  {}\n", clip(row)));
+    }
+    out
+}
+
+/// Split a row on `delimiter`, outside double quotes
+fn split_row(row: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+        .iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+/// Shape of a JSON document: its keys and their types
+fn json_shape(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut out = format!("JSON object with {} keys:\n", map.len());
+            out.push_str(&keys(map, "  "));
+            out
+        }
+        Value::Array(items) => {
+            let mut out = format!("JSON array of {} items", items.len());
+            match items.first() {
+                Some(Value::Object(map)) => {
+                    out.push_str(", the first with keys:\n");
+                    out.push_str(&keys(map, "  "));
+                }
+                Some(first) => out.push_str(&format!(" of {}\n", type_name(first))),
+                None => out.push('\n'),
+            }
+            out
+        }
+        other => format!("JSON {}\n", type_name(other)),
+    }
+}
+
+fn keys(map: &serde_json::Map<String, Value>, indent: &str) -> String {
+    let mut out = String::new();
+    for (key, value) in map.iter().take(MAX_JSON_KEYS) {
+        out.push_str(&format!("{indent}{key}: {}\n", type_name(value)));
+    }
+    if map.len() > MAX_JSON_KEYS {
+        out.push_str(&format!("{indent}... {} more\n", map.len() - MAX_JSON_KEYS));
+    }
+    out
+}
+
+/// A value's type, with one level of detail for arrays and objects
+fn type_name(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Array(items) => match items.first() {
+            Some(Value::Object(map)) => format!(
+                "array of objects {{{}}}",
+                map.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            Some(Value::Array(_)) => "array of arrays".to_string(),
+            Some(first) => format!("array of {}", type_name(first)),
+            None => "array".to_string(),
+        },
+        Value::Object(map) => format!(
+            "object {{{}}}",
+            map.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+fn clip(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        line.to_string()
+    } else {
+        let kept: String = line.chars().take(MAX_LINE_CHARS).collect();
+        format!("{kept}...")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_files_show_their_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.csv");
+        std::fs::write(
+            &path,
+            "id,\"name, full\",email\n1,\"Ada, L\",ada@example.com\n2,Alan,alan@example.com\n",
+        )
+        .unwrap();
+
+        let summary = summarize(&path).unwrap();
+        assert!(summary.contains("3 lines)"));
+        assert!(summary.contains("Columns (3): id, name, full, email"));
+        assert!(summary.contains("Rows: 2"));
+        assert!(summary.contains("  1,\"Ada, L\",ada@example.com"));
+    }
+
+    #[test]
+    fn json_files_show_their_shape() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.json");
+        std::fs::write(
+            &path,
+            r#"[{"id": 1, "user": {"name": "ada"}, "tags": ["a"]}, {"id": 2}]"#,
+        )
+        .unwrap();
+
+        let summary = summarize(&path).unwrap();
+        assert!(summary.contains("JSON array of 2 items, the first with keys:"));
+        assert!(summary.contains("  user: object {name}"));
+        assert!(summary.contains("  tags: array of string"));
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "line one\nline two\n").unwrap();
+        assert!(summarize(&text)
+            .unwrap()
+            .ends_with("  line one\n  line two"));
+
+        let summaries = summarize_all(&[path, text]).unwrap().unwrap();
+        assert_eq!(summaries.matches("File: ").count(), 2);
+        assert!(summarize(&dir.path().join("missing.csv")).is_err());
+    }
+}
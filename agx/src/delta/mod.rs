@@ -1,123 +1,185 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
-use crate::models::ModelManager;
-use crate::planner::{CandleBackend, CandleConfig, ModelRole, ModelBackend, PlanContext};
+use crate::agq_client::{AgqClient, AgqConfig};
+use crate::plan::WorkflowPlan;
+use crate::planner::{ModelRole, ModelBackend, PlanContext, ToolInfo};
+
+
+pub async fn run(
+    goal: String,
+    yes: bool,
+    dry_run: bool,
+    execute: bool,
+    context_files: &[PathBuf],
+    json: bool,
+) -> Result<()> {
+    // With --json, stdout carries only the result
+    let progress: fn(&str) = if json {
+        |message| eprintln!("{}", message)
+    } else {
+        |message| println!("{}", message)
+    };
+
+    progress("Agenix Delta (Planner)");
+    progress(&format!("Goal: {}", goal));
+    for path in context_files {
+        progress(&format!("Context: {}", path.display()));
+    }
+    progress("---------------------------------------");
+
+    let context = crate::context_file::summarize_all(context_files).map_err(anyhow::Error::msg)?;
+    let plan = plan_goal(&goal, context, progress).await?;
+
+    progress("Plan generated!");
+    if !json {
+        println!("---------------------------------------");
+        println!("{}", serde_json::to_string_pretty(&plan.tasks)?);
+        println!("---------------------------------------");
+    }
+
+    // Nothing the model produced is submitted without approval
+    let mut plan = plan;
+
+    if dry_run {
+        return crate::dry_run::run(&plan, execute, json).await.map_err(|e| {
+            if json {
+                crate::ReportedInJson(e).into()
+            } else {
+                anyhow::anyhow!(e)
+            }
+        });
+    }
+    plan.check_schema()
+        .map_err(|e| anyhow::anyhow!("Delta produced an invalid plan: {}", e))?;
+    if !yes {
+        match crate::pipeline::review(plan, json).map_err(|e| anyhow::anyhow!(e))? {
+            Some(approved) => plan = approved,
+            None => {
+                if json {
+                    println!("{}", serde_json::json!({"status": "rejected"}));
+                } else {
+                    println!("Plan rejected; nothing was submitted.");
+                }
+                return Ok(());
+            }
+        }
+    }
+    
+    // Submit to AGQ
+    progress("Submitting plan to AGQ...");
+    let job = crate::build_job_envelope(plan).map_err(anyhow::Error::msg)?;
+    let client = AgqClient::new(AgqConfig::from_env());
+    let submission = client
+        .submit_plan(&serde_json::to_string(&job)?)
+        .map_err(|e| anyhow::anyhow!("Failed to submit plan: {}", e))?;
 
+    if json {
+        println!(
+            "{:#}",
+            serde_json::json!({
+                "status": "submitted",
+                "plan_id": job.plan_id,
+                "job_id": submission.job_id,
+                "task_count": job.tasks.len(),
+                "plan": job,
+            })
+        );
+        return Ok(());
+    }
+
+    println!("Plan submitted successfully!");
+    println!("Plan ID: {}", job.plan_id);
+    println!("Use 'agx ACTION submit --plan-id {}' to run it.", job.plan_id);
 
-pub async fn run(goal: String) -> Result<()> {
-    println!("Agenix Delta (Planner)");
-    println!("Goal: {}", goal);
-    println!("---------------------------------------");
+    Ok(())
+}
 
+/// Plan a goal with Delta, reporting progress through `progress`
+///
+/// `input_summary` describes the data the plan will run on, such as the
+/// summaries of attached context files.
+pub async fn plan_goal(
+    goal: &str,
+    input_summary: Option<String>,
+    progress: fn(&str),
+) -> Result<WorkflowPlan> {
     // Load configuration to determine backend
-    let config = crate::planner::PlannerConfig::from_env();
-    println!("Backend: {:?}", config.backend);
+    let config = crate::planner::PlannerConfig::load().map_err(anyhow::Error::msg)?;
+    progress(&format!("Backend: {:?}", config.backend));
 
     let backend: Box<dyn ModelBackend> = match config.backend {
         crate::planner::BackendKind::Candle => {
-            println!("Initializing Model Manager...");
-            let manager = ModelManager::new()?;
-
-            // Use Qwen 2.5 Coder 1.5B for fast local testing
-            let repo = "Qwen/Qwen2.5-Coder-1.5B-Instruct-GGUF";
-            let file = "qwen2.5-coder-1.5b-instruct-q4_k_m.gguf";
-            
-            println!("Ensuring model is available: {}/{}", repo, file);
-            let model_path = manager.ensure_model(repo, file).await?;
-            println!("Model loaded from: {}", model_path.display());
-
-            // Ensure tokenizer is available (from base repo)
-            let tokenizer_repo = "Qwen/Qwen2.5-Coder-1.5B-Instruct";
-            let tokenizer_url = format!("https://huggingface.co/{}/resolve/main/tokenizer.json", tokenizer_repo);
-            let tokenizer_file = "tokenizer.json";
-            
-            println!("Ensuring tokenizer is available from: {}", tokenizer_url);
-            let raw_tokenizer_path = manager.download_file_raw(&tokenizer_url, tokenizer_file).await?;
-            
-            // Copy tokenizer to model directory
-            let model_dir = model_path.parent().unwrap();
-            let dest_tokenizer_path = model_dir.join("tokenizer.json");
-            if !dest_tokenizer_path.exists() {
-                println!("Copying tokenizer to model directory: {}", dest_tokenizer_path.display());
-                tokio::fs::copy(&raw_tokenizer_path, &dest_tokenizer_path).await?;
-            }
+            progress(&format!("Ensuring model is available: {}", config.model(ModelRole::Delta).describe()));
 
-            // Initialize Candle Backend
-            let candle_config = CandleConfig {
-                model_path: model_path.clone(),
-                model_role: ModelRole::Delta,
-                ..CandleConfig::default()
-            };
-            
-            println!("Initializing inference engine (Candle)...");
-            let backend = CandleBackend::new(candle_config).await
+            progress("Initializing inference engine (Candle)...");
+            let (backend, device) = config.candle_planner(ModelRole::Delta).await
                 .map_err(|e| anyhow::anyhow!("Failed to initialize backend: {:?}", e))?;
-                
-            Box::new(backend)
+            progress(&format!("Running on {}", device));
+
+            backend
         }
         crate::planner::BackendKind::Ollama => {
-            println!("Initializing inference engine (Ollama)...");
+            progress("Initializing inference engine (Ollama)...");
             let ollama_config = crate::planner::ollama::OllamaConfig::default();
             let backend = crate::planner::OllamaBackend::from_config(ollama_config);
             
             // Verify Ollama connection
             if let Err(e) = backend.health_check().await {
-                println!("Warning: Ollama health check failed: {:?}", e);
-                println!("Make sure Ollama is running and the model is pulled.");
+                progress(&format!("Warning: Ollama health check failed: {:?}", e));
+                progress("Make sure Ollama is running and the model is pulled.");
             }
             
             Box::new(backend)
         }
+        crate::planner::BackendKind::Anthropic => {
+            let backend = crate::planner::AnthropicBackend::from_config(
+                crate::planner::AnthropicConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("Anthropic backend unavailable: {}", e))?;
+            Box::new(backend)
+        }
+        crate::planner::BackendKind::Gemini => {
+            let backend = crate::planner::GeminiBackend::from_config(
+                crate::planner::GeminiConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("Gemini backend unavailable: {}", e))?;
+            Box::new(backend)
+        }
+        crate::planner::BackendKind::OpenAI => {
+            let backend = crate::planner::OpenAIBackend::from_config(
+                crate::planner::OpenAIConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("OpenAI backend unavailable: {}", e))?;
+            Box::new(backend)
+        }
     };
 
-    println!("Planning...");
+    progress("Planning...");
     
-    // Construct context (TODO: populate with actual tools)
-    let context = PlanContext::default();
+    // Plan with the tools workers can run
+    let registry = crate::registry::ToolRegistry::load().map_err(anyhow::Error::msg)?;
+    let context = PlanContext {
+        tool_registry: registry
+            .tools()
+            .iter()
+            .map(|t| ToolInfo::new(&t.id, &t.description))
+            .collect(),
+        input_summary,
+        documents: crate::index::retrieve(goal).await,
+        ..PlanContext::default()
+    };
     
     // Generate plan
-    let plan = backend.generate_plan(&goal, &context).await
+    let plan = backend.generate_plan(goal, &context).await
         .map_err(|e| anyhow::anyhow!("Failed to generate plan: {:?}", e))?;
 
-    println!("Plan generated!");
-    println!("---------------------------------------");
-    println!("{}", serde_json::to_string_pretty(&plan.tasks)?);
-    println!("---------------------------------------");
-    
-    // Submit to AGQ
-    println!("Submitting plan to AGQ...");
-    
-    // Construct Plan JSON
-    let plan_id = uuid::Uuid::new_v4().to_string();
-    let plan_payload = serde_json::json!({
-        "plan_id": plan_id,
-        "plan_description": goal,
-        "tasks": plan.tasks.iter().map(|t| {
-            serde_json::json!({
-                "task_number": t.task_number,
-                "command": t.command,
-                "args": t.args,
-                "timeout_secs": t.timeout_secs,
-                "input_from_task": t.input_from_task
-            })
-        }).collect::<Vec<_>>()
-    });
-    
-    let plan_json = serde_json::to_string(&plan_payload)?;
-    
-    // Connect to AGQ
-    let agq_addr = std::env::var("AGQ_ADDR").unwrap_or_else(|_| "127.0.0.1:6379".to_string());
-    let mut client = crate::client::AgqClient::connect(&agq_addr).await?;
-    
-    match client.submit_plan(&plan_json).await {
-        Ok(returned_id) => {
-            println!("Plan submitted successfully!");
-            println!("Plan ID: {}", returned_id);
-            println!("Use 'agx list' or 'agq' to monitor progress.");
-        }
-        Err(e) => {
-            println!("Failed to submit plan: {:?}", e);
-        }
-    }
-
-    Ok(())
+    Ok(WorkflowPlan {
+        plan_id: None,
+        plan_description: Some(goal.to_string()),
+        tasks: plan.tasks,
+    })
 }
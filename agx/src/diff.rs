@@ -0,0 +1,491 @@
+//! Differences between two revisions of a plan
+//!
+//! Tasks are matched by command, in order, so a task keeps its identity
+//! when others are inserted or removed before it. A matched task whose
+//! args, timeout, input, waits, or tags differ is shown as changed, with
+//! its args compared word by word. Renumbering alone is not a change: an
+//! input from task 2 that became task 3 is the same input.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::plan::{task_list, PlanStep};
+
+/// How the plans' tasks line up, in the order of the later plan
+#[derive(Debug, Clone)]
+pub struct PlanDiff {
+    pub tasks: Vec<TaskDiff>,
+    /// Task numbers of the earlier plan as the later plan numbers them
+    renumbered: HashMap<u32, u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TaskDiff {
+    Unchanged {
+        before: PlanStep,
+        after: PlanStep,
+    },
+    Added(PlanStep),
+    Removed(PlanStep),
+    Changed {
+        before: PlanStep,
+        after: PlanStep,
+        changes: Vec<Change>,
+    },
+}
+
+/// A field of a matched task that differs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Args(Vec<Word>),
+    Timeout(u32, u32),
+    Input(Option<u32>, Option<u32>),
+    DependsOn(Vec<u32>, Vec<u32>),
+    Tags(Vec<String>, Vec<String>),
+}
+
+/// An arg in a word-by-word comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Word {
+    Kept(String),
+    Removed(String),
+    Added(String),
+}
+
+/// One step of an alignment of two sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Both(usize, usize),
+    Before(usize),
+    After(usize),
+}
+
+/// Compare the tasks of two plans
+pub fn diff(before: &[PlanStep], after: &[PlanStep]) -> PlanDiff {
+    let steps = align(before, after, |old, new| old.command == new.command);
+
+    let renumbered: HashMap<u32, u32> = steps
+        .iter()
+        .filter_map(|step| match *step {
+            Step::Both(old, new) => Some((before[old].task_number, after[new].task_number)),
+            _ => None,
+        })
+        .collect();
+    let renumber = |number: u32| renumbered.get(&number).copied();
+
+    let tasks = steps
+        .into_iter()
+        .map(|step| match step {
+            Step::Before(old) => TaskDiff::Removed(before[old].clone()),
+            Step::After(new) => TaskDiff::Added(after[new].clone()),
+            Step::Both(old, new) => {
+                let (old, new) = (&before[old], &after[new]);
+                let changes = compare(old, new, &renumber);
+                if changes.is_empty() {
+                    TaskDiff::Unchanged {
+                        before: old.clone(),
+                        after: new.clone(),
+                    }
+                } else {
+                    TaskDiff::Changed {
+                        before: old.clone(),
+                        after: new.clone(),
+                        changes,
+                    }
+                }
+            }
+        })
+        .collect();
+    PlanDiff { tasks, renumbered }
+}
+
+fn compare(old: &PlanStep, new: &PlanStep, renumber: &dyn Fn(u32) -> Option<u32>) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if old.args != new.args {
+        let words = align(&old.args, &new.args, |a, b| a == b)
+            .into_iter()
+            .map(|step| match step {
+                Step::Both(index, _) => Word::Kept(old.args[index].clone()),
+                Step::Before(index) => Word::Removed(old.args[index].clone()),
+                Step::After(index) => Word::Added(new.args[index].clone()),
+            })
+            .collect();
+        changes.push(Change::Args(words));
+    }
+    if old.timeout_secs != new.timeout_secs {
+        changes.push(Change::Timeout(old.timeout_secs, new.timeout_secs));
+    }
+    if old.input_from_task.and_then(renumber) != new.input_from_task
+        || old.input_from_task.is_some() != new.input_from_task.is_some()
+    {
+        changes.push(Change::Input(old.input_from_task, new.input_from_task));
+    }
+    let waits: Vec<Option<u32>> = old.depends_on.iter().map(|&n| renumber(n)).collect();
+    let new_waits: Vec<Option<u32>> = new.depends_on.iter().map(|&n| Some(n)).collect();
+    if waits != new_waits {
+        changes.push(Change::DependsOn(
+            old.depends_on.clone(),
+            new.depends_on.clone(),
+        ));
+    }
+    if old.tags != new.tags {
+        changes.push(Change::Tags(old.tags.clone(), new.tags.clone()));
+    }
+    changes
+}
+
+/// The longest common subsequence of `before` and `after` under `same`,
+/// with the items outside it, removals before additions
+fn align<T>(before: &[T], after: &[T], same: impl Fn(&T, &T) -> bool) -> Vec<Step> {
+    // lengths[i][j]: longest common subsequence of before[i..] and after[j..]
+    let mut lengths = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lengths[i][j] = if same(&before[i], &after[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() && j < after.len() {
+        if same(&before[i], &after[j]) {
+            steps.push(Step::Both(i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            steps.push(Step::Before(i));
+            i += 1;
+        } else {
+            steps.push(Step::After(j));
+            j += 1;
+        }
+    }
+    steps.extend((i..before.len()).map(Step::Before));
+    steps.extend((j..after.len()).map(Step::After));
+    steps
+}
+
+impl PlanDiff {
+    /// Whether the plans have the same tasks
+    pub fn is_empty(&self) -> bool {
+        self.tasks
+            .iter()
+            .all(|task| matches!(task, TaskDiff::Unchanged { .. }))
+    }
+
+    /// Tasks added, removed, changed, and unchanged
+    fn counts(&self) -> [usize; 4] {
+        let mut counts = [0; 4];
+        for task in &self.tasks {
+            let index = match task {
+                TaskDiff::Added(_) => 0,
+                TaskDiff::Removed(_) => 1,
+                TaskDiff::Changed { .. } => 2,
+                TaskDiff::Unchanged { .. } => 3,
+            };
+            counts[index] += 1;
+        }
+        counts
+    }
+
+    /// The diff as text: a summary, then every task of both plans marked
+    /// `+` (added), `-` (removed), `~` (changed), or unmarked, with removed
+    /// and added args shown as `[-arg-]` and `{+arg+}`
+    pub fn render(&self) -> String {
+        let [added, removed, changed, unchanged] = self.counts();
+        let mut lines = vec![if self.is_empty() {
+            "No changes.".to_string()
+        } else {
+            format!("{added} added, {removed} removed, {changed} changed, {unchanged} unchanged")
+        }];
+
+        for task in &self.tasks {
+            match task {
+                TaskDiff::Unchanged { before, after } => lines.push(format!(
+                    "  {}. {}{}",
+                    after.task_number,
+                    command_line(after),
+                    was(before, after)
+                )),
+                TaskDiff::Added(task) => {
+                    lines.push(format!("+ {}. {}", task.task_number, command_line(task)))
+                }
+                TaskDiff::Removed(task) => lines.push(format!(
+                    "-    {}   (was task {})",
+                    command_line(task),
+                    task.task_number
+                )),
+                TaskDiff::Changed {
+                    before,
+                    after,
+                    changes,
+                } => {
+                    let mut line = after.command.clone();
+                    for word in args_words(after, changes) {
+                        line.push(' ');
+                        line.push_str(&word);
+                    }
+                    lines.push(format!(
+                        "~ {}. {line}{}",
+                        after.task_number,
+                        was(before, after)
+                    ));
+                    for change in changes {
+                        if let Some(detail) = self.describe(change) {
+                            lines.push(format!("      {detail}"));
+                        }
+                    }
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// A line for a change other than the args, which are shown inline
+    ///
+    /// Tasks of the earlier plan are given their numbers in the later one.
+    fn describe(&self, change: &Change) -> Option<String> {
+        let earlier = |numbers: &[u32]| {
+            let renumbered: Option<Vec<u32>> = numbers
+                .iter()
+                .map(|number| self.renumbered.get(number).copied())
+                .collect();
+            match renumbered {
+                _ if numbers.is_empty() => "none".to_string(),
+                Some(numbers) => task_list(&numbers),
+                None => format!("{} of the earlier plan", task_list(numbers)),
+            }
+        };
+        let later = |numbers: &[u32]| {
+            if numbers.is_empty() {
+                "none".to_string()
+            } else {
+                task_list(numbers)
+            }
+        };
+        let list = |values: &[String]| {
+            if values.is_empty() {
+                "none".to_string()
+            } else {
+                values.join(", ")
+            }
+        };
+        match change {
+            Change::Args(_) => None,
+            Change::Timeout(old, new) => Some(format!("timeout: {old}s → {new}s")),
+            Change::Input(old, new) => Some(format!(
+                "input: {} → {}",
+                earlier(old.as_slice()),
+                later(new.as_slice())
+            )),
+            Change::DependsOn(old, new) => {
+                Some(format!("after: {} → {}", earlier(old), later(new)))
+            }
+            Change::Tags(old, new) => Some(format!("tags: {} → {}", list(old), list(new))),
+        }
+    }
+
+    /// The diff as JSON, tasks in the same order as `render`
+    pub fn to_json(&self) -> Value {
+        let [added, removed, changed, unchanged] = self.counts();
+        let tasks: Vec<Value> = self
+            .tasks
+            .iter()
+            .map(|task| match task {
+                TaskDiff::Unchanged { before, after } => {
+                    task_json("unchanged", Some(before), Some(after), &[])
+                }
+                TaskDiff::Added(task) => task_json("added", None, Some(task), &[]),
+                TaskDiff::Removed(task) => task_json("removed", Some(task), None, &[]),
+                TaskDiff::Changed {
+                    before,
+                    after,
+                    changes,
+                } => task_json("changed", Some(before), Some(after), changes),
+            })
+            .collect();
+        json!({
+            "added": added,
+            "removed": removed,
+            "changed": changed,
+            "unchanged": unchanged,
+            "tasks": tasks,
+        })
+    }
+}
+
+fn task_json(
+    change: &str,
+    before: Option<&PlanStep>,
+    after: Option<&PlanStep>,
+    changes: &[Change],
+) -> Value {
+    let task = after.or(before).expect("a task is on at least one side");
+    let mut value = json!({
+        "change": change,
+        "task_number": after.map(|task| task.task_number),
+        "was": before.map(|task| task.task_number),
+        "command": task.command,
+        "args": task.args,
+    });
+    if changes.is_empty() {
+        return value;
+    }
+
+    let mut fields = serde_json::Map::new();
+    for change in changes {
+        let (name, field) = match change {
+            Change::Args(words) => {
+                let words: Vec<Value> = words
+                    .iter()
+                    .map(|word| match word {
+                        Word::Kept(arg) => json!({ "kept": arg }),
+                        Word::Removed(arg) => json!({ "removed": arg }),
+                        Word::Added(arg) => json!({ "added": arg }),
+                    })
+                    .collect();
+                ("args", json!(words))
+            }
+            Change::Timeout(old, new) => ("timeout_secs", json!({ "before": old, "after": new })),
+            Change::Input(old, new) => ("input_from_task", json!({ "before": old, "after": new })),
+            Change::DependsOn(old, new) => ("depends_on", json!({ "before": old, "after": new })),
+            Change::Tags(old, new) => ("tags", json!({ "before": old, "after": new })),
+        };
+        fields.insert(name.to_string(), field);
+    }
+    value["changes"] = Value::Object(fields);
+    value
+}
+
+/// The args of a changed task, word-diffed if they changed
+fn args_words(task: &PlanStep, changes: &[Change]) -> Vec<String> {
+    let words = changes.iter().find_map(|change| match change {
+        Change::Args(words) => Some(words),
+        _ => None,
+    });
+    match words {
+        Some(words) => words
+            .iter()
+            .map(|word| match word {
+                Word::Kept(arg) => arg.clone(),
+                Word::Removed(arg) => format!("[-{arg}-]"),
+                Word::Added(arg) => format!("{{+{arg}+}}"),
+            })
+            .collect(),
+        None => task.args.clone(),
+    }
+}
+
+fn was(before: &PlanStep, after: &PlanStep) -> String {
+    if before.task_number == after.task_number {
+        String::new()
+    } else {
+        format!("   (was task {})", before.task_number)
+    }
+}
+
+fn command_line(task: &PlanStep) -> String {
+    std::iter::once(task.command.as_str())
+        .chain(task.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(task_number: u32, command: &str, args: &[&str]) -> PlanStep {
+        PlanStep {
+            task_number,
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout_secs: 300,
+            input_from_task: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }
+    }
+
+    #[test]
+    fn tasks_are_matched_by_command_and_args_compared_word_by_word() {
+        let before = [
+            task(1, "cut", &["-d,", "-f2"]),
+            task(2, "grep", &["error"]),
+            task(3, "sort", &["-k", "2", "-r"]),
+        ];
+        let mut after = vec![
+            task(1, "cut", &["-d,", "-f2"]),
+            task(2, "sort", &["-k", "2", "-n"]),
+            task(3, "uniq", &["-c"]),
+        ];
+        after[1].timeout_secs = 600;
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.render(),
+            "\
+1 added, 1 removed, 1 changed, 1 unchanged
+  1. cut -d, -f2
+-    grep error   (was task 2)
+~ 2. sort -k 2 [--r-] {+-n+}   (was task 3)
+      timeout: 300s → 600s
++ 3. uniq -c"
+        );
+
+        let json = diff.to_json();
+        assert_eq!(json["changed"], 1);
+        assert_eq!(json["tasks"][2]["was"], 3);
+        assert_eq!(
+            json["tasks"][2]["changes"]["args"][2],
+            json!({ "removed": "-r" })
+        );
+        assert_eq!(
+            json["tasks"][2]["changes"]["timeout_secs"],
+            json!({ "before": 300, "after": 600 })
+        );
+    }
+
+    #[test]
+    fn renumbered_inputs_are_not_changes() {
+        let mut before = [task(1, "cat", &[]), task(2, "sort", &[])];
+        before[1].input_from_task = Some(1);
+        // A task inserted first moves both down; sort still reads cat
+        let mut after = [
+            task(1, "ls", &[]),
+            task(2, "cat", &[]),
+            task(3, "sort", &[]),
+        ];
+        after[2].input_from_task = Some(2);
+
+        let diff = diff(&before, &after);
+        assert_eq!(diff.to_json()["unchanged"], 2, "{}", diff.render());
+        assert!(diff.render().contains("  3. sort   (was task 2)"));
+
+        let mut rewired = after.clone();
+        rewired[2].input_from_task = Some(1);
+        rewired[2].depends_on = vec![2];
+        assert_eq!(
+            super::diff(&before, &rewired).render(),
+            "\
+1 added, 0 removed, 1 changed, 1 unchanged
++ 1. ls
+  2. cat   (was task 1)
+~ 3. sort   (was task 2)
+      input: task 2 → task 1
+      after: none → task 2"
+        );
+
+        assert!(super::diff(&before, &before).is_empty());
+        assert_eq!(
+            super::diff(&before, &before).render().lines().next(),
+            Some("No changes.")
+        );
+    }
+}
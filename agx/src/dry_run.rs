@@ -0,0 +1,494 @@
+//! Dry runs: check a generated plan, and optionally run it, without AGQ
+//!
+//! [`check`] looks for problems a worker would hit: tools missing from the
+//! registry, `input_from_task` and `depends_on` references that point
+//! nowhere, and anything agw's own plan validation rejects. [`execute`]
+//! then runs the plan locally through agw's executor, the same way a
+//! worker runs a job.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::plan::{task_list, WorkflowPlan};
+use crate::registry::ToolRegistry;
+
+/// Plan ID given to the plan when it is validated and run locally
+const DRY_RUN_PLAN_ID: &str = "dry-run";
+
+/// Timeout headroom below which a task that ran is flagged, in percent
+const TIMEOUT_HEADROOM_PERCENT: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The plan would fail or be rejected
+    Error,
+    /// The plan runs but probably not as intended
+    Warning,
+}
+
+/// A problem found in a plan
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub task: Option<u32>,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(task: Option<u32>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            task,
+            message,
+        }
+    }
+
+    fn warning(task: Option<u32>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            task,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.task {
+            Some(task) => write!(f, "{severity}: task {task}: {}", self.message),
+            None => write!(f, "{severity}: {}", self.message),
+        }
+    }
+}
+
+/// One task as it ran locally
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskRun {
+    pub task_number: u32,
+    pub command: String,
+    pub exit_code: i32,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub timeout_secs: u32,
+    pub stdout_bytes: usize,
+    pub stderr: String,
+}
+
+/// What a dry run found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    /// Longest the plan can take before timeouts stop it, in seconds
+    pub worst_case_secs: u64,
+    /// Tasks run locally, up to the first failure; `None` if not run
+    pub runs: Option<Vec<TaskRun>>,
+}
+
+impl Report {
+    pub fn has_errors(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.severity == Severity::Error)
+    }
+
+    /// Whether the local run, if any, failed
+    pub fn run_failed(&self) -> bool {
+        self.runs
+            .as_ref()
+            .is_some_and(|runs| runs.iter().any(|run| run.timed_out || run.exit_code != 0))
+    }
+}
+
+/// Check a plan without running it
+pub fn check(plan: &WorkflowPlan, registry: &ToolRegistry) -> Report {
+    let mut findings = Vec::new();
+
+    if plan.tasks.is_empty() {
+        findings.push(Finding::error(None, "plan contains no tasks".to_string()));
+    }
+
+    for (index, task) in plan.tasks.iter().enumerate() {
+        let number = task.task_number;
+        if number as usize != index + 1 {
+            findings.push(Finding::error(
+                Some(number),
+                format!(
+                    "expected task number {}; tasks must be numbered from 1 in order",
+                    index + 1
+                ),
+            ));
+        }
+
+        if registry.find_by_id(&task.command).is_none() {
+            findings.push(Finding::error(
+                Some(number),
+                format!("`{}` is not a tool in the registry", task.command),
+            ));
+        }
+
+        match task.input_from_task {
+            Some(from) if from == 0 || from >= number => findings.push(Finding::error(
+                Some(number),
+                format!("input_from_task {from} does not run before this task"),
+            )),
+            Some(from) if !plan.tasks.iter().any(|t| t.task_number == from) => {
+                findings.push(Finding::error(
+                    Some(number),
+                    format!("input_from_task {from} is not in the plan"),
+                ))
+            }
+            Some(_) => {}
+            // A filter with no arguments reads only stdin, which is empty here
+            None if index > 0 && task.args.is_empty() => findings.push(Finding::warning(
+                Some(number),
+                format!(
+                    "`{}` has no arguments and no input_from_task, so it reads no input",
+                    task.command
+                ),
+            )),
+            None => {}
+        }
+
+        for &from in &task.depends_on {
+            if from == 0 || from >= number {
+                findings.push(Finding::error(
+                    Some(number),
+                    format!("depends_on {from} does not run before this task"),
+                ));
+            } else if !plan.tasks.iter().any(|t| t.task_number == from) {
+                findings.push(Finding::error(
+                    Some(number),
+                    format!("depends_on {from} is not in the plan"),
+                ));
+            }
+        }
+    }
+
+    // Whatever agw would refuse to run, such as unsafe arguments or limits
+    if let Err(error) = agw::plan::validate_plan(&to_agw_plan(plan)) {
+        findings.push(Finding::error(None, format!("rejected by agw: {error}")));
+    }
+
+    Report {
+        findings,
+        worst_case_secs: plan.tasks.iter().map(|t| u64::from(t.timeout_secs)).sum(),
+        runs: None,
+    }
+}
+
+/// Run a checked plan locally through agw's executor
+///
+/// Tasks run in order until one fails, as on a worker. Plans with errors
+/// are not run.
+pub async fn execute(plan: &WorkflowPlan, report: &mut Report) -> Result<(), String> {
+    if report.has_errors() {
+        return Ok(());
+    }
+
+    let result = agw::executor::execute_plan(DRY_RUN_PLAN_ID, &to_agw_plan(plan))
+        .await
+        .map_err(|error| format!("local execution failed: {error}"))?;
+
+    let runs: Vec<TaskRun> = result
+        .task_results
+        .into_iter()
+        .filter_map(|result| {
+            let task = plan
+                .tasks
+                .iter()
+                .find(|task| task.task_number == result.task_number)?;
+            Some(TaskRun {
+                task_number: result.task_number,
+                command: task.command.clone(),
+                exit_code: result.exit_code,
+                timed_out: result.timed_out,
+                duration_ms: result.execution_time_ms,
+                timeout_secs: task.timeout_secs,
+                stdout_bytes: result.stdout.len(),
+                stderr: result.stderr,
+            })
+        })
+        .collect();
+
+    for run in &runs {
+        let budget_ms = u64::from(run.timeout_secs) * 1000;
+        if !run.timed_out && run.duration_ms * 100 > budget_ms * TIMEOUT_HEADROOM_PERCENT {
+            report.findings.push(Finding::warning(
+                Some(run.task_number),
+                format!(
+                    "took {:.1}s of its {}s timeout; a bigger input may time out",
+                    run.duration_ms as f64 / 1000.0,
+                    run.timeout_secs
+                ),
+            ));
+        }
+    }
+
+    report.runs = Some(runs);
+    Ok(())
+}
+
+/// Check a plan, run it if asked, and print the report
+///
+/// With `json`, the plan and report are printed as one JSON object.
+/// Returns an error if the plan has errors or its local run failed.
+pub async fn run(plan: &WorkflowPlan, execute_locally: bool, json: bool) -> Result<(), String> {
+    let mut report = check(plan, &ToolRegistry::load()?);
+    if execute_locally {
+        execute(plan, &mut report).await?;
+    }
+
+    let error = if report.has_errors() {
+        Some("dry run found errors in the plan")
+    } else if report.run_failed() {
+        Some("plan failed when run locally")
+    } else {
+        None
+    };
+    if json {
+        println!(
+            "{:#}",
+            json!({
+                "status": if error.is_some() { "error" } else { "ok" },
+                "error": error,
+                "plan": plan,
+                "report": report,
+            })
+        );
+    } else {
+        print_report(plan, &report, execute_locally);
+    }
+
+    match error {
+        Some(error) => Err(error.to_string()),
+        None => Ok(()),
+    }
+}
+
+fn print_report(plan: &WorkflowPlan, report: &Report, execute_locally: bool) {
+    println!(
+        "Dry run of {} tasks (nothing is submitted to AGQ)",
+        plan.tasks.len()
+    );
+    for task in &plan.tasks {
+        let mut line = format!("  {}. {}", task.task_number, task.command);
+        if !task.args.is_empty() {
+            line.push(' ');
+            line.push_str(&task.args.join(" "));
+        }
+        if let Some(from) = task.input_from_task {
+            line.push_str(&format!("   (input from task {from})"));
+        }
+        if !task.depends_on.is_empty() {
+            line.push_str(&format!("   (after {})", task_list(&task.depends_on)));
+        }
+        println!("{line}   [timeout {}s]", task.timeout_secs);
+    }
+    println!(
+        "Worst case: {}s if every task runs to its timeout",
+        report.worst_case_secs
+    );
+
+    if report.findings.is_empty() {
+        println!("No problems found.");
+    } else {
+        println!("Problems:");
+        for finding in &report.findings {
+            println!("  {finding}");
+        }
+    }
+
+    match &report.runs {
+        Some(runs) => {
+            println!("Local run:");
+            for run in runs {
+                let outcome = if run.timed_out {
+                    format!("timed out after {}s", run.timeout_secs)
+                } else {
+                    format!("exit {}", run.exit_code)
+                };
+                println!(
+                    "  {}. {}: {} in {:.2}s, {} bytes of output",
+                    run.task_number,
+                    run.command,
+                    outcome,
+                    run.duration_ms as f64 / 1000.0,
+                    run.stdout_bytes
+                );
+                for line in run.stderr.lines().filter(|_| run.exit_code != 0) {
+                    println!("      ! {line}");
+                }
+            }
+            if runs.len() < plan.tasks.len() {
+                println!(
+                    "  {} later tasks did not run",
+                    plan.tasks.len() - runs.len()
+                );
+            }
+        }
+        None if execute_locally => println!("Not run locally: fix the errors first."),
+        None => {}
+    }
+}
+
+fn to_agw_plan(plan: &WorkflowPlan) -> agw::plan::Plan {
+    agw::plan::Plan {
+        plan_id: Some(
+            plan.plan_id
+                .clone()
+                .unwrap_or_else(|| DRY_RUN_PLAN_ID.to_string()),
+        ),
+        plan_description: plan.plan_description.clone(),
+        tasks: plan
+            .tasks
+            .iter()
+            .map(|task| agw::plan::Task {
+                task_number: task.task_number,
+                command: task.command.clone(),
+                args: task.args.clone(),
+                input_from_task: task.input_from_task,
+                depends_on: task.depends_on.clone(),
+                timeout_secs: Some(task.timeout_secs),
+                tags: task.tags.clone(),
+                limits: task.limits,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::PlanStep;
+
+    fn step(task_number: u32, command: &str, args: &[&str], input: Option<u32>) -> PlanStep {
+        PlanStep {
+            task_number,
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout_secs: 30,
+            input_from_task: input,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }
+    }
+
+    fn plan(tasks: Vec<PlanStep>) -> WorkflowPlan {
+        WorkflowPlan {
+            plan_id: None,
+            plan_description: None,
+            tasks,
+        }
+    }
+
+    #[test]
+    fn check_reports_unknown_tools_and_bad_wiring() {
+        let report = check(
+            &plan(vec![
+                step(1, "sort", &["data.txt"], None),
+                step(2, "frobnicate", &[], Some(1)),
+                step(3, "uniq", &[], Some(3)),
+                step(4, "tr", &[], None),
+            ]),
+            &ToolRegistry::new(),
+        );
+
+        assert!(report.has_errors());
+        assert_eq!(report.worst_case_secs, 120);
+        let messages: Vec<String> = report.findings.iter().map(ToString::to_string).collect();
+        assert!(messages
+            .contains(&"error: task 2: `frobnicate` is not a tool in the registry".to_string()));
+        assert!(messages.contains(
+            &"error: task 3: input_from_task 3 does not run before this task".to_string()
+        ));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("warning: task 4: `tr` has no arguments")));
+    }
+
+    #[test]
+    fn check_reports_depends_on_that_does_not_run_first() {
+        let mut wc = step(3, "wc", &["a.txt", "b.txt"], None);
+        wc.depends_on = vec![1, 3, 7];
+        let report = check(
+            &plan(vec![
+                step(1, "sort", &["-o", "a.txt", "a.txt"], None),
+                step(2, "sort", &["-o", "b.txt", "b.txt"], None),
+                wc,
+            ]),
+            &ToolRegistry::new(),
+        );
+        let messages: Vec<String> = report.findings.iter().map(ToString::to_string).collect();
+        assert!(messages
+            .contains(&"error: task 3: depends_on 3 does not run before this task".to_string()));
+        assert!(messages
+            .contains(&"error: task 3: depends_on 7 does not run before this task".to_string()));
+        assert!(!messages.iter().any(|m| m.contains("depends_on 1")));
+        assert_eq!(task_list(&[2]), "task 2");
+        assert_eq!(task_list(&[1, 2]), "tasks 1, 2");
+    }
+
+    #[test]
+    fn check_passes_a_wired_plan() {
+        let report = check(
+            &plan(vec![
+                step(1, "sort", &["data.txt"], None),
+                step(2, "uniq", &[], Some(1)),
+            ]),
+            &ToolRegistry::new(),
+        );
+        assert_eq!(report.findings, Vec::new());
+    }
+
+    #[test]
+    fn agw_plan_keeps_dependencies_tags_and_limits() {
+        let mut wc = step(2, "wc", &["-l", "data.txt"], None);
+        wc.depends_on = vec![1];
+        wc.tags = vec!["gpu".to_string()];
+        wc.limits.memory_mb = Some(512);
+
+        let agw_plan = to_agw_plan(&plan(vec![step(1, "sort", &["data.txt"], None), wc]));
+        assert_eq!(agw_plan.plan_id.as_deref(), Some(DRY_RUN_PLAN_ID));
+        let task = &agw_plan.tasks[1];
+        assert_eq!(task.depends_on, [1]);
+        assert_eq!(task.tags, ["gpu"]);
+        assert_eq!(task.limits.memory_mb, Some(512));
+        assert!(agw::plan::validate_plan(&agw_plan).is_ok());
+    }
+
+    #[tokio::test]
+    async fn execute_runs_the_plan_through_agw() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path().join("data.txt");
+        std::fs::write(&data, "b\na\nb\n").unwrap();
+
+        let pipeline = plan(vec![
+            step(1, "sort", &[data.to_str().unwrap()], None),
+            step(2, "uniq", &[], Some(1)),
+        ]);
+        let mut report = check(&pipeline, &ToolRegistry::new());
+        execute(&pipeline, &mut report).await.unwrap();
+
+        let runs = report.runs.as_ref().expect("plan ran");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].exit_code, 0);
+        assert_eq!(runs[1].stdout_bytes, "a\nb\n".len());
+        assert!(!report.run_failed());
+
+        // Plans with errors are not run
+        let broken = plan(vec![step(1, "frobnicate", &[], None)]);
+        let mut report = check(&broken, &ToolRegistry::new());
+        execute(&broken, &mut report).await.unwrap();
+        assert!(report.runs.is_none());
+    }
+}
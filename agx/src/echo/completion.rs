@@ -0,0 +1,222 @@
+//! Tab completion and history for Echo's prompt
+//!
+//! Tab completes slash commands, a path after `/attach`, a saved session
+//! after `/load` or `/save`, and anywhere else tool names and the IDs of
+//! plans stored in AGQ. Plan IDs are fetched the first time one is completed and kept
+//! for a short while, so a slow or stopped AGQ doesn't stall every Tab.
+//! Entered lines are appended to a history file, so Up and Ctrl-R reach
+//! back into earlier sessions.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Slash commands, completed at the start of a line
+pub const COMMANDS: &[&str] = &[
+    "/attach",
+    "/clear",
+    "/diff",
+    "/exec",
+    "/exit",
+    "/help",
+    "/history",
+    "/load",
+    "/plan",
+    "/plans",
+    "/quit",
+    "/refine",
+    "/reset",
+    "/save",
+    "/sessions",
+];
+
+/// Options of `/plan`
+const PLAN_OPTIONS: &[&str] = &["--dry-run", "--execute"];
+
+/// How long fetched plan IDs are used before asking AGQ again
+const PLAN_IDS_TTL: Duration = Duration::from_secs(30);
+
+/// Lines kept in the history file
+pub const MAX_HISTORY: usize = 1000;
+
+/// Where Echo's input history is kept across sessions
+pub fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".local/share/agenix/echo_history"))
+}
+
+type PlanIdSource = Box<dyn Fn() -> Vec<String> + Send + Sync>;
+
+pub struct EchoHelper {
+    tools: Vec<String>,
+    plan_ids: PlanIdSource,
+    /// Plan IDs and when they were fetched
+    cached_plan_ids: Mutex<Option<(Instant, Vec<String>)>>,
+    files: FilenameCompleter,
+}
+
+impl EchoHelper {
+    /// Complete `tools`, and plan IDs from the AGQ named by the environment
+    pub fn new(tools: Vec<String>) -> Self {
+        Self::with_plan_ids(tools, Box::new(agq_plan_ids))
+    }
+
+    pub fn with_plan_ids(tools: Vec<String>, plan_ids: PlanIdSource) -> Self {
+        Self {
+            tools,
+            plan_ids,
+            cached_plan_ids: Mutex::new(None),
+            files: FilenameCompleter::new(),
+        }
+    }
+
+    fn plan_ids(&self) -> Vec<String> {
+        let mut cached = self
+            .cached_plan_ids
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        match cached.as_ref() {
+            Some((fetched, ids)) if fetched.elapsed() < PLAN_IDS_TTL => ids.clone(),
+            _ => {
+                let ids = (self.plan_ids)();
+                *cached = Some((Instant::now(), ids.clone()));
+                ids
+            }
+        }
+    }
+
+    /// Candidates for `line` up to the cursor, and where they start
+    fn candidates(&self, head: &str) -> (usize, Vec<String>) {
+        let start = head
+            .rfind(char::is_whitespace)
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let word = &head[start..];
+
+        if start == 0 && word.starts_with('/') {
+            return (start, matching(COMMANDS.iter().copied(), word));
+        }
+        let command = head.split_whitespace().next().unwrap_or_default();
+        match command {
+            "/plan" => (start, matching(PLAN_OPTIONS.iter().copied(), word)),
+            "/load" | "/save" if head.split_whitespace().count() <= 2 => {
+                let names = super::session::list().unwrap_or_default();
+                (start, matching(names.iter().map(String::as_str), word))
+            }
+            _ if word.is_empty() => (start, Vec::new()),
+            _ => {
+                let plan_ids = self.plan_ids();
+                let words = self.tools.iter().chain(plan_ids.iter()).map(String::as_str);
+                (start, matching(words, word))
+            }
+        }
+    }
+}
+
+/// Candidates starting with `prefix`, sorted and without duplicates
+fn matching<'a>(candidates: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<String> {
+    let mut matches: Vec<String> = candidates
+        .filter(|candidate| candidate.starts_with(prefix))
+        .map(str::to_string)
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+fn agq_plan_ids() -> Vec<String> {
+    let client = crate::agq_client::AgqClient::new(crate::agq_client::AgqConfig::from_env());
+    client
+        .list_plans()
+        .map(|plans| plans.into_iter().map(|plan| plan.plan_id).collect())
+        .unwrap_or_default()
+}
+
+impl Completer for EchoHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let head = &line[..pos];
+        if head.starts_with("/attach ") {
+            return self.files.complete_path(line, pos);
+        }
+
+        let (start, candidates) = self.candidates(head);
+        let pairs = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for EchoHelper {
+    type Hint = String;
+}
+
+impl Highlighter for EchoHelper {}
+
+impl Validator for EchoHelper {}
+
+impl Helper for EchoHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn helper(fetches: Arc<AtomicUsize>) -> EchoHelper {
+        EchoHelper::with_plan_ids(
+            vec!["sort".to_string(), "uniq".to_string()],
+            Box::new(move || {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                vec![
+                    "plan-7f3a2c".to_string(),
+                    "plan-7f9e01".to_string(),
+                    "sort".to_string(),
+                ]
+            }),
+        )
+    }
+
+    #[test]
+    fn completes_commands_tools_and_plan_ids() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let helper = helper(Arc::clone(&fetches));
+
+        assert_eq!(
+            helper.candidates("/s"),
+            (0, vec!["/save".to_string(), "/sessions".to_string()])
+        );
+        assert_eq!(
+            helper.candidates("/plan --d"),
+            (6, vec!["--dry-run".to_string()])
+        );
+        assert_eq!(
+            helper.candidates("run plan-7f"),
+            (
+                4,
+                vec!["plan-7f3a2c".to_string(), "plan-7f9e01".to_string()]
+            )
+        );
+        assert_eq!(helper.candidates("then so"), (5, vec!["sort".to_string()]));
+
+        // An empty word lists nothing, without asking AGQ
+        assert_eq!(helper.candidates("run "), (4, Vec::new()));
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+}
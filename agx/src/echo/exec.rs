@@ -0,0 +1,237 @@
+//! `/exec`: run one tool on the cluster from Echo
+//!
+//! The tool and its arguments become a one-task plan, submitted to AGQ and
+//! run once like `agx RUN`. Echo waits for the task to finish and shows its
+//! output in the chat, and adds a note of the run to the conversation so
+//! Echo can talk about the result.
+
+use std::time::{Duration, Instant};
+
+use crate::agq_client::{AgqClient, TaskOutput, TaskStatus};
+use crate::plan::{PlanStep, WorkflowPlan};
+use crate::registry::ToolRegistry;
+
+/// Time between status polls
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Longest the tool may run; the task's timeout plus time to be picked up
+pub const TASK_TIMEOUT_SECS: u32 = 300;
+const WAIT_LIMIT: Duration = Duration::from_secs(TASK_TIMEOUT_SECS as u64 + 60);
+
+/// Most output kept in the conversation, in characters
+const MAX_NOTE_OUTPUT: usize = 2000;
+
+/// How the task ended, and what it printed
+#[derive(Debug, Clone)]
+pub struct ExecResult {
+    pub plan_id: String,
+    pub task: TaskStatus,
+    pub output: Option<TaskOutput>,
+}
+
+impl ExecResult {
+    pub fn succeeded(&self) -> bool {
+        self.task.status == "completed"
+    }
+}
+
+/// A one-task plan running `words[0]` with the rest as arguments
+///
+/// The tool must be in the registry, as it must for a planned task.
+pub fn plan(words: Vec<String>, registry: &ToolRegistry) -> Result<WorkflowPlan, String> {
+    let mut words = words.into_iter();
+    let tool = words
+        .next()
+        .ok_or_else(|| "Usage: /exec <tool> [args]".to_string())?;
+    if registry.find_by_id(&tool).is_none() {
+        let known: Vec<&str> = registry.tools().iter().map(|t| t.id.as_str()).collect();
+        return Err(format!(
+            "`{}` is not a tool in the registry; try one of {}",
+            tool,
+            known.join(", ")
+        ));
+    }
+
+    Ok(WorkflowPlan {
+        plan_id: None,
+        plan_description: Some(format!("/exec {}", tool)),
+        tasks: vec![PlanStep {
+            task_number: 1,
+            command: tool,
+            args: words.collect(),
+            timeout_secs: TASK_TIMEOUT_SECS,
+            input_from_task: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }],
+    })
+}
+
+/// Submit the plan, run it once, and wait for its task to finish
+pub fn run(client: &AgqClient, plan: WorkflowPlan) -> Result<ExecResult, String> {
+    let (plan_id, action_id) = crate::submit_and_run(client, plan)?;
+    let started = Instant::now();
+
+    loop {
+        let status = client
+            .plan_status(&plan_id, Some(&action_id))
+            .map_err(|error| format!("failed to get plan status: {error}"))?;
+        if status.status == "completed" || status.status == "failed" {
+            let task = status
+                .tasks
+                .into_iter()
+                .next()
+                .ok_or_else(|| format!("plan {plan_id} finished without its task"))?;
+            let output = client
+                .plan_results(&plan_id, Some(&action_id))
+                .map_err(|error| format!("failed to get plan results: {error}"))?
+                .results
+                .into_iter()
+                .next();
+            return Ok(ExecResult {
+                plan_id,
+                task,
+                output,
+            });
+        }
+
+        if started.elapsed() > WAIT_LIMIT {
+            return Err(format!(
+                "gave up waiting after {}s; follow it with `agx WATCH {}`",
+                WAIT_LIMIT.as_secs(),
+                plan_id
+            ));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Text of an output stream, if it is text
+fn text(stream: Option<&str>, encoding: &str) -> Option<String> {
+    let stream = stream.filter(|stream| !stream.is_empty())?;
+    if encoding == "utf8" {
+        Some(stream.to_string())
+    } else {
+        Some(format!("({} bytes of {} output)", stream.len(), encoding))
+    }
+}
+
+/// The run's stdout and stderr, as text
+pub fn streams(result: &ExecResult) -> (Option<String>, Option<String>) {
+    match &result.output {
+        Some(output) => (
+            text(output.stdout.as_deref(), &output.stdout_encoding),
+            text(output.stderr.as_deref(), &output.stderr_encoding),
+        ),
+        None => (None, None),
+    }
+}
+
+/// One line describing how the task ended, e.g. `completed (exit 0, 0.2s)`
+pub fn outcome(task: &TaskStatus) -> String {
+    let mut details = Vec::new();
+    if let Some(exit_code) = task.exit_code {
+        details.push(format!("exit {exit_code}"));
+    }
+    if let Some(duration_ms) = task.duration_ms {
+        details.push(format!("{:.1}s", duration_ms as f64 / 1000.0));
+    }
+    if let Some(worker_id) = &task.worker_id {
+        details.push(format!("on {worker_id}"));
+    }
+    if details.is_empty() {
+        task.status.clone()
+    } else {
+        format!("{} ({})", task.status, details.join(", "))
+    }
+}
+
+/// A note of the run for the conversation, with the output cut short
+pub fn history_note(command_line: &str, result: &ExecResult) -> String {
+    let mut note = format!(
+        "The user ran `{}` on the cluster: {}",
+        command_line,
+        outcome(&result.task)
+    );
+    let (stdout, stderr) = streams(result);
+    for (name, stream) in [("stdout", stdout), ("stderr", stderr)] {
+        if let Some(stream) = stream {
+            note.push_str(&format!("\n{}:\n{}", name, truncate(&stream)));
+        }
+    }
+    note
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_NOTE_OUTPUT {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(MAX_NOTE_OUTPUT).collect();
+    format!("{}\n… (output cut short)", kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn result(status: &str, stdout: Option<&str>) -> ExecResult {
+        ExecResult {
+            plan_id: "plan_1".to_string(),
+            task: TaskStatus {
+                job_id: "job_1".to_string(),
+                task_number: 1,
+                command: "sort".to_string(),
+                status: status.to_string(),
+                exit_code: Some(0),
+                worker_id: Some("worker-a".to_string()),
+                started_at: None,
+                completed_at: None,
+                duration_ms: Some(250),
+                retries: 0,
+            },
+            output: Some(TaskOutput {
+                job_id: "job_1".to_string(),
+                task_number: 1,
+                status: status.to_string(),
+                stdout: stdout.map(String::from),
+                stdout_encoding: "utf8".to_string(),
+                stderr: None,
+                stderr_encoding: "utf8".to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn builds_a_one_task_plan_for_known_tools() {
+        let registry = ToolRegistry::new();
+        let plan = plan(words("sort -n -r"), &registry).unwrap();
+        assert_eq!(plan.tasks.len(), 1);
+        assert_eq!(plan.tasks[0].command, "sort");
+        assert_eq!(plan.tasks[0].args, ["-n", "-r"]);
+        assert!(plan.check_schema().is_ok());
+
+        let error = super::plan(words("frobnicate"), &registry).unwrap_err();
+        assert!(error.starts_with("`frobnicate` is not a tool in the registry"));
+        assert!(super::plan(Vec::new(), &registry).is_err());
+    }
+
+    #[test]
+    fn notes_record_the_outcome_and_output() {
+        let run = result("completed", Some("a\nb\n"));
+        assert!(run.succeeded());
+        assert_eq!(
+            history_note("sort -r", &run),
+            "The user ran `sort -r` on the cluster: completed (exit 0, 0.2s, on worker-a)\nstdout:\na\nb\n"
+        );
+
+        let long = "x".repeat(MAX_NOTE_OUTPUT + 10);
+        let note = history_note("sort", &result("failed", Some(&long)));
+        assert!(note.ends_with("… (output cut short)"));
+        assert!(note.contains(": failed (exit 0"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Keeping Echo's conversation within the model's context window
+//!
+//! Once the history takes up most of the context window, the older turns
+//! are summarized by the same backend into a rolling system note, and the
+//! most recent turns are kept as they are. Each summary replaces the last
+//! one, which is summarized along with the turns after it, so the history
+//! stays a system prompt, one note, and the latest exchanges.
+
+use crate::planner::types::ModelError;
+use crate::planner::{ChatMessage, ModelBackend, PlanContext};
+
+/// Start of the system note holding the summary
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// Context window assumed for backends that don't report one
+const DEFAULT_CONTEXT_TOKENS: usize = 8192;
+
+/// Share of the context window the history may fill, leaving the rest for
+/// the reply
+const HISTORY_SHARE_PERCENT: usize = 75;
+
+/// Most recent user and Echo messages kept word for word
+const KEEP_RECENT: usize = 4;
+
+/// Tokens each message adds for its role and separators
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+const SUMMARY_INSTRUCTIONS: &str = "You summarize conversations between a user and Echo, \
+     an assistant that builds task plans. Write a short summary that keeps what the user \
+     wants to achieve, the files and data involved, the decisions made, and any plan \
+     details discussed. Reply with the summary only.";
+
+/// Tokens the history may take up: `AGX_ECHO_CONTEXT_TOKENS`, or the
+/// backend's context length, less room for the reply
+pub fn history_budget(backend: &dyn ModelBackend) -> usize {
+    let context = std::env::var("AGX_ECHO_CONTEXT_TOKENS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .or_else(|| backend.context_length())
+        .unwrap_or(DEFAULT_CONTEXT_TOKENS);
+    context * HISTORY_SHARE_PERCENT / 100
+}
+
+/// Tokens the messages take up in a prompt
+pub fn tokens(messages: &[ChatMessage], backend: &dyn ModelBackend) -> usize {
+    messages
+        .iter()
+        .map(|message| backend.count_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS)
+        .sum()
+}
+
+/// Whether the history has outgrown its budget and has older turns to
+/// summarize
+pub fn needs_summary(history: &[ChatMessage], backend: &dyn ModelBackend) -> bool {
+    tokens(history, backend) > history_budget(backend) && older_turns(history).is_some()
+}
+
+/// The rolling summary, if the conversation has been summarized
+pub fn summary(history: &[ChatMessage]) -> Option<&str> {
+    history
+        .iter()
+        .filter(|message| message.role == "system")
+        .find_map(|message| message.content.strip_prefix(SUMMARY_PREFIX))
+}
+
+/// Messages to summarize: everything after the system prompt up to the
+/// most recent turns, including an earlier summary
+fn older_turns(history: &[ChatMessage]) -> Option<std::ops::Range<usize>> {
+    let start = match history.first() {
+        Some(first) if first.role == "system" && !first.content.starts_with(SUMMARY_PREFIX) => 1,
+        _ => 0,
+    };
+    let end = history
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, message)| message.role != "system")
+        .nth(KEEP_RECENT - 1)
+        .map(|(index, _)| index)?;
+
+    let exchanged = history[start..end]
+        .iter()
+        .any(|message| message.role != "system");
+    exchanged.then_some(start..end)
+}
+
+/// Replace the older turns with a summary of them, returning whether the
+/// history changed
+///
+/// Turns that together don't fit the budget are summarized a batch at a
+/// time, each batch with the summary so far.
+pub async fn summarize(
+    history: &mut Vec<ChatMessage>,
+    backend: &dyn ModelBackend,
+) -> Result<bool, ModelError> {
+    let Some(range) = older_turns(history) else {
+        return Ok(false);
+    };
+
+    // Half the budget per batch leaves room for the summary so far
+    let batch_budget = (history_budget(backend) / 2).max(1);
+    let mut rolling: Option<String> = None;
+    let mut batch = String::new();
+    for message in &history[range.clone()] {
+        let line = transcript_line(message, backend, batch_budget);
+        if !batch.is_empty()
+            && backend.count_tokens(&batch) + backend.count_tokens(&line) > batch_budget
+        {
+            rolling = Some(summarize_batch(rolling.as_deref(), &batch, backend).await?);
+            batch.clear();
+        }
+        batch.push_str(&line);
+    }
+    let summary = summarize_batch(rolling.as_deref(), &batch, backend).await?;
+
+    history.splice(
+        range,
+        [ChatMessage::system(format!(
+            "{}{}",
+            SUMMARY_PREFIX,
+            summary.trim()
+        ))],
+    );
+    Ok(true)
+}
+
+/// A message as a transcript line, cut to fit the batch if it alone is
+/// too long
+fn transcript_line(message: &ChatMessage, backend: &dyn ModelBackend, budget: usize) -> String {
+    let speaker = match message.role.as_str() {
+        "user" => "User",
+        "assistant" => "Echo",
+        _ => "Note",
+    };
+    let mut content = message.content.trim().to_string();
+    while backend.count_tokens(&content) > budget && !content.is_empty() {
+        let keep = content.chars().count() * 3 / 4;
+        content = content.chars().take(keep).collect();
+        content.push('…');
+    }
+    format!("{}: {}\n", speaker, content)
+}
+
+async fn summarize_batch(
+    previous: Option<&str>,
+    transcript: &str,
+    backend: &dyn ModelBackend,
+) -> Result<String, ModelError> {
+    let request = match previous {
+        Some(previous) => format!(
+            "Summary so far:\n{}\n\nThe conversation continued:\n{}",
+            previous, transcript
+        ),
+        None => format!("Conversation:\n{}", transcript),
+    };
+    let messages = [
+        ChatMessage::system(SUMMARY_INSTRUCTIONS),
+        ChatMessage::user(request),
+    ];
+    backend.chat(&messages, &PlanContext::default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::types::GeneratedPlan;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Backend with a tiny context that records what it was asked to
+    /// summarize
+    struct Summarizer {
+        context: usize,
+        requests: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ModelBackend for Summarizer {
+        async fn generate_plan(
+            &self,
+            _instruction: &str,
+            _context: &PlanContext,
+        ) -> Result<GeneratedPlan, ModelError> {
+            unreachable!("summaries are chats")
+        }
+
+        fn backend_type(&self) -> &'static str {
+            "mock"
+        }
+
+        fn model_name(&self) -> &str {
+            "mock-model"
+        }
+
+        async fn health_check(&self) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn context_length(&self) -> Option<usize> {
+            Some(self.context)
+        }
+
+        async fn chat(
+            &self,
+            history: &[ChatMessage],
+            _context: &PlanContext,
+        ) -> Result<String, ModelError> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push(history[1].content.clone());
+            Ok(format!("summary {}", requests.len()))
+        }
+    }
+
+    fn conversation(turns: usize) -> Vec<ChatMessage> {
+        let mut history = vec![ChatMessage::system("You are Echo")];
+        for turn in 0..turns {
+            history.push(ChatMessage::user(format!("question {turn} about my logs")));
+            history.push(ChatMessage::assistant(format!(
+                "answer {turn}, sort then uniq"
+            )));
+        }
+        history
+    }
+
+    #[tokio::test]
+    async fn older_turns_roll_into_one_summary() {
+        let backend = Summarizer {
+            context: 100,
+            requests: Mutex::new(Vec::new()),
+        };
+        let mut history = conversation(4);
+        assert!(needs_summary(&history, &backend));
+
+        assert!(summarize(&mut history, &backend).await.unwrap());
+        assert_eq!(history.len(), 2 + KEEP_RECENT);
+        assert_eq!(history[0].content, "You are Echo");
+        assert_eq!(summary(&history), Some("summary 1"));
+        assert_eq!(history[2].content, "question 2 about my logs");
+        assert!(backend.requests.lock().unwrap()[0].contains("User: question 0 about my logs"));
+
+        // The next summary takes in the last one
+        history.extend(conversation(2).into_iter().skip(1));
+        assert!(summarize(&mut history, &backend).await.unwrap());
+        let requests = backend.requests.lock().unwrap();
+        assert_eq!(
+            summary(&history),
+            Some(format!("summary {}", requests.len()).as_str())
+        );
+        assert_eq!(history.iter().filter(|m| m.role == "system").count(), 2);
+        assert!(requests[1].contains("Note: Summary of the earlier conversation:\nsummary 1"));
+    }
+
+    #[tokio::test]
+    async fn short_conversations_are_left_alone() {
+        let backend = Summarizer {
+            context: 8192,
+            requests: Mutex::new(Vec::new()),
+        };
+        let mut history = conversation(2);
+        assert!(!needs_summary(&history, &backend));
+        assert!(!summarize(&mut history, &backend).await.unwrap());
+        assert_eq!(history.len(), 5);
+        assert!(backend.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn long_histories_are_summarized_in_batches() {
+        let backend = Summarizer {
+            context: 40,
+            requests: Mutex::new(Vec::new()),
+        };
+        let mut history = conversation(8);
+        summarize(&mut history, &backend).await.unwrap();
+
+        let requests = backend.requests.lock().unwrap();
+        assert!(requests.len() > 1);
+        assert!(requests[1].starts_with("Summary so far:\nsummary 1"));
+        assert_eq!(
+            summary(&history),
+            Some(format!("summary {}", requests.len()).as_str())
+        );
+    }
+}
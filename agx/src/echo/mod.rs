@@ -1,11 +1,21 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use anyhow::Result;
 use rustyline::error::ReadlineError;
-use rustyline::{Config, DefaultEditor, EditMode};
+use rustyline::history::FileHistory;
+use rustyline::{Config, EditMode, Editor};
 
+pub mod completion;
+pub mod exec;
+pub mod memory;
+pub mod session;
 
-use crate::models::ModelManager;
-use crate::planner::{CandleBackend, CandleConfig, ModelRole, ModelBackend, PlanContext, ChatMessage, ToolInfo};
+use crate::planner::{ModelRole, ModelBackend, PlanContext, ChatMessage, ToolInfo};
 use crate::registry::ToolRegistry;
+use completion::EchoHelper;
+use session::{BackendSettings, Session, LAST_SESSION};
 
 // UI Colors
 const COLOR_RESET: &str = "\x1b[0m";
@@ -14,55 +24,36 @@ const COLOR_AI: &str = "\x1b[1;32m";   // Bold Green
 const COLOR_SYSTEM: &str = "\x1b[1;33m"; // Bold Yellow
 const COLOR_BOLD: &str = "\x1b[1m";
 
-pub async fn run() -> Result<()> {
+pub async fn run(context_files: Vec<PathBuf>) -> Result<()> {
     print_banner();
     
     // Load configuration to determine backend
-    let config = crate::planner::PlannerConfig::from_env();
+    let config = crate::planner::PlannerConfig::load().map_err(anyhow::Error::msg)?;
     println!("{}Backend: {:?}{}", COLOR_SYSTEM, config.backend, COLOR_RESET);
 
-    let backend: Box<dyn ModelBackend> = match config.backend {
+    let (backend, settings): (Box<dyn ModelBackend>, BackendSettings) = match config.backend {
         crate::planner::BackendKind::Candle => {
-            println!("{}Initializing Model Manager...{}", COLOR_SYSTEM, COLOR_RESET);
-            let manager = ModelManager::new()?;
-            
-            // Using Qwen 2.5 7B Instruct (GGUF)
-            let repo = "Qwen/Qwen2.5-7B-Instruct-GGUF";
-            let file = "qwen2.5-7b-instruct-q4_k_m.gguf";
-            
-            println!("{}Ensuring model is available: {}/{}{}", COLOR_SYSTEM, repo, file, COLOR_RESET);
-            let model_path = manager.ensure_model(repo, file).await?;
-            
-            // Also ensure tokenizer.json is available
-            let tokenizer_url = "https://huggingface.co/Qwen/Qwen2.5-7B-Instruct/resolve/main/tokenizer.json";
-            let tokenizer_file = "tokenizer.json";
-            
-            let raw_tokenizer_path = manager.download_file_raw(tokenizer_url, tokenizer_file).await?;
-            
-            // Copy tokenizer to model directory so Candle finds it
-            let model_dir = model_path.parent().unwrap();
-            let dest_tokenizer_path = model_dir.join("tokenizer.json");
-            
-            if !dest_tokenizer_path.exists() {
-                tokio::fs::copy(&raw_tokenizer_path, &dest_tokenizer_path).await?;
-            }
+            let model = config.model(ModelRole::Echo);
+            println!("{}Ensuring model is available: {}{}", COLOR_SYSTEM, model.describe(), COLOR_RESET);
 
-            // Initialize Candle Backend
-            let candle_config = CandleConfig {
-                model_path: model_path.clone(),
-                model_role: ModelRole::Echo,
-                ..CandleConfig::default()
-            };
-            
             println!("{}Initializing inference engine (Candle)...{}", COLOR_SYSTEM, COLOR_RESET);
-            let backend = CandleBackend::new(candle_config).await
+            let (backend, device) = config.candle_planner(ModelRole::Echo).await
                 .map_err(|e| anyhow::anyhow!("Failed to initialize backend: {:?}", e))?;
-                
-            Box::new(backend)
+            println!("{}Running on {}{}", COLOR_SYSTEM, device, COLOR_RESET);
+
+            let settings = BackendSettings {
+                backend: "candle".to_string(),
+                model: model.describe(),
+            };
+            (backend, settings)
         }
         crate::planner::BackendKind::Ollama => {
             println!("{}Initializing inference engine (Ollama)...{}", COLOR_SYSTEM, COLOR_RESET);
             let ollama_config = crate::planner::ollama::OllamaConfig::default();
+            let settings = BackendSettings {
+                backend: "ollama".to_string(),
+                model: ollama_config.model.clone(),
+            };
             let backend = crate::planner::OllamaBackend::from_config(ollama_config);
             
             // Verify Ollama connection
@@ -71,25 +62,70 @@ pub async fn run() -> Result<()> {
                 println!("Make sure Ollama is running and the model is pulled.");
             }
             
-            Box::new(backend)
+            (Box::new(backend), settings)
+        }
+        crate::planner::BackendKind::Anthropic => {
+            let backend = crate::planner::AnthropicBackend::from_config(
+                crate::planner::AnthropicConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("Anthropic backend unavailable: {}", e))?;
+            let settings = BackendSettings {
+                backend: "anthropic".to_string(),
+                model: backend.model_name().to_string(),
+            };
+            (Box::new(backend), settings)
+        }
+        crate::planner::BackendKind::Gemini => {
+            let backend = crate::planner::GeminiBackend::from_config(
+                crate::planner::GeminiConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("Gemini backend unavailable: {}", e))?;
+            let settings = BackendSettings {
+                backend: "gemini".to_string(),
+                model: backend.model_name().to_string(),
+            };
+            (Box::new(backend), settings)
+        }
+        crate::planner::BackendKind::OpenAI => {
+            let backend = crate::planner::OpenAIBackend::from_config(
+                crate::planner::OpenAIConfig::default(),
+            );
+            backend.health_check().await
+                .map_err(|e| anyhow::anyhow!("OpenAI backend unavailable: {}", e))?;
+            let settings = BackendSettings {
+                backend: "openai".to_string(),
+                model: backend.model_name().to_string(),
+            };
+            (Box::new(backend), settings)
         }
     };
 
-    // Initialize Rustyline Editor
+    // Initial System Prompt
+    let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
+    let tools_desc = reg.describe_for_planner();
+
+    // Initialize Rustyline Editor, with completion and the history of earlier sessions
     let config = Config::builder()
         .edit_mode(EditMode::Emacs)
         .auto_add_history(true)
+        .max_history_size(completion::MAX_HISTORY)?
+        .history_ignore_dups(true)?
         .build();
-    let mut editor = DefaultEditor::with_config(config)?;
-    
-    // Chat History
-    let mut history: Vec<ChatMessage> = Vec::new();
+    let mut editor: Editor<EchoHelper, FileHistory> = Editor::with_config(config)?;
+    let tool_names = reg.tools().iter().map(|t| t.id.clone()).collect();
+    editor.set_helper(Some(EchoHelper::new(tool_names)));
+    let history_path = completion::history_path();
+    if let Some(path) = &history_path {
+        // A missing file just means no history yet
+        let _ = editor.load_history(path);
+    }
     
-    // Initial System Prompt
-    let reg = ToolRegistry::new();
-    let tools_desc = reg.describe_for_planner();
+    // Chat history, plans, and backend, saved as the last session after each exchange
+    let mut session = Session::new(settings);
     
-    history.push(ChatMessage::system(format!(
+    session.history.push(ChatMessage::system(format!(
         "You are Echo, an intelligent assistant for the Agenix platform. \
          Your goal is to help the user clarify their intent and build a task plan. \
          Be helpful, concise, and conversational. \
@@ -98,9 +134,20 @@ pub async fn run() -> Result<()> {
          If a requested action is not supported by these tools, explain that.",
          tools_desc
     )));
+    for path in &context_files {
+        attach(&mut session, path).map_err(anyhow::Error::msg)?;
+    }
 
     println!("{}", COLOR_RESET);
     println!("Type {}/help{} for commands, {}/exit{} to quit", COLOR_BOLD, COLOR_RESET, COLOR_BOLD, COLOR_RESET);
+    if let Ok(last) = Session::load(LAST_SESSION) {
+        if last.exchanged() > 0 {
+            println!(
+                "Your last session has {} messages; type {}/load{} before chatting to continue it",
+                last.exchanged(), COLOR_BOLD, COLOR_RESET
+            );
+        }
+    }
     println!("---------------------------------------");
 
     loop {
@@ -113,55 +160,111 @@ pub async fn run() -> Result<()> {
                 if input.is_empty() {
                     continue;
                 }
+                if let Some(path) = &history_path {
+                    save_history(&mut editor, path);
+                }
 
                 // Handle Slash Commands
                 if input.starts_with('/') {
-                    match handle_command(input, &mut history, &backend).await {
+                    match handle_command(input, &mut session, &backend).await {
                         Ok(should_exit) => if should_exit { break },
                         Err(e) => println!("{}Error: {}{}", COLOR_SYSTEM, e, COLOR_RESET),
                     }
+                    autosave(&mut session);
                     continue;
                 }
 
                 // User Message
-                history.push(ChatMessage::user(input));
+                session.history.push(ChatMessage::user(input));
 
-                // AI Response
+                // Fold older turns into a summary before they overflow the context window
+                if memory::needs_summary(&session.history, backend.as_ref()) {
+                    println!("{}Summarizing earlier conversation to fit the context window...{}", COLOR_SYSTEM, COLOR_RESET);
+                    if let Err(e) = memory::summarize(&mut session.history, backend.as_ref()).await {
+                        println!("{}Warning: could not summarize the conversation: {:?}{}", COLOR_SYSTEM, e, COLOR_RESET);
+                    }
+                }
+
+                // AI Response, shown as "Thinking..." until the first text arrives
                 print!("{}🤖 Echo > {}Thinking...", COLOR_AI, COLOR_RESET);
                 use std::io::Write;
                 std::io::stdout().flush()?;
 
                 // Build context with tools
-                let reg = ToolRegistry::new();
+                let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
                 let tool_registry: Vec<ToolInfo> = reg.tools()
                     .iter()
-                    .map(|t| ToolInfo::new(t.id, t.description))
+                    .map(|t| ToolInfo::new(&t.id, &t.description))
                     .collect();
                 // Get cluster status
-            let status = get_cluster_status().await;
+                let status = get_cluster_status().await;
 
-            // Build context with tools and status
-            let context = PlanContext {
-                tool_registry: tool_registry.clone(),
-                input_summary: Some(status),
-                ..PlanContext::default()
-            };
+                // Build context with tools and status
+                let context = PlanContext {
+                    tool_registry: tool_registry.clone(),
+                    input_summary: Some(status),
+                    ..PlanContext::default()
+                };
 
-            // Generate response
-            let response = backend.chat(&history, &context).await;
-            
-            match response {
-                Ok(reply) => {
+                // Ctrl+C stops the generation, not Echo
+                let cancelled = Arc::new(AtomicBool::new(false));
+                let watcher = {
+                    let cancelled = Arc::clone(&cancelled);
+                    tokio::spawn(async move {
+                        if tokio::signal::ctrl_c().await.is_ok() {
+                            cancelled.store(true, Ordering::Relaxed);
+                        }
+                    })
+                };
+
+                // Stream the response as it is generated
+                let mut started = false;
+                let mut on_text = |text: &str| {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    if !started {
+                        print!("\r\x1b[K{}🤖 Echo > {}", COLOR_AI, COLOR_RESET);
+                        started = true;
+                    }
+                    print!("{}", text);
+                    let _ = std::io::stdout().flush();
+                    true
+                };
+                let response = backend.chat_stream(&session.history, &context, &mut on_text).await;
+                watcher.abort();
+
+                if !started {
                     // Clear "Thinking..."
-                        print!("\r\x1b[K");
-                        println!("{}🤖 Echo > {}{}", COLOR_AI, COLOR_RESET, reply);
-                        history.push(ChatMessage::assistant(reply));
+                    print!("\r\x1b[K");
+                }
+                match response {
+                    Ok(reply) if cancelled.load(Ordering::Relaxed) => {
+                        println!();
+                        println!("{}Generation cancelled.{}", COLOR_SYSTEM, COLOR_RESET);
+                        // Keep what was shown, or drop the unanswered message
+                        if reply.trim().is_empty() {
+                            session.history.pop();
+                        } else {
+                            session.history.push(ChatMessage::assistant(reply));
+                        }
+                    }
+                    Ok(reply) => {
+                        if started {
+                            println!();
+                        } else {
+                            println!("{}🤖 Echo > {}{}", COLOR_AI, COLOR_RESET, reply);
+                        }
+                        session.history.push(ChatMessage::assistant(reply));
                     }
                     Err(e) => {
-                        print!("\r\x1b[K");
+                        if started {
+                            println!();
+                        }
                         println!("{}Error: {:?}{}", COLOR_SYSTEM, e, COLOR_RESET);
                     }
                 }
+                autosave(&mut session);
             }
             Err(ReadlineError::Interrupted) => {
                 println!("^C");
@@ -181,6 +284,38 @@ pub async fn run() -> Result<()> {
     Ok(())
 }
 
+/// Save the session as the last one, so it survives a crash
+///
+/// A session with nothing in it yet leaves the last one in place.
+fn autosave(session: &mut Session) {
+    if session.exchanged() == 0 && session.plans.is_empty() {
+        return;
+    }
+    if let Err(e) = session.save(LAST_SESSION) {
+        println!("{}Warning: could not save the session: {}{}", COLOR_SYSTEM, e, COLOR_RESET);
+    }
+}
+
+/// Append the lines entered since the last save to the history file
+fn save_history(editor: &mut Editor<EchoHelper, FileHistory>, path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = editor.append_history(path) {
+        println!("{}Warning: could not save input history: {}{}", COLOR_SYSTEM, e, COLOR_RESET);
+    }
+}
+
+/// Attach a file to the session and show what the planner will see
+fn attach(session: &mut Session, path: &Path) -> Result<(), String> {
+    let attachment = session.attach(path)?;
+    println!("{}Attached {}:{}", COLOR_SYSTEM, attachment.path, COLOR_RESET);
+    for line in attachment.summary.lines().skip(1) {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
 fn print_banner() {
     println!("{}", COLOR_AI);
     println!("    ___    ______  __");
@@ -195,7 +330,7 @@ fn print_banner() {
 
 async fn handle_command(
     input: &str, 
-    history: &mut Vec<ChatMessage>, 
+    session: &mut Session, 
     backend: &Box<dyn ModelBackend>
 ) -> Result<bool> {
     let parts: Vec<&str> = input.split_whitespace().collect();
@@ -204,11 +339,13 @@ async fn handle_command(
     match cmd {
         "/exit" | "/quit" => return Ok(true),
         "/clear" | "/reset" => {
-            history.clear();
-            let reg = ToolRegistry::new();
+            session.history.clear();
+            session.plans.clear();
+            session.attachments.clear();
+            let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
             let tools_desc = reg.describe_for_planner();
             
-            history.push(ChatMessage::system(format!(
+            session.history.push(ChatMessage::system(format!(
                 "You are Echo, an intelligent assistant for the Agenix platform. \
                  Your goal is to help the user clarify their intent and build a task plan. \
                  Be helpful, concise, and conversational. \
@@ -221,7 +358,7 @@ async fn handle_command(
         }
         "/history" => {
             println!("{}Conversation History:{}", COLOR_BOLD, COLOR_RESET);
-            for msg in history.iter() {
+            for msg in session.history.iter() {
                 let color = match msg.role.as_str() {
                     "user" => COLOR_USER,
                     "assistant" => COLOR_AI,
@@ -231,13 +368,16 @@ async fn handle_command(
             }
         }
         "/plan" => {
+            let dry_run = parts.contains(&"--dry-run");
+            let execute = parts.contains(&"--execute");
+            if execute && !dry_run {
+                println!("{}--execute is only valid with --dry-run.{}", COLOR_SYSTEM, COLOR_RESET);
+                return Ok(false);
+            }
+
             println!("{}Generating plan from conversation...{}", COLOR_SYSTEM, COLOR_RESET);
             // Aggregate user messages for the instruction
-            let instruction = history.iter()
-                .filter(|m| m.role == "user")
-                .map(|m| m.content.clone())
-                .collect::<Vec<_>>()
-                .join("\n");
+            let instruction = session.instruction();
             
             if instruction.is_empty() {
                 println!("{}No user input to plan from.{}", COLOR_SYSTEM, COLOR_RESET);
@@ -245,14 +385,16 @@ async fn handle_command(
             }
 
             // Build context with tools
-            let reg = ToolRegistry::new();
+            let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
             let tool_registry: Vec<ToolInfo> = reg.tools()
                 .iter()
-                .map(|t| ToolInfo::new(t.id, t.description))
+                .map(|t| ToolInfo::new(&t.id, &t.description))
                 .collect();
             
             let context = PlanContext {
                 tool_registry,
+                input_summary: session.attachment_summary(),
+                documents: crate::index::retrieve(&instruction).await,
                 ..PlanContext::default()
             };
             
@@ -265,33 +407,225 @@ async fn handle_command(
                         tool_registry: context.tool_registry.clone(),
                         existing_tasks: plan.tasks.clone(),
                         input_summary: context.input_summary.clone(),
+                        documents: context.documents.clone(),
                         ..PlanContext::default()
                     };
 
                     // Run validation pass
-                    match backend.generate_plan(&instruction, &delta_context).await {
+                    let tasks = match backend.generate_plan(&instruction, &delta_context).await {
                         Ok(validated_plan) => {
                             println!("{}Plan Validated!{}", COLOR_AI, COLOR_RESET);
                             let json = serde_json::to_string_pretty(&validated_plan.tasks).unwrap();
                             println!("{}", json);
+                            validated_plan.tasks
                         }
                         Err(e) => {
                             println!("{}Validation failed, using original plan: {:?}{}", COLOR_SYSTEM, e, COLOR_RESET);
                             let json = serde_json::to_string_pretty(&plan.tasks).unwrap();
                             println!("{}", json);
+                            plan.tasks
+                        }
+                    };
+
+                    session.add_plan(tasks.clone());
+
+                    if dry_run {
+                        let plan = crate::plan::WorkflowPlan {
+                            plan_id: None,
+                            plan_description: None,
+                            tasks,
+                        };
+                        if let Err(e) = crate::dry_run::run(&plan, execute, false).await {
+                            println!("{}{}{}", COLOR_SYSTEM, e, COLOR_RESET);
                         }
                     }
                 }
                 Err(e) => println!("{}Error generating plan: {:?}{}", COLOR_SYSTEM, e, COLOR_RESET),
             }
         }
+        "/refine" => {
+            // The feedback is the rest of the line
+            let feedback = input["/refine".len()..].trim();
+            if feedback.is_empty() {
+                println!("{}Usage: /refine <feedback>{}", COLOR_SYSTEM, COLOR_RESET);
+                return Ok(false);
+            }
+            let Some(last) = session.plans.last() else {
+                println!("{}No plan to refine yet; generate one with /plan.{}", COLOR_SYSTEM, COLOR_RESET);
+                return Ok(false);
+            };
+
+            println!("{}Refining plan #{}...{}", COLOR_SYSTEM, session.plans.len(), COLOR_RESET);
+            let instruction = refine_instruction(&session.instruction(), feedback);
+            let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
+            let context = PlanContext {
+                tool_registry: reg.tools()
+                    .iter()
+                    .map(|t| ToolInfo::new(&t.id, &t.description))
+                    .collect(),
+                // Existing tasks make the backend revise them, with the Delta prompt
+                existing_tasks: last.tasks.clone(),
+                input_summary: session.attachment_summary(),
+                documents: crate::index::retrieve(&instruction).await,
+                ..PlanContext::default()
+            };
+
+            match backend.generate_plan(&instruction, &context).await {
+                Ok(plan) => {
+                    let json = serde_json::to_string_pretty(&plan.tasks).unwrap();
+                    println!("{}", json);
+                    session.add_revision(plan.tasks, feedback);
+                    println!("{}Saved as plan #{}.{}", COLOR_AI, session.plans.len(), COLOR_RESET);
+                }
+                Err(e) => println!("{}Error refining plan: {:?}{}", COLOR_SYSTEM, e, COLOR_RESET),
+            }
+        }
+        "/plans" => {
+            if let Some(number) = parts.get(1) {
+                let plan = number.trim_start_matches('#')
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| session.plan(number));
+                let Some(plan) = plan else {
+                    println!("{}No plan {}; /plans lists them.{}", COLOR_SYSTEM, number, COLOR_RESET);
+                    return Ok(false);
+                };
+                let json = serde_json::to_string_pretty(&plan.tasks).unwrap();
+                println!("{}", json);
+                return Ok(false);
+            }
+            if session.plans.is_empty() {
+                println!("{}No plans yet; generate one with /plan.{}", COLOR_SYSTEM, COLOR_RESET);
+            }
+            for (index, plan) in session.plans.iter().enumerate() {
+                let origin = match &plan.feedback {
+                    Some(feedback) => format!("refined: {}", feedback),
+                    None => "from /plan".to_string(),
+                };
+                let commands = plan.tasks.iter()
+                    .map(|task| task.command.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                println!("  #{:<3} {} ({} tasks: {})", index + 1, origin, plan.tasks.len(), commands);
+            }
+        }
+        "/diff" => {
+            let count = session.plans.len();
+            let numbers = match &parts[1..] {
+                [] if count >= 2 => Some((count - 1, count)),
+                [a, b] => a.trim_start_matches('#').parse().ok()
+                    .zip(b.trim_start_matches('#').parse().ok()),
+                _ => None,
+            };
+            let Some((a, b)) = numbers else {
+                println!("{}Usage: /diff [a b]; with no revisions, the last two plans are compared.{}", COLOR_SYSTEM, COLOR_RESET);
+                return Ok(false);
+            };
+            let (Some(before), Some(after)) = (session.plan(a), session.plan(b)) else {
+                println!("{}No plan {} or {}; /plans lists them.{}", COLOR_SYSTEM, a, b, COLOR_RESET);
+                return Ok(false);
+            };
+            println!("#{} → #{}: {}", a, b, crate::diff::diff(&before.tasks, &after.tasks).render());
+        }
+        "/save" => {
+            let Some(name) = parts.get(1) else {
+                println!("{}Usage: /save <name>{}", COLOR_SYSTEM, COLOR_RESET);
+                return Ok(false);
+            };
+            let path = session.save(name).map_err(anyhow::Error::msg)?;
+            println!("{}Session saved to {}{}", COLOR_SYSTEM, path.display(), COLOR_RESET);
+        }
+        "/load" => {
+            let name = parts.get(1).copied().unwrap_or(LAST_SESSION);
+            let loaded = Session::load(name).map_err(anyhow::Error::msg)?;
+            if loaded.backend != session.backend {
+                println!(
+                    "{}Note: '{}' was saved using {} ({}); continuing with {} ({}). Set AGX_BACKEND to switch.{}",
+                    COLOR_SYSTEM, name, loaded.backend.backend, loaded.backend.model,
+                    session.backend.backend, session.backend.model, COLOR_RESET
+                );
+            }
+            session.history = loaded.history;
+            session.plans = loaded.plans;
+            session.attachments = loaded.attachments;
+            println!(
+                "{}Loaded '{}': {} messages, {} plans.{}",
+                COLOR_SYSTEM, name, session.exchanged(), session.plans.len(), COLOR_RESET
+            );
+        }
+        "/attach" => {
+            // The path is the rest of the line, so it may contain spaces
+            let path = input["/attach".len()..].trim();
+            if path.is_empty() {
+                if session.attachments.is_empty() {
+                    println!("{}No attached files. Usage: /attach <path>{}", COLOR_SYSTEM, COLOR_RESET);
+                }
+                for attachment in &session.attachments {
+                    println!("  {}", attachment.path);
+                }
+                return Ok(false);
+            }
+            attach(session, Path::new(path)).map_err(anyhow::Error::msg)?;
+        }
+        "/exec" => {
+            let command_line = input["/exec".len()..].trim().to_string();
+            let words = crate::review::split_args(&command_line).map_err(anyhow::Error::msg)?;
+            let reg = ToolRegistry::load().map_err(anyhow::Error::msg)?;
+            let plan = exec::plan(words, &reg).map_err(anyhow::Error::msg)?;
+
+            println!("{}Running {} on the cluster...{}", COLOR_SYSTEM, command_line, COLOR_RESET);
+            let result = tokio::task::spawn_blocking(|| {
+                let config = crate::agq_client::AgqConfig::from_env();
+                let client = crate::agq_client::AgqClient::new(config);
+                exec::run(&client, plan)
+            })
+            .await?
+            .map_err(anyhow::Error::msg)?;
+
+            let (stdout, stderr) = exec::streams(&result);
+            if let Some(stdout) = &stdout {
+                println!("{}", stdout.trim_end());
+            }
+            if let Some(stderr) = &stderr {
+                println!("{}{}{}", COLOR_SYSTEM, stderr.trim_end(), COLOR_RESET);
+            }
+            let color = if result.succeeded() { COLOR_AI } else { COLOR_SYSTEM };
+            println!(
+                "{}{}: {}{}",
+                color, result.plan_id, exec::outcome(&result.task), COLOR_RESET
+            );
+            session
+                .history
+                .push(ChatMessage::system(exec::history_note(&command_line, &result)));
+        }
+        "/sessions" => {
+            let names = session::list().map_err(anyhow::Error::msg)?;
+            if names.is_empty() {
+                println!("{}No saved sessions.{}", COLOR_SYSTEM, COLOR_RESET);
+            }
+            for name in names {
+                println!("  {}", name);
+            }
+        }
         "/help" => {
             println!("{}Available Commands:{}", COLOR_BOLD, COLOR_RESET);
             println!("  /exit, /quit    - Exit the chat");
             println!("  /clear, /reset  - Clear conversation history");
             println!("  /history        - Show full conversation history");
+            println!("  /save <name>    - Save the conversation and its plans");
+            println!("  /load [name]    - Restore a saved session (default: the last one)");
+            println!("  /sessions       - List saved sessions");
+            println!("  /attach [path]  - Attach a file as context for chat and plans, or list them");
             println!("  /plan           - Generate a plan from the current conversation");
+            println!("  /plan --dry-run [--execute]");
+            println!("                  - Also check the plan, and with --execute run it locally");
+            println!("  /refine <text>  - Revise the last plan with your feedback");
+            println!("  /plans [n]      - List plan revisions, or show revision n");
+            println!("  /diff [a b]     - Show what changed between the last two plans, or revisions a and b");
+            println!("  /exec <tool> [args]");
+            println!("                  - Run one tool on the cluster and show its output");
             println!("  /help           - Show this help message");
+            println!("Tab completes commands, tool names, and plan IDs.");
         }
         _ => {
             println!("{}Unknown command: {}. Type /help for available commands.{}", COLOR_SYSTEM, cmd, COLOR_RESET);
@@ -300,6 +634,14 @@ async fn handle_command(
     Ok(false)
 }
 
+/// Instruction asking for the last plan to be revised with `feedback`
+fn refine_instruction(instruction: &str, feedback: &str) -> String {
+    format!(
+        "{}\n\nRevise the current plan with this feedback from the user: {}",
+        instruction, feedback
+    )
+}
+
 async fn get_cluster_status() -> String {
     tokio::task::spawn_blocking(|| {
         let config = crate::agq_client::AgqConfig::from_env();
@@ -307,26 +649,24 @@ async fn get_cluster_status() -> String {
         
         let mut status = String::from("Cluster Status:\n");
         
-        match client.list_workers() {
-            Ok(crate::agq_client::OpsResponse::Workers(w)) => {
-                 status.push_str(&format!("- Workers: {} active\n", w.len()));
-                 for worker in w {
-                     status.push_str(&format!("  - {}\n", worker));
+        match client.workers() {
+            Ok(workers) => {
+                 status.push_str(&format!("- Workers: {} active\n", workers.len()));
+                 for worker in workers {
+                     status.push_str(&format!("  - {} ({}): {}\n", worker.worker_id, worker.status, worker.tools));
                  }
             }
             Err(e) => status.push_str(&format!("- Workers: Error ({})\n", e)),
-            _ => status.push_str("- Workers: Unknown response\n"),
         }
 
-        match client.list_jobs() {
-            Ok(crate::agq_client::OpsResponse::Jobs(j)) => {
-                 status.push_str(&format!("- Jobs: {}\n", j.len()));
-                 for job in j {
-                     status.push_str(&format!("  - {}\n", job));
+        match client.jobs() {
+            Ok(jobs) => {
+                 status.push_str(&format!("- Jobs: {}\n", jobs.len()));
+                 for job in jobs {
+                     status.push_str(&format!("  - {} ({})\n", job.job_id, job.status));
                  }
             }
-             Err(e) => status.push_str(&format!("- Jobs: Error ({})\n", e)),
-            _ => status.push_str("- Jobs: Unknown response\n"),
+            Err(e) => status.push_str(&format!("- Jobs: Error ({})\n", e)),
         }
         
         status
@@ -0,0 +1,363 @@
+//! Saved Echo conversations
+//!
+//! A session holds the chat history, the plans generated from it and their
+//! revisions, the files attached to it, and the backend it ran on. Sessions are saved as JSON under
+//! `~/.local/share/agenix/sessions`: by name with `/save`, and after every
+//! exchange as `last`, so a conversation survives a crashed terminal.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::plan::PlanStep;
+use crate::planner::ChatMessage;
+
+/// Name of the automatically saved session
+pub const LAST_SESSION: &str = "last";
+
+/// Maximum length of a session name
+const MAX_NAME_LEN: usize = 64;
+
+/// Backend a session ran on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendSettings {
+    /// `ollama`, `candle`, `anthropic` or `gemini`
+    pub backend: String,
+    pub model: String,
+}
+
+/// A plan generated during the conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlan {
+    pub created_at: String,
+    pub tasks: Vec<PlanStep>,
+    /// Feedback given with `/refine` to revise the previous plan into this
+    /// one; `None` for a plan generated by `/plan`
+    #[serde(default)]
+    pub feedback: Option<String>,
+}
+
+/// A file attached with `/attach` or `--context-file`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub path: String,
+    /// Summary given to the planner, see [`crate::context_file`]
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub backend: BackendSettings,
+    pub history: Vec<ChatMessage>,
+    #[serde(default)]
+    pub plans: Vec<SavedPlan>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub saved_at: Option<String>,
+}
+
+impl Session {
+    pub fn new(backend: BackendSettings) -> Self {
+        Self {
+            backend,
+            history: Vec::new(),
+            plans: Vec::new(),
+            attachments: Vec::new(),
+            saved_at: None,
+        }
+    }
+
+    /// Record a generated plan
+    pub fn add_plan(&mut self, tasks: Vec<PlanStep>) {
+        self.plans.push(SavedPlan {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            tasks,
+            feedback: None,
+        });
+    }
+
+    /// Record a revision of the last plan, made from `feedback`
+    pub fn add_revision(&mut self, tasks: Vec<PlanStep>, feedback: &str) {
+        self.plans.push(SavedPlan {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            tasks,
+            feedback: Some(feedback.to_string()),
+        });
+    }
+
+    /// Plan revision `number`, counting from 1 as `/plans` shows them
+    pub fn plan(&self, number: usize) -> Option<&SavedPlan> {
+        number.checked_sub(1).and_then(|index| self.plans.get(index))
+    }
+
+    /// What the user asked for: the summary of the earlier conversation,
+    /// if any, and their messages since, joined
+    pub fn instruction(&self) -> String {
+        super::memory::summary(&self.history)
+            .into_iter()
+            .map(str::to_string)
+            .chain(
+                self.history
+                    .iter()
+                    .filter(|message| message.role == "user")
+                    .map(|message| message.content.clone()),
+            )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Summarize the file at `path` and attach it
+    ///
+    /// The summary is also added to the history, so Echo sees the file
+    /// while chatting.
+    pub fn attach(&mut self, path: &Path) -> Result<&Attachment, String> {
+        let summary = crate::context_file::summarize(path)?;
+        self.history.push(ChatMessage::system(format!(
+            "The user attached a file:\n{}",
+            summary
+        )));
+        self.attachments.push(Attachment {
+            path: path.display().to_string(),
+            summary,
+        });
+        Ok(self.attachments.last().expect("just attached"))
+    }
+
+    /// Summaries of the attached files, for planning
+    pub fn attachment_summary(&self) -> Option<String> {
+        let summaries: Vec<String> = self
+            .attachments
+            .iter()
+            .map(|attachment| attachment.summary.clone())
+            .collect();
+        crate::context_file::join(&summaries)
+    }
+
+    /// Number of messages the user and Echo exchanged
+    pub fn exchanged(&self) -> usize {
+        self.history
+            .iter()
+            .filter(|message| message.role != "system")
+            .count()
+    }
+
+    /// Save under `name` in the default sessions directory
+    pub fn save(&mut self, name: &str) -> Result<PathBuf, String> {
+        self.save_in(&sessions_dir()?, name)
+    }
+
+    pub fn save_in(&mut self, dir: &Path, name: &str) -> Result<PathBuf, String> {
+        validate_name(name)?;
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create sessions directory: {}", e))?;
+
+        self.saved_at = Some(chrono::Utc::now().to_rfc3339());
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize session: {}", e))?;
+
+        // Write then rename, so a crash mid-save keeps the previous copy
+        let path = dir.join(format!("{name}.json"));
+        let partial = dir.join(format!(".{name}.json.tmp"));
+        fs::write(&partial, json).map_err(|e| format!("failed to write session: {}", e))?;
+        fs::rename(&partial, &path).map_err(|e| format!("failed to write session: {}", e))?;
+        Ok(path)
+    }
+
+    /// Load the session saved as `name` in the default sessions directory
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_from(&sessions_dir()?, name)
+    }
+
+    pub fn load_from(dir: &Path, name: &str) -> Result<Self, String> {
+        validate_name(name)?;
+        let path = dir.join(format!("{name}.json"));
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(format!("no saved session named '{}'", name));
+            }
+            Err(e) => return Err(format!("failed to read session '{}': {}", name, e)),
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| format!("failed to parse session '{}': {}", name, e))
+    }
+}
+
+/// Names of the saved sessions in the default directory, sorted
+pub fn list() -> Result<Vec<String>, String> {
+    list_in(&sessions_dir()?)
+}
+
+pub fn list_in(dir: &Path) -> Result<Vec<String>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to list sessions: {}", e)),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".json")
+                .filter(|name| validate_name(name).is_ok())
+                .map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn sessions_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or_else(|| "could not determine home directory".to_string())?;
+    Ok(home.join(".local/share/agenix/sessions"))
+}
+
+/// Session names become file names, so only allow a safe set of characters
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!(
+            "session names must be 1 to {} characters",
+            MAX_NAME_LEN
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid session name '{}': use letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> BackendSettings {
+        BackendSettings {
+            backend: "ollama".to_string(),
+            model: "qwen2.5:7b".to_string(),
+        }
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut session = Session::new(settings());
+        session.history.push(ChatMessage::system("You are Echo"));
+        session.history.push(ChatMessage::user("dedupe my logs"));
+        session
+            .history
+            .push(ChatMessage::assistant("Sort, then uniq."));
+        session.add_plan(vec![PlanStep {
+            task_number: 1,
+            command: "sort".to_string(),
+            args: Vec::new(),
+            timeout_secs: 300,
+            input_from_task: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }]);
+        session.save_in(dir.path(), "logs").unwrap();
+        session.save_in(dir.path(), LAST_SESSION).unwrap();
+
+        let loaded = Session::load_from(dir.path(), "logs").unwrap();
+        assert_eq!(loaded.backend, settings());
+        assert_eq!(loaded.history.len(), 3);
+        assert_eq!(loaded.exchanged(), 2);
+        assert_eq!(loaded.plans[0].tasks[0].command, "sort");
+        assert!(loaded.saved_at.is_some());
+
+        assert_eq!(list_in(dir.path()).unwrap(), ["last", "logs"]);
+        let missing = Session::load_from(dir.path(), "other").unwrap_err();
+        assert!(missing.contains("no saved session named 'other'"));
+    }
+
+    #[test]
+    fn attachments_are_saved_with_the_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let attached = dir.path().join("users.csv");
+        std::fs::write(&attached, "id,email\n1,ada@example.com\n").unwrap();
+
+        let mut session = Session::new(settings());
+        assert_eq!(session.attachment_summary(), None);
+        session.attach(&attached).unwrap();
+        assert!(session.attach(&dir.path().join("missing.csv")).is_err());
+        session.save_in(dir.path(), "users").unwrap();
+
+        let loaded = Session::load_from(dir.path(), "users").unwrap();
+        assert_eq!(loaded.attachments.len(), 1);
+        assert!(loaded.history[0].content.contains("Columns (2): id, email"));
+        assert!(loaded
+            .attachment_summary()
+            .unwrap()
+            .starts_with("File: "));
+    }
+
+    #[test]
+    fn refined_plans_are_numbered_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let step = |command: &str| PlanStep {
+            task_number: 1,
+            command: command.to_string(),
+            args: Vec::new(),
+            timeout_secs: 300,
+            input_from_task: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        };
+
+        let mut session = Session::new(settings());
+        session.history.push(ChatMessage::user("dedupe my logs"));
+        session.history.push(ChatMessage::assistant("Sort, then uniq."));
+        session.history.push(ChatMessage::user("keep the counts"));
+        assert_eq!(session.instruction(), "dedupe my logs\nkeep the counts");
+
+        session.add_plan(vec![step("sort")]);
+        session.add_revision(vec![step("uniq")], "count duplicates");
+        session.save_in(dir.path(), "logs").unwrap();
+
+        let loaded = Session::load_from(dir.path(), "logs").unwrap();
+        assert_eq!(loaded.plan(1).unwrap().feedback, None);
+        let revision = loaded.plan(2).unwrap();
+        assert_eq!(revision.feedback.as_deref(), Some("count duplicates"));
+        assert_eq!(revision.tasks[0].command, "uniq");
+        assert!(loaded.plan(0).is_none());
+        assert!(loaded.plan(3).is_none());
+    }
+
+    #[test]
+    fn summarized_conversations_keep_the_summary_in_the_instruction() {
+        let summary = format!("{}The user has web logs.", crate::echo::memory::SUMMARY_PREFIX);
+        let mut session = Session::new(settings());
+        session.history.push(ChatMessage::system("You are Echo"));
+        session.history.push(ChatMessage::system(summary));
+        session.history.push(ChatMessage::user("keep the counts"));
+
+        assert_eq!(
+            session.instruction(),
+            "The user has web logs.\nkeep the counts"
+        );
+    }
+
+    #[test]
+    fn session_names_cannot_escape_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = Session::new(settings());
+        assert!(session.save_in(dir.path(), "../escape").is_err());
+        assert!(session.save_in(dir.path(), "").is_err());
+        assert!(Session::load_from(dir.path(), "a/b").is_err());
+        assert!(list_in(&dir.path().join("missing")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,272 @@
+//! How long a plan will take, and what it will cost, before it is submitted
+//!
+//! Estimates come from the execution history AGQ keeps per tool (see
+//! TOOLS.STATS). Tasks wait for the task they read output from and the
+//! tasks they depend on, and the rest run side by side, so the wall-clock
+//! estimate is the slowest chain of `input_from_task` and `depends_on`
+//! references. The cost is the CPU time of every task
+//! together. Tools without history count as nothing and are named instead.
+//!
+//! Budgets are set with `AGX_BUDGET_SECS` (wall-clock) and
+//! `AGX_BUDGET_CPU_SECS` (CPU time); estimates over them get a warning.
+
+use std::collections::HashMap;
+
+use crate::agq_client::{AgqClient, ToolStats};
+use crate::plan::WorkflowPlan;
+
+/// Limits a plan's estimate is checked against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    pub wall_secs: Option<u64>,
+    pub cpu_secs: Option<u64>,
+}
+
+impl Budget {
+    pub fn from_env() -> Self {
+        let secs = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse().ok());
+        Self {
+            wall_secs: secs("AGX_BUDGET_SECS"),
+            cpu_secs: secs("AGX_BUDGET_CPU_SECS"),
+        }
+    }
+}
+
+/// Expected duration and cost of a plan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Estimate {
+    /// Wall-clock time with every tool taking its average
+    pub wall_ms: u64,
+    /// Wall-clock time with every tool taking its longest
+    pub worst_wall_ms: u64,
+    /// CPU time of all tasks, or their execution time where no CPU time
+    /// was measured
+    pub cpu_ms: u64,
+    /// Tools with no history, sorted
+    pub unknown: Vec<String>,
+}
+
+/// Tool history fetched from AGQ, and the budgets to warn about
+#[derive(Debug, Clone, Default)]
+pub struct Estimator {
+    /// `None` when AGQ could not be asked
+    stats: Option<HashMap<String, ToolStats>>,
+    budget: Budget,
+}
+
+impl Estimator {
+    pub fn new(stats: Vec<ToolStats>, budget: Budget) -> Self {
+        Self {
+            stats: Some(
+                stats
+                    .into_iter()
+                    .map(|stats| (stats.tool.clone(), stats))
+                    .collect(),
+            ),
+            budget,
+        }
+    }
+
+    /// Fetch the history of the plan's tools, and the budgets from the
+    /// environment
+    pub fn fetch(client: &AgqClient, plan: &WorkflowPlan) -> Result<Self, String> {
+        let mut tools: Vec<&str> = plan.tasks.iter().map(|t| t.command.as_str()).collect();
+        tools.sort_unstable();
+        tools.dedup();
+        Ok(Self::new(client.tool_stats(&tools)?, Budget::from_env()))
+    }
+
+    /// Estimate for the plan, if there is history to estimate with
+    pub fn estimate(&self, plan: &WorkflowPlan) -> Option<Estimate> {
+        let stats = self.stats.as_ref()?;
+        let mut estimate = Estimate::default();
+        // Finish times of each task, by task number: average and longest
+        let mut finished: HashMap<u32, (u64, u64)> = HashMap::new();
+
+        for task in &plan.tasks {
+            let (mean, max, cpu) = match stats.get(&task.command) {
+                Some(stats) => (
+                    stats.mean_duration_ms,
+                    stats.max_duration_ms,
+                    stats.mean_cpu_ms.unwrap_or(stats.mean_duration_ms),
+                ),
+                None => {
+                    estimate.unknown.push(task.command.clone());
+                    (0, 0, 0)
+                }
+            };
+            // A task starts once the last of the tasks it waits for is done
+            let (start, worst_start) = task
+                .input_from_task
+                .iter()
+                .chain(&task.depends_on)
+                .filter_map(|from| finished.get(from).copied())
+                .fold((0, 0), |(start, worst), (done, worst_done)| {
+                    (start.max(done), worst.max(worst_done))
+                });
+            let done = (start + mean, worst_start + max);
+            finished.insert(task.task_number, done);
+
+            estimate.wall_ms = estimate.wall_ms.max(done.0);
+            estimate.worst_wall_ms = estimate.worst_wall_ms.max(done.1);
+            estimate.cpu_ms += cpu;
+        }
+
+        estimate.unknown.sort();
+        estimate.unknown.dedup();
+        Some(estimate)
+    }
+
+    /// Lines describing the plan's estimate and any budget it exceeds
+    pub fn report(&self, plan: &WorkflowPlan) -> Vec<String> {
+        let Some(estimate) = self.estimate(plan) else {
+            return Vec::new();
+        };
+
+        let mut lines = Vec::new();
+        if estimate.unknown.len() < plan.tasks.len() {
+            lines.push(format!(
+                "Estimate: ~{} wall-clock (up to {}), {} CPU",
+                format_ms(estimate.wall_ms),
+                format_ms(estimate.worst_wall_ms),
+                format_ms(estimate.cpu_ms)
+            ));
+        }
+        if !estimate.unknown.is_empty() {
+            lines.push(format!(
+                "No execution history for: {}",
+                estimate.unknown.join(", ")
+            ));
+        }
+        if let Some(limit) = self.budget.wall_secs {
+            if estimate.wall_ms > limit * 1000 {
+                lines.push(format!(
+                    "Warning: estimated {} exceeds the wall-clock budget of {} (AGX_BUDGET_SECS)",
+                    format_ms(estimate.wall_ms),
+                    format_ms(limit * 1000)
+                ));
+            }
+        }
+        if let Some(limit) = self.budget.cpu_secs {
+            if estimate.cpu_ms > limit * 1000 {
+                lines.push(format!(
+                    "Warning: estimated {} CPU exceeds the budget of {} (AGX_BUDGET_CPU_SECS)",
+                    format_ms(estimate.cpu_ms),
+                    format_ms(limit * 1000)
+                ));
+            }
+        }
+        lines
+    }
+}
+
+/// A duration for people, e.g. `<1s`, `45s`, `2m 05s` or `1h 03m`
+pub fn format_ms(ms: u64) -> String {
+    let secs = ms / 1000;
+    match secs {
+        0 => "<1s".to_string(),
+        1..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::PlanStep;
+
+    fn stats(tool: &str, mean_secs: u64, max_secs: u64, cpu_secs: Option<u64>) -> ToolStats {
+        ToolStats {
+            tool: tool.to_string(),
+            runs: 3,
+            failed: 0,
+            mean_duration_ms: mean_secs * 1000,
+            max_duration_ms: max_secs * 1000,
+            mean_cpu_ms: cpu_secs.map(|secs| secs * 1000),
+        }
+    }
+
+    fn plan(tasks: &[(&str, Option<u32>)]) -> WorkflowPlan {
+        WorkflowPlan {
+            plan_id: None,
+            plan_description: None,
+            tasks: tasks
+                .iter()
+                .zip(1..)
+                .map(|((command, input_from_task), task_number)| PlanStep {
+                    task_number,
+                    command: command.to_string(),
+                    args: Vec::new(),
+                    timeout_secs: 300,
+                    input_from_task: *input_from_task,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn chains_add_up_and_independent_tasks_overlap() {
+        let estimator = Estimator::new(
+            vec![
+                stats("sort", 10, 30, Some(8)),
+                stats("uniq", 5, 6, None),
+                stats("grep", 60, 90, Some(2)),
+            ],
+            Budget::default(),
+        );
+        // sort -> uniq runs beside grep; grep is the slower branch
+        let estimate = estimator
+            .estimate(&plan(&[("sort", None), ("uniq", Some(1)), ("grep", None)]))
+            .unwrap();
+        assert_eq!(estimate.wall_ms, 60_000);
+        assert_eq!(estimate.worst_wall_ms, 90_000);
+        assert_eq!(estimate.cpu_ms, 15_000);
+        assert!(estimate.unknown.is_empty());
+
+        let estimate = estimator
+            .estimate(&plan(&[("sort", None), ("uniq", Some(1)), ("jq", Some(2))]))
+            .unwrap();
+        assert_eq!(estimate.wall_ms, 15_000);
+        assert_eq!(estimate.unknown, ["jq"]);
+
+        // uniq waits for both sort and grep
+        let mut fan_in = plan(&[("sort", None), ("grep", None), ("uniq", Some(1))]);
+        fan_in.tasks[2].depends_on = vec![2];
+        let estimate = estimator.estimate(&fan_in).unwrap();
+        assert_eq!(estimate.wall_ms, 65_000);
+        assert_eq!(estimate.worst_wall_ms, 96_000);
+
+        assert_eq!(
+            Estimator::default().estimate(&plan(&[("sort", None)])),
+            None
+        );
+    }
+
+    #[test]
+    fn reports_warn_about_exceeded_budgets() {
+        let budget = Budget {
+            wall_secs: Some(60),
+            cpu_secs: Some(3600),
+        };
+        let estimator = Estimator::new(vec![stats("sort", 45, 130, Some(40))], budget);
+        let report = estimator.report(&plan(&[("sort", None), ("sort", Some(1)), ("wc", None)]));
+        assert_eq!(
+            report,
+            [
+                "Estimate: ~1m 30s wall-clock (up to 4m 20s), 1m 20s CPU",
+                "No execution history for: wc",
+                "Warning: estimated 1m 30s exceeds the wall-clock budget of 1m 00s (AGX_BUDGET_SECS)",
+            ]
+        );
+
+        assert!(Estimator::default()
+            .report(&plan(&[("sort", None)]))
+            .is_empty());
+        assert_eq!(format_ms(500), "<1s");
+        assert_eq!(format_ms(3_780_000), "1h 03m");
+    }
+}
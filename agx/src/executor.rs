@@ -31,7 +31,7 @@ impl Executor {
                 .find_by_id(&task.command)
                 .ok_or_else(|| format!("unknown tool in plan: {}", task.command))?;
 
-            let mut child = Command::new(tool.command);
+            let mut child = Command::new(&tool.command);
             child.args(&task.args);
 
             let mut child = child
@@ -0,0 +1,402 @@
+//! `agx PLAN show`: draw a Plan's task graph
+//!
+//! Tasks are nodes and each `input_from_task` is an edge from the task that
+//! feeds it, so a Plan whose tasks share an input branches. Tasks a task
+//! only waits for, its `depends_on`, are drawn as dashed edges. The graph is
+//! drawn as Mermaid or Graphviz dot for docs and reviews, or as a tree on
+//! the terminal, or listed as JSON for scripts. When the Plan has run, each
+//! task is colored by its status.
+
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::plan::{task_list, PlanStep, WorkflowPlan};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Mermaid,
+    Dot,
+    Ascii,
+}
+
+impl GraphFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "mermaid" => Ok(Self::Mermaid),
+            "dot" => Ok(Self::Dot),
+            "ascii" => Ok(Self::Ascii),
+            other => Err(format!(
+                "unknown graph format: {other}. Expected mermaid, dot, or ascii."
+            )),
+        }
+    }
+}
+
+/// Task statuses of one run, by task number
+pub type TaskStatuses = HashMap<u32, String>;
+
+/// Draw `plan` in `format`, coloring tasks by `statuses`
+///
+/// `color` only affects the ASCII tree, which uses terminal colors.
+pub fn render(
+    plan: &WorkflowPlan,
+    statuses: &TaskStatuses,
+    format: GraphFormat,
+    color: bool,
+) -> String {
+    let mut tasks: Vec<&PlanStep> = plan.tasks.iter().collect();
+    tasks.sort_by_key(|task| task.task_number);
+
+    match format {
+        GraphFormat::Mermaid => mermaid(&tasks, statuses),
+        GraphFormat::Dot => dot(&tasks, statuses),
+        GraphFormat::Ascii => ascii(&tasks, statuses, color),
+    }
+}
+
+/// The graph as JSON: each task with its status, `[from, to]` edges, and
+/// `[from, to]` pairs of `depends_on` tasks
+pub fn to_json(plan_id: &str, plan: &WorkflowPlan, statuses: &TaskStatuses) -> Value {
+    let mut tasks: Vec<&PlanStep> = plan.tasks.iter().collect();
+    tasks.sort_by_key(|task| task.task_number);
+
+    let nodes: Vec<Value> = tasks
+        .iter()
+        .map(|task| {
+            json!({
+                "task_number": task.task_number,
+                "command": task.command,
+                "args": task.args,
+                "status": statuses.get(&task.task_number),
+            })
+        })
+        .collect();
+    let edges: Vec<[u32; 2]> = tasks
+        .iter()
+        .filter_map(|task| parent(task, &tasks).map(|from| [from, task.task_number]))
+        .collect();
+    let depends_on: Vec<[u32; 2]> = tasks
+        .iter()
+        .flat_map(|task| {
+            waits_for(task, &tasks)
+                .into_iter()
+                .map(|from| [from, task.task_number])
+        })
+        .collect();
+
+    json!({
+        "status": "ok",
+        "plan_id": plan_id,
+        "tasks": nodes,
+        "edges": edges,
+        "depends_on": depends_on,
+    })
+}
+
+/// Color group of a task status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tone {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl Tone {
+    const ALL: [Tone; 4] = [Tone::Pending, Tone::Running, Tone::Completed, Tone::Failed];
+
+    fn of(status: &str) -> Self {
+        match status {
+            "completed" => Tone::Completed,
+            "running" => Tone::Running,
+            "failed" | "timeout" | "policy_violation" | "cancelled" => Tone::Failed,
+            _ => Tone::Pending,
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Tone::Pending => "pending",
+            Tone::Running => "running",
+            Tone::Completed => "completed",
+            Tone::Failed => "failed",
+        }
+    }
+
+    /// Fill and border colors for Mermaid and dot
+    fn fill(self) -> (&'static str, &'static str) {
+        match self {
+            Tone::Pending => ("#eeeeee", "#999999"),
+            Tone::Running => ("#fff3cd", "#d39e00"),
+            Tone::Completed => ("#d4edda", "#28a745"),
+            Tone::Failed => ("#f8d7da", "#dc3545"),
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Tone::Pending => "\x1b[2m",
+            Tone::Running => "\x1b[33m",
+            Tone::Completed => "\x1b[32m",
+            Tone::Failed => "\x1b[31m",
+        }
+    }
+}
+
+/// The task a task takes its input from, if it is an earlier task of the plan
+///
+/// Anything else is drawn as a root, so a malformed plan still draws as a
+/// tree without cycles.
+fn parent(task: &PlanStep, tasks: &[&PlanStep]) -> Option<u32> {
+    task.input_from_task.filter(|&from| {
+        from < task.task_number && tasks.iter().any(|other| other.task_number == from)
+    })
+}
+
+/// Earlier tasks of the plan a task waits for without reading their output
+fn waits_for(task: &PlanStep, tasks: &[&PlanStep]) -> Vec<u32> {
+    task.depends_on
+        .iter()
+        .copied()
+        .filter(|&from| {
+            from < task.task_number
+                && task.input_from_task != Some(from)
+                && tasks.iter().any(|other| other.task_number == from)
+        })
+        .collect()
+}
+
+fn command_line(task: &PlanStep) -> String {
+    std::iter::once(task.command.as_str())
+        .chain(task.args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn mermaid(tasks: &[&PlanStep], statuses: &TaskStatuses) -> String {
+    let mut out = String::from("flowchart TD\n");
+    for task in tasks {
+        let label = format!("{}. {}", task.task_number, command_line(task)).replace('"', "#quot;");
+        let class = statuses
+            .get(&task.task_number)
+            .map(|status| format!(":::{}", Tone::of(status).class()))
+            .unwrap_or_default();
+        out.push_str(&format!("    t{}[\"{label}\"]{class}\n", task.task_number));
+    }
+    for task in tasks {
+        if let Some(from) = parent(task, tasks) {
+            out.push_str(&format!("    t{from} --> t{}\n", task.task_number));
+        }
+        for from in waits_for(task, tasks) {
+            out.push_str(&format!("    t{from} -.-> t{}\n", task.task_number));
+        }
+    }
+    if !statuses.is_empty() {
+        for tone in Tone::ALL {
+            let (fill, stroke) = tone.fill();
+            out.push_str(&format!(
+                "    classDef {} fill:{fill},stroke:{stroke}\n",
+                tone.class()
+            ));
+        }
+    }
+    out
+}
+
+fn dot(tasks: &[&PlanStep], statuses: &TaskStatuses) -> String {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut out = String::from("digraph plan {\n");
+    out.push_str("    node [shape=box, style=\"rounded,filled\", fillcolor=\"#ffffff\"];\n");
+    for task in tasks {
+        let mut label = escape(&format!("{}. {}", task.task_number, command_line(task)));
+        let mut colors = String::new();
+        if let Some(status) = statuses.get(&task.task_number) {
+            let (fill, stroke) = Tone::of(status).fill();
+            label.push_str(&format!("\\n{}", escape(status)));
+            colors = format!(", fillcolor=\"{fill}\", color=\"{stroke}\"");
+        }
+        out.push_str(&format!(
+            "    t{} [label=\"{label}\"{colors}];\n",
+            task.task_number
+        ));
+    }
+    for task in tasks {
+        if let Some(from) = parent(task, tasks) {
+            out.push_str(&format!("    t{from} -> t{};\n", task.task_number));
+        }
+        for from in waits_for(task, tasks) {
+            out.push_str(&format!(
+                "    t{from} -> t{} [style=dashed];\n",
+                task.task_number
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn ascii(tasks: &[&PlanStep], statuses: &TaskStatuses, color: bool) -> String {
+    let mut out = String::new();
+    for task in tasks.iter().filter(|task| parent(task, tasks).is_none()) {
+        ascii_node(task, tasks, statuses, color, "", None, &mut out);
+    }
+    out
+}
+
+/// Draw `task` and the tasks fed by it; `last` is whether it is the last
+/// child of its parent, or `None` for a root
+fn ascii_node(
+    task: &PlanStep,
+    tasks: &[&PlanStep],
+    statuses: &TaskStatuses,
+    color: bool,
+    prefix: &str,
+    last: Option<bool>,
+    out: &mut String,
+) {
+    let branch = match last {
+        None => "",
+        Some(true) => "└── ",
+        Some(false) => "├── ",
+    };
+    let mut line = format!("{}. {}", task.task_number, command_line(task));
+    let waits = waits_for(task, tasks);
+    if !waits.is_empty() {
+        line.push_str(&format!(" (after {})", task_list(&waits)));
+    }
+    if let Some(status) = statuses.get(&task.task_number) {
+        line.push_str(&format!(" [{status}]"));
+        if color {
+            line = format!("{}{line}\x1b[0m", Tone::of(status).ansi());
+        }
+    }
+    out.push_str(&format!("{prefix}{branch}{line}\n"));
+
+    let child_prefix = match last {
+        None => prefix.to_string(),
+        Some(true) => format!("{prefix}    "),
+        Some(false) => format!("{prefix}│   "),
+    };
+    let children: Vec<&PlanStep> = tasks
+        .iter()
+        .copied()
+        .filter(|child| parent(child, tasks) == Some(task.task_number))
+        .collect();
+    for (index, child) in children.iter().enumerate() {
+        let is_last = index + 1 == children.len();
+        ascii_node(
+            child,
+            tasks,
+            statuses,
+            color,
+            &child_prefix,
+            Some(is_last),
+            out,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(task_number: u32, command: &str, input_from_task: Option<u32>) -> PlanStep {
+        PlanStep {
+            task_number,
+            command: command.to_string(),
+            args: Vec::new(),
+            timeout_secs: 300,
+            input_from_task,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }
+    }
+
+    /// cat feeds both sort and wc; sort feeds uniq
+    fn branching_plan() -> WorkflowPlan {
+        let mut cat = step(1, "cat", None);
+        cat.args = vec!["data \"raw\".csv".to_string()];
+        WorkflowPlan {
+            plan_id: Some("plan-1".to_string()),
+            plan_description: None,
+            tasks: vec![
+                cat,
+                step(2, "sort", Some(1)),
+                step(3, "uniq", Some(2)),
+                step(4, "wc", Some(1)),
+            ],
+        }
+    }
+
+    fn statuses() -> TaskStatuses {
+        HashMap::from([
+            (1, "completed".to_string()),
+            (2, "failed".to_string()),
+            (3, "pending".to_string()),
+            (4, "running".to_string()),
+        ])
+    }
+
+    #[test]
+    fn ascii_tree_shows_branches() {
+        let tree = render(&branching_plan(), &statuses(), GraphFormat::Ascii, false);
+        assert_eq!(
+            tree,
+            "1. cat data \"raw\".csv [completed]\n\
+             ├── 2. sort [failed]\n\
+             │   └── 3. uniq [pending]\n\
+             └── 4. wc [running]\n"
+        );
+
+        let colored = render(&branching_plan(), &statuses(), GraphFormat::Ascii, true);
+        assert!(colored.contains("\x1b[31m2. sort [failed]\x1b[0m"));
+    }
+
+    #[test]
+    fn mermaid_and_dot_draw_edges_and_statuses() {
+        let mermaid = render(&branching_plan(), &statuses(), GraphFormat::Mermaid, false);
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("t1[\"1. cat data #quot;raw#quot;.csv\"]:::completed"));
+        assert!(mermaid.contains("t1 --> t4"));
+        assert!(mermaid.contains("classDef failed fill:#f8d7da"));
+
+        let dot = render(
+            &branching_plan(),
+            &TaskStatuses::new(),
+            GraphFormat::Dot,
+            false,
+        );
+        assert!(dot.contains("t1 [label=\"1. cat data \\\"raw\\\".csv\"];"));
+        assert!(dot.contains("t2 -> t3;"));
+        assert!(!dot.contains("#f8d7da"));
+
+        let json = to_json("plan-1", &branching_plan(), &statuses());
+        assert_eq!(json["edges"], json!([[1, 2], [2, 3], [1, 4]]));
+        assert_eq!(json["depends_on"], json!([]));
+        assert_eq!(json["tasks"][1]["status"], "failed");
+
+        assert!(GraphFormat::parse("DOT").is_ok());
+        assert!(GraphFormat::parse("svg").is_err());
+    }
+    #[test]
+    fn depends_on_is_drawn_dashed() {
+        let mut plan = branching_plan();
+        plan.tasks[3].depends_on = vec![1, 3];
+
+        let mermaid = render(&plan, &TaskStatuses::new(), GraphFormat::Mermaid, false);
+        assert!(mermaid.contains("t1 --> t4"));
+        assert!(mermaid.contains("t3 -.-> t4"));
+        assert!(!mermaid.contains("t1 -.-> t4"));
+        let dot = render(&plan, &TaskStatuses::new(), GraphFormat::Dot, false);
+        assert!(dot.contains("t3 -> t4 [style=dashed];"));
+        let tree = render(&plan, &TaskStatuses::new(), GraphFormat::Ascii, false);
+        assert!(tree.ends_with("└── 4. wc (after task 3)\n"));
+        assert_eq!(
+            to_json("plan-1", &plan, &statuses())["depends_on"],
+            json!([[3, 4]])
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Sentence embeddings with a BERT model run by Candle
+//!
+//! The model's `config.json`, `tokenizer.json` and `model.safetensors` are
+//! downloaded into the model cache the first time they are needed. A text's
+//! embedding is the mean of its token embeddings, normalized to unit length
+//! so that the dot product of two embeddings is their cosine similarity.
+
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use crate::models::ModelManager;
+use crate::planner::device::DeviceSelector;
+
+/// Model used when `AGX_EMBED_MODEL` is not set
+pub const DEFAULT_MODEL: &str = "sentence-transformers/all-MiniLM-L6-v2";
+
+/// Tokens of a text that are embedded; the rest is cut off
+const MAX_TOKENS: usize = 256;
+
+/// Texts embedded in one forward pass
+const BATCH_SIZE: usize = 16;
+
+/// Turns texts into vectors whose dot product measures how similar they are
+pub trait Embedder {
+    /// Name of the model, stored with the index so a change of model is noticed
+    fn model(&self) -> &str;
+
+    /// One unit-length vector per text
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String>;
+}
+
+pub struct BertEmbedder {
+    repo: String,
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl BertEmbedder {
+    /// Load `AGX_EMBED_MODEL`, or the default model, downloading it if needed
+    pub async fn from_env() -> Result<Self, String> {
+        let repo = std::env::var("AGX_EMBED_MODEL")
+            .ok()
+            .filter(|repo| !repo.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+        Self::load(&repo).await
+    }
+
+    pub async fn load(repo: &str) -> Result<Self, String> {
+        let manager = ModelManager::new().map_err(|e| format!("{e:#}"))?;
+        let mut files = Vec::new();
+        for file in ["config.json", "tokenizer.json", "model.safetensors"] {
+            let path = manager
+                .ensure_model(repo, file)
+                .await
+                .map_err(|e| format!("failed to get embedding model {repo}: {e:#}"))?;
+            files.push(path);
+        }
+
+        let config = std::fs::read_to_string(&files[0])
+            .map_err(|e| format!("failed to read {}: {}", files[0].display(), e))?;
+        let config: Config = serde_json::from_str(&config)
+            .map_err(|e| format!("invalid config for embedding model {repo}: {e}"))?;
+
+        let mut tokenizer = Tokenizer::from_file(&files[1])
+            .map_err(|e| format!("failed to load tokenizer for {repo}: {e}"))?;
+        tokenizer.with_padding(Some(PaddingParams::default()));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..TruncationParams::default()
+            }))
+            .map_err(|e| format!("failed to configure tokenizer for {repo}: {e}"))?;
+
+        let device = DeviceSelector::auto_select().map_err(|e| e.to_string())?;
+        // Safety: the weights are memory-mapped from the model cache and
+        // not modified while the model is loaded
+        let weights = unsafe { VarBuilder::from_mmaped_safetensors(&files[2..], DTYPE, &device) }
+            .map_err(|e| format!("failed to load embedding model {repo}: {e}"))?;
+        let model = BertModel::load(weights, &config)
+            .map_err(|e| format!("failed to load embedding model {repo}: {e}"))?;
+
+        Ok(Self {
+            repo: repo.to_string(),
+            model,
+            tokenizer,
+            device,
+        })
+    }
+
+    fn embed_batch(&self, texts: &[&str]) -> candle_core::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(candle_core::Error::msg)?;
+        let mut ids = Vec::with_capacity(encodings.len());
+        let mut masks = Vec::with_capacity(encodings.len());
+        for encoding in &encodings {
+            ids.push(Tensor::new(encoding.get_ids(), &self.device)?);
+            masks.push(Tensor::new(encoding.get_attention_mask(), &self.device)?);
+        }
+        let ids = Tensor::stack(&ids, 0)?;
+        let mask = Tensor::stack(&masks, 0)?;
+
+        let output = self.model.forward(&ids, &ids.zeros_like()?, Some(&mask))?;
+
+        // Mean of the token embeddings, leaving out the padding
+        let mask = mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let mean = output
+            .broadcast_mul(&mask)?
+            .sum(1)?
+            .broadcast_div(&mask.sum(1)?)?;
+        let norm = mean.sqr()?.sum_keepdim(1)?.sqrt()?;
+        mean.broadcast_div(&norm)?.to_vec2::<f32>()
+    }
+}
+
+impl Embedder for BertEmbedder {
+    fn model(&self) -> &str {
+        &self.repo
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(BATCH_SIZE) {
+            vectors.extend(
+                self.embed_batch(batch)
+                    .map_err(|e| format!("embedding failed: {e}"))?,
+            );
+        }
+        Ok(vectors)
+    }
+}
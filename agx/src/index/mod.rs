@@ -0,0 +1,463 @@
+//! Local documents as planning context
+//!
+//! `agx INDEX <dir>` splits the text files under a directory into chunks of
+//! lines, embeds each chunk (see [`embed`]), and stores them in one index at
+//! `~/.local/share/agenix/index.json`, or `AGX_INDEX_PATH`. Indexing a
+//! directory again only embeds the files that changed, and drops the ones
+//! that are gone. Planning with Echo or Delta embeds the instruction and
+//! passes the closest chunks to the planner as [`PlanContext::documents`],
+//! so a plan about "our deployment scripts" can use what they contain.
+//!
+//! [`PlanContext::documents`]: crate::planner::PlanContext::documents
+
+pub mod embed;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use embed::{BertEmbedder, Embedder};
+
+/// Lines per chunk
+const CHUNK_LINES: usize = 40;
+
+/// Lines a chunk shares with the one before it, so text at a boundary is
+/// whole in one of them
+const CHUNK_OVERLAP: usize = 8;
+
+/// Characters kept of a chunk
+const MAX_CHUNK_CHARS: usize = 2000;
+
+/// Files larger than this are not indexed
+const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+/// Directories never indexed, besides hidden ones
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// Chunks given to the planner
+const TOP_K: usize = 4;
+
+/// Least similarity for a chunk to be relevant
+const MIN_SCORE: f32 = 0.3;
+
+/// Characters of retrieved text given to the planner
+const MAX_CONTEXT_CHARS: usize = 6000;
+
+/// The embedded chunks of every indexed file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    /// Embedding model the vectors came from
+    pub model: String,
+    /// Indexed files by absolute path
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// Modification time in seconds, to notice changes
+    pub modified: u64,
+    pub size: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// First line, counting from 1
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+/// A chunk close to a query
+#[derive(Debug, Clone)]
+pub struct Hit<'a> {
+    pub path: &'a str,
+    pub chunk: &'a Chunk,
+    pub score: f32,
+}
+
+/// What indexing a directory did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateSummary {
+    /// Files embedded, new or changed
+    pub indexed: usize,
+    pub unchanged: usize,
+    /// Files gone from the directory
+    pub removed: usize,
+    /// Chunks in the whole index
+    pub chunks: usize,
+}
+
+/// Where the index is kept: `AGX_INDEX_PATH`, or
+/// `~/.local/share/agenix/index.json`
+pub fn default_path() -> Result<PathBuf, String> {
+    if let Some(path) = std::env::var_os("AGX_INDEX_PATH").filter(|path| !path.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    let home = dirs::home_dir().ok_or_else(|| "could not determine home directory".to_string())?;
+    Ok(home.join(".local/share/agenix/index.json"))
+}
+
+impl Index {
+    /// Load the index at `path`, if anything has been indexed
+    pub fn load_from(path: &Path) -> Result<Option<Self>, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("failed to read index {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| format!("failed to parse index {}: {}", path.display(), e))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+        }
+        let json =
+            serde_json::to_string(self).map_err(|e| format!("failed to serialize index: {}", e))?;
+
+        // Write then rename, so an interrupted save keeps the previous index
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json).map_err(|e| format!("failed to write index: {}", e))?;
+        fs::rename(&partial, path).map_err(|e| format!("failed to write index: {}", e))
+    }
+
+    /// Index the text files under `dir`, embedding those that are new or
+    /// changed
+    ///
+    /// Vectors from another model can't be compared with the embedder's,
+    /// so switching models starts the index over.
+    pub fn update(&mut self, dir: &Path, embedder: &dyn Embedder) -> Result<UpdateSummary, String> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("cannot index {}: {}", dir.display(), e))?;
+        if !dir.is_dir() {
+            return Err(format!("{} is not a directory", dir.display()));
+        }
+        if self.model != embedder.model() {
+            self.model = embedder.model().to_string();
+            self.files.clear();
+        }
+
+        let mut summary = UpdateSummary::default();
+        let mut found = HashSet::new();
+        for path in text_files(&dir)? {
+            let key = path.display().to_string();
+            let metadata = fs::metadata(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            found.insert(key.clone());
+
+            if self
+                .files
+                .get(&key)
+                .is_some_and(|file| file.modified == modified && file.size == metadata.len())
+            {
+                summary.unchanged += 1;
+                continue;
+            }
+
+            // Files that turn out not to be UTF-8 text are left out
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut chunks = chunk(&text);
+            let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+            let vectors = embedder.embed(&texts)?;
+            for (chunk, vector) in chunks.iter_mut().zip(vectors) {
+                chunk.vector = vector;
+            }
+            self.files.insert(
+                key,
+                IndexedFile {
+                    modified,
+                    size: metadata.len(),
+                    chunks,
+                },
+            );
+            summary.indexed += 1;
+        }
+
+        let prefix = format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR);
+        let before = self.files.len();
+        self.files
+            .retain(|path, _| !path.starts_with(&prefix) || found.contains(path));
+        summary.removed = before - self.files.len();
+        summary.chunks = self.files.values().map(|file| file.chunks.len()).sum();
+        Ok(summary)
+    }
+
+    /// The `k` chunks closest to the query vector, most similar first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<Hit<'_>> {
+        let mut hits: Vec<Hit<'_>> = self
+            .files
+            .iter()
+            .flat_map(|(path, file)| {
+                file.chunks.iter().map(move |chunk| Hit {
+                    path,
+                    chunk,
+                    score: dot(query, &chunk.vector),
+                })
+            })
+            .filter(|hit| hit.score >= MIN_SCORE)
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        hits
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// Files under `dir` that may be text, sorted, skipping hidden and build
+/// directories, symbolic links, and large files
+fn text_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                pending.push(entry.path());
+            } else if file_type.is_file()
+                && entry
+                    .metadata()
+                    .is_ok_and(|metadata| metadata.len() <= MAX_FILE_BYTES)
+            {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Overlapping chunks of lines of a text, without their vectors
+fn chunk(text: &str) -> Vec<Chunk> {
+    if text.contains('\0') {
+        return Vec::new();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text: String = lines[start..end]
+            .join("\n")
+            .chars()
+            .take(MAX_CHUNK_CHARS)
+            .collect();
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                start_line: start + 1,
+                end_line: end,
+                text,
+                vector: Vec::new(),
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+/// Retrieved chunks as planning context, within the size limit
+pub fn format_hits(hits: &[Hit<'_>]) -> Option<String> {
+    let mut context = String::new();
+    for hit in hits {
+        let snippet = format!(
+            "From {} (lines {}-{}):\n{}\n\n",
+            hit.path, hit.chunk.start_line, hit.chunk.end_line, hit.chunk.text
+        );
+        if context.len() + snippet.len() > MAX_CONTEXT_CHARS {
+            break;
+        }
+        context.push_str(&snippet);
+    }
+    let context = context.trim_end();
+    (!context.is_empty()).then(|| context.to_string())
+}
+
+/// Chunks of the indexed documents relevant to `query`, for the planner
+///
+/// Returns `None` when nothing has been indexed or nothing is close enough.
+/// Retrieval only adds context, so failures are logged rather than
+/// stopping the plan.
+pub async fn retrieve(query: &str) -> Option<String> {
+    match try_retrieve(query).await {
+        Ok(documents) => documents,
+        Err(error) => {
+            crate::logging::info(&format!("Document retrieval skipped: {}", error));
+            None
+        }
+    }
+}
+
+async fn try_retrieve(query: &str) -> Result<Option<String>, String> {
+    let Some(index) = Index::load_from(&default_path()?)? else {
+        return Ok(None);
+    };
+    if index.files.is_empty() {
+        return Ok(None);
+    }
+    let embedder = BertEmbedder::load(&index.model).await?;
+    let query = embedder
+        .embed(&[query])?
+        .pop()
+        .ok_or_else(|| "no embedding for the query".to_string())?;
+    Ok(format_hits(&index.search(&query, TOP_K)))
+}
+
+/// `agx INDEX <dir>`: index a directory and report what changed
+pub async fn run(dir: &Path) -> anyhow::Result<()> {
+    let path = default_path().map_err(anyhow::Error::msg)?;
+    let mut index = Index::load_from(&path)
+        .map_err(anyhow::Error::msg)?
+        .unwrap_or_default();
+
+    eprintln!("Loading embedding model...");
+    let embedder = BertEmbedder::from_env().await.map_err(anyhow::Error::msg)?;
+    eprintln!("Indexing {}...", dir.display());
+    let summary = index.update(dir, &embedder).map_err(anyhow::Error::msg)?;
+    index.save_to(&path).map_err(anyhow::Error::msg)?;
+
+    println!(
+        "Indexed {} files ({} unchanged, {} removed); {} chunks from {} files in {}",
+        summary.indexed,
+        summary.unchanged,
+        summary.removed,
+        summary.chunks,
+        index.files.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Embeds texts by which of a few words they contain
+    struct Words {
+        embedded: Cell<usize>,
+    }
+
+    const WORDS: [&str; 3] = ["deploy", "backup", "invoice"];
+
+    impl Embedder for Words {
+        fn model(&self) -> &str {
+            "words"
+        }
+
+        fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String> {
+            self.embedded.set(self.embedded.get() + texts.len());
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let vector: Vec<f32> = WORDS
+                        .iter()
+                        .map(|word| text.matches(word).count() as f32)
+                        .collect();
+                    let norm = dot(&vector, &vector).sqrt().max(1.0);
+                    vector.iter().map(|value| value / norm).collect()
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn chunks_overlap_and_skip_blank_text() {
+        let text: Vec<String> = (1..=100).map(|n| format!("line {n}")).collect();
+        let chunks = chunk(&text.join("\n"));
+        let ranges: Vec<(usize, usize)> =
+            chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(ranges, [(1, 40), (33, 72), (65, 100)]);
+        assert!(chunks[1].text.starts_with("line 33\n"));
+
+        assert!(chunk("\n\n  \n").is_empty());
+        assert!(chunk("binary\0data").is_empty());
+    }
+
+    #[test]
+    fn updates_embed_only_changed_files_and_search_finds_them() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("scripts")).unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(
+            dir.path().join("scripts/deploy.sh"),
+            "# deploy the app\ndeploy --prod\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("backup.md"),
+            "Nightly backup of the database",
+        )
+        .unwrap();
+        fs::write(dir.path().join(".git/config"), "deploy deploy").unwrap();
+
+        let embedder = Words {
+            embedded: Cell::new(0),
+        };
+        let mut index = Index::default();
+        let summary = index.update(dir.path(), &embedder).unwrap();
+        assert_eq!((summary.indexed, summary.chunks), (2, 2));
+        assert_eq!(embedder.embedded.get(), 2);
+
+        let query = embedder.embed(&["how do we deploy"]).unwrap().remove(0);
+        let hits = index.search(&query, 4);
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].path.ends_with("scripts/deploy.sh"));
+        let context = format_hits(&hits).unwrap();
+        assert!(context.starts_with("From "));
+        assert!(context.contains("(lines 1-2):\n# deploy the app"));
+
+        // Saved and loaded, then updated after a file is removed
+        let state = tempfile::tempdir().unwrap();
+        let path = state.path().join("agenix/index.json");
+        index.save_to(&path).unwrap();
+        let mut index = Index::load_from(&path).unwrap().unwrap();
+        fs::remove_file(dir.path().join("backup.md")).unwrap();
+        let summary = index.update(dir.path(), &embedder).unwrap();
+        assert_eq!(
+            summary,
+            UpdateSummary {
+                indexed: 0,
+                unchanged: 1,
+                removed: 1,
+                chunks: 1,
+            }
+        );
+        assert_eq!(embedder.embedded.get(), 3);
+
+        let unrelated = embedder.embed(&["invoice"]).unwrap().remove(0);
+        assert!(index.search(&unrelated, 4).is_empty());
+        assert!(Index::load_from(&dir.path().join("missing.json"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -4,6 +4,9 @@ use crate::plan::WorkflowPlan;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JobEnvelope {
+    /// Version of the Plan schema the tasks follow
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     pub job_id: String,
     pub plan_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -21,18 +24,30 @@ pub struct JobTask {
     pub timeout_secs: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_from_task: Option<u32>,
+    /// Earlier tasks that must finish first, without their output being
+    /// piped to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<u32>,
+    /// Worker tags AGQ routes the task's jobs by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 fn default_timeout() -> u32 {
     300
 }
 
+fn default_schema_version() -> u32 {
+    agenix_plan::SCHEMA_VERSION
+}
+
 #[derive(Debug)]
 pub enum EnvelopeValidationError {
     EmptyTasks,
     TooManyTasks(usize),
     NonMonotonicTasks,
     BadInputReference(u32),
+    BadDependency(u32),
     FirstTaskNotOne(u32),
 }
 
@@ -49,6 +64,9 @@ impl std::fmt::Display for EnvelopeValidationError {
             EnvelopeValidationError::BadInputReference(task) => {
                 write!(f, "input_from_task references invalid task {task}")
             }
+            EnvelopeValidationError::BadDependency(task) => {
+                write!(f, "depends_on references invalid task {task}")
+            }
             EnvelopeValidationError::FirstTaskNotOne(n) => {
                 write!(f, "first task number must be 1 (found {n})")
             }
@@ -78,10 +96,13 @@ impl JobEnvelope {
                 args: task.args,
                 timeout_secs: task.timeout_secs,
                 input_from_task: task.input_from_task,
+                depends_on: task.depends_on,
+                tags: task.tags,
             })
             .collect();
 
         Self {
+            schema_version: agenix_plan::SCHEMA_VERSION,
             job_id,
             plan_id,
             plan_description,
@@ -118,6 +139,13 @@ impl JobEnvelope {
                     return Err(EnvelopeValidationError::BadInputReference(ref_id));
                 }
             }
+            if let Some(&ref_id) = task
+                .depends_on
+                .iter()
+                .find(|&&ref_id| ref_id >= task.task_number || !seen.contains(&ref_id))
+            {
+                return Err(EnvelopeValidationError::BadDependency(ref_id));
+            }
         }
 
         Ok(())
@@ -141,6 +169,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
                 },
                 PlanStep {
                     task_number: 2,
@@ -148,6 +179,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 30,
                     input_from_task: Some(1),
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
                 },
             ],
         };
@@ -164,6 +198,7 @@ mod tests {
     #[test]
     fn validates_monotonic_tasks() {
         let env = JobEnvelope {
+            schema_version: agenix_plan::SCHEMA_VERSION,
             job_id: "job".into(),
             plan_id: "plan".into(),
             plan_description: None,
@@ -174,6 +209,8 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: vec![],
+                    tags: vec![],
                 },
                 JobTask {
                     task_number: 3,
@@ -181,6 +218,8 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: vec![],
+                    tags: vec![],
                 },
             ],
         };
@@ -192,6 +231,7 @@ mod tests {
     #[test]
     fn rejects_invalid_input_refs() {
         let env = JobEnvelope {
+            schema_version: agenix_plan::SCHEMA_VERSION,
             job_id: "job".into(),
             plan_id: "plan".into(),
             plan_description: None,
@@ -202,6 +242,8 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: vec![],
+                    tags: vec![],
                 },
                 JobTask {
                     task_number: 2,
@@ -209,11 +251,23 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: Some(5),
+                    depends_on: vec![],
+                    tags: vec![],
                 },
             ],
         };
 
         let err = env.validate(10).unwrap_err();
         matches!(err, EnvelopeValidationError::BadInputReference(_));
+
+        let mut env = env;
+        env.tasks[1].input_from_task = None;
+        env.tasks[1].depends_on = vec![1, 2];
+        assert_eq!(
+            env.validate(10).unwrap_err().to_string(),
+            "depends_on references invalid task 2"
+        );
+        env.tasks[1].depends_on = vec![1];
+        assert!(env.validate(10).is_ok());
     }
 }
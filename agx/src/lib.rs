@@ -1,18 +1,29 @@
 pub mod agq_client;
 pub mod cli;
+pub mod context_file;
+pub mod diff;
+pub mod dry_run;
+pub mod estimate;
 pub mod executor;
+pub mod graph;
+pub mod index;
 pub mod input;
 pub mod job;
 pub mod logging;
 pub mod plan;
+pub mod pipeline;
 pub mod plan_buffer;
 pub mod planner;
+pub mod policy;
 pub mod registry;
+pub mod repair;
 pub mod repl;
+pub mod review;
 pub mod echo;
 pub mod delta;
 pub mod models;
-pub mod client;
+pub mod ops;
+pub mod watch;
 
 use anyhow::Result;
 use serde_json::json;
@@ -45,19 +56,95 @@ pub async fn run() -> Result<()> {
         logging::info("debug logging enabled");
     }
 
+    // Scripts reading JSON get the error as JSON too
+    let json_output = command.json();
+    let result = dispatch(command).await;
+    if let Err(error) = &result {
+        if json_output && error.downcast_ref::<ReportedInJson>().is_none() {
+            println!("{}", json!({"status": "error", "error": format!("{error:#}")}));
+        }
+    }
+    result
+}
+
+/// A failure whose details the command already printed as JSON
+#[derive(Debug)]
+pub(crate) struct ReportedInJson(pub(crate) String);
+
+impl std::fmt::Display for ReportedInJson {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReportedInJson {}
+
+async fn dispatch(command: cli::Command) -> Result<()> {
     match command {
         cli::Command::Repl => handle_repl().await.map_err(|e| anyhow::anyhow!(e)),
-        cli::Command::Chat => echo::run().await,
-        cli::Command::Run { goal } => delta::run(goal).await,
+        cli::Command::Chat { context_files } => echo::run(context_files).await,
+        cli::Command::Run {
+            goal,
+            yes,
+            dry_run,
+            execute,
+            context_files,
+            json,
+            follow,
+        } => {
+            if follow && !dry_run {
+                pipeline::run(&pipeline::RunOptions {
+                    goal,
+                    yes,
+                    json,
+                    context_files,
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!(e))
+            } else {
+                delta::run(goal, yes, dry_run, execute, &context_files, json).await
+            }
+        }
         cli::Command::Plan(plan_command) => handle_plan_command(plan_command).map_err(|e| anyhow::anyhow!(e)),
         cli::Command::Action(action_command) => handle_action_command(action_command).map_err(|e| anyhow::anyhow!(e)),
         cli::Command::Ops(ops_command) => handle_ops_command(ops_command).map_err(|e| anyhow::anyhow!(e)),
+        cli::Command::Watch {
+            plan_id,
+            action_id,
+            interval_secs,
+            json,
+            repair,
+        } => {
+            let client = agq_client::AgqClient::new(agq_client::AgqConfig::from_env());
+            let options = watch::WatchOptions {
+                plan_id,
+                action_id,
+                interval: std::time::Duration::from_secs(interval_secs),
+                json,
+            };
+            match repair {
+                Some(max_repairs) => repair::run(&client, &options, max_repairs).await,
+                None => watch::run(&client, &options),
+            }
+            .map_err(|e| anyhow::anyhow!(e))
+        }
+        cli::Command::Models(models_command) => handle_models_command(models_command).await,
+        cli::Command::Index { dir } => index::run(&dir).await,
+        #[cfg(unix)]
+        cli::Command::ServePlanner { roles } => {
+            planner::daemon::serve(&roles).await.map_err(|e| anyhow::anyhow!(e))
+        }
+        #[cfg(not(unix))]
+        cli::Command::ServePlanner { .. } => Err(anyhow::anyhow!(
+            "SERVE-PLANNER serves over Unix sockets and is not available on this platform; \
+             Echo and Delta load their models in-process"
+        )),
     }
 }
 
 async fn handle_repl() -> Result<(), String> {
     // Create backend for Echo model (interactive planning)
-    let config = planner::PlannerConfig::from_env();
+    let config = planner::PlannerConfig::load()?;
 
     // Create backend asynchronously (reuses existing tokio runtime from main)
     let backend: Box<dyn planner::ModelBackend> = match config.backend {
@@ -68,15 +155,20 @@ async fn handle_repl() -> Result<(), String> {
         }
         planner::BackendKind::Candle => {
             // Force Echo role for REPL
-            let role = planner::ModelRole::Echo;
-            let candle_config = planner::CandleConfig::from_env(role)
-                .map_err(|e| format!("failed to load Candle config: {}", e))?;
-
-            let backend = planner::CandleBackend::new(candle_config).await
+            let (backend, _) = config.candle_planner(planner::ModelRole::Echo).await
                 .map_err(|e| format!("failed to initialize Candle backend: {}", e))?;
 
-            Box::new(backend)
+            backend
         }
+        planner::BackendKind::Anthropic => Box::new(planner::AnthropicBackend::from_config(
+            planner::AnthropicConfig::default(),
+        )),
+        planner::BackendKind::Gemini => Box::new(planner::GeminiBackend::from_config(
+            planner::GeminiConfig::default(),
+        )),
+        planner::BackendKind::OpenAI => Box::new(planner::OpenAIBackend::from_config(
+            planner::OpenAIConfig::default(),
+        )),
     };
 
     // Create and run REPL
@@ -124,6 +216,7 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
                 "original_tasks": original_steps,
                 "validated_tasks": validated_steps,
                 "changes": diff_summary,
+                "diff": diff::diff(&plan.tasks, &validated_plan.tasks).to_json(),
                 "plan_path": storage.path().display().to_string()
             }));
         }
@@ -134,7 +227,7 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
                 "plan": plan
             }));
         }
-        cli::PlanCommand::Submit { json } => {
+        cli::PlanCommand::Submit { json, yes } => {
             let mut plan = storage.load()?;
 
             logging::info(&format!(
@@ -150,8 +243,22 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
                     "Auto-validation complete: {} task(s)",
                     plan.tasks.len()
                 ));
+
+                // Delta's output is not submitted until someone has approved it
+                if !yes {
+                    match review_plan(plan)? {
+                        Some(approved) => {
+                            storage.save(&approved)?;
+                            plan = approved;
+                        }
+                        None => {
+                            return Err("plan rejected; nothing was submitted".to_string());
+                        }
+                    }
+                }
             }
 
+            storage.check_schema()?;
             let job = build_job_envelope(plan)?;
             let plan_id = job.plan_id.clone();
             let task_count = job.tasks.len();
@@ -200,13 +307,13 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
                 instruction, input.bytes, input.lines, input.is_probably_binary
             ));
 
-            let registry = registry::ToolRegistry::new();
+            let registry = registry::ToolRegistry::load()?;
             logging::info(&format!(
                 "available tools: {}",
                 registry.describe_for_planner()
             ));
 
-            let planner_config = planner::PlannerConfig::from_env();
+            let planner_config = planner::PlannerConfig::load()?;
             let planner = planner::Planner::new(planner_config);
 
             let plan_output = planner.plan(&instruction, &input, &registry)?;
@@ -220,17 +327,21 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
             let offset = buffer.tasks.len() as u32;
             buffer.tasks.extend(executable_plan.tasks.into_iter());
 
-            // Renumber newly added tasks by offset and adjust their input_from_task references
+            // Renumber newly added tasks by offset and adjust their input_from_task
+            // and depends_on references
             // Existing tasks keep their numbers unchanged
             if offset > 0 {
                 for task in buffer.tasks.iter_mut().skip(offset as usize) {
                     let old_number = task.task_number;
                     task.task_number = old_number + offset;
 
-                    // Adjust input_from_task references within newly added tasks
+                    // Adjust references within newly added tasks
                     if let Some(old_ref) = task.input_from_task {
                         task.input_from_task = Some(old_ref + offset);
                     }
+                    for old_ref in &mut task.depends_on {
+                        *old_ref += offset;
+                    }
                 }
             }
 
@@ -298,11 +409,97 @@ fn handle_plan_command(command: cli::PlanCommand) -> Result<(), String> {
                 }
             }
         }
+        cli::PlanCommand::Diff { from, to, json } => {
+            let agq_config = agq_client::AgqConfig::from_env();
+            let client = agq_client::AgqClient::new(agq_config);
+
+            let get = |plan_id: &str| {
+                client
+                    .get_plan(plan_id)
+                    .map_err(|e| format!("failed to get plan {}: {}", plan_id, e))
+            };
+            let plan_diff = diff::diff(&get(&from)?.tasks, &get(&to)?.tasks);
+
+            if json {
+                let mut output = plan_diff.to_json();
+                output["from"] = json!(from);
+                output["to"] = json!(to);
+                print_json(output);
+                return Ok(());
+            }
+            println!("{} → {}: {}", from, to, plan_diff.render());
+        }
+        cli::PlanCommand::Show {
+            plan_id,
+            action_id,
+            format,
+            json,
+        } => {
+            let agq_config = agq_client::AgqConfig::from_env();
+            let client = agq_client::AgqClient::new(agq_config);
+
+            let plan = client
+                .get_plan(&plan_id)
+                .map_err(|e| format!("failed to get plan: {}", e))?;
+            // A Plan that has not run yet is drawn without statuses
+            let statuses = match client.plan_status(&plan_id, action_id.as_deref()) {
+                Ok(status) => status
+                    .tasks
+                    .into_iter()
+                    .map(|task| (task.task_number, task.status))
+                    .collect(),
+                Err(e) if action_id.is_none() && e.contains("No actions found") => {
+                    graph::TaskStatuses::new()
+                }
+                Err(e) => return Err(format!("failed to get plan status: {}", e)),
+            };
+
+            if json {
+                print_json(graph::to_json(&plan_id, &plan, &statuses));
+                return Ok(());
+            }
+            let color = std::io::IsTerminal::is_terminal(&std::io::stdout());
+            print!("{}", graph::render(&plan, &statuses, format, color));
+        }
     }
 
     Ok(())
 }
 
+/// Show a generated plan for approval on the terminal
+///
+/// Returns `None` if it was rejected.
+pub fn review_plan(plan: plan::WorkflowPlan) -> Result<Option<plan::WorkflowPlan>, String> {
+    if !input::InputCollector::stdin_is_terminal() {
+        return Err(
+            "a generated plan must be reviewed before submission, which needs a terminal; pass --yes to submit it unreviewed"
+                .to_string(),
+        );
+    }
+
+    let estimator = estimator_for(&plan);
+    let policy = policy::Policy::load()?;
+    let stdin = std::io::stdin();
+    review::review(
+        plan,
+        &estimator,
+        &policy,
+        &mut stdin.lock(),
+        &mut std::io::stdout(),
+    )
+}
+
+/// Tool history for estimating the plan under review
+///
+/// Review goes on without an estimate if AGQ can't be asked.
+pub fn estimator_for(plan: &plan::WorkflowPlan) -> estimate::Estimator {
+    let client = agq_client::AgqClient::new(agq_client::AgqConfig::from_env());
+    estimate::Estimator::fetch(&client, plan).unwrap_or_else(|error| {
+        logging::info(&format!("No plan estimate: {}", error));
+        estimate::Estimator::default()
+    })
+}
+
 fn collect_planner_input() -> Result<input::InputSummary, String> {
     if input::InputCollector::stdin_is_terminal() {
         return Ok(input::InputSummary::empty());
@@ -385,7 +582,7 @@ fn run_delta_validation(
     let planner = planner::Planner::new(delta_config);
 
     // Get tool registry
-    let registry = registry::ToolRegistry::new();
+    let registry = registry::ToolRegistry::load()?;
 
     // Run Delta validation with existing plan as context
     let input = input::InputSummary::empty();
@@ -400,7 +597,9 @@ fn run_delta_validation(
     logging::info(&format!("Delta validation output: {}", plan_output.raw_json));
 
     let parsed = plan_output.parse()?;
-    let validated_plan = parsed.normalize_for_execution();
+    let validated_plan = parsed
+        .normalize_for_execution()
+        .with_required_tags(&registry);
 
     // Save validated plan to buffer
     storage.save(&validated_plan)?;
@@ -414,6 +613,14 @@ fn run_delta_validation(
 }
 
 pub fn build_job_envelope(plan: plan::WorkflowPlan) -> Result<job::JobEnvelope, String> {
+    // Hand-written and older plans may lack the tags AGQ routes by
+    let plan = plan.with_required_tags(&registry::ToolRegistry::load()?);
+    plan.check_schema()?;
+    let violations = policy::Policy::load()?.check(&plan);
+    if !violations.is_empty() {
+        return Err(policy::violation_message(&violations));
+    }
+
     let job_id = uuid::Uuid::new_v4().to_string();
     let plan_id = uuid::Uuid::new_v4().to_string();
     let plan_description = std::env::var("AGX_PLAN_DESCRIPTION").ok();
@@ -431,6 +638,31 @@ pub fn build_job_envelope(plan: plan::WorkflowPlan) -> Result<job::JobEnvelope,
     Ok(envelope)
 }
 
+/// Submit a plan to AGQ and run it once with no inputs
+///
+/// Returns the Plan ID and the Action ID of the run.
+pub fn submit_and_run(
+    client: &agq_client::AgqClient,
+    plan: plan::WorkflowPlan,
+) -> Result<(String, String), String> {
+    let job = build_job_envelope(plan)?;
+    let job_json = serde_json::to_string(&job)
+        .map_err(|error| format!("failed to serialize job for submission: {error}"))?;
+    client
+        .submit_plan(&job_json)
+        .map_err(|error| format!("PLAN submit failed: {error}"))?;
+
+    let action = json!({
+        "action_id": format!("action_{}", uuid::Uuid::new_v4().simple()),
+        "plan_id": job.plan_id,
+        "inputs": [],
+    });
+    let envelope = client
+        .submit_action(&action.to_string())
+        .map_err(|error| format!("ACTION submit failed: {error}"))?;
+    Ok((envelope.plan_id, envelope.action_id))
+}
+
 /// Validate file path to prevent path traversal attacks
 /// Rejects absolute paths, parent directory references, and symlinks
 fn validate_file_path(path: &str) -> Result<(), String> {
@@ -594,35 +826,76 @@ fn handle_ops_command(command: cli::OpsCommand) -> Result<(), String> {
     let agq_config = agq_client::AgqConfig::from_env();
     let client = agq_client::AgqClient::new(agq_config);
 
-    let (resp, json_output) = match command {
-        cli::OpsCommand::Jobs { json } => (client.list_jobs()?, json),
-        cli::OpsCommand::Workers { json } => (client.list_workers()?, json),
-        cli::OpsCommand::Queue { json } => (client.queue_stats()?, json),
-    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
 
-    if json_output {
-        print_json(match resp {
-            agq_client::OpsResponse::Jobs(items)
-            | agq_client::OpsResponse::Workers(items)
-            | agq_client::OpsResponse::QueueStats(items) => json!({"status": "ok", "items": items}),
-        });
-        return Ok(());
-    }
-
-    match resp {
-        agq_client::OpsResponse::Jobs(items) => {
-            println!("JOBS:");
-            for item in items {
-                println!("- {item}");
+    match command {
+        cli::OpsCommand::Jobs { filter, json } => {
+            let page = client.job_list(&filter)?;
+            if json {
+                print_json(json!({"status": "ok", "total": page.total, "items": page.jobs}));
+                return Ok(());
+            }
+            if page.jobs.is_empty() {
+                println!("No jobs found");
+                return Ok(());
+            }
+            println!("{}", ops::jobs_table(&page.jobs, now));
+            if page.total > page.jobs.len() as u64 {
+                println!("({} of {} jobs; use --limit to see more)", page.jobs.len(), page.total);
             }
         }
-        agq_client::OpsResponse::Workers(items) => {
-            println!("WORKERS:");
-            for item in items {
-                println!("- {item}");
+        cli::OpsCommand::Workers { json } => {
+            let workers = client.workers()?;
+            if json {
+                print_json(json!({"status": "ok", "items": workers}));
+                return Ok(());
+            }
+            if workers.is_empty() {
+                println!("No live workers");
+                return Ok(());
+            }
+            println!("{}", ops::workers_table(&workers, now));
+        }
+        cli::OpsCommand::Cancel { target, json } => {
+            let (kind, id, cancelled) = match &target {
+                cli::CancelTarget::Plan(plan_id) => ("plan", plan_id, client.cancel_plan(plan_id)?),
+                cli::CancelTarget::Job(job_id) => ("job", job_id, client.cancel_job(job_id)?),
+            };
+            if json {
+                print_json(json!({"status": "ok", kind: id, "cancelled": cancelled}));
+                return Ok(());
+            }
+            match (&target, cancelled) {
+                (cli::CancelTarget::Job(job_id), 0) => {
+                    println!("Job {} had already finished; nothing was cancelled", job_id)
+                }
+                _ => println!("Cancelled {} job(s) of {} {}", cancelled, kind, id),
+            }
+        }
+        cli::OpsCommand::Purge { target, json } => {
+            let purged = match &target {
+                cli::PurgeTarget::Job(job_id) => client.purge_job(job_id)?,
+                cli::PurgeTarget::OlderThan(secs) => client.purge_older_than(*secs)?,
+            };
+            if json {
+                print_json(json!({"status": "ok", "purged": purged}));
+                return Ok(());
+            }
+            match target {
+                cli::PurgeTarget::Job(job_id) if purged == 0 => println!("No job {}", job_id),
+                _ => println!("Purged {} job(s)", purged),
             }
         }
-        agq_client::OpsResponse::QueueStats(items) => {
+        cli::OpsCommand::Queue { json } => {
+            let agq_client::OpsResponse::QueueStats(items) = client.queue_stats()? else {
+                return Err("unexpected QUEUE.STATS response".to_string());
+            };
+            if json {
+                print_json(json!({"status": "ok", "items": items}));
+                return Ok(());
+            }
             println!("QUEUE:");
             for item in items {
                 println!("- {item}");
@@ -633,6 +906,123 @@ fn handle_ops_command(command: cli::OpsCommand) -> Result<(), String> {
     Ok(())
 }
 
+async fn handle_models_command(command: cli::ModelsCommand) -> anyhow::Result<()> {
+    let manager = models::ModelManager::new()?;
+    let cached = models::cache::list_in(manager.cache_dir())?;
+
+    match command {
+        cli::ModelsCommand::List { json } => {
+            if json {
+                let items: Vec<_> = cached
+                    .iter()
+                    .map(|model| {
+                        json!({
+                            "repo": model.repo,
+                            "path": model.path,
+                            "commit": model.commit,
+                            "size": model.size,
+                            "incomplete": model.incomplete,
+                            "files": model.files.iter().map(|file| json!({
+                                "name": file.name,
+                                "size": file.size,
+                            })).collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect();
+                print_json(json!({"status": "ok", "items": items}));
+                return Ok(());
+            }
+            if cached.is_empty() {
+                println!("No models cached in {}", manager.cache_dir().display());
+                return Ok(());
+            }
+            println!("MODELS ({}) in {}:", cached.len(), manager.cache_dir().display());
+            for model in &cached {
+                let mut line = format!("- {} | {}", model.repo, models::cache::format_size(model.size));
+                if model.incomplete > 0 {
+                    line.push_str(&format!(
+                        " ({} in unfinished downloads)",
+                        models::cache::format_size(model.incomplete)
+                    ));
+                }
+                println!("{line}");
+                for file in &model.files {
+                    println!("    {} ({})", file.name, models::cache::format_size(file.size));
+                }
+            }
+        }
+        cli::ModelsCommand::Remove { repo, json } => {
+            let freed = models::cache::remove_in(manager.cache_dir(), &repo)?;
+            if json {
+                print_json(json!({"status": "ok", "repo": repo, "freed": freed}));
+                return Ok(());
+            }
+            println!("Removed {} ({} freed)", repo, models::cache::format_size(freed));
+        }
+        cli::ModelsCommand::Verify { repo, json } => {
+            let selected: Vec<_> = match &repo {
+                Some(repo) => {
+                    let model = cached
+                        .iter()
+                        .find(|model| &model.repo == repo)
+                        .ok_or_else(|| anyhow::anyhow!("No cached model named {}", repo))?;
+                    vec![model]
+                }
+                None => cached.iter().collect(),
+            };
+            if selected.is_empty() && !json {
+                println!("No models cached in {}", manager.cache_dir().display());
+                return Ok(());
+            }
+
+            let mut mismatches = 0;
+            let mut items = Vec::new();
+            for model in selected {
+                if !json {
+                    println!("{}:", model.repo);
+                }
+                let mut files = Vec::new();
+                for (name, check) in manager.verify(model).await? {
+                    let (result, reason) = match check {
+                        models::FileCheck::Verified => ("ok", None),
+                        models::FileCheck::NotInRepo => ("skipped", Some("not in the repository".to_string())),
+                        models::FileCheck::Mismatch(reason) => {
+                            mismatches += 1;
+                            ("mismatch", Some(reason))
+                        }
+                    };
+                    if !json {
+                        match &reason {
+                            Some(reason) if result == "mismatch" => println!("    MISMATCH  {name}: {reason}"),
+                            Some(reason) => println!("    {:<9} {name} ({reason})", result),
+                            None => println!("    {:<9} {name}", result),
+                        }
+                    }
+                    files.push(json!({"name": name, "result": result, "reason": reason}));
+                }
+                items.push(json!({"repo": model.repo, "commit": model.commit, "files": files}));
+            }
+
+            let error = (mismatches > 0).then(|| format!(
+                "{} cached file(s) do not match Hugging Face; remove the model and download it again",
+                mismatches
+            ));
+            if json {
+                print_json(json!({
+                    "status": if error.is_some() { "error" } else { "ok" },
+                    "error": error,
+                    "items": items,
+                }));
+            }
+            if let Some(error) = error {
+                return Err(ReportedInJson(error).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn print_json(value: serde_json::Value) {
     match serde_json::to_string_pretty(&value) {
         Ok(json_text) => println!("{json_text}"),
@@ -667,6 +1057,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
                 },
                 plan::PlanStep {
                     task_number: 2,
@@ -674,6 +1067,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: Some(1),
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
                 },
             ],
         };
@@ -700,6 +1096,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
                 },
                 plan::PlanStep {
                     task_number: 2,
@@ -707,6 +1106,9 @@ mod tests {
                     args: vec![],
                     timeout_secs: 300,
                     input_from_task: Some(1), // Depends on task 1
+                    tags: Vec::new(),
+                    depends_on: Vec::new(),
+                    limits: Default::default(),
                 },
             ],
         };
@@ -721,6 +1123,9 @@ mod tests {
                 args: vec![],
                 timeout_secs: 300,
                 input_from_task: None,
+                depends_on: Vec::new(),
+                tags: Vec::new(),
+                limits: Default::default(),
             }],
         };
 
@@ -0,0 +1,364 @@
+//! What is in the model cache
+//!
+//! Models are cached in the Hugging Face hub layout, in the `hub` directory
+//! of `HF_HOME` or `~/.cache/huggingface`. Each repository has a
+//! `models--<org>--<name>` directory holding content-addressed `blobs`, and
+//! `snapshots/<commit>` linking each file name to its blob. `refs/main` names the commit in use.
+//! Downloads in progress are blobs ending in `.incomplete`.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::{FileCheck, RemoteFile};
+
+/// Suffix of a blob still being downloaded
+pub const INCOMPLETE_SUFFIX: &str = ".incomplete";
+
+/// A cached model repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedModel {
+    /// Repository ID, e.g. `Qwen/Qwen2.5-1.5B-Instruct-GGUF`
+    pub repo: String,
+    pub path: PathBuf,
+    /// Commit of the files in use, from `refs/main`
+    pub commit: Option<String>,
+    /// Files of that commit
+    pub files: Vec<CachedFile>,
+    /// Bytes used on disk, including other revisions and partial downloads
+    pub size: u64,
+    /// Bytes of downloads that have not finished
+    pub incomplete: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Cached model repositories in `cache_dir`, sorted by repository ID
+pub fn list_in(cache_dir: &Path) -> Result<Vec<CachedModel>> {
+    let entries = match fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).context(format!(
+                "Failed to read model cache {}",
+                cache_dir.display()
+            ))
+        }
+    };
+
+    let mut models = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(repo) = repo_id(&name) else {
+            continue;
+        };
+        models.push(read_model(repo, entry.path())?);
+    }
+    models.sort_by(|a, b| a.repo.cmp(&b.repo));
+    Ok(models)
+}
+
+/// Delete a cached repository, returning the bytes freed
+pub fn remove_in(cache_dir: &Path, repo: &str) -> Result<u64> {
+    let model = list_in(cache_dir)?
+        .into_iter()
+        .find(|model| model.repo == repo)
+        .ok_or_else(|| anyhow::anyhow!("No cached model named {}", repo))?;
+    fs::remove_dir_all(&model.path)
+        .context(format!("Failed to remove {}", model.path.display()))?;
+    Ok(model.size)
+}
+
+/// Directory of a repository in the cache
+pub fn repo_dir(cache_dir: &Path, repo: &str) -> PathBuf {
+    cache_dir.join(format!("models--{}", repo.replace('/', "--")))
+}
+
+/// `Org/name` from a `models--Org--name` directory name
+fn repo_id(dir_name: &str) -> Option<String> {
+    let rest = dir_name.strip_prefix("models--")?;
+    let (org, name) = rest.split_once("--")?;
+    Some(format!("{}/{}", org, name))
+}
+
+fn read_model(repo: String, path: PathBuf) -> Result<CachedModel> {
+    let commit = fs::read_to_string(path.join("refs/main"))
+        .ok()
+        .map(|commit| commit.trim().to_string());
+
+    let mut files = Vec::new();
+    if let Some(commit) = &commit {
+        let snapshot = path.join("snapshots").join(commit);
+        collect_files(&snapshot, &snapshot, &mut files)?;
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // Blobs hold the content; snapshot entries that are real files (not
+    // links to blobs) use space of their own
+    let mut size = 0;
+    let mut incomplete = 0;
+    if let Ok(blobs) = fs::read_dir(path.join("blobs")) {
+        for blob in blobs.filter_map(|blob| blob.ok()) {
+            let len = blob.metadata().map(|m| m.len()).unwrap_or(0);
+            size += len;
+            if blob
+                .file_name()
+                .to_string_lossy()
+                .ends_with(INCOMPLETE_SUFFIX)
+            {
+                incomplete += len;
+            }
+        }
+    }
+    size += unlinked_size(&path.join("snapshots"));
+
+    Ok(CachedModel {
+        repo,
+        path,
+        commit,
+        files,
+        size,
+        incomplete,
+    })
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<CachedFile>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        // Follows the link to the blob
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        files.push(CachedFile {
+            name,
+            path,
+            size: metadata.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Bytes of regular files under `dir`, not counting symlinks
+fn unlinked_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match fs::symlink_metadata(entry.path()) {
+            Ok(metadata) if metadata.is_dir() => unlinked_size(&entry.path()),
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Compare a cached file with the hub's record of it: its size, and the
+/// SHA-256 for LFS files or the git blob ID for others
+pub fn check_file(file: &CachedFile, remote: &RemoteFile) -> Result<FileCheck> {
+    let expected_size = remote.lfs.as_ref().map(|lfs| lfs.size).or(remote.size);
+    if let Some(expected) = expected_size.filter(|expected| *expected != file.size) {
+        return Ok(FileCheck::Mismatch(format!(
+            "size is {} bytes, expected {}",
+            file.size, expected
+        )));
+    }
+
+    let (actual, expected, kind) = match (&remote.lfs, &remote.blob_id) {
+        (Some(lfs), _) => (sha256_file(&file.path)?, lfs.sha256.as_str(), "sha256"),
+        (None, Some(blob_id)) => (git_blob_id(&file.path)?, blob_id.as_str(), "blob ID"),
+        (None, None) => return Ok(FileCheck::Verified),
+    };
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(FileCheck::Verified)
+    } else {
+        Ok(FileCheck::Mismatch(format!(
+            "{} is {}, expected {}",
+            kind, actual, expected
+        )))
+    }
+}
+
+/// SHA-256 of a file, as lowercase hex
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    hash_file(path, |chunk| context.update(chunk))?;
+    Ok(hex(context.finish().as_ref()))
+}
+
+/// Git blob ID of a file, which the hub reports for files not in LFS
+pub fn git_blob_id(path: &Path) -> Result<String> {
+    let len = fs::metadata(path)
+        .context(format!("Failed to read {}", path.display()))?
+        .len();
+    let mut context = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(format!("blob {}\0", len).as_bytes());
+    hash_file(path, |chunk| context.update(chunk))?;
+    Ok(hex(context.finish().as_ref()))
+}
+
+fn hash_file(path: &Path, mut update: impl FnMut(&[u8])) -> Result<()> {
+    let mut file = fs::File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .context(format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            return Ok(());
+        }
+        update(&buffer[..read]);
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A byte count for people, e.g. `1.4 GB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cached repo with one linked file, one copied file, and a partial download
+    fn cached_repo(cache: &Path) {
+        let repo = repo_dir(cache, "Qwen/Qwen2.5-0.5B-Instruct-GGUF");
+        fs::create_dir_all(repo.join("blobs")).unwrap();
+        fs::create_dir_all(repo.join("refs")).unwrap();
+        fs::create_dir_all(repo.join("snapshots/abc123")).unwrap();
+        fs::write(repo.join("refs/main"), "abc123\n").unwrap();
+        fs::write(repo.join("blobs/e3b0"), vec![1u8; 1000]).unwrap();
+        fs::write(repo.join("blobs/9f86.incomplete"), vec![1u8; 400]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(
+            repo.join("blobs/e3b0"),
+            repo.join("snapshots/abc123/model-q4_k_m.gguf"),
+        )
+        .unwrap();
+        #[cfg(not(unix))]
+        fs::copy(
+            repo.join("blobs/e3b0"),
+            repo.join("snapshots/abc123/model-q4_k_m.gguf"),
+        )
+        .unwrap();
+        fs::write(repo.join("snapshots/abc123/tokenizer.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn lists_and_removes_cached_models() {
+        let cache = tempfile::tempdir().unwrap();
+        cached_repo(cache.path());
+        fs::create_dir_all(cache.path().join("datasets--org--data")).unwrap();
+
+        let models = list_in(cache.path()).unwrap();
+        assert_eq!(models.len(), 1);
+        let model = &models[0];
+        assert_eq!(model.repo, "Qwen/Qwen2.5-0.5B-Instruct-GGUF");
+        assert_eq!(model.commit.as_deref(), Some("abc123"));
+        let names: Vec<&str> = model.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["model-q4_k_m.gguf", "tokenizer.json"]);
+        assert_eq!(model.files[0].size, 1000);
+        #[cfg(unix)]
+        assert_eq!(model.size, 1402);
+        assert_eq!(model.incomplete, 400);
+
+        assert!(remove_in(cache.path(), "Qwen/missing").is_err());
+        assert_eq!(model.size, remove_in(cache.path(), &model.repo).unwrap());
+        assert!(list_in(cache.path()).unwrap().is_empty());
+        assert!(list_in(&cache.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn hashes_match_the_hub() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hello.txt");
+        fs::write(&path, "hello\n").unwrap();
+
+        // `sha256sum` and `git hash-object` of "hello\n"
+        assert_eq!(
+            sha256_file(&path).unwrap(),
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+        assert_eq!(
+            git_blob_id(&path).unwrap(),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1_450_000_000), "1.4 GB");
+    }
+
+    #[test]
+    fn files_are_checked_against_the_hub() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        fs::write(&path, "hello\n").unwrap();
+        let file = CachedFile {
+            name: "model.gguf".to_string(),
+            path,
+            size: 6,
+        };
+        let remote: RemoteFile = serde_json::from_value(serde_json::json!({
+            "rfilename": "model.gguf",
+            "size": 6,
+            "blobId": "0000",
+            "lfs": {
+                "sha256": "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03",
+                "size": 6
+            }
+        }))
+        .unwrap();
+        assert_eq!(check_file(&file, &remote).unwrap(), FileCheck::Verified);
+
+        let mut corrupted = remote.clone();
+        corrupted.lfs.as_mut().unwrap().sha256 = "ab".repeat(32);
+        assert!(matches!(
+            check_file(&file, &corrupted).unwrap(),
+            FileCheck::Mismatch(reason) if reason.starts_with("sha256 is 5891")
+        ));
+
+        let mut in_git = remote.clone();
+        in_git.lfs = None;
+        in_git.blob_id = Some("ce013625030ba8dba906f756967f9e9ca394464a".to_string());
+        assert_eq!(check_file(&file, &in_git).unwrap(), FileCheck::Verified);
+
+        in_git.size = Some(7);
+        assert_eq!(
+            check_file(&file, &in_git).unwrap(),
+            FileCheck::Mismatch("size is 6 bytes, expected 7".to_string())
+        );
+    }
+}
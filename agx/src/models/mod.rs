@@ -1,65 +1,402 @@
+pub mod cache;
+
 use anyhow::{Context, Result};
-use hf_hub::{api::tokio::Api, Repo, RepoType};
-use std::path::PathBuf;
+use hf_hub::Cache;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_RANGE, LOCATION, RANGE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::planner::ModelSettings;
+use cache::{CachedModel, INCOMPLETE_SUFFIX};
+
+/// Hub used when `HF_ENDPOINT` is not set
+const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
 
+/// Downloads models into the cache, reporting progress on stderr so stdout
+/// stays free for a command's output
 pub struct ModelManager {
-    api: Api,
+    client: reqwest::Client,
+    /// Stops at the hub's redirect, whose headers describe the file
+    no_redirect: reqwest::Client,
+    cache: Cache,
+    endpoint: String,
+}
+
+/// What the hub says about a file before it is downloaded
+struct FileMetadata {
+    commit: String,
+    etag: String,
+    size: u64,
+}
+
+/// A file of a repository revision, from the hub's model API
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteFile {
+    pub rfilename: String,
+    pub size: Option<u64>,
+    /// Git blob ID, for files stored in git
+    #[serde(rename = "blobId")]
+    pub blob_id: Option<String>,
+    /// Set for files stored in LFS, which GGUF weights are
+    pub lfs: Option<LfsInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LfsInfo {
+    pub sha256: String,
+    pub size: u64,
 }
 
-use hf_hub::api::tokio::ApiBuilder;
+#[derive(Debug, Deserialize)]
+struct RevisionInfo {
+    #[serde(default)]
+    siblings: Vec<RemoteFile>,
+}
+
+/// Outcome of checking one cached file against the hub
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileCheck {
+    Verified,
+    /// The file is not part of the repository, like a tokenizer copied in
+    NotInRepo,
+    Mismatch(String),
+}
 
 impl ModelManager {
     pub fn new() -> Result<Self> {
-        let api = ApiBuilder::new()
-            .with_progress(true)
+        let cache = Cache::default();
+        let mut headers = HeaderMap::new();
+        if let Some(token) = cache.token() {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token.trim()))
+                .context("Invalid Hugging Face token")?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        let user_agent = concat!("agx/", env!("CARGO_PKG_VERSION"));
+
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .default_headers(headers.clone())
             .build()
-            .context("Failed to initialize Hugging Face API client")?;
-        Ok(Self { api })
+            .context("Failed to initialize HTTP client")?;
+        let no_redirect = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .default_headers(headers)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .context("Failed to initialize HTTP client")?;
+        let endpoint = std::env::var("HF_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string());
+
+        Ok(Self {
+            client,
+            no_redirect,
+            cache,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Directory of the Hugging Face cache models are kept in
+    pub fn cache_dir(&self) -> &Path {
+        self.cache.path()
     }
 
     /// Ensures a model file exists locally, downloading it if necessary.
     /// Returns the path to the local file.
+    ///
+    /// Files are kept in the Hugging Face cache, so models downloaded by
+    /// other tools are reused. An interrupted download resumes where it
+    /// stopped the next time the model is needed.
     pub async fn ensure_model(&self, repo_id: &str, filename: &str) -> Result<PathBuf> {
-        println!("Checking for model: {}/{}", repo_id, filename);
-        
-        let repo = self.api.repo(Repo::new(repo_id.to_string(), RepoType::Model));
-        
-        // download method automatically checks cache and downloads if missing
-        let path = repo.download(filename).await
-            .map_err(|e| {
-                println!("Error downloading {} from {}: {:?}", filename, repo_id, e);
-                e
-            })
-            .context(format!("Failed to download model {} from {}", filename, repo_id))?;
-            
-        println!("Model available at: {}", path.display());
-        Ok(path)
+        let cached = self.cache.model(repo_id.to_string());
+        if let Some(path) = cached.get(filename) {
+            return Ok(path);
+        }
+
+        eprintln!("Checking for model: {}/{}", repo_id, filename);
+        let url = format!("{}/{}/resolve/main/{}", self.endpoint, repo_id, filename);
+        let metadata = self
+            .metadata(&url)
+            .await
+            .context(format!("Failed to find model {} in {}", filename, repo_id))?;
+
+        let repo_dir = cache::repo_dir(self.cache.path(), repo_id);
+        let blob = repo_dir.join("blobs").join(&metadata.etag);
+        if !blob.exists() {
+            let partial = repo_dir
+                .join("blobs")
+                .join(format!("{}{}", metadata.etag, INCOMPLETE_SUFFIX));
+            self.download_resumable(&url, &partial, Some(metadata.size), filename)
+                .await
+                .context(format!("Failed to download model {} from {}", filename, repo_id))?;
+            tokio::fs::rename(&partial, &blob).await?;
+        }
+
+        let pointer = repo_dir.join("snapshots").join(&metadata.commit).join(filename);
+        link_blob(&blob, &pointer, filename)?;
+        cached.create_ref(&metadata.commit)?;
+
+        eprintln!("Model available at: {}", pointer.display());
+        Ok(pointer)
+    }
+
+    /// Ensures a Candle model and its tokenizer are available locally.
+    /// Returns the path to the GGUF file, with `tokenizer.json` beside it.
+    pub async fn ensure_candle_model(&self, settings: &ModelSettings) -> Result<PathBuf> {
+        if let Some(path) = &settings.path {
+            if !path.is_file() {
+                return Err(anyhow::anyhow!("Model file not found: {}", path.display()));
+            }
+            return Ok(path.clone());
+        }
+
+        let model_path = self.ensure_model(&settings.repo, &settings.gguf_file()).await?;
+
+        // GGUF repositories rarely ship the tokenizer, so fetch it from the base model
+        let tokenizer_repo = settings.tokenizer_repo();
+        let tokenizer_url = format!("{}/{}/resolve/main/tokenizer.json", self.endpoint, tokenizer_repo);
+        let cache_name = format!("{}--tokenizer.json", tokenizer_repo.replace('/', "--"));
+        let raw_tokenizer_path = self.download_file_raw(&tokenizer_url, &cache_name).await?;
+
+        // Copy tokenizer to model directory so Candle finds it
+        let model_dir = model_path.parent()
+            .ok_or_else(|| anyhow::anyhow!("Model path has no parent: {}", model_path.display()))?;
+        let dest_tokenizer_path = model_dir.join("tokenizer.json");
+        if !dest_tokenizer_path.exists() {
+            tokio::fs::copy(&raw_tokenizer_path, &dest_tokenizer_path).await?;
+        }
+
+        Ok(model_path)
     }
 
     /// Manually download a file from a URL to the local cache
     pub async fn download_file_raw(&self, url: &str, filename: &str) -> Result<PathBuf> {
-        println!("Downloading raw file: {}", url);
         let cache_dir = dirs::home_dir()
             .ok_or_else(|| anyhow::anyhow!("Failed to determine home directory"))?
             .join(".cache/agenix/models/raw");
-            
+
         tokio::fs::create_dir_all(&cache_dir).await?;
         let path = cache_dir.join(filename);
-        
+
         if path.exists() {
-            println!("File already exists: {}", path.display());
             return Ok(path);
         }
-        
-        let response = reqwest::get(url).await?;
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!("Failed to download file: {}", response.status()));
-        }
-        
-        let content = response.bytes().await?;
-        tokio::fs::write(&path, content).await?;
-        
-        println!("Downloaded to: {}", path.display());
+
+        eprintln!("Downloading raw file: {}", url);
+        let partial = cache_dir.join(format!("{}{}", filename, INCOMPLETE_SUFFIX));
+        self.download_resumable(url, &partial, None, filename).await?;
+        tokio::fs::rename(&partial, &path).await?;
+
+        eprintln!("Downloaded to: {}", path.display());
         Ok(path)
     }
+
+    /// Check a cached model's files against the sizes and hashes the hub
+    /// lists for its commit
+    pub async fn verify(&self, model: &CachedModel) -> Result<Vec<(String, FileCheck)>> {
+        let commit = model
+            .commit
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("{} has no cached revision", model.repo))?;
+        let url = format!(
+            "{}/api/models/{}/revision/{}?blobs=true",
+            self.endpoint, model.repo, commit
+        );
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context(format!("Failed to reach {}", self.endpoint))?
+            .error_for_status()
+            .context(format!("Failed to get metadata for {}", model.repo))?;
+        let revision: RevisionInfo = response
+            .json()
+            .await
+            .context(format!("Invalid metadata for {}", model.repo))?;
+
+        let mut checks = Vec::new();
+        for file in &model.files {
+            let check = match revision.siblings.iter().find(|remote| remote.rfilename == file.name) {
+                Some(remote) => cache::check_file(file, remote)?,
+                None => FileCheck::NotInRepo,
+            };
+            checks.push((file.name.clone(), check));
+        }
+        Ok(checks)
+    }
+
+    /// Commit, etag, and size of a file, from the headers of its first byte
+    async fn metadata(&self, url: &str) -> Result<FileMetadata> {
+        let response = self
+            .no_redirect
+            .get(url)
+            .header(RANGE, "bytes=0-0")
+            .send()
+            .await?
+            .error_for_status()?;
+        let headers = response.headers();
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.replace('"', ""))
+        };
+
+        let commit = header("x-repo-commit")
+            .ok_or_else(|| anyhow::anyhow!("Missing x-repo-commit header"))?;
+        let etag = header("x-linked-etag")
+            .or_else(|| header("etag"))
+            .map(|etag| etag.trim_start_matches("W/").to_string())
+            .ok_or_else(|| anyhow::anyhow!("Missing etag header"))?;
+        // The etag names a file in the cache
+        if etag.is_empty() || etag.contains(['/', '\\', '.']) {
+            return Err(anyhow::anyhow!("Invalid etag header: {}", etag));
+        }
+
+        // Files in LFS redirect to storage that knows their size
+        let content_range = if response.status().is_redirection() {
+            let location = header(LOCATION.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Redirect without a location"))?;
+            let location = reqwest::Url::parse(url)?.join(&location)?;
+            let response = self
+                .client
+                .get(location)
+                .header(RANGE, "bytes=0-0")
+                .send()
+                .await?
+                .error_for_status()?;
+            response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        } else {
+            header(CONTENT_RANGE.as_str())
+        };
+        let size = content_range
+            .as_deref()
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Missing file size for {}", url))?;
+
+        Ok(FileMetadata { commit, etag, size })
+    }
+
+    /// Download `url` into `partial`, continuing from what it already holds
+    async fn download_resumable(
+        &self,
+        url: &str,
+        partial: &Path,
+        size: Option<u64>,
+        label: &str,
+    ) -> Result<()> {
+        if let Some(parent) = partial.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut offset = tokio::fs::metadata(partial)
+            .await
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        if size.is_some_and(|size| offset > size) {
+            offset = 0;
+        }
+        if offset > 0 && size == Some(offset) {
+            return Ok(());
+        }
+
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = request.send().await?.error_for_status()?;
+        if offset > 0 {
+            if response.status() == StatusCode::PARTIAL_CONTENT {
+                eprintln!("Resuming {} from {}", label, cache::format_size(offset));
+            } else {
+                // The server sent the whole file
+                offset = 0;
+            }
+        }
+        let total = size.or_else(|| response.content_length().map(|length| length + offset));
+
+        let mut options = tokio::fs::OpenOptions::new();
+        if offset > 0 {
+            options.append(true);
+        } else {
+            options.create(true).write(true).truncate(true);
+        }
+        let mut file = options
+            .open(partial)
+            .await
+            .context(format!("Failed to open {}", partial.display()))?;
+
+        let mut written = offset;
+        let mut shown = None;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+            if let Some(total) = total.filter(|total| *total > 0) {
+                let percent = written * 100 / total;
+                if shown != Some(percent) {
+                    shown = Some(percent);
+                    eprint!(
+                        "\rDownloading {}: {}% of {}",
+                        label,
+                        percent,
+                        cache::format_size(total)
+                    );
+                    let _ = std::io::stderr().flush();
+                }
+            }
+        }
+        file.flush().await?;
+        if shown.is_some() {
+            eprintln!();
+        }
+
+        if let Some(total) = total {
+            if written != total {
+                return Err(anyhow::anyhow!(
+                    "Download of {} stopped at {} of {}; run again to resume",
+                    label,
+                    cache::format_size(written),
+                    cache::format_size(total)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Point the snapshot entry for a file at its blob
+fn link_blob(blob: &Path, pointer: &Path, filename: &str) -> Result<()> {
+    if pointer.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = pointer.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(unix)]
+    {
+        // Relative, like the hub's own tools, so the cache can be moved
+        let depth = filename.matches('/').count();
+        let mut target = PathBuf::from("../".repeat(depth + 2));
+        target.push("blobs");
+        target.push(blob.file_name().unwrap_or_default());
+        std::os::unix::fs::symlink(target, pointer)?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = filename;
+        if std::fs::hard_link(blob, pointer).is_err() {
+            std::fs::copy(blob, pointer)?;
+        }
+    }
+    Ok(())
 }
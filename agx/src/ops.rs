@@ -0,0 +1,115 @@
+//! Tables for the cluster administration commands (JOBS, WORKERS)
+
+use crate::agq_client::{JobEntry, WorkerInfo};
+use crate::estimate::format_ms;
+
+/// Jobs as a table, newest first as AGQ lists them
+pub fn jobs_table(jobs: &[JobEntry], now: u64) -> String {
+    let rows: Vec<Vec<String>> = jobs
+        .iter()
+        .map(|job| {
+            vec![
+                job.job_id.clone(),
+                job.plan_id.clone(),
+                job.task_number.to_string(),
+                job.command.clone(),
+                job.status.clone(),
+                job.worker_id.clone().unwrap_or_else(|| "-".to_string()),
+                ago(now, job.created_at),
+            ]
+        })
+        .collect();
+    table(
+        &[
+            "JOB", "PLAN", "TASK", "COMMAND", "STATUS", "WORKER", "CREATED",
+        ],
+        &rows,
+    )
+}
+
+/// Workers as a table, most recently seen first as AGQ lists them
+pub fn workers_table(workers: &[WorkerInfo], now: u64) -> String {
+    let rows: Vec<Vec<String>> = workers
+        .iter()
+        .map(|worker| {
+            let tools = if worker.tools.is_empty() {
+                "(no tools)".to_string()
+            } else {
+                worker.tools.replace(',', ", ")
+            };
+            vec![
+                worker.worker_id.clone(),
+                worker.status.clone(),
+                ago(now, worker.last_seen),
+                tools,
+            ]
+        })
+        .collect();
+    table(&["WORKER", "STATUS", "LAST SEEN", "TOOLS"], &rows)
+}
+
+/// Rows under a header, each column as wide as its widest cell
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut lines = vec![line(headers.to_vec())];
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+    lines.join("\n")
+}
+
+/// How long before `now` a Unix time was, e.g. `2m 05s ago`
+fn ago(now: u64, then: u64) -> String {
+    format!("{} ago", format_ms(now.saturating_sub(then) * 1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_fit_their_widest_cell() {
+        let workers = [
+            WorkerInfo {
+                worker_id: "gpu-worker-1".to_string(),
+                last_seen: 995,
+                status: "busy".to_string(),
+                tools: "sort,ocr".to_string(),
+                metrics: serde_json::Value::Null,
+            },
+            WorkerInfo {
+                worker_id: "w2".to_string(),
+                last_seen: 880,
+                status: "idle".to_string(),
+                tools: String::new(),
+                metrics: serde_json::Value::Null,
+            },
+        ];
+        assert_eq!(
+            workers_table(&workers, 1000),
+            "\
+WORKER        STATUS  LAST SEEN   TOOLS
+gpu-worker-1  busy    5s ago      sort, ocr
+w2            idle    2m 00s ago  (no tools)"
+        );
+        assert_eq!(table(&["JOB", "STATUS"], &[]), "JOB  STATUS");
+    }
+}
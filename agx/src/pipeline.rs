@@ -0,0 +1,294 @@
+//! `agx RUN`: plan a goal, submit it, and follow it to its result
+//!
+//! Chains the steps otherwise run one by one: Delta plans the goal, the plan
+//! is checked as a dry run would, reviewed unless `--yes` is given, then
+//! submitted to AGQ and run once. The run is followed as WATCH does, and the
+//! results are gathered into one summary whose output is the last task's
+//! stdout. With `--json`, stdout carries WATCH's task events and a final
+//! `result` event; progress goes to stderr.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::agq_client::{AgqClient, AgqConfig, PlanResults, PlanStatus};
+use crate::registry::ToolRegistry;
+use crate::watch::{self, WatchOptions};
+
+/// Seconds between status polls while following the run
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    pub goal: String,
+    pub yes: bool,
+    pub json: bool,
+    /// Files summarized for Delta as planning context
+    pub context_files: Vec<PathBuf>,
+}
+
+/// Outcome of a run, gathered from PLAN.STATUS and PLAN.RESULTS
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub goal: String,
+    pub plan_id: String,
+    pub action_id: String,
+    pub status: String,
+    pub tasks: Vec<TaskResult>,
+    /// Stdout of the plan's last task, if it completed
+    pub output: Option<String>,
+    pub output_encoding: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskResult {
+    pub task_number: u32,
+    pub command: String,
+    pub status: String,
+    pub exit_code: Option<i64>,
+    pub duration_ms: Option<u64>,
+}
+
+impl RunResult {
+    pub fn new(goal: &str, status: &PlanStatus, results: &PlanResults) -> Self {
+        let mut tasks: Vec<TaskResult> = status
+            .tasks
+            .iter()
+            .map(|task| TaskResult {
+                task_number: task.task_number,
+                command: task.command.clone(),
+                status: task.status.clone(),
+                exit_code: task.exit_code,
+                duration_ms: task.duration_ms,
+            })
+            .collect();
+        tasks.sort_by_key(|task| task.task_number);
+
+        let last = status
+            .tasks
+            .iter()
+            .max_by_key(|task| task.task_number)
+            .filter(|task| task.status == "completed");
+        let output = last.and_then(|task| {
+            results
+                .results
+                .iter()
+                .find(|output| output.job_id == task.job_id)
+        });
+
+        Self {
+            goal: goal.to_string(),
+            plan_id: status.plan_id.clone(),
+            action_id: status.action_id.clone(),
+            status: status.status.clone(),
+            tasks,
+            output: output.and_then(|output| output.stdout.clone()),
+            output_encoding: output
+                .map(|output| output.stdout_encoding.clone())
+                .unwrap_or_else(|| "utf8".to_string()),
+        }
+    }
+}
+
+pub async fn run(options: &RunOptions) -> Result<(), String> {
+    // Keep stdout for the JSON events
+    let progress: fn(&str) = if options.json {
+        |message| eprintln!("{message}")
+    } else {
+        |message| println!("{message}")
+    };
+
+    progress(&format!("Goal: {}", options.goal));
+    let context = crate::context_file::summarize_all(&options.context_files)?;
+    let plan = crate::delta::plan_goal(&options.goal, context, progress)
+        .await
+        .map_err(|error| format!("{error:#}"))?;
+
+    let report = crate::dry_run::check(&plan, &ToolRegistry::load()?);
+    for finding in &report.findings {
+        progress(&format!("  {finding}"));
+    }
+    if report.has_errors() {
+        return Err("generated plan has errors; nothing was submitted".to_string());
+    }
+
+    let plan = if options.yes {
+        plan
+    } else {
+        match review(plan, options.json)? {
+            Some(approved) => approved,
+            None => {
+                progress("Plan rejected; nothing was submitted.");
+                return Ok(());
+            }
+        }
+    };
+
+    let client = AgqClient::new(AgqConfig::from_env());
+    let (plan_id, action_id) = crate::submit_and_run(&client, plan)?;
+    progress(&format!("Submitted plan {plan_id} as action {action_id}"));
+
+    let status = watch::follow(
+        &client,
+        &WatchOptions {
+            plan_id,
+            action_id: Some(action_id),
+            interval: POLL_INTERVAL,
+            json: options.json,
+        },
+    )?;
+    let results = client
+        .plan_results(&status.plan_id, Some(&status.action_id))
+        .map_err(|error| format!("failed to get plan results: {error}"))?;
+    let result = RunResult::new(&options.goal, &status, &results);
+
+    if options.json {
+        let mut event = json!(result);
+        event["event"] = json!("result");
+        println!("{event}");
+        if status.status == "failed" {
+            return Err(format!("plan {} failed", status.plan_id));
+        }
+        return Ok(());
+    }
+
+    print_output(&result);
+    watch::finish(&status, false)
+}
+
+/// Review the plan, writing to stderr when stdout carries JSON
+pub fn review(
+    plan: crate::plan::WorkflowPlan,
+    json: bool,
+) -> Result<Option<crate::plan::WorkflowPlan>, String> {
+    if !json {
+        return crate::review_plan(plan);
+    }
+    if !crate::input::InputCollector::stdin_is_terminal() {
+        return Err(
+            "a generated plan must be reviewed before submission, which needs a terminal; pass --yes to submit it unreviewed"
+                .to_string(),
+        );
+    }
+    let estimator = crate::estimator_for(&plan);
+    let policy = crate::policy::Policy::load()?;
+    let stdin = std::io::stdin();
+    crate::review::review(
+        plan,
+        &estimator,
+        &policy,
+        &mut stdin.lock(),
+        &mut std::io::stderr(),
+    )
+}
+
+fn print_output(result: &RunResult) {
+    let Some(output) = result.output.as_deref() else {
+        return;
+    };
+    println!();
+    if result.output_encoding != "utf8" {
+        println!(
+            "Output: {} bytes of {} data",
+            output.len(),
+            result.output_encoding
+        );
+    } else if output.is_empty() {
+        println!("Output: (empty)");
+    } else {
+        println!("Output:");
+        print!("{output}");
+        if !output.ends_with('\n') {
+            println!();
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agq_client::{TaskOutput, TaskStatus};
+
+    fn task(job_id: &str, task_number: u32, status: &str) -> TaskStatus {
+        TaskStatus {
+            job_id: job_id.to_string(),
+            task_number,
+            command: if task_number == 1 { "sort" } else { "uniq" }.to_string(),
+            status: status.to_string(),
+            exit_code: Some(0),
+            worker_id: None,
+            started_at: None,
+            completed_at: None,
+            duration_ms: Some(12),
+            retries: 0,
+        }
+    }
+
+    fn output(job_id: &str, task_number: u32, stdout: &str) -> TaskOutput {
+        TaskOutput {
+            job_id: job_id.to_string(),
+            task_number,
+            status: "completed".to_string(),
+            stdout: Some(stdout.to_string()),
+            stdout_encoding: "utf8".to_string(),
+            stderr: None,
+            stderr_encoding: "utf8".to_string(),
+        }
+    }
+
+    fn status(plan_status: &str, tasks: Vec<TaskStatus>) -> PlanStatus {
+        PlanStatus {
+            plan_id: "plan-1".to_string(),
+            action_id: "action-1".to_string(),
+            status: plan_status.to_string(),
+            summary: Default::default(),
+            tasks,
+        }
+    }
+
+    #[test]
+    fn result_output_is_the_last_tasks_stdout() {
+        let results = PlanResults {
+            status: "completed".to_string(),
+            results: vec![
+                output("job-1", 1, "a\na\nb\n"),
+                output("job-2", 2, "a\nb\n"),
+            ],
+        };
+        let result = RunResult::new(
+            "dedupe",
+            &status(
+                "completed",
+                vec![task("job-2", 2, "completed"), task("job-1", 1, "completed")],
+            ),
+            &results,
+        );
+
+        assert_eq!(result.output.as_deref(), Some("a\nb\n"));
+        assert_eq!(result.tasks[0].command, "sort");
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["tasks"][1]["duration_ms"], 12);
+        assert_eq!(json["status"], "completed");
+    }
+
+    #[test]
+    fn failed_run_has_no_output() {
+        let results = PlanResults {
+            status: "failed".to_string(),
+            results: vec![output("job-1", 1, "a\n")],
+        };
+        let result = RunResult::new(
+            "dedupe",
+            &status(
+                "failed",
+                vec![task("job-1", 1, "completed"), task("job-2", 2, "failed")],
+            ),
+            &results,
+        );
+        assert_eq!(result.output, None);
+        assert_eq!(result.tasks[1].status, "failed");
+    }
+}
@@ -1,3 +1,4 @@
+use agenix_plan::ResourceLimits;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +20,16 @@ pub struct PlanStep {
     pub timeout_secs: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_from_task: Option<u32>,
+    /// Earlier tasks that must finish first, without their output being
+    /// piped to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<u32>,
+    /// Worker tags the task's jobs need, e.g. `gpu` or `network`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Memory, CPU time and process limits the task's jobs run under
+    #[serde(default, skip_serializing_if = "ResourceLimits::is_unlimited")]
+    pub limits: ResourceLimits,
 }
 
 fn default_timeout() -> u32 {
@@ -46,6 +57,32 @@ impl WorkflowPlan {
         parse_any_form(&cleaned)
     }
 
+    /// Check the plan against the Plan schema shared with AGQ and AGW
+    ///
+    /// Unlike parsing, which accepts whatever shape a model produced, this
+    /// reports task numbering, input references and timeouts that AGQ
+    /// would reject or that only work by being renumbered.
+    pub fn check_schema(&self) -> Result<(), String> {
+        let value = serde_json::to_value(self)
+            .map_err(|error| format!("failed to serialize plan: {error}"))?;
+        agenix_plan::validate(&value)
+            .map(|_| ())
+            .map_err(|errors| schema_error_message(&errors))
+    }
+
+    /// Tag each task with the worker tags its tool requires, keeping any
+    /// tags it already has
+    pub fn with_required_tags(mut self, registry: &crate::registry::ToolRegistry) -> Self {
+        for task in &mut self.tasks {
+            for tag in registry.required_tags(&task.command) {
+                if !task.tags.contains(tag) {
+                    task.tags.push(tag.clone());
+                }
+            }
+        }
+        self
+    }
+
     pub fn normalize_for_execution(mut self) -> Self {
         // Handle special case: bare "uniq" needs "sort" first
         if self.tasks.len() == 1 && self.tasks[0].command == "uniq" {
@@ -56,6 +93,9 @@ impl WorkflowPlan {
                     args: Vec::new(),
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 },
                 PlanStep {
                     task_number: 2,
@@ -63,6 +103,9 @@ impl WorkflowPlan {
                     args: Vec::new(),
                     timeout_secs: 300,
                     input_from_task: Some(1),
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 },
             ];
         }
@@ -76,6 +119,25 @@ impl WorkflowPlan {
     }
 }
 
+/// `task 2` or `tasks 1, 2`, for the tasks a task depends on
+pub fn task_list(numbers: &[u32]) -> String {
+    let numbers: Vec<String> = numbers.iter().map(u32::to_string).collect();
+    match numbers.as_slice() {
+        [number] => format!("task {number}"),
+        _ => format!("tasks {}", numbers.join(", ")),
+    }
+}
+
+/// One message listing every schema problem, a line each
+pub fn schema_error_message(errors: &[agenix_plan::SchemaError]) -> String {
+    let lines: Vec<String> = errors.iter().map(|error| format!("  {error}")).collect();
+    format!(
+        "plan does not match plan schema v{}:\n{}",
+        agenix_plan::SCHEMA_VERSION,
+        lines.join("\n")
+    )
+}
+
 fn strip_markdown_fence(value: &str) -> String {
     let trimmed = value.trim();
 
@@ -173,6 +235,9 @@ fn try_all_known_forms(text: &str) -> Option<WorkflowPlan> {
                     args: step.args,
                     timeout_secs: step.timeout_secs.unwrap_or(300),
                     input_from_task: step.input_from_step,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 })
                 .collect(),
         });
@@ -193,6 +258,9 @@ fn try_all_known_forms(text: &str) -> Option<WorkflowPlan> {
                     args: Vec::new(),
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 })
                 .collect(),
         });
@@ -221,6 +289,9 @@ fn try_all_known_forms(text: &str) -> Option<WorkflowPlan> {
                     args: step.args,
                     timeout_secs: step.timeout_secs.unwrap_or(300),
                     input_from_task: step.input_from_step,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 })
                 .collect(),
         });
@@ -240,6 +311,9 @@ fn try_all_known_forms(text: &str) -> Option<WorkflowPlan> {
                     args: Vec::new(),
                     timeout_secs: 300,
                     input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: ResourceLimits::default(),
                 })
                 .collect(),
         });
@@ -394,4 +468,45 @@ mod tests {
 
         assert_eq!(valid, repaired);
     }
+
+    #[test]
+    fn schema_check_reports_misnumbered_tasks() {
+        let plan = WorkflowPlan::from_str(
+            r#"{"tasks": [
+                {"task_number": 1, "command": "sort"},
+                {"task_number": 3, "command": "uniq", "input_from_task": 1}
+            ]}"#,
+        )
+        .unwrap();
+        let error = plan.check_schema().unwrap_err();
+        assert_eq!(
+            error,
+            "plan does not match plan schema v1:\n  tasks[1].task_number: is 3, but tasks are numbered 1, 2, 3, … in order, so this should be 2"
+        );
+
+        assert!(plan.normalize_for_execution().check_schema().is_ok());
+    }
+
+    #[test]
+    fn tasks_are_tagged_with_their_tools_requirements() {
+        let plan = WorkflowPlan::from_str(
+            r#"{"tasks": [
+                {"task_number": 1, "command": "train_model", "tags": ["vram:24"]},
+                {"task_number": 2, "command": "sort"}
+            ]}"#,
+        )
+        .unwrap()
+        .with_required_tags(&crate::registry::ToolRegistry::new());
+
+        assert_eq!(plan.tasks[0].tags, ["vram:24", "gpu"]);
+        assert!(plan.tasks[1].tags.is_empty());
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(
+            json["tasks"][0]["tags"],
+            serde_json::json!(["vram:24", "gpu"])
+        );
+        assert!(json["tasks"][1].get("tags").is_none());
+        assert!(plan.check_schema().is_ok());
+    }
 }
@@ -63,6 +63,24 @@ impl PlanStorage {
         }
     }
 
+    /// Check the buffer file against the Plan schema, which unlike
+    /// [`load`](Self::load) rejects fields it doesn't know
+    pub fn check_schema(&self) -> Result<(), String> {
+        let contents = fs::read_to_string(&self.path).map_err(|error| {
+            format!(
+                "failed to read plan buffer {}: {error}",
+                self.display_path()
+            )
+        })?;
+        agenix_plan::parse(&contents).map(|_| ()).map_err(|errors| {
+            format!(
+                "plan buffer {}: {}",
+                self.display_path(),
+                crate::plan::schema_error_message(&errors)
+            )
+        })
+    }
+
     pub fn save(&self, plan: &WorkflowPlan) -> Result<(), String> {
         if let Some(parent) = self.path.parent() {
             if !parent.as_os_str().is_empty() {
@@ -156,6 +174,26 @@ mod tests {
         assert_eq!(plan.tasks.len(), 0);
     }
 
+    #[test]
+    fn check_schema_rejects_unknown_fields() {
+        let path = temp_path("schema");
+        fs::write(
+            &path,
+            r#"{"tasks": [{"task_number": 1, "command": "sort", "input_from": 1}]}"#,
+        )
+        .unwrap();
+
+        let storage = PlanStorage::new(path.clone());
+        assert_eq!(storage.load().unwrap().tasks.len(), 1);
+        let error = storage.check_schema().unwrap_err();
+        assert!(
+            error.contains("tasks[0]: unknown field \"input_from\"; did you mean input_from_task?"),
+            "{error}"
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn save_and_load_roundtrip() {
         let path = temp_path("roundtrip");
@@ -174,6 +212,9 @@ mod tests {
                 args: vec!["-r".to_string()],
                 timeout_secs: 300,
                 input_from_task: None,
+                depends_on: Vec::new(),
+                tags: Vec::new(),
+                limits: Default::default(),
             }],
         };
 
@@ -0,0 +1,248 @@
+//! Anthropic backend using the Messages API
+//!
+//! Configured from the environment: `ANTHROPIC_API_KEY` is required, and
+//! `AGX_ANTHROPIC_MODEL`, `AGX_ANTHROPIC_MAX_TOKENS` and `ANTHROPIC_BASE_URL`
+//! override the defaults.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::backend::ModelBackend;
+use super::types::{ChatMessage, GeneratedPlan, ModelError, PlanContext, PlanMetadata};
+use crate::plan::WorkflowPlan;
+
+const DEFAULT_MODEL: &str = "claude-sonnet-4-5";
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+const API_VERSION: &str = "2023-06-01";
+
+/// Timeout for one API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Anthropic backend configuration
+#[derive(Debug, Clone)]
+pub struct AnthropicConfig {
+    pub api_key: String,
+    pub model: String,
+    pub max_tokens: u32,
+    pub base_url: String,
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self {
+            api_key: std::env::var("ANTHROPIC_API_KEY").unwrap_or_default(),
+            model: std::env::var("AGX_ANTHROPIC_MODEL")
+                .unwrap_or_else(|_| DEFAULT_MODEL.to_string()),
+            max_tokens: std::env::var("AGX_ANTHROPIC_MAX_TOKENS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_MAX_TOKENS),
+            base_url: std::env::var("ANTHROPIC_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+        }
+    }
+}
+
+/// Anthropic backend (Claude models)
+pub struct AnthropicBackend {
+    client: Client,
+    config: AnthropicConfig,
+}
+
+impl AnthropicBackend {
+    pub fn from_config(config: AnthropicConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    /// Send a conversation, returning the reply and the tokens used
+    async fn send(&self, history: &[ChatMessage]) -> Result<(String, Option<usize>), ModelError> {
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "ANTHROPIC_API_KEY not set".to_string(),
+            ));
+        }
+
+        let url = format!("{}/v1/messages", self.config.base_url.trim_end_matches('/'));
+        let response = self
+            .client
+            .post(url)
+            .header("x-api-key", &self.config.api_key)
+            .header("anthropic-version", API_VERSION)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&request_body(
+                &self.config.model,
+                self.config.max_tokens,
+                history,
+            ))
+            .send()
+            .await
+            .map_err(|e| ModelError::InferenceError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(ModelError::InferenceError(format!(
+                "Anthropic API error: {} - {}",
+                status, text
+            )));
+        }
+
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| ModelError::InferenceError(e.to_string()))?;
+        parse_response(&json)
+    }
+}
+
+#[async_trait]
+impl ModelBackend for AnthropicBackend {
+    async fn generate_plan(
+        &self,
+        instruction: &str,
+        context: &PlanContext,
+    ) -> Result<GeneratedPlan, ModelError> {
+        let history = super::prompts::build_plan_messages(instruction, context);
+
+        let start = Instant::now();
+        let (response, tokens) = self.send(&history).await?;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let plan = WorkflowPlan::from_str(&response).map_err(|e| {
+            ModelError::ParseError(format!(
+                "Failed to parse Anthropic response: {}. Response: {}",
+                e, response
+            ))
+        })?;
+
+        Ok(GeneratedPlan {
+            tasks: plan.tasks,
+            metadata: PlanMetadata {
+                model_used: self.config.model.clone(),
+                tokens,
+                latency_ms,
+                backend: "anthropic".to_string(),
+            },
+        })
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn health_check(&self) -> Result<(), ModelError> {
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "ANTHROPIC_API_KEY not set".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn chat(
+        &self,
+        history: &[ChatMessage],
+        _context: &PlanContext,
+    ) -> Result<String, ModelError> {
+        let (response, _) = self.send(history).await?;
+        Ok(response)
+    }
+}
+
+/// Messages API request for a conversation
+///
+/// System messages go in the top-level `system` field, which the API
+/// requires, rather than in `messages`.
+fn request_body(model: &str, max_tokens: u32, history: &[ChatMessage]) -> Value {
+    let system: Vec<&str> = history
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| message.content.as_str())
+        .collect();
+    let messages: Vec<Value> = history
+        .iter()
+        .filter(|message| message.role != "system")
+        .map(|message| json!({ "role": message.role, "content": message.content }))
+        .collect();
+
+    let mut body = json!({
+        "model": model,
+        "max_tokens": max_tokens,
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    body
+}
+
+/// Text of a Messages API response, and the tokens it used
+fn parse_response(json: &Value) -> Result<(String, Option<usize>), ModelError> {
+    let content = json["content"].as_array().ok_or_else(|| {
+        ModelError::ParseError("Invalid response format from Anthropic".to_string())
+    })?;
+    let text: String = content
+        .iter()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+
+    let usage = &json["usage"];
+    let tokens = match (
+        usage["input_tokens"].as_u64(),
+        usage["output_tokens"].as_u64(),
+    ) {
+        (Some(input), Some(output)) => Some((input + output) as usize),
+        _ => None,
+    };
+    Ok((text, tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_messages_are_sent_separately() {
+        let history = vec![
+            ChatMessage::system("You are Echo"),
+            ChatMessage::user("dedupe my logs"),
+            ChatMessage::assistant("Sort, then uniq."),
+        ];
+        let body = request_body("claude-sonnet-4-5", 1024, &history);
+
+        assert_eq!(body["system"], "You are Echo");
+        assert_eq!(body["max_tokens"], 1024);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["role"], "user");
+        assert_eq!(messages[1]["content"], "Sort, then uniq.");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let json = json!({
+            "content": [
+                { "type": "text", "text": "{\"tasks\": " },
+                { "type": "text", "text": "[]}" }
+            ],
+            "usage": { "input_tokens": 120, "output_tokens": 8 }
+        });
+        let (text, tokens) = parse_response(&json).unwrap();
+        assert_eq!(text, "{\"tasks\": []}");
+        assert_eq!(tokens, Some(128));
+
+        assert!(parse_response(&json!({ "error": "overloaded" })).is_err());
+    }
+}
@@ -28,10 +28,37 @@ pub trait ModelBackend: Send + Sync {
     /// Validate that the model is loaded and ready to generate plans
     async fn health_check(&self) -> Result<(), ModelError>;
 
+    /// Tokens the model attends to, prompt and reply together, if known
+    fn context_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// Tokens `text` takes up, estimated at four characters a token unless
+    /// the backend has the model's tokenizer
+    fn count_tokens(&self, text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
     /// Generate a conversational response
     async fn chat(
         &self,
         history: &[super::types::ChatMessage],
         context: &PlanContext,
     ) -> Result<String, ModelError>;
+
+    /// Generate a conversational response, passing text to `on_text` as it is generated
+    ///
+    /// Generation stops early when `on_text` returns `false`, and the text so
+    /// far is returned. Backends that cannot stream pass the whole response
+    /// at once.
+    async fn chat_stream(
+        &self,
+        history: &[super::types::ChatMessage],
+        context: &PlanContext,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+    ) -> Result<String, ModelError> {
+        let response = self.chat(history, context).await?;
+        on_text(&response);
+        Ok(response)
+    }
 }
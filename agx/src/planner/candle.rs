@@ -9,10 +9,14 @@ use candle_transformers::models::quantized_qwen2;
 use tokenizers::Tokenizer;
 
 use super::backend::ModelBackend;
-use super::device::select_device_from_env;
+use super::device::{device_name, select_device_from_env, DeviceSpec};
+use super::lora;
 use super::types::{GeneratedPlan, ModelError, PlanContext, PlanMetadata, ToolInfo};
 use crate::plan::{PlanStep, WorkflowPlan};
 
+/// Called with the tokens generated so far; returning `false` stops generation
+type TokenCallback<'a> = dyn FnMut(&[u32]) -> bool + 'a;
+
 /// Unified model wrapper supporting multiple architectures
 enum ModelWeights {
     Llama(quantized_llama::ModelWeights),
@@ -86,6 +90,12 @@ pub struct CandleConfig {
     pub seed: Option<u64>,
     /// Context window size for token generation
     pub context_size: usize,
+    /// Tokens kept in the KV cache, at most `context_size` (None = `context_size`)
+    pub kv_cache_size: Option<usize>,
+    /// Device to load the model on (None = `AGX_DEVICE`, or auto-detect)
+    pub device: Option<DeviceSpec>,
+    /// LoRA adapter directory merged into the model as it loads
+    pub adapter_path: Option<PathBuf>,
 }
 
 /// Model role determines prompt style
@@ -108,6 +118,9 @@ impl Default for CandleConfig {
             model_role: ModelRole::Echo,
             seed: None, // Random seed by default
             context_size: 2048,
+            kv_cache_size: None,
+            device: None,
+            adapter_path: None,
         }
     }
 }
@@ -140,6 +153,24 @@ impl CandleConfig {
             }
         };
 
+        let context_size = std::env::var("AGX_CANDLE_CONTEXT_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2048);
+
+        let kv_cache_size = std::env::var("AGX_CANDLE_KV_CACHE")
+            .ok()
+            .and_then(|s| s.parse().ok());
+
+        Ok(Self {
+            kv_cache_size,
+            ..Self::for_model(role, model_path, context_size)
+        })
+    }
+
+    /// Build configuration for a model file, with sampling settings from
+    /// environment variables
+    pub fn for_model(role: ModelRole, model_path: PathBuf, context_size: usize) -> Self {
         let temperature = std::env::var("AGX_CANDLE_TEMPERATURE")
             .ok()
             .and_then(|s| s.parse().ok())
@@ -159,12 +190,7 @@ impl CandleConfig {
             .ok()
             .and_then(|s| s.parse().ok());
 
-        let context_size = std::env::var("AGX_CANDLE_CONTEXT_SIZE")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(2048);
-
-        Ok(Self {
+        Self {
             model_path,
             temperature,
             top_p,
@@ -173,7 +199,18 @@ impl CandleConfig {
             model_role: role,
             seed,
             context_size,
-        })
+            kv_cache_size: None,
+            device: None,
+            adapter_path: None,
+        }
+    }
+
+    /// Tokens the KV cache holds before it is rebuilt from recent tokens
+    fn kv_window(&self) -> usize {
+        self.kv_cache_size
+            .unwrap_or(self.context_size)
+            .min(self.context_size)
+            .max(1)
     }
 
     /// Get tokenizer path (assumes tokenizer.json in same directory as model)
@@ -185,6 +222,39 @@ impl CandleConfig {
     }
 }
 
+/// Tracks the tokens in the model's KV cache during generation
+///
+/// The prompt fills the cache, then each step adds one token. When the cache
+/// is full it is rebuilt from the last half of the window, so memory stays
+/// bounded however long the prompt or the output runs.
+struct KvWindow {
+    window: usize,
+    cached: usize,
+}
+
+impl KvWindow {
+    fn new(window: usize) -> Self {
+        Self { window, cached: 0 }
+    }
+
+    /// Where the next model input starts in `len` tokens, and its position
+    /// in the cache; position 0 makes the model drop its cache
+    fn next_input(&mut self, len: usize) -> (usize, usize) {
+        if self.cached > 0 && self.cached < self.window {
+            self.cached += 1;
+            return (len - 1, self.cached - 1);
+        }
+
+        let keep = if self.cached == 0 {
+            len.min(self.window)
+        } else {
+            (self.window / 2).max(1)
+        };
+        self.cached = keep;
+        (len - keep, 0)
+    }
+}
+
 /// Candle-based model backend for local LLM inference
 pub struct CandleBackend {
     model: Mutex<ModelWeights>,
@@ -199,12 +269,15 @@ impl CandleBackend {
     pub async fn new(config: CandleConfig) -> Result<Self, ModelError> {
         // Run model loading in a blocking task to avoid blocking async runtime
         let backend = tokio::task::spawn_blocking(move || {
-            let device = select_device_from_env()?;
+            let device = match config.device {
+                Some(spec) => spec.open()?,
+                None => select_device_from_env()?,
+            };
 
             log::info!(
-                "Loading model from {:?} on {:?}",
+                "Loading model from {:?} on {}",
                 config.model_path,
-                device
+                device_name(&device)
             );
 
             if !config.model_path.exists() {
@@ -225,8 +298,26 @@ impl CandleBackend {
             // Parse GGUF file content
             let content = candle_core::quantized::gguf_file::Content::read(&mut file)?;
 
-            // Load model from GGUF
-            let model = ModelWeights::from_gguf(content, &mut file, &device)?;
+            // Load model from GGUF, with the LoRA adapter merged in
+            let model = match &config.adapter_path {
+                Some(adapter_path) => {
+                    log::info!("Merging LoRA adapter from {:?}", adapter_path);
+                    let adapter = lora::Adapter::load(adapter_path)?;
+                    let (content, mut merged) = lora::merge(&content, &mut file, &adapter)?;
+                    ModelWeights::from_gguf(content, &mut merged, &device)
+                }
+                None => ModelWeights::from_gguf(content, &mut file, &device),
+            }
+            .map_err(|e| {
+                if device.is_cpu() {
+                    return e;
+                }
+                ModelError::LoadError(format!(
+                    "{} (on {}; a smaller quantization or device = \"cpu\" may fit)",
+                    e,
+                    device_name(&device)
+                ))
+            })?;
 
             // Load tokenizer
             let tokenizer_path = config.tokenizer_path();
@@ -265,6 +356,16 @@ impl CandleBackend {
         Ok(backend)
     }
 
+    /// The device the model was loaded on, e.g. `cuda:0`
+    pub fn device_name(&self) -> String {
+        device_name(&self.device)
+    }
+
+    /// The `tokenizer.json` the model was loaded with
+    pub fn tokenizer_path(&self) -> PathBuf {
+        self.config.tokenizer_path()
+    }
+
     /// Sanitize user input for safe inclusion in prompts
     ///
     /// Prevents prompt injection by:
@@ -315,7 +416,15 @@ impl CandleBackend {
     }
 
     /// Generate tokens using the model
-    fn generate_tokens(&self, input_tokens: &[u32], stop_on_json: bool) -> Result<Vec<u32>, ModelError> {
+    ///
+    /// `on_token` sees the tokens generated so far after each step, and
+    /// stops generation by returning `false`.
+    fn generate_tokens(
+        &self,
+        input_tokens: &[u32],
+        stop_on_json: bool,
+        mut on_token: Option<&mut TokenCallback<'_>>,
+    ) -> Result<Vec<u32>, ModelError> {
         use candle_transformers::generation::LogitsProcessor;
 
         // Use configured seed or generate random one
@@ -349,21 +458,17 @@ impl CandleBackend {
             ModelError::InferenceError(format!("Failed to lock model mutex: {}", e))
         })?;
 
+        let mut kv_cache = KvWindow::new(self.config.kv_window());
+
         // Generate tokens one by one
         for _ in 0..self.config.max_tokens {
-            let context_size = if tokens.len() > self.config.context_size {
-                self.config.context_size
-            } else {
-                tokens.len()
-            };
-
-            let start_pos = tokens.len().saturating_sub(context_size);
-            let context_tokens = &tokens[start_pos..];
+            let (start, index_pos) = kv_cache.next_input(tokens.len());
+            let context_tokens = &tokens[start..];
 
             let input = candle_core::Tensor::new(context_tokens, &self.device)?
                 .unsqueeze(0)?;
 
-            let logits = model.forward(&input, start_pos)?;
+            let logits = model.forward(&input, index_pos)?;
             let logits = logits.squeeze(0)?.to_dtype(candle_core::DType::F32)?;
 
             let next_token = logits_processor.sample(&logits)?;
@@ -375,6 +480,12 @@ impl CandleBackend {
                 break;
             }
 
+            if let Some(on_token) = on_token.as_mut() {
+                if !on_token(&generated_tokens) {
+                    break;
+                }
+            }
+
             // Early stopping if we can parse valid JSON
             // Check every 10 tokens to avoid too much overhead
             if stop_on_json && generated_tokens.len() % 10 == 0 {
@@ -417,7 +528,7 @@ impl ModelBackend for CandleBackend {
 
         // Generate tokens (CPU-intensive, but we keep it sync for now)
         // TODO: Consider using spawn_blocking if generation is too slow
-        let output_tokens = self.generate_tokens(&input_tokens, true)?;
+        let output_tokens = self.generate_tokens(&input_tokens, true, None)?;
 
         // Decode
         let response = self.tokenizer.decode(&output_tokens, true)?;
@@ -451,6 +562,17 @@ impl ModelBackend for CandleBackend {
         &self.model_name
     }
 
+    fn context_length(&self) -> Option<usize> {
+        Some(self.config.context_size)
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer
+            .encode(text, false)
+            .map(|encoding| encoding.len())
+            .unwrap_or_else(|_| text.chars().count().div_ceil(4))
+    }
+
     async fn health_check(&self) -> Result<(), ModelError> {
         // Simple test: try to tokenize a short string
         self.tokenizer
@@ -461,9 +583,18 @@ impl ModelBackend for CandleBackend {
     }
 
     async fn chat(
+        &self,
+        history: &[super::types::ChatMessage],
+        context: &PlanContext,
+    ) -> Result<String, ModelError> {
+        self.chat_stream(history, context, &mut |_| true).await
+    }
+
+    async fn chat_stream(
         &self,
         history: &[super::types::ChatMessage],
         _context: &PlanContext,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
     ) -> Result<String, ModelError> {
         // Build prompt
         let mut prompt = String::new();
@@ -481,8 +612,22 @@ impl ModelBackend for CandleBackend {
         let encoding = self.tokenizer.encode(prompt, true)?;
         let input_tokens: Vec<u32> = encoding.get_ids().to_vec();
 
-        // Generate tokens (no JSON stopping)
-        let output_tokens = self.generate_tokens(&input_tokens, false)?;
+        // Generate tokens (no JSON stopping), passing on the text each new
+        // token completes
+        let mut emitted = 0;
+        let mut on_token = |tokens: &[u32]| {
+            let Ok(text) = self.tokenizer.decode(tokens, true) else {
+                return true;
+            };
+            match new_text(&text, emitted) {
+                Some(new) => {
+                    emitted = text.len();
+                    on_text(new)
+                }
+                None => true,
+            }
+        };
+        let output_tokens = self.generate_tokens(&input_tokens, false, Some(&mut on_token))?;
 
         // Decode
         let response = self.tokenizer.decode(&output_tokens, true)?;
@@ -491,6 +636,17 @@ impl ModelBackend for CandleBackend {
     }
 }
 
+/// Text decoded past the first `emitted` bytes, once it is complete
+///
+/// A token can end partway through a multi-byte character, which decodes
+/// as U+FFFD until the next token completes it, so that is held back.
+fn new_text(text: &str, emitted: usize) -> Option<&str> {
+    if text.len() <= emitted || text.ends_with('\u{FFFD}') {
+        return None;
+    }
+    text.get(emitted..)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -696,6 +852,32 @@ mod tests {
         assert!(prompt.contains("ls: list files"));
     }
 
+    #[test]
+    fn test_prompts_include_retrieved_documents() {
+        let documents = "From /srv/deploy.sh (lines 1-2):\nrsync dist/ web01:/srv/app";
+        let mut context = PlanContext {
+            tool_registry: vec![ToolInfo::new("ls", "list files")],
+            documents: Some(documents.to_string()),
+            ..Default::default()
+        };
+
+        let user = crate::planner::prompts::build_user_prompt("deploy the app", &context);
+        assert!(user.starts_with(&format!("Relevant documents:\n{}", documents)));
+
+        context.existing_tasks = vec![PlanStep {
+            task_number: 1,
+            command: "ls".to_string(),
+            args: vec![],
+            timeout_secs: 300,
+            input_from_task: None,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }];
+        let delta = crate::planner::prompts::build_delta_prompt("deploy the app", &context);
+        assert!(delta.contains(&format!("RELEVANT DOCUMENTS:\n{}\n\nAVAILABLE TOOLS:", documents)));
+    }
+
     #[test]
     fn test_delta_prompt_structure() {
         let context = PlanContext {
@@ -706,6 +888,9 @@ mod tests {
                 args: vec![],
                 timeout_secs: 300,
                 input_from_task: None,
+                depends_on: Vec::new(),
+                tags: Vec::new(),
+                limits: Default::default(),
             }],
             ..Default::default()
         };
@@ -742,6 +927,15 @@ mod tests {
         assert!(formatted.contains("grep (search text)"));
     }
 
+    #[test]
+    fn test_new_text_holds_back_partial_characters() {
+        assert_eq!(new_text("Hello", 0), Some("Hello"));
+        assert_eq!(new_text("Hello wor", 5), Some(" wor"));
+        assert_eq!(new_text("Hello", 5), None);
+        assert_eq!(new_text("caf\u{FFFD}", 3), None);
+        assert_eq!(new_text("café", 3), Some("é"));
+    }
+
     #[test]
     fn test_model_role_enum() {
         assert_eq!(ModelRole::Echo, ModelRole::Echo);
@@ -802,6 +996,26 @@ mod tests {
         std::env::remove_var("AGX_CANDLE_SEED");
     }
 
+    #[test]
+    fn test_kv_window_bounds_the_cache() {
+        let mut kv_cache = KvWindow::new(8);
+
+        // A long prompt is cut to the window
+        assert_eq!(kv_cache.next_input(10), (2, 0));
+        assert_eq!(kv_cache.next_input(11), (7, 0));
+
+        // Otherwise the prompt is fed whole, then one token per step
+        let mut kv_cache = KvWindow::new(8);
+        assert_eq!(kv_cache.next_input(5), (0, 0));
+        assert_eq!(kv_cache.next_input(6), (5, 5));
+        assert_eq!(kv_cache.next_input(7), (6, 6));
+        assert_eq!(kv_cache.next_input(8), (7, 7));
+
+        // A full cache is rebuilt from the last half of the window
+        assert_eq!(kv_cache.next_input(9), (5, 0));
+        assert_eq!(kv_cache.next_input(10), (9, 4));
+    }
+
     #[test]
     fn test_config_with_context_size() {
         std::env::set_var("AGX_ECHO_MODEL", "/tmp/test.gguf");
@@ -0,0 +1,548 @@
+//! Planner daemon: keeps Candle models loaded between agx invocations
+//!
+//! `agx SERVE-PLANNER` loads the Echo and Delta models once and answers
+//! plan and chat requests over a Unix socket per role. Echo and Delta use
+//! the daemon when it is running with the model they are configured for,
+//! and load the model themselves otherwise.
+//!
+//! Requests and replies are JSON, one per line. A chat reply streams
+//! `text` lines as the response is generated and ends with `done`;
+//! closing the connection stops generation.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokenizers::Tokenizer;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use super::backend::ModelBackend;
+use super::candle::ModelRole;
+use super::types::{ChatMessage, GeneratedPlan, ModelError, PlanContext};
+use super::wrapper::PlannerConfig;
+use crate::logging;
+
+/// How long to wait for a daemon to answer before loading the model locally
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A request to the daemon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Info,
+    GeneratePlan {
+        instruction: String,
+        context: PlanContext,
+    },
+    Chat {
+        history: Vec<ChatMessage>,
+        context: PlanContext,
+    },
+}
+
+/// A line the daemon sends back
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+enum Reply {
+    Info(ModelInfo),
+    Plan { plan: GeneratedPlan },
+    Text { text: String },
+    Done { response: String },
+    Error { error: String },
+}
+
+/// The model a daemon serves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// The model file or repository, as `ModelSettings::describe` gives it
+    pub model: String,
+    pub model_name: String,
+    pub device: String,
+    pub context_length: Option<usize>,
+    /// `tokenizer.json` next to the model, so clients count tokens exactly
+    pub tokenizer: Option<PathBuf>,
+}
+
+/// The socket the daemon serves `role` on: `AGX_PLANNER_SOCKET_DIR`, or
+/// an `agx` directory in the runtime (or temp) directory
+pub fn socket_path(role: ModelRole) -> PathBuf {
+    let dir = match std::env::var_os("AGX_PLANNER_SOCKET_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::runtime_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("agx"),
+    };
+    dir.join(format!("planner-{}.sock", role_name(role)))
+}
+
+fn role_name(role: ModelRole) -> &'static str {
+    match role {
+        ModelRole::Echo => "echo",
+        ModelRole::Delta => "delta",
+    }
+}
+
+/// Load the models for `roles` and serve them until interrupted
+pub async fn serve(roles: &[ModelRole]) -> Result<(), String> {
+    let config = PlannerConfig::load()?;
+    let mut sockets = Vec::new();
+
+    for &role in roles {
+        let path = socket_path(role);
+        claim_socket(&path)?;
+
+        let settings = config.model(role);
+        eprintln!(
+            "Loading {} model {}...",
+            role_name(role),
+            settings.describe()
+        );
+        let backend = config
+            .candle_backend(role)
+            .await
+            .map_err(|e| format!("failed to load the {} model: {}", role_name(role), e))?;
+        let info = ModelInfo {
+            model: settings.describe(),
+            model_name: backend.model_name().to_string(),
+            device: backend.device_name(),
+            context_length: backend.context_length(),
+            tokenizer: Some(backend.tokenizer_path()),
+        };
+
+        let listener = bind(&path)?;
+        eprintln!(
+            "Serving {} on {} ({})",
+            role_name(role),
+            path.display(),
+            info.device
+        );
+        let runtime = tokio::runtime::Handle::current();
+        let backend: Arc<dyn ModelBackend> = Arc::new(backend);
+        // A plain thread, so exiting does not wait on the accept loop
+        std::thread::spawn(move || serve_listener(listener, backend, info, runtime));
+        sockets.push(path);
+    }
+
+    tokio::signal::ctrl_c()
+        .await
+        .map_err(|e| format!("failed to wait for Ctrl-C: {e}"))?;
+    for path in sockets {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// Refuse a socket another daemon is answering on, and remove a stale one
+fn claim_socket(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(format!(
+            "a planner daemon is already serving on {}",
+            path.display()
+        ));
+    }
+    std::fs::remove_file(path)
+        .map_err(|e| format!("failed to remove stale socket {}: {e}", path.display()))
+}
+
+/// Bind the socket, readable and writable by this user only
+fn bind(path: &Path) -> Result<UnixListener, String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .map_err(|e| format!("failed to listen on {}: {e}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("failed to restrict {}: {e}", path.display()))?;
+    Ok(listener)
+}
+
+/// Answer connections one at a time, as the model runs one request at a time
+fn serve_listener(
+    listener: UnixListener,
+    backend: Arc<dyn ModelBackend>,
+    info: ModelInfo,
+    runtime: tokio::runtime::Handle,
+) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| serve_connection(stream, &*backend, &info, &runtime));
+        if let Err(e) = result {
+            logging::info(&format!("planner daemon connection failed: {e}"));
+        }
+    }
+}
+
+fn serve_connection(
+    stream: UnixStream,
+    backend: &dyn ModelBackend,
+    info: &ModelInfo,
+    runtime: &tokio::runtime::Handle,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                send(
+                    &mut writer,
+                    &Reply::Error {
+                        error: format!("invalid request: {e}"),
+                    },
+                )?;
+                continue;
+            }
+        };
+
+        let reply = match request {
+            Request::Info => Reply::Info(info.clone()),
+            Request::GeneratePlan {
+                instruction,
+                context,
+            } => match runtime.block_on(backend.generate_plan(&instruction, &context)) {
+                Ok(plan) => Reply::Plan { plan },
+                Err(e) => Reply::Error {
+                    error: e.to_string(),
+                },
+            },
+            Request::Chat { history, context } => {
+                // A failed write means the client hung up, which stops generation
+                let mut stream_writer = writer.try_clone()?;
+                let mut on_text = |text: &str| {
+                    send(
+                        &mut stream_writer,
+                        &Reply::Text {
+                            text: text.to_string(),
+                        },
+                    )
+                    .is_ok()
+                };
+                match runtime.block_on(backend.chat_stream(&history, &context, &mut on_text)) {
+                    Ok(response) => Reply::Done { response },
+                    Err(e) => Reply::Error {
+                        error: e.to_string(),
+                    },
+                }
+            }
+        };
+        send(&mut writer, &reply)?;
+    }
+    Ok(())
+}
+
+fn send(writer: &mut UnixStream, reply: &Reply) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(reply)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())
+}
+
+/// A backend forwarding requests to a running planner daemon
+pub struct DaemonBackend {
+    path: PathBuf,
+    info: ModelInfo,
+    tokenizer: Option<Tokenizer>,
+}
+
+impl DaemonBackend {
+    /// The daemon serving `role`, if one is running with `model` loaded
+    pub async fn connect(role: ModelRole, model: &str) -> Option<Self> {
+        Self::connect_at(socket_path(role), model).await
+    }
+
+    async fn connect_at(path: PathBuf, model: &str) -> Option<Self> {
+        if !path.exists() {
+            return None;
+        }
+        let reply =
+            tokio::time::timeout(CONNECT_TIMEOUT, call(&path, &Request::Info, &mut |_| true)).await;
+        let info = match reply {
+            Ok(Ok(Reply::Info(info))) => info,
+            Ok(Ok(_)) => return None,
+            Ok(Err(e)) => {
+                logging::info(&format!("planner daemon unavailable: {e}"));
+                return None;
+            }
+            Err(_) => {
+                logging::info("planner daemon did not answer in time");
+                return None;
+            }
+        };
+        if info.model != model {
+            logging::info(&format!(
+                "planner daemon serves {}, not {model}; loading the model here",
+                info.model
+            ));
+            return None;
+        }
+
+        let tokenizer = info
+            .tokenizer
+            .as_ref()
+            .and_then(|path| Tokenizer::from_file(path).ok());
+        Some(Self {
+            path,
+            info,
+            tokenizer,
+        })
+    }
+
+    /// The device the daemon runs the model on
+    pub fn device_name(&self) -> String {
+        format!("{} (planner daemon)", self.info.device)
+    }
+}
+
+#[async_trait]
+impl ModelBackend for DaemonBackend {
+    async fn generate_plan(
+        &self,
+        instruction: &str,
+        context: &PlanContext,
+    ) -> Result<GeneratedPlan, ModelError> {
+        let request = Request::GeneratePlan {
+            instruction: instruction.to_string(),
+            context: context.clone(),
+        };
+        match call(&self.path, &request, &mut |_| true).await? {
+            Reply::Plan { plan } => Ok(plan),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "candle"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.info.model_name
+    }
+
+    async fn health_check(&self) -> Result<(), ModelError> {
+        match call(&self.path, &Request::Info, &mut |_| true).await {
+            Ok(Reply::Info(_)) => Ok(()),
+            Ok(other) => Err(ModelError::HealthCheckError(unexpected(other).to_string())),
+            Err(e) => Err(ModelError::HealthCheckError(e.to_string())),
+        }
+    }
+
+    fn context_length(&self) -> Option<usize> {
+        self.info.context_length
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer
+            .as_ref()
+            .and_then(|tokenizer| tokenizer.encode(text, false).ok())
+            .map(|encoding| encoding.len())
+            .unwrap_or_else(|| text.chars().count().div_ceil(4))
+    }
+
+    async fn chat(
+        &self,
+        history: &[ChatMessage],
+        context: &PlanContext,
+    ) -> Result<String, ModelError> {
+        self.chat_stream(history, context, &mut |_| true).await
+    }
+
+    async fn chat_stream(
+        &self,
+        history: &[ChatMessage],
+        context: &PlanContext,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+    ) -> Result<String, ModelError> {
+        let request = Request::Chat {
+            history: history.to_vec(),
+            context: context.clone(),
+        };
+        match call(&self.path, &request, on_text).await? {
+            Reply::Done { response } => Ok(response),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+/// Send one request, passing streamed text to `on_text`
+///
+/// When `on_text` returns `false` the connection is closed, which stops
+/// the daemon generating, and the text so far is returned as `Done`.
+async fn call(
+    path: &Path,
+    request: &Request,
+    on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+) -> Result<Reply, ModelError> {
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    let mut streamed = String::new();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<Reply>(&line)? {
+            Reply::Text { text } => {
+                streamed.push_str(&text);
+                if !on_text(&text) {
+                    return Ok(Reply::Done { response: streamed });
+                }
+            }
+            Reply::Error { error } => {
+                return Err(ModelError::InferenceError(format!(
+                    "planner daemon: {error}"
+                )))
+            }
+            reply => return Ok(reply),
+        }
+    }
+    Err(ModelError::InferenceError(
+        "planner daemon closed the connection".to_string(),
+    ))
+}
+
+fn unexpected(reply: Reply) -> ModelError {
+    ModelError::InferenceError(format!("unexpected reply from planner daemon: {reply:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::types::PlanMetadata;
+
+    /// Echoes the instruction back as a plan and streams chat a word at a time
+    struct WordBackend;
+
+    #[async_trait]
+    impl ModelBackend for WordBackend {
+        async fn generate_plan(
+            &self,
+            instruction: &str,
+            _context: &PlanContext,
+        ) -> Result<GeneratedPlan, ModelError> {
+            if instruction.is_empty() {
+                return Err(ModelError::InferenceError("empty instruction".to_string()));
+            }
+            Ok(GeneratedPlan {
+                tasks: Vec::new(),
+                metadata: PlanMetadata {
+                    model_used: instruction.to_string(),
+                    tokens: None,
+                    latency_ms: 0,
+                    backend: "candle".to_string(),
+                },
+            })
+        }
+
+        fn backend_type(&self) -> &'static str {
+            "candle"
+        }
+
+        fn model_name(&self) -> &str {
+            "word"
+        }
+
+        async fn health_check(&self) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        async fn chat(
+            &self,
+            history: &[ChatMessage],
+            context: &PlanContext,
+        ) -> Result<String, ModelError> {
+            self.chat_stream(history, context, &mut |_| true).await
+        }
+
+        async fn chat_stream(
+            &self,
+            history: &[ChatMessage],
+            _context: &PlanContext,
+            on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+        ) -> Result<String, ModelError> {
+            let mut response = String::new();
+            for word in history.last().unwrap().content.split_inclusive(' ') {
+                response.push_str(word);
+                if !on_text(word) {
+                    break;
+                }
+            }
+            Ok(response)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn requests_are_answered_over_the_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("planner-echo.sock");
+        let listener = bind(&path).unwrap();
+        let info = ModelInfo {
+            model: "Qwen/Qwen2.5-1.5B-Instruct-GGUF/qwen2.5-1.5b-instruct-q4_k_m.gguf".to_string(),
+            model_name: "word".to_string(),
+            device: "cpu".to_string(),
+            context_length: Some(4096),
+            tokenizer: None,
+        };
+        let runtime = tokio::runtime::Handle::current();
+        let served = info.clone();
+        std::thread::spawn(move || {
+            serve_listener(listener, Arc::new(WordBackend), served, runtime)
+        });
+
+        assert!(
+            DaemonBackend::connect_at(path.clone(), "another-model.gguf")
+                .await
+                .is_none()
+        );
+        let backend = DaemonBackend::connect_at(path, &info.model).await.unwrap();
+        assert_eq!(backend.device_name(), "cpu (planner daemon)");
+        assert_eq!(backend.context_length(), Some(4096));
+
+        let context = PlanContext::default();
+        let plan = backend
+            .generate_plan("sort the file", &context)
+            .await
+            .unwrap();
+        assert_eq!(plan.metadata.model_used, "sort the file");
+        let error = backend.generate_plan("", &context).await.unwrap_err();
+        assert!(error.to_string().contains("planner daemon: "), "{error}");
+
+        let history = [ChatMessage::user("one two three")];
+        let mut streamed = Vec::new();
+        let response = backend
+            .chat_stream(&history, &context, &mut |text| {
+                streamed.push(text.to_string());
+                true
+            })
+            .await
+            .unwrap();
+        assert_eq!(response, "one two three");
+        assert_eq!(streamed, ["one ", "two ", "three"]);
+
+        let response = backend
+            .chat_stream(&history, &context, &mut |_| false)
+            .await
+            .unwrap();
+        assert_eq!(response, "one ");
+    }
+
+    #[test]
+    fn stale_sockets_are_replaced_and_live_ones_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("planner-delta.sock");
+
+        let listener = bind(&path).unwrap();
+        assert!(claim_socket(&path).unwrap_err().contains("already serving"));
+        drop(listener);
+
+        claim_socket(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
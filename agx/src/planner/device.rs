@@ -1,4 +1,7 @@
-use candle_core::Device;
+use std::fmt;
+
+use candle_core::{Device, DeviceLocation};
+use serde::Deserialize;
 
 use super::types::ModelError;
 
@@ -85,67 +88,121 @@ impl DeviceSelector {
     }
 }
 
+/// Device a Candle model runs on, as given in `AGX_DEVICE` or the planner
+/// config: `auto`, `cpu`, `cuda`, `cuda:N`, `metal` or `metal:N`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum DeviceSpec {
+    /// CUDA, then Metal, then CPU
+    #[default]
+    Auto,
+    Cpu,
+    Cuda(usize),
+    Metal(usize),
+}
+
+impl DeviceSpec {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let normalized = value.trim().to_lowercase();
+        let (kind, index) = match normalized.split_once(':') {
+            Some((kind, index)) => {
+                let index = index
+                    .parse()
+                    .map_err(|_| format!("invalid device index in '{}'", value))?;
+                (kind, Some(index))
+            }
+            None => (normalized.as_str(), None),
+        };
+
+        match (kind, index) {
+            ("" | "auto", None) => Ok(DeviceSpec::Auto),
+            ("cpu", None) => Ok(DeviceSpec::Cpu),
+            ("cuda", index) => Ok(DeviceSpec::Cuda(index.unwrap_or(0))),
+            ("metal", index) => Ok(DeviceSpec::Metal(index.unwrap_or(0))),
+            _ => Err(format!(
+                "invalid device '{}'; expected auto, cpu, cuda[:N] or metal[:N]",
+                value
+            )),
+        }
+    }
+
+    /// Open the device, failing rather than falling back when an explicit
+    /// one is unavailable
+    pub fn open(self) -> Result<Device, ModelError> {
+        match self {
+            DeviceSpec::Auto => DeviceSelector::auto_select(),
+            DeviceSpec::Cpu => Ok(Device::Cpu),
+            #[cfg(feature = "cuda")]
+            DeviceSpec::Cuda(index) => Device::new_cuda(index).map_err(|e| {
+                ModelError::ConfigError(format!("Failed to initialize {}: {}", self, e))
+            }),
+            #[cfg(not(feature = "cuda"))]
+            DeviceSpec::Cuda(_) => Err(ModelError::ConfigError(
+                "CUDA requested but not compiled with cuda feature".to_string(),
+            )),
+            #[cfg(feature = "metal")]
+            DeviceSpec::Metal(index) => Device::new_metal(index).map_err(|e| {
+                ModelError::ConfigError(format!("Failed to initialize {}: {}", self, e))
+            }),
+            #[cfg(not(feature = "metal"))]
+            DeviceSpec::Metal(_) => Err(ModelError::ConfigError(
+                "Metal requested but not compiled with metal feature".to_string(),
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for DeviceSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl fmt::Display for DeviceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSpec::Auto => write!(f, "auto"),
+            DeviceSpec::Cpu => write!(f, "cpu"),
+            DeviceSpec::Cuda(index) => write!(f, "cuda:{}", index),
+            DeviceSpec::Metal(index) => write!(f, "metal:{}", index),
+        }
+    }
+}
+
+/// Name of an opened device, e.g. `cuda:1`
+pub fn device_name(device: &Device) -> String {
+    match device.location() {
+        DeviceLocation::Cpu => DeviceSpec::Cpu,
+        DeviceLocation::Cuda { gpu_id } => DeviceSpec::Cuda(gpu_id),
+        DeviceLocation::Metal { gpu_id } => DeviceSpec::Metal(gpu_id),
+    }
+    .to_string()
+}
+
 /// Select device from environment variable or auto-detect
 ///
 /// Respects `AGX_DEVICE` environment variable:
-/// - `cuda` - Force CUDA
-/// - `metal` - Force Metal
+/// - `cuda` or `cuda:N` - Force CUDA
+/// - `metal` or `metal:N` - Force Metal
 /// - `cpu` - Force CPU
 /// - Not set or invalid - Auto-detect
 pub fn select_device_from_env() -> Result<Device, ModelError> {
     match std::env::var("AGX_DEVICE") {
-        Ok(dev) => {
-            let normalized = dev.to_lowercase();
-            match normalized.as_str() {
-                "cuda" => {
-                    #[cfg(feature = "cuda")]
-                    {
-                        log::info!("Device selection: CUDA (forced by AGX_DEVICE)");
-                        return Device::new_cuda(0).map_err(|e| {
-                            ModelError::ConfigError(format!(
-                                "Failed to initialize CUDA (forced by AGX_DEVICE): {}",
-                                e
-                            ))
-                        });
-                    }
-                    #[cfg(not(feature = "cuda"))]
-                    {
-                        return Err(ModelError::ConfigError(
-                            "CUDA requested but not compiled with cuda feature".to_string(),
-                        ));
-                    }
-                }
-                "metal" => {
-                    #[cfg(feature = "metal")]
-                    {
-                        log::info!("Device selection: Metal (forced by AGX_DEVICE)");
-                        return Device::new_metal(0).map_err(|e| {
-                            ModelError::ConfigError(format!(
-                                "Failed to initialize Metal (forced by AGX_DEVICE): {}",
-                                e
-                            ))
-                        });
-                    }
-                    #[cfg(not(feature = "metal"))]
-                    {
-                        return Err(ModelError::ConfigError(
-                            "Metal requested but not compiled with metal feature".to_string(),
-                        ));
-                    }
-                }
-                "cpu" => {
-                    log::info!("Device selection: CPU (forced by AGX_DEVICE)");
-                    return Ok(Device::Cpu);
-                }
-                _ => {
-                    log::warn!(
-                        "Invalid AGX_DEVICE value: '{}', falling back to auto-detect",
-                        dev
-                    );
-                    DeviceSelector::auto_select()
-                }
+        Ok(dev) => match DeviceSpec::parse(&dev) {
+            Ok(spec) => {
+                log::info!("Device selection: {} (forced by AGX_DEVICE)", spec);
+                spec.open()
             }
-        }
+            Err(_) => {
+                log::warn!(
+                    "Invalid AGX_DEVICE value: '{}', falling back to auto-detect",
+                    dev
+                );
+                DeviceSelector::auto_select()
+            }
+        },
         Err(_) => DeviceSelector::auto_select(),
     }
 }
@@ -170,6 +227,20 @@ mod tests {
         std::env::remove_var("AGX_DEVICE");
     }
 
+    #[test]
+    fn test_parse_device_spec() {
+        assert_eq!(DeviceSpec::parse("auto"), Ok(DeviceSpec::Auto));
+        assert_eq!(DeviceSpec::parse("CPU"), Ok(DeviceSpec::Cpu));
+        assert_eq!(DeviceSpec::parse("cuda"), Ok(DeviceSpec::Cuda(0)));
+        assert_eq!(DeviceSpec::parse("cuda:1"), Ok(DeviceSpec::Cuda(1)));
+        assert_eq!(DeviceSpec::parse("metal:0"), Ok(DeviceSpec::Metal(0)));
+        assert!(DeviceSpec::parse("cuda:x").is_err());
+        assert!(DeviceSpec::parse("cpu:1").is_err());
+        assert!(DeviceSpec::parse("tpu").is_err());
+        assert_eq!(DeviceSpec::Cuda(1).to_string(), "cuda:1");
+        assert_eq!(device_name(&Device::Cpu), "cpu");
+    }
+
     #[test]
     fn test_invalid_device_from_env() {
         // Invalid value should fall back to auto-detect
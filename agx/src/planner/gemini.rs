@@ -0,0 +1,246 @@
+//! Gemini backend using the Generative Language API
+//!
+//! Configured from the environment: `GEMINI_API_KEY` is required, and
+//! `AGX_GEMINI_MODEL` and `GEMINI_BASE_URL` override the defaults.
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::backend::ModelBackend;
+use super::types::{ChatMessage, GeneratedPlan, ModelError, PlanContext, PlanMetadata};
+use crate::plan::WorkflowPlan;
+
+const DEFAULT_MODEL: &str = "gemini-2.5-flash";
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Timeout for one API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Gemini backend configuration
+#[derive(Debug, Clone)]
+pub struct GeminiConfig {
+    pub api_key: String,
+    pub model: String,
+    pub base_url: String,
+}
+
+impl Default for GeminiConfig {
+    fn default() -> Self {
+        Self {
+            api_key: std::env::var("GEMINI_API_KEY").unwrap_or_default(),
+            model: std::env::var("AGX_GEMINI_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string()),
+            base_url: std::env::var("GEMINI_BASE_URL")
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+        }
+    }
+}
+
+/// Gemini backend (Google models)
+pub struct GeminiBackend {
+    client: Client,
+    config: GeminiConfig,
+}
+
+impl GeminiBackend {
+    pub fn from_config(config: GeminiConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    /// Send a conversation, returning the reply and the tokens used
+    async fn send(&self, history: &[ChatMessage]) -> Result<(String, Option<usize>), ModelError> {
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "GEMINI_API_KEY not set".to_string(),
+            ));
+        }
+
+        let url = format!(
+            "{}/v1beta/models/{}:generateContent",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.model
+        );
+        let response = self
+            .client
+            .post(url)
+            .header("x-goog-api-key", &self.config.api_key)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&request_body(history))
+            .send()
+            .await
+            .map_err(|e| ModelError::InferenceError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(ModelError::InferenceError(format!(
+                "Gemini API error: {} - {}",
+                status, text
+            )));
+        }
+
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| ModelError::InferenceError(e.to_string()))?;
+        parse_response(&json)
+    }
+}
+
+#[async_trait]
+impl ModelBackend for GeminiBackend {
+    async fn generate_plan(
+        &self,
+        instruction: &str,
+        context: &PlanContext,
+    ) -> Result<GeneratedPlan, ModelError> {
+        let history = super::prompts::build_plan_messages(instruction, context);
+
+        let start = Instant::now();
+        let (response, tokens) = self.send(&history).await?;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let plan = WorkflowPlan::from_str(&response).map_err(|e| {
+            ModelError::ParseError(format!(
+                "Failed to parse Gemini response: {}. Response: {}",
+                e, response
+            ))
+        })?;
+
+        Ok(GeneratedPlan {
+            tasks: plan.tasks,
+            metadata: PlanMetadata {
+                model_used: self.config.model.clone(),
+                tokens,
+                latency_ms,
+                backend: "gemini".to_string(),
+            },
+        })
+    }
+
+    fn backend_type(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn health_check(&self) -> Result<(), ModelError> {
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "GEMINI_API_KEY not set".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn chat(
+        &self,
+        history: &[ChatMessage],
+        _context: &PlanContext,
+    ) -> Result<String, ModelError> {
+        let (response, _) = self.send(history).await?;
+        Ok(response)
+    }
+}
+
+/// generateContent request for a conversation
+///
+/// System messages become the `systemInstruction`, and Gemini calls the
+/// assistant role `model`.
+fn request_body(history: &[ChatMessage]) -> Value {
+    let system: Vec<&str> = history
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| message.content.as_str())
+        .collect();
+    let contents: Vec<Value> = history
+        .iter()
+        .filter(|message| message.role != "system")
+        .map(|message| {
+            let role = if message.role == "assistant" {
+                "model"
+            } else {
+                "user"
+            };
+            json!({ "role": role, "parts": [{ "text": message.content }] })
+        })
+        .collect();
+
+    let mut body = json!({ "contents": contents });
+    if !system.is_empty() {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+    }
+    body
+}
+
+/// Text of a generateContent response, and the tokens it used
+fn parse_response(json: &Value) -> Result<(String, Option<usize>), ModelError> {
+    let candidate = &json["candidates"][0];
+    let parts = candidate["content"]["parts"].as_array().ok_or_else(|| {
+        // A blocked prompt has no candidates, only the reason
+        match json["promptFeedback"]["blockReason"].as_str() {
+            Some(reason) => {
+                ModelError::InferenceError(format!("Gemini blocked the prompt: {}", reason))
+            }
+            None => ModelError::ParseError("Invalid response format from Gemini".to_string()),
+        }
+    })?;
+    let text: String = parts
+        .iter()
+        .filter_map(|part| part["text"].as_str())
+        .collect();
+
+    let tokens = json["usageMetadata"]["totalTokenCount"]
+        .as_u64()
+        .map(|count| count as usize);
+    Ok((text, tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_uses_gemini_roles() {
+        let history = vec![
+            ChatMessage::system("You are Echo"),
+            ChatMessage::user("dedupe my logs"),
+            ChatMessage::assistant("Sort, then uniq."),
+        ];
+        let body = request_body(&history);
+
+        assert_eq!(
+            body["systemInstruction"]["parts"][0]["text"],
+            "You are Echo"
+        );
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0]["role"], "user");
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["text"], "Sort, then uniq.");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let json = json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "{\"tasks\": []}" }] }
+            }],
+            "usageMetadata": { "totalTokenCount": 42 }
+        });
+        let (text, tokens) = parse_response(&json).unwrap();
+        assert_eq!(text, "{\"tasks\": []}");
+        assert_eq!(tokens, Some(42));
+
+        let blocked = json!({ "promptFeedback": { "blockReason": "SAFETY" } });
+        let error = parse_response(&blocked).unwrap_err();
+        assert!(error.to_string().contains("SAFETY"));
+    }
+}
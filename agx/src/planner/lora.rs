@@ -0,0 +1,319 @@
+//! LoRA adapters merged into a GGUF model's weights at load time
+//!
+//! Adapters are read in the PEFT layout Axolotl and Unsloth save: a
+//! directory with `adapter_config.json` and `adapter_model.safetensors`.
+//! Each adapted weight is dequantized, has `scale · B·A` added, and is
+//! quantized back to its own type, so the merged model runs as fast as the
+//! base model. Merging needs the model's size again in memory while it
+//! loads.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+use candle_core::quantized::gguf_file::{self, Content};
+use candle_core::quantized::QTensor;
+use candle_core::{DType, Device, Tensor};
+use serde::Deserialize;
+
+use super::types::ModelError;
+
+/// The parts of `adapter_config.json` the merge needs
+#[derive(Debug, Deserialize)]
+struct AdapterConfig {
+    r: usize,
+    lora_alpha: f64,
+    #[serde(default)]
+    use_rslora: bool,
+}
+
+/// A loaded adapter: the A and B matrices of each weight it adapts
+pub struct Adapter {
+    scale: f64,
+    /// `(A, B)` by GGUF tensor name, e.g. `blk.0.attn_q.weight`
+    weights: HashMap<String, (Tensor, Tensor)>,
+}
+
+impl Adapter {
+    /// Read an adapter directory
+    pub fn load(dir: &Path) -> Result<Self, ModelError> {
+        let config_path = dir.join("adapter_config.json");
+        let config: AdapterConfig =
+            serde_json::from_str(&std::fs::read_to_string(&config_path).map_err(|e| {
+                ModelError::ConfigError(format!(
+                    "failed to read LoRA adapter config {}: {}",
+                    config_path.display(),
+                    e
+                ))
+            })?)?;
+        if config.r == 0 {
+            return Err(ModelError::ConfigError(format!(
+                "LoRA adapter {} has rank 0",
+                dir.display()
+            )));
+        }
+        let scale = if config.use_rslora {
+            config.lora_alpha / (config.r as f64).sqrt()
+        } else {
+            config.lora_alpha / config.r as f64
+        };
+
+        let weights_path = dir.join("adapter_model.safetensors");
+        if !weights_path.exists() {
+            return Err(ModelError::ConfigError(format!(
+                "LoRA adapter weights not found at '{}'",
+                weights_path.display()
+            )));
+        }
+        let tensors = candle_core::safetensors::load(&weights_path, &Device::Cpu)?;
+        Self::from_tensors(scale, tensors)
+    }
+
+    /// Pair up PEFT tensors, e.g. `…layers.0.self_attn.q_proj.lora_A.weight`,
+    /// under the GGUF names of the weights they adapt
+    fn from_tensors(scale: f64, tensors: HashMap<String, Tensor>) -> Result<Self, ModelError> {
+        let mut halves: HashMap<String, (Option<Tensor>, Option<Tensor>)> = HashMap::new();
+        for (name, tensor) in tensors {
+            let (gguf_name, is_a) = gguf_name(&name).ok_or_else(|| {
+                ModelError::LoadError(format!(
+                    "LoRA adapter tensor {} adapts a weight agx can't merge into a GGUF model",
+                    name
+                ))
+            })?;
+            let tensor = tensor.to_dtype(DType::F32)?;
+            let entry = halves.entry(gguf_name).or_default();
+            if is_a {
+                entry.0 = Some(tensor);
+            } else {
+                entry.1 = Some(tensor);
+            }
+        }
+
+        let mut weights = HashMap::new();
+        for (name, halves) in halves {
+            match halves {
+                (Some(a), Some(b)) => {
+                    weights.insert(name, (a, b));
+                }
+                _ => {
+                    return Err(ModelError::LoadError(format!(
+                        "LoRA adapter has only one of the A and B matrices for {}",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(Self { scale, weights })
+    }
+}
+
+/// The GGUF weight a PEFT LoRA tensor adapts, and whether it is the A matrix
+fn gguf_name(peft_name: &str) -> Option<(String, bool)> {
+    let rest = &peft_name[peft_name.find("layers.")? + "layers.".len()..];
+    let (layer, rest) = rest.split_once('.')?;
+    let layer: usize = layer.parse().ok()?;
+    let (module, rest) = rest.split_once('.')?;
+    let (projection, matrix) = rest.split_once('.')?;
+    let is_a = match matrix {
+        "lora_A.weight" => true,
+        "lora_B.weight" => false,
+        _ => return None,
+    };
+    let weight = match (module, projection) {
+        ("self_attn", "q_proj") => "attn_q",
+        ("self_attn", "k_proj") => "attn_k",
+        ("self_attn", "v_proj") => "attn_v",
+        ("self_attn", "o_proj") => "attn_output",
+        ("mlp", "gate_proj") => "ffn_gate",
+        ("mlp", "up_proj") => "ffn_up",
+        ("mlp", "down_proj") => "ffn_down",
+        _ => return None,
+    };
+    Some((format!("blk.{layer}.{weight}.weight"), is_a))
+}
+
+/// Merge `adapter` into the model `content` describes, returning the merged
+/// model as an in-memory GGUF file
+pub fn merge<R: Seek + Read>(
+    content: &Content,
+    reader: &mut R,
+    adapter: &Adapter,
+) -> Result<(Content, Cursor<Vec<u8>>), ModelError> {
+    let head_count = |key: &str| {
+        content
+            .metadata
+            .get(key)
+            .and_then(|value| value.to_u32().ok())
+    };
+    // llama.cpp reorders the query and key rows of llama models for its
+    // rotary embedding, so their deltas must be reordered the same way
+    let llama_heads = head_count("llama.attention.head_count").map(|heads| {
+        let kv_heads = head_count("llama.attention.head_count_kv").unwrap_or(heads);
+        (heads as usize, kv_heads as usize)
+    });
+
+    let mut tensors = Vec::with_capacity(content.tensor_infos.len());
+    for name in content.tensor_infos.keys() {
+        tensors.push((name.as_str(), content.tensor(reader, name, &Device::Cpu)?));
+    }
+
+    for (name, (a, b)) in &adapter.weights {
+        let Some((_, weight)) = tensors.iter_mut().find(|(tensor, _)| tensor == name) else {
+            return Err(ModelError::LoadError(format!(
+                "LoRA adapter adapts {}, which the model doesn't have; was it trained on another base model?",
+                name
+            )));
+        };
+
+        let mut delta = (b.matmul(a)? * adapter.scale)?;
+        if delta.dims() != weight.shape().dims() {
+            return Err(ModelError::LoadError(format!(
+                "LoRA adapter changes {} by {:?}, but the weight is {:?}; was it trained on another base model?",
+                name,
+                delta.dims(),
+                weight.shape().dims()
+            )));
+        }
+        if let Some((heads, kv_heads)) = llama_heads {
+            if name.ends_with(".attn_q.weight") {
+                delta = permute_rows(&delta, heads)?;
+            } else if name.ends_with(".attn_k.weight") {
+                delta = permute_rows(&delta, kv_heads)?;
+            }
+        }
+
+        let merged = (weight.dequantize(&Device::Cpu)? + delta)?;
+        *weight = QTensor::quantize(&merged, weight.dtype())?;
+    }
+
+    let metadata: Vec<(&str, &gguf_file::Value)> = content
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.as_str(), value))
+        .collect();
+    let tensors: Vec<(&str, &QTensor)> = tensors
+        .iter()
+        .map(|(name, tensor)| (*name, tensor))
+        .collect();
+    let mut merged = Cursor::new(Vec::new());
+    gguf_file::write(&mut merged, &metadata, &tensors)?;
+
+    merged.set_position(0);
+    let content = Content::read(&mut merged)?;
+    Ok((content, merged))
+}
+
+/// Reorder rows the way llama.cpp's converter does for query and key weights
+fn permute_rows(weight: &Tensor, heads: usize) -> Result<Tensor, ModelError> {
+    let (rows, columns) = weight.dims2()?;
+    Ok(weight
+        .reshape((heads, 2, rows / heads / 2, columns))?
+        .transpose(1, 2)?
+        .reshape((rows, columns))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::quantized::GgmlDType;
+
+    /// A one-layer model whose weights are all zero, as a GGUF file
+    fn model(arch: &str) -> Cursor<Vec<u8>> {
+        let zeros = QTensor::quantize(
+            &Tensor::zeros((4, 2), DType::F32, &Device::Cpu).unwrap(),
+            GgmlDType::F32,
+        )
+        .unwrap();
+        let heads = gguf_file::Value::U32(1);
+        let key = format!("{arch}.attention.head_count");
+        let mut file = Cursor::new(Vec::new());
+        gguf_file::write(
+            &mut file,
+            &[(key.as_str(), &heads)],
+            &[
+                ("blk.0.attn_q.weight", &zeros),
+                ("blk.0.ffn_up.weight", &zeros),
+            ],
+        )
+        .unwrap();
+        file.set_position(0);
+        file
+    }
+
+    fn adapter(projection: &str) -> Adapter {
+        let tensor = |values: &[f32], shape: (usize, usize)| {
+            Tensor::from_slice(values, shape, &Device::Cpu).unwrap()
+        };
+        let prefix = format!("base_model.model.model.layers.0.{projection}");
+        let tensors = HashMap::from([
+            (
+                format!("{prefix}.lora_A.weight"),
+                tensor(&[1.0, 2.0], (1, 2)),
+            ),
+            (
+                format!("{prefix}.lora_B.weight"),
+                tensor(&[1.0, 2.0, 3.0, 4.0], (4, 1)),
+            ),
+        ]);
+        Adapter::from_tensors(0.5, tensors).unwrap()
+    }
+
+    fn merged_weight(arch: &str, adapter: &Adapter, name: &str) -> Vec<Vec<f32>> {
+        let mut file = model(arch);
+        let content = Content::read(&mut file).unwrap();
+        let (content, mut merged) = merge(&content, &mut file, adapter).unwrap();
+        content
+            .tensor(&mut merged, name, &Device::Cpu)
+            .unwrap()
+            .dequantize(&Device::Cpu)
+            .unwrap()
+            .to_vec2()
+            .unwrap()
+    }
+
+    #[test]
+    fn deltas_are_added_to_the_adapted_weight() {
+        let adapter = adapter("mlp.up_proj");
+        let expected = [[0.5, 1.0], [1.0, 2.0], [1.5, 3.0], [2.0, 4.0]];
+        assert_eq!(
+            merged_weight("qwen2", &adapter, "blk.0.ffn_up.weight"),
+            expected
+        );
+        assert_eq!(
+            merged_weight("qwen2", &adapter, "blk.0.attn_q.weight"),
+            [[0.0; 2]; 4]
+        );
+    }
+
+    #[test]
+    fn llama_query_deltas_follow_the_converted_row_order() {
+        let adapter = adapter("self_attn.q_proj");
+        assert_eq!(
+            merged_weight("qwen2", &adapter, "blk.0.attn_q.weight"),
+            [[0.5, 1.0], [1.0, 2.0], [1.5, 3.0], [2.0, 4.0]]
+        );
+        // Rows [0, 1 | 2, 3] of a head are interleaved as 0, 2, 1, 3
+        assert_eq!(
+            merged_weight("llama", &adapter, "blk.0.attn_q.weight"),
+            [[0.5, 1.0], [1.5, 3.0], [1.0, 2.0], [2.0, 4.0]]
+        );
+    }
+
+    #[test]
+    fn adapters_for_other_weights_or_models_are_refused() {
+        assert_eq!(
+            gguf_name("base_model.model.model.layers.12.self_attn.o_proj.lora_B.weight"),
+            Some(("blk.12.attn_output.weight".to_string(), false))
+        );
+        assert_eq!(gguf_name("base_model.model.lm_head.lora_A.weight"), None);
+
+        let adapter = adapter("mlp.down_proj");
+        let mut file = model("qwen2");
+        let content = Content::read(&mut file).unwrap();
+        let error = merge(&content, &mut file, &adapter).err().unwrap();
+        assert!(
+            error.to_string().contains("blk.0.ffn_down.weight"),
+            "{error}"
+        );
+    }
+}
@@ -6,7 +6,13 @@ pub mod types;
 pub mod device;
 
 // Backend implementations
+pub mod anthropic;
 pub mod candle;
+// Unix sockets only; elsewhere Echo and Delta load the model in-process
+#[cfg(unix)]
+pub mod daemon;
+pub mod gemini;
+pub mod lora;
 pub mod ollama;
 pub mod openai;
 
@@ -15,9 +21,13 @@ pub mod wrapper;
 
 pub mod prompts;
 
+pub use anthropic::{AnthropicBackend, AnthropicConfig};
 pub use backend::ModelBackend;
 pub use candle::{CandleBackend, CandleConfig, ModelRole};
+#[cfg(unix)]
+pub use daemon::DaemonBackend;
+pub use gemini::{GeminiBackend, GeminiConfig};
 pub use ollama::{OllamaBackend, OllamaConfig};
-pub use openai::OpenAIBackend;
+pub use openai::{OpenAIBackend, OpenAIConfig};
 pub use types::{ChatMessage, PlanContext, ToolInfo};
-pub use wrapper::{Planner, PlannerConfig, BackendKind};
+pub use wrapper::{Planner, PlannerConfig, BackendKind, ModelSettings};
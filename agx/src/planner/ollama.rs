@@ -1,6 +1,7 @@
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use serde::Deserialize;
 
 use super::backend::ModelBackend;
 use super::types::{GeneratedPlan, ModelError, PlanContext, PlanMetadata};
@@ -65,11 +66,7 @@ impl ModelBackend for OllamaBackend {
         };
         let model = self.model.clone();
 
-        // Timeout for Ollama calls (default 5 minutes)
-        let timeout_secs = std::env::var("AGX_OLLAMA_TIMEOUT_SECS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(300);
+        let timeout_secs = timeout_secs();
 
         // Run ollama in a blocking task with timeout
         let (response, latency_ms) = tokio::time::timeout(
@@ -199,11 +196,7 @@ impl ModelBackend for OllamaBackend {
 
         let model = self.model.clone();
 
-        // Timeout for Ollama calls (default 5 minutes)
-        let timeout_secs = std::env::var("AGX_OLLAMA_TIMEOUT_SECS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(300);
+        let timeout_secs = timeout_secs();
 
         // Run ollama in a blocking task with timeout
         let (response, _) = tokio::time::timeout(
@@ -249,6 +242,151 @@ impl ModelBackend for OllamaBackend {
 
         Ok(response)
     }
+
+    async fn chat_stream(
+        &self,
+        history: &[super::types::ChatMessage],
+        context: &PlanContext,
+        on_text: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+    ) -> Result<String, ModelError> {
+        let mut messages: Vec<serde_json::Value> = history
+            .iter()
+            .map(|msg| serde_json::json!({ "role": msg.role, "content": msg.content }))
+            .collect();
+        if let Some(summary) = &context.input_summary {
+            messages.push(serde_json::json!({
+                "role": "system",
+                "content": format!("Context: {}", summary),
+            }));
+        }
+
+        let url = format!("{}/api/chat", server_url());
+        let timeout_secs = timeout_secs();
+        let timeout = Duration::from_secs(timeout_secs);
+        let timed_out = || {
+            ModelError::InferenceError(format!(
+                "Ollama call timed out after {} seconds",
+                timeout_secs
+            ))
+        };
+
+        let request = reqwest::Client::new().post(&url).json(&serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+        }));
+        let mut response = tokio::time::timeout(timeout, request.send())
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|e| ModelError::InferenceError(format!("failed to reach ollama at {}: {}", url, e)))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ModelError::InferenceError(format!(
+                "ollama returned {}: {}",
+                status,
+                body.trim()
+            )));
+        }
+
+        // The reply arrives as one JSON object per line, each with the next
+        // piece of the message
+        let mut reply = String::new();
+        let mut pending = Vec::new();
+        while let Some(bytes) = tokio::time::timeout(timeout, response.chunk())
+            .await
+            .map_err(|_| timed_out())?
+            .map_err(|e| ModelError::InferenceError(format!("ollama stream failed: {}", e)))?
+        {
+            pending.extend_from_slice(&bytes);
+            while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let Some(chunk) = parse_chat_chunk(&line)? else {
+                    continue;
+                };
+                if !chunk.content.is_empty() {
+                    reply.push_str(&chunk.content);
+                    if !on_text(&chunk.content) {
+                        return Ok(reply);
+                    }
+                }
+                if chunk.done {
+                    return Ok(reply);
+                }
+            }
+        }
+
+        Ok(reply)
+    }
+}
+
+/// Timeout for Ollama calls (default 5 minutes)
+fn timeout_secs() -> u64 {
+    std::env::var("AGX_OLLAMA_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300)
+}
+
+/// Base URL of the Ollama server, from `OLLAMA_HOST` as the ollama CLI reads it
+fn server_url() -> String {
+    let host = std::env::var("OLLAMA_HOST").unwrap_or_default();
+    let host = host.trim().trim_end_matches('/');
+    if host.is_empty() {
+        return "http://127.0.0.1:11434".to_string();
+    }
+
+    let (scheme, address) = host.split_once("://").unwrap_or(("http", host));
+    if address.contains(':') {
+        format!("{}://{}", scheme, address)
+    } else {
+        format!("{}://{}:11434", scheme, address)
+    }
+}
+
+/// One line of a streamed `/api/chat` response
+#[derive(Debug, Default, PartialEq, Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    done: bool,
+}
+
+fn parse_chat_chunk(line: &[u8]) -> Result<Option<ChatChunk>, ModelError> {
+    #[derive(Deserialize)]
+    struct Line {
+        #[serde(default)]
+        message: Option<Message>,
+        #[serde(default)]
+        done: bool,
+        #[serde(default)]
+        error: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct Message {
+        #[serde(default)]
+        content: String,
+    }
+
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    let parsed: Line = serde_json::from_str(line)
+        .map_err(|e| ModelError::ParseError(format!("invalid ollama stream line: {}", e)))?;
+    if let Some(error) = parsed.error {
+        return Err(ModelError::InferenceError(format!("ollama: {}", error)));
+    }
+
+    Ok(Some(ChatChunk {
+        content: parsed.message.map(|message| message.content).unwrap_or_default(),
+        done: parsed.done,
+    }))
 }
 
 #[cfg(test)]
@@ -272,4 +410,28 @@ mod tests {
         assert!(prompt.contains("test input"));
         assert!(prompt.contains("ls: list files"));
     }
+
+    #[test]
+    fn test_parse_chat_chunk() {
+        let chunk = parse_chat_chunk(
+            br#"{"model":"qwen2.5:7b","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            chunk,
+            Some(ChatChunk {
+                content: "Hel".to_string(),
+                done: false
+            })
+        );
+
+        let last = parse_chat_chunk(b"{\"done\":true,\"total_duration\":12}\n").unwrap();
+        assert!(last.unwrap().done);
+
+        assert_eq!(parse_chat_chunk(b"\n").unwrap(), None);
+        assert!(parse_chat_chunk(br#"{"error":"model not found"}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("model not found"));
+    }
 }
@@ -1,3 +1,15 @@
+//! OpenAI backend using the Chat Completions API
+//!
+//! Configured from the environment: `OPENAI_API_KEY` is required, and
+//! `AGX_OPENAI_MODEL`, `OPENAI_BASE_URL`, `OPENAI_ORGANIZATION`,
+//! `AGX_OPENAI_TEMPERATURE` and `AGX_OPENAI_MAX_TOKENS` override the
+//! defaults. The base URL can point at
+//! any OpenAI-compatible gateway.
+//!
+//! Setting `OPENAI_API_VERSION` switches to Azure OpenAI: the base URL is
+//! the resource endpoint, the model is the deployment name, and the key is
+//! sent in an `api-key` header.
+
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::{json, Value};
@@ -6,19 +18,101 @@ use std::env;
 use super::backend::ModelBackend;
 use super::types::{ChatMessage, GeneratedPlan, ModelError, PlanContext};
 
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_TEMPERATURE: f32 = 0.7;
+
+/// OpenAI backend configuration
+#[derive(Debug, Clone)]
+pub struct OpenAIConfig {
+    pub api_key: String,
+    pub model: String,
+    pub base_url: String,
+    /// Azure OpenAI `api-version`; `None` for OpenAI and compatible gateways
+    pub api_version: Option<String>,
+    pub organization: Option<String>,
+    pub temperature: f32,
+    pub max_tokens: Option<u32>,
+}
+
+impl Default for OpenAIConfig {
+    fn default() -> Self {
+        Self {
+            api_key: env::var("OPENAI_API_KEY").unwrap_or_default(),
+            model: env::var("AGX_OPENAI_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string()),
+            base_url: env::var("OPENAI_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+            api_version: env::var("OPENAI_API_VERSION").ok(),
+            organization: env::var("OPENAI_ORGANIZATION").ok(),
+            temperature: env::var("AGX_OPENAI_TEMPERATURE")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_TEMPERATURE),
+            max_tokens: env::var("AGX_OPENAI_MAX_TOKENS")
+                .ok()
+                .and_then(|value| value.parse().ok()),
+        }
+    }
+}
+
+impl OpenAIConfig {
+    /// Chat Completions endpoint, deployment-style for Azure
+    fn chat_url(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        match &self.api_version {
+            Some(version) => format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                base, self.model, version
+            ),
+            None => format!("{}/chat/completions", base),
+        }
+    }
+
+    /// Request body for a conversation
+    ///
+    /// Azure picks the model from the deployment in the URL, so it is only
+    /// named in the body for OpenAI.
+    fn request_body(&self, history: &[ChatMessage]) -> Value {
+        let messages: Vec<Value> = history
+            .iter()
+            .map(|msg| {
+                json!({
+                    "role": msg.role,
+                    "content": msg.content
+                })
+            })
+            .collect();
+
+        let mut body = json!({
+            "messages": messages,
+            "temperature": self.temperature
+        });
+        if self.api_version.is_none() {
+            body["model"] = json!(self.model);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        body
+    }
+}
+
 pub struct OpenAIBackend {
     client: Client,
-    model: String,
-    api_key: String,
+    config: OpenAIConfig,
 }
 
 impl OpenAIBackend {
     pub fn new(model: String) -> Self {
-        let api_key = env::var("OPENAI_API_KEY").unwrap_or_default();
+        Self::from_config(OpenAIConfig {
+            model,
+            ..OpenAIConfig::default()
+        })
+    }
+
+    pub fn from_config(config: OpenAIConfig) -> Self {
         Self {
             client: Client::new(),
-            model,
-            api_key,
+            config,
         }
     }
 }
@@ -52,7 +146,10 @@ impl ModelBackend for OpenAIBackend {
             .trim();
 
         let plan: GeneratedPlan = serde_json::from_str(clean_json).map_err(|e| {
-            ModelError::ParseError(format!("Failed to parse OpenAI response: {}. Response: {}", e, clean_json))
+            ModelError::ParseError(format!(
+                "Failed to parse OpenAI response: {}. Response: {}",
+                e, clean_json
+            ))
         })?;
 
         Ok(plan)
@@ -63,12 +160,14 @@ impl ModelBackend for OpenAIBackend {
     }
 
     fn model_name(&self) -> &str {
-        &self.model
+        &self.config.model
     }
 
     async fn health_check(&self) -> Result<(), ModelError> {
-        if self.api_key.is_empty() {
-            return Err(ModelError::ConfigError("OPENAI_API_KEY not set".to_string()));
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "OPENAI_API_KEY not set".to_string(),
+            ));
         }
         Ok(())
     }
@@ -78,31 +177,23 @@ impl ModelBackend for OpenAIBackend {
         history: &[ChatMessage],
         _context: &PlanContext,
     ) -> Result<String, ModelError> {
-        if self.api_key.is_empty() {
-            return Err(ModelError::ConfigError("OPENAI_API_KEY not set".to_string()));
+        if self.config.api_key.is_empty() {
+            return Err(ModelError::ConfigError(
+                "OPENAI_API_KEY not set".to_string(),
+            ));
         }
 
-        let messages: Vec<Value> = history
-            .iter()
-            .map(|msg| {
-                json!({
-                    "role": msg.role,
-                    "content": msg.content
-                })
-            })
-            .collect();
-
-        let body = json!({
-            "model": self.model,
-            "messages": messages,
-            "temperature": 0.7
-        });
+        let mut request = self.client.post(self.config.chat_url());
+        request = match self.config.api_version {
+            Some(_) => request.header("api-key", &self.config.api_key),
+            None => request.header("Authorization", format!("Bearer {}", self.config.api_key)),
+        };
+        if let Some(organization) = &self.config.organization {
+            request = request.header("OpenAI-Organization", organization);
+        }
 
-        let res = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&body)
+        let res = request
+            .json(&self.config.request_body(history))
             .send()
             .await
             .map_err(|e| ModelError::InferenceError(e.to_string()))?;
@@ -123,8 +214,52 @@ impl ModelBackend for OpenAIBackend {
 
         let content = json["choices"][0]["message"]["content"]
             .as_str()
-            .ok_or_else(|| ModelError::ParseError("Invalid response format from OpenAI".to_string()))?;
+            .ok_or_else(|| {
+                ModelError::ParseError("Invalid response format from OpenAI".to_string())
+            })?;
 
         Ok(content.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(api_version: Option<&str>) -> OpenAIConfig {
+        OpenAIConfig {
+            api_key: "key".to_string(),
+            model: "gpt-4o".to_string(),
+            base_url: "https://gateway.example.com/v1/".to_string(),
+            api_version: api_version.map(String::from),
+            organization: None,
+            temperature: 0.2,
+            max_tokens: Some(512),
+        }
+    }
+
+    #[test]
+    fn test_openai_compatible_url_and_body() {
+        let config = config(None);
+        assert_eq!(
+            config.chat_url(),
+            "https://gateway.example.com/v1/chat/completions"
+        );
+
+        let body = config.request_body(&[ChatMessage::user("sort my logs")]);
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["max_tokens"], 512);
+        assert!((body["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+        assert_eq!(body["messages"][0]["content"], "sort my logs");
+    }
+
+    #[test]
+    fn test_azure_deployment_url() {
+        let config = config(Some("2024-06-01"));
+        assert_eq!(
+            config.chat_url(),
+            "https://gateway.example.com/v1/openai/deployments/gpt-4o/chat/completions?api-version=2024-06-01"
+        );
+        assert!(config.request_body(&[]).get("model").is_none());
+    }
+}
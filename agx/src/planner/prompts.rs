@@ -1,4 +1,4 @@
-use crate::planner::{PlanContext, ToolInfo};
+use crate::planner::{ChatMessage, PlanContext, ToolInfo};
 
 pub const SYSTEM_PROMPT_TEMPLATE: &str = "\
 You are the AGX Planner, an intelligent agent responsible for creating execution plans.
@@ -22,7 +22,8 @@ JSON FORMAT:
       \"command\": \"tool-id\",
       \"args\": [\"arg1\", \"arg2\"],
       \"timeout_secs\": 300,
-      \"input_from_task\": null
+      \"input_from_task\": null,
+      \"depends_on\": []
     }
   ]
 }
@@ -32,6 +33,9 @@ JSON FORMAT:
 - args: arguments for the command (empty array if none)
 - timeout_secs: timeout in seconds (default 300)
 - input_from_task: task_number of the task whose output should be piped as input (optional)
+- depends_on: task_numbers of earlier tasks that must finish first without piping their output, e.g. a task reading a file another task writes (optional)
+
+Tasks that neither take input from nor depend on each other run in parallel, so only chain tasks that really need each other.
 
 EXAMPLES:
 
@@ -76,6 +80,35 @@ Plan:
     }
   ]
 }
+
+User: \"Download a.html and b.html from example.com, then count the words in both\"
+Plan:
+{
+  \"tasks\": [
+    {
+      \"task_number\": 1,
+      \"command\": \"curl\",
+      \"args\": [\"-o\", \"a.html\", \"https://example.com/a.html\"],
+      \"timeout_secs\": 300,
+      \"input_from_task\": null
+    },
+    {
+      \"task_number\": 2,
+      \"command\": \"curl\",
+      \"args\": [\"-o\", \"b.html\", \"https://example.com/b.html\"],
+      \"timeout_secs\": 300,
+      \"input_from_task\": null
+    },
+    {
+      \"task_number\": 3,
+      \"command\": \"wc\",
+      \"args\": [\"-w\", \"a.html\", \"b.html\"],
+      \"timeout_secs\": 300,
+      \"input_from_task\": null,
+      \"depends_on\": [1, 2]
+    }
+  ]
+}
 ";
 
 pub fn build_system_prompt(context: &PlanContext) -> String {
@@ -95,10 +128,30 @@ pub fn build_user_prompt(instruction: &str, context: &PlanContext) -> String {
     if let Some(summary) = &context.input_summary {
         prompt = format!("Context:\n{}\n\n{}", summary, prompt);
     }
+
+    // Passages of the user's own documents, so arguments match what they contain
+    if let Some(documents) = &context.documents {
+        prompt = format!("Relevant documents:\n{}\n\n{}", documents, prompt);
+    }
     
     prompt
 }
 
+/// Chat messages asking for a plan, for backends with a chat API
+///
+/// Existing tasks are sent to Delta for refinement; otherwise Echo's system
+/// prompt is sent with the instruction.
+pub fn build_plan_messages(instruction: &str, context: &PlanContext) -> Vec<ChatMessage> {
+    if context.existing_tasks.is_empty() {
+        vec![
+            ChatMessage::system(build_system_prompt(context)),
+            ChatMessage::user(build_user_prompt(instruction, context)),
+        ]
+    } else {
+        vec![ChatMessage::user(build_delta_prompt(instruction, context))]
+    }
+}
+
 pub fn build_delta_prompt(instruction: &str, context: &PlanContext) -> String {
     let tools_description = context
         .tool_registry
@@ -110,6 +163,18 @@ pub fn build_delta_prompt(instruction: &str, context: &PlanContext) -> String {
     let existing_plan_json = serde_json::to_string_pretty(&context.existing_tasks)
         .unwrap_or_else(|_| "[]".to_string());
 
+    // Describe the input, e.g. attached files, so arguments match its real structure
+    let input_section = context
+        .input_summary
+        .as_ref()
+        .map(|summary| format!("INPUT DATA:\n{}\n\n", summary))
+        .unwrap_or_default();
+    let documents_section = context
+        .documents
+        .as_ref()
+        .map(|documents| format!("RELEVANT DOCUMENTS:\n{}\n\n", documents))
+        .unwrap_or_default();
+
     format!(
         "You are Delta, an expert QA agent. Your goal is to validate and refine the following execution plan.\n\
          \n\
@@ -118,13 +183,13 @@ pub fn build_delta_prompt(instruction: &str, context: &PlanContext) -> String {
          Current Plan:\n\
          {}\n\
          \n\
-         AVAILABLE TOOLS:\n\
+         {}{}AVAILABLE TOOLS:\n\
          {}\n\
          \n\
          CRITIQUE & FIX:\n\
          1. Check if the plan correctly fulfills the user instruction.\n\
          2. Verify that all tools exist and arguments are correct.\n\
-         3. Ensure task dependencies (input_from_task) are logical.\n\
+         3. Ensure task dependencies (input_from_task, depends_on) are logical, and that tasks not needing each other's output or files don't depend on each other, so they run in parallel.\n\
          4. If the plan is perfect, return it exactly as is.\n\
          5. If there are errors, return the CORRECTED plan.\n\
          \n\
@@ -137,10 +202,11 @@ pub fn build_delta_prompt(instruction: &str, context: &PlanContext) -> String {
                \"command\": \"tool-id\",\n\
                \"args\": [\"arg1\"],\n\
                \"timeout_secs\": 300,\n\
-               \"input_from_task\": null\n\
+               \"input_from_task\": null,\n\
+               \"depends_on\": []\n\
              }}\n\
            ]\n\
          }}",
-        instruction, existing_plan_json, tools_description
+        instruction, existing_plan_json, input_section, documents_section, tools_description
     )
 }
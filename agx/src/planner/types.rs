@@ -3,12 +3,14 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Context provided to the model for plan generation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlanContext {
     /// Available tools/commands with descriptions
     pub tool_registry: Vec<ToolInfo>,
     /// Summary of input data (optional)
     pub input_summary: Option<String>,
+    /// Passages of indexed local documents relevant to the instruction
+    pub documents: Option<String>,
     /// Existing tasks for refinement (used by Delta model)
     pub existing_tasks: Vec<PlanStep>,
     /// Maximum number of tasks to generate
@@ -20,6 +22,7 @@ impl Default for PlanContext {
         Self {
             tool_registry: Vec::new(),
             input_summary: None,
+            documents: None,
             existing_tasks: Vec::new(),
             max_tasks: 20,
         }
@@ -1,39 +1,68 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use serde::Deserialize;
+
 use crate::input::InputSummary;
 use crate::plan::{PlanStep, WorkflowPlan};
 use crate::registry::ToolRegistry;
 
+use super::anthropic::{AnthropicBackend, AnthropicConfig};
 use super::backend::ModelBackend;
 use super::candle::{CandleBackend, CandleConfig, ModelRole};
+#[cfg(unix)]
+use super::daemon::DaemonBackend;
+use super::device::DeviceSpec;
+use super::gemini::{GeminiBackend, GeminiConfig};
 use super::ollama::{OllamaBackend, OllamaConfig};
+use super::openai::{OpenAIBackend, OpenAIConfig};
 use super::types::{ModelError, PlanContext, ToolInfo};
 
+/// Default Hugging Face repository for Echo's Candle model
+const DEFAULT_ECHO_REPO: &str = "Qwen/Qwen2.5-7B-Instruct-GGUF";
+/// Default Hugging Face repository for Delta's Candle model
+const DEFAULT_DELTA_REPO: &str = "Qwen/Qwen2.5-Coder-1.5B-Instruct-GGUF";
+const DEFAULT_QUANTIZATION: &str = "q4_k_m";
+const DEFAULT_CONTEXT_LENGTH: usize = 2048;
+/// GGUF quantizations Candle can load; i-quants such as `iq4_xs` are not
+const SUPPORTED_QUANTIZATIONS: &[&str] = &[
+    "f32", "f16", "bf16", "q8_0", "q8_1", "q6_k", "q5_0", "q5_1", "q5_k_s", "q5_k_m", "q4_0",
+    "q4_1", "q4_k_s", "q4_k_m", "q3_k_s", "q3_k_m", "q3_k_l", "q2_k",
+];
+
 /// Backend selection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackendKind {
     Ollama,
     Candle,
+    Anthropic,
+    Gemini,
+    OpenAI,
 }
 
 impl BackendKind {
     pub fn from_env() -> Self {
         match std::env::var("AGX_BACKEND") {
-            Ok(value) => {
-                let normalized = value.to_lowercase();
-                match normalized.as_str() {
-                    "candle" => BackendKind::Candle,
-                    "" | "ollama" => BackendKind::Ollama,
-                    _ => {
-                        log::warn!("Unknown backend '{}', defaulting to ollama", value);
-                        BackendKind::Ollama
-                    }
-                }
-            }
+            Ok(value) => Self::parse(&value).unwrap_or_else(|| {
+                log::warn!("Unknown backend '{}', defaulting to ollama", value);
+                BackendKind::Ollama
+            }),
             Err(_) => BackendKind::Ollama,
         }
     }
 
+    /// Parse a backend name, as given in `AGX_BACKEND` or the config file
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "candle" => Some(BackendKind::Candle),
+            "anthropic" | "claude" => Some(BackendKind::Anthropic),
+            "gemini" => Some(BackendKind::Gemini),
+            "openai" | "azure" => Some(BackendKind::OpenAI),
+            "" | "ollama" => Some(BackendKind::Ollama),
+            _ => None,
+        }
+    }
+
     /// Create backend explicitly for Delta validation
     /// Uses the same backend as environment, but forces Delta model role
     pub fn for_delta() -> Result<Self, String> {
@@ -42,32 +71,359 @@ impl BackendKind {
     }
 }
 
+/// Candle model used for one role
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSettings {
+    /// Hugging Face repository holding the GGUF files
+    pub repo: String,
+    /// GGUF file in `repo`; named after the repository and quantization when unset
+    pub file: Option<String>,
+    /// Quantization level, such as `q4_k_m` or `q8_0`
+    pub quantization: String,
+    /// Repository holding `tokenizer.json`; `repo` without `-GGUF` when unset
+    pub tokenizer_repo: Option<String>,
+    /// Context window in tokens
+    pub context_length: usize,
+    /// Tokens kept in the KV cache; `context_length` when unset
+    pub kv_cache: Option<usize>,
+    /// Device to run on
+    pub device: DeviceSpec,
+    /// Local GGUF file to use instead of downloading one
+    pub path: Option<PathBuf>,
+    /// LoRA adapter directory, as Axolotl or Unsloth save it, merged into
+    /// the model as it loads
+    pub adapter: Option<PathBuf>,
+}
+
+impl ModelSettings {
+    pub fn defaults(role: ModelRole) -> Self {
+        let repo = match role {
+            ModelRole::Echo => DEFAULT_ECHO_REPO,
+            ModelRole::Delta => DEFAULT_DELTA_REPO,
+        };
+        Self {
+            repo: repo.to_string(),
+            file: None,
+            quantization: DEFAULT_QUANTIZATION.to_string(),
+            tokenizer_repo: None,
+            context_length: DEFAULT_CONTEXT_LENGTH,
+            kv_cache: None,
+            device: DeviceSpec::Auto,
+            path: None,
+            adapter: None,
+        }
+    }
+
+    /// Reject a quantization Candle can't load before downloading the file
+    fn validate(&self) -> Result<(), String> {
+        let quantization = self.quantization.to_lowercase();
+        if self.file.is_none()
+            && self.path.is_none()
+            && !SUPPORTED_QUANTIZATIONS.contains(&quantization.as_str())
+        {
+            return Err(format!(
+                "unsupported quantization '{}' for {}; use one of {}",
+                self.quantization,
+                self.repo,
+                SUPPORTED_QUANTIZATIONS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// GGUF file to download, e.g. `qwen2.5-7b-instruct-q4_k_m.gguf`
+    pub fn gguf_file(&self) -> String {
+        self.file.clone().unwrap_or_else(|| {
+            let name = self.repo.rsplit('/').next().unwrap_or(&self.repo);
+            format!(
+                "{}-{}.gguf",
+                strip_gguf_suffix(name).to_lowercase(),
+                self.quantization.to_lowercase()
+            )
+        })
+    }
+
+    pub fn tokenizer_repo(&self) -> String {
+        self.tokenizer_repo
+            .clone()
+            .unwrap_or_else(|| strip_gguf_suffix(&self.repo).to_string())
+    }
+
+    /// The model file or repository, for display and saved sessions
+    pub fn describe(&self) -> String {
+        let model = match &self.path {
+            Some(path) => path.display().to_string(),
+            None => format!("{}/{}", self.repo, self.gguf_file()),
+        };
+        match &self.adapter {
+            Some(adapter) => format!("{} + LoRA {}", model, adapter.display()),
+            None => model,
+        }
+    }
+}
+
+fn strip_gguf_suffix(name: &str) -> &str {
+    name.strip_suffix("-GGUF")
+        .or_else(|| name.strip_suffix("-gguf"))
+        .unwrap_or(name)
+}
+
+/// Model settings for a role given in the config file or environment
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ModelOverrides {
+    repo: Option<String>,
+    file: Option<String>,
+    quantization: Option<String>,
+    tokenizer_repo: Option<String>,
+    context_length: Option<usize>,
+    kv_cache: Option<usize>,
+    device: Option<DeviceSpec>,
+    path: Option<PathBuf>,
+    adapter: Option<PathBuf>,
+}
+
+impl ModelOverrides {
+    /// `AGX_<ROLE>_REPO`, `_FILE`, `_QUANT`, `_TOKENIZER_REPO`,
+    /// `_CONTEXT_LENGTH`, `_KV_CACHE`, `_DEVICE` and `_ADAPTER`;
+    /// `AGX_<ROLE>_MODEL` or `AGX_MODEL_PATH` names a local file
+    fn from_env(role: ModelRole) -> Result<Self, String> {
+        let prefix = match role {
+            ModelRole::Echo => "AGX_ECHO",
+            ModelRole::Delta => "AGX_DELTA",
+        };
+        let var = |name: &str| {
+            std::env::var(format!("{prefix}_{name}"))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+
+        let context_length = match var("CONTEXT_LENGTH")
+            .or_else(|| std::env::var("AGX_CANDLE_CONTEXT_SIZE").ok())
+        {
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid context length '{}'", value))?,
+            ),
+            None => None,
+        };
+
+        let kv_cache = match var("KV_CACHE").or_else(|| std::env::var("AGX_CANDLE_KV_CACHE").ok()) {
+            Some(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid KV cache size '{}'", value))?,
+            ),
+            None => None,
+        };
+
+        let device = match var("DEVICE").or_else(|| std::env::var("AGX_DEVICE").ok()) {
+            Some(value) => Some(DeviceSpec::parse(&value)?),
+            None => None,
+        };
+
+        Ok(Self {
+            repo: var("REPO"),
+            file: var("FILE"),
+            quantization: var("QUANT"),
+            tokenizer_repo: var("TOKENIZER_REPO"),
+            context_length,
+            kv_cache,
+            device,
+            path: var("MODEL")
+                .or_else(|| std::env::var("AGX_MODEL_PATH").ok())
+                .map(PathBuf::from),
+            adapter: var("ADAPTER").map(PathBuf::from),
+        })
+    }
+
+    fn apply(self, settings: &mut ModelSettings) {
+        if let Some(repo) = self.repo {
+            settings.repo = repo;
+        }
+        if let Some(file) = self.file {
+            settings.file = Some(file);
+        }
+        if let Some(quantization) = self.quantization {
+            settings.quantization = quantization;
+        }
+        if let Some(tokenizer_repo) = self.tokenizer_repo {
+            settings.tokenizer_repo = Some(tokenizer_repo);
+        }
+        if let Some(context_length) = self.context_length {
+            settings.context_length = context_length;
+        }
+        if let Some(kv_cache) = self.kv_cache {
+            settings.kv_cache = Some(kv_cache);
+        }
+        if let Some(device) = self.device {
+            settings.device = device;
+        }
+        if let Some(path) = self.path {
+            settings.path = Some(path);
+        }
+        if let Some(adapter) = self.adapter {
+            settings.adapter = Some(adapter);
+        }
+    }
+}
+
+/// The planner config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlannerFile {
+    backend: Option<String>,
+    echo: ModelOverrides,
+    delta: ModelOverrides,
+}
+
+impl PlannerFile {
+    /// Parse a config file, as JSON if it ends in `.json` and TOML otherwise
+    fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|error| error.to_string())
+        } else {
+            toml::from_str(&text).map_err(|error| error.to_string())
+        };
+        parsed.map_err(|error| format!("invalid planner config {}: {error}", path.display()))
+    }
+
+    /// The config file in use: `AGX_PLANNER_CONFIG`, or the first of
+    /// `planner.toml` and `planner.json` in the agx config directory
+    fn locate() -> Result<Option<PathBuf>, String> {
+        if let Ok(path) = std::env::var("AGX_PLANNER_CONFIG") {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(format!(
+                    "AGX_PLANNER_CONFIG points to a missing file: {}",
+                    path.display()
+                ));
+            }
+            return Ok(Some(path));
+        }
+
+        let Some(dir) = dirs::config_dir().map(|dir| dir.join("agx")) else {
+            return Ok(None);
+        };
+        Ok(["planner.toml", "planner.json"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file()))
+    }
+}
+
 /// Planner configuration
 pub struct PlannerConfig {
     pub backend: BackendKind,
     /// Optional model role override (for Delta validation)
     /// If None, uses AGX_MODEL_ROLE environment variable
     pub model_role_override: Option<ModelRole>,
+    /// Candle model for Echo
+    pub echo_model: ModelSettings,
+    /// Candle model for Delta
+    pub delta_model: ModelSettings,
 }
 
 impl PlannerConfig {
-    pub fn from_env() -> Self {
-        let backend = BackendKind::from_env();
-        Self {
+    /// Load the config file, if any, with environment variables taking precedence
+    pub fn load() -> Result<Self, String> {
+        let file = match PlannerFile::locate()? {
+            Some(path) => PlannerFile::from_file(&path)?,
+            None => PlannerFile::default(),
+        };
+        Self::from_file_and_env(file)
+    }
+
+    fn from_file_and_env(file: PlannerFile) -> Result<Self, String> {
+        let backend = match (std::env::var("AGX_BACKEND"), &file.backend) {
+            (Err(_), Some(name)) => BackendKind::parse(name)
+                .ok_or_else(|| format!("unknown backend '{}' in planner config", name))?,
+            _ => BackendKind::from_env(),
+        };
+
+        let mut echo_model = ModelSettings::defaults(ModelRole::Echo);
+        file.echo.apply(&mut echo_model);
+        ModelOverrides::from_env(ModelRole::Echo)?.apply(&mut echo_model);
+        echo_model.validate()?;
+
+        let mut delta_model = ModelSettings::defaults(ModelRole::Delta);
+        file.delta.apply(&mut delta_model);
+        ModelOverrides::from_env(ModelRole::Delta)?.apply(&mut delta_model);
+        delta_model.validate()?;
+
+        Ok(Self {
             backend,
             model_role_override: None,
-        }
+            echo_model,
+            delta_model,
+        })
     }
 
     /// Create config explicitly for Delta validation
     /// This avoids environment variable mutation and is thread-safe
     pub fn for_delta() -> Result<Self, String> {
-        let backend = BackendKind::for_delta()?;
         Ok(Self {
-            backend,
             model_role_override: Some(ModelRole::Delta),
+            ..Self::load()?
         })
     }
+
+    /// The role to plan as: the override, or `AGX_MODEL_ROLE` (default Echo)
+    pub fn role(&self) -> ModelRole {
+        self.model_role_override
+            .unwrap_or_else(|| match std::env::var("AGX_MODEL_ROLE") {
+                Ok(r) if r.eq_ignore_ascii_case("delta") => ModelRole::Delta,
+                _ => ModelRole::Echo,
+            })
+    }
+
+    pub fn model(&self, role: ModelRole) -> &ModelSettings {
+        match role {
+            ModelRole::Echo => &self.echo_model,
+            ModelRole::Delta => &self.delta_model,
+        }
+    }
+
+    /// Load a role's Candle model, downloading it first if needed
+    pub async fn candle_backend(&self, role: ModelRole) -> Result<CandleBackend, ModelError> {
+        let settings = self.model(role);
+        let model_path = async {
+            crate::models::ModelManager::new()?
+                .ensure_candle_model(settings)
+                .await
+        }
+        .await
+        .map_err(|e| ModelError::LoadError(format!("{:#}", e)))?;
+
+        let candle_config = CandleConfig {
+            kv_cache_size: settings.kv_cache,
+            device: Some(settings.device),
+            adapter_path: settings.adapter.clone(),
+            ..CandleConfig::for_model(role, model_path, settings.context_length)
+        };
+        CandleBackend::new(candle_config).await
+    }
+
+    /// The Candle model for `role` and the device it runs on, served by
+    /// `agx SERVE-PLANNER` when it has the same model loaded, and loaded in
+    /// this process otherwise, as it always is off Unix
+    pub async fn candle_planner(
+        &self,
+        role: ModelRole,
+    ) -> Result<(Box<dyn ModelBackend>, String), ModelError> {
+        #[cfg(unix)]
+        if let Some(backend) = DaemonBackend::connect(role, &self.model(role).describe()).await {
+            let device = backend.device_name();
+            return Ok((Box::new(backend), device));
+        }
+        let backend = self.candle_backend(role).await?;
+        let device = backend.device_name();
+        Ok((Box::new(backend), device))
+    }
 }
 
 /// Main planner that wraps backend implementations
@@ -112,21 +468,12 @@ impl Planner {
                 let ollama_config = OllamaConfig::default();
                 Arc::new(OllamaBackend::from_config(ollama_config))
             }
-            BackendKind::Candle => {
-                // Use override if provided, otherwise read from environment
-                let role = if let Some(override_role) = config.model_role_override {
-                    override_role
-                } else {
-                    match std::env::var("AGX_MODEL_ROLE") {
-                        Ok(r) if r.eq_ignore_ascii_case("delta") => ModelRole::Delta,
-                        _ => ModelRole::Echo,
-                    }
-                };
-
-                let candle_config = CandleConfig::from_env(role)?;
-                let backend = CandleBackend::new(candle_config).await?;
-                Arc::new(backend)
+            BackendKind::Candle => Arc::from(config.candle_planner(config.role()).await?.0),
+            BackendKind::Anthropic => {
+                Arc::new(AnthropicBackend::from_config(AnthropicConfig::default()))
             }
+            BackendKind::Gemini => Arc::new(GeminiBackend::from_config(GeminiConfig::default())),
+            BackendKind::OpenAI => Arc::new(OpenAIBackend::from_config(OpenAIConfig::default())),
         };
 
         Ok(Self { backend })
@@ -177,6 +524,7 @@ impl Planner {
         let context = PlanContext {
             tool_registry,
             input_summary,
+            documents: crate::index::retrieve(instruction).await,
             existing_tasks: Vec::new(),
             max_tasks: 20,
         };
@@ -188,12 +536,13 @@ impl Planner {
             .await
             .map_err(|e| format!("Backend error: {}", e))?;
 
-        // Convert to canonical format (with task numbering)
+        // Convert to canonical format, tagged for the workers its tools need
         let plan = WorkflowPlan {
             plan_id: None,
             plan_description: None,
             tasks: generated.tasks,
-        };
+        }
+        .with_required_tags(registry);
 
         let raw_json =
             serde_json::to_string(&plan).map_err(|e| format!("JSON serialization error: {}", e))?;
@@ -227,7 +576,7 @@ impl Planner {
     }
 
     /// Async version of plan_with_existing
-    async fn plan_with_existing_async(
+    pub async fn plan_with_existing_async(
         &self,
         instruction: &str,
         input: &InputSummary,
@@ -253,6 +602,7 @@ impl Planner {
         let context = PlanContext {
             tool_registry,
             input_summary,
+            documents: None,
             existing_tasks: existing_tasks.to_vec(),
             max_tasks: 20,
         };
@@ -264,12 +614,13 @@ impl Planner {
             .await
             .map_err(|e| format!("Backend error: {}", e))?;
 
-        // Convert to canonical format (with task numbering)
+        // Convert to canonical format, tagged for the workers its tools need
         let plan = WorkflowPlan {
             plan_id: None,
             plan_description: None,
             tasks: generated.tasks,
-        };
+        }
+        .with_required_tags(registry);
 
         let raw_json =
             serde_json::to_string(&plan).map_err(|e| format!("JSON serialization error: {}", e))?;
@@ -287,3 +638,83 @@ impl Planner {
         self.backend.health_check().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_models_keep_the_qwen_files() {
+        let echo = ModelSettings::defaults(ModelRole::Echo);
+        assert_eq!(echo.gguf_file(), "qwen2.5-7b-instruct-q4_k_m.gguf");
+        assert_eq!(echo.tokenizer_repo(), "Qwen/Qwen2.5-7B-Instruct");
+
+        let delta = ModelSettings::defaults(ModelRole::Delta);
+        assert_eq!(
+            delta.describe(),
+            "Qwen/Qwen2.5-Coder-1.5B-Instruct-GGUF/qwen2.5-coder-1.5b-instruct-q4_k_m.gguf"
+        );
+    }
+
+    #[test]
+    fn config_file_overrides_each_role() {
+        let file: PlannerFile = toml::from_str(
+            r#"
+            backend = "candle"
+
+            [echo]
+            repo = "bartowski/Llama-3.2-3B-Instruct-GGUF"
+            quantization = "Q8_0"
+            tokenizer_repo = "meta-llama/Llama-3.2-3B-Instruct"
+            context_length = 8192
+            kv_cache = 1024
+            device = "cuda:1"
+
+            [delta]
+            file = "delta.gguf"
+            adapter = "/models/delta-lora"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.backend.as_deref(), Some("candle"));
+
+        let mut echo = ModelSettings::defaults(ModelRole::Echo);
+        file.echo.apply(&mut echo);
+        assert_eq!(echo.gguf_file(), "llama-3.2-3b-instruct-q8_0.gguf");
+        assert_eq!(echo.tokenizer_repo(), "meta-llama/Llama-3.2-3B-Instruct");
+        assert_eq!(echo.context_length, 8192);
+        assert_eq!(echo.kv_cache, Some(1024));
+        assert_eq!(echo.device, DeviceSpec::Cuda(1));
+
+        let mut delta = ModelSettings::defaults(ModelRole::Delta);
+        file.delta.apply(&mut delta);
+        assert_eq!(delta.gguf_file(), "delta.gguf");
+        assert_eq!(delta.repo, DEFAULT_DELTA_REPO);
+        assert_eq!(delta.device, DeviceSpec::Auto);
+        assert_eq!(
+            delta.describe(),
+            "Qwen/Qwen2.5-Coder-1.5B-Instruct-GGUF/delta.gguf + LoRA /models/delta-lora"
+        );
+
+        assert!(toml::from_str::<PlannerFile>("[echo]\nquant = \"q8_0\"").is_err());
+        assert!(toml::from_str::<PlannerFile>("[echo]\ndevice = \"tpu\"").is_err());
+    }
+
+    #[test]
+    fn unsupported_quantizations_are_rejected() {
+        let mut echo = ModelSettings::defaults(ModelRole::Echo);
+        echo.quantization = "Q5_K_M".to_string();
+        assert!(echo.validate().is_ok());
+
+        echo.quantization = "iq4_xs".to_string();
+        let error = echo.validate().unwrap_err();
+        assert!(
+            error.contains("unsupported quantization 'iq4_xs'"),
+            "{error}"
+        );
+
+        // A named file is taken as given
+        echo.file = Some("model-iq4_xs.gguf".to_string());
+        assert!(echo.validate().is_ok());
+    }
+}
@@ -0,0 +1,359 @@
+//! Checks on the commands and args of generated plans
+//!
+//! Workers enforce their own limits, but a plan breaking these rules is
+//! stopped before it is submitted and flagged during review:
+//!
+//! - absolute paths in args must be inside a workspace directory (the
+//!   current directory unless configured), and relative paths must not
+//!   climb out of it with `..`;
+//! - args must not contain shell metacharacters, except for tools whose
+//!   own languages use them, like `jq` and `grep`;
+//! - blocked commands, such as shells and `rm`, may not run, directly or
+//!   through a launcher like `xargs` or `env`.
+//!
+//! The rules are read from `AGX_POLICY_CONFIG`, or `policy.toml` /
+//! `policy.json` in the agx config directory.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::logging;
+use crate::plan::WorkflowPlan;
+
+/// Characters a shell would treat as more than part of a word
+const METACHARACTERS: &[char] = &[';', '&', '|', '`', '$', '<', '>', '\n', '\r'];
+
+/// Commands that run their args as another command
+const LAUNCHERS: &[&str] = &["env", "xargs", "nohup", "nice", "timeout", "time", "exec"];
+
+/// The rules plans are checked against
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Directories absolute paths must be inside; the current directory
+    /// when empty
+    pub workspaces: Vec<PathBuf>,
+    /// Reject args containing shell metacharacters
+    pub deny_metacharacters: bool,
+    /// Tools whose args may contain metacharacters
+    pub metacharacter_tools: Vec<String>,
+    /// Commands no task may run
+    pub blocked_commands: Vec<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        Self {
+            workspaces: Vec::new(),
+            deny_metacharacters: true,
+            metacharacter_tools: strings(&["jq", "grep"]),
+            blocked_commands: strings(&[
+                "sh", "bash", "zsh", "dash", "sudo", "su", "rm", "dd", "mkfs", "chmod", "chown",
+            ]),
+        }
+    }
+}
+
+/// A task breaking a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub task_number: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "task {}: {}", self.task_number, self.message)
+    }
+}
+
+impl Policy {
+    /// Parse a config file, as JSON if it ends in `.json` and TOML otherwise
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|error| error.to_string())
+        } else {
+            toml::from_str(&text).map_err(|error| error.to_string())
+        };
+        parsed.map_err(|error| format!("invalid policy config {}: {error}", path.display()))
+    }
+
+    /// The policy for this process, loaded once
+    pub fn load() -> Result<Self, String> {
+        static LOADED: OnceLock<Result<Policy, String>> = OnceLock::new();
+        LOADED
+            .get_or_init(|| match locate()? {
+                Some(path) => {
+                    logging::info(&format!("loading policy from {}", path.display()));
+                    Self::from_file(&path)
+                }
+                None => Ok(Self::default()),
+            })
+            .clone()
+    }
+
+    /// Every rule the plan's tasks break, in task order
+    pub fn check(&self, plan: &WorkflowPlan) -> Vec<Violation> {
+        let workspaces = self.workspaces();
+        let mut violations = Vec::new();
+
+        for task in &plan.tasks {
+            let mut violate = |message: String| {
+                violations.push(Violation {
+                    task_number: task.task_number,
+                    message,
+                })
+            };
+
+            let command = file_name(&task.command);
+            if self.is_blocked(command) {
+                violate(format!("`{command}` is a blocked command"));
+            }
+            if LAUNCHERS.contains(&command) {
+                for arg in &task.args {
+                    let launched = file_name(arg);
+                    if self.is_blocked(launched) {
+                        violate(format!(
+                            "`{command}` would run `{launched}`, a blocked command"
+                        ));
+                    }
+                }
+            }
+
+            let metacharacters_allowed = !self.deny_metacharacters
+                || self.metacharacter_tools.iter().any(|tool| tool == command);
+            for arg in &task.args {
+                if !metacharacters_allowed && arg.contains(METACHARACTERS) {
+                    violate(format!("`{arg}` contains shell metacharacters"));
+                }
+                for path in path_candidates(arg) {
+                    if let Some(problem) = check_path(path, &workspaces) {
+                        violate(format!("`{arg}` {problem}"));
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    fn is_blocked(&self, command: &str) -> bool {
+        self.blocked_commands
+            .iter()
+            .any(|blocked| blocked == command)
+    }
+
+    fn workspaces(&self) -> Vec<PathBuf> {
+        let current = std::env::current_dir().ok();
+        if self.workspaces.is_empty() {
+            return current.into_iter().collect();
+        }
+        self.workspaces
+            .iter()
+            .map(|dir| match &current {
+                Some(current) => normalize(&current.join(dir)),
+                None => normalize(dir),
+            })
+            .collect()
+    }
+}
+
+/// One message listing every violation, a line each
+pub fn violation_message(violations: &[Violation]) -> String {
+    let lines: Vec<String> = violations
+        .iter()
+        .map(|violation| format!("  {violation}"))
+        .collect();
+    format!("plan breaks the tool policy:\n{}", lines.join("\n"))
+}
+
+/// The config file in use: `AGX_POLICY_CONFIG`, or the first of
+/// `policy.toml` and `policy.json` in the agx config directory
+fn locate() -> Result<Option<PathBuf>, String> {
+    if let Ok(path) = std::env::var("AGX_POLICY_CONFIG") {
+        let path = PathBuf::from(path);
+        if !path.is_file() {
+            return Err(format!(
+                "AGX_POLICY_CONFIG points to a missing file: {}",
+                path.display()
+            ));
+        }
+        return Ok(Some(path));
+    }
+
+    let Some(dir) = dirs::config_dir().map(|dir| dir.join("agx")) else {
+        return Ok(None);
+    };
+    Ok(["policy.toml", "policy.json"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file()))
+}
+
+fn file_name(command: &str) -> &str {
+    Path::new(command)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(command)
+}
+
+/// The arg, and the value of an `--option=value` arg
+fn path_candidates(arg: &str) -> Vec<&str> {
+    let mut candidates = vec![arg];
+    if let Some((_, value)) = arg.strip_prefix('-').and_then(|rest| rest.split_once('=')) {
+        candidates.push(value);
+    }
+    candidates
+}
+
+fn check_path(path: &str, workspaces: &[PathBuf]) -> Option<&'static str> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        let path = normalize(path);
+        if !workspaces.iter().any(|dir| path.starts_with(dir)) {
+            return Some("is outside the workspace");
+        }
+        return None;
+    }
+
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return Some("climbs out of the workspace"),
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::PlanStep;
+
+    fn plan(tasks: &[(&str, &[&str])]) -> WorkflowPlan {
+        WorkflowPlan {
+            plan_id: None,
+            plan_description: None,
+            tasks: tasks
+                .iter()
+                .zip(1..)
+                .map(|((command, args), task_number)| PlanStep {
+                    task_number,
+                    command: command.to_string(),
+                    args: args.iter().map(|arg| arg.to_string()).collect(),
+                    timeout_secs: 300,
+                    input_from_task: None,
+                    depends_on: Vec::new(),
+                    tags: Vec::new(),
+                    limits: Default::default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn policy() -> Policy {
+        Policy {
+            workspaces: vec![PathBuf::from("/work")],
+            ..Policy::default()
+        }
+    }
+
+    fn tasks_breaking(policy: &Policy, plan: &WorkflowPlan) -> Vec<(u32, String)> {
+        policy
+            .check(plan)
+            .into_iter()
+            .map(|violation| (violation.task_number, violation.message))
+            .collect()
+    }
+
+    #[test]
+    fn paths_must_stay_inside_the_workspace() {
+        let plan = plan(&[
+            ("sort", &["/work/data/input.txt", "--output=/work/out.txt"]),
+            ("cut", &["/etc/passwd"]),
+            ("sort", &["--output=/work/../etc/cron.d/job"]),
+            ("uniq", &["data/../../secrets", "data/../input.txt"]),
+        ]);
+        assert_eq!(
+            tasks_breaking(&policy(), &plan),
+            [
+                (2, "`/etc/passwd` is outside the workspace".to_string()),
+                (
+                    3,
+                    "`--output=/work/../etc/cron.d/job` is outside the workspace".to_string()
+                ),
+                (
+                    4,
+                    "`data/../../secrets` climbs out of the workspace".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn metacharacters_are_refused_outside_exempt_tools() {
+        let plan = plan(&[
+            ("sort", &["-k", "2; rm -rf ~"]),
+            ("jq", &[".items[] | select(.size > 1)"]),
+            ("grep", &["-E", "error|warn$"]),
+            ("cut", &["-d", "$(id)"]),
+        ]);
+        assert_eq!(
+            tasks_breaking(&policy(), &plan),
+            [
+                (1, "`2; rm -rf ~` contains shell metacharacters".to_string()),
+                (4, "`$(id)` contains shell metacharacters".to_string()),
+            ]
+        );
+
+        let relaxed = Policy {
+            deny_metacharacters: false,
+            ..policy()
+        };
+        assert!(relaxed.check(&plan).is_empty());
+    }
+
+    #[test]
+    fn blocked_commands_are_refused_directly_and_through_launchers() {
+        let plan = plan(&[
+            ("/bin/rm", &["-rf", "data"]),
+            ("xargs", &["rm"]),
+            ("env", &["FOO=bar", "sort"]),
+        ]);
+        assert_eq!(
+            tasks_breaking(&policy(), &plan),
+            [
+                (1, "`rm` is a blocked command".to_string()),
+                (2, "`xargs` would run `rm`, a blocked command".to_string()),
+            ]
+        );
+
+        let config: Policy = toml::from_str("blocked_commands = [\"curl\"]").unwrap();
+        assert!(config.deny_metacharacters);
+        assert_eq!(config.blocked_commands, ["curl"]);
+        assert!(toml::from_str::<Policy>("blocked = []").is_err());
+    }
+}
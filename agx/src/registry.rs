@@ -1,30 +1,321 @@
+//! Tools the planner may use
+//!
+//! The registry starts from the built-in text tools and adds, in order of
+//! precedence:
+//!
+//! 1. tools listed in the config file (`AGX_TOOLS_CONFIG`, or
+//!    `tools.toml` / `tools.json` in the agx config directory), which
+//!    replace built-ins with the same ID;
+//! 2. Agentic Units found on `PATH` (or listed in the config), described by
+//!    running them with `--describe`;
+//! 3. with `catalog = true` or `AGX_TOOLS_CATALOG=1`, the tools AGQ's
+//!    fleet catalog says live workers can run.
+//!
+//! Discovered and catalog tools never replace one that is already known.
+//!
+//! Tools may declare the worker tags they need, such as `gpu` for OCR or
+//! `network` for `curl`; planned tasks are tagged with them so AGQ routes
+//! their jobs to workers that can run them.
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::logging;
+
+/// Maximum time to wait for a tool to describe itself
+const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Prefix of Agentic Unit binaries looked for on `PATH`
+const DEFAULT_UNIT_PREFIX: &str = "agx-";
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Tool {
-    pub id: &'static str,
-    pub command: &'static str,
-    pub description: &'static str,
-    pub patterns: &'static [&'static str],
-    pub ok_exit_codes: &'static [i32],
+    pub id: String,
+    pub command: String,
+    pub description: String,
+    pub patterns: Vec<String>,
+    pub ok_exit_codes: Vec<i32>,
+    /// Worker tags the tool needs, e.g. `gpu` or `network`
+    pub requires: Vec<String>,
+}
+
+impl Tool {
+    /// A tool from an Agentic Unit's `--describe` model card
+    ///
+    /// Its capabilities become the patterns the planner matches on, and
+    /// its `requires` list the worker tags it needs.
+    pub fn from_model_card(command: &str, card: &serde_json::Value) -> Option<Self> {
+        let name = card.get("name")?.as_str()?;
+        let description = card.get("description")?.as_str()?;
+        let strings = |key: &str| -> Vec<String> {
+            card.get(key)
+                .and_then(|values| values.as_array())
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|value| value.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            id: name.to_string(),
+            command: command.to_string(),
+            description: description.to_string(),
+            patterns: strings("capabilities"),
+            ok_exit_codes: vec![0],
+            requires: strings("requires"),
+        })
+    }
+}
+
+/// The registry config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// Whether to start from the built-in tools (default true)
+    pub builtin: Option<bool>,
+    pub tools: Vec<ToolConfig>,
+    pub discovery: DiscoveryConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ToolConfig {
+    pub id: String,
+    /// Command to run; defaults to the ID
+    pub command: Option<String>,
+    pub description: String,
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default = "default_ok_exit_codes")]
+    pub ok_exit_codes: Vec<i32>,
+    /// Worker tags the tool needs, e.g. `gpu` or `network`
+    #[serde(default)]
+    pub requires: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// Look on `PATH` for Agentic Units named with `unit_prefix`
+    pub scan_path: bool,
+    pub unit_prefix: String,
+    /// Further Agentic Units to describe, by command or path
+    pub units: Vec<String>,
+    /// Add the tools in AGQ's fleet catalog
+    pub catalog: bool,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            scan_path: true,
+            unit_prefix: DEFAULT_UNIT_PREFIX.to_string(),
+            units: Vec::new(),
+            catalog: false,
+        }
+    }
+}
+
+fn default_ok_exit_codes() -> Vec<i32> {
+    vec![0]
+}
+
+impl RegistryConfig {
+    /// Parse a config file, as JSON if it ends in `.json` and TOML otherwise
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|error| error.to_string())
+        } else {
+            toml::from_str(&text).map_err(|error| error.to_string())
+        };
+        parsed.map_err(|error| format!("invalid tool config {}: {error}", path.display()))
+    }
+
+    /// The config file in use: `AGX_TOOLS_CONFIG`, or the first of
+    /// `tools.toml` and `tools.json` in the agx config directory
+    fn locate() -> Result<Option<PathBuf>, String> {
+        if let Ok(path) = std::env::var("AGX_TOOLS_CONFIG") {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(format!(
+                    "AGX_TOOLS_CONFIG points to a missing file: {}",
+                    path.display()
+                ));
+            }
+            return Ok(Some(path));
+        }
+
+        let Some(dir) = dirs::config_dir().map(|dir| dir.join("agx")) else {
+            return Ok(None);
+        };
+        Ok(["tools.toml", "tools.json"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file()))
+    }
 }
 
-pub struct ToolRegistry;
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Tool>,
+}
 
 impl ToolRegistry {
+    /// The built-in tools only
     pub fn new() -> Self {
-        Self
+        Self {
+            tools: BUILTIN_TOOLS.iter().map(Builtin::to_tool).collect(),
+        }
+    }
+
+    /// The registry for this process: built-ins, config, and discovered
+    /// tools, loaded once
+    ///
+    /// Errors only for an unreadable or invalid config file; units that
+    /// fail to describe themselves and an unreachable AGQ are skipped.
+    pub fn load() -> Result<Self, String> {
+        static LOADED: OnceLock<Result<ToolRegistry, String>> = OnceLock::new();
+        LOADED
+            .get_or_init(|| {
+                let config = match RegistryConfig::locate()? {
+                    Some(path) => {
+                        logging::info(&format!("loading tool config from {}", path.display()));
+                        RegistryConfig::from_file(&path)?
+                    }
+                    None => RegistryConfig::default(),
+                };
+                Ok(Self::from_config(&config))
+            })
+            .clone()
+    }
+
+    /// Build a registry from a config, running its discovery
+    pub fn from_config(config: &RegistryConfig) -> Self {
+        let mut registry = if config.builtin.unwrap_or(true) {
+            Self::new()
+        } else {
+            Self::default()
+        };
+
+        for tool in &config.tools {
+            registry.insert(Tool {
+                id: tool.id.clone(),
+                command: tool.command.clone().unwrap_or_else(|| tool.id.clone()),
+                description: tool.description.clone(),
+                patterns: tool.patterns.clone(),
+                ok_exit_codes: tool.ok_exit_codes.clone(),
+                requires: tool.requires.clone(),
+            });
+        }
+
+        let discovery = &config.discovery;
+        let mut units = discovery.units.clone();
+        if discovery.scan_path {
+            units.extend(units_on_path(&discovery.unit_prefix));
+        }
+        let mut described = HashSet::new();
+        for unit in units {
+            if registry.has_command(&unit) || !described.insert(unit.clone()) {
+                continue;
+            }
+            match describe(&unit) {
+                Some(tool) => {
+                    logging::info(&format!("discovered tool {} ({unit})", tool.id));
+                    registry.add(tool);
+                }
+                None => logging::info(&format!("{unit} did not describe itself; skipped")),
+            }
+        }
+
+        let use_catalog =
+            discovery.catalog || std::env::var("AGX_TOOLS_CATALOG").is_ok_and(|value| value == "1");
+        if use_catalog {
+            let client =
+                crate::agq_client::AgqClient::new(crate::agq_client::AgqConfig::from_env());
+            match client.tools_catalog() {
+                Ok(entries) => {
+                    for entry in entries {
+                        registry.add_catalog_entry(&entry);
+                    }
+                }
+                Err(error) => logging::info(&format!("tool catalog unavailable: {error}")),
+            }
+        }
+
+        registry
     }
 
-    pub fn tools(&self) -> &'static [Tool] {
-        TOOLS
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
     }
 
-    pub fn list_tools(&self) -> &'static [Tool] {
+    pub fn list_tools(&self) -> &[Tool] {
         self.tools()
     }
 
-    pub fn find_by_id(&self, id: &str) -> Option<&'static Tool> {
+    pub fn find_by_id(&self, id: &str) -> Option<&Tool> {
         self.tools().iter().find(|tool| tool.id == id)
     }
 
+    /// Worker tags a task running the tool `id` needs; none for unknown tools
+    pub fn required_tags(&self, id: &str) -> &[String] {
+        self.find_by_id(id)
+            .map(|tool| tool.requires.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Add a tool, replacing any with the same ID
+    pub fn insert(&mut self, tool: Tool) {
+        match self
+            .tools
+            .iter_mut()
+            .find(|existing| existing.id == tool.id)
+        {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    /// Add a tool unless one with its ID or command is already known
+    pub fn add(&mut self, tool: Tool) {
+        if self.find_by_id(&tool.id).is_none() && !self.has_command(&tool.command) {
+            self.tools.push(tool);
+        }
+    }
+
+    fn has_command(&self, command: &str) -> bool {
+        self.tools.iter().any(|tool| tool.command == command)
+    }
+
+    /// Add a tool from AGQ's catalog, which workers run by its name
+    fn add_catalog_entry(&mut self, entry: &crate::agq_client::CatalogEntry) {
+        let tool = entry
+            .card
+            .as_ref()
+            .and_then(|card| Tool::from_model_card(&entry.name, card))
+            .unwrap_or_else(|| Tool {
+                id: entry.name.clone(),
+                command: entry.name.clone(),
+                description: format!("Available on {} worker(s).", entry.workers.len()),
+                patterns: Vec::new(),
+                ok_exit_codes: vec![0],
+                requires: Vec::new(),
+            });
+        self.add(tool);
+    }
+
     pub fn describe_for_planner(&self) -> String {
         let mut description = String::new();
 
@@ -34,17 +325,22 @@ impl ToolRegistry {
             }
 
             description.push_str("- ");
-            description.push_str(tool.id);
+            description.push_str(&tool.id);
             description.push_str(": ");
-            description.push_str(tool.description);
+            description.push_str(&tool.description);
             description.push_str(" (command: ");
-            description.push_str(tool.command);
+            description.push_str(&tool.command);
 
             if !tool.patterns.is_empty() {
                 description.push_str(", patterns: ");
                 description.push_str(&tool.patterns.join(", "));
             }
 
+            if !tool.requires.is_empty() {
+                description.push_str(", requires: ");
+                description.push_str(&tool.requires.join(", "));
+            }
+
             description.push(')');
         }
 
@@ -52,6 +348,88 @@ impl ToolRegistry {
     }
 }
 
+/// Names of the executables on `PATH` that start with `prefix`, sorted
+fn units_on_path(prefix: &str) -> Vec<String> {
+    let Some(path) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let mut units: Vec<String> = std::env::split_paths(&path)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix) && name.len() > prefix.len())
+        .collect();
+    units.sort();
+    units.dedup();
+    units
+}
+
+/// Run a unit with `--describe` and read its model card
+fn describe(command: &str) -> Option<Tool> {
+    let mut child = Command::new(command)
+        .arg("--describe")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read while waiting so a large card cannot fill the pipe and stall it
+    let mut stdout = child.stdout.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+
+    let deadline = Instant::now() + DESCRIBE_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+
+    let output = reader.join().ok()?.ok()?;
+    if !status.success() {
+        return None;
+    }
+    let card: serde_json::Value = serde_json::from_slice(&output).ok()?;
+    Tool::from_model_card(command, &card)
+}
+
+/// A built-in tool, as listed in [`BUILTIN_TOOLS`]
+struct Builtin {
+    id: &'static str,
+    command: &'static str,
+    description: &'static str,
+    patterns: &'static [&'static str],
+    ok_exit_codes: &'static [i32],
+    requires: &'static [&'static str],
+}
+
+impl Builtin {
+    fn to_tool(&self) -> Tool {
+        Tool {
+            id: self.id.to_string(),
+            command: self.command.to_string(),
+            description: self.description.to_string(),
+            patterns: self.patterns.iter().map(|p| p.to_string()).collect(),
+            ok_exit_codes: self.ok_exit_codes.to_vec(),
+            requires: self.requires.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,56 +445,154 @@ mod tests {
         let registry = ToolRegistry::new();
         assert!(registry.find_by_id("does-not-exist").is_none());
     }
+
+    #[test]
+    fn config_file_adds_and_replaces_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tools.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[tools]]
+id = "sort"
+description = "Sort lines, numerically when asked."
+patterns = ["sort"]
+
+[[tools]]
+id = "wc"
+description = "Count lines, words, and bytes."
+ok_exit_codes = [0, 1]
+
+[[tools]]
+id = "curl"
+description = "Fetch a URL."
+requires = ["network"]
+
+[discovery]
+scan_path = false
+"#,
+        )
+        .unwrap();
+
+        let registry = ToolRegistry::from_config(&RegistryConfig::from_file(&path).unwrap());
+        assert_eq!(registry.tools().len(), BUILTIN_TOOLS.len() + 2);
+        assert_eq!(registry.required_tags("curl"), ["network"]);
+        assert!(registry.required_tags("wc").is_empty());
+        assert_eq!(
+            registry.find_by_id("sort").unwrap().description,
+            "Sort lines, numerically when asked."
+        );
+        let wc = registry.find_by_id("wc").unwrap();
+        assert_eq!(wc.command, "wc");
+        assert_eq!(wc.ok_exit_codes, [0, 1]);
+
+        let json = dir.path().join("tools.json");
+        std::fs::write(
+            &json,
+            r#"{"builtin": false, "discovery": {"scan_path": false}}"#,
+        )
+        .unwrap();
+        let registry = ToolRegistry::from_config(&RegistryConfig::from_file(&json).unwrap());
+        assert!(registry.tools().is_empty());
+
+        std::fs::write(&json, r#"{"tools": [{"id": "wc"}]}"#).unwrap();
+        let error = RegistryConfig::from_file(&json).unwrap_err();
+        assert!(error.contains("invalid tool config"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn units_are_described_by_their_model_card() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let unit = dir.path().join("agx-test-unit");
+        std::fs::write(
+            &unit,
+            "#!/bin/sh\n\
+             echo '{\"name\": \"test-unit\", \"version\": \"1.0\", \
+             \"description\": \"Answers questions.\", \"capabilities\": [\"qa\"], \
+             \"requires\": [\"gpu\"]}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&unit, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = RegistryConfig {
+            discovery: DiscoveryConfig {
+                scan_path: false,
+                units: vec![
+                    unit.to_string_lossy().into_owned(),
+                    "agx-missing-unit".to_string(),
+                ],
+                ..DiscoveryConfig::default()
+            },
+            ..RegistryConfig::default()
+        };
+        let registry = ToolRegistry::from_config(&config);
+
+        let tool = registry.find_by_id("test-unit").expect("unit discovered");
+        assert_eq!(tool.command, unit.to_string_lossy());
+        assert_eq!(tool.patterns, ["qa"]);
+        assert_eq!(tool.requires, ["gpu"]);
+        assert_eq!(registry.tools().len(), BUILTIN_TOOLS.len() + 1);
+    }
 }
 
-static TOOLS: &[Tool] = &[
-    Tool {
+static BUILTIN_TOOLS: &[Builtin] = &[
+    Builtin {
         id: "sort",
         command: "sort",
         description: "Sort lines of text.",
         patterns: &["sort", "order", "alphabetize", "sort lines"],
         ok_exit_codes: &[0],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "uniq",
         command: "uniq",
         description: "Remove duplicate lines.",
         patterns: &["dedupe", "unique", "remove duplicates"],
         ok_exit_codes: &[0],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "grep",
         command: "grep",
         description: "Filter lines that match a pattern.",
         patterns: &["search", "filter", "match", "grep"],
         ok_exit_codes: &[0, 1],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "cut",
         command: "cut",
         description: "Extract fields or columns from lines.",
         patterns: &["columns", "fields", "delimiter", "extract columns"],
         ok_exit_codes: &[0],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "tr",
         command: "tr",
         description: "Translate or delete characters in text.",
         patterns: &["translate", "replace characters", "lowercase", "uppercase"],
         ok_exit_codes: &[0],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "jq",
         command: "jq",
         description: "Filter and transform JSON data.",
         patterns: &["json", "jq", "filter json", "transform json"],
         ok_exit_codes: &[0],
+        requires: &[],
     },
-    Tool {
+    Builtin {
         id: "train_model",
         command: "agx-train",
         description: "Train a model using Axolotl.",
         patterns: &["train", "fine-tune", "axolotl", "training"],
         ok_exit_codes: &[0],
+        requires: &["gpu"],
     },
 ];
@@ -0,0 +1,301 @@
+//! `agx WATCH --repair`: ask Delta to fix a plan whose run failed
+//!
+//! The run is followed as with a plain WATCH. When a task fails, its
+//! command, exit status, and stderr go to Delta together with the plan and
+//! its goal, and Delta proposes a corrected plan. The user confirms each
+//! attempt and reviews the corrected plan; once approved it is submitted,
+//! run, and followed in turn, up to a bounded number of attempts.
+
+use std::io::Write;
+
+use crate::agq_client::{AgqClient, PlanResults, PlanStatus};
+use crate::plan::WorkflowPlan;
+use crate::watch::{self, WatchOptions};
+
+/// Repair attempts allowed by `--repair` alone
+pub const DEFAULT_MAX_REPAIRS: u32 = 3;
+
+/// Most stderr sent to Delta, keeping the end where errors usually are
+const MAX_STDERR_CHARS: usize = 2000;
+
+/// Goal used for plans submitted without a description
+const DEFAULT_GOAL: &str = "Complete the plan's tasks";
+
+/// The first task of a run that did not complete
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskFailure {
+    pub task_number: u32,
+    pub command: String,
+    pub status: String,
+    pub exit_code: Option<i64>,
+    pub stderr: String,
+}
+
+/// The lowest-numbered task that finished without completing
+pub fn first_failure(status: &PlanStatus, results: &PlanResults) -> Option<TaskFailure> {
+    let task = status
+        .tasks
+        .iter()
+        .filter(|task| watch::is_finished(&task.status) && task.status != "completed")
+        .min_by_key(|task| task.task_number)?;
+
+    let stderr = results
+        .results
+        .iter()
+        .find(|output| output.job_id == task.job_id)
+        .and_then(|output| {
+            let stderr = output.stderr.as_deref()?;
+            Some(if output.stderr_encoding == "utf8" {
+                tail(stderr, MAX_STDERR_CHARS)
+            } else {
+                format!(
+                    "({} bytes of {} output)",
+                    stderr.len(),
+                    output.stderr_encoding
+                )
+            })
+        })
+        .unwrap_or_default();
+
+    Some(TaskFailure {
+        task_number: task.task_number,
+        command: task.command.clone(),
+        status: task.status.clone(),
+        exit_code: task.exit_code,
+        stderr,
+    })
+}
+
+/// Instruction asking Delta to correct `plan` after `failure`
+pub fn repair_instruction(goal: &str, plan: &WorkflowPlan, failure: &TaskFailure) -> String {
+    let command_line = plan
+        .tasks
+        .iter()
+        .find(|task| task.task_number == failure.task_number)
+        .map(|task| {
+            std::iter::once(task.command.as_str())
+                .chain(task.args.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_else(|| failure.command.clone());
+
+    let mut outcome = failure.status.clone();
+    if let Some(exit_code) = failure.exit_code {
+        outcome.push_str(&format!(" with exit code {exit_code}"));
+    }
+    let stderr = if failure.stderr.trim().is_empty() {
+        "(no stderr)".to_string()
+    } else {
+        failure.stderr.trim_end().to_string()
+    };
+
+    format!(
+        "{goal}\n\n\
+         The current plan failed when it ran: task {} (`{}`) {}.\n\
+         Its stderr was:\n{}\n\n\
+         Fix the plan so it achieves the goal without this failure.",
+        failure.task_number, command_line, outcome, stderr
+    )
+}
+
+/// Follow a run, repairing it with Delta after a failure
+pub async fn run(
+    client: &AgqClient,
+    options: &WatchOptions,
+    max_repairs: u32,
+) -> Result<(), String> {
+    if !crate::input::InputCollector::stdin_is_terminal() {
+        return Err("--repair confirms each attempt, which needs a terminal".to_string());
+    }
+
+    let mut options = options.clone();
+    let mut planner = None;
+    let mut attempt = 0;
+
+    loop {
+        let status = watch::follow(client, &options)?;
+        let outcome = watch::finish(&status, false);
+        if outcome.is_ok() {
+            return outcome;
+        }
+        if attempt == max_repairs {
+            println!("Giving up after {max_repairs} repair attempts.");
+            return outcome;
+        }
+
+        let results = client
+            .plan_results(&status.plan_id, Some(&status.action_id))
+            .map_err(|error| format!("failed to get plan results: {error}"))?;
+        let Some(failure) = first_failure(&status, &results) else {
+            return outcome;
+        };
+        let plan = client
+            .get_plan(&status.plan_id)
+            .map_err(|error| format!("failed to get plan {}: {error}", status.plan_id))?;
+        let goal = plan
+            .plan_description
+            .clone()
+            .filter(|goal| !goal.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_GOAL.to_string());
+
+        println!();
+        println!(
+            "Task {} `{}` {}.",
+            failure.task_number, failure.command, failure.status
+        );
+        attempt += 1;
+        if !confirm(&format!(
+            "Ask Delta for a corrected plan? (attempt {attempt} of {max_repairs}) [y/N] "
+        ))? {
+            return outcome;
+        }
+
+        let planner = match &mut planner {
+            Some(planner) => planner,
+            None => {
+                let config = crate::planner::PlannerConfig::for_delta()?;
+                let created = crate::planner::Planner::new_async(config)
+                    .await
+                    .map_err(|error| format!("failed to start Delta: {error}"))?;
+                planner.insert(created)
+            }
+        };
+        println!("Asking Delta to repair the plan...");
+        let output = planner
+            .plan_with_existing_async(
+                &repair_instruction(&goal, &plan, &failure),
+                &crate::input::InputSummary::empty(),
+                &crate::registry::ToolRegistry::load()?,
+                &plan.tasks,
+            )
+            .await?;
+        let mut repaired = output.parse()?.normalize_for_execution();
+        repaired.plan_description = Some(goal);
+
+        let Some(approved) = crate::review_plan(repaired)? else {
+            println!("Repair rejected; nothing was submitted.");
+            return outcome;
+        };
+
+        let (plan_id, action_id) = crate::submit_and_run(client, approved)?;
+        println!("Submitted repaired plan {plan_id} as action {action_id}");
+        options.plan_id = plan_id;
+        options.action_id = Some(action_id);
+    }
+}
+
+fn confirm(question: &str) -> Result<bool, String> {
+    print!("{question}");
+    std::io::stdout()
+        .flush()
+        .map_err(|error| format!("failed to write prompt: {error}"))?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|error| format!("failed to read answer: {error}"))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// The last `max` characters of `text`
+fn tail(text: &str, max: usize) -> String {
+    let count = text.chars().count();
+    if count <= max {
+        return text.to_string();
+    }
+    let skipped: String = text.chars().skip(count - max).collect();
+    format!("...{skipped}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agq_client::{TaskOutput, TaskStatus};
+    use crate::plan::PlanStep;
+
+    fn task(job_id: &str, task_number: u32, command: &str, status: &str) -> TaskStatus {
+        TaskStatus {
+            job_id: job_id.to_string(),
+            task_number,
+            command: command.to_string(),
+            status: status.to_string(),
+            exit_code: None,
+            worker_id: None,
+            started_at: None,
+            completed_at: None,
+            duration_ms: None,
+            retries: 0,
+        }
+    }
+
+    fn output(job_id: &str, task_number: u32, stderr: &str) -> TaskOutput {
+        TaskOutput {
+            job_id: job_id.to_string(),
+            task_number,
+            status: "failed".to_string(),
+            stdout: None,
+            stdout_encoding: "utf8".to_string(),
+            stderr: Some(stderr.to_string()),
+            stderr_encoding: "utf8".to_string(),
+        }
+    }
+
+    #[test]
+    fn first_failure_picks_the_earliest_failed_task() {
+        let mut failed = task("job-2", 2, "cut", "failed");
+        failed.exit_code = Some(1);
+        let status = PlanStatus {
+            plan_id: "plan-1".to_string(),
+            action_id: "action-1".to_string(),
+            status: "failed".to_string(),
+            summary: Default::default(),
+            tasks: vec![
+                task("job-3", 3, "uniq", "timeout"),
+                failed,
+                task("job-1", 1, "sort", "completed"),
+            ],
+        };
+        let results = PlanResults {
+            status: "failed".to_string(),
+            results: vec![output(
+                "job-2",
+                2,
+                "cut: you must specify a list of fields\n",
+            )],
+        };
+
+        let failure = first_failure(&status, &results).expect("a task failed");
+        assert_eq!(failure.task_number, 2);
+        assert_eq!(failure.exit_code, Some(1));
+        assert!(failure.stderr.starts_with("cut: you must specify"));
+
+        let plan = WorkflowPlan {
+            plan_id: Some("plan-1".to_string()),
+            plan_description: Some("count unique users".to_string()),
+            tasks: vec![PlanStep {
+                task_number: 2,
+                command: "cut".to_string(),
+                args: vec!["-d,".to_string()],
+                timeout_secs: 300,
+                input_from_task: Some(1),
+                depends_on: Vec::new(),
+                tags: Vec::new(),
+                limits: Default::default(),
+            }],
+        };
+        let instruction = repair_instruction("count unique users", &plan, &failure);
+        assert!(instruction.starts_with("count unique users\n"));
+        assert!(instruction.contains("task 2 (`cut -d,`) failed with exit code 1"));
+        assert!(instruction.contains("you must specify a list of fields"));
+    }
+
+    #[test]
+    fn long_stderr_keeps_its_end() {
+        let stderr = format!("{}final error", "x".repeat(5000));
+        let kept = tail(&stderr, MAX_STDERR_CHARS);
+        assert!(kept.ends_with("final error"));
+        assert_eq!(kept.chars().count(), MAX_STDERR_CHARS + 3);
+        assert_eq!(tail("short", MAX_STDERR_CHARS), "short");
+    }
+}
@@ -10,7 +10,7 @@ use rustyline::error::ReadlineError;
 use rustyline::{Config, DefaultEditor, EditMode};
 use serde::{Deserialize, Serialize};
 
-use crate::plan::WorkflowPlan;
+use crate::plan::{task_list, WorkflowPlan};
 use crate::plan_buffer::PlanStorage;
 use crate::planner::{ModelBackend, PlanContext, ToolInfo};
 use crate::registry;
@@ -433,10 +433,10 @@ impl Repl {
         println!("🤖 Generating plan steps...");
 
         // Build context for planner
-        let reg = registry::ToolRegistry::new();
+        let reg = registry::ToolRegistry::load()?;
         let tool_registry: Vec<ToolInfo> = reg.tools()
             .iter()
-            .map(|t| ToolInfo::new(t.id, t.description))
+            .map(|t| ToolInfo::new(&t.id, &t.description))
             .collect();
 
         let context = PlanContext {
@@ -493,6 +493,9 @@ impl Repl {
             if let Some(input_from) = task.input_from_task {
                 println!("     ← input from task {}", input_from);
             }
+            if !task.depends_on.is_empty() {
+                println!("     ← after {}", task_list(&task.depends_on));
+            }
         }
 
         println!();
@@ -668,50 +671,48 @@ impl Repl {
 
     /// List jobs from AGQ (AGX-058)
     fn cmd_job_list(&self) -> Result<(), String> {
-        use crate::agq_client::{AgqClient, AgqConfig, OpsResponse};
+        use crate::agq_client::{AgqClient, AgqConfig};
 
         let config = AgqConfig::from_env();
         let client = AgqClient::new(config);
 
-        match client.list_jobs() {
-            Ok(OpsResponse::Jobs(jobs)) => {
+        match client.jobs() {
+            Ok(jobs) => {
                 if jobs.is_empty() {
                     println!("No jobs found");
                 } else {
                     println!("\nJobs ({}):", jobs.len());
                     for job in jobs {
-                        println!("  - {}", job);
+                        println!("  - {} | {} | {}", job.job_id, job.status, job.created_at);
                     }
                     println!();
                 }
                 Ok(())
             }
-            Ok(_) => Err("unexpected response from AGQ".to_string()),
             Err(e) => Err(format!("failed to list jobs: {}", e)),
         }
     }
 
     /// List workers from AGQ (AGX-058)
     fn cmd_worker_list(&self) -> Result<(), String> {
-        use crate::agq_client::{AgqClient, AgqConfig, OpsResponse};
+        use crate::agq_client::{AgqClient, AgqConfig};
 
         let config = AgqConfig::from_env();
         let client = AgqClient::new(config);
 
-        match client.list_workers() {
-            Ok(OpsResponse::Workers(workers)) => {
+        match client.workers() {
+            Ok(workers) => {
                 if workers.is_empty() {
                     println!("No active workers");
                 } else {
                     println!("\nActive Workers ({}):", workers.len());
                     for worker in workers {
-                        println!("  - {}", worker);
+                        println!("  - {} | {} | {}", worker.worker_id, worker.status, worker.tools);
                     }
                     println!();
                 }
                 Ok(())
             }
-            Ok(_) => Err("unexpected response from AGQ".to_string()),
             Err(e) => Err(format!("failed to list workers: {}", e)),
         }
     }
@@ -1258,6 +1259,14 @@ mod tests {
             async fn health_check(&self) -> Result<(), ModelError> {
                 Ok(())
             }
+
+            async fn chat(
+                &self,
+                _history: &[crate::planner::types::ChatMessage],
+                _ctx: &PlanContext,
+            ) -> Result<String, ModelError> {
+                unreachable!("chat should not be called in this test")
+            }
         }
 
         let backend = Box::new(MockBackend);
@@ -0,0 +1,517 @@
+//! Human review of a generated plan before it is submitted to AGQ
+//!
+//! The planner's output is shown task by task, and nothing is submitted
+//! until the user approves it. Tasks can be edited, deleted, or moved
+//! first; `input_from_task` and `depends_on` references follow the tasks
+//! they point at, and a move that would make a task read from or wait for
+//! one running after it is refused. Tasks breaking the tool policy are
+//! flagged, and the plan can't be approved until they are fixed or deleted.
+
+use std::io::{BufRead, Write};
+
+use crate::estimate::Estimator;
+use crate::plan::{task_list, WorkflowPlan};
+use crate::policy::Policy;
+
+const REVIEW_HELP: &str = "\
+Commands:
+  a                 approve and submit
+  e <n> [args...]   replace task n's args (prompts when none are given)
+  d <n>             delete task n
+  m <n> <to>        move task n to position <to>
+  r                 reject; nothing is submitted
+  ?                 show this help";
+
+#[derive(Debug, Clone, PartialEq)]
+enum ReviewCommand {
+    Approve,
+    Reject,
+    Help,
+    Edit {
+        task: usize,
+        args: Option<Vec<String>>,
+    },
+    Delete {
+        task: usize,
+    },
+    Move {
+        task: usize,
+        to: usize,
+    },
+}
+
+/// Review a plan interactively, returning it once approved
+///
+/// Returns `Ok(None)` if the user rejects the plan or the input ends.
+pub fn review<R: BufRead, W: Write>(
+    mut plan: WorkflowPlan,
+    estimator: &Estimator,
+    policy: &Policy,
+    input: &mut R,
+    output: &mut W,
+) -> Result<Option<WorkflowPlan>, String> {
+    let io_error = |error: std::io::Error| format!("plan review failed: {error}");
+
+    print_plan(&plan, estimator, policy, output).map_err(io_error)?;
+    writeln!(output, "{REVIEW_HELP}").map_err(io_error)?;
+
+    loop {
+        write!(output, "review> ").map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+
+        let Some(line) = read_line(input).map_err(io_error)? else {
+            writeln!(output).map_err(io_error)?;
+            return Ok(None);
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = match parse_command(&line) {
+            Ok(command) => command,
+            Err(error) => {
+                writeln!(output, "{error}").map_err(io_error)?;
+                continue;
+            }
+        };
+
+        let result = match command {
+            ReviewCommand::Approve => {
+                if plan.tasks.is_empty() {
+                    Err("plan has no tasks left; reject it instead".to_string())
+                } else if !policy.check(&plan).is_empty() {
+                    Err("plan breaks the tool policy; edit or delete the flagged tasks".to_string())
+                } else {
+                    return Ok(Some(plan));
+                }
+            }
+            ReviewCommand::Reject => return Ok(None),
+            ReviewCommand::Help => {
+                writeln!(output, "{REVIEW_HELP}").map_err(io_error)?;
+                continue;
+            }
+            ReviewCommand::Edit {
+                task,
+                args: Some(args),
+            } => set_args(&mut plan, task, args),
+            ReviewCommand::Edit { task, args: None } => match check_task(&plan, task) {
+                Ok(()) => {
+                    let step = &plan.tasks[task - 1];
+                    write!(
+                        output,
+                        "args for task {task} `{}` (empty keeps `{}`): ",
+                        step.command,
+                        step.args.join(" ")
+                    )
+                    .map_err(io_error)?;
+                    output.flush().map_err(io_error)?;
+                    match read_line(input)
+                        .map_err(io_error)?
+                        .filter(|line| !line.trim().is_empty())
+                    {
+                        Some(line) => {
+                            split_args(&line).and_then(|args| set_args(&mut plan, task, args))
+                        }
+                        None => Ok(()),
+                    }
+                }
+                Err(error) => Err(error),
+            },
+            ReviewCommand::Delete { task } => delete_task(&mut plan, task),
+            ReviewCommand::Move { task, to } => move_task(&mut plan, task, to),
+        };
+
+        match result {
+            Ok(()) => print_plan(&plan, estimator, policy, output).map_err(io_error)?,
+            Err(error) => writeln!(output, "{error}").map_err(io_error)?,
+        }
+    }
+}
+
+fn read_line<R: BufRead>(input: &mut R) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+fn print_plan<W: Write>(
+    plan: &WorkflowPlan,
+    estimator: &Estimator,
+    policy: &Policy,
+    output: &mut W,
+) -> std::io::Result<()> {
+    let violations = policy.check(plan);
+    writeln!(output)?;
+    writeln!(output, "Plan to submit ({} tasks):", plan.tasks.len())?;
+    for task in &plan.tasks {
+        let flagged = violations
+            .iter()
+            .any(|violation| violation.task_number == task.task_number);
+        let marker = if flagged { "!" } else { " " };
+        let mut line = format!("{marker} {}. {}", task.task_number, task.command);
+        if !task.args.is_empty() {
+            line.push(' ');
+            line.push_str(&task.args.join(" "));
+        }
+        if let Some(from) = task.input_from_task {
+            line.push_str(&format!("   (input from task {from})"));
+        }
+        if !task.depends_on.is_empty() {
+            line.push_str(&format!("   (after {})", task_list(&task.depends_on)));
+        }
+        writeln!(output, "{line}")?;
+    }
+    if !violations.is_empty() {
+        writeln!(output)?;
+        writeln!(output, "Policy violations:")?;
+        for violation in &violations {
+            writeln!(output, "  {violation}")?;
+        }
+    }
+    let estimate = estimator.report(plan);
+    if !estimate.is_empty() {
+        writeln!(output)?;
+        for line in estimate {
+            writeln!(output, "{line}")?;
+        }
+    }
+    writeln!(output)
+}
+
+fn parse_command(line: &str) -> Result<ReviewCommand, String> {
+    let words = split_args(line)?;
+    let Some((name, rest)) = words.split_first() else {
+        return Err("empty command".to_string());
+    };
+
+    let task_number = |index: usize| -> Result<usize, String> {
+        let word = rest
+            .get(index)
+            .ok_or_else(|| format!("`{name}` needs a task number"))?;
+        word.parse::<usize>()
+            .map_err(|_| format!("not a task number: {word}"))
+    };
+
+    match name.to_lowercase().as_str() {
+        "a" | "approve" => Ok(ReviewCommand::Approve),
+        "r" | "reject" | "q" | "quit" => Ok(ReviewCommand::Reject),
+        "?" | "h" | "help" => Ok(ReviewCommand::Help),
+        "e" | "edit" => Ok(ReviewCommand::Edit {
+            task: task_number(0)?,
+            args: (rest.len() > 1).then(|| rest[1..].to_vec()),
+        }),
+        "d" | "delete" => Ok(ReviewCommand::Delete {
+            task: task_number(0)?,
+        }),
+        "m" | "move" => Ok(ReviewCommand::Move {
+            task: task_number(0)?,
+            to: task_number(1)?,
+        }),
+        _ => Err(format!("unknown command: {name} (? for help)")),
+    }
+}
+
+/// Split a line into words, keeping quoted text together
+pub(crate) fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+fn check_task(plan: &WorkflowPlan, task: usize) -> Result<(), String> {
+    if task == 0 || task > plan.tasks.len() {
+        return Err(format!(
+            "no task {task}; the plan has {} tasks",
+            plan.tasks.len()
+        ));
+    }
+    Ok(())
+}
+
+fn set_args(plan: &mut WorkflowPlan, task: usize, args: Vec<String>) -> Result<(), String> {
+    check_task(plan, task)?;
+    plan.tasks[task - 1].args = args;
+    Ok(())
+}
+
+/// Delete a task; tasks reading its output read its input instead, and
+/// tasks waiting for it wait for what it waited for
+fn delete_task(plan: &mut WorkflowPlan, task: usize) -> Result<(), String> {
+    check_task(plan, task)?;
+    let removed = plan.tasks.remove(task - 1);
+    let removed_number = removed.task_number;
+
+    for step in &mut plan.tasks {
+        if step.input_from_task == Some(removed_number) {
+            step.input_from_task = removed.input_from_task;
+        }
+        if let Some(position) = step
+            .depends_on
+            .iter()
+            .position(|&from| from == removed_number)
+        {
+            step.depends_on.remove(position);
+            for &from in removed.input_from_task.iter().chain(&removed.depends_on) {
+                if !step.depends_on.contains(&from) {
+                    step.depends_on.push(from);
+                }
+            }
+            step.depends_on.sort_unstable();
+        }
+    }
+    renumber(plan);
+    Ok(())
+}
+
+/// Move a task to another position, refusing moves that would make a task
+/// read from or wait for one that runs after it
+fn move_task(plan: &mut WorkflowPlan, task: usize, to: usize) -> Result<(), String> {
+    check_task(plan, task)?;
+    check_task(plan, to)?;
+
+    let mut moved = plan.clone();
+    let step = moved.tasks.remove(task - 1);
+    moved.tasks.insert(to - 1, step);
+    renumber(&mut moved);
+
+    for step in &moved.tasks {
+        if let Some(from) = step.input_from_task {
+            if from >= step.task_number {
+                return Err(format!(
+                    "cannot move task {task} to {to}: task {} `{}` would run before task {from}, which it takes input from",
+                    step.task_number, step.command
+                ));
+            }
+        }
+        if let Some(from) = step
+            .depends_on
+            .iter()
+            .find(|&&from| from >= step.task_number)
+        {
+            return Err(format!(
+                "cannot move task {task} to {to}: task {} `{}` would run before task {from}, which it waits for",
+                step.task_number, step.command
+            ));
+        }
+    }
+
+    *plan = moved;
+    Ok(())
+}
+
+/// Number tasks by position, pointing references at the renumbered tasks
+fn renumber(plan: &mut WorkflowPlan) {
+    let numbers: std::collections::HashMap<u32, u32> = plan
+        .tasks
+        .iter()
+        .enumerate()
+        .map(|(index, step)| (step.task_number, index as u32 + 1))
+        .collect();
+
+    for (index, step) in plan.tasks.iter_mut().enumerate() {
+        step.task_number = index as u32 + 1;
+        step.input_from_task = step
+            .input_from_task
+            .and_then(|from| numbers.get(&from).copied());
+        step.depends_on = step
+            .depends_on
+            .iter()
+            .filter_map(|from| numbers.get(from).copied())
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agq_client::ToolStats;
+    use crate::estimate::Budget;
+    use crate::plan::PlanStep;
+
+    fn step(task_number: u32, command: &str, input_from_task: Option<u32>) -> PlanStep {
+        PlanStep {
+            task_number,
+            command: command.to_string(),
+            args: Vec::new(),
+            timeout_secs: 300,
+            input_from_task,
+            depends_on: Vec::new(),
+            tags: Vec::new(),
+            limits: Default::default(),
+        }
+    }
+
+    fn plan() -> WorkflowPlan {
+        WorkflowPlan {
+            plan_id: None,
+            plan_description: None,
+            tasks: vec![
+                step(1, "cat", None),
+                step(2, "sort", Some(1)),
+                step(3, "uniq", Some(2)),
+            ],
+        }
+    }
+
+    fn run_review(script: &str) -> (Option<WorkflowPlan>, String) {
+        review_with(script, &Estimator::default())
+    }
+
+    fn review_with(script: &str, estimator: &Estimator) -> (Option<WorkflowPlan>, String) {
+        let mut input = script.as_bytes();
+        let mut output = Vec::new();
+        let reviewed = review(
+            plan(),
+            estimator,
+            &Policy::default(),
+            &mut input,
+            &mut output,
+        )
+        .expect("review runs");
+        (reviewed, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn edits_are_applied_only_on_approval() {
+        let (reviewed, _) = run_review("e 2 -r -k \"2 3\"\nd 1\na\n");
+        let reviewed = reviewed.expect("approved");
+        assert_eq!(reviewed.tasks.len(), 2);
+        assert_eq!(reviewed.tasks[0].command, "sort");
+        assert_eq!(reviewed.tasks[0].args, ["-r", "-k", "2 3"]);
+        // sort read from the deleted cat, which read nothing
+        assert_eq!(reviewed.tasks[0].input_from_task, None);
+        assert_eq!(reviewed.tasks[1].input_from_task, Some(1));
+
+        let (rejected, _) = run_review("d 1\nr\n");
+        assert!(rejected.is_none());
+        let (ended, _) = run_review("e 1 -n\n");
+        assert!(ended.is_none());
+    }
+
+    #[test]
+    fn estimates_follow_the_edited_plan() {
+        let stats = |tool: &str, secs: u64| ToolStats {
+            tool: tool.to_string(),
+            runs: 1,
+            failed: 0,
+            mean_duration_ms: secs * 1000,
+            max_duration_ms: secs * 1000,
+            mean_cpu_ms: None,
+        };
+        let estimator = Estimator::new(
+            vec![stats("cat", 1), stats("sort", 20), stats("uniq", 5)],
+            Budget {
+                wall_secs: Some(20),
+                cpu_secs: None,
+            },
+        );
+
+        let (_, output) = review_with("d 2\na\n", &estimator);
+        let (before, after) = output.split_once("review> ").unwrap();
+        assert!(before.contains("Estimate: ~26s wall-clock (up to 26s), 26s CPU"));
+        assert!(before.contains("exceeds the wall-clock budget of 20s"));
+        assert!(after.contains("Estimate: ~6s wall-clock"));
+        assert!(!after.contains("Warning"));
+    }
+
+    #[test]
+    fn policy_violations_are_flagged_and_block_approval() {
+        let mut input = "e 2 -o /etc/hosts\na\ne 2 -o sorted.txt\na\n".as_bytes();
+        let mut output = Vec::new();
+        let reviewed = review(
+            plan(),
+            &Estimator::default(),
+            &Policy::default(),
+            &mut input,
+            &mut output,
+        )
+        .expect("review runs");
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("! 2. sort -o /etc/hosts"));
+        assert!(output.contains("task 2: `/etc/hosts` is outside the workspace"));
+        assert!(output.contains("plan breaks the tool policy"));
+        assert_eq!(reviewed.unwrap().tasks[1].args, ["-o", "sorted.txt"]);
+    }
+
+    #[test]
+    fn edit_prompts_for_args() {
+        let (reviewed, output) = run_review("e 1\n-n file.txt\na\n");
+        assert!(output.contains("args for task 1 `cat`"));
+        assert_eq!(reviewed.unwrap().tasks[0].args, ["-n", "file.txt"]);
+    }
+
+    #[test]
+    fn moves_keep_references_and_refuse_reading_ahead() {
+        let mut plan = WorkflowPlan {
+            plan_id: None,
+            plan_description: None,
+            tasks: vec![
+                step(1, "cat", None),
+                step(2, "date", None),
+                step(3, "sort", Some(1)),
+            ],
+        };
+        move_task(&mut plan, 2, 1).unwrap();
+        assert_eq!(plan.tasks[0].command, "date");
+        assert_eq!(plan.tasks[2].input_from_task, Some(2));
+
+        let error = move_task(&mut plan, 3, 1).unwrap_err();
+        assert!(error.contains("takes input from"));
+        assert_eq!(plan.tasks[0].command, "date");
+
+        // sort now also waits for date; deleting date makes it wait for
+        // nothing, as date waited for nothing
+        plan.tasks[2].depends_on = vec![1];
+        let error = move_task(&mut plan, 1, 3).unwrap_err();
+        assert!(error.contains("which it waits for"));
+        move_task(&mut plan, 2, 1).unwrap();
+        assert_eq!(plan.tasks[2].depends_on, [2]);
+        delete_task(&mut plan, 2).unwrap();
+        assert!(plan.tasks[1].depends_on.is_empty());
+        assert_eq!(plan.tasks[1].input_from_task, Some(1));
+
+        assert!(move_task(&mut plan, 4, 1).is_err());
+    }
+
+    #[test]
+    fn unknown_commands_and_bad_tasks_are_reported() {
+        let (reviewed, output) = run_review("x\nd 9\nm 1\na\n");
+        assert!(output.contains("unknown command: x"));
+        assert!(output.contains("no task 9"));
+        assert!(output.contains("needs a task number"));
+        assert_eq!(reviewed.unwrap().tasks.len(), 3);
+    }
+}
@@ -0,0 +1,316 @@
+//! `agx WATCH`: follow one run of a Plan until it finishes
+//!
+//! Polls PLAN.STATUS and prints each task's status changes with their
+//! timing. When tasks finish, their output is fetched with PLAN.RESULTS and
+//! printed under them. Without an Action ID the Plan's latest run is
+//! followed, waiting for one to be submitted if there is none yet.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::agq_client::{AgqClient, PlanStatus, TaskOutput, TaskStatus};
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub plan_id: String,
+    pub action_id: Option<String>,
+    pub interval: Duration,
+    pub json: bool,
+}
+
+/// A task whose status differs from the last poll
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskChange {
+    pub task: TaskStatus,
+    pub previous: Option<String>,
+}
+
+/// Last status seen for each task
+#[derive(Debug, Default)]
+pub struct Tracker {
+    seen: HashMap<String, String>,
+}
+
+impl Tracker {
+    /// Record a poll, returning the tasks that changed in task order
+    pub fn update(&mut self, status: &PlanStatus) -> Vec<TaskChange> {
+        let mut changes = Vec::new();
+        for task in &status.tasks {
+            let previous = self.seen.insert(task.job_id.clone(), task.status.clone());
+            if previous.as_deref() != Some(task.status.as_str()) {
+                changes.push(TaskChange {
+                    task: task.clone(),
+                    previous,
+                });
+            }
+        }
+        changes.sort_by_key(|change| change.task.task_number);
+        changes
+    }
+}
+
+/// Whether a task has stopped for good
+pub fn is_finished(status: &str) -> bool {
+    matches!(
+        status,
+        "completed" | "failed" | "timeout" | "policy_violation" | "cancelled"
+    )
+}
+
+pub fn run(client: &AgqClient, options: &WatchOptions) -> Result<(), String> {
+    let status = follow(client, options)?;
+    finish(&status, options.json)
+}
+
+/// Print a run's progress until it completes or fails, returning its final status
+pub fn follow(client: &AgqClient, options: &WatchOptions) -> Result<PlanStatus, String> {
+    let mut tracker = Tracker::default();
+    let mut action_id = options.action_id.clone();
+    let mut waiting = false;
+    let mut started = false;
+
+    loop {
+        let status = match client.plan_status(&options.plan_id, action_id.as_deref()) {
+            Ok(status) => status,
+            Err(error) if action_id.is_none() && error.contains("No actions found") => {
+                if !waiting && !options.json {
+                    println!("Waiting for an Action of plan {}...", options.plan_id);
+                }
+                waiting = true;
+                std::thread::sleep(options.interval);
+                continue;
+            }
+            Err(error) => return Err(format!("failed to get plan status: {error}")),
+        };
+
+        if !started && !options.json {
+            println!(
+                "Watching plan {} (action {}, {} tasks)",
+                status.plan_id,
+                status.action_id,
+                status.tasks.len()
+            );
+        }
+        started = true;
+        // Stay on this run even if the Plan is run again while watching
+        action_id.get_or_insert_with(|| status.action_id.clone());
+
+        let changes = tracker.update(&status);
+        let outputs = if changes.iter().any(|c| is_finished(&c.task.status)) {
+            client
+                .plan_results(&options.plan_id, action_id.as_deref())
+                .map_err(|error| format!("failed to get plan results: {error}"))?
+                .results
+                .into_iter()
+                .map(|output| (output.job_id.clone(), output))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        for change in &changes {
+            let output = outputs.get(&change.task.job_id);
+            if options.json {
+                println!("{}", change_json(change, output));
+            } else {
+                print_change(change, output);
+            }
+        }
+
+        if status.status == "completed" || status.status == "failed" {
+            return Ok(status);
+        }
+        std::thread::sleep(options.interval);
+    }
+}
+
+/// Print a finished run's summary; a failed run is an error
+pub fn finish(status: &PlanStatus, json: bool) -> Result<(), String> {
+    if json {
+        println!(
+            "{}",
+            json!({
+                "event": "plan",
+                "plan_id": status.plan_id,
+                "action_id": status.action_id,
+                "status": status.status,
+                "summary": status.summary,
+            })
+        );
+    } else {
+        let counts: Vec<String> = status
+            .summary
+            .iter()
+            .map(|(task_status, count)| format!("{count} {task_status}"))
+            .collect();
+        println!(
+            "Plan {} {}: {}",
+            status.plan_id,
+            status.status,
+            counts.join(", ")
+        );
+    }
+
+    if status.status == "failed" {
+        let failed = status
+            .tasks
+            .iter()
+            .filter(|task| task.status != "completed")
+            .count();
+        return Err(format!(
+            "plan {} failed: {} of {} tasks did not complete",
+            status.plan_id,
+            failed,
+            status.tasks.len()
+        ));
+    }
+    Ok(())
+}
+
+fn change_json(change: &TaskChange, output: Option<&TaskOutput>) -> serde_json::Value {
+    let task = &change.task;
+    let mut event = json!({
+        "event": "task",
+        "job_id": task.job_id,
+        "task_number": task.task_number,
+        "command": task.command,
+        "status": task.status,
+        "previous": change.previous,
+        "worker_id": task.worker_id,
+        "exit_code": task.exit_code,
+        "duration_ms": task.duration_ms,
+        "retries": task.retries,
+    });
+    if let Some(output) = output {
+        event["stdout"] = json!(output.stdout);
+        event["stdout_encoding"] = json!(output.stdout_encoding);
+        event["stderr"] = json!(output.stderr);
+        event["stderr_encoding"] = json!(output.stderr_encoding);
+    }
+    event
+}
+
+fn print_change(change: &TaskChange, output: Option<&TaskOutput>) {
+    let task = &change.task;
+    let mut details = Vec::new();
+    if let Some(worker_id) = task
+        .worker_id
+        .as_deref()
+        .filter(|_| !is_finished(&task.status))
+    {
+        details.push(format!("worker {worker_id}"));
+    }
+    if let Some(exit_code) = task.exit_code {
+        details.push(format!("exit {exit_code}"));
+    }
+    if let Some(duration_ms) = task.duration_ms {
+        details.push(format!("{:.1}s", duration_ms as f64 / 1000.0));
+    }
+    if task.retries > 0 {
+        details.push(format!("retry {}", task.retries));
+    }
+    let details = if details.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", details.join(", "))
+    };
+
+    println!(
+        "[{}] task {} {}: {}{}",
+        chrono::Local::now().format("%H:%M:%S"),
+        task.task_number,
+        task.command,
+        task.status,
+        details
+    );
+
+    if let Some(output) = output {
+        print_stream(output.stdout.as_deref(), &output.stdout_encoding, "|");
+        print_stream(output.stderr.as_deref(), &output.stderr_encoding, "!");
+    }
+}
+
+fn print_stream(text: Option<&str>, encoding: &str, marker: &str) {
+    let Some(text) = text.filter(|text| !text.is_empty()) else {
+        return;
+    };
+    if encoding != "utf8" {
+        println!("    {marker} ({} bytes of {encoding} output)", text.len());
+        return;
+    }
+    for line in text.lines() {
+        println!("    {marker} {line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(job_id: &str, task_number: u32, status: &str) -> TaskStatus {
+        TaskStatus {
+            job_id: job_id.to_string(),
+            task_number,
+            command: "sort".to_string(),
+            status: status.to_string(),
+            exit_code: None,
+            worker_id: None,
+            started_at: None,
+            completed_at: None,
+            duration_ms: None,
+            retries: 0,
+        }
+    }
+
+    fn plan_status(tasks: Vec<TaskStatus>) -> PlanStatus {
+        PlanStatus {
+            plan_id: "plan-1".to_string(),
+            action_id: "action-1".to_string(),
+            status: "running".to_string(),
+            summary: Default::default(),
+            tasks,
+        }
+    }
+
+    #[test]
+    fn tracker_reports_only_changed_tasks() {
+        let mut tracker = Tracker::default();
+
+        let first = tracker.update(&plan_status(vec![
+            task("job-2", 2, "pending"),
+            task("job-1", 1, "running"),
+        ]));
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].task.job_id, "job-1");
+        assert_eq!(first[0].previous, None);
+
+        let second = tracker.update(&plan_status(vec![
+            task("job-1", 1, "completed"),
+            task("job-2", 2, "pending"),
+        ]));
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].task.status, "completed");
+        assert_eq!(second[0].previous.as_deref(), Some("running"));
+
+        assert!(is_finished("completed"));
+        assert!(is_finished("timeout"));
+        assert!(!is_finished("running"));
+    }
+
+    #[test]
+    fn failed_plan_is_an_error() {
+        let mut status = plan_status(vec![
+            task("job-1", 1, "completed"),
+            task("job-2", 2, "failed"),
+        ]);
+        status.status = "failed".to_string();
+        let error = finish(&status, true).unwrap_err();
+        assert!(error.contains("1 of 2 tasks"));
+
+        status.status = "completed".to_string();
+        status.tasks[1].status = "completed".to_string();
+        assert!(finish(&status, true).is_ok());
+    }
+}